@@ -0,0 +1,52 @@
+//! Timings for reading typed lists out of a large document, as done repeatedly while planning.
+//! Run with `cargo bench --bench json`.
+
+use std::{collections::HashMap, hint::black_box, time::Instant};
+
+use gltf_ktxer::{gltf::{GltfBufferView, GltfDoc, GltfImage, GltfIndex, GltfTexture}, Input};
+use serde_json::json;
+
+const ITERATIONS: u32 = 20;
+
+/// A geometry-heavy document with many buffer views and a few hundred textures.
+fn large_doc() -> GltfDoc {
+    let buffer_views: Vec<_> = (0..50_000).map(|i| json!({ "buffer": 0, "byteOffset": i * 64, "byteLength": 64, "target": 34962 })).collect();
+    let images: Vec<_> = (0..500).map(|i| json!({ "bufferView": i, "mimeType": "image/png", "name": format!("image{i}") })).collect();
+    let textures: Vec<_> = (0..500).map(|i| json!({ "source": i, "extensions": { "KHR_texture_basisu": { "source": i } } })).collect();
+    serde_json::from_value(json!({
+        "asset": { "version": "2.0" },
+        "buffers": [{ "byteLength": 50_000 * 64 }],
+        "bufferViews": buffer_views,
+        "images": images,
+        "textures": textures,
+    }))
+    .unwrap()
+}
+
+fn bench(name: &str, mut f: impl FnMut()) {
+    f();
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    println!("{name}: {:?} per iteration", start.elapsed() / ITERATIONS);
+}
+
+fn main() {
+    let mut doc = large_doc();
+    let binaries = HashMap::new();
+    let input = Input { gltf_json: &mut doc, binaries: &binaries };
+
+    bench("get_list bufferViews", || {
+        black_box(input.get_list::<GltfBufferView>("bufferViews").unwrap());
+    });
+    bench("get_list images + textures", || {
+        black_box(input.get_list::<GltfImage>("images").unwrap());
+        black_box(input.get_list::<GltfTexture>("textures").unwrap());
+    });
+    bench("get_gltf_index every image", || {
+        for i in 0..500 {
+            black_box(input.get_gltf_index::<GltfImage>(GltfIndex::of(i), "images").unwrap());
+        }
+    });
+}
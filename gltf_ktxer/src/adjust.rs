@@ -0,0 +1,69 @@
+//! Simple color adjustments applied to a texture before encoding, for small look fixes without a trip through a DCC tool.
+//!
+//! Exposure and saturation are applied to linear light, converting sRGB data to linear and back, so they behave the
+//! same on color and data textures. Gamma is applied last, to the stored values. Alpha is never changed.
+
+use image::RgbaImage;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorAdjustments {
+    /// Multiplies linear color, e.g. 2.0 brightens by a stop.
+    pub exposure: f32,
+    /// Stored values become `value^(1/gamma)`, so values above 1 brighten midtones.
+    pub gamma: f32,
+    /// 0 is grayscale, 1 is unchanged, and larger values are more saturated.
+    pub saturation: f32,
+}
+impl Default for ColorAdjustments {
+    fn default() -> Self {
+        Self { exposure: 1.0, gamma: 1.0, saturation: 1.0 }
+    }
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 { value / 12.92 } else { ((value + 0.055) / 1.055).powf(2.4) }
+}
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 { value * 12.92 } else { 1.055 * value.powf(1.0 / 2.4) - 0.055 }
+}
+
+impl ColorAdjustments {
+    /// Why these adjustments can't be applied, if they can't.
+    pub fn check(&self) -> Option<String> {
+        let valid = |value: f32| value.is_finite() && value >= 0.0;
+        if !valid(self.exposure) || !valid(self.saturation) {
+            Some("exposure and saturation must be finite and non-negative".to_string())
+        } else if !(self.gamma.is_finite() && self.gamma > 0.0) {
+            Some("gamma must be finite and positive".to_string())
+        } else {
+            None
+        }
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Adjust the color channels of `image`, which holds sRGB-encoded data if `srgb` is set and linear data otherwise.
+    pub fn apply(&self, image: &mut RgbaImage, srgb: bool) {
+        if self.is_identity() {
+            return;
+        }
+        // Only 256 possible inputs per channel, so decode through a table
+        let to_linear: Vec<f32> = (0..=255u8)
+            .map(|value| value as f32 / 255.0)
+            .map(|value| if srgb { srgb_to_linear(value) } else { value })
+            .collect();
+        for texel in image.pixels_mut() {
+            let [r, g, b] = [0, 1, 2].map(|channel| to_linear[texel.0[channel] as usize] * self.exposure);
+            // Rec. 709 luma
+            let luma = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+            let adjusted = [r, g, b].map(|value| luma + (value - luma) * self.saturation);
+            for (channel, value) in texel.0[..3].iter_mut().zip(adjusted) {
+                let value = value.clamp(0.0, 1.0);
+                let value = if srgb { linear_to_srgb(value) } else { value };
+                *channel = (value.powf(1.0 / self.gamma) * 255.0).round() as u8;
+            }
+        }
+    }
+}
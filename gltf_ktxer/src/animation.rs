@@ -0,0 +1,111 @@
+//! Detecting animated source images, which KTX2 and glTF textures can't represent.
+//!
+//! Animated GIF, APNG and animated WebP decode to their first frame through the `image` crate,
+//! which silently drops the animation. [AnimationPolicy] picks what to do instead, see [crate::Params::animated_images].
+
+/// What to do with a texture whose source image is animated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum AnimationPolicy {
+    /// Encode the first frame, with a warning.
+    #[default]
+    FirstFrame,
+    /// Fail with [crate::Error::AnimatedImage].
+    Error,
+    /// Leave the texture pointing at its source image unchanged, with a warning.
+    Skip,
+}
+impl std::str::FromStr for AnimationPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "first-frame" => Ok(AnimationPolicy::FirstFrame),
+            "error" => Ok(AnimationPolicy::Error),
+            "skip" => Ok(AnimationPolicy::Skip),
+            _ => Err(format!("unknown animated image policy '{s}', expected 'first-frame', 'error' or 'skip'")),
+        }
+    }
+}
+impl std::fmt::Display for AnimationPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            AnimationPolicy::FirstFrame => "first-frame",
+            AnimationPolicy::Error => "error",
+            AnimationPolicy::Skip => "skip",
+        })
+    }
+}
+
+/// Whether the encoded image `data` has more than one frame. Only reads headers, never pixel data.
+pub fn is_animated(data: &[u8]) -> bool {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        apng_frame_count(data) > 1
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        gif_has_several_frames(data)
+    } else if data.starts_with(b"RIFF") && data.get(8..12) == Some(b"WEBP") {
+        webp_is_animated(data)
+    } else {
+        false
+    }
+}
+
+/// The frame count from the `acTL` chunk, or 1 for a plain PNG.
+fn apng_frame_count(data: &[u8]) -> u32 {
+    let mut chunks = &data[8..];
+    while chunks.len() >= 12 {
+        let len = u32::from_be_bytes(chunks[0..4].try_into().unwrap()) as usize;
+        match (&chunks[4..8], chunks.get(8..8 + len)) {
+            (b"acTL", Some(body)) if len >= 4 => return u32::from_be_bytes(body[0..4].try_into().unwrap()),
+            // acTL must come before the image data
+            (b"IDAT", _) | (b"IEND", _) | (_, None) => break,
+            _ => chunks = &chunks[(12 + len).min(chunks.len())..],
+        }
+    }
+    1
+}
+
+/// Walk the GIF's blocks, stopping at the second image descriptor.
+fn gif_has_several_frames(data: &[u8]) -> bool {
+    // The global color table follows the 13 byte header and logical screen descriptor
+    let Some(&flags) = data.get(10) else {
+        return false;
+    };
+    let color_table_len = |flags: u8| if flags & 0x80 != 0 { 3 << ((flags & 0x07) + 1) } else { 0 };
+    let mut pos = 13 + color_table_len(flags);
+    // Skip data sub-blocks, each a length byte then that many bytes, ending at a zero length
+    let skip_sub_blocks = |mut pos: usize| -> Option<usize> {
+        loop {
+            let len = *data.get(pos)? as usize;
+            pos += 1 + len;
+            if len == 0 {
+                return Some(pos);
+            }
+        }
+    };
+    let mut frames = 0;
+    loop {
+        let next = match data.get(pos) {
+            // extension: introducer, label, then sub-blocks
+            Some(0x21) => skip_sub_blocks(pos + 2),
+            // image descriptor: 10 bytes, an optional local color table, the LZW code size, then sub-blocks
+            Some(0x2c) => {
+                frames += 1;
+                if frames > 1 {
+                    return true;
+                }
+                data.get(pos + 9).and_then(|&flags| skip_sub_blocks(pos + 10 + color_table_len(flags) + 1))
+            }
+            // trailer, or anything unexpected
+            _ => None,
+        };
+        match next {
+            Some(next) => pos = next,
+            None => return false,
+        }
+    }
+}
+
+/// Check the animation flag in the `VP8X` chunk, which must be the first chunk if present.
+fn webp_is_animated(data: &[u8]) -> bool {
+    data.get(12..16) == Some(b"VP8X") && data.get(20).is_some_and(|&flags| flags & 0x02 != 0)
+}
@@ -0,0 +1,69 @@
+//! `@file` response files on the command line, for build systems whose generated command lines outgrow the OS limit.
+//!
+//! An argument `@path` is replaced by the arguments read from `path`, the same way GCC and Clang read them:
+//! separated by whitespace, with single or double quotes around arguments containing spaces, and backslash escapes.
+//! Response files can refer to further response files.
+
+use std::{ffi::OsString, path::Path};
+
+use crate::{Error, Result};
+
+/// Response files referring to response files this deep are assumed to be a loop.
+const MAX_DEPTH: usize = 16;
+
+/// Split the contents of a response file into arguments.
+pub fn split(text: &str) -> Result<Vec<String>> {
+    let mut args = vec![];
+    let mut chars = text.chars();
+    let mut current: Option<String> = None;
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => args.extend(current.take()),
+            '\\' => current.get_or_insert_default().push(chars.next().unwrap_or('\\')),
+            '\'' | '"' => {
+                let arg = current.get_or_insert_default();
+                loop {
+                    match chars.next() {
+                        Some(end) if end == c => break,
+                        // Backslashes only escape inside double quotes
+                        Some('\\') if c == '"' => arg.push(chars.next().unwrap_or('\\')),
+                        Some(other) => arg.push(other),
+                        None => return Err(Error::BadArgFile(format!("unterminated {c} quote"))),
+                    }
+                }
+            }
+            c => current.get_or_insert_default().push(c),
+        }
+    }
+    args.extend(current);
+    Ok(args)
+}
+
+/// Replace every `@path` in `args` with the arguments in that file. The first argument, the program name, is left alone.
+pub fn expand(args: impl IntoIterator<Item = OsString>) -> Result<Vec<OsString>> {
+    let mut args = args.into_iter();
+    let mut expanded: Vec<OsString> = args.next().into_iter().collect();
+    for arg in args {
+        expand_into(arg, &mut expanded, 0)?;
+    }
+    Ok(expanded)
+}
+
+fn expand_into(arg: OsString, expanded: &mut Vec<OsString>, depth: usize) -> Result<()> {
+    let Some(path) = arg.to_str().and_then(|arg| arg.strip_prefix('@')) else {
+        expanded.push(arg);
+        return Ok(());
+    };
+    if depth >= MAX_DEPTH {
+        return Err(Error::BadArgFile(format!("{path}: response files nest more than {MAX_DEPTH} deep")));
+    }
+    let text = std::fs::read_to_string(Path::new(path)).map_err(|e| Error::BadArgFile(format!("{path}: {e}")))?;
+    let args = split(&text).map_err(|e| match e {
+        Error::BadArgFile(message) => Error::BadArgFile(format!("{path}: {message}")),
+        e => e,
+    })?;
+    for arg in args {
+        expand_into(arg.into(), expanded, depth + 1)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,306 @@
+//! Export of Basis Universal textures as standalone `.basis` files, for toolchains which don't read KTX2.
+//!
+//! A `.basis` file holds the same compressed data as a BasisLZ/ETC1S or UASTC KTX2 file in a different container,
+//! so textures are converted from the KTX2 images the encoder already produced rather than encoded again.
+//! The layout follows `basisu_file_headers.h` from the Basis Universal repository
+//! (https://github.com/BinomialLLC/basis_universal): a header, slice descriptions, the ETC1S codebooks and tables,
+//! then the slice data. Every field is little-endian and unaligned.
+
+use std::collections::HashMap;
+
+use serde_json::json;
+
+use crate::{
+    edit::texture_ktx_source,
+    gltf::{GltfBuffer, GltfBufferView, GltfImage, GltfList, GltfTexture, U8VecOrSlice},
+    ktx2::{Ktx2Texture, KHR_DF_MODEL_UASTC, KHR_DF_TRANSFER_SRGB, SUPERCOMPRESSION_BASIS_LZ},
+    Error, Input, Result,
+};
+
+/// The key in a texture's `extras` which points at its exported `.basis` file.
+pub const BASIS_EXTRAS_KEY: &str = "GLTF_KTXER_basis";
+
+const BASIS_SIGNATURE: u16 = u16::from_le_bytes(*b"sB");
+const BASIS_VERSION: u16 = 0x13;
+const HEADER_LEN: usize = 77;
+const SLICE_DESC_LEN: usize = 23;
+
+const TEX_FORMAT_ETC1S: u8 = 0;
+const TEX_FORMAT_UASTC4X4: u8 = 1;
+
+const HEADER_FLAG_ETC1S: u16 = 1;
+const HEADER_FLAG_HAS_ALPHA_SLICES: u16 = 4;
+const HEADER_FLAG_SRGB: u16 = 16;
+
+const TEX_TYPE_2D: u8 = 0;
+const TEX_TYPE_2D_ARRAY: u8 = 1;
+const TEX_TYPE_CUBEMAP_ARRAY: u8 = 2;
+const TEX_TYPE_VOLUME: u8 = 4;
+
+const SLICE_FLAG_HAS_ALPHA: u8 = 1;
+
+/// UASTC channel ids from the Khronos Data Format Specification section 5.6.7, for textures with alpha.
+const KHR_DF_CHANNEL_UASTC_RGBA: u8 = 3;
+const KHR_DF_CHANNEL_UASTC_RRRG: u8 = 5;
+
+struct Slice<'a> {
+    image_index: u32,
+    level_index: u8,
+    flags: u8,
+    orig_width: u32,
+    orig_height: u32,
+    data: &'a [u8],
+}
+
+/// Convert a Basis Universal compressed KTX2 texture to a `.basis` file.
+///
+/// Only BasisLZ (ETC1S) and non-supercompressed UASTC textures can be converted,
+/// as those are the only payloads a `.basis` file can hold.
+pub fn ktx2_to_basis(ktx: &Ktx2Texture) -> Result<Vec<u8>> {
+    let is_etc1s = ktx.supercompression_scheme == SUPERCOMPRESSION_BASIS_LZ;
+    let is_uastc = ktx.supercompression_scheme == 0 && ktx.dfd_color_model() == Some(KHR_DF_MODEL_UASTC);
+    if !is_etc1s && !is_uastc {
+        return Err(Error::Ktx2NotBasis);
+    }
+
+    let images_per_level = ktx.images_per_level() as usize;
+    let level_dims = |level: usize| ((ktx.pixel_width >> level).max(1), (ktx.pixel_height >> level).max(1));
+
+    let mut flags = 0;
+    if ktx.dfd_transfer_function() == Some(KHR_DF_TRANSFER_SRGB) {
+        flags |= HEADER_FLAG_SRGB;
+    }
+    // Slices are ordered by image, then by mip level, with each alpha slice directly after its color slice.
+    let mut slices = vec![];
+    let (endpoints, selectors, tables, num_endpoints, num_selectors) = if is_etc1s {
+        flags |= HEADER_FLAG_ETC1S;
+        let sgd = BasisLzGlobalData::parse(&ktx.sgd, ktx.levels.len() * images_per_level)?;
+        if sgd.image_descs.iter().any(|desc| desc.alpha_slice.1 > 0) {
+            flags |= HEADER_FLAG_HAS_ALPHA_SLICES;
+        }
+        for image in 0..images_per_level {
+            for (level_idx, level) in ktx.levels.iter().enumerate() {
+                let desc = &sgd.image_descs[level_idx * images_per_level + image];
+                let (orig_width, orig_height) = level_dims(level_idx);
+                let mut push = |(offset, len): (usize, usize), flags: u8| -> Result<()> {
+                    let data = offset
+                        .checked_add(len)
+                        .and_then(|end| level.data.get(offset..end))
+                        .ok_or(Error::Ktx2Malformed("BasisLZ slice is out of bounds"))?;
+                    slices.push(Slice { image_index: image as u32, level_index: level_idx as u8, flags, orig_width, orig_height, data });
+                    Ok(())
+                };
+                push(desc.rgb_slice, 0)?;
+                if flags & HEADER_FLAG_HAS_ALPHA_SLICES != 0 {
+                    push(desc.alpha_slice, SLICE_FLAG_HAS_ALPHA)?;
+                }
+            }
+        }
+        (sgd.endpoints, sgd.selectors, sgd.tables, sgd.num_endpoints, sgd.num_selectors)
+    } else {
+        let channels = ktx.dfd_sample_channels();
+        let has_alpha = channels.iter().any(|&c| c == KHR_DF_CHANNEL_UASTC_RGBA || c == KHR_DF_CHANNEL_UASTC_RRRG);
+        if has_alpha {
+            flags |= HEADER_FLAG_HAS_ALPHA_SLICES;
+        }
+        for image in 0..images_per_level {
+            for (level_idx, level) in ktx.levels.iter().enumerate() {
+                let (orig_width, orig_height) = level_dims(level_idx);
+                // Each UASTC block is 16 bytes, and the images of a level are stored back to back
+                let image_len = orig_width.div_ceil(4) as usize * orig_height.div_ceil(4) as usize * 16;
+                let data = level
+                    .data
+                    .get(image * image_len..(image + 1) * image_len)
+                    .ok_or(Error::Ktx2Malformed("UASTC level is too short"))?;
+                let flags = if has_alpha { SLICE_FLAG_HAS_ALPHA } else { 0 };
+                slices.push(Slice { image_index: image as u32, level_index: level_idx as u8, flags, orig_width, orig_height, data });
+            }
+        }
+        (&[][..], &[][..], &[][..], 0, 0)
+    };
+
+    let tex_type = if ktx.face_count == 6 {
+        TEX_TYPE_CUBEMAP_ARRAY
+    } else if ktx.layer_count > 0 {
+        TEX_TYPE_2D_ARRAY
+    } else if ktx.pixel_depth > 0 {
+        TEX_TYPE_VOLUME
+    } else {
+        TEX_TYPE_2D
+    };
+
+    let endpoints_offset = HEADER_LEN + slices.len() * SLICE_DESC_LEN;
+    let selectors_offset = endpoints_offset + endpoints.len();
+    let tables_offset = selectors_offset + selectors.len();
+    let mut slice_offset = tables_offset + tables.len();
+
+    let mut out = vec![0; HEADER_LEN];
+    for slice in &slices {
+        let mut desc = FieldWriter(&mut out);
+        desc.uint(slice.image_index as u64, 3);
+        desc.uint(slice.level_index as u64, 1);
+        desc.uint(slice.flags as u64, 1);
+        desc.uint(slice.orig_width as u64, 2);
+        desc.uint(slice.orig_height as u64, 2);
+        desc.uint(slice.orig_width.div_ceil(4) as u64, 2);
+        desc.uint(slice.orig_height.div_ceil(4) as u64, 2);
+        desc.uint(slice_offset as u64, 4);
+        desc.uint(slice.data.len() as u64, 4);
+        desc.uint(crc16(slice.data) as u64, 2);
+        slice_offset += slice.data.len();
+    }
+    out.extend_from_slice(endpoints);
+    out.extend_from_slice(selectors);
+    out.extend_from_slice(tables);
+    for slice in &slices {
+        out.extend_from_slice(slice.data);
+    }
+
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    let mut fields = FieldWriter(&mut header);
+    fields.uint(BASIS_SIGNATURE as u64, 2);
+    fields.uint(BASIS_VERSION as u64, 2);
+    fields.uint(HEADER_LEN as u64, 2);
+    fields.uint(0, 2); // header crc16, filled in below
+    fields.uint((out.len() - HEADER_LEN) as u64, 4);
+    fields.uint(crc16(&out[HEADER_LEN..]) as u64, 2);
+    fields.uint(slices.len() as u64, 3);
+    fields.uint(images_per_level as u64, 3);
+    fields.uint(if is_etc1s { TEX_FORMAT_ETC1S } else { TEX_FORMAT_UASTC4X4 } as u64, 1);
+    fields.uint(flags as u64, 2);
+    fields.uint(tex_type as u64, 1);
+    fields.uint(0, 3); // us_per_frame, only used for video
+    fields.uint(0, 4); // reserved
+    fields.uint(0, 4); // userdata0
+    fields.uint(0, 4); // userdata1
+    fields.uint(num_endpoints as u64, 2);
+    fields.uint(endpoints_offset as u64, 4);
+    fields.uint(endpoints.len() as u64, 3);
+    fields.uint(num_selectors as u64, 2);
+    fields.uint(selectors_offset as u64, 4);
+    fields.uint(selectors.len() as u64, 3);
+    fields.uint(tables_offset as u64, 4);
+    fields.uint(tables.len() as u64, 4);
+    fields.uint(HEADER_LEN as u64, 4); // slice descriptions directly follow the header
+    fields.uint(0, 4); // extended data offset
+    fields.uint(0, 4); // extended data size
+    assert_eq!(header.len(), HEADER_LEN);
+    // The header crc covers everything after the crc field itself
+    let header_crc = crc16(&header[8..]);
+    header[6..8].copy_from_slice(&header_crc.to_le_bytes());
+
+    out[..HEADER_LEN].copy_from_slice(&header);
+    Ok(out)
+}
+
+/// Convert the KTX2 source of every texture to a `.basis` file named by `uri_for(image_idx)`,
+/// and point each texture's `extras` at it under [BASIS_EXTRAS_KEY].
+/// [crate::filenames::image_file_names] gives names which are safe to write whatever the images are called.
+///
+/// Returns the `.basis` files to write, keyed by URI. The files are always external, as no extension embeds them.
+pub fn export_basis_files(
+    mut input: Input<'_>,
+    uri_for: impl Fn(usize) -> String,
+) -> Result<HashMap<String, Vec<u8>>> {
+    let mut textures: Vec<GltfTexture> = input.get_list("textures")?;
+    let images: Vec<GltfImage> = input.get_list("images")?;
+    let buffer_views: Vec<GltfBufferView> = input.get_list("bufferViews")?;
+    let buffers: Vec<GltfBuffer> = input.get_list("buffers")?;
+    let buffer_datas: Vec<U8VecOrSlice<'_>> = buffers
+        .into_iter()
+        .enumerate()
+        .map(|(idx, b)| b.dump_data(idx, input.binaries))
+        .collect::<Result<_>>()?;
+
+    let mut files = HashMap::new();
+    let mut image_uris = HashMap::new();
+    for texture in textures.iter_mut() {
+        let Some(ktx_img_idx) = texture_ktx_source(texture) else {
+            continue;
+        };
+        let uri = match image_uris.get(&ktx_img_idx) {
+            Some(uri) => String::clone(uri),
+            None => {
+                let image = images.gltf_index_required(ktx_img_idx, "images")?;
+                let data = image.dump_data(&buffer_views, &buffer_datas, input.binaries)?;
+                let uri = uri_for(ktx_img_idx.raw_idx());
+                files.insert(uri.clone(), ktx2_to_basis(&Ktx2Texture::from_bytes(&data)?)?);
+                image_uris.insert(ktx_img_idx, uri.clone());
+                uri
+            }
+        };
+        let extras = texture.extras.get_or_insert_with(|| json!({}));
+        extras
+            .as_object_mut()
+            .ok_or(Error::ExpectedObject { key: "extras" })?
+            .insert(BASIS_EXTRAS_KEY.to_string(), json!({ "uri": uri }));
+    }
+    input.set_list("textures", textures)?;
+    Ok(files)
+}
+
+/// The supercompression global data of a BasisLZ texture, KTX2 spec section 3.12.
+pub(crate) struct BasisLzGlobalData<'a> {
+    pub(crate) num_endpoints: u16,
+    pub(crate) num_selectors: u16,
+    pub(crate) image_descs: Vec<ImageDesc>,
+    pub(crate) endpoints: &'a [u8],
+    pub(crate) selectors: &'a [u8],
+    pub(crate) tables: &'a [u8],
+}
+/// (offset, length) of the color and alpha slices within the image's level
+pub(crate) struct ImageDesc {
+    pub(crate) rgb_slice: (usize, usize),
+    pub(crate) alpha_slice: (usize, usize),
+}
+impl<'a> BasisLzGlobalData<'a> {
+    pub(crate) fn parse(sgd: &'a [u8], num_images: usize) -> Result<Self> {
+        const TRUNCATED: Error = Error::Ktx2Malformed("BasisLZ global data is truncated");
+        let u16_at = |offset: usize| sgd.get(offset..offset + 2).map(|b| u16::from_le_bytes(b.try_into().unwrap())).ok_or(TRUNCATED);
+        let u32_at = |offset: usize| sgd.get(offset..offset + 4).map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize).ok_or(TRUNCATED);
+
+        let image_descs = (0..num_images)
+            .map(|i| {
+                let desc = 20 + i * 20;
+                Ok(ImageDesc {
+                    rgb_slice: (u32_at(desc + 4)?, u32_at(desc + 8)?),
+                    alpha_slice: (u32_at(desc + 12)?, u32_at(desc + 16)?),
+                })
+            })
+            .collect::<Result<_>>()?;
+        let mut cursor = 20 + num_images * 20;
+        let mut take = |len: usize| -> Result<&'a [u8]> {
+            let section = sgd.get(cursor..cursor + len).ok_or(TRUNCATED)?;
+            cursor += len;
+            Ok(section)
+        };
+        Ok(Self {
+            num_endpoints: u16_at(0)?,
+            num_selectors: u16_at(2)?,
+            image_descs,
+            endpoints: take(u32_at(4)?)?,
+            selectors: take(u32_at(8)?)?,
+            tables: take(u32_at(12)?)?,
+        })
+    }
+}
+
+/// Appends little-endian integers of arbitrary byte widths, as .basis headers pack fields into 1-4 bytes.
+struct FieldWriter<'a>(&'a mut Vec<u8>);
+impl FieldWriter<'_> {
+    fn uint(&mut self, value: u64, bytes: usize) {
+        debug_assert!(bytes == 8 || value >> (bytes * 8) == 0, "{value} doesn't fit in {bytes} bytes");
+        self.0.extend_from_slice(&value.to_le_bytes()[..bytes]);
+    }
+}
+
+/// The CRC-16 variant used by Basis Universal (CCITT polynomial, inverted input and output).
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = !0;
+    for &byte in data {
+        let q = (byte as u16) ^ (crc >> 8);
+        let k = (q >> 4) ^ q;
+        crc = (crc << 8) ^ k ^ (k << 5) ^ (k << 12);
+    }
+    !crc
+}
@@ -0,0 +1,1107 @@
+use std::{cell::RefCell, io::IsTerminal, num::NonZeroUsize, path::{Path, PathBuf}, sync::Arc, time::{Instant, SystemTime}};
+
+use clap::{parser::ValueSource, ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use gltf_ktxer::{argfile, config::{Config, CONFIG_FILE_NAME}, depfile, disk_cache::{Age, DiskCache}, edit::ReferenceRegistry, etc1s, execute_reencode_jobs, fallback::EncodeFailurePolicy, fingerprint::{changed_jobs, PreviousOutput}, get_reencode_jobs, external_validate::{into_findings, run_validator, DEFAULT_VALIDATOR_COMMAND}, glb::JsonFormat, gltf::{GltfDoc, GltfSampler}, levels::level_count, memory::{rgba8_bytes, ByteSize, MemoryTracker}, mipmap::TileGrid, profile::TargetProfile, prepare_output_buffers, preset::Preset, KtxCodec, Params, report::{error_json, render_error, render_warning, warning_json}, ktx2::{self, fit_within, ColorSpace, Ktx2Texture}, load::{load_gltf_with, LoadOptions}, manifest::ImageManifest, output::{input_stamp, is_up_to_date, remove_temp_files, stamp_path, write_atomic, write_stamp, OverwritePolicy}, pipeline::apply_transforms, quality::{check_regressions, psnr, QualityReport, RegressionAction, RegressionThresholds, TextureQuality}, placeholder::{parse_color, Placeholder}, precompress::{Sidecar, DEFAULT_BROTLI_COMMAND}, semantic::SlotRegistry, shutdown, slim::SlimOptions, summary::render_summary, tuning::{describe, EncodeEffort, HostCapabilities, Tuning}, uv_checker::UvChecker, validate::{check_images_decode, lint, validate, Warning}};
+
+/// Options can also be set with GLTF_KTXER_* environment variables, or in a gltf-ktxer.toml file in the working directory.
+/// The command line takes precedence over the environment, which takes precedence over the file.
+/// Arguments can be read from response files given as '@path', separated by whitespace and quoted like a shell.
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+    /// Don't color error output. Also disabled by setting NO_COLOR, or when stderr isn't a terminal
+    #[arg(long, global = true, env = "GLTF_KTXER_NO_COLOR")]
+    no_color: bool,
+    #[arg(long, global = true, value_enum, default_value_t = ErrorFormat::Human, env = "GLTF_KTXER_ERROR_FORMAT")]
+    error_format: ErrorFormat,
+    /// Exit with a failure code on warnings as well as errors
+    #[arg(long, global = true, value_enum, default_value_t = FailOn::Error, env = "GLTF_KTXER_FAIL_ON")]
+    fail_on: FailOn,
+    /// Keep the previous version of each output file that gets replaced, with '.bak' appended to its name
+    #[arg(long, global = true, env = "GLTF_KTXER_BACKUP")]
+    backup: bool,
+    /// Replace existing output files (the default)
+    #[arg(long, global = true, conflicts_with_all = ["skip_existing", "if_newer"])]
+    overwrite: bool,
+    /// Leave existing output files alone
+    #[arg(long, global = true, conflicts_with = "if_newer", env = "GLTF_KTXER_SKIP_EXISTING")]
+    skip_existing: bool,
+    /// Only replace output files whose inputs or settings have changed since they were written.
+    /// What each output was made from is recorded in a '.stamp' file next to it
+    #[arg(long, global = true, env = "GLTF_KTXER_IF_NEWER")]
+    if_newer: bool,
+    /// Write a Make-style dependency file here, listing every file each output was made from,
+    /// including referenced buffers and images and the config file
+    #[arg(long, global = true, env = "GLTF_KTXER_DEPFILE")]
+    depfile: Option<PathBuf>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum FailOn {
+    Error,
+    Warning,
+}
+
+/// Process exit codes. Build systems rely on these, so they must never change.
+mod exit_code {
+    pub const OK: i32 = 0;
+    /// Only returned with --fail-on=warning
+    pub const WARNINGS: i32 = 2;
+    /// The input or the command line was invalid
+    pub const INVALID_INPUT: i32 = 3;
+    pub const ENCODE_FAILURE: i32 = 4;
+    pub const IO: i32 = 5;
+    /// Stopped by SIGINT or SIGTERM, after cleaning up
+    pub const INTERRUPTED: i32 = gltf_ktxer::shutdown::FORCED_EXIT_CODE;
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum ErrorFormat {
+    Human,
+    /// One JSON object per line with the error code, message, json_pointer and file
+    Json,
+}
+
+/// How a command writes its output files, from the global options.
+struct OutputOptions {
+    backup: bool,
+    overwrite: OverwritePolicy,
+    depfile: Option<PathBuf>,
+    /// The config file the options were read from, which every output depends on.
+    config_file: Option<PathBuf>,
+    /// Every file written so far, for the summary printed if the run is interrupted.
+    written: RefCell<Vec<PathBuf>>,
+}
+impl OutputOptions {
+    fn write(&self, path: &Path, data: &[u8]) -> gltf_ktxer::Result<()> {
+        write_atomic(path, data, self.backup)?;
+        self.written.borrow_mut().push(path.to_path_buf());
+        Ok(())
+    }
+    /// The stamp of `inputs` and the command's `settings`, if it's needed to decide whether to skip the command.
+    fn stamp(&self, settings: &str, inputs: impl FnOnce() -> gltf_ktxer::Result<Vec<Vec<u8>>>) -> gltf_ktxer::Result<Option<String>> {
+        if self.overwrite != OverwritePolicy::IfNewer {
+            return Ok(None);
+        }
+        let inputs = inputs()?;
+        Ok(Some(input_stamp(settings, &inputs.iter().map(Vec::as_slice).collect::<Vec<_>>())))
+    }
+    /// Whether every one of `outputs` can be left as it is, printing a note if so.
+    fn skip(&self, outputs: &[PathBuf], stamp: Option<&str>) -> bool {
+        let (skip, reason) = match (self.overwrite, stamp) {
+            (OverwritePolicy::SkipExisting, _) => (outputs.iter().all(|output| output.exists()), "already exists"),
+            (OverwritePolicy::IfNewer, Some(stamp)) => (outputs.iter().all(|output| is_up_to_date(output, stamp)), "is up to date"),
+            _ => (false, ""),
+        };
+        if skip {
+            for output in outputs {
+                println!("{} {reason}, skipping", output.display());
+            }
+        }
+        skip
+    }
+    /// Record the stamp of each of `outputs` once they're all written, and the depfile listing `inputs`.
+    /// Outputs written somewhere else instead aren't stamped.
+    /// Without a stamp, any left from an earlier run are removed, as they no longer describe the outputs.
+    fn finish(&self, outputs: &[PathBuf], stamp: Option<&str>, inputs: &[PathBuf]) -> gltf_ktxer::Result<()> {
+        if let Some(depfile) = &self.depfile {
+            let inputs: Vec<PathBuf> = inputs.iter().chain(&self.config_file).cloned().collect();
+            write_atomic(depfile, depfile::render(outputs, &inputs).as_bytes(), false)?;
+        }
+        for output in outputs.iter().filter(|output| output.exists()) {
+            match stamp {
+                Some(stamp) => write_stamp(output, stamp)?,
+                None => match std::fs::remove_file(stamp_path(output)) {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                    _ => {}
+                },
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What a command was working on, for error and warning reports.
+#[derive(Default)]
+struct RunContext {
+    file: Option<PathBuf>,
+    doc: Option<GltfDoc>,
+    warnings: Vec<Warning>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Generate a solid-color or checkerboard placeholder KTX2 texture
+    Placeholder {
+        /// Width and height of the texture in pixels
+        #[arg(long, default_value_t = 256)]
+        size: u32,
+        /// Fill color as '#rrggbb' or '#rrggbbaa'
+        #[arg(long, default_value = "#ff00ff")]
+        color: String,
+        /// If set, generate a checkerboard alternating between --color and this color
+        #[arg(long)]
+        checker: Option<String>,
+        /// Size of each checkerboard cell in pixels
+        #[arg(long, default_value_t = 32)]
+        cell_size: u32,
+        /// Store the texture as linear instead of sRGB
+        #[arg(long)]
+        linear: bool,
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Encode a single image to KTX2, without a glTF
+    EncodeImage {
+        input: PathBuf,
+        /// Treat the input as a JSON manifest listing the images for each layer, cubemap face and mip level
+        #[arg(long)]
+        manifest: bool,
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Start from a named bundle of settings: web-fast, web-quality, mobile, desktop-bc or archival-lossless-fallback.
+        /// Other options override the preset's settings
+        #[arg(long, env = "GLTF_KTXER_PRESET")]
+        preset: Option<Preset>,
+        /// rgba8 or etc1s. Defaults to the preset's codec, or rgba8 without a preset
+        #[arg(long, value_parser = parse_codec, env = "GLTF_KTXER_CODEC")]
+        codec: Option<Codec>,
+        /// Generate a full mip chain
+        #[arg(long, env = "GLTF_KTXER_MIPMAPS")]
+        mipmaps: bool,
+        /// Downscale the image to fit within this many pixels in each dimension. Doesn't apply to manifests
+        #[arg(long, env = "GLTF_KTXER_MAX_SIZE")]
+        max_size: Option<u32>,
+        /// Store the texture as linear instead of sRGB.
+        /// Without it, images whose file name suggests they hold data, like `wall_normal.png`, are stored as linear too,
+        /// see semantic-names in gltf-ktxer.toml
+        #[arg(long, env = "GLTF_KTXER_LINEAR")]
+        linear: bool,
+        /// Adapt the texture to a platform: webgl1, webgl2, webgpu, vulkan, gles3 or metal.
+        /// Downscales to the platform's maximum texture size, and drops mip levels it can't use.
+        /// Can be given several times, writing one file per platform named like `output.webgl2.ktx2`
+        #[arg(long, env = "GLTF_KTXER_TARGET", value_delimiter = ',')]
+        target: Vec<TargetProfile>,
+        /// The image is an atlas of '<columns>x<rows>' tiles, which mipmapping shouldn't bleed between
+        #[arg(long, env = "GLTF_KTXER_ATLAS")]
+        atlas: Option<TileGrid>,
+        /// Fail before running out of memory if the image would need more than this, e.g. '2G'.
+        /// Counts the decoded image, its mip chain and the encoded output
+        #[arg(long, env = "GLTF_KTXER_MAX_MEMORY")]
+        max_memory: Option<ByteSize>,
+        /// How hard the ETC1S encoder works: fast, balanced or thorough. Defaults to one picked for the machine's core count
+        #[arg(long, env = "GLTF_KTXER_EFFORT")]
+        effort: Option<EncodeEffort>,
+        /// Replace the image with a UV checker of the same size, to check that UV mapping survives conversion. Doesn't apply to manifests
+        #[arg(long, env = "GLTF_KTXER_DEBUG_UV_CHECKER")]
+        debug_uv_checker: bool,
+        /// Write the size and PSNR of each output to this JSON file, to pass as --baseline to a later run
+        #[arg(long, env = "GLTF_KTXER_REPORT")]
+        report: Option<PathBuf>,
+        /// Compare the size and PSNR of each output to this report from an earlier run,
+        /// failing if any got worse by more than --max-size-growth or --max-psnr-drop
+        #[arg(long, env = "GLTF_KTXER_BASELINE")]
+        baseline: Option<PathBuf>,
+        /// What to do when an output regressed against --baseline: fail or warn
+        #[arg(long, requires = "baseline", default_value_t = RegressionAction::Fail, env = "GLTF_KTXER_ON_REGRESSION")]
+        on_regression: RegressionAction,
+        /// The most an output may grow against --baseline, in percent
+        #[arg(long, requires = "baseline", default_value_t = RegressionThresholds::default().max_size_growth_percent, env = "GLTF_KTXER_MAX_SIZE_GROWTH")]
+        max_size_growth: f64,
+        /// The most an output's PSNR may drop against --baseline, in dB
+        #[arg(long, requires = "baseline", default_value_t = RegressionThresholds::default().max_psnr_drop, env = "GLTF_KTXER_MAX_PSNR_DROP")]
+        max_psnr_drop: f64,
+        /// Don't print the table summarizing each output's codec, size and time at the end
+        #[arg(long, env = "GLTF_KTXER_NO_SUMMARY")]
+        no_summary: bool,
+    },
+    /// Convert the textures of a .gltf or .glb file to KTX2, writing a .glb or a .gltf with a .bin alongside it.
+    /// Each texture keeps its original image as a fallback, downscaled if --max-size applies
+    Convert {
+        input: PathBuf,
+        /// The output file. Written as GLB if the extension is .glb
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Start from a named bundle of settings: web-fast, web-quality, mobile, desktop-bc or archival-lossless-fallback.
+        /// Other options override the preset's settings
+        #[arg(long, env = "GLTF_KTXER_PRESET")]
+        preset: Option<Preset>,
+        /// The Basis Universal codec. Only etc1s can be encoded so far. Defaults to the preset's codec, or etc1s without a preset
+        #[arg(long, value_parser = parse_ktx_codec, env = "GLTF_KTXER_KTX_CODEC")]
+        codec: Option<KtxCodec>,
+        /// Generate a full mip chain for each KTX2 image
+        #[arg(long, env = "GLTF_KTXER_MIPMAPS")]
+        mipmaps: bool,
+        /// Downscale images to fit within this many pixels in each dimension
+        #[arg(long, env = "GLTF_KTXER_MAX_SIZE")]
+        max_size: Option<u32>,
+        /// Keep the original image of textures whose encoding fails, with a warning, instead of failing
+        #[arg(long, env = "GLTF_KTXER_KEEP_FAILED_ORIGINALS")]
+        keep_failed_originals: bool,
+        /// Give up on high-effort ETC1S encodes taking longer than this many seconds, and encode them with less effort,
+        /// so one slow texture can't stall a CI build
+        #[arg(long, env = "GLTF_KTXER_MAX_ENCODE_SECONDS_PER_TEXTURE")]
+        max_encode_seconds_per_texture: Option<f64>,
+        /// How hard the ETC1S encoder works: fast, balanced or thorough. Defaults to one picked for the machine's core count
+        #[arg(long, env = "GLTF_KTXER_EFFORT")]
+        effort: Option<EncodeEffort>,
+        /// The number of images to encode at once. Defaults to one per core
+        #[arg(long, env = "GLTF_KTXER_THREADS")]
+        threads: Option<NonZeroUsize>,
+        /// Re-encode only the images whose source or settings changed since the existing output was written,
+        /// reusing the rest from it. Outputs record what each image was made from in asset.extras
+        #[arg(long, env = "GLTF_KTXER_ONLY_CHANGED")]
+        only_changed: bool,
+        /// Indent the output JSON, for readable diffs
+        #[arg(long, conflicts_with = "json_minify", env = "GLTF_KTXER_JSON_PRETTY")]
+        json_pretty: bool,
+        /// Write the output JSON without any whitespace (the default)
+        #[arg(long)]
+        json_minify: bool,
+        /// Read buffers and images outside the input's directory, e.g. '../textures/wood.png'. Only use this with trusted files
+        #[arg(long, env = "GLTF_KTXER_ALLOW_OUTSIDE_ROOT")]
+        allow_outside_root: bool,
+    },
+    /// Print the header, levels, data format and key/value data of a KTX2 file
+    KtxInfo {
+        input: PathBuf,
+    },
+    /// Repack the buffers of a .gltf file into one, writing a .glb or a .gltf with a .bin alongside it
+    Pack {
+        input: PathBuf,
+        /// The output file. Written as GLB if the extension is .glb
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Indent the output JSON, for readable diffs
+        #[arg(long, conflicts_with = "json_minify", env = "GLTF_KTXER_JSON_PRETTY")]
+        json_pretty: bool,
+        /// Write the output JSON without any whitespace (the default)
+        #[arg(long)]
+        json_minify: bool,
+        /// What to do if a .glb output would be over the 4 GiB GLB size limit
+        #[arg(long, value_enum, default_value_t = GlbOverflow::Fail, env = "GLTF_KTXER_GLB_OVERFLOW")]
+        glb_overflow: GlbOverflow,
+        /// Also check the output with the Khronos glTF-Validator, failing if it finds errors and reporting its warnings
+        #[arg(long, env = "GLTF_KTXER_EXTERNAL_VALIDATE")]
+        external_validate: bool,
+        /// The command running glTF-Validator, e.g. 'npx gltf-validator'. Defaults to gltf_validator on the PATH
+        #[arg(long, env = "GLTF_KTXER_EXTERNAL_VALIDATOR")]
+        external_validator: Option<String>,
+        /// Read buffers and images outside the input's directory, e.g. '../textures/wood.png'. Only use this with trusted files
+        #[arg(long, env = "GLTF_KTXER_ALLOW_OUTSIDE_ROOT")]
+        allow_outside_root: bool,
+        /// Merge identical samplers, and textures with the same image and sampler, updating the materials using them
+        #[arg(long, env = "GLTF_KTXER_DEDUP_TEXTURES")]
+        dedup_textures: bool,
+        /// Shrink the output JSON by removing extras, properties set to their default value and empty arrays
+        #[arg(long, env = "GLTF_KTXER_SLIM_JSON")]
+        slim_json: bool,
+        /// With --slim-json, keep extras keys matching these patterns, e.g. 'GLTF_KTXER_*'. Can be given several times
+        #[arg(long, requires = "slim_json", env = "GLTF_KTXER_KEEP_EXTRAS", value_delimiter = ',')]
+        keep_extras: Vec<String>,
+        /// With --slim-json, also remove the name of every object
+        #[arg(long, requires = "slim_json", env = "GLTF_KTXER_STRIP_NAMES")]
+        strip_names: bool,
+        /// Indices in vendor extensions or extras to renumber when objects are merged, e.g. '/materials/*/extensions/MY_ext/imageIndex -> images'. Can be given several times
+        #[arg(long, env = "GLTF_KTXER_REFERENCE", value_delimiter = ',')]
+        reference: Vec<String>,
+        /// Like --reference, but the objects referred to are never removed, e.g. '/nodes/*/extras/lod/* -> materials' read by an engine
+        #[arg(long, env = "GLTF_KTXER_GC_ROOT", value_delimiter = ',')]
+        gc_root: Vec<String>,
+        /// Also write precompressed copies of each output file for web servers to serve as they are: gzip (.gz) and/or brotli (.br)
+        #[arg(long, env = "GLTF_KTXER_PRECOMPRESS", value_delimiter = ',')]
+        precompress: Vec<Sidecar>,
+        /// The command compressing stdin to stdout for --precompress brotli. Defaults to 'brotli --quality=11 --stdout'
+        #[arg(long, env = "GLTF_KTXER_BROTLI_COMMAND")]
+        brotli_command: Option<String>,
+    },
+    /// Inspect or shrink the on-disk encode cache shared by conversions
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+    /// Check a .gltf file and the files it references are structurally valid and all images decode
+    Validate {
+        input: PathBuf,
+        /// Also validate against the glTF 2.0 JSON schema
+        #[cfg(feature = "schema")]
+        #[arg(long)]
+        schema: bool,
+        /// Also check the file with the Khronos glTF-Validator, failing if it finds errors and reporting its warnings
+        #[arg(long, env = "GLTF_KTXER_EXTERNAL_VALIDATE")]
+        external_validate: bool,
+        /// The command running glTF-Validator, e.g. 'npx gltf-validator'. Defaults to gltf_validator on the PATH
+        #[arg(long, env = "GLTF_KTXER_EXTERNAL_VALIDATOR")]
+        external_validator: Option<String>,
+        /// Read buffers and images outside the input's directory, e.g. '../textures/wood.png'. Only use this with trusted files
+        #[arg(long, env = "GLTF_KTXER_ALLOW_OUTSIDE_ROOT")]
+        allow_outside_root: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum CacheAction {
+    /// Print the number, total size and age of the entries in the encode cache
+    Stats {
+        /// The encode cache directory
+        #[arg(long, env = "GLTF_KTXER_CACHE_DIR")]
+        cache_dir: Option<PathBuf>,
+    },
+    /// Delete old entries from the encode cache, least recently used first, along with files abandoned by crashed runs
+    Prune {
+        /// The encode cache directory
+        #[arg(long, env = "GLTF_KTXER_CACHE_DIR")]
+        cache_dir: Option<PathBuf>,
+        /// Delete entries until the rest take up at most this much, e.g. '10GB'
+        #[arg(long)]
+        max_size: Option<ByteSize>,
+        /// Delete entries last used longer ago than this, e.g. '30d'. Units are s, m, h, d and w
+        #[arg(long)]
+        max_age: Option<Age>,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum GlbOverflow {
+    Fail,
+    /// Write a .gltf with a separate .bin next to the requested .glb path instead, with a warning
+    Gltf,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Codec {
+    /// Uncompressed RGBA8
+    Rgba8,
+    /// Basis Universal UASTC
+    Uastc,
+    /// Basis Universal ETC1S
+    Etc1s,
+}
+
+const UASTC_UNAVAILABLE: &str = "there's no UASTC encoder yet, use etc1s";
+
+/// Parse `--codec` for encode-image, rejecting UASTC up front rather than once the image is decoded.
+fn parse_codec(s: &str) -> Result<Codec, String> {
+    match Codec::from_str(s, false).map_err(|_| format!("unknown codec '{s}', expected 'rgba8' or 'etc1s'"))? {
+        Codec::Uastc => Err(UASTC_UNAVAILABLE.to_string()),
+        codec => Ok(codec),
+    }
+}
+
+/// Parse `--codec` for convert, rejecting UASTC up front rather than failing every texture.
+fn parse_ktx_codec(s: &str) -> Result<KtxCodec, String> {
+    match s.parse()? {
+        KtxCodec::Uastc => Err(UASTC_UNAVAILABLE.to_string()),
+        codec => Ok(codec),
+    }
+}
+
+fn main() {
+    shutdown::install_handlers();
+    let argv = match argfile::expand(std::env::args_os()) {
+        Ok(argv) => argv,
+        Err(e) => {
+            eprint!("{}", render_error(&e, None, false));
+            std::process::exit(exit_code::INVALID_INPUT);
+        }
+    };
+    let (mut args, matches) = match Args::command().try_get_matches_from(argv).and_then(|matches| Ok((Args::from_arg_matches(&matches)?, matches))) {
+        Ok(parsed) => parsed,
+        // --help and --version aren't errors
+        Err(e) if !e.use_stderr() => e.exit(),
+        Err(e) => {
+            let _ = e.print();
+            std::process::exit(exit_code::INVALID_INPUT);
+        }
+    };
+    let config = std::env::current_dir().map_err(gltf_ktxer::Error::from).and_then(|dir| Config::discover(&dir));
+    let config_file = matches!(config, Ok(Some(_))).then(|| PathBuf::from(CONFIG_FILE_NAME));
+    let configured = config.and_then(|config| apply_config(&mut args, &matches, &config.unwrap_or_default()));
+    if configured.is_ok() {
+        if let Some(tuning) = apply_tuning(&mut args.command) {
+            println!("{tuning}");
+        }
+    }
+    let color = !args.no_color && std::env::var_os("NO_COLOR").is_none() && std::io::stderr().is_terminal();
+
+    let mut context = RunContext::default();
+    let overwrite = if args.skip_existing {
+        OverwritePolicy::SkipExisting
+    } else if args.if_newer {
+        OverwritePolicy::IfNewer
+    } else {
+        OverwritePolicy::Overwrite
+    };
+    let output = OutputOptions { backup: args.backup, overwrite, depfile: args.depfile, config_file, written: RefCell::default() };
+    let result = configured.and_then(|()| run(args.command, &output, &mut context));
+    for warning in &context.warnings {
+        match args.error_format {
+            ErrorFormat::Human => eprint!("{}", render_warning(warning, context.doc.as_ref(), color)),
+            ErrorFormat::Json => eprintln!("{}", warning_json(warning, context.file.as_deref())),
+        }
+    }
+    if let Err(e) = result {
+        let interrupted = matches!(e.without_location(), gltf_ktxer::Error::Interrupted);
+        if interrupted {
+            remove_temp_files();
+        }
+        match args.error_format {
+            ErrorFormat::Human => eprint!("{}", render_error(&e, context.doc.as_ref(), color)),
+            ErrorFormat::Json => eprintln!("{}", error_json(&e, context.file.as_deref())),
+        }
+        if interrupted && matches!(args.error_format, ErrorFormat::Human) {
+            eprint!("{}", interrupted_summary(&output.written.borrow()));
+        }
+        std::process::exit(error_exit_code(&e));
+    }
+    if args.fail_on == FailOn::Warning && !context.warnings.is_empty() {
+        std::process::exit(exit_code::WARNINGS);
+    }
+    std::process::exit(exit_code::OK);
+}
+
+/// Fill in the encoder effort which wasn't given from [Tuning::auto] for this machine,
+/// returning a line describing the machine and the settings the command runs with, to log.
+/// The effort is filled in before the command's settings are stamped, as it changes the output, but the thread count isn't.
+fn apply_tuning(command: &mut Command) -> Option<String> {
+    let (effort, threads) = match command {
+        // One image at a time
+        Command::EncodeImage { effort, .. } => (effort, Some(NonZeroUsize::MIN)),
+        Command::Convert { effort, threads, .. } => (effort, *threads),
+        _ => return None,
+    };
+    let host = HostCapabilities::detect();
+    let auto = Tuning::auto(&host);
+    let tuning = Tuning { effort: *effort.get_or_insert(auto.effort), threads: threads.unwrap_or(auto.threads) };
+    Some(describe(&host, &tuning))
+}
+
+/// Fill in the options which weren't given on the command line or through the environment from `config`.
+fn apply_config(args: &mut Args, matches: &ArgMatches, config: &Config) -> gltf_ktxer::Result<()> {
+    fn unset(matches: &ArgMatches, id: &str) -> bool {
+        !matches!(matches.value_source(id), Some(ValueSource::CommandLine | ValueSource::EnvVariable))
+    }
+    fn parse<T: ValueEnum>(key: &str, value: &str) -> gltf_ktxer::Result<T> {
+        T::from_str(value, false).map_err(|e| gltf_ktxer::Error::BadConfig(format!("{key}: {e}")))
+    }
+
+    if let Some(no_color) = config.no_color.filter(|_| unset(matches, "no_color")) {
+        args.no_color = no_color;
+    }
+    if let Some(error_format) = config.error_format.as_deref().filter(|_| unset(matches, "error_format")) {
+        args.error_format = parse("error-format", error_format)?;
+    }
+    if let Some(fail_on) = config.fail_on.as_deref().filter(|_| unset(matches, "fail_on")) {
+        args.fail_on = parse("fail-on", fail_on)?;
+    }
+    if let Some(backup) = config.backup.filter(|_| unset(matches, "backup")) {
+        args.backup = backup;
+    }
+
+    let Some((_, matches)) = matches.subcommand() else {
+        return Ok(());
+    };
+    match &mut args.command {
+        Command::EncodeImage { input, preset, codec, mipmaps, max_size, linear, target, atlas, max_memory, effort, .. } => {
+            let file_name = input.file_name().unwrap_or_default().to_string_lossy();
+            if let Some(texture) = config.texture_overrides()?.get(&file_name).filter(|_| atlas.is_none()) {
+                *atlas = texture.atlas;
+            }
+            if let Some(name) = config.preset.as_deref().filter(|_| preset.is_none()) {
+                *preset = Some(name.parse().map_err(|e| gltf_ktxer::Error::BadConfig(format!("preset: {e}")))?);
+            }
+            if let Some(names) = config.target.as_ref().filter(|_| target.is_empty()) {
+                *target = names
+                    .iter()
+                    .map(|name| name.parse().map_err(|e| gltf_ktxer::Error::BadConfig(format!("target: {e}"))))
+                    .collect::<gltf_ktxer::Result<_>>()?;
+            }
+            if let Some(name) = config.codec.as_deref().filter(|_| codec.is_none()) {
+                *codec = Some(parse_codec(name).map_err(|e| gltf_ktxer::Error::BadConfig(format!("codec: {e}")))?);
+            }
+            *max_size = max_size.or(config.max_size);
+            if let Some(name) = config.effort.as_deref().filter(|_| effort.is_none()) {
+                *effort = Some(name.parse().map_err(|e| gltf_ktxer::Error::BadConfig(format!("effort: {e}")))?);
+            }
+            if let Some(size) = config.max_memory.as_deref().filter(|_| max_memory.is_none()) {
+                *max_memory = Some(size.parse().map_err(|e| gltf_ktxer::Error::BadConfig(format!("max-memory: {e}")))?);
+            }
+            if let Some(value) = config.mipmaps.filter(|_| unset(matches, "mipmaps")) {
+                *mipmaps = value;
+            }
+            if let Some(value) = config.linear.filter(|_| unset(matches, "linear")) {
+                *linear = value;
+            } else if unset(matches, "linear") {
+                let mut slots = SlotRegistry::default();
+                for rule in config.name_rules()?.into_iter().rev() {
+                    slots.add_name_rule(rule);
+                }
+                let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+                if let Some(semantic) = slots.semantic_for_names([stem.as_ref()]) {
+                    *linear = slots.settings(semantic).color_space == ColorSpace::Linear;
+                }
+            }
+        }
+        Command::Convert { preset, mipmaps, max_size, max_encode_seconds_per_texture, effort, threads, json_pretty, .. } => {
+            if let Some(name) = config.preset.as_deref().filter(|_| preset.is_none()) {
+                *preset = Some(name.parse().map_err(|e| gltf_ktxer::Error::BadConfig(format!("preset: {e}")))?);
+            }
+            *max_size = max_size.or(config.max_size);
+            *max_encode_seconds_per_texture = max_encode_seconds_per_texture.or(config.max_encode_seconds_per_texture);
+            if let Some(name) = config.effort.as_deref().filter(|_| effort.is_none()) {
+                *effort = Some(name.parse().map_err(|e| gltf_ktxer::Error::BadConfig(format!("effort: {e}")))?);
+            }
+            *threads = threads.or(config.threads);
+            if let Some(value) = config.mipmaps.filter(|_| unset(matches, "mipmaps")) {
+                *mipmaps = value;
+            }
+            if let Some(value) = config.json_pretty.filter(|_| unset(matches, "json_pretty") && unset(matches, "json_minify")) {
+                *json_pretty = value;
+            }
+        }
+        Command::Pack { json_pretty, glb_overflow, external_validate, external_validator, dedup_textures, slim_json, keep_extras, strip_names, reference, gc_root, precompress, brotli_command, .. } => {
+            if let Some(value) = config.json_pretty.filter(|_| unset(matches, "json_pretty") && unset(matches, "json_minify")) {
+                *json_pretty = value;
+            }
+            if let Some(value) = config.dedup_textures.filter(|_| unset(matches, "dedup_textures")) {
+                *dedup_textures = value;
+            }
+            if let Some(value) = config.slim_json.filter(|_| unset(matches, "slim_json")) {
+                *slim_json = value;
+            }
+            if let Some(patterns) = config.keep_extras.as_ref().filter(|_| keep_extras.is_empty()) {
+                *keep_extras = patterns.clone();
+            }
+            if let Some(value) = config.strip_names.filter(|_| unset(matches, "strip_names")) {
+                *strip_names = value;
+            }
+            if let Some(patterns) = config.references.as_ref().filter(|_| reference.is_empty()) {
+                *reference = patterns.clone();
+            }
+            if let Some(patterns) = config.gc_roots.as_ref().filter(|_| gc_root.is_empty()) {
+                *gc_root = patterns.clone();
+            }
+            if let Some(names) = config.precompress.as_ref().filter(|_| precompress.is_empty()) {
+                *precompress = names
+                    .iter()
+                    .map(|name| name.parse().map_err(|e| gltf_ktxer::Error::BadConfig(format!("precompress: {e}"))))
+                    .collect::<gltf_ktxer::Result<_>>()?;
+            }
+            if brotli_command.is_none() {
+                brotli_command.clone_from(&config.brotli_command);
+            }
+            if let Some(value) = config.glb_overflow.as_deref().filter(|_| unset(matches, "glb_overflow")) {
+                *glb_overflow = parse("glb-overflow", value)?;
+            }
+            apply_external_validate_config(unset(matches, "external_validate"), config, external_validate, external_validator);
+        }
+        Command::Validate { external_validate, external_validator, .. } => {
+            apply_external_validate_config(unset(matches, "external_validate"), config, external_validate, external_validator);
+        }
+        Command::Cache { action: CacheAction::Stats { cache_dir } | CacheAction::Prune { cache_dir, .. } } if cache_dir.is_none() => {
+            *cache_dir = config.cache_dir.as_ref().map(PathBuf::from);
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Fill in the glTF-Validator options shared by several commands from `config`, like [apply_config].
+fn apply_external_validate_config(external_validate_unset: bool, config: &Config, external_validate: &mut bool, external_validator: &mut Option<String>) {
+    if let Some(value) = config.external_validate.filter(|_| external_validate_unset) {
+        *external_validate = value;
+    }
+    if external_validator.is_none() {
+        external_validator.clone_from(&config.external_validator);
+    }
+}
+
+/// Run a `cache` subcommand.
+fn run_cache(action: CacheAction) -> gltf_ktxer::Result<()> {
+    let (CacheAction::Stats { cache_dir } | CacheAction::Prune { cache_dir, .. }) = &action;
+    let Some(dir) = cache_dir else {
+        return Err(gltf_ktxer::Error::NoCacheDir);
+    };
+    let cache = DiskCache::open(dir)?;
+    let now = SystemTime::now();
+    let ago = |time: SystemTime| {
+        let secs = now.duration_since(time).unwrap_or_default().as_secs();
+        let units = [("w", 7 * 24 * 60 * 60), ("d", 24 * 60 * 60), ("h", 60 * 60), ("m", 60)];
+        match units.into_iter().find(|&(_, unit)| secs >= unit) {
+            Some((suffix, unit)) => format!("{}{suffix} ago", secs / unit),
+            None => format!("{secs}s ago"),
+        }
+    };
+    match action {
+        CacheAction::Stats { .. } => {
+            let stats = cache.stats()?;
+            println!("{}: {} entries, {} bytes", dir.display(), stats.entries, stats.bytes);
+            if let (Some(least), Some(most)) = (stats.least_recently_used, stats.most_recently_used) {
+                println!("least recently used {}, most recently used {}", ago(least), ago(most));
+            }
+        }
+        CacheAction::Prune { max_size, max_age, .. } => {
+            let stats = cache.prune(max_size.map(|size| size.0), max_age.map(|age| age.0), now)?;
+            println!(
+                "removed {} entries ({} bytes) and {} abandoned temporary files, keeping {} entries ({} bytes)",
+                stats.removed_entries, stats.removed_bytes, stats.removed_temp_files, stats.kept_entries, stats.kept_bytes
+            );
+        }
+    }
+    Ok(())
+}
+
+/// What an interrupted run got done, and how to carry on from there.
+fn interrupted_summary(written: &[PathBuf]) -> String {
+    let mut summary = match written.len() {
+        0 => "No files were written before stopping.\n".to_string(),
+        count => format!("{count} files were written before stopping:\n"),
+    };
+    for path in written {
+        summary.push_str(&format!("  {}\n", path.display()));
+    }
+    summary.push_str("Files are only ever replaced whole, so none were left half-written. Run the command again to finish, with --if-newer to skip outputs which are already up to date.\n");
+    summary
+}
+
+fn error_exit_code(e: &gltf_ktxer::Error) -> i32 {
+    use gltf_ktxer::Error;
+    match e.without_location() {
+        Error::Io(_) => exit_code::IO,
+        Error::Interrupted => exit_code::INTERRUPTED,
+        Error::Image(image::ImageError::IoError(_)) => exit_code::IO,
+        Error::Image(image::ImageError::Encoding(_)) => exit_code::ENCODE_FAILURE,
+        Error::EncoderUnavailable(_)
+        | Error::Ktx2ZeroSize { .. }
+        | Error::Ktx2BadMipLevel { .. }
+        | Error::Ktx2WrongImageCount { .. }
+        | Error::Ktx2MismatchedLevelCounts
+        | Error::Ktx2BadImageIndex { .. }
+        | Error::Ktx2WrongImageSize { .. }
+        | Error::Ktx2Supercompressed
+        | Error::OutputVerificationFailed(_)
+        | Error::Ktx2NotBasis => exit_code::ENCODE_FAILURE,
+        _ => exit_code::INVALID_INPUT,
+    }
+}
+
+/// Run `command`, recording the file and glTF document it works on and any warnings in `context`.
+/// Output files are written atomically, and existing ones are kept or replaced following `options`.
+fn run(command: Command, options: &OutputOptions, context: &mut RunContext) -> gltf_ktxer::Result<()> {
+    let write = |path: &Path, data: &[u8]| options.write(path, data);
+    // Stamps cover every setting, so changing any option makes outputs out of date
+    let settings = format!("{command:?}");
+    match command {
+        Command::Placeholder { size, color, checker, cell_size, linear, output } => {
+            let outputs = [output.clone()];
+            let stamp = options.stamp(&settings, || Ok(vec![]))?;
+            if options.skip(&outputs, stamp.as_deref()) {
+                return Ok(());
+            }
+            let a = parse_color(&color)?;
+            let placeholder = match checker {
+                Some(b) => Placeholder::Checkerboard { a, b: parse_color(&b)?, cell_size },
+                None => Placeholder::Solid(a),
+            };
+            let color_space = if linear { ColorSpace::Linear } else { ColorSpace::Srgb };
+            write(&output, &placeholder.generate_ktx2(size, size, color_space)?)?;
+            options.finish(&outputs, stamp.as_deref(), &[])?;
+        }
+        Command::EncodeImage {
+            input,
+            manifest,
+            output,
+            preset,
+            codec,
+            mipmaps,
+            max_size,
+            linear,
+            target,
+            atlas,
+            max_memory,
+            effort,
+            debug_uv_checker,
+            report,
+            baseline,
+            on_regression,
+            max_size_growth,
+            max_psnr_drop,
+            no_summary,
+        } => {
+            context.file = Some(input.clone());
+            let target_count = target.len();
+            let preset = preset.map(Params::from_preset);
+            let codec = codec
+                .or(preset.as_ref().map(|params| match params.ktx_codec {
+                    KtxCodec::Etc1s => Codec::Etc1s,
+                    KtxCodec::Uastc => Codec::Uastc,
+                }))
+                .unwrap_or(Codec::Rgba8);
+            let mipmaps = mipmaps || preset.as_ref().is_some_and(|params| params.generate_mipmaps);
+            let max_size = max_size.or(preset.as_ref().and_then(|params| params.max_texture_size));
+            let color_space = if linear { ColorSpace::Linear } else { ColorSpace::Srgb };
+            match codec {
+                Codec::Rgba8 => {}
+                Codec::Uastc => return Err(gltf_ktxer::Error::EncoderUnavailable("UASTC")),
+                // Manifests can hold arrays and cubemaps, which the ETC1S encoder doesn't take
+                Codec::Etc1s if manifest => return Err(gltf_ktxer::Error::EncoderUnavailable("ETC1S")),
+                Codec::Etc1s => {}
+            }
+            let basis_quality = preset.as_ref().and_then(|params| params.ktx_basis_compression_quality);
+            let targets: Vec<Option<TargetProfile>> = if target.is_empty() { vec![None] } else { target.iter().copied().map(Some).collect() };
+            let outputs: Vec<PathBuf> = targets
+                .iter()
+                .map(|target| match target.filter(|_| target_count > 1) {
+                    Some(target) => output_for_target(&output, target),
+                    None => output.clone(),
+                })
+                .collect();
+            let mut inputs = vec![input.clone()];
+            if manifest {
+                let dir = input.parent().unwrap_or(Path::new(""));
+                inputs.extend(ImageManifest::load(&input)?.images.iter().flat_map(|entry| entry.levels()).map(|level| dir.join(level)));
+            }
+            let all_outputs: Vec<PathBuf> = outputs.iter().chain(&report).cloned().collect();
+            let stamp = options.stamp(&settings, || inputs.iter().chain(&baseline).map(|input| Ok(std::fs::read(input)?)).collect())?;
+            if options.skip(&all_outputs, stamp.as_deref()) {
+                return Ok(());
+            }
+            let baseline = baseline.as_deref().map(QualityReport::load).transpose()?;
+            let source_bytes = inputs.iter().map(|input| Ok(std::fs::metadata(input)?.len())).sum::<gltf_ktxer::Result<u64>>()?;
+            let codec_name = codec.to_possible_value().map(|value| value.get_name().to_string());
+            let mut quality = QualityReport::default();
+            let tracker = MemoryTracker::new(max_memory.map(|size| size.0));
+            // Decode once, however many targets there are
+            let _decoded = if manifest { None } else { Some(tracker.alloc(rgba8_bytes(image::image_dimensions(&input)?, false))?) };
+            let source = if manifest { None } else { Some(image::open(&input)?.into_rgba8()) };
+            for (target_idx, target) in targets.into_iter().enumerate() {
+                shutdown::check()?;
+                let started = Instant::now();
+                let max_size = match target {
+                    Some(target) => Some(target.max_texture_size(max_size)),
+                    None => max_size,
+                };
+                let (ktx, reference) = match &source {
+                    None => {
+                        let dir = input.parent().unwrap_or(Path::new(""));
+                        (ImageManifest::load(&input)?.encode(dir, mipmaps, color_space)?, None)
+                    }
+                    Some(source) => {
+                        let dimensions = max_size.map_or(source.dimensions(), |max_size| ktx2::fit_dimensions(source.dimensions(), max_size));
+                        // The resized image and mip chain, then the encoded output, which is at most as big
+                        let _working = tracker.alloc(rgba8_bytes(dimensions, mipmaps))?;
+                        let _output = tracker.alloc(rgba8_bytes(dimensions, mipmaps))?;
+                        let mut image = match max_size {
+                            Some(max_size) => fit_within(source, max_size),
+                            None => source.clone(),
+                        };
+                        if debug_uv_checker {
+                            apply_transforms(&[Arc::new(UvChecker::default())], &mut image, !linear)?;
+                        }
+                        let levels = if mipmaps {
+                            let mut levels = match atlas {
+                                Some(grid) => ktx2::generate_atlas_mipmaps(&image, grid),
+                                None => ktx2::generate_mipmaps(&image),
+                            };
+                            if let Some(target) = target {
+                                levels.truncate(level_count(image.dimensions(), &GltfSampler::default(), target) as usize);
+                            }
+                            levels
+                        } else {
+                            vec![image.clone()]
+                        };
+                        let ktx = match codec {
+                            Codec::Etc1s => etc1s::encode_levels(&levels, color_space, basis_quality, effort)?,
+                            _ => Ktx2Texture::from_rgba8_levels(&levels, color_space)?,
+                        };
+                        (ktx, Some(image))
+                    }
+                };
+                let bytes = ktx.to_bytes();
+                write(&outputs[target_idx], &bytes)?;
+                let psnr = match reference {
+                    Some(reference) if matches!(codec, Codec::Etc1s) => Some(psnr(&reference, &etc1s::decode(&ktx, 0)?)),
+                    Some(reference) => ktx.level0_rgba8()?.map(|level0| psnr(&reference, &level0)),
+                    None => None,
+                };
+                let name = outputs[target_idx].file_name().unwrap_or_default().to_string_lossy().into_owned();
+                quality.textures.push(TextureQuality {
+                    name,
+                    bytes: bytes.len() as u64,
+                    psnr,
+                    codec: codec_name.clone(),
+                    source_bytes: Some(source_bytes),
+                    seconds: Some(started.elapsed().as_secs_f64()),
+                });
+            }
+            if let Some(report) = &report {
+                write(report, &quality.to_json()?)?;
+            }
+            if !no_summary {
+                print!("{}", render_summary(&quality));
+            }
+            // Before stamping, so outputs which regressed aren't skipped as up to date next time
+            if let Some(baseline) = &baseline {
+                let thresholds = RegressionThresholds { max_size_growth_percent: max_size_growth, max_psnr_drop };
+                context.warnings.extend(check_regressions(baseline, &quality, thresholds, on_regression)?);
+            }
+            options.finish(&all_outputs, stamp.as_deref(), &inputs)?;
+        }
+        Command::Convert {
+            input,
+            output,
+            preset,
+            codec,
+            mipmaps,
+            max_size,
+            keep_failed_originals,
+            max_encode_seconds_per_texture,
+            effort,
+            threads,
+            only_changed,
+            json_pretty,
+            json_minify: _,
+            allow_outside_root,
+        } => {
+            context.file = Some(input.clone());
+            let json_format = if json_pretty { JsonFormat::Pretty } else { JsonFormat::Minified };
+            let load_options = LoadOptions { allow_outside_root };
+            let mut loaded = load_gltf_with(&input, &load_options)?;
+            let outputs = [output.clone()];
+            let stamp = options.stamp(&settings, || {
+                let mut binaries: Vec<_> = loaded.binaries.iter().collect();
+                binaries.sort_by_key(|(uri, _)| *uri);
+                Ok([std::fs::read(&input)?].into_iter().chain(binaries.into_iter().map(|(_, data)| data.clone())).collect())
+            })?;
+            if options.skip(&outputs, stamp.as_deref()) {
+                return Ok(());
+            }
+            let previous = match output.exists() && only_changed {
+                true => {
+                    let mut previous = load_gltf_with(&output, &load_options)?;
+                    Some(Arc::new(PreviousOutput::read(&previous.input())?))
+                }
+                false => None,
+            };
+            // Planning takes its params by value, and running the jobs needs them again
+            let params = || {
+                let mut params = preset.map(Params::from_preset).unwrap_or_default();
+                params.ktx_codec = codec.unwrap_or(params.ktx_codec);
+                params.generate_mipmaps |= mipmaps;
+                params.max_texture_size = max_size.or(params.max_texture_size);
+                params.max_encode_seconds_per_texture = max_encode_seconds_per_texture;
+                params.encode_effort = effort;
+                params.max_threads = threads;
+                if keep_failed_originals {
+                    params.on_encode_failure = EncodeFailurePolicy::KeepOriginal;
+                }
+                Params { record_fingerprints: true, reuse_from: previous.clone(), ..params }
+            };
+            context.doc = Some(loaded.doc.clone());
+            let mut jobs = get_reencode_jobs(loaded.input(), params())?;
+            if let Some(previous) = &previous {
+                let changed = changed_jobs(&jobs.new_images, previous).len();
+                println!("{} of {} images are unchanged since {} was written, reusing them", jobs.new_images.len() - changed, jobs.new_images.len(), output.display());
+            }
+            let result = execute_reencode_jobs(&mut jobs, &mut loaded.doc, loaded.binaries, &params());
+            context.warnings.append(&mut jobs.warnings);
+            let packed = result?;
+            let dir = output.parent().unwrap_or(Path::new(""));
+            // Write the files the document references before the document, so it never points at missing data
+            for (uri, data) in &packed.external_binaries {
+                write(&dir.join(uri), data)?;
+            }
+            if output.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("glb")) {
+                write(&output, &packed.to_glb(json_format)?)?;
+            } else {
+                let binary_uri = output.with_extension("bin").file_name().unwrap().to_string_lossy().into_owned();
+                if !packed.binary.is_empty() {
+                    write(&dir.join(&binary_uri), &packed.binary)?;
+                }
+                write(&output, &packed.to_gltf(&binary_uri, json_format)?)?;
+            }
+            options.finish(&outputs, stamp.as_deref(), &depfile::gltf_inputs(&input, context.doc.as_ref().expect("set above")))?;
+        }
+        Command::KtxInfo { input } => {
+            context.file = Some(input.clone());
+            print_ktx_info(&input)?
+        }
+        Command::Pack { input, output, json_pretty, json_minify: _, glb_overflow, external_validate, external_validator, allow_outside_root, dedup_textures, slim_json, keep_extras, strip_names, reference, gc_root, precompress, brotli_command } => {
+            context.file = Some(input.clone());
+            let json_format = if json_pretty { JsonFormat::Pretty } else { JsonFormat::Minified };
+            let mut loaded = load_gltf_with(&input, &LoadOptions { allow_outside_root })?;
+            let outputs: Vec<PathBuf> = std::iter::once(output.clone()).chain(precompress.iter().map(|sidecar| sidecar.path_for(&output))).collect();
+            // Each file along with its precompressed copies, all compressed before any is written so a failure leaves none out of step
+            let write = |path: &Path, data: &[u8]| -> gltf_ktxer::Result<()> {
+                let compressed = precompress
+                    .iter()
+                    .map(|&sidecar| Ok((sidecar, sidecar.compress(data, brotli_command.as_deref().unwrap_or(DEFAULT_BROTLI_COMMAND))?)))
+                    .collect::<gltf_ktxer::Result<Vec<_>>>()?;
+                write(path, data)?;
+                let mut sizes = vec![];
+                for (sidecar, compressed) in compressed {
+                    write(&sidecar.path_for(path), &compressed)?;
+                    sizes.push(format!("{sidecar} {} bytes ({:.0}%)", compressed.len(), compressed.len() as f64 * 100.0 / data.len().max(1) as f64));
+                }
+                if !sizes.is_empty() {
+                    println!("{}: {} bytes, {}", path.display(), data.len(), sizes.join(", "));
+                }
+                Ok(())
+            };
+            let stamp = options.stamp(&settings, || {
+                let mut binaries: Vec<_> = loaded.binaries.iter().collect();
+                binaries.sort_by_key(|(uri, _)| *uri);
+                Ok([std::fs::read(&input)?].into_iter().chain(binaries.into_iter().map(|(_, data)| data.clone())).collect())
+            })?;
+            if options.skip(&outputs, stamp.as_deref()) {
+                return Ok(());
+            }
+            let mut references = ReferenceRegistry::default();
+            for pattern in &reference {
+                references.register(pattern.parse()?);
+            }
+            for pattern in &gc_root {
+                references.register_root(pattern.parse()?);
+            }
+            let params = Params {
+                dedup_samplers_and_textures: dedup_textures,
+                references,
+                slim_json: slim_json.then(|| SlimOptions { keep_extras, strip_names, ..SlimOptions::default() }),
+                ..Params::default()
+            };
+            let packed = prepare_output_buffers(loaded.input(), &params)?;
+            let dedup = packed.image_view_dedup;
+            if dedup.views_removed > 0 {
+                println!("{} images shared data with another image, saving {} bytes", dedup.images_repointed, dedup.bytes_saved);
+            }
+            let table_dedup = packed.table_dedup;
+            if table_dedup.samplers_removed + table_dedup.textures_removed > 0 {
+                println!("Merged {} duplicate samplers and {} duplicate textures", table_dedup.samplers_removed, table_dedup.textures_removed);
+            }
+            if slim_json {
+                let slim = packed.json_slim;
+                let binary_bytes = packed.binary.len() + packed.external_binaries.values().map(Vec::len).sum::<usize>();
+                println!("JSON: {} bytes before slimming, {} after; binary: {binary_bytes} bytes", slim.json_bytes_before, slim.json_bytes_after);
+            }
+            let dir = output.parent().unwrap_or(Path::new(""));
+            // Write the files the document references before the document, so it never points at missing data
+            for (uri, data) in &packed.external_binaries {
+                write(&dir.join(uri), data)?;
+            }
+            let write_gltf = |output: &Path| -> gltf_ktxer::Result<()> {
+                let binary_uri = output.with_extension("bin").file_name().unwrap().to_string_lossy().into_owned();
+                if !packed.binary.is_empty() {
+                    write(&dir.join(&binary_uri), &packed.binary)?;
+                }
+                write(output, &packed.to_gltf(&binary_uri, json_format)?)
+            };
+            let written = if output.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("glb")) {
+                match packed.to_glb(json_format) {
+                    Ok(glb) => {
+                        write(&output, &glb)?;
+                        output.clone()
+                    }
+                    Err(e) if glb_overflow == GlbOverflow::Gltf && matches!(e.without_location(), gltf_ktxer::Error::GlbTooLarge { .. }) => {
+                        let gltf_output = output.with_extension("gltf");
+                        write_gltf(&gltf_output)?;
+                        context.warnings.push(Warning {
+                            code: "glb_too_large",
+                            json_pointer: String::new(),
+                            message: format!("{e}, so wrote {} instead", gltf_output.display()),
+                        });
+                        gltf_output
+                    }
+                    Err(e) => return Err(e),
+                }
+            } else {
+                write_gltf(&output)?;
+                output.clone()
+            };
+            // Before stamping, so outputs the validator rejected aren't skipped as up to date next time
+            if external_validate {
+                run_external_validator(&written, external_validator.as_deref(), context)?;
+            }
+            options.finish(&outputs, stamp.as_deref(), &depfile::gltf_inputs(&input, &loaded.doc))?;
+        }
+        Command::Cache { action } => run_cache(action)?,
+        Command::Validate { input, #[cfg(feature = "schema")] schema, external_validate, external_validator, allow_outside_root } => {
+            context.file = Some(input.clone());
+            let loaded = load_gltf_with(&input, &LoadOptions { allow_outside_root })?;
+            let loaded_doc = context.doc.insert(loaded.doc);
+            validate(loaded_doc, &loaded.binaries)?;
+            check_images_decode(loaded_doc, &loaded.binaries)?;
+            #[cfg(feature = "schema")]
+            if schema {
+                gltf_ktxer::schema::validate_schema(loaded_doc)?;
+            }
+            context.warnings = lint(loaded_doc)?;
+            if external_validate {
+                run_external_validator(&input, external_validator.as_deref(), context)?;
+            }
+            println!("{} is valid", input.display());
+        }
+    }
+    Ok(())
+}
+
+/// Check `path` with glTF-Validator, run with `command` or [DEFAULT_VALIDATOR_COMMAND],
+/// adding its warnings to `context` and failing if it finds errors.
+fn run_external_validator(path: &Path, command: Option<&str>, context: &mut RunContext) -> gltf_ktxer::Result<()> {
+    let messages = run_validator(command.unwrap_or(DEFAULT_VALIDATOR_COMMAND), path)?;
+    let (error, warnings) = into_findings(messages);
+    context.warnings.extend(warnings);
+    error.map_or(Ok(()), Err)
+}
+
+/// Where to write the output for `target` when there are several: `output` with the target name before its extension.
+fn output_for_target(output: &Path, target: TargetProfile) -> PathBuf {
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    match output.extension() {
+        Some(ext) => output.with_file_name(format!("{stem}.{target}.{}", ext.to_string_lossy())),
+        None => output.with_file_name(format!("{stem}.{target}")),
+    }
+}
+
+fn print_ktx_info(path: &Path) -> gltf_ktxer::Result<()> {
+    let ktx = Ktx2Texture::from_bytes(&std::fs::read(path)?)?;
+
+    println!("{}", path.display());
+    println!("  vkFormat: {}", ktx.vk_format);
+    println!("  typeSize: {}", ktx.type_size);
+    println!("  size: {}x{}x{}", ktx.pixel_width, ktx.pixel_height, ktx.pixel_depth);
+    println!("  layers: {}, faces: {}", ktx.layer_count, ktx.face_count);
+    println!("  supercompression: {} ({})", ktx2::supercompression_name(ktx.supercompression_scheme), ktx.supercompression_scheme);
+
+    println!("  levels:");
+    for (i, level) in ktx.levels.iter().enumerate() {
+        let (width, height) = ((ktx.pixel_width >> i).max(1), (ktx.pixel_height >> i).max(1));
+        println!("    {i}: {width}x{height}, {} bytes ({} uncompressed)", level.data.len(), level.uncompressed_byte_length);
+    }
+
+    println!("  data format:");
+    match (ktx.dfd_color_model(), ktx.dfd_color_primaries(), ktx.dfd_transfer_function()) {
+        (Some(model), Some(primaries), Some(transfer)) => {
+            println!("    model: {} ({model})", ktx2::color_model_name(model));
+            println!("    primaries: {} ({primaries})", ktx2::color_primaries_name(primaries));
+            println!("    transfer: {} ({transfer})", ktx2::transfer_function_name(transfer));
+            println!("    channels: {:?}", ktx.dfd_sample_channels());
+        }
+        _ => println!("    missing basic descriptor block"),
+    }
+
+    if !ktx.sgd.is_empty() {
+        println!("  supercompression global data: {} bytes", ktx.sgd.len());
+    }
+
+    println!("  key/value data:");
+    for (key, value) in &ktx.key_values {
+        // Most values are NUL-terminated strings, anything else is shown as hex.
+        match value.strip_suffix(&[0]).map(std::str::from_utf8) {
+            Some(Ok(text)) => println!("    {key}: {text}"),
+            _ => println!("    {key}: {}", value.iter().map(|b| format!("{b:02x}")).collect::<String>()),
+        }
+    }
+    Ok(())
+}
@@ -1,4 +1,5 @@
-use gltf_ktxer;
+use clap::Parser;
+use gltf_ktxer::gltf::GltfDoc;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
@@ -9,5 +10,11 @@ struct Args {
 
 fn main() {
     let args = Args::parse();
-    
+
+    let bytes = std::fs::read(&args.input).expect("couldn't read input file");
+    let doc: GltfDoc = serde_json::from_slice(&bytes).expect("input file wasn't glTF JSON");
+    for list_name in ["buffers", "bufferViews", "images", "textures", "materials"] {
+        let len = doc.get(list_name).and_then(|val| val.as_array()).map_or(0, |arr| arr.len());
+        println!("{list_name}: {len}");
+    }
 }
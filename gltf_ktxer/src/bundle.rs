@@ -0,0 +1,108 @@
+//! Texture-only output, for engines whose own material system loads textures separately from the glTF file.
+//!
+//! Instead of rewriting the document, [texture_bundle] names a KTX2 file for each planned KTX2 job and builds a small JSON mapping
+//! from each material's texture slots to those files. The host encodes the jobs and writes the files next to the mapping.
+
+use std::collections::HashMap;
+
+use serde_json::{json, Map, Value};
+
+use crate::{
+    edit,
+    filenames::{self, FileNamer},
+    gltf::{deserialize_list, GltfDoc, GltfImage, GltfList, GltfSampler, GltfTexture},
+    validate, variants, ImageReencodeFormat, ReencodeJobs, Result,
+};
+
+/// The `version` written to the mapping, increased whenever its layout changes incompatibly.
+pub const BUNDLE_MAPPING_VERSION: u64 = 1;
+
+/// A file of the bundle: the output of [ReencodeJobs::new_images]`[job]`, written as `file_name`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BundleFile {
+    pub job: usize,
+    pub file_name: String,
+}
+
+pub struct TextureBundle {
+    /// In job order.
+    pub files: Vec<BundleFile>,
+    /// Each material's slots, like `pbrMetallicRoughness/baseColorTexture`, mapped to the file to load with the texture's
+    /// color space, sampler and the rest of the textureInfo, e.g. `texCoord`.
+    /// Materials `KHR_materials_variants` switches to list the names of their variants.
+    pub mapping: Value,
+    /// Slots left out of the mapping, as their texture has no KTX2 image, e.g. a video texture.
+    pub warnings: Vec<validate::Warning>,
+}
+
+/// The files and mapping for a texture-only output of `doc`, planned as `jobs` by [crate::get_reencode_jobs].
+pub fn texture_bundle(doc: &GltfDoc, jobs: &ReencodeJobs) -> Result<TextureBundle> {
+    let images: Vec<GltfImage> = deserialize_list(doc, "images")?;
+    let textures: Vec<GltfTexture> = deserialize_list(doc, "textures")?;
+    let samplers: Vec<GltfSampler> = deserialize_list(doc, "samplers")?;
+    let materials = doc.get("materials").and_then(Value::as_array).map_or(&[][..], Vec::as_slice);
+    let variants = variants::material_variants(doc)?;
+
+    let mut namer = FileNamer::default();
+    let mut file_names: HashMap<usize, String> = HashMap::new();
+    let mut files = vec![];
+    let mut slots_of: Vec<Map<String, Value>> = vec![Map::new(); materials.len()];
+    let mut warnings = vec![];
+    for (pointer, info) in edit::texture_infos(doc)? {
+        let (material_idx, slot) = pointer["/materials/".len()..].split_once('/').expect("texture_infos points into a material");
+        let material_idx: usize = material_idx.parse().expect("texture_infos points into a material");
+        let tex_idx = info.index.raw_idx();
+        let job = jobs.new_textures.get(tex_idx).and_then(edit::texture_ktx_source).map(|img| img.raw_idx()).filter(|&job| {
+            jobs.new_images.get(job).is_some_and(|job| matches!(job.reencode_as, ImageReencodeFormat::Ktx { .. }))
+        });
+        let Some(job) = job else {
+            warnings.push(validate::Warning {
+                code: "bundle_slot_without_ktx2",
+                json_pointer: pointer.clone(),
+                message: format!("texture {tex_idx} has no KTX2 image, so the slot is left out of the bundle"),
+            });
+            continue;
+        };
+        let texture = textures.gltf_index_required(info.index, "textures")?;
+        let file_name = file_names
+            .entry(job)
+            .or_insert_with(|| {
+                let original = [texture.source].into_iter().chain(edit::texture_ktx_source(texture)).find(|img| img.is_defined());
+                let stem = original.and_then(|img| images.gltf_index(img, "images").ok().flatten()).and_then(filenames::image_stem);
+                let file_name = namer.name(stem.as_deref(), &format!("texture{tex_idx}"), job, "ktx2");
+                files.push(BundleFile { job, file_name: file_name.clone() });
+                file_name
+            })
+            .clone();
+
+        let Value::Object(mut entry) = serde_json::to_value(&info)? else {
+            unreachable!("a textureInfo serializes to an object")
+        };
+        entry.remove("index");
+        entry.insert("uri".to_string(), json!(filenames::file_uri(&file_name)));
+        entry.insert("colorSpace".to_string(), json!(if jobs.new_images[job].data_used_as_srgb { "srgb" } else { "linear" }));
+        if let Some(sampler) = samplers.gltf_index(texture.sampler, "samplers")? {
+            entry.insert("sampler".to_string(), serde_json::to_value(sampler)?);
+        }
+        slots_of[material_idx].insert(slot.to_string(), Value::Object(entry));
+    }
+
+    files.sort_by_key(|file| file.job);
+    let materials: Vec<Value> = materials
+        .iter()
+        .zip(slots_of)
+        .enumerate()
+        .map(|(idx, (material, slots))| {
+            let mut entry = json!({ "material": idx, "slots": slots });
+            if let Some(name) = material.get("name") {
+                entry["name"] = name.clone();
+            }
+            if variants.materials.contains_key(&idx) {
+                entry["variants"] = json!(variants.names_of(idx));
+            }
+            entry
+        })
+        .collect();
+    Ok(TextureBundle { files, mapping: json!({ "version": BUNDLE_MAPPING_VERSION, "materials": materials }), warnings })
+}
+
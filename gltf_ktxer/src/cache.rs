@@ -0,0 +1,128 @@
+//! An in-memory, content-addressed cache of encoded images shared by every worker in a run.
+//!
+//! Models converted in the same batch often share textures, e.g. tiling materials.
+//! Keying on the hash of the source bytes and the encode settings (see [crate::hash]) means each one is only encoded once per run,
+//! however many documents or images refer to it.
+//! With a [DiskCache] underneath (see [EncodeCache::with_disk_cache]), encodes are also kept between runs.
+
+use std::{collections::HashMap, sync::{atomic::{AtomicUsize, Ordering}, Arc, Mutex}};
+
+use crate::{disk_cache::DiskCache, hash::sha256, ImageReencodeJob, Result};
+
+/// Identifies an encoded image by its source data and everything which affects how it is encoded.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EncodeCacheKey {
+    pub source_sha256: [u8; 32],
+    pub encode_params_key: String,
+}
+impl EncodeCacheKey {
+    pub fn for_job(job: &ImageReencodeJob) -> Self {
+        Self { source_sha256: sha256(&job.source.data), encode_params_key: job.encode_params_key() }
+    }
+}
+
+type Entry = Arc<Mutex<Option<Arc<Vec<u8>>>>>;
+
+/// A cache of encoded images, safe to share between threads.
+/// Share one between every document in a batch, e.g. behind an [Arc] or a reference into a thread scope.
+#[derive(Default)]
+pub struct EncodeCache {
+    entries: Mutex<HashMap<EncodeCacheKey, Entry>>,
+    disk: Option<DiskCache>,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+impl EncodeCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Look encodes up in `disk` before encoding them, and store new encodes there.
+    pub fn with_disk_cache(disk: DiskCache) -> Self {
+        Self { disk: Some(disk), ..Self::default() }
+    }
+
+    /// Return the encoded bytes for `job`, calling `encode` only if no other job with the same key has been encoded.
+    /// Concurrent callers with the same key wait for the first encode to finish instead of encoding again.
+    /// Failed encodes aren't cached, so the next caller will retry.
+    pub fn get_or_encode(&self, job: &ImageReencodeJob, encode: impl FnOnce(&ImageReencodeJob) -> Result<Vec<u8>>) -> Result<Arc<Vec<u8>>> {
+        let key = EncodeCacheKey::for_job(job);
+        let entry = self.entries.lock().unwrap().entry(key.clone()).or_default().clone();
+        // Only this entry is locked while encoding, so encodes of different images still run in parallel
+        let mut encoded = entry.lock().unwrap();
+        if let Some(encoded) = encoded.as_ref() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(encoded.clone());
+        }
+        let encode = || {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            encode(job)
+        };
+        let data = match &self.disk {
+            Some(disk) => {
+                let mut encoded_now = false;
+                let data = disk.get_or_encode(&key, || {
+                    encoded_now = true;
+                    encode()
+                })?;
+                if !encoded_now {
+                    self.hits.fetch_add(1, Ordering::Relaxed);
+                }
+                data
+            }
+            None => encode()?,
+        };
+        let data = Arc::new(data);
+        *encoded = Some(data.clone());
+        Ok(data)
+    }
+
+    /// Drop the entries of encodes which failed or never finished, e.g. ones cut short by [crate::shutdown],
+    /// returning how many were dropped. Finished encodes are kept.
+    pub fn discard_unfinished(&self) -> usize {
+        let mut entries = self.entries.lock().unwrap();
+        let before = entries.len();
+        // Entries being encoded are locked, so they count as unfinished too
+        entries.retain(|_, entry| entry.try_lock().is_ok_and(|encoded| encoded.is_some()));
+        before - entries.len()
+    }
+
+    /// The lookup counters, for reporting how well the cache did.
+    pub fn counters(&self) -> CacheCounters {
+        CacheCounters {
+            hits: self.hits(),
+            misses: self.misses(),
+            corrupt_entries: self.disk.as_ref().map_or(0, DiskCache::corrupt_entries),
+        }
+    }
+
+    /// The number of lookups which reused an existing encode.
+    pub fn hits(&self) -> usize {
+        self.hits.load(Ordering::Relaxed)
+    }
+    /// The number of lookups which had to encode.
+    pub fn misses(&self) -> usize {
+        self.misses.load(Ordering::Relaxed)
+    }
+}
+
+/// How an [EncodeCache] did, see [EncodeCache::counters].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CacheCounters {
+    /// Lookups which reused an encode from memory or the disk cache.
+    pub hits: usize,
+    /// Lookups which had to encode.
+    pub misses: usize,
+    /// Damaged disk cache entries which were found, deleted and encoded again.
+    pub corrupt_entries: usize,
+}
+impl std::fmt::Display for CacheCounters {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let lookups = self.hits + self.misses;
+        let rate = if lookups == 0 { 0.0 } else { self.hits as f64 * 100.0 / lookups as f64 };
+        write!(f, "{} cache hits and {} misses ({rate:.0}% hit rate)", self.hits, self.misses)?;
+        if self.corrupt_entries > 0 {
+            write!(f, ", {} damaged entries replaced", self.corrupt_entries)?;
+        }
+        Ok(())
+    }
+}
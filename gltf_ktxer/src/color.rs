@@ -0,0 +1,253 @@
+//! Embedded color profiles of source images, and converting images in other color spaces to sRGB.
+//!
+//! glTF color textures are sRGB, but images can carry their own color space: PNGs through a `cICP` chunk,
+//! and PNGs and JPEGs through an embedded ICC profile. Decoding those as if they were sRGB shifts their colors,
+//! e.g. Display P3 images look washed out. Following PNG's precedence, `cICP` is used over an ICC profile.
+//!
+//! Only RGB matrix/TRC ICC profiles are understood, which covers the profiles image editors embed for
+//! Display P3, Adobe RGB and similar. LUT-based profiles and HDR transfer functions can't be converted.
+
+use std::io::Cursor;
+
+use image::RgbaImage;
+
+type Mat3 = [[f64; 3]; 3];
+
+/// A color space description embedded in an image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EmbeddedProfile {
+    /// PNG `cICP` code points from ITU-T H.273.
+    Cicp { primaries: u8, transfer: u8 },
+    /// The bytes of an ICC profile.
+    Icc(Vec<u8>),
+}
+
+/// The color profile embedded in the encoded image `data`, if any.
+pub fn embedded_profile(data: &[u8]) -> Option<EmbeddedProfile> {
+    if let Some(cicp) = png_cicp(data) {
+        return Some(cicp);
+    }
+    let reader = image::ImageReader::new(Cursor::new(data)).with_guessed_format().ok()?;
+    let mut decoder = reader.into_decoder().ok()?;
+    image::ImageDecoder::icc_profile(&mut decoder).ok().flatten().map(EmbeddedProfile::Icc)
+}
+
+fn png_cicp(data: &[u8]) -> Option<EmbeddedProfile> {
+    let mut chunks = data.strip_prefix(b"\x89PNG\r\n\x1a\n")?;
+    while chunks.len() >= 12 {
+        let len = u32::from_be_bytes(chunks[0..4].try_into().unwrap()) as usize;
+        let (kind, body) = (&chunks[4..8], chunks.get(8..8 + len)?);
+        match kind {
+            b"cICP" if len == 4 => return Some(EmbeddedProfile::Cicp { primaries: body[0], transfer: body[1] }),
+            // cICP must come before the image data
+            b"IDAT" | b"IEND" => return None,
+            _ => chunks = chunks.get(12 + len..)?,
+        }
+    }
+    None
+}
+
+/// A transfer function from encoded values to linear light, both in [0, 1].
+#[derive(Debug, Clone, PartialEq)]
+enum Trc {
+    Srgb,
+    Bt709,
+    Gamma(f64),
+    /// Samples spaced evenly over [0, 1], linearly interpolated.
+    Table(Vec<f64>),
+    /// ICC parametric curve `parametricCurveType` 4, with the simpler types expanded to it.
+    /// `[g, a, b, c, d, e, f]`: `(aX + b)^g + e` if `X >= d`, otherwise `cX + f`.
+    Parametric([f64; 7]),
+}
+impl Trc {
+    fn to_linear(&self, x: f64) -> f64 {
+        match self {
+            Trc::Srgb => if x <= 0.04045 { x / 12.92 } else { ((x + 0.055) / 1.055).powf(2.4) },
+            Trc::Bt709 => if x < 0.081 { x / 4.5 } else { ((x + 0.099) / 1.099).powf(1.0 / 0.45) },
+            Trc::Gamma(gamma) => x.powf(*gamma),
+            Trc::Table(table) => {
+                let pos = x * (table.len() - 1) as f64;
+                let (i, t) = (pos.floor() as usize, pos.fract());
+                let next = table[(i + 1).min(table.len() - 1)];
+                table[i] * (1.0 - t) + next * t
+            }
+            Trc::Parametric([g, a, b, c, d, e, f]) => if x >= *d { (a * x + b).max(0.0).powf(*g) + e } else { c * x + f },
+        }
+    }
+}
+
+fn linear_to_srgb(x: f64) -> f64 {
+    if x <= 0.0031308 { x * 12.92 } else { 1.055 * x.powf(1.0 / 2.4) - 0.055 }
+}
+
+fn mul(a: &Mat3, b: &Mat3) -> Mat3 {
+    std::array::from_fn(|row| std::array::from_fn(|col| (0..3).map(|k| a[row][k] * b[k][col]).sum()))
+}
+
+fn invert(m: &Mat3) -> Option<Mat3> {
+    let cofactor = |r0: usize, r1: usize, c0: usize, c1: usize| m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0];
+    let det = m[0][0] * cofactor(1, 2, 1, 2) - m[0][1] * cofactor(1, 2, 0, 2) + m[0][2] * cofactor(1, 2, 0, 1);
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    Some([
+        [cofactor(1, 2, 1, 2) / det, -cofactor(0, 2, 1, 2) / det, cofactor(0, 1, 1, 2) / det],
+        [-cofactor(1, 2, 0, 2) / det, cofactor(0, 2, 0, 2) / det, -cofactor(0, 1, 0, 2) / det],
+        [cofactor(1, 2, 0, 1) / det, -cofactor(0, 2, 0, 1) / det, cofactor(0, 1, 0, 1) / det],
+    ])
+}
+
+/// The matrix from linear RGB with the given red, green and blue chromaticities and white point to CIE XYZ.
+fn rgb_to_xyz(primaries: [(f64, f64); 3], white: (f64, f64)) -> Mat3 {
+    let xyz = |(x, y): (f64, f64)| [x / y, 1.0, (1.0 - x - y) / y];
+    let columns = primaries.map(xyz);
+    let unscaled: Mat3 = std::array::from_fn(|row| std::array::from_fn(|col| columns[col][row]));
+    let white = xyz(white);
+    let scale = invert(&unscaled).expect("primaries aren't collinear");
+    let scale: [f64; 3] = std::array::from_fn(|row| (0..3).map(|k| scale[row][k] * white[k]).sum());
+    std::array::from_fn(|row| std::array::from_fn(|col| unscaled[row][col] * scale[col]))
+}
+
+const D65: (f64, f64) = (0.3127, 0.3290);
+const BT709_PRIMARIES: [(f64, f64); 3] = [(0.640, 0.330), (0.300, 0.600), (0.150, 0.060)];
+const P3_PRIMARIES: [(f64, f64); 3] = [(0.680, 0.320), (0.265, 0.690), (0.150, 0.060)];
+const BT2020_PRIMARIES: [(f64, f64); 3] = [(0.708, 0.292), (0.170, 0.797), (0.131, 0.046)];
+
+/// sRGB's primaries adapted to the ICC D50 profile connection space with the Bradford transform, from the sRGB ICC profile.
+const SRGB_TO_D50_XYZ: Mat3 = [
+    [0.4360747, 0.3850649, 0.1430804],
+    [0.2225045, 0.7168786, 0.0606169],
+    [0.0139322, 0.0971045, 0.7141733],
+];
+
+/// Converts decoded images from an embedded color space to sRGB.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorConversion {
+    trc: [Trc; 3],
+    /// From linear source RGB to linear sRGB.
+    matrix: Mat3,
+}
+impl ColorConversion {
+    /// The conversion from `profile` to sRGB, or None if it already describes sRGB.
+    /// Fails with the reason if the profile can't be converted.
+    pub fn from_profile(profile: &EmbeddedProfile) -> std::result::Result<Option<Self>, String> {
+        let conversion = match profile {
+            EmbeddedProfile::Cicp { primaries, transfer } => Self::from_cicp(*primaries, *transfer)?,
+            EmbeddedProfile::Icc(icc) => Self::from_icc(icc)?,
+        };
+        Ok(Some(conversion).filter(|conversion| !conversion.is_srgb()))
+    }
+
+    fn from_cicp(primaries: u8, transfer: u8) -> std::result::Result<Self, String> {
+        let primaries = match primaries {
+            1 => BT709_PRIMARIES,
+            9 => BT2020_PRIMARIES,
+            12 => P3_PRIMARIES,
+            _ => return Err(format!("cICP color primaries {primaries} aren't supported")),
+        };
+        let trc = match transfer {
+            13 => Trc::Srgb,
+            1 | 6 | 14 | 15 => Trc::Bt709,
+            4 => Trc::Gamma(2.2),
+            5 => Trc::Gamma(2.8),
+            8 => Trc::Gamma(1.0),
+            16 | 18 => return Err("cICP transfer function is HDR, which can't be converted to sRGB".to_string()),
+            _ => return Err(format!("cICP transfer function {transfer} isn't supported")),
+        };
+        let to_srgb = invert(&rgb_to_xyz(BT709_PRIMARIES, D65)).expect("sRGB is invertible");
+        Ok(Self { trc: [trc.clone(), trc.clone(), trc], matrix: mul(&to_srgb, &rgb_to_xyz(primaries, D65)) })
+    }
+
+    fn from_icc(icc: &[u8]) -> std::result::Result<Self, String> {
+        let u32_at = |offset: usize| icc.get(offset..offset + 4).map(|bytes| u32::from_be_bytes(bytes.try_into().unwrap()));
+        if icc.get(16..20) != Some(b"RGB ") {
+            return Err("ICC profile isn't for RGB images".to_string());
+        }
+        let tag_count = u32_at(128).ok_or("ICC profile is truncated")? as usize;
+        let tag = |signature: &[u8; 4]| -> Option<&[u8]> {
+            (0..tag_count.min(1024)).find_map(|i| {
+                let entry = 132 + i * 12;
+                (icc.get(entry..entry + 4)? == signature).then_some(())?;
+                let (offset, size) = (u32_at(entry + 4)? as usize, u32_at(entry + 8)? as usize);
+                icc.get(offset..offset.checked_add(size)?)
+            })
+        };
+        let unsupported = || "ICC profile isn't a matrix/TRC profile, so can't be converted".to_string();
+
+        let mut to_xyz = [[0.0; 3]; 3];
+        for (col, signature) in [b"rXYZ", b"gXYZ", b"bXYZ"].into_iter().enumerate() {
+            let xyz = tag(signature).filter(|xyz| xyz.starts_with(b"XYZ ") && xyz.len() >= 20).ok_or_else(unsupported)?;
+            for (row, value) in to_xyz.iter_mut().enumerate() {
+                let bytes: [u8; 4] = xyz[8 + row * 4..12 + row * 4].try_into().unwrap();
+                value[col] = i32::from_be_bytes(bytes) as f64 / 65536.0;
+            }
+        }
+
+        let parse_trc = |signature: &[u8; 4]| -> std::result::Result<Trc, String> {
+            let curve = tag(signature).ok_or_else(unsupported)?;
+            let u16_at = |offset: usize| curve.get(offset..offset + 2).map(|bytes| u16::from_be_bytes(bytes.try_into().unwrap()));
+            let truncated = || "ICC profile tone curve is truncated".to_string();
+            match curve.get(0..4) {
+                Some(b"curv") => {
+                    let count = curve.get(8..12).map(|bytes| u32::from_be_bytes(bytes.try_into().unwrap())).ok_or_else(truncated)? as usize;
+                    match count {
+                        0 => Ok(Trc::Gamma(1.0)),
+                        1 => Ok(Trc::Gamma(u16_at(12).ok_or_else(truncated)? as f64 / 256.0)),
+                        _ => (0..count)
+                            .map(|i| u16_at(12 + i * 2).map(|value| value as f64 / 65535.0))
+                            .collect::<Option<Vec<_>>>()
+                            .map(Trc::Table)
+                            .ok_or_else(truncated),
+                    }
+                }
+                Some(b"para") => {
+                    let param = |i: usize| {
+                        curve.get(12 + i * 4..16 + i * 4).map(|bytes| i32::from_be_bytes(bytes.try_into().unwrap()) as f64 / 65536.0).ok_or_else(truncated)
+                    };
+                    let g = param(0)?;
+                    let params = match u16_at(8).ok_or_else(truncated)? {
+                        0 => [g, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+                        1 => {
+                            let (a, b) = (param(1)?, param(2)?);
+                            [g, a, b, 0.0, -b / a, 0.0, 0.0]
+                        }
+                        2 => {
+                            let (a, b, c) = (param(1)?, param(2)?, param(3)?);
+                            [g, a, b, 0.0, -b / a, c, c]
+                        }
+                        3 => [g, param(1)?, param(2)?, param(3)?, param(4)?, 0.0, 0.0],
+                        4 => [g, param(1)?, param(2)?, param(3)?, param(4)?, param(5)?, param(6)?],
+                        other => return Err(format!("ICC parametric curve type {other} isn't supported")),
+                    };
+                    Ok(Trc::Parametric(params))
+                }
+                _ => Err(unsupported()),
+            }
+        };
+        let trc = [parse_trc(b"rTRC")?, parse_trc(b"gTRC")?, parse_trc(b"bTRC")?];
+        let from_xyz = invert(&SRGB_TO_D50_XYZ).expect("sRGB is invertible");
+        Ok(Self { trc, matrix: mul(&from_xyz, &to_xyz) })
+    }
+
+    /// Whether the conversion changes no 8-bit value by more than rounding.
+    fn is_srgb(&self) -> bool {
+        let identity = (0..3).all(|row| (0..3).all(|col| (self.matrix[row][col] - if row == col { 1.0 } else { 0.0 }).abs() < 0.01));
+        let srgb_curves = self.trc.iter().all(|trc| (0..=16).all(|i| {
+            let x = i as f64 / 16.0;
+            (linear_to_srgb(trc.to_linear(x)) - x).abs() < 1.0 / 255.0
+        }));
+        identity && srgb_curves
+    }
+
+    /// Convert the color channels of `image` to sRGB. Alpha is left alone.
+    pub fn apply(&self, image: &mut RgbaImage) {
+        let to_linear: [Vec<f64>; 3] = std::array::from_fn(|channel| (0..=255).map(|value| self.trc[channel].to_linear(value as f64 / 255.0)).collect());
+        for texel in image.pixels_mut() {
+            let linear: [f64; 3] = std::array::from_fn(|channel| to_linear[channel][texel.0[channel] as usize]);
+            for (row, value) in texel.0[..3].iter_mut().enumerate() {
+                let srgb_linear: f64 = (0..3).map(|k| self.matrix[row][k] * linear[k]).sum();
+                *value = (linear_to_srgb(srgb_linear.clamp(0.0, 1.0)) * 255.0).round() as u8;
+            }
+        }
+    }
+}
@@ -0,0 +1,174 @@
+//! Default command-line options from a `gltf-ktxer.toml` file, so repositories can pin their conversion settings.
+//!
+//! The file is looked for in the working directory. Keys are the long option names, e.g.
+//! ```toml
+//! preset = "web-quality"
+//! max-size = 2048
+//! error-format = "json"
+//! ```
+//! Individual images can be given [TextureConfig] overrides, keyed on the image's name or URI (or the input file name for `encode-image`):
+//! ```toml
+//! [textures."atlas.png"]
+//! atlas = "4x4"
+//! [textures.sky]
+//! dither = "blue-noise"
+//! exposure = 1.2
+//! saturation = 0.9
+//! ```
+//! Groups of textures which must share settings, see [crate::groups], list material names and texture or image names, URIs or globs:
+//! ```toml
+//! [groups.hero]
+//! materials = ["Hero_Face", "Hero_Body"]
+//! textures = ["hero_*.png"]
+//! ```
+//! Naming conventions for images no material uses, see [crate::semantic::NameRule], are tried in order before the built-in ones:
+//! ```toml
+//! [[semantic-names]]
+//! pattern = "*_nor"
+//! semantic = "normal"
+//! ```
+//! Options given on the command line or through `GLTF_KTXER_*` environment variables take precedence over the file.
+//! Values are kept as written and checked by the command-line tool, the same way it checks its own arguments.
+
+use std::{collections::BTreeMap, path::Path};
+
+use serde_derive::Deserialize;
+
+use crate::{adjust::ColorAdjustments, groups::TextureGroup, overrides::{TextureOverride, TextureOverrides}, semantic::NameRule, Error, Result};
+
+pub const CONFIG_FILE_NAME: &str = "gltf-ktxer.toml";
+
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    pub no_color: Option<bool>,
+    pub error_format: Option<String>,
+    pub fail_on: Option<String>,
+    pub backup: Option<bool>,
+    pub preset: Option<String>,
+    pub codec: Option<String>,
+    pub mipmaps: Option<bool>,
+    pub max_size: Option<u32>,
+    pub linear: Option<bool>,
+    /// One or more target names, e.g. `target = ["webgl2", "vulkan"]`.
+    pub target: Option<Vec<String>>,
+    /// A size like `"2G"`, see [crate::memory::ByteSize].
+    pub max_memory: Option<String>,
+    pub json_pretty: Option<bool>,
+    pub glb_overflow: Option<String>,
+    pub dedup_textures: Option<bool>,
+    pub slim_json: Option<bool>,
+    /// Patterns for `extras` keys kept by `slim-json`, see [crate::slim::SlimOptions::keep_extras].
+    pub keep_extras: Option<Vec<String>>,
+    pub strip_names: Option<bool>,
+    /// Patterns for references in vendor extensions, see [crate::edit::CustomReference].
+    pub references: Option<Vec<String>>,
+    /// Patterns for references whose objects are always kept, see [crate::edit::ReferenceRegistry::register_root].
+    pub gc_roots: Option<Vec<String>>,
+    /// One or more of `"gzip"` and `"brotli"`, see [crate::precompress].
+    pub precompress: Option<Vec<String>>,
+    pub brotli_command: Option<String>,
+    pub external_validate: Option<bool>,
+    /// The command running glTF-Validator, see [crate::external_validate::run_validator].
+    pub external_validator: Option<String>,
+    /// The encode cache directory, see [crate::disk_cache].
+    pub cache_dir: Option<String>,
+    /// Seconds, see [crate::Params::max_encode_seconds_per_texture].
+    pub max_encode_seconds_per_texture: Option<f64>,
+    /// "fast", "balanced" or "thorough", see [crate::tuning::EncodeEffort].
+    pub effort: Option<String>,
+    pub threads: Option<std::num::NonZeroUsize>,
+    #[serde(default)]
+    pub textures: BTreeMap<String, TextureConfig>,
+    #[serde(default)]
+    pub groups: BTreeMap<String, GroupConfig>,
+    #[serde(default)]
+    pub semantic_names: Vec<SemanticNameConfig>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct TextureConfig {
+    /// The image is an atlas with this tile grid, e.g. "4x4".
+    pub atlas: Option<String>,
+    /// Dither before ETC1S encoding: "ordered" or "blue-noise".
+    pub dither: Option<String>,
+    /// Color adjustments applied before encoding, see [crate::adjust::ColorAdjustments].
+    pub exposure: Option<f32>,
+    pub gamma: Option<f32>,
+    pub saturation: Option<f32>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct GroupConfig {
+    #[serde(default)]
+    pub materials: Vec<String>,
+    #[serde(default)]
+    pub textures: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct SemanticNameConfig {
+    pub pattern: String,
+    /// A [crate::semantic::Semantic], e.g. "normal" or "base-color".
+    pub semantic: String,
+}
+
+impl Config {
+    pub fn parse(text: &str) -> Result<Self> {
+        toml::from_str(text).map_err(|e| Error::BadConfig(e.to_string()))
+    }
+
+    /// The [Config::textures] overrides, checking their values.
+    pub fn texture_overrides(&self) -> Result<TextureOverrides> {
+        let mut overrides = TextureOverrides::default();
+        for (key, texture) in &self.textures {
+            let atlas = texture.atlas.as_deref().map(str::parse).transpose().map_err(|e| Error::BadConfig(format!("textures.{key}.atlas: {e}")))?;
+            let dither = texture.dither.as_deref().map(str::parse).transpose().map_err(|e| Error::BadConfig(format!("textures.{key}.dither: {e}")))?;
+            let adjustments = (texture.exposure.is_some() || texture.gamma.is_some() || texture.saturation.is_some()).then(|| {
+                let defaults = ColorAdjustments::default();
+                ColorAdjustments {
+                    exposure: texture.exposure.unwrap_or(defaults.exposure),
+                    gamma: texture.gamma.unwrap_or(defaults.gamma),
+                    saturation: texture.saturation.unwrap_or(defaults.saturation),
+                }
+            });
+            if let Some(problem) = adjustments.and_then(|adjustments| adjustments.check()) {
+                return Err(Error::BadConfig(format!("textures.{key}: {problem}")));
+            }
+            overrides.insert(key, TextureOverride { atlas, dither, adjustments });
+        }
+        Ok(overrides)
+    }
+
+    /// The [Config::groups], in name order.
+    pub fn texture_groups(&self) -> Vec<TextureGroup> {
+        self.groups
+            .iter()
+            .map(|(name, group)| TextureGroup { name: name.clone(), materials: group.materials.clone(), textures: group.textures.clone() })
+            .collect()
+    }
+
+    /// The [Config::semantic_names], in order, checking their semantics.
+    pub fn name_rules(&self) -> Result<Vec<NameRule>> {
+        self.semantic_names
+            .iter()
+            .enumerate()
+            .map(|(idx, rule)| {
+                let semantic = rule.semantic.parse().map_err(|e| Error::BadConfig(format!("semantic-names[{idx}].semantic: {e}")))?;
+                Ok(NameRule::new(&rule.pattern, semantic))
+            })
+            .collect()
+    }
+
+    /// Load [CONFIG_FILE_NAME] from `dir`, or return None if there isn't one.
+    pub fn discover(dir: &Path) -> Result<Option<Self>> {
+        match std::fs::read_to_string(dir.join(CONFIG_FILE_NAME)) {
+            Ok(text) => Self::parse(&text).map(Some),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
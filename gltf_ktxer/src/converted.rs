@@ -0,0 +1,118 @@
+//! Reading back documents this crate has converted, for tooling which inspects or repackages the KTX2 textures in them.
+//!
+//! [ConvertedAsset::open] indexes every texture with a `KHR_texture_basisu` image, parsing each KTX2 header once,
+//! and [ConvertedAsset::ktx2_bytes] extracts a texture's payload for writing out or handing to a transcoder.
+
+use std::{collections::BTreeSet, path::Path};
+
+use crate::{
+    edit,
+    gltf::{deserialize_list, GltfBuffer, GltfBufferView, GltfDoc, GltfImage, GltfIndex, GltfList, GltfTexture, U8VecOrSlice},
+    ktx2::{self, ColorSpace, Ktx2Texture},
+    load::{self, LoadedGltf},
+    semantic::{Semantic, SlotRegistry},
+    KtxCodec, Result,
+};
+
+/// What a converted texture's KTX2 image holds, read from its header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConvertedTexture {
+    pub texture: GltfIndex<GltfTexture>,
+    /// The KTX2 image, the `source` of the texture's `KHR_texture_basisu`.
+    pub image: GltfIndex<GltfImage>,
+    pub name: Option<String>,
+    /// None if the image isn't Basis Universal, e.g. uncompressed RGBA8.
+    pub codec: Option<KtxCodec>,
+    pub color_space: ColorSpace,
+    pub width: u32,
+    pub height: u32,
+    pub level_count: u32,
+    /// The length of the KTX2 file.
+    pub byte_length: usize,
+    /// The semantics the texture is used with, from the default [SlotRegistry].
+    pub semantics: BTreeSet<Semantic>,
+}
+
+/// A converted `.glb` or `.gltf` file, with its textures indexed.
+pub struct ConvertedAsset {
+    loaded: LoadedGltf,
+    textures: Vec<ConvertedTexture>,
+}
+impl ConvertedAsset {
+    /// Open a converted `.glb` or `.gltf` file, loading it with [load::load_gltf].
+    pub fn open(path: &Path) -> Result<Self> {
+        Self::from_loaded(load::load_gltf(path)?)
+    }
+
+    /// Index an already loaded document.
+    pub fn from_loaded(loaded: LoadedGltf) -> Result<Self> {
+        let texture_list: Vec<GltfTexture> = deserialize_list(&loaded.doc, "textures")?;
+        let semantics = SlotRegistry::default().texture_semantics(&loaded.doc)?;
+        let mut textures = vec![];
+        for (tex_idx, texture) in texture_list.iter().enumerate() {
+            let Some(image) = edit::texture_ktx_source(texture) else {
+                continue;
+            };
+            let data = image_data(&loaded, image).map_err(|e| e.at(format!("/textures/{tex_idx}")))?;
+            let ktx = Ktx2Texture::from_bytes(&data).map_err(|e| e.at(format!("/images/{}", image.raw_idx())))?;
+            textures.push(ConvertedTexture {
+                texture: GltfIndex::of(tex_idx),
+                image,
+                name: texture.name.clone(),
+                codec: match ktx.dfd_color_model() {
+                    Some(ktx2::KHR_DF_MODEL_ETC1S) => Some(KtxCodec::Etc1s),
+                    Some(ktx2::KHR_DF_MODEL_UASTC) => Some(KtxCodec::Uastc),
+                    _ => None,
+                },
+                color_space: match ktx.dfd_transfer_function() {
+                    Some(ktx2::KHR_DF_TRANSFER_SRGB) => ColorSpace::Srgb,
+                    _ => ColorSpace::Linear,
+                },
+                width: ktx.pixel_width,
+                height: ktx.pixel_height,
+                level_count: ktx.levels.len() as u32,
+                byte_length: data.len(),
+                semantics: semantics.get(&GltfIndex::of(tex_idx)).cloned().unwrap_or_default(),
+            });
+        }
+        Ok(Self { loaded, textures })
+    }
+
+    pub fn doc(&self) -> &GltfDoc {
+        &self.loaded.doc
+    }
+
+    /// Every texture with a KTX2 image, in document order.
+    pub fn textures(&self) -> &[ConvertedTexture] {
+        &self.textures
+    }
+
+    /// The texture at `texture`, if it has a KTX2 image.
+    pub fn texture(&self, texture: GltfIndex<GltfTexture>) -> Option<&ConvertedTexture> {
+        self.textures.iter().find(|converted| converted.texture == texture)
+    }
+
+    /// The KTX2 file of `texture`, exactly as stored in the document.
+    pub fn ktx2_bytes(&self, texture: &ConvertedTexture) -> Result<Vec<u8>> {
+        image_data(&self.loaded, texture.image)
+    }
+
+    /// The KTX2 file of `texture`, parsed.
+    pub fn ktx2(&self, texture: &ConvertedTexture) -> Result<Ktx2Texture> {
+        Ktx2Texture::from_bytes(&self.ktx2_bytes(texture)?)
+    }
+}
+
+/// The data of the image at `image`, wherever it's stored.
+fn image_data(loaded: &LoadedGltf, image: GltfIndex<GltfImage>) -> Result<Vec<u8>> {
+    let images: Vec<GltfImage> = deserialize_list(&loaded.doc, "images")?;
+    let buffer_views: Vec<GltfBufferView> = deserialize_list(&loaded.doc, "bufferViews")?;
+    let buffers: Vec<GltfBuffer> = deserialize_list(&loaded.doc, "buffers")?;
+    let buffer_datas: Vec<U8VecOrSlice<'_>> = buffers
+        .into_iter()
+        .enumerate()
+        .map(|(idx, b)| b.dump_data(idx, &loaded.binaries))
+        .collect::<Result<_>>()?;
+    let data = images.gltf_index_required(image, "images")?.dump_data(&buffer_views, &buffer_datas, &loaded.binaries)?;
+    Ok(data.to_vec())
+}
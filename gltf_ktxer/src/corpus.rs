@@ -0,0 +1,102 @@
+use std::path::{Path, PathBuf};
+
+use crate::{execute_reencode_jobs, get_reencode_jobs, load::load_gltf, validate::{check_images_decode, check_output_alignment, validate}, Params, Result};
+
+/// Extensions which, if required by an asset, don't stop us from converting it.
+const SUPPORTED_REQUIRED_EXTENSIONS: &[&str] = &[
+    "KHR_texture_basisu",
+    "EXT_texture_webp",
+    #[cfg(feature = "avif")]
+    "EXT_texture_avif",
+];
+
+pub enum AssetOutcome {
+    Passed,
+    /// The asset requires an extension we don't know how to handle.
+    Skipped { unsupported_extension: String },
+    Failed(crate::Error),
+}
+
+pub struct CorpusReport {
+    pub assets: Vec<(PathBuf, AssetOutcome)>,
+}
+impl CorpusReport {
+    pub fn num_passed(&self) -> usize {
+        self.assets.iter().filter(|(_, outcome)| matches!(outcome, AssetOutcome::Passed)).count()
+    }
+    pub fn failures(&self) -> impl Iterator<Item = (&Path, &crate::Error)> {
+        self.assets.iter().filter_map(|(path, outcome)| match outcome {
+            AssetOutcome::Failed(e) => Some((path.as_path(), e)),
+            _ => None,
+        })
+    }
+}
+
+/// Convert every `.gltf` and `.glb` file under `dir` (recursively), then validate the output, check its buffer views are aligned
+/// (see [check_output_alignment]) and that all its images decode,
+/// KTX2 ones included, see [check_images_decode].
+/// With the `schema` feature, outputs are also checked against the glTF JSON schema.
+///
+/// This is the harness behind `cargo test -- --ignored corpus`, and can be pointed at any directory of assets.
+pub fn run_corpus(dir: &Path) -> Result<CorpusReport> {
+    let mut paths = vec![];
+    find_assets(dir, &mut paths)?;
+    paths.sort();
+
+    let assets = paths
+        .into_iter()
+        .map(|path| {
+            let outcome = roundtrip_asset(&path);
+            (path, outcome)
+        })
+        .collect();
+    Ok(CorpusReport { assets })
+}
+
+pub fn roundtrip_asset(path: &Path) -> AssetOutcome {
+    let mut loaded = match load_gltf(path) {
+        Ok(loaded) => loaded,
+        Err(e) => return AssetOutcome::Failed(e),
+    };
+
+    let required = loaded.doc.get("extensionsRequired").and_then(|val| val.as_array());
+    if let Some(unsupported) = required
+        .into_iter()
+        .flatten()
+        .filter_map(|ext| ext.as_str())
+        .find(|ext| !SUPPORTED_REQUIRED_EXTENSIONS.contains(ext))
+    {
+        return AssetOutcome::Skipped { unsupported_extension: unsupported.to_string() };
+    }
+
+    let result = (|| {
+        let mut jobs = get_reencode_jobs(loaded.input(), Params::default())?;
+        let output = execute_reencode_jobs(&mut jobs, &mut loaded.doc, loaded.binaries.clone(), &Params::default())?;
+
+        // Images with external URIs aren't packed, so they still need to be resolved from the original files.
+        let mut binaries = loaded.binaries.clone();
+        binaries.insert(None, output.binary);
+        binaries.extend(output.external_binaries.into_iter().map(|(uri, data)| (Some(uri), data)));
+        validate(&output.gltf_json, &binaries)?;
+        check_output_alignment(&output.gltf_json)?;
+        #[cfg(feature = "schema")]
+        crate::schema::validate_schema(&output.gltf_json)?;
+        check_images_decode(&output.gltf_json, &binaries)
+    })();
+    match result {
+        Ok(()) => AssetOutcome::Passed,
+        Err(e) => AssetOutcome::Failed(e),
+    }
+}
+
+fn find_assets(dir: &Path, paths: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_assets(&path, paths)?;
+        } else if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("gltf") || ext.eq_ignore_ascii_case("glb")) {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,48 @@
+//! Per-texture decisions made by the embedding application, see [crate::Params::decision_hook].
+//!
+//! Static settings like [crate::overrides::TextureOverrides] match textures by name. A hook instead sees each texture as it's planned,
+//! with its names, how materials use it, and statistics of its image, and can pick its codec and quality or leave it alone.
+
+use std::{collections::BTreeSet, num::NonZeroU8};
+
+use crate::{gltf::{GltfImage, GltfIndex, GltfTexture}, hints::ConversionHints, semantic::Semantic, stats::ImageStats, usage::TextureUsage, KtxCodec, Result, SourceImage};
+
+/// What a hook is told about a texture.
+pub struct TextureContext<'a> {
+    pub texture: GltfIndex<GltfTexture>,
+    pub texture_name: Option<&'a str>,
+    /// The image the texture is planned from.
+    pub image: GltfIndex<GltfImage>,
+    pub image_name: Option<&'a str>,
+    /// The image's URI, unless it's a data URI or the image is in a buffer view.
+    pub image_uri: Option<&'a str>,
+    pub mime_type: &'a str,
+    /// How materials use the texture. Empty if no material does.
+    pub semantics: &'a BTreeSet<Semantic>,
+    /// How materials sample the texture, see [crate::usage]. Empty if no material does.
+    pub usages: &'a BTreeSet<TextureUsage>,
+    pub data_used_as_srgb: bool,
+    /// The texture's conversion hints, including [crate::Params::override_hints], see [crate::hints].
+    pub hints: ConversionHints,
+    pub(crate) source: &'a SourceImage,
+}
+impl TextureContext<'_> {
+    /// Statistics of the texture's image, see [SourceImage::stats].
+    pub fn stats(&self) -> Result<ImageStats> {
+        self.source.stats()
+    }
+}
+
+/// What a hook decided for a texture. The default changes nothing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TextureDecision {
+    /// Use this codec instead of [crate::Params::ktx_codec].
+    pub codec: Option<KtxCodec>,
+    /// Use this quality instead of [crate::Params::ktx_basis_compression_quality].
+    pub quality: Option<NonZeroU8>,
+    /// Leave the texture using its source image unchanged.
+    pub skip: bool,
+}
+
+/// A hook deciding how each texture is encoded. Textures sharing an image get the decision made for the first of them.
+pub type DecisionHook = Box<dyn Fn(&TextureContext) -> TextureDecision + Send + Sync>;
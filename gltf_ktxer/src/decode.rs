@@ -0,0 +1,171 @@
+//! Pluggable decoders for source images.
+//!
+//! The `image` crate handles every format, but optional backends can take over formats where decode time dominates.
+//! With the `zune-jpeg` feature, JPEGs are decoded by calling zune-jpeg directly, straight to RGBA8,
+//! instead of through the `image` crate's generic decoder and a separate RGB to RGBA conversion.
+//!
+//! When the output will be downscaled anyway, [ImageDecoder::decode_within] may return a smaller image. Non-interlaced PNGs
+//! are decoded a row at a time and box filtered on the fly by [decode_png_within], so a huge source never needs a full
+//! resolution buffer.
+//!
+//! Decoders return texels as stored. EXIF orientation is read separately with [exif_orientation],
+//! as Basis Universal and KTX2 have no way to carry it, so it has to be applied before encoding.
+
+use image::{metadata::Orientation, DynamicImage, ImageFormat, RgbaImage};
+
+use crate::Result;
+
+/// Decodes encoded image bytes to RGBA8.
+pub trait ImageDecoder: Send + Sync {
+    /// Decode `data`. `format` is taken from the image's MIME type if known, otherwise the decoder should guess from the data.
+    fn decode(&self, data: &[u8], format: Option<ImageFormat>) -> Result<RgbaImage>;
+    /// Decode `data` for an output which fits within `max_dimension`. The result may be downscaled by a power of two,
+    /// but never so far that its longer side drops below `max_dimension`. By default this is [ImageDecoder::decode].
+    fn decode_within(&self, data: &[u8], format: Option<ImageFormat>, max_dimension: u32) -> Result<RgbaImage> {
+        let _ = max_dimension;
+        self.decode(data, format)
+    }
+}
+
+/// Decodes every format the `image` crate supports.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ImageCrateDecoder;
+impl ImageDecoder for ImageCrateDecoder {
+    fn decode(&self, data: &[u8], format: Option<ImageFormat>) -> Result<RgbaImage> {
+        let image = match format {
+            Some(format) => image::load_from_memory_with_format(data, format)?,
+            None => image::load_from_memory(data)?,
+        };
+        Ok(image.into_rgba8())
+    }
+    fn decode_within(&self, data: &[u8], format: Option<ImageFormat>, max_dimension: u32) -> Result<RgbaImage> {
+        match format.or_else(|| image::guess_format(data).ok()) {
+            Some(ImageFormat::Png) => decode_png_within(data, max_dimension),
+            format => self.decode(data, format),
+        }
+    }
+}
+
+/// Decodes JPEGs with zune-jpeg, and everything else with [ImageCrateDecoder].
+#[cfg(feature = "zune-jpeg")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ZuneJpegDecoder;
+#[cfg(feature = "zune-jpeg")]
+impl ImageDecoder for ZuneJpegDecoder {
+    fn decode(&self, data: &[u8], format: Option<ImageFormat>) -> Result<RgbaImage> {
+        use zune_jpeg::zune_core::{colorspace::ColorSpace, options::DecoderOptions};
+
+        let format = format.or_else(|| image::guess_format(data).ok());
+        if format != Some(ImageFormat::Jpeg) {
+            return ImageCrateDecoder.decode(data, format);
+        }
+        let to_image_error = |e: zune_jpeg::errors::DecodeErrors| {
+            image::ImageError::Decoding(image::error::DecodingError::new(ImageFormat::Jpeg.into(), e))
+        };
+        let mut decoder = zune_jpeg::JpegDecoder::new_with_options(data, DecoderOptions::default().jpeg_set_out_colorspace(ColorSpace::RGBA));
+        let pixels = decoder.decode().map_err(to_image_error)?;
+        let (width, height) = decoder.dimensions().expect("headers are decoded by decode()");
+        Ok(RgbaImage::from_raw(width as u32, height as u32, pixels).expect("zune-jpeg output is width * height RGBA pixels"))
+    }
+    fn decode_within(&self, data: &[u8], format: Option<ImageFormat>, max_dimension: u32) -> Result<RgbaImage> {
+        match format.or_else(|| image::guess_format(data).ok()) {
+            Some(ImageFormat::Jpeg) => self.decode(data, Some(ImageFormat::Jpeg)),
+            format => ImageCrateDecoder.decode_within(data, format, max_dimension),
+        }
+    }
+}
+
+/// The fastest decoder enabled by the crate features.
+pub fn default_decoder() -> &'static dyn ImageDecoder {
+    #[cfg(feature = "zune-jpeg")]
+    return &ZuneJpegDecoder;
+    #[cfg(not(feature = "zune-jpeg"))]
+    return &ImageCrateDecoder;
+}
+
+/// The largest power of two an image of `dimensions` can be divided by while its longer side stays at least `max_dimension`.
+pub fn downscale_factor((width, height): (u32, u32), max_dimension: u32) -> u32 {
+    let longest = width.max(height);
+    let mut factor = 1;
+    while factor < 1 << 30 && longest / (factor * 2) >= max_dimension.max(1) {
+        factor *= 2;
+    }
+    factor
+}
+
+/// Decode a PNG downscaled by [downscale_factor], reading one row at a time and summing each row into the output texels it
+/// covers, so memory use is that of the output rather than the source. Each output texel averages a `factor` x `factor`
+/// block, with the leftover rows and columns of an odd size folded into the last ones. Interlaced PNGs, whose rows
+/// arrive out of order, and PNGs which don't need downscaling are decoded whole with [ImageCrateDecoder].
+pub fn decode_png_within(data: &[u8], max_dimension: u32) -> Result<RgbaImage> {
+    let to_image_error = |e: png::DecodingError| image::ImageError::Decoding(image::error::DecodingError::new(ImageFormat::Png.into(), e));
+    let mut decoder = png::Decoder::new(std::io::Cursor::new(data));
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let mut reader = decoder.read_info().map_err(to_image_error)?;
+    let info = reader.info();
+    let (width, height) = (info.width, info.height);
+    let factor = downscale_factor((width, height), max_dimension);
+    if info.interlaced || factor == 1 {
+        return ImageCrateDecoder.decode(data, Some(ImageFormat::Png));
+    }
+    let (color_type, bit_depth) = reader.output_color_type();
+    let channels = color_type.samples();
+    let sixteen_bit = bit_depth == png::BitDepth::Sixteen;
+    let (out_width, out_height) = ((width / factor).max(1), (height / factor).max(1));
+    // The number of source texels along one axis which land in output texel `i`
+    let span = |i: u32, size: u32, out_size: u32| if i + 1 == out_size { size - i * factor } else { factor } as u64;
+
+    let mut image = RgbaImage::new(out_width, out_height);
+    let mut sums = vec![0u64; out_width as usize * 4];
+    for y in 0..height {
+        let row = reader.next_row().map_err(to_image_error)?.expect("a non-interlaced PNG has height rows");
+        let row = row.data();
+        for x in 0..width {
+            let texel = x as usize * channels;
+            let sample = |c: usize| if sixteen_bit {
+                (u16::from_be_bytes([row[(texel + c) * 2], row[(texel + c) * 2 + 1]]) as u32 + 128) / 257
+            } else {
+                row[texel + c] as u32
+            } as u64;
+            let rgba = match channels {
+                1 => [sample(0), sample(0), sample(0), 255],
+                2 => [sample(0), sample(0), sample(0), sample(1)],
+                3 => [sample(0), sample(1), sample(2), 255],
+                _ => [sample(0), sample(1), sample(2), sample(3)],
+            };
+            let out_x = (x / factor).min(out_width - 1) as usize;
+            for (sum, value) in sums[out_x * 4..out_x * 4 + 4].iter_mut().zip(rgba) {
+                *sum += value;
+            }
+        }
+        let out_y = (y / factor).min(out_height - 1);
+        if y + 1 == height || ((y + 1) / factor).min(out_height - 1) != out_y {
+            for (out_x, sum) in sums.chunks_exact_mut(4).enumerate() {
+                let count = span(out_x as u32, width, out_width) * span(out_y, height, out_height);
+                image.put_pixel(out_x as u32, out_y, image::Rgba(std::array::from_fn(|c| ((sum[c] + count / 2) / count) as u8)));
+                sum.fill(0);
+            }
+        }
+    }
+    Ok(image)
+}
+
+/// The EXIF orientation of the encoded image `data`, if it has one other than the identity.
+/// The `image` crate reads it from JPEG, WebP and TIFF.
+pub fn exif_orientation(data: &[u8]) -> Option<Orientation> {
+    let reader = image::ImageReader::new(std::io::Cursor::new(data)).with_guessed_format().ok()?;
+    let mut decoder = reader.into_decoder().ok()?;
+    image::ImageDecoder::orientation(&mut decoder).ok().filter(|&orientation| orientation != Orientation::NoTransforms)
+}
+
+/// Whether applying `orientation` swaps the width and height of an image.
+pub fn swaps_dimensions(orientation: Orientation) -> bool {
+    matches!(orientation, Orientation::Rotate90 | Orientation::Rotate270 | Orientation::Rotate90FlipH | Orientation::Rotate270FlipH)
+}
+
+/// Rotate and flip `image` to its canonical orientation.
+pub fn apply_orientation(image: RgbaImage, orientation: Orientation) -> RgbaImage {
+    let mut image = DynamicImage::ImageRgba8(image);
+    image.apply_orientation(orientation);
+    image.into_rgba8()
+}
@@ -0,0 +1,155 @@
+//! Sharing one buffer view between images whose data is byte-identical, which happens when the same texture is exported
+//! under several names, or several textures are replaced by the same placeholder.
+//!
+//! This runs on the views themselves rather than on source images, so it also catches different sources which encode to the same KTX2 file.
+//!
+//! [dedup_samplers_and_textures] does the same for the sampler and texture tables, as many exporters write one sampler per texture.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use serde_json::Value;
+
+use crate::{edit::{self, ReferenceRegistry}, gltf::{deserialize_list, GltfBuffer, GltfBufferView, GltfDoc, GltfImage, GltfIndex, GltfSampler, GltfTexture, U8VecOrSlice}, hash, Result};
+
+/// What [dedup_image_views] merged.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DedupStats {
+    /// Images pointed at another image's view.
+    pub images_repointed: usize,
+    /// Views removed as they held the same bytes as another view.
+    pub views_removed: usize,
+    /// The total length of the removed views.
+    pub bytes_saved: usize,
+}
+
+/// Point every image whose buffer view holds the same bytes as an earlier image's at the earlier view, and remove the views left unused.
+/// Views which anything but images refer to are left alone, so accessor data never ends up shared by accident.
+///
+/// Removing views renumbers the rest, so references in vendor extensions must be registered in `registry`.
+pub fn dedup_image_views(doc: &mut GltfDoc, binaries: &HashMap<Option<String>, Vec<u8>>, registry: &ReferenceRegistry) -> Result<DedupStats> {
+    let buffers: Vec<GltfBuffer> = deserialize_list(doc, "buffers")?;
+    let views: Vec<GltfBufferView> = deserialize_list(doc, "bufferViews")?;
+    let images: Vec<GltfImage> = deserialize_list(doc, "images")?;
+
+    let mut not_only_images = BTreeSet::new();
+    edit::for_each_reference_by_holder(doc, "bufferViews", registry, &mut |holder, reference| {
+        if holder.is_none_or(|(list_name, _)| list_name != "images") {
+            not_only_images.extend(reference.as_u64().map(|idx| idx as usize));
+        }
+    })?;
+
+    let buffer_datas: Vec<U8VecOrSlice<'_>> = buffers
+        .iter()
+        .enumerate()
+        .map(|(idx, buffer)| buffer.dump_data(idx, binaries))
+        .collect::<Result<_>>()?;
+
+    // The views kept for each hash, and what each duplicate view is replaced with
+    let mut kept: HashMap<[u8; 32], Vec<usize>> = HashMap::new();
+    let mut replacements: BTreeMap<usize, usize> = BTreeMap::new();
+    for image in &images {
+        let view_idx = image.buffer_view.raw_idx();
+        if !image.buffer_view.is_defined() || not_only_images.contains(&view_idx) || replacements.contains_key(&view_idx) {
+            continue;
+        }
+        let Some(view) = views.get(view_idx) else {
+            continue;
+        };
+        let data = view.slice_from(&buffer_datas).map_err(|e| e.at(format!("/bufferViews/{view_idx}")))?;
+        let candidates = kept.entry(hash::sha256(data)).or_default();
+        if candidates.contains(&view_idx) {
+            continue;
+        }
+        // Hashes only say which views are worth comparing
+        let same = candidates.iter().copied().find(|&other| {
+            let other_view = &views[other];
+            other_view.byte_stride == view.byte_stride && other_view.target == view.target && other_view.slice_from(&buffer_datas).is_ok_and(|other| other == data)
+        });
+        match same {
+            Some(other) => {
+                replacements.insert(view_idx, other);
+            }
+            None => candidates.push(view_idx),
+        }
+    }
+
+    let mut stats = DedupStats::default();
+    if let Some(images) = doc.get_mut("images").and_then(Value::as_array_mut) {
+        for image in images {
+            let replacement = image.get("bufferView").and_then(Value::as_u64).and_then(|idx| replacements.get(&(idx as usize)));
+            if let Some(&replacement) = replacement {
+                image["bufferView"] = replacement.into();
+                stats.images_repointed += 1;
+            }
+        }
+    }
+    // From the back, so the indices of the remaining duplicates don't shift
+    for &view_idx in replacements.keys().rev() {
+        edit::remove_with::<GltfBufferView>(doc, "bufferViews", GltfIndex::of(view_idx), registry)?;
+        stats.views_removed += 1;
+        stats.bytes_saved += views[view_idx].byte_length;
+    }
+    Ok(stats)
+}
+
+/// What [dedup_samplers_and_textures] merged.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TableDedupStats {
+    pub samplers_removed: usize,
+    pub textures_removed: usize,
+}
+
+/// Merge samplers with the same settings, then textures with the same sources and sampler, renumbering the references to them.
+/// Names are ignored, and the first of each set of duplicates keeps its own. A sampler leaving a wrap mode unset is the same as one setting it to repeat.
+///
+/// Removing samplers and textures renumbers the rest, so references in vendor extensions must be registered in `registry`.
+pub fn dedup_samplers_and_textures(doc: &mut GltfDoc, registry: &ReferenceRegistry) -> Result<TableDedupStats> {
+    let samplers = duplicates(doc, "samplers", |sampler| {
+        for wrap in ["wrapS", "wrapT"] {
+            sampler.entry(wrap).or_insert(GltfSampler::WRAP_REPEAT.into());
+        }
+    });
+    merge::<GltfSampler>(doc, "samplers", &samplers, registry)?;
+    // Textures differing only in their sampler may have become duplicates
+    let textures = duplicates(doc, "textures", |_| {});
+    merge::<GltfTexture>(doc, "textures", &textures, registry)?;
+    Ok(TableDedupStats { samplers_removed: samplers.len(), textures_removed: textures.len() })
+}
+
+/// The index of the first earlier element each element of `list_name` is a duplicate of,
+/// comparing them without their names once `normalize` has filled in any defaults.
+fn duplicates(doc: &GltfDoc, list_name: &str, normalize: impl Fn(&mut serde_json::Map<String, Value>)) -> BTreeMap<usize, usize> {
+    let list = doc.get(list_name).and_then(Value::as_array).map(Vec::as_slice).unwrap_or_default();
+    let mut first_with_key: HashMap<Value, usize> = HashMap::new();
+    let mut replacements = BTreeMap::new();
+    for (idx, item) in list.iter().enumerate() {
+        let Some(mut item) = item.as_object().cloned() else {
+            continue;
+        };
+        item.remove("name");
+        normalize(&mut item);
+        match first_with_key.entry(Value::Object(item)) {
+            std::collections::hash_map::Entry::Occupied(first) => {
+                replacements.insert(idx, *first.get());
+            }
+            std::collections::hash_map::Entry::Vacant(slot) => {
+                slot.insert(idx);
+            }
+        }
+    }
+    replacements
+}
+
+/// Point every reference to a key of `replacements` at its value, then remove the elements left unused.
+fn merge<T>(doc: &mut GltfDoc, list_name: &'static str, replacements: &BTreeMap<usize, usize>, registry: &ReferenceRegistry) -> Result<()> {
+    edit::for_each_reference(doc, list_name, registry, &mut |reference| {
+        if let Some(&replacement) = reference.as_u64().and_then(|idx| replacements.get(&(idx as usize))) {
+            *reference = replacement.into();
+        }
+    })?;
+    // From the back, so the indices of the remaining duplicates don't shift
+    for &idx in replacements.keys().rev() {
+        edit::remove_with::<T>(doc, list_name, GltfIndex::of(idx), registry)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,38 @@
+//! Make-style dependency files listing every file an output was made from, for build systems' incremental rebuilds.
+//!
+//! A depfile has one rule per output, e.g.
+//! ```make
+//! out/scene.glb: scene.gltf scene.bin textures/wood.png gltf-ktxer.toml
+//! ```
+//! which Make can `-include` and Ninja and Bazel read through their `depfile` support,
+//! so changing any referenced texture rebuilds the model without listing textures in the build rules.
+
+use std::path::{Path, PathBuf};
+
+use crate::{gltf::GltfDoc, load};
+
+/// Escape `path` for a depfile: spaces and `#` are backslash-escaped, and `$` is doubled.
+fn escape(path: &Path) -> String {
+    let mut escaped = String::new();
+    for c in path.to_string_lossy().chars() {
+        match c {
+            ' ' | '#' => escaped.extend(['\\', c]),
+            '$' => escaped.push_str("$$"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// A depfile with a rule for each of `outputs`, depending on every one of `inputs`.
+pub fn render(outputs: &[PathBuf], inputs: &[PathBuf]) -> String {
+    let inputs: String = inputs.iter().map(|input| format!(" {}", escape(input))).collect();
+    outputs.iter().map(|output| format!("{}:{inputs}\n", escape(output))).collect()
+}
+
+/// The `.gltf` file at `path` and every external buffer and image it references.
+pub fn gltf_inputs(path: &Path, doc: &GltfDoc) -> Vec<PathBuf> {
+    let mut inputs = vec![path.to_path_buf()];
+    inputs.extend(load::referenced_files(path, doc).into_iter().map(|(_, file)| file));
+    inputs
+}
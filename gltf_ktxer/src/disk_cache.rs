@@ -0,0 +1,275 @@
+//! An encode cache kept in a directory, so runs and CI jobs sharing the directory reuse each other's encodes.
+//! Add it under an [crate::cache::EncodeCache] with [crate::cache::EncodeCache::with_disk_cache].
+//!
+//! Any number of processes may use the same directory at once:
+//! - entries are written to a temporary file and renamed into place (see [crate::output::write_atomic]),
+//!   so readers see either no entry or a whole one
+//! - an advisory lock on `<entry>.lock` is held while encoding, so only one process encodes each entry,
+//!   and the others wait for it and then read its result
+//! - each entry starts with the SHA-256 of its data, and entries which don't match, e.g. after a disk filled up
+//!   or another tool wrote to the directory, are treated as missing and replaced
+//!
+//! Lock files are left behind while their entries exist, as removing one while another process waits on it would let
+//! two processes encode at once.
+//!
+//! Reading an entry updates its modification time, so [DiskCache::prune] removes the least recently used entries first.
+
+use std::{
+    fmt::Display,
+    fs::File,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, SystemTime},
+};
+
+use crate::{cache::EncodeCacheKey, hash, output::write_atomic, Result};
+
+/// How long a temporary file must have been left for [DiskCache::prune] to assume its writer died.
+const ABANDONED_TEMP_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// The start of every entry, changed whenever the entry format is.
+const ENTRY_MAGIC: &[u8; 8] = b"gktxc\x00\x00\x01";
+
+/// A directory of encoded images, keyed by [EncodeCacheKey].
+#[derive(Debug)]
+pub struct DiskCache {
+    dir: PathBuf,
+    corrupt: AtomicUsize,
+}
+impl DiskCache {
+    /// Use `dir` as a cache, creating it if needed.
+    pub fn open(dir: &Path) -> Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        Ok(Self { dir: dir.to_path_buf(), corrupt: AtomicUsize::new(0) })
+    }
+
+    /// Where the entry for `key` is stored: named by the hash of the key, in a subdirectory named by its first byte
+    /// so no one directory gets too big.
+    pub fn entry_path(&self, key: &EncodeCacheKey) -> PathBuf {
+        let mut key_bytes = key.source_sha256.to_vec();
+        key_bytes.extend_from_slice(key.encode_params_key.as_bytes());
+        let name = hash::hex(&hash::sha256(&key_bytes));
+        self.dir.join(&name[..2]).join(format!("{name}.entry"))
+    }
+
+    /// The data stored for `key`, if there is an intact entry for it. Damaged entries are deleted.
+    pub fn get(&self, key: &EncodeCacheKey) -> Result<Option<Vec<u8>>> {
+        let path = self.entry_path(key);
+        let entry = match std::fs::read(&path) {
+            Ok(entry) => entry,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        match read_entry(&entry) {
+            Some(data) => {
+                // Best effort, as another process may be replacing or pruning the entry
+                let _ = File::options().write(true).open(&path).and_then(|file| file.set_modified(SystemTime::now()));
+                Ok(Some(data.to_vec()))
+            }
+            None => {
+                self.corrupt.fetch_add(1, Ordering::Relaxed);
+                let _ = std::fs::remove_file(&path);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Store `data` for `key`, replacing any existing entry.
+    pub fn put(&self, key: &EncodeCacheKey, data: &[u8]) -> Result<()> {
+        let path = self.entry_path(key);
+        std::fs::create_dir_all(path.parent().expect("entries are in a subdirectory"))?;
+        let mut entry = Vec::with_capacity(ENTRY_MAGIC.len() + 32 + data.len());
+        entry.extend_from_slice(ENTRY_MAGIC);
+        entry.extend_from_slice(&hash::sha256(data));
+        entry.extend_from_slice(data);
+        write_atomic(&path, &entry, false)
+    }
+
+    /// The data stored for `key`, calling `encode` and storing its result if there is none.
+    /// Holds the entry's lock while encoding, so a process waiting on the same key reads this result instead of encoding again.
+    /// Failed encodes aren't stored.
+    pub fn get_or_encode(&self, key: &EncodeCacheKey, encode: impl FnOnce() -> Result<Vec<u8>>) -> Result<Vec<u8>> {
+        if let Some(data) = self.get(key)? {
+            return Ok(data);
+        }
+        let path = self.entry_path(key);
+        std::fs::create_dir_all(path.parent().expect("entries are in a subdirectory"))?;
+        let mut lock_path = path.into_os_string();
+        lock_path.push(".lock");
+        let lock = File::options().create(true).truncate(false).write(true).open(&lock_path)?;
+        lock.lock()?;
+        // Another process may have finished the entry while we waited
+        if let Some(data) = self.get(key)? {
+            return Ok(data);
+        }
+        let data = encode()?;
+        self.put(key, &data)?;
+        Ok(data)
+    }
+
+    /// The number of damaged entries found and deleted so far.
+    pub fn corrupt_entries(&self) -> usize {
+        self.corrupt.load(Ordering::Relaxed)
+    }
+
+    /// Every entry file in the cache, least recently used first.
+    fn entries(&self) -> Result<Vec<EntryFile>> {
+        let mut entries = vec![];
+        for subdir in std::fs::read_dir(&self.dir)? {
+            let subdir = subdir?;
+            if !subdir.file_type()?.is_dir() {
+                continue;
+            }
+            for file in std::fs::read_dir(subdir.path())? {
+                let file = file?;
+                let path = file.path();
+                // Files can disappear while listing, when other processes prune or replace them
+                let Ok(metadata) = file.metadata() else {
+                    continue;
+                };
+                let kind = match path.extension().and_then(|ext| ext.to_str()) {
+                    Some("entry") => EntryFileKind::Entry,
+                    Some("lock") => EntryFileKind::Lock,
+                    Some("tmp") => EntryFileKind::Temp,
+                    _ => continue,
+                };
+                entries.push(EntryFile { path, kind, len: metadata.len(), modified: metadata.modified()? });
+            }
+        }
+        entries.sort_by_key(|entry| entry.modified);
+        Ok(entries)
+    }
+
+    /// The number, size and age of the entries in the cache.
+    pub fn stats(&self) -> Result<DirStats> {
+        let entries: Vec<EntryFile> = self.entries()?.into_iter().filter(|entry| entry.kind == EntryFileKind::Entry).collect();
+        Ok(DirStats {
+            entries: entries.len(),
+            bytes: entries.iter().map(|entry| entry.len).sum(),
+            least_recently_used: entries.first().map(|entry| entry.modified),
+            most_recently_used: entries.last().map(|entry| entry.modified),
+        })
+    }
+
+    /// Delete entries last used more than `max_age` before `now`, then the least recently used entries until the rest
+    /// take up at most `max_bytes`. Temporary files abandoned by writers which died are deleted too,
+    /// along with the lock files of deleted entries which no process holds.
+    pub fn prune(&self, max_bytes: Option<u64>, max_age: Option<Duration>, now: SystemTime) -> Result<PruneStats> {
+        let files = self.entries()?;
+        let age = |file: &EntryFile| now.duration_since(file.modified).unwrap_or_default();
+        let mut kept_bytes: u64 = files.iter().filter(|file| file.kind == EntryFileKind::Entry).map(|file| file.len).sum();
+        let mut stats = PruneStats::default();
+        let mut removed_entries = vec![];
+        for file in &files {
+            match file.kind {
+                EntryFileKind::Entry => {
+                    let too_old = max_age.is_some_and(|max_age| age(file) > max_age);
+                    let too_big = max_bytes.is_some_and(|max_bytes| kept_bytes > max_bytes);
+                    if !(too_old || too_big) {
+                        stats.kept_entries += 1;
+                        continue;
+                    }
+                    match std::fs::remove_file(&file.path) {
+                        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                        _ => {}
+                    }
+                    kept_bytes -= file.len;
+                    stats.removed_entries += 1;
+                    stats.removed_bytes += file.len;
+                    removed_entries.push(file.path.clone());
+                }
+                EntryFileKind::Temp if age(file) > ABANDONED_TEMP_AGE => {
+                    if std::fs::remove_file(&file.path).is_ok() {
+                        stats.removed_temp_files += 1;
+                    }
+                }
+                EntryFileKind::Temp | EntryFileKind::Lock => {}
+            }
+        }
+        for entry in removed_entries {
+            let mut lock_path = entry.into_os_string();
+            lock_path.push(".lock");
+            // Held locks belong to processes encoding the entry again, which need the same lock file
+            if let Ok(lock) = File::options().write(true).open(&lock_path) {
+                if lock.try_lock().is_ok() {
+                    let _ = std::fs::remove_file(&lock_path);
+                }
+            }
+        }
+        stats.kept_bytes = kept_bytes;
+        Ok(stats)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EntryFileKind {
+    Entry,
+    Lock,
+    Temp,
+}
+
+struct EntryFile {
+    path: PathBuf,
+    kind: EntryFileKind,
+    len: u64,
+    modified: SystemTime,
+}
+
+/// What's in a cache directory, see [DiskCache::stats].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DirStats {
+    pub entries: usize,
+    /// The total size of the entries.
+    pub bytes: u64,
+    pub least_recently_used: Option<SystemTime>,
+    pub most_recently_used: Option<SystemTime>,
+}
+
+/// What [DiskCache::prune] deleted and kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PruneStats {
+    pub removed_entries: usize,
+    pub removed_bytes: u64,
+    pub removed_temp_files: usize,
+    pub kept_entries: usize,
+    pub kept_bytes: u64,
+}
+
+/// A length of time as given on the command line: a number followed by `s`, `m`, `h`, `d` or `w`, e.g. `30d`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Age(pub Duration);
+impl FromStr for Age {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let bad = || format!("bad age '{s}', expected a number followed by s, m, h, d or w, e.g. '30d'");
+        let digits = s.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+        let unit: u64 = match &s[digits.len()..] {
+            "s" => 1,
+            "m" => 60,
+            "h" => 60 * 60,
+            "d" => 24 * 60 * 60,
+            "w" => 7 * 24 * 60 * 60,
+            _ => return Err(bad()),
+        };
+        digits.parse::<u64>().ok().and_then(|count| count.checked_mul(unit)).map(|secs| Age(Duration::from_secs(secs))).ok_or_else(bad)
+    }
+}
+impl Display for Age {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let secs = self.0.as_secs();
+        let units = [("w", 7 * 24 * 60 * 60), ("d", 24 * 60 * 60), ("h", 60 * 60), ("m", 60)];
+        match units.into_iter().find(|&(_, unit)| secs >= unit && secs.is_multiple_of(unit)) {
+            Some((suffix, unit)) => write!(f, "{}{suffix}", secs / unit),
+            None => write!(f, "{secs}s"),
+        }
+    }
+}
+
+/// The data in `entry`, if it has the current format and its hash matches.
+fn read_entry(entry: &[u8]) -> Option<&[u8]> {
+    let rest = entry.strip_prefix(ENTRY_MAGIC)?;
+    let (expected, data) = rest.split_at_checked(32)?;
+    (hash::sha256(data) == expected).then_some(data)
+}
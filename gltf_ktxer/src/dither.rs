@@ -0,0 +1,72 @@
+//! Dithering, to break up the banding that coarse quantization leaves in smooth gradients such as skies and vignettes.
+//!
+//! ETC1S stores colors as endpoints with 5 bits per channel, so a gradient spanning a few dozen values collapses into visible bands.
+//! Adding up to half a quantization step of structured noise first makes the encoder alternate between neighbouring endpoints,
+//! which reads as the original gradient from a distance. Flat-color art only gets noisier, so dithering is opt-in per texture,
+//! see [crate::overrides::TextureOverride::dither].
+
+use image::RgbaImage;
+
+/// The bits per channel of ETC1S color endpoints.
+pub const ETC1S_ENDPOINT_BITS: u32 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Dither {
+    /// An 8x8 Bayer matrix. Cheap and stable, but leaves a visible cross-hatch pattern.
+    Ordered,
+    /// Interleaved gradient noise, which approximates blue noise without a precomputed texture.
+    /// Has no repeating pattern, and compresses a little worse than [Dither::Ordered].
+    BlueNoise,
+}
+impl std::str::FromStr for Dither {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "ordered" => Ok(Dither::Ordered),
+            "blue-noise" => Ok(Dither::BlueNoise),
+            _ => Err(format!("unknown dither '{s}', expected 'ordered' or 'blue-noise'")),
+        }
+    }
+}
+impl std::fmt::Display for Dither {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Dither::Ordered => "ordered",
+            Dither::BlueNoise => "blue-noise",
+        })
+    }
+}
+
+const BAYER_8X8: [[u8; 8]; 8] = [
+    [0, 32, 8, 40, 2, 34, 10, 42],
+    [48, 16, 56, 24, 50, 18, 58, 26],
+    [12, 44, 4, 36, 14, 46, 6, 38],
+    [60, 28, 52, 20, 62, 30, 54, 22],
+    [3, 35, 11, 43, 1, 33, 9, 41],
+    [51, 19, 59, 27, 49, 17, 57, 25],
+    [15, 47, 7, 39, 13, 45, 5, 37],
+    [63, 31, 55, 23, 61, 29, 53, 21],
+];
+
+impl Dither {
+    /// The threshold at texel (x, y), in [0, 1).
+    fn threshold(self, x: u32, y: u32) -> f32 {
+        match self {
+            Dither::Ordered => (BAYER_8X8[y as usize % 8][x as usize % 8] as f32 + 0.5) / 64.0,
+            // Jimenez 2014, "Next Generation Post Processing in Call of Duty: Advanced Warfare"
+            Dither::BlueNoise => (52.982918 * (0.06711056 * x as f32 + 0.00583715 * y as f32).fract()).fract(),
+        }
+    }
+
+    /// Dither the color channels of `image` for quantization to `bits` per channel. Alpha is left alone.
+    pub fn apply(self, image: &mut RgbaImage, bits: u32) {
+        let step = 255.0 / ((1u32 << bits) - 1) as f32;
+        for (x, y, texel) in image.enumerate_pixels_mut() {
+            let offset = (self.threshold(x, y) - 0.5) * step;
+            for channel in &mut texel.0[..3] {
+                *channel = (*channel as f32 + offset).round().clamp(0.0, 255.0) as u8;
+            }
+        }
+    }
+}
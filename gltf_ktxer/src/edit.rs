@@ -0,0 +1,564 @@
+//! Index-consistent editing operations on a glTF document.
+//!
+//! Appending never invalidates existing indices.
+//! Removing an element shifts every later element down by one, so every reference to the list
+//! (wherever it lives in the document) is rewritten to match.
+//! Removing an element which is still referenced is an error.
+
+use std::{collections::{BTreeSet, HashSet}, str::FromStr};
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Value};
+
+use crate::{gltf::{GltfBufferView, GltfDoc, GltfImage, GltfIndex, GltfSampler, GltfTexture, GltfTextureInfo}, ktx2::Ktx2Texture, Error, Result};
+
+/// Texture extensions which point at an alternate image through a `source` property.
+pub const TEXTURE_SOURCE_EXTENSIONS: &[&str] = &[
+    "KHR_texture_basisu",
+    "EXT_texture_webp",
+    "EXT_texture_avif",
+    "MSFT_texture_dds",
+];
+
+pub fn texture_ktx_source(texture: &GltfTexture) -> Option<GltfIndex<GltfImage>> {
+    texture_extension_source(texture, "KHR_texture_basisu")
+}
+pub fn set_texture_ktx_source(texture: &mut GltfTexture, new_idx: GltfIndex<GltfImage>) {
+    set_texture_extension_source(texture, "KHR_texture_basisu", new_idx)
+}
+
+/// The image pointed to by one of the [TEXTURE_SOURCE_EXTENSIONS] on `texture`, if it has that extension.
+pub fn texture_extension_source(texture: &GltfTexture, ext_name: &str) -> Option<GltfIndex<GltfImage>> {
+    texture
+        .extensions
+        .as_ref()?
+        .get(ext_name)?
+        .as_object()?
+        .get("source")?
+        .as_u64()
+        .map(|idx| GltfIndex::of(idx as usize))
+}
+pub fn set_texture_extension_source(texture: &mut GltfTexture, ext_name: &str, new_idx: GltfIndex<GltfImage>) {
+    assert!(new_idx.is_defined());
+
+    let ext = texture.extensions.get_or_insert_with(Default::default);
+    ext.insert(ext_name.to_string(), json!({
+        "source": (new_idx.raw_idx())
+    }));
+}
+
+/// Add `ext_name` to the document's `extensionsUsed` list, creating it if necessary.
+/// If `required` is set, also add it to `extensionsRequired`.
+pub fn add_extension_used(doc: &mut GltfDoc, ext_name: &str, required: bool) -> Result<()> {
+    let lists: &[&'static str] = if required {
+        &["extensionsUsed", "extensionsRequired"]
+    } else {
+        &["extensionsUsed"]
+    };
+    for &key in lists {
+        let list = doc
+            .entry(key)
+            .or_insert_with(|| Value::Array(vec![]))
+            .as_array_mut()
+            .ok_or(Error::ExpectedList { key })?;
+        if !list.iter().any(|ext| ext.as_str() == Some(ext_name)) {
+            list.push(ext_name.into());
+        }
+    }
+    Ok(())
+}
+
+pub fn get<T: DeserializeOwned>(doc: &GltfDoc, list_name: &'static str, idx: GltfIndex<T>) -> Result<T> {
+    let list = list(doc, list_name)?;
+    let idx = idx.idx_within(list_name, list.len())?.ok_or(Error::IdxNotSet { list_name })?;
+    Ok(T::deserialize(&list[idx])?)
+}
+
+/// Append `item` to the end of a top-level list, creating the list if necessary, and return its index.
+pub fn append<T: Serialize>(doc: &mut GltfDoc, list_name: &'static str, item: &T) -> Result<GltfIndex<T>> {
+    let list = list_mut(doc, list_name)?;
+    list.push(serde_json::to_value(item)?);
+    Ok(GltfIndex::of(list.len() - 1))
+}
+
+/// Overwrite an existing element of a top-level list.
+pub fn replace<T: Serialize>(doc: &mut GltfDoc, list_name: &'static str, idx: GltfIndex<T>, item: &T) -> Result<()> {
+    let list = list_mut(doc, list_name)?;
+    let idx = idx.idx_within(list_name, list.len())?.ok_or(Error::IdxNotSet { list_name })?;
+    list[idx] = serde_json::to_value(item)?;
+    Ok(())
+}
+
+/// Remove an element from a top-level list and renumber every reference to later elements.
+/// Fails with [Error::StillReferenced] if anything in the document refers to the removed element.
+///
+/// Only lists with known reference locations (see [references_to]) can be removed from.
+pub fn remove<T>(doc: &mut GltfDoc, list_name: &'static str, idx: GltfIndex<T>) -> Result<Value> {
+    remove_with(doc, list_name, idx, &ReferenceRegistry::default())
+}
+
+/// Like [remove], but also renumbering the [CustomReference]s in `registry`.
+pub fn remove_with<T>(doc: &mut GltfDoc, list_name: &'static str, idx: GltfIndex<T>, registry: &ReferenceRegistry) -> Result<Value> {
+    let len = list(doc, list_name)?.len();
+    let removed = idx.idx_within(list_name, len)?.ok_or(Error::IdxNotSet { list_name })?;
+
+    let mut still_referenced = false;
+    for_each_reference(doc, list_name, registry, &mut |reference| {
+        if reference.as_u64() == Some(removed as u64) {
+            still_referenced = true;
+        }
+    })?;
+    if still_referenced {
+        return Err(Error::StillReferenced { list_name, idx: removed });
+    }
+
+    for_each_reference(doc, list_name, registry, &mut |reference| {
+        if let Some(old) = reference.as_u64() {
+            if old > removed as u64 {
+                *reference = (old - 1).into();
+            }
+        }
+    })?;
+    Ok(list_mut(doc, list_name)?.remove(removed))
+}
+
+pub fn append_image(doc: &mut GltfDoc, image: &GltfImage) -> Result<GltfIndex<GltfImage>> {
+    append(doc, "images", image)
+}
+pub fn replace_image(doc: &mut GltfDoc, idx: GltfIndex<GltfImage>, image: &GltfImage) -> Result<()> {
+    replace(doc, "images", idx, image)
+}
+pub fn remove_image(doc: &mut GltfDoc, idx: GltfIndex<GltfImage>) -> Result<GltfImage> {
+    Ok(serde_json::from_value(remove(doc, "images", idx)?)?)
+}
+
+pub fn append_texture(doc: &mut GltfDoc, texture: &GltfTexture) -> Result<GltfIndex<GltfTexture>> {
+    append(doc, "textures", texture)
+}
+pub fn replace_texture(doc: &mut GltfDoc, idx: GltfIndex<GltfTexture>, texture: &GltfTexture) -> Result<()> {
+    replace(doc, "textures", idx, texture)
+}
+pub fn remove_texture(doc: &mut GltfDoc, idx: GltfIndex<GltfTexture>) -> Result<GltfTexture> {
+    Ok(serde_json::from_value(remove(doc, "textures", idx)?)?)
+}
+
+/// Append `data` to the GLB binary chunk `bin` (buffer 0), starting at a multiple of `align` bytes,
+/// and add a buffer view covering it. Buffer 0's `byteLength` is updated to match, and buffer 0 is created if it doesn't exist.
+///
+/// Image views shouldn't set `target`, see glTF2.0 section 3.6.1.
+pub fn append_buffer_view(doc: &mut GltfDoc, bin: &mut Vec<u8>, data: &[u8], align: usize, target: Option<u64>) -> Result<GltfIndex<GltfBufferView>> {
+    append_buffer_view_with(doc, bin, align, target, |bin| bin.extend_from_slice(data))
+}
+
+/// Like [append_buffer_view], but serializes `ktx` straight into `bin` instead of building the KTX2 file separately and copying it in.
+/// The view starts on an 8-byte boundary, so the KTX2 sections' alignment within the file also holds within the buffer.
+pub fn append_ktx2_buffer_view(doc: &mut GltfDoc, bin: &mut Vec<u8>, ktx: &Ktx2Texture) -> Result<GltfIndex<GltfBufferView>> {
+    bin.reserve(ktx.encoded_len() + 8);
+    append_buffer_view_with(doc, bin, 8, None, |bin| ktx.write_to(bin))
+}
+
+/// Append whatever `write` appends to `bin` as a new buffer view, see [append_buffer_view].
+fn append_buffer_view_with(doc: &mut GltfDoc, bin: &mut Vec<u8>, align: usize, target: Option<u64>, write: impl FnOnce(&mut Vec<u8>)) -> Result<GltfIndex<GltfBufferView>> {
+    let buffers = list_mut(doc, "buffers")?;
+    if buffers.is_empty() {
+        buffers.push(json!({ "byteLength": 0 }));
+    }
+    let buffer = buffers[0].as_object_mut().ok_or(Error::ExpectedObject { key: "buffers" })?;
+    if buffer.get("uri").is_some_and(|uri| !uri.is_null()) {
+        return Err(Error::BufferZeroNotBinaryChunk);
+    }
+
+    let byte_offset = bin.len().div_ceil(align.max(1)) * align.max(1);
+    bin.resize(byte_offset, 0);
+    write(bin);
+    let byte_length = bin.len() - byte_offset;
+    buffer.insert("byteLength".to_string(), bin.len().into());
+
+    append(doc, "bufferViews", &GltfBufferView {
+        target,
+        ..GltfBufferView::new(GltfIndex::of(0), byte_offset, byte_length)
+    })
+}
+pub fn replace_buffer_view(doc: &mut GltfDoc, idx: GltfIndex<GltfBufferView>, view: &GltfBufferView) -> Result<()> {
+    replace(doc, "bufferViews", idx, view)
+}
+/// Note this doesn't remove the viewed bytes from the underlying buffer.
+pub fn remove_buffer_view(doc: &mut GltfDoc, idx: GltfIndex<GltfBufferView>) -> Result<GltfBufferView> {
+    Ok(serde_json::from_value(remove(doc, "bufferViews", idx)?)?)
+}
+
+/// The buffer views holding image data which set a GPU `target`, in order.
+/// glTF2.0 section 3.6.1 only gives `target` to views of vertex attributes and indices, but some exporters set it on image views anyway.
+/// Views also used by an accessor are left out, as their target is then meaningful.
+pub fn image_views_with_target(doc: &GltfDoc) -> Result<Vec<GltfIndex<GltfBufferView>>> {
+    let accessor_views: HashSet<u64> = list(doc, "accessors")?
+        .iter()
+        .flat_map(|accessor| {
+            ["/bufferView", "/sparse/indices/bufferView", "/sparse/values/bufferView"].map(|pointer| accessor.pointer(pointer))
+        })
+        .filter_map(|view| view?.as_u64())
+        .collect();
+    let views = list(doc, "bufferViews")?;
+    let image_views: BTreeSet<u64> = list(doc, "images")?
+        .iter()
+        .filter_map(|image| image.get("bufferView")?.as_u64())
+        .filter(|view_idx| !accessor_views.contains(view_idx))
+        .collect();
+    Ok(image_views
+        .into_iter()
+        .filter(|&view_idx| {
+            views.get(view_idx as usize).and_then(|view| view.get("target")).is_some_and(|target| !target.is_null())
+        })
+        .map(|view_idx| GltfIndex::of(view_idx as usize))
+        .collect())
+}
+/// Remove the `target` from every view in [image_views_with_target], returning the views which were changed.
+pub fn strip_image_view_targets(doc: &mut GltfDoc) -> Result<Vec<GltfIndex<GltfBufferView>>> {
+    let stripped = image_views_with_target(doc)?;
+    let views = list_mut(doc, "bufferViews")?;
+    for view_idx in &stripped {
+        if let Some(view) = views[view_idx.raw_idx()].as_object_mut() {
+            view.remove("target");
+        }
+    }
+    Ok(stripped)
+}
+
+/// Materials aren't given a typed representation, so are passed around as raw JSON.
+pub fn append_material(doc: &mut GltfDoc, material: &Value) -> Result<GltfIndex<Value>> {
+    append(doc, "materials", material)
+}
+pub fn replace_material(doc: &mut GltfDoc, idx: GltfIndex<Value>, material: &Value) -> Result<()> {
+    replace(doc, "materials", idx, material)
+}
+pub fn remove_material(doc: &mut GltfDoc, idx: GltfIndex<Value>) -> Result<Value> {
+    remove(doc, "materials", idx)
+}
+
+/// Point the textureInfo at `slot` within a material at `info`, creating any objects on the way.
+/// `slot` is a `/`-separated path within the material ending in a key ending in "Texture",
+/// e.g. `occlusionTexture` or `pbrMetallicRoughness/baseColorTexture`, so [for_each_texture_info] finds it.
+pub fn set_material_texture(doc: &mut GltfDoc, material: GltfIndex<Value>, slot: &str, info: &GltfTextureInfo) -> Result<()> {
+    let path: Vec<&str> = slot.split('/').collect();
+    if path.iter().any(|key| key.is_empty()) || !path.last().is_some_and(|key| key.ends_with("Texture")) {
+        return Err(Error::NotATextureSlot(slot.to_string()));
+    }
+    let materials = list_mut(doc, "materials")?;
+    let idx = material.idx_within("materials", materials.len())?.ok_or(Error::IdxNotSet { list_name: "materials" })?;
+    let mut value = &mut materials[idx];
+    for key in path {
+        value = value
+            .as_object_mut()
+            .ok_or(Error::ExpectedObject { key: "materials" })?
+            .entry(key)
+            .or_insert_with(|| Value::Object(Default::default()));
+    }
+    *value = serde_json::to_value(info)?;
+    Ok(())
+}
+
+/// Samplers aren't given a typed representation, so are passed around as raw JSON.
+pub fn append_sampler(doc: &mut GltfDoc, sampler: &Value) -> Result<GltfIndex<GltfSampler>> {
+    append(doc, "samplers", sampler).map(|idx| GltfIndex::of(idx.raw_idx()))
+}
+
+/// Add `ktx` as a brand-new texture sampled with `sampler`: its data is appended to `bin` as a new buffer view
+/// (see [append_ktx2_buffer_view]), with a new image and a new texture using it through KHR_texture_basisu.
+/// There's no fallback `source`, so KHR_texture_basisu is marked required.
+pub fn add_ktx2_texture(doc: &mut GltfDoc, bin: &mut Vec<u8>, ktx: &Ktx2Texture, sampler: GltfIndex<GltfSampler>) -> Result<GltfIndex<GltfTexture>> {
+    let view = append_ktx2_buffer_view(doc, bin, ktx)?;
+    let image = append_image(doc, &GltfImage::from_buffer_view(view, "image/ktx2"))?;
+    let mut texture = GltfTexture::default().with_sampler(sampler);
+    set_texture_ktx_source(&mut texture, image);
+    add_extension_used(doc, "KHR_texture_basisu", true)?;
+    append_texture(doc, &texture)
+}
+
+/// The locations in the document which hold indices into `list_name`, as paths from the document root.
+/// `*` matches every element of an array, or every value of an object.
+pub fn references_to(list_name: &str) -> Option<Vec<Vec<&'static str>>> {
+    let paths = match list_name {
+        "images" => {
+            let mut paths = vec![vec!["textures", "*", "source"]];
+            for ext in TEXTURE_SOURCE_EXTENSIONS.iter().chain(crate::video::VIDEO_TEXTURE_EXTENSIONS) {
+                paths.push(vec!["textures", "*", "extensions", ext, "source"]);
+            }
+            paths
+        }
+        // Texture references in materials are handled separately, see for_each_texture_info
+        "textures" => vec![],
+        "accessors" => vec![
+            vec!["meshes", "*", "primitives", "*", "attributes", "*"],
+            vec!["meshes", "*", "primitives", "*", "indices"],
+            vec!["meshes", "*", "primitives", "*", "targets", "*", "*"],
+            vec!["nodes", "*", "extensions", "EXT_mesh_gpu_instancing", "attributes", "*"],
+            vec!["skins", "*", "inverseBindMatrices"],
+            vec!["animations", "*", "samplers", "*", "input"],
+            vec!["animations", "*", "samplers", "*", "output"],
+        ],
+        "bufferViews" => vec![
+            vec!["accessors", "*", "bufferView"],
+            vec!["accessors", "*", "sparse", "indices", "bufferView"],
+            vec!["accessors", "*", "sparse", "values", "bufferView"],
+            vec!["images", "*", "bufferView"],
+            vec!["meshes", "*", "primitives", "*", "extensions", "KHR_draco_mesh_compression", "bufferView"],
+        ],
+        "samplers" => vec![vec!["textures", "*", "sampler"]],
+        "materials" => vec![
+            vec!["meshes", "*", "primitives", "*", "material"],
+            vec!["meshes", "*", "primitives", "*", "extensions", "KHR_materials_variants", "mappings", "*", "material"],
+        ],
+        _ => return None,
+    };
+    Some(paths)
+}
+
+/// The top-level lists of a glTF document, whose elements are referred to by index.
+pub const GLTF_LISTS: &[&str] = &[
+    "accessors", "animations", "buffers", "bufferViews", "cameras", "images", "materials",
+    "meshes", "nodes", "samplers", "scenes", "skins", "textures",
+];
+
+/// A location holding indices into a top-level list, inside a vendor extension this crate doesn't know about.
+/// Registering it in a [ReferenceRegistry] means removing objects renumbers it, instead of leaving it pointing at the wrong object.
+///
+/// Parsed from a JSON pointer from the document root, where `*` matches every element of an array or value of an object,
+/// followed by `->` and the list, e.g. `/materials/*/extensions/MY_ext/imageIndex -> images`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CustomReference {
+    pub path: Vec<String>,
+    pub list_name: &'static str,
+    /// Whether the objects it refers to are kept by [crate::gc] even when whatever holds the reference isn't,
+    /// e.g. for `extras` an engine reads out-of-band, see [ReferenceRegistry::register_root].
+    pub root: bool,
+}
+impl FromStr for CustomReference {
+    type Err = Error;
+
+    fn from_str(pattern: &str) -> Result<Self> {
+        let bad = || Error::BadReferencePattern(pattern.to_string());
+        let (pointer, list_name) = pattern.split_once("->").ok_or_else(bad)?;
+        let list_name = GLTF_LISTS.iter().copied().find(|&name| name == list_name.trim()).ok_or_else(bad)?;
+        let path: Vec<String> = pointer
+            .trim()
+            .strip_prefix('/')
+            .ok_or_else(bad)?
+            .split('/')
+            .map(|token| token.replace("~1", "/").replace("~0", "~"))
+            .collect();
+        if path.iter().any(String::is_empty) {
+            return Err(bad());
+        }
+        Ok(Self { path, list_name, root: false })
+    }
+}
+
+/// Where references into each list live: the locations from [references_to], plus any registered [CustomReference]s.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReferenceRegistry {
+    custom: Vec<CustomReference>,
+}
+impl ReferenceRegistry {
+    pub fn register(&mut self, reference: CustomReference) {
+        self.custom.push(reference);
+    }
+    /// Register `reference` as a garbage collection root: the objects it refers to are always kept,
+    /// like those referred to from the scene graph, even if the object holding it is removed.
+    /// Use this for indices a downstream engine reads itself, e.g. a `/nodes/*/extras/lod/* -> materials` array.
+    pub fn register_root(&mut self, reference: CustomReference) {
+        self.custom.push(CustomReference { root: true, ..reference });
+    }
+    /// The paths to every reference into `list_name`, see [references_to].
+    ///
+    /// Custom references only add to the built-in locations: lists without any, like `nodes`,
+    /// still can't be removed from as their references elsewhere wouldn't be renumbered.
+    pub fn paths_to(&self, list_name: &'static str) -> Result<Vec<Vec<&str>>> {
+        Ok(self.paths_and_roots_to(list_name)?.into_iter().map(|(path, _)| path).collect())
+    }
+    /// Like [ReferenceRegistry::paths_to], also saying whether each path was registered as a root.
+    fn paths_and_roots_to(&self, list_name: &'static str) -> Result<Vec<(Vec<&str>, bool)>> {
+        let mut paths: Vec<_> = references_to(list_name)
+            .ok_or(Error::UnknownReferenceList { list_name })?
+            .into_iter()
+            .map(|path| (path, false))
+            .collect();
+        for reference in self.custom.iter().filter(|reference| reference.list_name == list_name) {
+            paths.push((reference.path.iter().map(String::as_str).collect(), reference.root));
+        }
+        Ok(paths)
+    }
+}
+
+/// Call `f` on every JSON value in the document that holds an index into `list_name`.
+pub fn for_each_reference(doc: &mut GltfDoc, list_name: &'static str, registry: &ReferenceRegistry, f: &mut dyn FnMut(&mut Value)) -> Result<()> {
+    for path in registry.paths_to(list_name)? {
+        if let Some(root) = doc.get_mut(path[0]) {
+            visit_path(root, &path[1..], f);
+        }
+    }
+    if list_name == "textures" {
+        if let Some(materials) = doc.get_mut("materials").and_then(|val| val.as_array_mut()) {
+            for material in materials {
+                for_each_texture_info(material, &mut |info| {
+                    if let Some(index) = info.get_mut("index") {
+                        f(index)
+                    }
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The top-level list and index of the element a reference is inside.
+pub type Holder<'a> = (&'a str, usize);
+
+/// Like [for_each_reference], but read-only, also passing `f` the top-level list and index of the element holding each reference.
+/// References outside any element of a top-level list, like those in the document's own `extensions`, have no holder,
+/// and neither do references registered with [ReferenceRegistry::register_root].
+pub fn for_each_reference_by_holder(doc: &GltfDoc, list_name: &'static str, registry: &ReferenceRegistry, f: &mut dyn FnMut(Option<Holder<'_>>, &Value)) -> Result<()> {
+    for (path, is_root) in registry.paths_and_roots_to(list_name)? {
+        let holder_list = GLTF_LISTS.iter().copied().find(|&name| path.first() == Some(&name)).filter(|_| !is_root);
+        match (holder_list, path.as_slice()) {
+            (Some(holder_list), [_, "*", rest @ ..]) => {
+                for (holder_idx, holder) in list(doc, holder_list)?.iter().enumerate() {
+                    visit_path_ref(holder, rest, &mut |reference| f(Some((holder_list, holder_idx)), reference));
+                }
+            }
+            (_, [first, rest @ ..]) => {
+                if let Some(root) = doc.get(*first) {
+                    visit_path_ref(root, rest, &mut |reference| f(None, reference));
+                }
+            }
+            (_, []) => {}
+        }
+    }
+    if list_name == "textures" {
+        for (material_idx, material) in list(doc, "materials")?.iter().enumerate() {
+            for_each_texture_info(&mut material.clone(), &mut |info| {
+                if let Some(index) = info.get("index") {
+                    f(Some(("materials", material_idx)), index)
+                }
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Call `f` on every textureInfo object within a material, including those inside material extensions.
+/// Any object stored under a key ending in "Texture" which has an "index" property is considered a textureInfo.
+pub fn for_each_texture_info(value: &mut Value, f: &mut dyn FnMut(&mut serde_json::Map<String, Value>)) {
+    match value {
+        Value::Object(obj) => {
+            for (key, child) in obj.iter_mut() {
+                match child {
+                    Value::Object(info) if key.ends_with("Texture") && info.contains_key("index") => f(info),
+                    _ => for_each_texture_info(child, f),
+                }
+            }
+        }
+        Value::Array(arr) => {
+            for child in arr {
+                for_each_texture_info(child, f);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Every textureInfo in the document's materials, see [for_each_texture_info], with the JSON pointer to it.
+pub fn texture_infos(doc: &GltfDoc) -> Result<Vec<(String, GltfTextureInfo)>> {
+    fn collect(value: &Value, pointer: String, out: &mut Vec<(String, Value)>) {
+        match value {
+            Value::Object(obj) => {
+                for (key, child) in obj {
+                    let child_pointer = format!("{pointer}/{}", key.replace('~', "~0").replace('/', "~1"));
+                    match child {
+                        Value::Object(info) if key.ends_with("Texture") && info.contains_key("index") => out.push((child_pointer, child.clone())),
+                        _ => collect(child, child_pointer, out),
+                    }
+                }
+            }
+            Value::Array(arr) => {
+                for (idx, child) in arr.iter().enumerate() {
+                    collect(child, format!("{pointer}/{idx}"), out);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut infos = vec![];
+    for (idx, material) in list(doc, "materials")?.iter().enumerate() {
+        collect(material, format!("/materials/{idx}"), &mut infos);
+    }
+    infos
+        .into_iter()
+        .map(|(pointer, info)| match serde_json::from_value(info) {
+            Ok(info) => Ok((pointer, info)),
+            Err(e) => Err(Error::from(e).at(pointer)),
+        })
+        .collect()
+}
+
+fn visit_path(value: &mut Value, path: &[&str], f: &mut dyn FnMut(&mut Value)) {
+    match path.split_first() {
+        None => f(value),
+        Some((&"*", rest)) => match value {
+            Value::Array(arr) => {
+                for child in arr {
+                    visit_path(child, rest, f);
+                }
+            }
+            Value::Object(obj) => {
+                for child in obj.values_mut() {
+                    visit_path(child, rest, f);
+                }
+            }
+            _ => {}
+        },
+        Some((key, rest)) => {
+            if let Some(child) = value.get_mut(*key) {
+                visit_path(child, rest, f);
+            }
+        }
+    }
+}
+
+fn visit_path_ref(value: &Value, path: &[&str], f: &mut dyn FnMut(&Value)) {
+    match path.split_first() {
+        None => f(value),
+        Some((&"*", rest)) => match value {
+            Value::Array(arr) => {
+                for child in arr {
+                    visit_path_ref(child, rest, f);
+                }
+            }
+            Value::Object(obj) => {
+                for child in obj.values() {
+                    visit_path_ref(child, rest, f);
+                }
+            }
+            _ => {}
+        },
+        Some((key, rest)) => {
+            if let Some(child) = value.get(*key) {
+                visit_path_ref(child, rest, f);
+            }
+        }
+    }
+}
+
+fn list<'a>(doc: &'a GltfDoc, list_name: &'static str) -> Result<&'a [Value]> {
+    match doc.get(list_name) {
+        None => Ok(&[]),
+        Some(val) => val.as_array().map(Vec::as_slice).ok_or(Error::ExpectedList { key: list_name }),
+    }
+}
+
+fn list_mut<'a>(doc: &'a mut GltfDoc, list_name: &'static str) -> Result<&'a mut Vec<Value>> {
+    doc.entry(list_name)
+        .or_insert_with(|| Value::Array(vec![]))
+        .as_array_mut()
+        .ok_or(Error::ExpectedList { key: list_name })
+}
@@ -4,40 +4,410 @@ use crate::gltf::{GltfBufferView, GltfIndex};
 
 #[derive(Error, Debug)]
 pub enum Error {
+    /// Another error, located at a JSON pointer into the glTF document.
+    #[error("{json_pointer}: {source}")]
+    At {
+        json_pointer: String,
+        source: Box<Error>,
+    },
     // Gltf(#[from] gltf::Error),
     // Ktx(#[from] KtxError),
+    #[error("image error: {0}")]
     Image(#[from] image::ImageError),
+    #[error("JSON error: {0}")]
     Serde(#[from] serde_json::Error),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("buffer {0} has no uri, but only buffer 0 may refer to the GLB binary chunk")]
     BufferHadNoUri(usize),
+    #[error("no data supplied for buffer uri {0:?}")]
     BufferUriMissingData(Option<String>),
+    #[error("buffer data uri had bad base64: {0}")]
     BufferUriBadBase64(#[from] base64::DecodeError),
+    #[error("buffer expected to have {expected_bytes} bytes, got {got_bytes}")]
     BufferNotLongEnough {
         expected_bytes: usize,
         got_bytes: usize,
     },
+    #[error("buffer view (offset {buffer_view_off}, length {buffer_view_len}) doesn't fit in buffer of length {buffer_len}")]
     BufferViewSizeOOB {
         buffer_len: usize,
         buffer_view_off: usize,
         buffer_view_len: usize,
     },
+    #[error("buffer view {buffer_view} has byteOffset {byte_offset}, which isn't 4-byte aligned")]
+    BufferViewMisaligned {
+        buffer_view: usize,
+        byte_offset: usize,
+    },
+    #[error("required index into glTF document list '{list_name}' was not set")]
     IdxNotSet {
         list_name: &'static str,
     },
-    #[error("glTF document list '{list_name}' has {num} elements ")]
+    #[error("glTF document list '{list_name}' has {num} elements, index {idx} out of bounds")]
     IdxOOB {
         list_name: &'static str,
         idx: usize,
         num: usize,
     },
+    #[error("can't remove element {idx} of '{list_name}' as it is still referenced")]
+    StillReferenced {
+        list_name: &'static str,
+        idx: usize,
+    },
+    #[error("image {idx} is referenced from outside the textures, but no planned job keeps it")]
+    ImageReferenceNotPlanned {
+        idx: usize,
+    },
+    #[error("don't know where references to glTF document list '{list_name}' live")]
+    UnknownReferenceList {
+        list_name: &'static str,
+    },
+    #[error("expected glTF document key '{key}' to be a list")]
     ExpectedList {
         key: &'static str,
     },
+    #[error("expected glTF document key '{key}' to contain objects")]
+    ExpectedObject {
+        key: &'static str,
+    },
+    #[error("buffer 0 has a uri, so data can't be appended to the GLB binary chunk")]
+    BufferZeroNotBinaryChunk,
+    #[error("image must have exactly one of a uri ({uri:?}) or a bufferView ({buffer_view:?})")]
     ImageNeedsDataUriXorBufferView {
         uri: Option<String>,
         buffer_view: GltfIndex<GltfBufferView>,
     },
+    #[error("couldn't determine image format")]
     ImageCouldntFindFormat,
+    #[error("image was referenced as KTX2 but did not have the KTX2 identifier")]
     ImageClaimedKtx2ButWasNot,
+    #[error("texture referenced no image sources")]
+    ImageHasNoSources,
+    #[cfg(feature = "schema")]
+    #[error("document doesn't match the glTF schema:{}", .0.iter().map(|v| format!("\n  {v}")).collect::<String>())]
+    SchemaViolations(Vec<crate::schema::SchemaViolation>),
+    #[error("bad color string '{0}', expected '#rrggbb' or '#rrggbbaa'")]
+    BadColorString(String),
+    #[error("KTX2 texture dimensions must be nonzero, got {width}x{height}")]
+    Ktx2ZeroSize {
+        width: u32,
+        height: u32,
+    },
+    #[error("KTX2 mip level {level} is {width}x{height}, which isn't half the size of the previous level")]
+    Ktx2BadMipLevel {
+        level: usize,
+        width: u32,
+        height: u32,
+    },
+    #[error("KTX2 texture needs {expected} images (one per layer and face), got {got}")]
+    Ktx2WrongImageCount {
+        expected: u32,
+        got: usize,
+    },
+    #[error("every layer and face of a KTX2 texture must have the same number of mip levels")]
+    Ktx2MismatchedLevelCounts,
+    #[error("KTX2 texture has no image at level {level}, layer {layer}, face {face}")]
+    Ktx2BadImageIndex {
+        level: usize,
+        layer: u32,
+        face: u32,
+    },
+    #[error("KTX2 image must be {expected} bytes, got {got}")]
+    Ktx2WrongImageSize {
+        expected: usize,
+        got: usize,
+    },
+    #[error("the images of a supercompressed KTX2 texture can't be accessed individually")]
+    Ktx2Supercompressed,
+    #[error("encoded image doesn't match its source: {0}")]
+    OutputVerificationFailed(String),
+    #[error("material texture slot '{0}' must be a path ending in a key ending in 'Texture'")]
+    NotATextureSlot(String),
+    #[error("bad image manifest: {0}")]
+    BadManifest(String),
+    #[error("the {0} encoder isn't available yet")]
+    EncoderUnavailable(&'static str),
+    #[error("malformed KTX2 file: {0}")]
+    Ktx2Malformed(&'static str),
+    #[error("KTX2 texture must be BasisLZ/ETC1S or non-supercompressed UASTC to be written as .basis")]
+    Ktx2NotBasis,
+    #[error("{0}")]
+    LimitExceeded(String),
+    #[error("bad gltf-ktxer.toml: {0}")]
+    BadConfig(String),
+    #[error("bad texture override: {0}")]
+    BadOverride(String),
+    #[error("GLB output would be {bytes} bytes, but GLB lengths are 32-bit so it can be at most 4 GiB")]
+    GlbTooLarge { bytes: u64 },
+    #[error("malformed GLB file: {0}")]
+    GlbMalformed(&'static str),
+    #[error("texture coordinate set changed from {before} to {after}")]
+    TexCoordChanged { before: u64, after: u64 },
+    #[error("bad reference pattern '{0}', expected a JSON pointer, '->' and a list, e.g. '/materials/*/extensions/MY_ext/imageIndex -> images'")]
+    BadReferencePattern(String),
+    #[error("image is animated, which textures can't represent")]
+    AnimatedImage,
+    #[error("needs an estimated {needed} bytes of memory, more than the limit of {limit}{hint}")]
+    MemoryLimitExceeded { needed: u64, limit: u64, hint: String },
+    #[error("bad response file {0}")]
+    BadArgFile(String),
+    #[error("image transform '{key}' failed: {message}")]
+    TransformFailed { key: String, message: String },
+    #[error("bad conversion hint: {0}")]
+    BadHint(String),
+    #[error("glTF-Validator found errors:{}", .0.iter().map(|m| format!("\n  {m}")).collect::<String>())]
+    ExternalValidation(Vec<crate::external_validate::ValidatorMessage>),
+    #[error("couldn't run glTF-Validator: {0}")]
+    ExternalValidatorUnavailable(String),
+    #[error("couldn't precompress output: {0}")]
+    CompressorUnavailable(String),
+    #[error("uri {0:?} refers to a file outside the document's directory")]
+    UriOutsideRoot(String),
+    #[error("job limit exceeded: {0}")]
+    JobLimitExceeded(String),
+    #[error("job took longer than its timeout of {0:?}")]
+    JobTimedOut(std::time::Duration),
+    #[error("stopped early, as the process was asked to stop")]
+    Interrupted,
+    #[error("no cache directory given, pass --cache-dir or set cache-dir in gltf-ktxer.toml")]
+    NoCacheDir,
+    #[error("quality regressed against the baseline: {0}")]
+    QualityRegression(String),
+    #[error("bad quality report: {0}")]
+    BadQualityReport(String),
+    #[error("max_encode_seconds_per_texture must be a finite number of seconds, zero or more, not {0}")]
+    BadTimeLimit(f64),
+    #[error("encode abandoned after taking longer than the time limit")]
+    EncodeAbandoned,
+}
+
+impl Error {
+    /// Attach the location of the part of the document this error came from, as an RFC 6901 JSON pointer.
+    /// Errors which already have a location keep it, as the innermost location is the most specific.
+    pub fn at(self, json_pointer: impl Into<String>) -> Self {
+        match self {
+            Error::At { .. } => self,
+            _ => Error::At { json_pointer: json_pointer.into(), source: Box::new(self) },
+        }
+    }
+    pub fn json_pointer(&self) -> Option<&str> {
+        match self {
+            Error::At { json_pointer, .. } => Some(json_pointer),
+            _ => None,
+        }
+    }
+    /// The stable code identifying what kind of error this is, ignoring its location.
+    pub fn code(&self) -> ErrorCode {
+        match self.without_location() {
+            Error::At { .. } => unreachable!("without_location strips every location"),
+            Error::Image(_) => ErrorCode::Image,
+            Error::Serde(_) => ErrorCode::Serde,
+            Error::Io(_) => ErrorCode::Io,
+            Error::BufferHadNoUri(_) => ErrorCode::BufferHadNoUri,
+            Error::BufferUriMissingData(_) => ErrorCode::BufferUriMissingData,
+            Error::BufferUriBadBase64(_) => ErrorCode::BufferUriBadBase64,
+            Error::BufferNotLongEnough { .. } => ErrorCode::BufferNotLongEnough,
+            Error::BufferViewSizeOOB { .. } => ErrorCode::BufferViewOutOfBounds,
+            Error::BufferViewMisaligned { .. } => ErrorCode::BufferViewMisaligned,
+            Error::IdxNotSet { .. } => ErrorCode::IndexNotSet,
+            Error::IdxOOB { .. } => ErrorCode::IndexOutOfBounds,
+            Error::StillReferenced { .. } => ErrorCode::StillReferenced,
+            Error::ImageReferenceNotPlanned { .. } => ErrorCode::ImageReferenceNotPlanned,
+            Error::UnknownReferenceList { .. } => ErrorCode::UnknownReferenceList,
+            Error::ExpectedList { .. } => ErrorCode::ExpectedList,
+            Error::ExpectedObject { .. } => ErrorCode::ExpectedObject,
+            Error::BufferZeroNotBinaryChunk => ErrorCode::BufferZeroNotBinaryChunk,
+            Error::ImageNeedsDataUriXorBufferView { .. } => ErrorCode::ImageNeedsUriXorBufferView,
+            Error::ImageCouldntFindFormat => ErrorCode::ImageFormatUnknown,
+            Error::ImageClaimedKtx2ButWasNot => ErrorCode::ImageNotKtx2,
+            Error::ImageHasNoSources => ErrorCode::ImageHasNoSources,
+            #[cfg(feature = "schema")]
+            Error::SchemaViolations(_) => ErrorCode::SchemaViolations,
+            Error::BadColorString(_) => ErrorCode::BadColorString,
+            Error::Ktx2ZeroSize { .. } => ErrorCode::Ktx2ZeroSize,
+            Error::Ktx2BadMipLevel { .. } => ErrorCode::Ktx2BadMipLevel,
+            Error::Ktx2WrongImageCount { .. } => ErrorCode::Ktx2WrongImageCount,
+            Error::Ktx2MismatchedLevelCounts => ErrorCode::Ktx2MismatchedLevelCounts,
+            Error::Ktx2BadImageIndex { .. } => ErrorCode::Ktx2BadImageIndex,
+            Error::Ktx2WrongImageSize { .. } => ErrorCode::Ktx2WrongImageSize,
+            Error::Ktx2Supercompressed => ErrorCode::Ktx2Supercompressed,
+            Error::OutputVerificationFailed(_) => ErrorCode::OutputVerificationFailed,
+            Error::NotATextureSlot(_) => ErrorCode::NotATextureSlot,
+            Error::BadManifest(_) => ErrorCode::BadManifest,
+            Error::EncoderUnavailable(_) => ErrorCode::EncoderUnavailable,
+            Error::Ktx2Malformed(_) => ErrorCode::Ktx2Malformed,
+            Error::Ktx2NotBasis => ErrorCode::Ktx2NotBasis,
+            Error::LimitExceeded(_) => ErrorCode::LimitExceeded,
+            Error::BadConfig(_) => ErrorCode::BadConfig,
+            Error::BadOverride(_) => ErrorCode::BadOverride,
+            Error::GlbTooLarge { .. } => ErrorCode::GlbTooLarge,
+            Error::GlbMalformed(_) => ErrorCode::GlbMalformed,
+            Error::TexCoordChanged { .. } => ErrorCode::TexCoordChanged,
+            Error::BadReferencePattern(_) => ErrorCode::BadReferencePattern,
+            Error::AnimatedImage => ErrorCode::AnimatedImage,
+            Error::MemoryLimitExceeded { .. } => ErrorCode::MemoryLimitExceeded,
+            Error::BadArgFile(_) => ErrorCode::BadArgFile,
+            Error::TransformFailed { .. } => ErrorCode::TransformFailed,
+            Error::BadHint(_) => ErrorCode::BadHint,
+            Error::ExternalValidation(_) => ErrorCode::ExternalValidation,
+            Error::ExternalValidatorUnavailable(_) => ErrorCode::ExternalValidatorUnavailable,
+            Error::CompressorUnavailable(_) => ErrorCode::CompressorUnavailable,
+            Error::UriOutsideRoot(_) => ErrorCode::UriOutsideRoot,
+            Error::JobLimitExceeded(_) => ErrorCode::JobLimitExceeded,
+            Error::JobTimedOut(_) => ErrorCode::JobTimedOut,
+            Error::Interrupted => ErrorCode::Interrupted,
+            Error::NoCacheDir => ErrorCode::NoCacheDir,
+            Error::QualityRegression(_) => ErrorCode::QualityRegression,
+            Error::BadQualityReport(_) => ErrorCode::BadQualityReport,
+            Error::BadTimeLimit(_) => ErrorCode::BadTimeLimit,
+            Error::EncodeAbandoned => ErrorCode::EncodeAbandoned,
+        }
+    }
+    /// The error without its location.
+    pub fn without_location(&self) -> &Error {
+        match self {
+            Error::At { source, .. } => source.without_location(),
+            _ => self,
+        }
+    }
+}
+
+/// A stable identifier for each kind of [Error], for machine-readable output.
+/// The string forms are part of the command-line interface, so existing codes must never be renamed or reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    Image,
+    Serde,
+    Io,
+    BufferHadNoUri,
+    BufferUriMissingData,
+    BufferUriBadBase64,
+    BufferNotLongEnough,
+    BufferViewOutOfBounds,
+    BufferViewMisaligned,
+    IndexNotSet,
+    IndexOutOfBounds,
+    StillReferenced,
+    ImageReferenceNotPlanned,
+    UnknownReferenceList,
+    ExpectedList,
+    ExpectedObject,
+    BufferZeroNotBinaryChunk,
+    ImageNeedsUriXorBufferView,
+    ImageFormatUnknown,
+    ImageNotKtx2,
+    ImageHasNoSources,
+    SchemaViolations,
+    BadColorString,
+    Ktx2ZeroSize,
+    Ktx2BadMipLevel,
+    Ktx2WrongImageCount,
+    Ktx2MismatchedLevelCounts,
+    Ktx2BadImageIndex,
+    Ktx2WrongImageSize,
+    Ktx2Supercompressed,
+    OutputVerificationFailed,
+    NotATextureSlot,
+    BadManifest,
+    EncoderUnavailable,
+    Ktx2Malformed,
+    Ktx2NotBasis,
+    LimitExceeded,
+    BadConfig,
+    BadOverride,
+    GlbTooLarge,
+    GlbMalformed,
+    TexCoordChanged,
+    BadReferencePattern,
+    AnimatedImage,
+    MemoryLimitExceeded,
+    BadArgFile,
+    TransformFailed,
+    BadHint,
+    ExternalValidation,
+    ExternalValidatorUnavailable,
+    CompressorUnavailable,
+    UriOutsideRoot,
+    JobLimitExceeded,
+    JobTimedOut,
+    Interrupted,
+    NoCacheDir,
+    QualityRegression,
+    BadQualityReport,
+    BadTimeLimit,
+    EncodeAbandoned,
+}
+impl ErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::Image => "image",
+            ErrorCode::Serde => "json",
+            ErrorCode::Io => "io",
+            ErrorCode::BufferHadNoUri => "buffer_had_no_uri",
+            ErrorCode::BufferUriMissingData => "buffer_uri_missing_data",
+            ErrorCode::BufferUriBadBase64 => "buffer_uri_bad_base64",
+            ErrorCode::BufferNotLongEnough => "buffer_not_long_enough",
+            ErrorCode::BufferViewOutOfBounds => "buffer_view_out_of_bounds",
+            ErrorCode::BufferViewMisaligned => "buffer_view_misaligned",
+            ErrorCode::IndexNotSet => "index_not_set",
+            ErrorCode::IndexOutOfBounds => "index_out_of_bounds",
+            ErrorCode::StillReferenced => "still_referenced",
+            ErrorCode::ImageReferenceNotPlanned => "image_reference_not_planned",
+            ErrorCode::UnknownReferenceList => "unknown_reference_list",
+            ErrorCode::ExpectedList => "expected_list",
+            ErrorCode::ExpectedObject => "expected_object",
+            ErrorCode::BufferZeroNotBinaryChunk => "buffer_zero_not_binary_chunk",
+            ErrorCode::ImageNeedsUriXorBufferView => "image_needs_uri_xor_buffer_view",
+            ErrorCode::ImageFormatUnknown => "image_format_unknown",
+            ErrorCode::ImageNotKtx2 => "image_not_ktx2",
+            ErrorCode::ImageHasNoSources => "image_has_no_sources",
+            ErrorCode::SchemaViolations => "schema_violations",
+            ErrorCode::BadColorString => "bad_color_string",
+            ErrorCode::Ktx2ZeroSize => "ktx2_zero_size",
+            ErrorCode::Ktx2BadMipLevel => "ktx2_bad_mip_level",
+            ErrorCode::Ktx2WrongImageCount => "ktx2_wrong_image_count",
+            ErrorCode::Ktx2MismatchedLevelCounts => "ktx2_mismatched_level_counts",
+            ErrorCode::Ktx2BadImageIndex => "ktx2_bad_image_index",
+            ErrorCode::Ktx2WrongImageSize => "ktx2_wrong_image_size",
+            ErrorCode::Ktx2Supercompressed => "ktx2_supercompressed",
+            ErrorCode::OutputVerificationFailed => "output_verification_failed",
+            ErrorCode::NotATextureSlot => "not_a_texture_slot",
+            ErrorCode::BadManifest => "bad_manifest",
+            ErrorCode::EncoderUnavailable => "encoder_unavailable",
+            ErrorCode::Ktx2Malformed => "ktx2_malformed",
+            ErrorCode::Ktx2NotBasis => "ktx2_not_basis",
+            ErrorCode::LimitExceeded => "limit_exceeded",
+            ErrorCode::BadConfig => "bad_config",
+            ErrorCode::BadOverride => "bad_override",
+            ErrorCode::GlbTooLarge => "glb_too_large",
+            ErrorCode::GlbMalformed => "glb_malformed",
+            ErrorCode::TexCoordChanged => "tex_coord_changed",
+            ErrorCode::BadReferencePattern => "bad_reference_pattern",
+            ErrorCode::AnimatedImage => "animated_image",
+            ErrorCode::MemoryLimitExceeded => "memory_limit_exceeded",
+            ErrorCode::BadArgFile => "bad_arg_file",
+            ErrorCode::TransformFailed => "transform_failed",
+            ErrorCode::BadHint => "bad_hint",
+            ErrorCode::ExternalValidation => "external_validation",
+            ErrorCode::ExternalValidatorUnavailable => "external_validator_unavailable",
+            ErrorCode::CompressorUnavailable => "compressor_unavailable",
+            ErrorCode::UriOutsideRoot => "uri_outside_root",
+            ErrorCode::JobLimitExceeded => "job_limit_exceeded",
+            ErrorCode::JobTimedOut => "job_timed_out",
+            ErrorCode::Interrupted => "interrupted",
+            ErrorCode::NoCacheDir => "no_cache_dir",
+            ErrorCode::QualityRegression => "quality_regression",
+            ErrorCode::BadQualityReport => "bad_quality_report",
+            ErrorCode::BadTimeLimit => "bad_time_limit",
+            ErrorCode::EncodeAbandoned => "encode_abandoned",
+        }
+    }
+}
+impl std::fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
-pub type Result<T> = std::result::Result<T, Error>;
\ No newline at end of file
+pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,1042 @@
+//! A Basis Universal ETC1S encoder, producing BasisLZ-supercompressed KTX2 textures, and a decoder for them.
+//!
+//! ETC1S is the subset of ETC1 where both halves of a 4x4 block share one 5-bit base color and intensity table.
+//! BasisLZ stores a texture as two global codebooks, one of these endpoints (color and intensity) and one of selectors
+//! (the 2-bit modifier index of each texel), plus a Huffman-coded index into each per block, see KTX2 spec section 3.12
+//! and `basisu_transcoder.cpp` in the Basis Universal repository (https://github.com/BinomialLLC/basis_universal).
+//!
+//! The encoder clusters the blocks' ideal endpoints, then their selectors, each with a tree-structured vector quantizer.
+//! The quality (1-255, like `basisu -q`) sets how big the codebooks may get, and [EncodeEffort] how hard each split is refined.
+//! Every block's endpoint index is delta-coded against the previous block's, and selector indices are coded directly.
+//!
+//! Textures with transparent texels get a second, alpha slice per image, a grayscale ETC1S image of the alpha channel
+//! sharing the same codebooks.
+
+use std::{collections::HashMap, num::NonZeroU8};
+
+use image::{Rgba, RgbaImage};
+
+use crate::{
+    basis::BasisLzGlobalData,
+    fallback,
+    ktx2::{self, ColorSpace, Ktx2Level, Ktx2Texture, KHR_DF_MODEL_ETC1S, SUPERCOMPRESSION_BASIS_LZ, VK_FORMAT_UNDEFINED},
+    tuning::EncodeEffort,
+    Error, Result,
+};
+
+/// The quality used when none is given, the same as `basisu`'s default.
+pub const DEFAULT_QUALITY: u8 = 128;
+
+/// The smallest and largest number of endpoints and selectors in the codebooks, at quality 1 and 255.
+const MIN_CODEBOOK_SIZE: usize = 32;
+const MAX_CODEBOOK_SIZE: usize = 4096;
+
+/// The ETC1 intensity modifier tables, with selectors in increasing order rather than ETC1's bit order, as Basis stores them.
+const INTENSITY_TABLES: [[i32; 4]; 8] = [
+    [-8, -2, 2, 8],
+    [-17, -5, 5, 17],
+    [-29, -9, 9, 29],
+    [-42, -13, 13, 42],
+    [-60, -18, 18, 60],
+    [-80, -24, 24, 80],
+    [-106, -33, 33, 106],
+    [-183, -47, 47, 183],
+];
+
+/// Color5 deltas are coded with one of three models, depending on the previous value of the channel, see [color5_model].
+const COLOR5_PAL0_PREV_HI: u8 = 9;
+const COLOR5_PAL1_PREV_HI: u8 = 21;
+
+/// 4 2-bit predictions for each 2x2 group of blocks, plus a symbol to repeat the last group's.
+const ENDPOINT_PRED_TOTAL_SYMBOLS: usize = 4 * 4 * 4 * 4 + 1;
+const ENDPOINT_PRED_REPEAT_LAST_SYMBOL: u32 = 256;
+const ENDPOINT_PRED_COUNT_VLC_BITS: u32 = 4;
+const ENDPOINT_PRED_MIN_REPEAT_COUNT: u32 = 3;
+/// Every block in the group takes its endpoint as a delta from the previous block's.
+const ENDPOINT_PRED_ALL_DELTA: usize = 0xFF;
+const ENDPOINT_PRED_DELTA: u32 = 3;
+
+const SELECTOR_HISTORY_BUF_SIZE: usize = 64;
+const SELECTOR_HISTORY_BUF_RLE_COUNT_THRESH: u32 = 3;
+const SELECTOR_HISTORY_BUF_RLE_COUNT_TOTAL: usize = 64;
+
+/// Huffman table serialization, from `basisu_huffman`: code sizes are themselves Huffman-coded,
+/// with codes 17 and 18 for runs of zeros and 19 and 20 to repeat the previous size.
+const HUFFMAN_MAX_CODE_SIZE: u8 = 16;
+const HUFFMAN_MAX_CODE_LENGTH_CODE_SIZE: u8 = 7;
+const HUFFMAN_MAX_SYMS_LOG2: u32 = 14;
+const HUFFMAN_TOTAL_CODE_LENGTH_CODES: usize = 21;
+const HUFFMAN_SMALL_ZERO_RUN: (u32, u32, usize) = (17, 3, 3);
+const HUFFMAN_BIG_ZERO_RUN: (u32, u32, usize) = (18, 7, 11);
+const HUFFMAN_SMALL_REPEAT: (u32, u32, usize) = (19, 2, 3);
+const HUFFMAN_BIG_REPEAT: (u32, u32, usize) = (20, 7, 7);
+/// The order code length code sizes are sent in, most likely to be used first.
+const HUFFMAN_SORTED_CODE_LENGTH_CODES: [usize; HUFFMAN_TOTAL_CODE_LENGTH_CODES] = [17, 18, 19, 20, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15, 16];
+
+/// A base color and intensity table, shared by every texel in a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Endpoint {
+    color5: [u8; 3],
+    intensity: u8,
+}
+impl Endpoint {
+    /// The four colors a block with this endpoint can use, in selector order.
+    fn colors(self) -> [[u8; 3]; 4] {
+        INTENSITY_TABLES[self.intensity as usize].map(|modifier| {
+            self.color5.map(|c| {
+                let base = ((c << 3) | (c >> 2)) as i32;
+                (base + modifier).clamp(0, 255) as u8
+            })
+        })
+    }
+}
+
+/// Which of the three color5 delta models codes a channel whose previous value was `prev`.
+fn color5_model(prev: u8) -> usize {
+    if prev <= COLOR5_PAL0_PREV_HI {
+        0
+    } else if prev <= COLOR5_PAL1_PREV_HI {
+        1
+    } else {
+        2
+    }
+}
+
+/// The selector of each texel in a block, in row-major order.
+type Selector = [u8; 16];
+/// The texels of a block, in row-major order.
+type Block = [[u8; 3]; 16];
+
+fn color_error(a: [u8; 3], b: [u8; 3]) -> u32 {
+    a.iter().zip(b).map(|(&a, b)| (a as i32 - b as i32).pow(2) as u32).sum()
+}
+
+/// The selector closest to `texel` out of `colors`, and its error.
+fn best_selector(colors: &[[u8; 3]; 4], texel: [u8; 3]) -> (u8, u32) {
+    (0..4u8).map(|s| (s, color_error(colors[s as usize], texel))).min_by_key(|&(_, error)| error).unwrap()
+}
+
+/// How hard the encoder works at each [EncodeEffort].
+struct EffortSettings {
+    /// Rounds of 2-means refinement each time a cluster is split.
+    split_iterations: usize,
+    /// How far from the mean color, along the gray axis, to search for a better base color.
+    search_radius: i32,
+}
+impl From<EncodeEffort> for EffortSettings {
+    fn from(effort: EncodeEffort) -> Self {
+        match effort {
+            EncodeEffort::Fast => Self { split_iterations: 1, search_radius: 0 },
+            EncodeEffort::Balanced => Self { split_iterations: 3, search_radius: 1 },
+            EncodeEffort::Thorough => Self { split_iterations: 6, search_radius: 2 },
+        }
+    }
+}
+
+/// The number of endpoints and selectors the codebooks may hold at `quality`, from 32 at 1 up to 4096 at 255, on a log scale.
+pub fn codebook_size(quality: NonZeroU8) -> usize {
+    let t = (quality.get() - 1) as f64 / 254.0;
+    let size = MIN_CODEBOOK_SIZE as f64 * (MAX_CODEBOOK_SIZE as f64 / MIN_CODEBOOK_SIZE as f64).powf(t);
+    (size.round() as usize).clamp(MIN_CODEBOOK_SIZE, MAX_CODEBOOK_SIZE)
+}
+
+/// Encode `image` as a single-level ETC1S texture, see [encode_levels].
+pub fn encode(image: &RgbaImage, color_space: ColorSpace, quality: Option<NonZeroU8>, effort: Option<EncodeEffort>) -> Result<Ktx2Texture> {
+    encode_levels(std::slice::from_ref(image), color_space, quality, effort)
+}
+
+/// Encode mip levels (largest first, as for [Ktx2Texture::from_rgba8_levels]) as a BasisLZ/ETC1S texture.
+///
+/// `quality` defaults to [DEFAULT_QUALITY], and `effort` to [EncodeEffort::Balanced].
+/// Every level shares one pair of codebooks, so they're sized for all the blocks together.
+pub fn encode_levels(levels: &[RgbaImage], color_space: ColorSpace, quality: Option<NonZeroU8>, effort: Option<EncodeEffort>) -> Result<Ktx2Texture> {
+    // Check the levels the same way as an uncompressed texture would be
+    let mut ktx = Ktx2Texture::from_rgba8_levels(levels, color_space)?;
+    let settings = EffortSettings::from(effort.unwrap_or(EncodeEffort::Balanced));
+    let max_codebook_size = codebook_size(quality.unwrap_or(NonZeroU8::new(DEFAULT_QUALITY).unwrap()));
+    let has_alpha = levels.iter().any(|level| level.pixels().any(|texel| texel[3] != 255));
+
+    // Every slice's blocks, color slices and alpha slices alike, are quantized together
+    let mut slices = vec![];
+    for level in levels {
+        slices.push(Slice::of(level, |texel| [texel[0], texel[1], texel[2]]));
+        if has_alpha {
+            slices.push(Slice::of(level, |texel| [texel[3]; 3]));
+        }
+    }
+    let blocks: Vec<&Block> = slices.iter().flat_map(|slice| &slice.blocks).collect();
+    let codebooks = Codebooks::build(&blocks, max_codebook_size, &settings)?;
+
+    let mut slice_data = vec![];
+    let mut models = SliceModels::new(codebooks.endpoints.len(), codebooks.selectors.len());
+    let mut block_idx = 0;
+    for slice in &slices {
+        let indices = &codebooks.block_indices[block_idx..block_idx + slice.blocks.len()];
+        block_idx += slice.blocks.len();
+        models.count(slice, indices);
+        slice_data.push(indices);
+    }
+    let models = models.finish();
+    let mut image_descs = vec![];
+    let mut level_datas = vec![];
+    let per_level = if has_alpha { 2 } else { 1 };
+    for level_idx in 0..levels.len() {
+        let mut data = vec![];
+        let mut desc = [0u32; 5];
+        for i in 0..per_level {
+            let slice_idx = level_idx * per_level + i;
+            let bytes = models.write_slice(&slices[slice_idx], slice_data[slice_idx]);
+            desc[1 + i * 2] = data.len() as u32;
+            desc[2 + i * 2] = bytes.len() as u32;
+            data.extend_from_slice(&bytes);
+        }
+        image_descs.push(desc);
+        level_datas.push(data);
+    }
+
+    let endpoints = codebooks.write_endpoints();
+    let selectors = codebooks.write_selectors();
+    let tables = models.write_tables();
+    let mut sgd = vec![];
+    sgd.extend_from_slice(&(codebooks.endpoints.len() as u16).to_le_bytes());
+    sgd.extend_from_slice(&(codebooks.selectors.len() as u16).to_le_bytes());
+    for len in [endpoints.len(), selectors.len(), tables.len(), 0] {
+        sgd.extend_from_slice(&(len as u32).to_le_bytes());
+    }
+    for desc in image_descs {
+        sgd.extend(desc.into_iter().flat_map(u32::to_le_bytes));
+    }
+    sgd.extend_from_slice(&endpoints);
+    sgd.extend_from_slice(&selectors);
+    sgd.extend_from_slice(&tables);
+
+    ktx.vk_format = VK_FORMAT_UNDEFINED;
+    ktx.supercompression_scheme = SUPERCOMPRESSION_BASIS_LZ;
+    ktx.dfd = ktx2::etc1s_dfd(color_space, has_alpha);
+    ktx.sgd = sgd;
+    // Section 3.9.7: the uncompressed length of BasisLZ levels is 0, as it depends on what they're transcoded to
+    ktx.levels = level_datas.into_iter().map(|data| Ktx2Level { data, uncompressed_byte_length: 0 }).collect();
+    Ok(ktx)
+}
+
+/// Decode the first image of `level` of a BasisLZ/ETC1S texture, e.g. one made by [encode], to RGBA8.
+///
+/// Textures without an alpha slice decode as opaque. Alpha is taken from the green channel of the alpha slice.
+/// The image is only allocated once its slices have decoded to a block for every 4x4 block of the level,
+/// so a header claiming a bigger size than the slice data covers fails with [Error::Ktx2Malformed] once the data runs out,
+/// rather than by running out of memory.
+pub fn decode(ktx: &Ktx2Texture, level: usize) -> Result<RgbaImage> {
+    if ktx.supercompression_scheme != SUPERCOMPRESSION_BASIS_LZ || ktx.dfd_color_model() != Some(KHR_DF_MODEL_ETC1S) {
+        return Err(Error::Ktx2NotBasis);
+    }
+    let images_per_level = ktx.images_per_level() as usize;
+    let sgd = BasisLzGlobalData::parse(&ktx.sgd, ktx.levels.len() * images_per_level)?;
+    let level_data = &ktx.levels.get(level).ok_or(Error::Ktx2BadImageIndex { level, layer: 0, face: 0 })?.data;
+    let desc = &sgd.image_descs[level * images_per_level];
+    let (width, height) = ((ktx.pixel_width >> level).max(1), (ktx.pixel_height >> level).max(1));
+
+    let endpoints = read_endpoints(sgd.endpoints, sgd.num_endpoints as usize)?;
+    let selectors = read_selectors(sgd.selectors, sgd.num_selectors as usize)?;
+    let models = SliceDecoder::read(sgd.tables, endpoints.len(), selectors.len())?;
+    let slice = |(offset, len): (usize, usize)| {
+        offset.checked_add(len).and_then(|end| level_data.get(offset..end)).ok_or(Error::Ktx2Malformed("BasisLZ slice is out of bounds"))
+    };
+
+    let blocks_x = width.div_ceil(4);
+    let blocks_y = height.div_ceil(4);
+    let rgb_blocks = models.decode_slice(slice(desc.rgb_slice)?, blocks_x, blocks_y)?;
+    let alpha_blocks = match desc.alpha_slice.1 {
+        0 => None,
+        _ => Some(models.decode_slice(slice(desc.alpha_slice)?, blocks_x, blocks_y)?),
+    };
+
+    let mut image = RgbaImage::new(width, height);
+    let mut put = |blocks: Vec<(u16, u16)>, alpha: bool| {
+        for (i, (endpoint, selector)) in blocks.into_iter().enumerate() {
+            let colors = endpoints[endpoint as usize].colors();
+            let (bx, by) = (i as u32 % blocks_x, i as u32 / blocks_x);
+            for (t, &s) in selectors[selector as usize].iter().enumerate() {
+                let (x, y) = (bx * 4 + t as u32 % 4, by * 4 + t as u32 / 4);
+                if x < width && y < height {
+                    let [r, g, b] = colors[s as usize];
+                    let texel = image.get_pixel_mut(x, y);
+                    *texel = if alpha { Rgba([texel[0], texel[1], texel[2], g]) } else { Rgba([r, g, b, 255]) };
+                }
+            }
+        }
+    };
+    put(rgb_blocks, false);
+    if let Some(alpha_blocks) = alpha_blocks {
+        put(alpha_blocks, true);
+    }
+    Ok(image)
+}
+
+/// One image (or its alpha channel) split into 4x4 blocks, with edge texels repeated to fill partial blocks.
+struct Slice {
+    blocks_x: u32,
+    blocks_y: u32,
+    blocks: Vec<Block>,
+}
+impl Slice {
+    fn of(image: &RgbaImage, channels: impl Fn(&Rgba<u8>) -> [u8; 3]) -> Self {
+        let (width, height) = image.dimensions();
+        let (blocks_x, blocks_y) = (width.div_ceil(4), height.div_ceil(4));
+        let mut blocks = Vec::with_capacity((blocks_x * blocks_y) as usize);
+        for by in 0..blocks_y {
+            for bx in 0..blocks_x {
+                blocks.push(std::array::from_fn(|t| {
+                    let x = (bx * 4 + t as u32 % 4).min(width - 1);
+                    let y = (by * 4 + t as u32 / 4).min(height - 1);
+                    channels(image.get_pixel(x, y))
+                }));
+            }
+        }
+        Self { blocks_x, blocks_y, blocks }
+    }
+}
+
+/// The endpoint and selector codebooks, and the (endpoint, selector) index of every block.
+struct Codebooks {
+    endpoints: Vec<Endpoint>,
+    selectors: Vec<Selector>,
+    block_indices: Vec<(u16, u16)>,
+}
+impl Codebooks {
+    fn build(blocks: &[&Block], max_size: usize, settings: &EffortSettings) -> Result<Self> {
+        // Endpoints: cluster each block's own best endpoint by its color and the spread of its intensity table
+        let features: Vec<[f32; 4]> = blocks
+            .iter()
+            .map(|block| {
+                let endpoint = fit_endpoint(block.iter().copied(), 0);
+                let [r, g, b] = endpoint.color5.map(|c| ((c << 3) | (c >> 2)) as f32);
+                [r, g, b, INTENSITY_TABLES[endpoint.intensity as usize][3] as f32]
+            })
+            .collect();
+        let clusters = cluster(&features, max_size, settings.split_iterations)?;
+        let mut block_endpoints = vec![0; blocks.len()];
+        let mut endpoints = vec![];
+        let mut endpoint_indices = HashMap::new();
+        for members in &clusters {
+            let endpoint = fit_endpoint(members.iter().flat_map(|&i| blocks[i as usize].iter().copied()), settings.search_radius);
+            let next = endpoints.len() as u16;
+            let idx = *endpoint_indices.entry(endpoint).or_insert(next);
+            if idx == next {
+                endpoints.push(endpoint);
+            }
+            for &i in members {
+                block_endpoints[i as usize] = idx;
+            }
+        }
+
+        // Selectors: cluster each block's best selectors for its endpoint,
+        // then give each cluster the selectors with the least total error over its blocks, which are independent per texel
+        let block_colors: Vec<[[u8; 3]; 4]> = block_endpoints.iter().map(|&e| endpoints[e as usize].colors()).collect();
+        let features: Vec<[f32; 16]> = blocks
+            .iter()
+            .zip(&block_colors)
+            .map(|(block, colors)| block.map(|texel| best_selector(colors, texel).0 as f32))
+            .collect();
+        let clusters = cluster(&features, max_size, settings.split_iterations)?;
+        let mut block_selectors = vec![0; blocks.len()];
+        let mut selectors = vec![];
+        let mut selector_indices = HashMap::new();
+        for members in &clusters {
+            let selector: Selector = std::array::from_fn(|t| {
+                (0..4u8)
+                    .min_by_key(|&s| members.iter().map(|&i| color_error(block_colors[i as usize][s as usize], blocks[i as usize][t]) as u64).sum::<u64>())
+                    .unwrap()
+            });
+            let next = selectors.len() as u16;
+            let idx = *selector_indices.entry(selector).or_insert(next);
+            if idx == next {
+                selectors.push(selector);
+            }
+            for &i in members {
+                block_selectors[i as usize] = idx;
+            }
+        }
+
+        Ok(Self { endpoints, selectors, block_indices: block_endpoints.into_iter().zip(block_selectors).collect() })
+    }
+
+    /// The endpoint codebook: each channel and intensity is a Huffman-coded delta from the previous endpoint's.
+    fn write_endpoints(&self) -> Vec<u8> {
+        let grayscale = self.endpoints.iter().all(|e| e.color5[0] == e.color5[1] && e.color5[1] == e.color5[2]);
+        let channels = if grayscale { 1 } else { 3 };
+        // (model, symbol) for every delta, in the order they're written. Model 3 is the intensity.
+        let mut symbols = vec![];
+        let mut prev_color5 = [16u8; 3];
+        let mut prev_intensity = 0u8;
+        for endpoint in &self.endpoints {
+            symbols.push((3, endpoint.intensity.wrapping_sub(prev_intensity) & 7));
+            prev_intensity = endpoint.intensity;
+            for (prev, &c) in prev_color5.iter_mut().zip(&endpoint.color5).take(channels) {
+                symbols.push((color5_model(*prev), c.wrapping_sub(*prev) & 31));
+                *prev = c;
+            }
+        }
+        let mut freqs = [vec![0; 32], vec![0; 32], vec![0; 32], vec![0; 8]];
+        for &(model, symbol) in &symbols {
+            freqs[model][symbol as usize] += 1;
+        }
+        let codes = freqs.map(|freqs| HuffmanCode::new(&freqs, HUFFMAN_MAX_CODE_SIZE));
+
+        let mut bits = BitWriter::default();
+        for code in &codes {
+            code.write_table(&mut bits);
+        }
+        bits.put(grayscale as u32, 1);
+        for (model, symbol) in symbols {
+            codes[model].put(&mut bits, symbol as usize);
+        }
+        bits.finish()
+    }
+
+    /// The selector codebook, stored raw: one byte per row, with the first texel in the low bits.
+    fn write_selectors(&self) -> Vec<u8> {
+        let mut bits = BitWriter::default();
+        bits.put(0, 1); // no global selector codebook
+        bits.put(0, 1); // no hybrid selector codebook
+        bits.put(1, 1); // raw selectors
+        for selector in &self.selectors {
+            for row in selector.chunks_exact(4) {
+                bits.put(row.iter().enumerate().map(|(x, &s)| (s as u32) << (x * 2)).sum(), 8);
+            }
+        }
+        bits.finish()
+    }
+}
+
+/// The endpoint of an ETC1S block (or cluster of blocks) with the least error for `texels`.
+/// The base color is the mean color, moved up to `search_radius` steps along the gray axis.
+fn fit_endpoint(texels: impl Iterator<Item = [u8; 3]> + Clone, search_radius: i32) -> Endpoint {
+    let (mut sum, mut count) = ([0u64; 3], 0u64);
+    for texel in texels.clone() {
+        for c in 0..3 {
+            sum[c] += texel[c] as u64;
+        }
+        count += 1;
+    }
+    let mean5 = sum.map(|s| (s as f64 / count.max(1) as f64 * 31.0 / 255.0).round() as i32);
+    let mut best = (u64::MAX, Endpoint { color5: [0; 3], intensity: 0 });
+    for offset in -search_radius..=search_radius {
+        let color5 = mean5.map(|c| (c + offset).clamp(0, 31) as u8);
+        for intensity in 0..8 {
+            let endpoint = Endpoint { color5, intensity };
+            let colors = endpoint.colors();
+            let error = texels.clone().map(|texel| best_selector(&colors, texel).1 as u64).sum();
+            if error < best.0 {
+                best = (error, endpoint);
+            }
+        }
+    }
+    best.1
+}
+
+/// Split `points` into at most `max_clusters` clusters, by repeatedly splitting the cluster with the most squared error in two.
+/// Each split starts either side of the mean along the axis of most variance, then runs `iterations` rounds of 2-means.
+/// Returns the indices of the points in each cluster.
+/// This is most of the encoder's work, so it stops early if [fallback::check_abandoned] says to.
+fn cluster<const D: usize>(points: &[[f32; D]], max_clusters: usize, iterations: usize) -> Result<Vec<Vec<u32>>> {
+    let mut clusters = vec![(0..points.len() as u32).collect::<Vec<_>>()];
+    let mut errors = vec![squared_error(points, &clusters[0])];
+    while clusters.len() < max_clusters {
+        fallback::check_abandoned()?;
+        let (worst, &error) = errors.iter().enumerate().max_by(|a, b| a.1.total_cmp(b.1)).unwrap();
+        if error <= 0.0 {
+            break;
+        }
+        match split(points, &clusters[worst], iterations) {
+            Some((a, b)) => {
+                errors[worst] = squared_error(points, &a);
+                errors.push(squared_error(points, &b));
+                clusters[worst] = a;
+                clusters.push(b);
+            }
+            None => errors[worst] = 0.0,
+        }
+    }
+    Ok(clusters)
+}
+
+fn mean<const D: usize>(points: &[[f32; D]], members: &[u32]) -> [f32; D] {
+    let mut mean = [0.0; D];
+    for &i in members {
+        for (m, p) in mean.iter_mut().zip(points[i as usize]) {
+            *m += p;
+        }
+    }
+    mean.map(|m| m / members.len().max(1) as f32)
+}
+
+fn distance<const D: usize>(a: &[f32; D], b: &[f32; D]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| (a - b) * (a - b)).sum()
+}
+
+fn squared_error<const D: usize>(points: &[[f32; D]], members: &[u32]) -> f32 {
+    let mean = mean(points, members);
+    members.iter().map(|&i| distance(&points[i as usize], &mean)).sum()
+}
+
+fn split<const D: usize>(points: &[[f32; D]], members: &[u32], iterations: usize) -> Option<(Vec<u32>, Vec<u32>)> {
+    let center = mean(points, members);
+    let variance: [f32; D] = std::array::from_fn(|d| members.iter().map(|&i| (points[i as usize][d] - center[d]).powi(2)).sum::<f32>());
+    let axis = (0..D).max_by(|&a, &b| variance[a].total_cmp(&variance[b]))?;
+    let spread = (variance[axis] / members.len() as f32).sqrt();
+    let mut centroids = [center, center];
+    centroids[0][axis] -= spread;
+    centroids[1][axis] += spread;
+    let mut halves = (vec![], vec![]);
+    for iteration in 0..=iterations.max(1) {
+        halves = members.iter().partition(|&&i| distance(&points[i as usize], &centroids[0]) <= distance(&points[i as usize], &centroids[1]));
+        if halves.0.is_empty() || halves.1.is_empty() {
+            return None;
+        }
+        if iteration < iterations.max(1) {
+            centroids = [mean(points, &halves.0), mean(points, &halves.1)];
+        }
+    }
+    Some(halves)
+}
+
+/// Symbol frequencies for the models shared by every slice, then the models themselves.
+struct SliceModels<C> {
+    endpoint_pred: C,
+    delta_endpoint: C,
+    selector: C,
+    selector_history_buf_rle: C,
+    num_endpoints: usize,
+}
+impl SliceModels<Vec<u32>> {
+    fn new(num_endpoints: usize, num_selectors: usize) -> Self {
+        Self {
+            endpoint_pred: vec![0; ENDPOINT_PRED_TOTAL_SYMBOLS],
+            delta_endpoint: vec![0; num_endpoints],
+            selector: vec![0; num_selectors + SELECTOR_HISTORY_BUF_SIZE + 1],
+            selector_history_buf_rle: vec![0; SELECTOR_HISTORY_BUF_RLE_COUNT_TOTAL],
+            num_endpoints,
+        }
+    }
+
+    fn count(&mut self, slice: &Slice, indices: &[(u16, u16)]) {
+        self.endpoint_pred[ENDPOINT_PRED_ALL_DELTA] += slice.blocks_x.div_ceil(2) * slice.blocks_y.div_ceil(2);
+        let mut prev_endpoint = 0;
+        for &(endpoint, selector) in indices {
+            self.delta_endpoint[endpoint_delta(prev_endpoint, endpoint, self.num_endpoints)] += 1;
+            self.selector[selector as usize] += 1;
+            prev_endpoint = endpoint;
+        }
+    }
+
+    fn finish(self) -> SliceModels<HuffmanCode> {
+        let code = |freqs: &[u32]| HuffmanCode::new(freqs, HUFFMAN_MAX_CODE_SIZE);
+        SliceModels {
+            endpoint_pred: code(&self.endpoint_pred),
+            delta_endpoint: code(&self.delta_endpoint),
+            selector: code(&self.selector),
+            selector_history_buf_rle: code(&self.selector_history_buf_rle),
+            num_endpoints: self.num_endpoints,
+        }
+    }
+}
+impl SliceModels<HuffmanCode> {
+    fn write_tables(&self) -> Vec<u8> {
+        let mut bits = BitWriter::default();
+        for code in [&self.endpoint_pred, &self.delta_endpoint, &self.selector, &self.selector_history_buf_rle] {
+            code.write_table(&mut bits);
+        }
+        bits.put(SELECTOR_HISTORY_BUF_SIZE as u32, 13);
+        bits.finish()
+    }
+
+    /// Each 2x2 group of blocks starts with its predictions, which are always deltas,
+    /// then every block has its endpoint delta and selector index.
+    fn write_slice(&self, slice: &Slice, indices: &[(u16, u16)]) -> Vec<u8> {
+        let mut bits = BitWriter::default();
+        let mut prev_endpoint = 0;
+        for by in 0..slice.blocks_y {
+            for bx in 0..slice.blocks_x {
+                if bx % 2 == 0 && by % 2 == 0 {
+                    self.endpoint_pred.put(&mut bits, ENDPOINT_PRED_ALL_DELTA);
+                }
+                let (endpoint, selector) = indices[(by * slice.blocks_x + bx) as usize];
+                self.delta_endpoint.put(&mut bits, endpoint_delta(prev_endpoint, endpoint, self.num_endpoints));
+                self.selector.put(&mut bits, selector as usize);
+                prev_endpoint = endpoint;
+            }
+        }
+        bits.finish()
+    }
+}
+
+fn endpoint_delta(prev: u16, endpoint: u16, num_endpoints: usize) -> usize {
+    (endpoint as usize + num_endpoints - prev as usize) % num_endpoints
+}
+
+/// Appends bits least significant first, as Basis reads them.
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buf: u64,
+    len: u32,
+}
+impl BitWriter {
+    fn put(&mut self, value: u32, bits: u32) {
+        debug_assert!(bits == 32 || value >> bits == 0, "{value} doesn't fit in {bits} bits");
+        self.buf |= (value as u64) << self.len;
+        self.len += bits;
+        while self.len >= 8 {
+            self.bytes.push(self.buf as u8);
+            self.buf >>= 8;
+            self.len -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.len > 0 {
+            self.bytes.push(self.buf as u8);
+        }
+        self.bytes
+    }
+}
+
+/// A canonical Huffman code, with each code bit-reversed so it can be written least significant bit first.
+struct HuffmanCode {
+    sizes: Vec<u8>,
+    codes: Vec<u16>,
+}
+impl HuffmanCode {
+    /// Build a code for symbols with `freqs`, no longer than `max_size` bits.
+    /// Basis readers reject incomplete codes, so there are always at least two symbols, adding unused ones if needed.
+    fn new(freqs: &[u32], max_size: u8) -> Self {
+        let mut freqs = freqs.to_vec();
+        freqs.resize(freqs.len().max(2), 0);
+        for i in 0..2 {
+            if freqs.iter().filter(|&&f| f > 0).count() < 2 && freqs[i] == 0 {
+                freqs[i] = 1;
+            }
+        }
+        let sizes = loop {
+            let sizes = huffman_code_sizes(&freqs);
+            if sizes.iter().all(|&size| size <= max_size) {
+                break sizes;
+            }
+            // Flatten the distribution until the longest code fits
+            for f in freqs.iter_mut().filter(|f| **f > 0) {
+                *f = (*f / 2).max(1);
+            }
+        };
+        let codes = canonical_codes(&sizes).into_iter().zip(&sizes).map(|(code, &size)| reverse_bits(code, size)).collect();
+        Self { sizes, codes }
+    }
+
+    fn put(&self, bits: &mut BitWriter, symbol: usize) {
+        debug_assert!(self.sizes[symbol] > 0, "symbol {symbol} has no code");
+        bits.put(self.codes[symbol] as u32, self.sizes[symbol] as u32);
+    }
+
+    /// Write the code sizes, as read by `read_huffman_table` in `basisu_transcoder.cpp`.
+    fn write_table(&self, bits: &mut BitWriter) {
+        let used = self.sizes.iter().rposition(|&size| size > 0).map_or(0, |last| last + 1);
+        bits.put(used as u32, HUFFMAN_MAX_SYMS_LOG2);
+
+        // Zero runs become run codes, everything else is sent as is
+        let mut symbols: Vec<(u32, u32, u32)> = vec![];
+        let mut i = 0;
+        while i < used {
+            let zeros = self.sizes[i..used].iter().take_while(|&&size| size == 0).count();
+            let (code, extra_bits, min) = if zeros >= HUFFMAN_BIG_ZERO_RUN.2 {
+                HUFFMAN_BIG_ZERO_RUN
+            } else if zeros >= HUFFMAN_SMALL_ZERO_RUN.2 {
+                HUFFMAN_SMALL_ZERO_RUN
+            } else {
+                symbols.push((self.sizes[i] as u32, 0, 0));
+                i += 1;
+                continue;
+            };
+            let run = zeros.min(min + (1 << extra_bits) - 1);
+            symbols.push((code, extra_bits, (run - min) as u32));
+            i += run;
+        }
+
+        let mut freqs = vec![0; HUFFMAN_TOTAL_CODE_LENGTH_CODES];
+        for &(code, _, _) in &symbols {
+            freqs[code as usize] += 1;
+        }
+        let code_length_code = HuffmanCode::new(&freqs, HUFFMAN_MAX_CODE_LENGTH_CODE_SIZE);
+        let sent = HUFFMAN_SORTED_CODE_LENGTH_CODES.iter().rposition(|&code| code_length_code.sizes[code] > 0).unwrap() + 1;
+        bits.put(sent as u32, 5);
+        for &code in &HUFFMAN_SORTED_CODE_LENGTH_CODES[..sent] {
+            bits.put(code_length_code.sizes[code] as u32, 3);
+        }
+        for (code, extra_bits, extra) in symbols {
+            code_length_code.put(bits, code as usize);
+            if extra_bits > 0 {
+                bits.put(extra, extra_bits);
+            }
+        }
+    }
+}
+
+/// The length of each symbol's code in a Huffman code for `freqs`, 0 for symbols which never occur.
+fn huffman_code_sizes(freqs: &[u32]) -> Vec<u8> {
+    // Nodes are leaves (the symbols) followed by internal nodes, each with the index of its parent
+    let mut parents = vec![usize::MAX; freqs.len()];
+    let mut queue: std::collections::BinaryHeap<std::cmp::Reverse<(u64, usize)>> =
+        freqs.iter().enumerate().filter(|(_, &f)| f > 0).map(|(i, &f)| std::cmp::Reverse((f as u64, i))).collect();
+    while queue.len() > 1 {
+        let std::cmp::Reverse((fa, a)) = queue.pop().unwrap();
+        let std::cmp::Reverse((fb, b)) = queue.pop().unwrap();
+        let node = parents.len();
+        parents.push(usize::MAX);
+        parents[a] = node;
+        parents[b] = node;
+        queue.push(std::cmp::Reverse((fa + fb, node)));
+    }
+    (0..freqs.len())
+        .map(|symbol| {
+            if freqs[symbol] == 0 {
+                return 0;
+            }
+            let (mut node, mut depth) = (symbol, 0u8);
+            while parents[node] != usize::MAX {
+                node = parents[node];
+                depth = depth.saturating_add(1);
+            }
+            depth
+        })
+        .collect()
+}
+
+/// Canonical codes for `sizes`: shorter codes first, then in symbol order, as in DEFLATE.
+fn canonical_codes(sizes: &[u8]) -> Vec<u16> {
+    let max_size = sizes.iter().copied().max().unwrap_or(0) as usize;
+    let mut counts = vec![0u32; max_size + 1];
+    for &size in sizes.iter().filter(|&&size| size > 0) {
+        counts[size as usize] += 1;
+    }
+    let mut next_code = vec![0u32; max_size + 1];
+    let mut code = 0;
+    for size in 1..=max_size {
+        code = (code + counts[size - 1]) << 1;
+        next_code[size] = code;
+    }
+    sizes
+        .iter()
+        .map(|&size| {
+            if size == 0 {
+                return 0;
+            }
+            let code = next_code[size as usize];
+            next_code[size as usize] += 1;
+            code as u16
+        })
+        .collect()
+}
+
+fn reverse_bits(code: u16, size: u8) -> u16 {
+    if size == 0 { 0 } else { code.reverse_bits() >> (16 - size) }
+}
+
+/// Reads bits least significant first. Reading past the end gives zeros, as in Basis.
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+impl<'a> BitReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    fn get(&mut self, bits: u32) -> u32 {
+        let mut value = 0;
+        for i in 0..bits {
+            let bit = self.bytes.get(self.pos / 8).map_or(0, |byte| (byte >> (self.pos % 8)) & 1);
+            value |= (bit as u32) << i;
+            self.pos += 1;
+        }
+        value
+    }
+
+    /// Whether more bits have been read than there are, which [BitReader::get] reads as zeros.
+    fn overran(&self) -> bool {
+        self.pos > self.bytes.len() * 8
+    }
+
+    /// A variable-length integer: chunks of `chunk_bits`, each followed by a bit saying whether another follows.
+    fn get_vlc(&mut self, chunk_bits: u32) -> Result<u32> {
+        let mut value = 0;
+        let mut shift = 0;
+        loop {
+            let chunk = self.get(chunk_bits + 1);
+            value |= (chunk & ((1 << chunk_bits) - 1)) << shift;
+            shift += chunk_bits;
+            if chunk >> chunk_bits == 0 {
+                return Ok(value);
+            }
+            if shift >= 32 {
+                return Err(Error::Ktx2Malformed("BasisLZ variable-length integer is too long"));
+            }
+        }
+    }
+
+    /// Read a code written by [HuffmanCode::write_table].
+    fn huffman_table(&mut self) -> Result<HuffmanDecoder> {
+        const BAD: Error = Error::Ktx2Malformed("BasisLZ Huffman table is invalid");
+        let used = self.get(HUFFMAN_MAX_SYMS_LOG2) as usize;
+        if used == 0 {
+            return Ok(HuffmanDecoder::new(&[]));
+        }
+        let sent = self.get(5) as usize;
+        if !(1..=HUFFMAN_TOTAL_CODE_LENGTH_CODES).contains(&sent) {
+            return Err(BAD);
+        }
+        let mut code_length_sizes = [0u8; HUFFMAN_TOTAL_CODE_LENGTH_CODES];
+        for &code in &HUFFMAN_SORTED_CODE_LENGTH_CODES[..sent] {
+            code_length_sizes[code] = self.get(3) as u8;
+        }
+        let code_length_code = HuffmanDecoder::new(&code_length_sizes);
+        let mut sizes = Vec::with_capacity(used);
+        while sizes.len() < used {
+            let code = code_length_code.decode(self)?;
+            let (extra_bits, min, value) = match code {
+                0..=16 => {
+                    sizes.push(code as u8);
+                    continue;
+                }
+                17 => (HUFFMAN_SMALL_ZERO_RUN.1, HUFFMAN_SMALL_ZERO_RUN.2, 0),
+                18 => (HUFFMAN_BIG_ZERO_RUN.1, HUFFMAN_BIG_ZERO_RUN.2, 0),
+                19 | 20 => {
+                    let (_, extra_bits, min) = if code == 19 { HUFFMAN_SMALL_REPEAT } else { HUFFMAN_BIG_REPEAT };
+                    (extra_bits, min, sizes.last().copied().filter(|&size| size > 0).ok_or(BAD)?)
+                }
+                _ => return Err(BAD),
+            };
+            let run = self.get(extra_bits) as usize + min;
+            if sizes.len() + run > used {
+                return Err(BAD);
+            }
+            sizes.resize(sizes.len() + run, value);
+        }
+        Ok(HuffmanDecoder::new(&sizes))
+    }
+}
+
+/// Decodes a canonical Huffman code one bit at a time.
+struct HuffmanDecoder {
+    /// How many codes there are of each size
+    counts: [u32; HUFFMAN_MAX_CODE_SIZE as usize + 1],
+    /// The symbols, ordered by code
+    symbols: Vec<u32>,
+}
+impl HuffmanDecoder {
+    fn new(sizes: &[u8]) -> Self {
+        let mut counts = [0; HUFFMAN_MAX_CODE_SIZE as usize + 1];
+        let mut symbols: Vec<u32> = (0..sizes.len() as u32).filter(|&s| (1..=HUFFMAN_MAX_CODE_SIZE).contains(&sizes[s as usize])).collect();
+        symbols.sort_by_key(|&s| sizes[s as usize]);
+        for &s in &symbols {
+            counts[sizes[s as usize] as usize] += 1;
+        }
+        Self { counts, symbols }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    fn decode(&self, bits: &mut BitReader) -> Result<u32> {
+        let (mut code, mut first, mut index) = (0u32, 0u32, 0u32);
+        for &count in &self.counts[1..] {
+            code |= bits.get(1);
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(Error::Ktx2Malformed("BasisLZ data has an invalid Huffman code"))
+    }
+}
+
+fn read_endpoints(data: &[u8], num_endpoints: usize) -> Result<Vec<Endpoint>> {
+    let mut bits = BitReader::new(data);
+    let models = [bits.huffman_table()?, bits.huffman_table()?, bits.huffman_table()?, bits.huffman_table()?];
+    if models.iter().any(HuffmanDecoder::is_empty) {
+        return Err(Error::Ktx2Malformed("BasisLZ endpoint codebook is missing a model"));
+    }
+    let grayscale = bits.get(1) == 1;
+    let mut prev_color5 = [16u8; 3];
+    let mut prev_intensity = 0u8;
+    (0..num_endpoints)
+        .map(|_| {
+            prev_intensity = (prev_intensity + models[3].decode(&mut bits)? as u8) & 7;
+            for prev in prev_color5.iter_mut().take(if grayscale { 1 } else { 3 }) {
+                *prev = (*prev + models[color5_model(*prev)].decode(&mut bits)? as u8) & 31;
+            }
+            if grayscale {
+                prev_color5 = [prev_color5[0]; 3];
+            }
+            Ok(Endpoint { color5: prev_color5, intensity: prev_intensity })
+        })
+        .collect()
+}
+
+fn read_selectors(data: &[u8], num_selectors: usize) -> Result<Vec<Selector>> {
+    let mut bits = BitReader::new(data);
+    if bits.get(1) == 1 || bits.get(1) == 1 {
+        return Err(Error::Ktx2Malformed("BasisLZ global and hybrid selector codebooks aren't supported"));
+    }
+    let raw = bits.get(1) == 1;
+    let delta_model = if raw { None } else { Some(bits.huffman_table()?) };
+    let mut prev_rows = [0u8; 4];
+    (0..num_selectors)
+        .map(|i| {
+            for row in prev_rows.iter_mut() {
+                *row = match &delta_model {
+                    Some(model) if i > 0 => *row ^ model.decode(&mut bits)? as u8,
+                    _ => bits.get(8) as u8,
+                };
+            }
+            Ok(std::array::from_fn(|t| (prev_rows[t / 4] >> ((t % 4) * 2)) & 3))
+        })
+        .collect()
+}
+
+/// The models shared by every slice, read from the BasisLZ tables.
+struct SliceDecoder {
+    endpoint_pred: HuffmanDecoder,
+    delta_endpoint: HuffmanDecoder,
+    selector: HuffmanDecoder,
+    selector_history_buf_rle: HuffmanDecoder,
+    selector_history_buf_size: usize,
+    num_endpoints: usize,
+    num_selectors: usize,
+}
+impl SliceDecoder {
+    fn read(tables: &[u8], num_endpoints: usize, num_selectors: usize) -> Result<Self> {
+        let mut bits = BitReader::new(tables);
+        let decoder = Self {
+            endpoint_pred: bits.huffman_table()?,
+            delta_endpoint: bits.huffman_table()?,
+            selector: bits.huffman_table()?,
+            selector_history_buf_rle: bits.huffman_table()?,
+            selector_history_buf_size: bits.get(13) as usize,
+            num_endpoints,
+            num_selectors,
+        };
+        if decoder.endpoint_pred.is_empty() || decoder.delta_endpoint.is_empty() || decoder.selector.is_empty() || decoder.selector_history_buf_rle.is_empty() {
+            return Err(Error::Ktx2Malformed("BasisLZ tables are missing a model"));
+        }
+        Ok(decoder)
+    }
+
+    /// The (endpoint, selector) index of every block of a slice, in row-major order.
+    ///
+    /// Memory grows with the blocks decoded, not the dimensions given, as those come from the header,
+    /// and runs longer than the blocks left are rejected, as they are by the Basis Universal transcoder.
+    fn decode_slice(&self, data: &[u8], blocks_x: u32, blocks_y: u32) -> Result<Vec<(u16, u16)>> {
+        const BAD: Error = Error::Ktx2Malformed("BasisLZ slice is invalid");
+        let total_blocks = (blocks_x as usize).checked_mul(blocks_y as usize).ok_or(BAD)?;
+        let mut bits = BitReader::new(data);
+        let mut blocks: Vec<(u16, u16)> = vec![];
+        // The predictions for the lower half of each 2x2 group, saved from its upper half
+        let mut lower_preds: Vec<u32> = vec![];
+        let (mut pred_bits, mut prev_pred_symbol, mut pred_repeats) = (0u32, 0u32, 0u32);
+        let mut prev_endpoint = 0usize;
+        let mut history = vec![0u16; self.selector_history_buf_size];
+        let mut history_rover = 0;
+        let mut selector_rle = 0u32;
+        let rle_symbol = (self.num_selectors + self.selector_history_buf_size) as u32;
+        for by in 0..blocks_y as usize {
+            for bx in 0..blocks_x as usize {
+                if bx % 2 == 0 {
+                    if by % 2 == 0 {
+                        if pred_repeats > 0 {
+                            pred_repeats -= 1;
+                            pred_bits = prev_pred_symbol;
+                        } else {
+                            pred_bits = self.endpoint_pred.decode(&mut bits)?;
+                            if pred_bits == ENDPOINT_PRED_REPEAT_LAST_SYMBOL {
+                                pred_repeats = bits.get_vlc(ENDPOINT_PRED_COUNT_VLC_BITS)? + ENDPOINT_PRED_MIN_REPEAT_COUNT - 1;
+                                // Each repeat is a 2x2 group, so at least one block
+                                if pred_repeats as usize > total_blocks - blocks.len() {
+                                    return Err(BAD);
+                                }
+                                pred_bits = prev_pred_symbol;
+                            } else {
+                                prev_pred_symbol = pred_bits;
+                            }
+                        }
+                        match lower_preds.get_mut(bx / 2) {
+                            Some(lower) => *lower = pred_bits >> 4,
+                            None => lower_preds.push(pred_bits >> 4),
+                        }
+                    } else {
+                        pred_bits = lower_preds[bx / 2];
+                    }
+                }
+                let pred = pred_bits & 3;
+                pred_bits >>= 2;
+                let endpoint = match pred {
+                    0 if bx > 0 => prev_endpoint,
+                    1 if by > 0 => blocks[(by - 1) * blocks_x as usize + bx].0 as usize,
+                    2 if bx > 0 && by > 0 => blocks[(by - 1) * blocks_x as usize + bx - 1].0 as usize,
+                    ENDPOINT_PRED_DELTA => (prev_endpoint + self.delta_endpoint.decode(&mut bits)? as usize) % self.num_endpoints.max(1),
+                    _ => return Err(BAD),
+                };
+                prev_endpoint = endpoint;
+
+                let symbol = if selector_rle > 0 {
+                    selector_rle -= 1;
+                    self.num_selectors as u32
+                } else {
+                    let symbol = self.selector.decode(&mut bits)?;
+                    if symbol == rle_symbol {
+                        let run = self.selector_history_buf_rle.decode(&mut bits)?;
+                        selector_rle = if run as usize == SELECTOR_HISTORY_BUF_RLE_COUNT_TOTAL - 1 {
+                            bits.get_vlc(7)? + SELECTOR_HISTORY_BUF_RLE_COUNT_THRESH
+                        } else {
+                            run + SELECTOR_HISTORY_BUF_RLE_COUNT_THRESH
+                        };
+                        if selector_rle as usize > total_blocks - blocks.len() {
+                            return Err(BAD);
+                        }
+                        selector_rle -= 1;
+                        self.num_selectors as u32
+                    } else {
+                        symbol
+                    }
+                };
+                let selector = match (symbol as usize).checked_sub(self.num_selectors) {
+                    None => {
+                        if !history.is_empty() {
+                            history[history_rover] = symbol as u16;
+                            history_rover += 1;
+                            if history_rover == history.len() {
+                                history_rover = history.len() / 2;
+                            }
+                        }
+                        symbol as u16
+                    }
+                    Some(idx) => {
+                        let selector = *history.get(idx).ok_or(BAD)?;
+                        // Move used entries towards the front
+                        history.swap(idx / 2, idx);
+                        selector
+                    }
+                };
+                if endpoint >= self.num_endpoints || selector as usize >= self.num_selectors {
+                    return Err(BAD);
+                }
+                // Every block not covered by a run takes at least one bit, so a header claiming more blocks than the data holds ends here
+                if bits.overran() {
+                    return Err(Error::Ktx2Malformed("BasisLZ slice ends early"));
+                }
+                blocks.push((endpoint as u16, selector));
+            }
+        }
+        Ok(blocks)
+    }
+}
@@ -0,0 +1,115 @@
+//! Running the Khronos glTF-Validator (https://github.com/KhronosGroup/glTF-Validator) on written files,
+//! to catch what [crate::validate] doesn't check, like accessor bounds or animation data.
+//!
+//! The validator's errors fail with [Error::ExternalValidation], and its warnings become [Warning]s like [crate::validate::lint]'s.
+
+use std::{fmt::Display, path::Path, process::Command};
+
+use serde_json::Value;
+
+use crate::{argfile, validate::Warning, Error, Result};
+
+/// The validator's executable, looked for on `PATH` unless another command is given.
+pub const DEFAULT_VALIDATOR_COMMAND: &str = "gltf_validator";
+
+/// How serious a validator message is, numbered as in the validator's report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Error,
+    Warning,
+    Information,
+    Hint,
+}
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Information => "information",
+            Severity::Hint => "hint",
+        })
+    }
+}
+
+/// A single finding from the validator's report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidatorMessage {
+    /// The validator's own code for the finding, e.g. `ACCESSOR_MIN_MISMATCH`.
+    pub code: String,
+    pub message: String,
+    pub severity: Severity,
+    /// RFC 6901 JSON pointer to the offending value, empty if the validator gave none, e.g. for findings in the GLB binary chunk.
+    pub json_pointer: String,
+}
+impl Display for ValidatorMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let pointer = if self.json_pointer.is_empty() { "/" } else { &self.json_pointer };
+        write!(f, "{pointer}: {} ({})", self.message, self.code)
+    }
+}
+
+/// Run the validator `command` on the glTF or GLB file at `path`. `command` is the program followed by any arguments,
+/// quoted like a response file (see [argfile::split]), e.g. `"npx gltf-validator"`.
+pub fn run_validator(command: &str, path: &Path) -> Result<Vec<ValidatorMessage>> {
+    let args = argfile::split(command)?;
+    let Some((program, args)) = args.split_first() else {
+        return Err(Error::ExternalValidatorUnavailable("the validator command is empty".to_string()));
+    };
+    let output = Command::new(program).args(args).arg("--stdout").arg(path).output().map_err(|e| {
+        Error::ExternalValidatorUnavailable(match e.kind() {
+            std::io::ErrorKind::NotFound => format!("'{program}' wasn't found, install glTF-Validator or configure its command"),
+            _ => format!("couldn't run '{program}': {e}"),
+        })
+    })?;
+    // The validator's exit status doesn't say whether it found problems, only whether it produced a report
+    let report: Value = serde_json::from_slice(&output.stdout).map_err(|_| {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Error::ExternalValidatorUnavailable(format!("'{program}' didn't print a report ({}): {}", output.status, stderr.trim()))
+    })?;
+    parse_report(&report)
+}
+
+/// Read the messages from a validator report, the JSON object printed by `gltf_validator --stdout`.
+pub fn parse_report(report: &Value) -> Result<Vec<ValidatorMessage>> {
+    let bad = |what: &str| Error::ExternalValidatorUnavailable(format!("the validator's report {what}"));
+    let messages = report
+        .pointer("/issues/messages")
+        .and_then(Value::as_array)
+        .ok_or_else(|| bad("has no issues.messages list"))?;
+    messages
+        .iter()
+        .map(|message| {
+            let field = |key: &str| message.get(key).and_then(Value::as_str).map(str::to_string).ok_or_else(|| bad(&format!("has a message without a '{key}'")));
+            let severity = match message.get("severity").and_then(Value::as_u64) {
+                Some(0) => Severity::Error,
+                Some(1) => Severity::Warning,
+                Some(2) => Severity::Information,
+                Some(3) => Severity::Hint,
+                _ => return Err(bad("has a message with an unknown severity")),
+            };
+            Ok(ValidatorMessage {
+                code: field("code")?,
+                message: field("message")?,
+                severity,
+                json_pointer: message.get("pointer").and_then(Value::as_str).unwrap_or_default().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Merge `messages` into our own findings: an [Error::ExternalValidation] listing the errors if there are any,
+/// and a warning for each warning. Information and hints are dropped.
+pub fn into_findings(messages: Vec<ValidatorMessage>) -> (Option<Error>, Vec<Warning>) {
+    let (errors, rest): (Vec<_>, Vec<_>) = messages.into_iter().partition(|message| message.severity == Severity::Error);
+    let warnings = rest
+        .into_iter()
+        .filter(|message| message.severity == Severity::Warning)
+        .map(|message| Warning {
+            code: "external_validator_warning",
+            message: format!("glTF-Validator: {} ({})", message.message, message.code),
+            json_pointer: message.json_pointer,
+        })
+        .collect();
+    let error = (!errors.is_empty()).then_some(Error::ExternalValidation(errors));
+    (error, warnings)
+}
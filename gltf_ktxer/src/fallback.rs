@@ -0,0 +1,332 @@
+//! Retrying failed encodes with easier settings, and keeping the original image of a texture whose encoding still failed,
+//! instead of failing the whole run.
+//!
+//! Hosts run each of [crate::ReencodeJobs::new_images] however they like, through [encode_with_retries] to climb
+//! [crate::Params::retry_ladder], then hand every result to [finish_jobs], which applies [crate::Params::on_encode_failure].
+//! [encode_with_time_limit] also gives up on high-effort encodes which run past [crate::Params::max_encode_seconds_per_texture].
+
+use std::{
+    cell::RefCell,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
+    time::Duration,
+};
+
+use crate::{
+    edit, etc1s,
+    gltf::{GltfImage, GltfIndex},
+    tuning::EncodeEffort,
+    validate, Error, ImageReencodeFormat, ImageReencodeJob, KtxCodec, ReencodeJobs, Result,
+};
+
+/// A change to a job's settings which makes it easier on the encoder, tried when it fails, see [encode_with_retries].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Degradation {
+    /// Encode at the default quality rather than the one asked for, as high quality levels take the encoder down slower paths.
+    DefaultQuality,
+    /// Halve the largest dimension, for images too large for the encoder.
+    HalveResolution,
+    /// Switch from ETC1S to UASTC, which copes with more dimensions and content.
+    SwitchToUastc,
+    /// Encode with one step less [EncodeEffort], which [encode_with_time_limit] applies to encodes taking too long.
+    LowerEffort,
+}
+impl Degradation {
+    /// `job` with this applied, or None if it doesn't apply, e.g. [Degradation::SwitchToUastc] to a job already using UASTC.
+    pub fn apply(self, job: &ImageReencodeJob) -> Option<ImageReencodeJob> {
+        let (reencode_as, max_dimension) = match (self, job.reencode_as) {
+            (Degradation::DefaultQuality, ImageReencodeFormat::Ktx { basis_compression_quality: Some(_), .. }) => {
+                let mut reencode_as = job.reencode_as;
+                if let ImageReencodeFormat::Ktx { basis_compression_quality, .. } = &mut reencode_as {
+                    *basis_compression_quality = None;
+                }
+                (reencode_as, job.max_dimension)
+            }
+            (Degradation::HalveResolution, ImageReencodeFormat::Ktx { .. } | ImageReencodeFormat::Basic(_)) => {
+                let dimensions = job.source_dimensions()?;
+                let (width, height) = job.fit_dimensions(dimensions);
+                let halved = width.max(height) / 2;
+                if halved == 0 {
+                    return None;
+                }
+                (job.reencode_as, Some(halved))
+            }
+            (Degradation::LowerEffort, ImageReencodeFormat::Ktx { effort, .. }) if effort != Some(EncodeEffort::Fast) => {
+                let mut reencode_as = job.reencode_as;
+                if let ImageReencodeFormat::Ktx { effort, .. } = &mut reencode_as {
+                    *effort = Some(effort.unwrap_or(EncodeEffort::Balanced).lower());
+                }
+                (reencode_as, job.max_dimension)
+            }
+            (Degradation::SwitchToUastc, ImageReencodeFormat::Ktx { codec: KtxCodec::Etc1s, .. }) => {
+                let mut reencode_as = job.reencode_as;
+                if let ImageReencodeFormat::Ktx { codec, dither, .. } = &mut reencode_as {
+                    *codec = KtxCodec::Uastc;
+                    // Dithering is for ETC1S only
+                    *dither = None;
+                }
+                (reencode_as, job.max_dimension)
+            }
+            _ => return None,
+        };
+        Some(ImageReencodeJob {
+            source: job.source.clone(),
+            data_used_as_srgb: job.data_used_as_srgb,
+            reencode_as,
+            max_dimension,
+            repeats: job.repeats,
+            adjustments: job.adjustments,
+            transforms: job.transforms.clone(),
+            preexisting_buffer_view_idx: job.preexisting_buffer_view_idx,
+        })
+    }
+}
+impl std::str::FromStr for Degradation {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "default-quality" => Ok(Degradation::DefaultQuality),
+            "halve-resolution" => Ok(Degradation::HalveResolution),
+            "switch-to-uastc" => Ok(Degradation::SwitchToUastc),
+            "lower-effort" => Ok(Degradation::LowerEffort),
+            _ => Err(format!("unknown retry step '{s}', expected 'default-quality', 'halve-resolution', 'switch-to-uastc' or 'lower-effort'")),
+        }
+    }
+}
+impl std::fmt::Display for Degradation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Degradation::DefaultQuality => "default-quality",
+            Degradation::HalveResolution => "halve-resolution",
+            Degradation::SwitchToUastc => "switch-to-uastc",
+            Degradation::LowerEffort => "lower-effort",
+        })
+    }
+}
+
+/// The default [crate::Params::retry_ladder], from the change least noticeable in the output to the most.
+pub const DEFAULT_RETRY_LADDER: &[Degradation] = &[Degradation::DefaultQuality, Degradation::HalveResolution, Degradation::SwitchToUastc];
+
+/// The output of a job, and the degradations applied, in order, before it succeeded.
+/// The output is the encoded data, or e.g. an unserialized [crate::pipeline::EncodedImage].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Encoded<T = Vec<u8>> {
+    pub data: T,
+    pub degradations: Vec<Degradation>,
+    /// Whether the first encode was abandoned for taking too long, see [encode_with_time_limit].
+    pub timed_out: bool,
+}
+impl<T> From<T> for Encoded<T> {
+    fn from(data: T) -> Self {
+        Self { data, degradations: vec![], timed_out: false }
+    }
+}
+
+/// Run `job` with `encode`, and if it fails, retry after each step of `ladder` in turn, each on top of the ones before it.
+/// Steps which don't apply to the job are skipped.
+/// Fails with the original error if every step fails, or without retrying if the encoder isn't available at all.
+pub fn encode_with_retries<T>(job: &ImageReencodeJob, ladder: &[Degradation], encode: impl Fn(&ImageReencodeJob) -> Result<T>) -> Result<Encoded<T>> {
+    match encode(job) {
+        Ok(data) => Ok(data.into()),
+        Err(e) => retry(job, ladder, e, encode),
+    }
+}
+
+/// Climb `ladder` after `job` failed with `e`, see [encode_with_retries].
+fn retry<T>(job: &ImageReencodeJob, ladder: &[Degradation], e: Error, encode: impl Fn(&ImageReencodeJob) -> Result<T>) -> Result<Encoded<T>> {
+    if matches!(e.without_location(), Error::EncoderUnavailable(_)) {
+        return Err(e);
+    }
+    let mut degraded: Option<ImageReencodeJob> = None;
+    let mut degradations = vec![];
+    for &step in ladder {
+        let Some(next) = step.apply(degraded.as_ref().unwrap_or(job)) else {
+            continue;
+        };
+        degradations.push(step);
+        if let Ok(data) = encode(&next) {
+            return Ok(Encoded { data, degradations, timed_out: false });
+        }
+        degraded = Some(next);
+    }
+    Err(e)
+}
+
+/// Whether `job` is an ETC1S encode above the default quality or at [EncodeEffort::Thorough],
+/// either of which can take the encoder down much slower paths.
+pub fn is_high_effort(job: &ImageReencodeJob) -> bool {
+    match job.reencode_as {
+        ImageReencodeFormat::Ktx { codec: KtxCodec::Etc1s, basis_compression_quality, effort, .. } => {
+            basis_compression_quality.is_some_and(|quality| quality.get() > etc1s::DEFAULT_QUALITY) || effort == Some(EncodeEffort::Thorough)
+        }
+        _ => false,
+    }
+}
+
+/// The time limit [crate::Params::max_encode_seconds_per_texture] gives, failing with [Error::BadTimeLimit] if it isn't a duration.
+pub fn time_limit(seconds: Option<f64>) -> Result<Option<Duration>> {
+    seconds.map(|seconds| Duration::try_from_secs_f64(seconds).map_err(|_| Error::BadTimeLimit(seconds))).transpose()
+}
+
+thread_local! {
+    /// Raised by [encode_with_time_limit] when it abandons the encode running on this thread, see [check_abandoned].
+    static ABANDONED: RefCell<Option<Arc<AtomicBool>>> = const { RefCell::new(None) };
+}
+
+/// Fail with [Error::EncodeAbandoned] if [encode_with_time_limit] has given up on the encode running on this thread,
+/// so it stops using CPU nobody is waiting on. Encoders call this between steps; outside a time-limited encode it always succeeds.
+pub fn check_abandoned() -> Result<()> {
+    match ABANDONED.with(|abandoned| abandoned.borrow().as_ref().is_some_and(|abandoned| abandoned.load(Ordering::Relaxed))) {
+        true => Err(Error::EncodeAbandoned),
+        false => Ok(()),
+    }
+}
+
+/// Like [encode_with_retries], but if `job` [is_high_effort] and `encode` takes longer than `limit`,
+/// abandon it and encode with less effort instead: [Degradation::LowerEffort], and [Degradation::DefaultQuality]
+/// if it asked for a higher quality, recorded first in the output.
+///
+/// The abandoned encode runs on a thread of its own, which stops at the encoder's next [check_abandoned],
+/// so the time it keeps using a core past the limit is short.
+/// If `encode` panics, the panic is resumed on the calling thread.
+pub fn encode_with_time_limit<T: Send + 'static>(
+    job: &ImageReencodeJob,
+    ladder: &[Degradation],
+    limit: Option<Duration>,
+    encode: impl Fn(&ImageReencodeJob) -> Result<T> + Clone + Send + 'static,
+) -> Result<Encoded<T>> {
+    let Some(limit) = limit.filter(|_| is_high_effort(job)) else {
+        return encode_with_retries(job, ladder, encode);
+    };
+    let (sender, receiver) = mpsc::channel();
+    let abandoned = Arc::new(AtomicBool::new(false));
+    let (timed_job, timed_encode, timed_abandoned) = (job.clone(), encode.clone(), abandoned.clone());
+    let handle = std::thread::spawn(move || {
+        ABANDONED.with(|abandoned| *abandoned.borrow_mut() = Some(timed_abandoned));
+        // The receiver is gone if the encode was abandoned
+        let _ = sender.send(timed_encode(&timed_job));
+    });
+    match receiver.recv_timeout(limit) {
+        Ok(Ok(data)) => Ok(data.into()),
+        Ok(Err(e)) => retry(job, ladder, e, encode),
+        Err(mpsc::RecvTimeoutError::Disconnected) => match handle.join() {
+            Err(payload) => std::panic::resume_unwind(payload),
+            Ok(()) => unreachable!("the encoder thread sends its result unless it panics"),
+        },
+        Err(mpsc::RecvTimeoutError::Timeout) => {
+            abandoned.store(true, Ordering::Relaxed);
+            // The default quality would be more effort for jobs asking for less
+            let above_default_quality = matches!(job.reencode_as, ImageReencodeFormat::Ktx { basis_compression_quality: Some(quality), .. } if quality.get() > etc1s::DEFAULT_QUALITY);
+            let steps = [Degradation::LowerEffort, Degradation::DefaultQuality].into_iter().filter(|&step| step != Degradation::DefaultQuality || above_default_quality);
+            let mut lower_effort = job.clone();
+            let mut degradations = vec![];
+            for step in steps {
+                if let Some(next) = step.apply(&lower_effort) {
+                    lower_effort = next;
+                    degradations.push(step);
+                }
+            }
+            let mut encoded = encode_with_retries(&lower_effort, ladder, encode)?;
+            degradations.append(&mut encoded.degradations);
+            encoded.degradations = degradations;
+            encoded.timed_out = true;
+            Ok(encoded)
+        }
+    }
+}
+
+/// What to do when a job fails, e.g. because its source is corrupt or has dimensions the encoder can't handle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum EncodeFailurePolicy {
+    /// Fail the run with the first failure.
+    #[default]
+    Abort,
+    /// Output the source image unchanged in place of the failed image, with a warning.
+    KeepOriginal,
+}
+impl std::str::FromStr for EncodeFailurePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "abort" => Ok(EncodeFailurePolicy::Abort),
+            "keep-original" => Ok(EncodeFailurePolicy::KeepOriginal),
+            _ => Err(format!("unknown encode failure policy '{s}', expected 'abort' or 'keep-original'")),
+        }
+    }
+}
+impl std::fmt::Display for EncodeFailurePolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            EncodeFailurePolicy::Abort => "abort",
+            EncodeFailurePolicy::KeepOriginal => "keep-original",
+        })
+    }
+}
+
+/// The output data of each of `jobs`, given `results`, the result of running each job in order.
+///
+/// Jobs which only succeeded after [encode_with_retries] degraded them are updated to match their output,
+/// with a warning listing the degradations added to [ReencodeJobs::warnings].
+/// With [EncodeFailurePolicy::Abort], the first failure is returned, located at its image.
+/// With [EncodeFailurePolicy::KeepOriginal], each failed job becomes an [ImageReencodeFormat::Copy] of its source,
+/// texture extensions pointing at it are removed so they don't claim the wrong format, and a warning recording the failure is added.
+pub fn finish_jobs<T: From<Vec<u8>>>(jobs: &mut ReencodeJobs, results: Vec<Result<Encoded<T>>>, policy: EncodeFailurePolicy) -> Result<Vec<T>> {
+    assert_eq!(results.len(), jobs.new_images.len(), "one result per job");
+    let mut outputs = Vec::with_capacity(results.len());
+    for (idx, result) in results.into_iter().enumerate() {
+        let e = match (result, policy) {
+            (Ok(encoded), _) => {
+                if !encoded.degradations.is_empty() {
+                    let job = &mut jobs.new_images[idx];
+                    for step in &encoded.degradations {
+                        *job = step.apply(job).expect("the step applied when retrying");
+                    }
+                    let steps: Vec<String> = encoded.degradations.iter().map(ToString::to_string).collect();
+                    let problem = if encoded.timed_out { "encoding took longer than the time limit" } else { "encoding failed" };
+                    jobs.warnings.push(validate::Warning {
+                        code: "encode_degraded",
+                        json_pointer: format!("/images/{idx}"),
+                        message: format!("{problem}, and succeeded after retrying with {}", steps.join(", ")),
+                    });
+                }
+                outputs.push(encoded.data);
+                continue;
+            }
+            (Err(e), EncodeFailurePolicy::Abort) => return Err(e.at(format!("/images/{idx}"))),
+            (Err(e), EncodeFailurePolicy::KeepOriginal) => e,
+        };
+        let job = &mut jobs.new_images[idx];
+        job.reencode_as = ImageReencodeFormat::Copy;
+        outputs.push(job.source.data.clone().into());
+        keep_original_in_textures(jobs, GltfIndex::of(idx));
+        jobs.warnings.push(validate::Warning {
+            code: "encode_failed",
+            json_pointer: format!("/images/{idx}"),
+            message: format!("encoding failed ({e}), so the original image is kept"),
+        });
+    }
+    Ok(outputs)
+}
+
+/// Remove the source extensions pointing at `image` from every texture, making it the texture's `source` if it had none.
+fn keep_original_in_textures(jobs: &mut ReencodeJobs, image: GltfIndex<GltfImage>) {
+    for texture in &mut jobs.new_textures {
+        let pointing: Vec<&str> = edit::TEXTURE_SOURCE_EXTENSIONS.iter().copied().filter(|&ext| edit::texture_extension_source(texture, ext) == Some(image)).collect();
+        if pointing.is_empty() {
+            continue;
+        }
+        if let Some(extensions) = texture.extensions.as_mut() {
+            extensions.retain(|name, _| !pointing.contains(&name.as_str()));
+        }
+        if texture.extensions.as_ref().is_some_and(|extensions| extensions.is_empty()) {
+            texture.extensions = None;
+        }
+        if !texture.source.is_defined() {
+            texture.source = image;
+        }
+    }
+}
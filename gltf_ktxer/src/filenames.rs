@@ -0,0 +1,120 @@
+//! Naming external files after the images they come from, e.g. the `.basis` files of [crate::basis::export_basis_files].
+//!
+//! `image.name` is free text, so it may hold path separators, characters Windows forbids, names Windows reserves like `CON`,
+//! or more bytes than a file system allows. Names are cleaned up into a single path component which is valid on every OS,
+//! and names which still clash (ignoring case, as on Windows and macOS) get the image's index as a suffix,
+//! so the same document always gets the same names.
+
+use std::collections::HashSet;
+
+use crate::{
+    gltf::{deserialize_list, GltfDoc, GltfImage},
+    load, Result,
+};
+
+/// The longest stem kept from a name, in bytes, leaving room for a suffix, an extension, and the output directory
+/// within Windows' 260 character path limit.
+pub const MAX_STEM_BYTES: usize = 100;
+
+/// Device names Windows reserves whatever their extension, compared ignoring case.
+const WINDOWS_RESERVED: &[&str] = &[
+    "con", "prn", "aux", "nul", "com0", "com1", "com2", "com3", "com4", "com5", "com6", "com7", "com8", "com9", "com¹", "com²", "com³", "lpt0",
+    "lpt1", "lpt2", "lpt3", "lpt4", "lpt5", "lpt6", "lpt7", "lpt8", "lpt9", "lpt¹", "lpt²", "lpt³",
+];
+
+/// `name` made safe to use as a file stem, or [None] if nothing usable is left.
+///
+/// Letters and digits of any script are kept, along with `-`, `_` and `.`. Anything else, including spaces, separators,
+/// and invisible characters like bidi overrides, becomes `_`. Leading and trailing dots and underscores are removed,
+/// so the result is never `.`, `..` or a hidden file, and reserved Windows device names get a trailing `_`.
+pub fn sanitize_stem(name: &str) -> Option<String> {
+    let mut stem = String::new();
+    for c in name.chars() {
+        let c = if c.is_alphanumeric() || c == '-' || c == '.' { c } else { '_' };
+        if !(c == '_' && stem.ends_with('_')) {
+            stem.push(c);
+        }
+    }
+    let mut stem = truncate(stem.trim_matches(['.', '_']), MAX_STEM_BYTES).trim_end_matches(['.', '_']).to_string();
+    if stem.is_empty() {
+        return None;
+    }
+    // Windows treats `con.tar.gz` as `con` too
+    let device = stem.split('.').next().unwrap_or_default().to_lowercase();
+    if WINDOWS_RESERVED.contains(&device.as_str()) {
+        stem.insert(device.len(), '_');
+    }
+    Some(stem)
+}
+
+/// The longest prefix of `s` of at most `max_bytes` bytes, without splitting a character.
+fn truncate(s: &str, max_bytes: usize) -> &str {
+    if s.len() <= max_bytes {
+        return s;
+    }
+    let end = (0..=max_bytes).rev().find(|&i| s.is_char_boundary(i)).unwrap_or(0);
+    &s[..end]
+}
+
+/// Percent-encode `file_name` for use as a relative glTF URI, which must not hold spaces or non-ASCII characters unescaped.
+pub fn file_uri(file_name: &str) -> String {
+    let mut uri = String::new();
+    for byte in file_name.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => uri.push(byte as char),
+            _ => uri.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    uri
+}
+
+/// Hands out file names which don't clash with each other, ignoring case.
+#[derive(Debug, Default)]
+pub struct FileNamer {
+    taken: HashSet<String>,
+}
+impl FileNamer {
+    /// A file name for the item at `idx`, made from `name` (falling back to `fallback` if nothing of `name` is usable)
+    /// and `extension`. If the name is taken, `-{idx}` is added, and then a counter if that's taken too.
+    pub fn name(&mut self, name: Option<&str>, fallback: &str, idx: usize, extension: &str) -> String {
+        let stem = name.and_then(sanitize_stem).unwrap_or_else(|| fallback.to_string());
+        let with_extension = |stem: &str| if extension.is_empty() { stem.to_string() } else { format!("{stem}.{extension}") };
+        let candidates = std::iter::once(stem.clone())
+            .chain(std::iter::once(format!("{stem}-{idx}")))
+            .chain((2..).map(|n| format!("{stem}-{idx}-{n}")));
+        for candidate in candidates {
+            let file_name = with_extension(&candidate);
+            if self.taken.insert(file_name.to_lowercase()) {
+                return file_name;
+            }
+        }
+        unreachable!("the counter never runs out")
+    }
+}
+
+/// A file name for every image in `doc`, in image order, with the given `extension`.
+///
+/// Names come from `image.name`, or the file name of the image's URI if it has no name, or `image{idx}` otherwise.
+/// Use [file_uri] to refer to the files from the document.
+pub fn image_file_names(doc: &GltfDoc, extension: &str) -> Result<Vec<String>> {
+    let images: Vec<GltfImage> = deserialize_list(doc, "images")?;
+    let mut namer = FileNamer::default();
+    Ok(images
+        .iter()
+        .enumerate()
+        .map(|(idx, image)| namer.name(image_stem(image).as_deref(), &format!("image{idx}"), idx, extension))
+        .collect())
+}
+
+/// What to name files made from `image`: its name, or the file name of its URI without the extension if it has no usable name.
+pub fn image_stem(image: &GltfImage) -> Option<String> {
+    let uri_stem = image.uri.as_ref().filter(|uri| !uri.is_data_uri()).map(|uri| {
+        let uri = uri.as_str();
+        let file_name = load::percent_decode(uri.rsplit('/').next().unwrap_or(uri));
+        match file_name.rsplit_once('.') {
+            Some((stem, _)) if !stem.is_empty() => stem.to_string(),
+            _ => file_name,
+        }
+    });
+    image.name.clone().filter(|name| sanitize_stem(name).is_some()).or(uri_stem)
+}
@@ -0,0 +1,87 @@
+//! Fingerprints of what each output image was made from, stored in the output itself so the next run can re-encode
+//! only the images whose sources changed and reuse the rest from the previous output.
+//!
+//! [record_fingerprints] stores one fingerprint per output image in the document's `asset`:
+//! ```json
+//! "asset": { "extras": { "GLTF_KTXER_fingerprints": [{ "source": "sha256:...", "encodeParams": "sha256:..." }, ...] } }
+//! ```
+//! with the same hashes as [crate::hash]. Fingerprints are matched on both hashes rather than on the image's position,
+//! so adding or reordering textures doesn't stop the others being reused, and changed settings re-encode like changed sources.
+
+use std::collections::HashMap;
+
+use serde_json::{json, Value};
+
+use crate::{
+    gltf::{GltfBuffer, GltfBufferView, GltfDoc, GltfImage, GltfList, GltfIndex, U8VecOrSlice},
+    Error, ImageReencodeJob, Input, Result,
+};
+
+/// The key in `asset.extras` holding the fingerprints of the output images.
+pub const FINGERPRINTS_EXTRAS_KEY: &str = "GLTF_KTXER_fingerprints";
+
+/// Record the fingerprint of each of `jobs` in `doc`, where `jobs` are the [crate::ReencodeJobs::new_images] the output's images came from.
+pub fn record_fingerprints(doc: &mut GltfDoc, jobs: &[ImageReencodeJob]) -> Result<()> {
+    let asset = doc.entry("asset").or_insert_with(|| json!({})).as_object_mut().ok_or(Error::ExpectedObject { key: "asset" })?;
+    let extras = asset.entry("extras").or_insert_with(|| json!({})).as_object_mut().ok_or(Error::ExpectedObject { key: "extras" })?;
+    extras.insert(FINGERPRINTS_EXTRAS_KEY.to_string(), jobs.iter().map(ImageReencodeJob::hash_extras).collect());
+    Ok(())
+}
+
+/// The images of a previous output, keyed on their fingerprints.
+#[derive(Debug, Default)]
+pub struct PreviousOutput {
+    images: HashMap<Value, Vec<u8>>,
+}
+impl PreviousOutput {
+    /// Read the fingerprinted images of a previous output.
+    /// Outputs written without fingerprints, and fingerprints of images which no longer exist, give nothing to reuse.
+    pub fn read(input: &Input<'_>) -> Result<Self> {
+        let fingerprints = input
+            .gltf_json
+            .get("asset")
+            .and_then(|asset| asset.get("extras"))
+            .and_then(|extras| extras.get(FINGERPRINTS_EXTRAS_KEY))
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let images: Vec<GltfImage> = input.get_list("images")?;
+        let buffer_views: Vec<GltfBufferView> = input.get_list("bufferViews")?;
+        let buffers: Vec<GltfBuffer> = input.get_list("buffers")?;
+        let buffer_datas: Vec<U8VecOrSlice<'_>> = buffers
+            .into_iter()
+            .enumerate()
+            .map(|(idx, b)| b.dump_data(idx, input.binaries))
+            .collect::<Result<_>>()?;
+        let mut previous = Self::default();
+        for (idx, fingerprint) in fingerprints.iter().enumerate() {
+            let Some(image) = images.gltf_index(GltfIndex::of(idx), "images")? else {
+                continue;
+            };
+            if previous.images.contains_key(fingerprint) {
+                continue;
+            }
+            let data = image.dump_data(&buffer_views, &buffer_datas, input.binaries).map_err(|e| e.at(format!("/images/{idx}")))?;
+            previous.images.insert(fingerprint.clone(), data.to_vec());
+        }
+        Ok(previous)
+    }
+
+    /// The data previously produced for `job`, if neither its source nor its settings have changed since.
+    pub fn reusable(&self, job: &ImageReencodeJob) -> Option<&[u8]> {
+        self.images.get(&job.hash_extras()).map(Vec::as_slice)
+    }
+
+    /// The number of distinct images which can be reused.
+    pub fn len(&self) -> usize {
+        self.images.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.images.is_empty()
+    }
+}
+
+/// The indices of the `jobs` which need encoding, as [PreviousOutput::reusable] has nothing for them.
+pub fn changed_jobs(jobs: &[ImageReencodeJob], previous: &PreviousOutput) -> Vec<usize> {
+    jobs.iter().enumerate().filter(|(_, job)| previous.reusable(job).is_none()).map(|(idx, _)| idx).collect()
+}
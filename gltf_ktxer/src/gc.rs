@@ -0,0 +1,66 @@
+//! Removal of objects nothing uses any more, such as the original images once every texture points at its KTX2 copy.
+//!
+//! Only the lists in [COLLECTED_LISTS] are ever removed from. Everything else is a root and is never modified:
+//! `scene`, `scenes`, the node hierarchy, meshes, skins, animations and so on.
+//! Anything a root refers to, directly or through other collected objects, is kept,
+//! so objects used only by a scene other than the default one survive.
+//! Objects referred to out-of-band, e.g. from `extras` a game engine reads itself, can be kept by registering
+//! those references with [ReferenceRegistry::register_root].
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde_json::Value;
+
+use crate::{edit::{self, ReferenceRegistry}, gltf::{GltfDoc, GltfIndex}, Result};
+
+/// The lists objects are removed from, in the order they're swept.
+/// Each list is only referred to by roots and the lists before it, so sweeping in order never removes anything still referenced.
+pub const COLLECTED_LISTS: &[&str] = &["materials", "textures", "images", "accessors", "bufferViews"];
+
+/// The indices of the objects in each of [COLLECTED_LISTS] which are reachable from the roots.
+/// References without a holder, such as [edit::CustomReference]s in the document's own `extensions`, are roots too.
+pub fn live_objects(doc: &GltfDoc, registry: &ReferenceRegistry) -> Result<BTreeMap<&'static str, BTreeSet<usize>>> {
+    let mut live: BTreeMap<&'static str, BTreeSet<usize>> = COLLECTED_LISTS.iter().map(|&list_name| (list_name, BTreeSet::new())).collect();
+    loop {
+        let mut changed = false;
+        for &list_name in COLLECTED_LISTS {
+            let mut reached = vec![];
+            edit::for_each_reference_by_holder(doc, list_name, registry, &mut |holder, reference| {
+                let holder_is_live = holder.is_none_or(|(holder_list, holder_idx)| {
+                    live.get(holder_list).is_none_or(|holders| holders.contains(&holder_idx))
+                });
+                if let Some(idx) = reference.as_u64().filter(|_| holder_is_live) {
+                    reached.push(idx as usize);
+                }
+            })?;
+            let live = live.get_mut(list_name).expect("every collected list has a live set");
+            for idx in reached {
+                changed |= live.insert(idx);
+            }
+        }
+        if !changed {
+            return Ok(live);
+        }
+    }
+}
+
+/// Remove every object in [COLLECTED_LISTS] which isn't reachable from the roots, renumbering references to the rest.
+/// Returns the original indices of the removed objects in each list, leaving out lists nothing was removed from.
+///
+/// References in vendor extensions must be registered in `registry`, or the objects they refer to may be removed or renumbered under them.
+pub fn collect_garbage(doc: &mut GltfDoc, registry: &ReferenceRegistry) -> Result<BTreeMap<&'static str, Vec<usize>>> {
+    let live = live_objects(doc, registry)?;
+    let mut removed = BTreeMap::new();
+    for &list_name in COLLECTED_LISTS {
+        let len = doc.get(list_name).and_then(Value::as_array).map_or(0, Vec::len);
+        let dead: Vec<usize> = (0..len).filter(|idx| !live[list_name].contains(idx)).collect();
+        // Remove from the back, so the indices of the remaining dead objects don't shift
+        for &idx in dead.iter().rev() {
+            edit::remove_with::<Value>(doc, list_name, GltfIndex::of(idx), registry)?;
+        }
+        if !dead.is_empty() {
+            removed.insert(list_name, dead);
+        }
+    }
+    Ok(removed)
+}
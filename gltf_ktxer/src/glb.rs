@@ -0,0 +1,127 @@
+//! Serialization of glTF documents as `.gltf` JSON or binary `.glb` containers, and reading `.glb` containers back.
+
+use crate::{gltf::GltfDoc, Error, Result};
+
+/// glTF2.0 section 4.4.3.1: the `magic` field of the GLB header, "glTF" as a little-endian u32.
+pub const GLB_MAGIC: u32 = 0x46546C67;
+pub const GLB_VERSION: u32 = 2;
+pub const CHUNK_TYPE_JSON: u32 = 0x4E4F534A;
+pub const CHUNK_TYPE_BIN: u32 = 0x004E4942;
+/// The magic, version and length words before the first chunk.
+const GLB_HEADER_LEN: usize = 12;
+
+/// The GLB header and chunk lengths are u32s, so no GLB can be longer than this.
+pub const MAX_GLB_LEN: u64 = u32::MAX as u64;
+
+/// How the JSON of an output document is laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum JsonFormat {
+    /// No whitespace at all, for the smallest output.
+    #[default]
+    Minified,
+    /// Indented, one property per line, for readable diffs.
+    Pretty,
+}
+
+/// Serialize `doc` in the given format.
+pub fn json_bytes(doc: &GltfDoc, format: JsonFormat) -> Result<Vec<u8>> {
+    Ok(match format {
+        JsonFormat::Minified => serde_json::to_vec(doc)?,
+        JsonFormat::Pretty => serde_json::to_vec_pretty(doc)?,
+    })
+}
+
+/// The total length of a GLB with a JSON chunk of `json_len` bytes and a binary chunk of `bin_len` bytes, before padding.
+///
+/// Fails with [Error::GlbTooLarge] if it's over [MAX_GLB_LEN], so the output should be written as `.gltf` with separate binaries instead.
+pub fn glb_len(json_len: usize, bin_len: usize) -> Result<u32> {
+    let mut total_len = GLB_HEADER_LEN as u64 + 8 + (json_len as u64).next_multiple_of(4);
+    if bin_len != 0 {
+        total_len += 8 + (bin_len as u64).next_multiple_of(4);
+    }
+    u32::try_from(total_len).map_err(|_| Error::GlbTooLarge { bytes: total_len })
+}
+
+/// Write `doc` and the binary chunk `bin` as a GLB container.
+/// The BIN chunk is left out entirely if `bin` is empty.
+/// Fails with [Error::GlbTooLarge] if the result wouldn't fit the GLB's 32-bit lengths, see [glb_len].
+///
+/// glTF2.0 section 4.4.3.2: "The start and the end of each chunk MUST be aligned to a 4-byte boundary [...]
+/// The JSON Chunk MUST be padded with trailing Space chars (0x20) to satisfy alignment requirements.
+/// The BIN Chunk MUST be padded with trailing zeros (0x00) to satisfy alignment requirements."
+pub fn write(doc: &GltfDoc, bin: &[u8], format: JsonFormat) -> Result<Vec<u8>> {
+    let json = json_bytes(doc, format)?;
+    // Every length below fits in a u32 because the total does
+    let total_len = glb_len(json.len(), bin.len())?;
+    let json_padded_len = json.len().next_multiple_of(4);
+    let bin_padded_len = bin.len().next_multiple_of(4);
+
+    let mut glb = Vec::with_capacity(total_len as usize);
+    glb.extend_from_slice(&GLB_MAGIC.to_le_bytes());
+    glb.extend_from_slice(&GLB_VERSION.to_le_bytes());
+    glb.extend_from_slice(&total_len.to_le_bytes());
+
+    glb.extend_from_slice(&(json_padded_len as u32).to_le_bytes());
+    glb.extend_from_slice(&CHUNK_TYPE_JSON.to_le_bytes());
+    glb.extend_from_slice(&json);
+    glb.resize(glb.len() + json_padded_len - json.len(), b' ');
+
+    if !bin.is_empty() {
+        glb.extend_from_slice(&(bin_padded_len as u32).to_le_bytes());
+        glb.extend_from_slice(&CHUNK_TYPE_BIN.to_le_bytes());
+        glb.extend_from_slice(bin);
+        glb.resize(glb.len() + bin_padded_len - bin.len(), 0);
+    }
+
+    debug_assert_eq!(glb.len(), total_len as usize);
+    Ok(glb)
+}
+
+/// Split a GLB container into its document and binary chunk, which is empty if the GLB has none.
+///
+/// The header's magic, version and length are checked, and every chunk must lie within that length,
+/// so a truncated file is an error rather than a document missing its binary data. Bytes after the length are ignored.
+/// glTF2.0 section 4.4.3.1: the JSON chunk must come first and the BIN chunk, if any, second.
+/// Chunks of unknown types are skipped, as section 4.4.3.2 requires.
+pub fn read(glb: &[u8]) -> Result<(GltfDoc, Vec<u8>)> {
+    let u32_at = |offset: usize| -> Result<u32> {
+        let word = glb.get(offset..offset + 4).ok_or(Error::GlbMalformed("header is truncated"))?;
+        Ok(u32::from_le_bytes(word.try_into().unwrap()))
+    };
+    if u32_at(0)? != GLB_MAGIC {
+        return Err(Error::GlbMalformed("file doesn't start with 'glTF'"));
+    }
+    if u32_at(4)? != GLB_VERSION {
+        return Err(Error::GlbMalformed("only version 2 is supported"));
+    }
+    let total_len = u32_at(8)? as usize;
+    if total_len < GLB_HEADER_LEN {
+        return Err(Error::GlbMalformed("length in the header is shorter than the header"));
+    }
+    let glb = glb.get(..total_len).ok_or(Error::GlbMalformed("file is shorter than the length in its header"))?;
+
+    let mut chunks = vec![];
+    let mut offset = GLB_HEADER_LEN;
+    while offset < total_len {
+        let header = glb.get(offset..offset + 8).ok_or(Error::GlbMalformed("chunk header is truncated"))?;
+        let chunk_len = u32::from_le_bytes(header[..4].try_into().unwrap()) as usize;
+        let chunk_type = u32::from_le_bytes(header[4..].try_into().unwrap());
+        let data = (offset + 8)
+            .checked_add(chunk_len)
+            .and_then(|end| glb.get(offset + 8..end))
+            .ok_or(Error::GlbMalformed("chunk is truncated"))?;
+        chunks.push((chunk_type, data));
+        offset += 8 + chunk_len.next_multiple_of(4);
+    }
+    let Some(&(CHUNK_TYPE_JSON, json)) = chunks.first() else {
+        return Err(Error::GlbMalformed("the first chunk must be JSON"));
+    };
+    if chunks.iter().skip(1).any(|&(chunk_type, _)| chunk_type == CHUNK_TYPE_JSON) {
+        return Err(Error::GlbMalformed("there must be only one JSON chunk"));
+    }
+    if chunks.iter().skip(2).any(|&(chunk_type, _)| chunk_type == CHUNK_TYPE_BIN) {
+        return Err(Error::GlbMalformed("the BIN chunk must be the second chunk"));
+    }
+    let bin = chunks.get(1).filter(|&&(chunk_type, _)| chunk_type == CHUNK_TYPE_BIN).map_or(&[][..], |(_, data)| data);
+    Ok((serde_json::from_slice(json)?, bin.to_vec()))
+}
@@ -1,4 +1,4 @@
-use std::{collections::HashMap, hash::Hash, marker::PhantomData, ops::{Deref, Index}, slice::SliceIndex, u64};
+use std::{collections::{BTreeMap, HashMap}, hash::Hash, marker::PhantomData, ops::{Deref, Index}, slice::SliceIndex};
 
 use crate::{Error, Result};
 
@@ -7,9 +7,26 @@ use serde_derive::{Deserialize, Serialize};
 
 pub type GltfDoc = serde_json::Map<String, serde_json::Value>;
 
+/// Deserialize every element of the top-level list `name`, or return an empty list if the document doesn't have it.
+/// Errors are located at the element which failed to deserialize.
+pub(crate) fn deserialize_list<T: serde::de::DeserializeOwned>(doc: &GltfDoc, name: &str) -> Result<Vec<T>> {
+    match doc.get(name) {
+        None => Ok(vec![]),
+        Some(serde_json::Value::Array(items)) => items
+            .iter()
+            .enumerate()
+            .map(|(idx, item)| serde::Deserialize::deserialize(item).map_err(|e| Error::from(e).at(format!("/{name}/{idx}"))))
+            .collect(),
+        Some(value) => serde::Deserialize::deserialize(value).map_err(|e| Error::from(e).at(format!("/{name}"))),
+    }
+}
+/// The `extensions` property of any glTF object, mapping extension names to extension-specific objects.
+pub type GltfExtensions = serde_json::Map<String, serde_json::Value>;
+
 /// A wrapper for u64 that uses the maximum value as a sentinel for undefined.
 /// Defaults to undefined.
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+/// Fields of this type should be `#[serde(default, skip_serializing_if = "GltfIndex::is_undefined")]`.
+#[derive(Debug, PartialEq, Eq)]
 pub struct GltfIndex<T>(usize, PhantomData<T>);
 impl<T> GltfIndex<T> {
     pub const UNDEFINED: Self = Self(usize::MAX, PhantomData);
@@ -53,10 +70,20 @@ impl<T> Hash for GltfIndex<T> {
 }
 impl<T> Clone for GltfIndex<T> {
     fn clone(&self) -> Self {
-        Self::of(self.0.clone())
+        *self
     }
 }
 impl<T> Copy for GltfIndex<T> {}
+impl<T> serde::Serialize for GltfIndex<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_u64(self.0 as u64)
+    }
+}
+impl<'de, T> serde::Deserialize<'de> for GltfIndex<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        <usize as serde::Deserialize>::deserialize(deserializer).map(GltfIndex::of)
+    }
+}
 
 pub trait GltfList<T> : std::ops::Index<usize, Output = T> + Sized {
     fn gltf_index(&self, idx: GltfIndex<T>, list_name: &'static str) -> Result<Option<&T>>;
@@ -76,24 +103,61 @@ impl<T> GltfList<T> for Vec<T> {
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub struct GltfUri(String);
+impl GltfUri {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+    pub fn is_data_uri(&self) -> bool {
+        self.0.starts_with("data:")
+    }
+    /// Embed `data` in a buffer data URI.
+    pub fn buffer_data_uri(data: &[u8]) -> Self {
+        GltfUri(format!("data:application/octet-stream;base64,{}", BASE64_STANDARD.encode(data)))
+    }
+}
+impl From<String> for GltfUri {
+    fn from(value: String) -> Self {
+        GltfUri(value)
+    }
+}
+impl From<&str> for GltfUri {
+    fn from(value: &str) -> Self {
+        GltfUri(value.to_string())
+    }
+}
 
 /// A buffer points to binary geometry, animation, or skins.
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq, Clone)]
 pub struct GltfBuffer {
     /// The URI (or IRI) of the buffer.
     /// Relative paths are relative to the current glTF asset.
     /// Instead of referencing an external file, this field **MAY** contain a `data:`-URI.
     /// It may also be None if referencing a KTX
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub uri: Option<GltfUri>,
     /// The length of the buffer in bytes.
     #[serde(rename = "byteLength")]
     pub byte_length: usize,
-    pub name: serde_json::Value,
-    pub extensions: serde_json::Value,
-    pub extras: serde_json::Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extensions: Option<GltfExtensions>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extras: Option<serde_json::Value>,
 }
 
 impl GltfBuffer {
+    /// A buffer with no uri, i.e. the GLB binary chunk if this is buffer 0.
+    pub fn new(byte_length: usize) -> Self {
+        Self { byte_length, ..Default::default() }
+    }
+    pub fn with_uri(self, uri: impl Into<GltfUri>) -> Self {
+        Self { uri: Some(uri.into()), ..self }
+    }
+    pub fn with_name(self, name: impl Into<String>) -> Self {
+        Self { name: Some(name.into()), ..self }
+    }
+
     pub fn dump_data<'a>(&self, idx: usize, map: &'a HashMap<Option<String>, Vec<u8>>) -> Result<U8VecOrSlice<'a>> {
         match &self.uri {
             None if idx == 0 => match map.get(&None) {
@@ -125,9 +189,10 @@ impl<'a> From<GltfIndex<GltfBuffer>> for GltfIndex<U8VecOrSlice<'a>> {
 }
 
 /// A view into a buffer generally representing a subset of the buffer.
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq, Clone)]
 pub struct GltfBufferView {
     /// The index of the buffer.
+    #[serde(default, skip_serializing_if = "GltfIndex::is_undefined")]
     pub buffer: GltfIndex<GltfBuffer>,
     /// The offset into the buffer in bytes.
     #[serde(rename = "byteOffset", default)]
@@ -138,16 +203,33 @@ pub struct GltfBufferView {
     /// The stride, in bytes, between vertex attributes.
     /// When this is not defined, data is tightly packed.
     /// When two or more accessors use the same buffer view, this field **MUST** be defined.
-    #[serde(rename = "byteStride")]
+    #[serde(rename = "byteStride", default, skip_serializing_if = "Option::is_none")]
     pub byte_stride: Option<usize>,
     /// The hint representing the intended GPU buffer type to use with this buffer view.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub target: Option<u64>,
-    pub name: serde_json::Value,
-    pub extensions: serde_json::Value,
-    pub extras: serde_json::Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extensions: Option<GltfExtensions>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extras: Option<serde_json::Value>,
 }
 
 impl GltfBufferView {
+    pub fn new(buffer: GltfIndex<GltfBuffer>, byte_offset: usize, byte_length: usize) -> Self {
+        Self { buffer, byte_offset, byte_length, ..Default::default() }
+    }
+    pub fn with_stride(self, byte_stride: usize) -> Self {
+        Self { byte_stride: Some(byte_stride), ..self }
+    }
+    pub fn with_target(self, target: u64) -> Self {
+        Self { target: Some(target), ..self }
+    }
+    pub fn with_name(self, name: impl Into<String>) -> Self {
+        Self { name: Some(name.into()), ..self }
+    }
+
     pub fn slice_from<'a>(&self, buffer_datas: &'a Vec<U8VecOrSlice<'a>>) -> Result<&'a [u8]> {
         let buffer = buffer_datas.gltf_index_required(self.buffer.into(), "buffers")?;
         if self.byte_offset + self.byte_length > buffer.len() {
@@ -159,48 +241,213 @@ impl GltfBufferView {
     }
 }
 
+/// Texture sampler properties for filtering and wrapping modes.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq, Clone)]
+pub struct GltfSampler {
+    /// Magnification filter, one of the `FILTER_` constants.
+    #[serde(rename = "magFilter", default, skip_serializing_if = "Option::is_none")]
+    pub mag_filter: Option<u32>,
+    /// Minification filter, one of the `FILTER_` constants.
+    #[serde(rename = "minFilter", default, skip_serializing_if = "Option::is_none")]
+    pub min_filter: Option<u32>,
+    /// S (U) wrapping mode, one of the `WRAP_` constants. Defaults to [GltfSampler::WRAP_REPEAT].
+    #[serde(rename = "wrapS", default, skip_serializing_if = "Option::is_none")]
+    pub wrap_s: Option<u32>,
+    /// T (V) wrapping mode, one of the `WRAP_` constants. Defaults to [GltfSampler::WRAP_REPEAT].
+    #[serde(rename = "wrapT", default, skip_serializing_if = "Option::is_none")]
+    pub wrap_t: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extensions: Option<GltfExtensions>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extras: Option<serde_json::Value>,
+}
+impl GltfSampler {
+    pub const FILTER_NEAREST: u32 = 9728;
+    pub const FILTER_LINEAR: u32 = 9729;
+    pub const FILTER_NEAREST_MIPMAP_NEAREST: u32 = 9984;
+    pub const FILTER_LINEAR_MIPMAP_NEAREST: u32 = 9985;
+    pub const FILTER_NEAREST_MIPMAP_LINEAR: u32 = 9986;
+    pub const FILTER_LINEAR_MIPMAP_LINEAR: u32 = 9987;
+    pub const WRAP_CLAMP_TO_EDGE: u32 = 33071;
+    pub const WRAP_MIRRORED_REPEAT: u32 = 33648;
+    pub const WRAP_REPEAT: u32 = 10497;
+
+    /// Whether mip levels below the first can ever be sampled.
+    /// An unset minification filter leaves the choice to the client, which may use mipmaps.
+    pub fn uses_mipmaps(&self) -> bool {
+        self.min_filter.is_none_or(|filter| (Self::FILTER_NEAREST_MIPMAP_NEAREST..=Self::FILTER_LINEAR_MIPMAP_LINEAR).contains(&filter))
+    }
+    /// Whether either wrapping mode repeats the texture, including mirrored repeat.
+    pub fn repeats(&self) -> bool {
+        [self.wrap_s, self.wrap_t].iter().any(|wrap| wrap.unwrap_or(Self::WRAP_REPEAT) != Self::WRAP_CLAMP_TO_EDGE)
+    }
+}
+
 #[derive(Debug, PartialEq, Eq)]
-struct GltfSampler();
+pub struct GltfAccessor();
+
+/// The accessor for each vertex attribute, keyed by attribute semantic such as `POSITION` or `TEXCOORD_0`.
+pub type GltfAttributes = BTreeMap<String, GltfIndex<GltfAccessor>>;
+
+/// A set of primitives to be rendered.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Clone)]
+pub struct GltfMesh {
+    /// An array of primitives, each defining geometry to be rendered.
+    pub primitives: Vec<GltfPrimitive>,
+    /// Array of weights to be applied to the morph targets.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weights: Option<Vec<f64>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extensions: Option<GltfExtensions>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extras: Option<serde_json::Value>,
+}
+
+/// Geometry to be rendered with the given material.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Clone)]
+pub struct GltfPrimitive {
+    /// A plain JSON object, where each key corresponds to a mesh attribute semantic and each value is the index of the accessor containing attribute's data.
+    pub attributes: GltfAttributes,
+    /// The index of the accessor that contains the vertex indices.
+    #[serde(default, skip_serializing_if = "GltfIndex::is_undefined")]
+    pub indices: GltfIndex<GltfAccessor>,
+    /// The index of the material to apply to this primitive when rendering.
+    #[serde(default, skip_serializing_if = "GltfIndex::is_undefined")]
+    pub material: GltfIndex<serde_json::Value>,
+    /// The topology type of primitives to render.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<u64>,
+    /// An array of morph targets, each mapping attribute semantics to accessors of displacements.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub targets: Option<Vec<GltfAttributes>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extensions: Option<GltfExtensions>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extras: Option<serde_json::Value>,
+}
+impl GltfPrimitive {
+    /// Every accessor the primitive uses: its attributes, its indices and every morph target's attributes.
+    pub fn accessors(&self) -> impl Iterator<Item = GltfIndex<GltfAccessor>> + '_ {
+        let targets = self.targets.iter().flatten().flat_map(|target| target.values());
+        self.attributes
+            .values()
+            .chain(targets)
+            .copied()
+            .chain(Some(self.indices).filter(GltfIndex::is_defined))
+    }
+}
+
+/// A reference from a material to a texture, and the texture coordinate set used to sample it.
+/// Covers `normalTextureInfo` and `occlusionTextureInfo` as well, which add `scale` and `strength`.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Clone)]
+pub struct GltfTextureInfo {
+    /// The index of the texture.
+    pub index: GltfIndex<GltfTexture>,
+    /// The set index of the texture's TEXCOORD attribute used for texture coordinate mapping.
+    /// Kept as written, so a document which doesn't set it still doesn't after a round-trip.
+    #[serde(rename = "texCoord", default, skip_serializing_if = "Option::is_none")]
+    pub tex_coord: Option<u64>,
+    /// The scalar parameter applied to each normal vector of a `normalTextureInfo`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scale: Option<f64>,
+    /// The scalar multiplier controlling the amount of occlusion applied by an `occlusionTextureInfo`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strength: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extensions: Option<GltfExtensions>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extras: Option<serde_json::Value>,
+}
+impl GltfTextureInfo {
+    pub fn new(index: GltfIndex<GltfTexture>) -> Self {
+        Self { index, ..Default::default() }
+    }
+    /// The texture coordinate set, defaulting to 0.
+    pub fn tex_coord(&self) -> u64 {
+        self.tex_coord.unwrap_or(0)
+    }
+    /// The texture coordinate set actually sampled: KHR_texture_transform can override [Self::tex_coord].
+    pub fn effective_tex_coord(&self) -> u64 {
+        self.extensions
+            .as_ref()
+            .and_then(|exts| exts.get("KHR_texture_transform")?.get("texCoord")?.as_u64())
+            .unwrap_or(self.tex_coord())
+    }
+}
 
 /// A texture and its sampler.
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq, Clone)]
 pub struct GltfTexture {
     /// The index of the sampler used by this texture.
     /// When undefined, a sampler with repeat wrapping and auto filtering **SHOULD** be used.
+    #[serde(default, skip_serializing_if = "GltfIndex::is_undefined")]
     pub sampler: GltfIndex<GltfSampler>,
     /// The index of the image used by this texture.
     /// When undefined, an extension or other mechanism **SHOULD** supply an alternate texture source, otherwise behavior is undefined.
+    #[serde(default, skip_serializing_if = "GltfIndex::is_undefined")]
     pub source: GltfIndex<GltfImage>,
-    pub name: serde_json::Value,
-    pub extensions: serde_json::Value,
-    pub extras: serde_json::Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extensions: Option<GltfExtensions>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extras: Option<serde_json::Value>,
+}
+impl GltfTexture {
+    pub fn new(source: GltfIndex<GltfImage>) -> Self {
+        Self { source, ..Default::default() }
+    }
+    pub fn with_sampler(self, sampler: GltfIndex<GltfSampler>) -> Self {
+        Self { sampler, ..self }
+    }
+    pub fn with_name(self, name: impl Into<String>) -> Self {
+        Self { name: Some(name.into()), ..self }
+    }
 }
 
 /// Image data used to create a texture. Image **MAY** be referenced by an URI (or IRI) or a buffer view index.
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq, Clone)]
 pub struct GltfImage {
     /// The URI (or IRI) of the image.
     /// Relative paths are relative to the current glTF asset.
     /// Instead of referencing an external file, this field **MAY** contain a `data:`-URI.
     /// This field **MUST NOT** be defined when `bufferView` is defined.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub uri: Option<GltfUri>,
     /// The image's media type.
     /// This field **MUST** be defined when `bufferView` is defined.
-    #[serde(rename = "mimeType")]
+    #[serde(rename = "mimeType", default, skip_serializing_if = "Option::is_none")]
     pub mime_type: Option<String>,
     /// The index of the bufferView that contains the image.
     /// This field **MUST NOT** be defined when `uri` is defined.
-    #[serde(rename = "bufferView", default)]
+    #[serde(rename = "bufferView", default, skip_serializing_if = "GltfIndex::is_undefined")]
     pub buffer_view: GltfIndex<GltfBufferView>,
-    pub name: serde_json::Value,
-    pub extensions: serde_json::Value,
-    pub extras: serde_json::Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extensions: Option<GltfExtensions>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extras: Option<serde_json::Value>,
 }
 impl GltfImage {
+    pub fn from_uri(uri: impl Into<GltfUri>) -> Self {
+        Self { uri: Some(uri.into()), ..Default::default() }
+    }
+    pub fn from_buffer_view(buffer_view: GltfIndex<GltfBufferView>, mime_type: impl Into<String>) -> Self {
+        Self { buffer_view, mime_type: Some(mime_type.into()), ..Default::default() }
+    }
+    pub fn with_name(self, name: impl Into<String>) -> Self {
+        Self { name: Some(name.into()), ..self }
+    }
+
     pub fn dump_data<'a>(&self, buffer_views: &'a Vec<GltfBufferView>, buffer_datas: &'a Vec<U8VecOrSlice<'a>>, map: &'a HashMap<Option<String>, Vec<u8>>) -> Result<U8VecOrSlice<'a>> {
         match (&self.uri, self.buffer_view) {
             (Some(uri), GltfIndex::UNDEFINED) => {
-                if let Some(data) = base64str_from_data_uri(uri.0.as_str()) {
+                if let Some(data) = base64str_from_image_data_uri(uri.0.as_str()) {
                     // RFC 2397 for data URIs contains an example in section 4
                     // which uses the '/' character. While the base64 crate does have a URL-safe alphabet which avoids + and /, 
                     // we can assume we don't need to use it.
@@ -216,7 +463,7 @@ impl GltfImage {
             }
             (None, buffer_view_idx) if buffer_view_idx.is_defined() => {
                 let view = buffer_views.gltf_index_required(buffer_view_idx, "bufferViews")?;
-                view.slice_from(buffer_datas).map(|slice| U8VecOrSlice::S(slice))
+                view.slice_from(buffer_datas).map(U8VecOrSlice::S)
             }
             _ => Err(Error::ImageNeedsDataUriXorBufferView { uri: self.uri.clone().map(|u| u.0), buffer_view: self.buffer_view })
         }
@@ -228,7 +475,7 @@ pub enum U8VecOrSlice<'a> {
     S(&'a [u8]),
 }
 impl<'a> U8VecOrSlice<'a> {
-    fn of_sliced_vec(v: &'a Vec<u8>, len: usize) -> Result<U8VecOrSlice<'a>> {
+    fn of_sliced_vec(v: &'a [u8], len: usize) -> Result<U8VecOrSlice<'a>> {
         if len > v.len() {
             Err(Error::BufferNotLongEnough { expected_bytes: len, got_bytes: v.len() })
         } else {
@@ -239,9 +486,7 @@ impl<'a> U8VecOrSlice<'a> {
         if len > v.len() {
             Err(Error::BufferNotLongEnough { expected_bytes: len, got_bytes: v.len() })
         } else {
-            for _ in 0..(len-v.len()) {
-                v.pop();
-            }
+            v.truncate(len);
             Ok(U8VecOrSlice::V(v))
         }
     }
@@ -251,6 +496,9 @@ impl<'a> U8VecOrSlice<'a> {
             U8VecOrSlice::S(items) => items.len(),
         }
     }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 }
 impl<'a, I: SliceIndex<[u8]>> Index<I> for U8VecOrSlice<'a> {
     type Output = I::Output;
@@ -277,11 +525,11 @@ impl<'a> Deref for U8VecOrSlice<'a> {
 /// Extract the base64-encoded part of a value glTF2.0 buffer data URI, returning None if the URI is not a valid base64 data URI.
 /// 
 /// 1. glTF2.0 section 2.8: 
-/// "Data URIs that embed binary resources in the glTF JSON as defined by the RFC 2397. The Data URI’s mediatype field MUST match the encoded content."
+///    "Data URIs that embed binary resources in the glTF JSON as defined by the RFC 2397. The Data URI’s mediatype field MUST match the encoded content."
 /// 
 /// 2. glTF2.0 section 3.6.1.1:
-/// "Buffer data MAY alternatively be embedded in the glTF file via data: URI with base64 encoding.
-/// When data: URI is used for buffer storage, its mediatype field MUST be set to application/octet-stream or application/gltf-buffer."
+///    "Buffer data MAY alternatively be embedded in the glTF file via data: URI with base64 encoding.
+///    When data: URI is used for buffer storage, its mediatype field MUST be set to application/octet-stream or application/gltf-buffer."
 /// 
 /// 3. RFC 2397:
 /// ```notest
@@ -322,3 +570,18 @@ fn base64str_from_data_uri(uri: &str) -> Option<&str> {
     // optionally has ";base64", always has comma
     uri.strip_prefix(";base64,").or_else(|| uri.strip_prefix(","))
 }
+
+/// Extract the base64-encoded part of an image data URI, returning None if the URI is not a base64 `data:image/...` URI.
+///
+/// glTF2.0 section 5.17 (image.uri) allows any data URI whose mediatype matches the encoded image,
+/// so unlike [base64str_from_data_uri] we accept any `image/*` mediatype.
+/// Image data URIs always use base64, as the image bytes aren't textual.
+fn base64str_from_image_data_uri(uri: &str) -> Option<&str> {
+    let uri = uri.strip_prefix("data:")?;
+    let (mediatype, data) = uri.split_once(',')?;
+    if mediatype.starts_with("image/") && mediatype.ends_with(";base64") {
+        Some(data)
+    } else {
+        None
+    }
+}
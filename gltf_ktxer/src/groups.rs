@@ -0,0 +1,120 @@
+//! Groups of textures which must be encoded with the same settings, so e.g. a character's face and body don't end up visibly differently compressed.
+//!
+//! After everything else has chosen each texture's settings (hints, overrides and the [crate::Params::decision_hook]),
+//! every texture in a group is given the strongest settings any of them got: UASTC over ETC1S, the highest quality, and the largest size limit.
+
+use std::collections::{BTreeSet, HashSet};
+
+use serde_json::Value;
+
+use crate::{edit, gltf::{GltfDoc, GltfImage, GltfIndex, GltfTexture}, ImageReencodeFormat, ImageReencodeJob, KtxCodec, Result};
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TextureGroup {
+    /// Identifies the group in warnings.
+    pub name: String,
+    /// Every texture used by a material whose name matches one of these [glob_match] patterns is in the group.
+    pub materials: Vec<String>,
+    /// Every texture whose name, or the name or URI of any of its source images, matches one of these [glob_match] patterns is in the group.
+    pub textures: Vec<String>,
+}
+impl TextureGroup {
+    /// The textures in the group. `images` are the document's images, for matching on their names and URIs.
+    pub fn members(&self, doc: &GltfDoc, textures: &[GltfTexture], images: &[GltfImage]) -> Result<BTreeSet<usize>> {
+        let matches_any = |patterns: &[String], name: &str| patterns.iter().any(|pattern| glob_match(pattern, name));
+        let mut members = BTreeSet::new();
+
+        let materials = doc.get("materials").and_then(Value::as_array).map_or(&[][..], Vec::as_slice);
+        let grouped_materials: HashSet<usize> = materials
+            .iter()
+            .enumerate()
+            .filter(|(_, material)| material.get("name").and_then(Value::as_str).is_some_and(|name| matches_any(&self.materials, name)))
+            .map(|(idx, _)| idx)
+            .collect();
+        if !grouped_materials.is_empty() {
+            for (slot, info) in edit::texture_infos(doc)? {
+                let material_idx = slot.strip_prefix("/materials/").and_then(|rest| rest.split('/').next()?.parse::<usize>().ok());
+                if material_idx.is_some_and(|idx| grouped_materials.contains(&idx)) && info.index.is_defined() {
+                    members.insert(info.index.raw_idx());
+                }
+            }
+        }
+
+        for (idx, texture) in textures.iter().enumerate() {
+            let sources = std::iter::once(texture.source)
+                .chain(edit::TEXTURE_SOURCE_EXTENSIONS.iter().filter_map(|ext_name| edit::texture_extension_source(texture, ext_name)));
+            let images = sources.filter_map(|source| images.get(source.raw_idx()));
+            let image_names = images.flat_map(|image| [image.name.as_deref(), image.uri.as_ref().filter(|uri| !uri.is_data_uri()).map(|uri| uri.as_str())]);
+            if texture.name.as_deref().into_iter().chain(image_names.flatten()).any(|name| matches_any(&self.textures, name)) {
+                members.insert(idx);
+            }
+        }
+        Ok(members)
+    }
+}
+
+/// Whether `text` matches `pattern`, in which `*` matches any run of characters and `?` any one character.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let (pattern, text): (Vec<char>, Vec<char>) = (pattern.chars().collect(), text.chars().collect());
+    let (mut p, mut t) = (0, 0);
+    // Where to resume after the last `*`, matching one more character with it
+    let mut backtrack = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, star_t)) => {
+                    backtrack = Some((star, star_t + 1));
+                    p = star + 1;
+                    t = star_t + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Give the KTX2 jobs among `job_indices` the strongest settings any of them have.
+pub fn unify_jobs(jobs: &mut [ImageReencodeJob], job_indices: &BTreeSet<usize>) {
+    let mut codec = KtxCodec::Etc1s;
+    let mut quality = None;
+    let mut max_dimension = Some(0);
+    for job in job_indices.iter().filter_map(|&idx| jobs.get(idx)) {
+        if let ImageReencodeFormat::Ktx { codec: job_codec, basis_compression_quality, .. } = job.reencode_as {
+            if job_codec == KtxCodec::Uastc {
+                codec = KtxCodec::Uastc;
+            }
+            quality = quality.max(basis_compression_quality);
+            // No limit beats any limit
+            max_dimension = max_dimension.zip(job.max_dimension).map(|(a, b)| a.max(b));
+        }
+    }
+    for job in jobs.iter_mut().enumerate().filter(|(idx, _)| job_indices.contains(idx)).map(|(_, job)| job) {
+        if let ImageReencodeFormat::Ktx { codec: job_codec, basis_compression_quality, dither, .. } = &mut job.reencode_as {
+            *job_codec = codec;
+            *basis_compression_quality = quality;
+            if codec != KtxCodec::Etc1s {
+                *dither = None;
+            }
+            job.max_dimension = max_dimension;
+        }
+    }
+}
+
+/// The KTX2 jobs the textures in `members` use, once planned.
+pub fn member_jobs(textures: &[GltfTexture], members: &BTreeSet<usize>) -> BTreeSet<usize> {
+    members
+        .iter()
+        .filter_map(|&idx| edit::texture_ktx_source(textures.get(idx)?))
+        .filter(GltfIndex::is_defined)
+        .map(|idx| idx.raw_idx())
+        .collect()
+}
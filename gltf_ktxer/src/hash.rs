@@ -0,0 +1,115 @@
+//! Stable hashes of texture sources and encode settings, so incremental build systems can tell when a converted texture is stale.
+//!
+//! With [crate::Params::record_texture_hashes] set, every produced image gets
+//! ```json
+//! "extras": { "GLTF_KTXER_hashes": { "source": "sha256:...", "encodeParams": "sha256:..." } }
+//! ```
+//! `source` is the SHA-256 of the encoded source image bytes, so it can be compared against e.g. `sha256sum texture.png`.
+//! `encodeParams` is the SHA-256 of [ImageReencodeJob::encode_params_key], and changes whenever the output would.
+//! [crate::fingerprint] records the same hashes for the whole output, to re-encode only what changed.
+
+use serde_json::{json, Value};
+
+use crate::{ImageReencodeFormat, ImageReencodeJob};
+
+/// The key in an image's `extras` holding its hashes.
+pub const HASH_EXTRAS_KEY: &str = "GLTF_KTXER_hashes";
+
+impl ImageReencodeJob {
+    /// A canonical description of everything which affects the encoded output apart from the source data,
+    /// including the crate version as encoders may change between releases.
+    pub fn encode_params_key(&self) -> String {
+        let format = match &self.reencode_as {
+            ImageReencodeFormat::Basic(format) => format.extensions_str().first().copied().unwrap_or("unknown").to_string(),
+            ImageReencodeFormat::Ktx { codec, basis_compression_quality, transcoded_to_bc1_or_bc3, mipmaps, level_count, atlas, dither, effort } => format!(
+                "ktx2;codec={codec};quality={};bc1_or_bc3={transcoded_to_bc1_or_bc3};mipmaps={mipmaps}{}{}{}{}",
+                basis_compression_quality.map_or("default".to_string(), |quality| quality.to_string()),
+                level_count.map_or(String::new(), |count| format!(";levels={count}")),
+                atlas.map_or(String::new(), |grid| format!(";atlas={grid}")),
+                dither.map_or(String::new(), |dither| format!(";dither={dither}")),
+                effort.map_or(String::new(), |effort| format!(";effort={effort}")),
+            ),
+            ImageReencodeFormat::Copy => "copy".to_string(),
+        };
+        let max_size = self.max_dimension.map_or("none".to_string(), |size| if self.repeats { format!("{size};halved") } else { size.to_string() });
+        let adjustments = self.adjustments.map_or(String::new(), |adjust| {
+            format!(";exposure={};gamma={};saturation={}", adjust.exposure, adjust.gamma, adjust.saturation)
+        });
+        let transforms: String = self.transforms.iter().map(|transform| format!(";transform={}", transform.key())).collect();
+        let to_srgb = if self.source.color_conversion().is_some() { ";to_srgb" } else { "" };
+        let orientation = self.source.orientation().map_or(String::new(), |orientation| format!(";orientation={}", orientation.to_exif()));
+        format!(
+            "gltf_ktxer={};format={format};srgb={};max_size={max_size}{to_srgb}{orientation}{adjustments}{transforms}",
+            env!("CARGO_PKG_VERSION"),
+            self.data_used_as_srgb
+        )
+    }
+
+    /// The value to record under [HASH_EXTRAS_KEY] in the produced image's `extras`.
+    pub fn hash_extras(&self) -> Value {
+        json!({
+            "source": format!("sha256:{}", hex(&sha256(&self.source.data))),
+            "encodeParams": format!("sha256:{}", hex(&sha256(self.encode_params_key().as_bytes()))),
+        })
+    }
+}
+
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// SHA-256 as specified in FIPS 180-4.
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    const K: [u32; 64] = [
+        0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+        0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+        0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+        0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+        0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+        0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+        0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+        0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+    ];
+    let mut state: [u32; 8] = [
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+    ];
+
+    // Pad with a 1 bit, zeros, then the message length in bits, to a multiple of 64 bytes
+    let mut message = data.to_vec();
+    message.push(0x80);
+    message.resize((message.len() + 8).next_multiple_of(64), 0);
+    let len = message.len();
+    message[len - 8..].copy_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+
+    for block in message.chunks_exact(64) {
+        let mut w = [0u32; 64];
+        for (i, word) in block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes(word.try_into().unwrap());
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = state;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let temp1 = h.wrapping_add(s1).wrapping_add(ch).wrapping_add(K[i]).wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let temp2 = s0.wrapping_add(maj);
+            (h, g, f, e, d, c, b, a) = (g, f, e, d.wrapping_add(temp1), c, b, a, temp1.wrapping_add(temp2));
+        }
+        for (word, add) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *word = word.wrapping_add(add);
+        }
+    }
+
+    let mut digest = [0; 32];
+    for (bytes, word) in digest.chunks_exact_mut(4).zip(state) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
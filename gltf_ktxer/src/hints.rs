@@ -0,0 +1,87 @@
+//! Conversion hints asset authors embed in the document itself, in the `extras` of textures, images or materials:
+//! ```json
+//! "extras": { "gltf_ktxer": { "codec": "uastc", "quality": 200, "maxSize": 1024 } }
+//! ```
+//! Each setting comes from the most specific place which has it: the texture, then its source image, then the materials using it in document order.
+//! [crate::Params::override_hints] take precedence over the document's hints, and a [crate::Params::decision_hook] over both.
+
+use std::{collections::HashMap, num::NonZeroU8};
+
+use serde_json::Value;
+
+use crate::{edit, gltf::{GltfDoc, GltfIndex, GltfTexture}, Error, KtxCodec, Result};
+
+/// The key in `extras` holding hints.
+pub const HINTS_EXTRAS_KEY: &str = "gltf_ktxer";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ConversionHints {
+    pub codec: Option<KtxCodec>,
+    pub quality: Option<NonZeroU8>,
+    /// Downscale to fit within this many pixels in each dimension, in place of [crate::Params::max_texture_size].
+    /// Target profiles still apply their own limits.
+    pub max_size: Option<u32>,
+}
+impl ConversionHints {
+    /// Parse the hints object under [HINTS_EXTRAS_KEY].
+    pub fn from_json(value: &Value) -> Result<Self> {
+        let Value::Object(hints) = value else {
+            return Err(Error::BadHint("hints must be an object".to_string()));
+        };
+        let mut parsed = Self::default();
+        for (key, value) in hints {
+            let bad = |expected: &str| Error::BadHint(format!("'{key}' must be {expected}, not {value}"));
+            match key.as_str() {
+                "codec" => parsed.codec = Some(value.as_str().ok_or_else(|| bad("a string"))?.parse().map_err(Error::BadHint)?),
+                "quality" => {
+                    let quality = value.as_u64().and_then(|quality| u8::try_from(quality).ok()).and_then(NonZeroU8::new);
+                    parsed.quality = Some(quality.ok_or_else(|| bad("an integer from 1 to 255"))?);
+                }
+                "maxSize" => {
+                    let max_size = value.as_u64().and_then(|size| u32::try_from(size).ok()).filter(|&size| size > 0);
+                    parsed.max_size = Some(max_size.ok_or_else(|| bad("a positive integer"))?);
+                }
+                _ => return Err(Error::BadHint(format!("unknown hint '{key}', expected 'codec', 'quality' or 'maxSize'"))),
+            }
+        }
+        Ok(parsed)
+    }
+
+    /// The hints of item `idx` of the top-level list `list_name`, located at the hints if they're invalid.
+    pub fn of_item(doc: &GltfDoc, list_name: &str, idx: usize) -> Result<Self> {
+        let pointer = format!("/{list_name}/{idx}/extras/{HINTS_EXTRAS_KEY}");
+        let item = doc.get(list_name).and_then(|list| list.get(idx));
+        match item.and_then(|item| item.get("extras")?.get(HINTS_EXTRAS_KEY)) {
+            Some(hints) => Self::from_json(hints).map_err(|e| e.at(pointer)),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Each setting from `self` if it's set, otherwise from `fallback`.
+    pub fn or(self, fallback: Self) -> Self {
+        Self {
+            codec: self.codec.or(fallback.codec),
+            quality: self.quality.or(fallback.quality),
+            max_size: self.max_size.or(fallback.max_size),
+        }
+    }
+}
+
+/// The hints of the materials using each texture, with each setting from the first material in document order which has it.
+pub fn material_hints(doc: &GltfDoc) -> Result<HashMap<GltfIndex<GltfTexture>, ConversionHints>> {
+    let mut hints = HashMap::new();
+    let mut slots = edit::texture_infos(doc)?
+        .into_iter()
+        .filter_map(|(slot, info)| {
+            let material_idx: usize = slot.strip_prefix("/materials/")?.split('/').next()?.parse().ok()?;
+            Some((material_idx, info.index))
+        })
+        .collect::<Vec<_>>();
+    slots.sort_by_key(|&(material_idx, _)| material_idx);
+    for (material_idx, texture) in slots {
+        let material = ConversionHints::of_item(doc, "materials", material_idx)?;
+        let texture_hints: &mut ConversionHints = hints.entry(texture).or_default();
+        *texture_hints = texture_hints.or(material);
+    }
+    Ok(hints)
+}
@@ -0,0 +1,110 @@
+//! Per-job limits for hosts converting documents they don't trust, like a conversion service or a batch worker,
+//! so one pathological upload can't starve the jobs after it.
+//!
+//! Neither the CLI nor the library runs jobs on its own schedule, so the host checks a [JobBudget] between stages:
+//! [JobBudget::check_input] once the upload is read, [JobBudget::check_jobs] once it's planned,
+//! and [JobBudget::check_time] before each expensive step. [JobBudget::usage] gives what the job used, for accounting.
+//! Memory is limited separately, see [crate::memory::MemoryTracker].
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use serde_json::Value;
+
+use crate::{Error, ImageReencodeJob, Input, Result};
+
+/// Limits on a single job, set once when the host starts. [None] means unlimited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct JobLimits {
+    /// The most bytes the document's JSON and the files it refers to may take up together.
+    pub max_input_bytes: Option<u64>,
+    /// The most images the document may have.
+    pub max_images: Option<usize>,
+    /// The most pixels all the source images may have together once decoded.
+    /// Images whose dimensions can't be read from their header are rejected while this is set, as they can't be accounted for.
+    pub max_decode_pixels: Option<u64>,
+    /// How long the job may run for, counted from [JobBudget::start].
+    pub timeout: Option<Duration>,
+}
+
+/// What a job has used so far, as counted by its [JobBudget].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct JobUsage {
+    pub input_bytes: u64,
+    pub images: usize,
+    pub decode_pixels: u64,
+    pub elapsed: Duration,
+}
+
+/// A job's [JobLimits], along with what it has used so far.
+#[derive(Debug, Clone)]
+pub struct JobBudget {
+    limits: JobLimits,
+    started: Instant,
+    usage: JobUsage,
+}
+impl JobBudget {
+    /// Start timing a job with the given `limits`.
+    pub fn start(limits: JobLimits) -> Self {
+        Self { limits, started: Instant::now(), usage: JobUsage::default() }
+    }
+
+    /// Fail if the document's JSON and files or its number of images are over the limits.
+    pub fn check_input(&mut self, input: &Input<'_>) -> Result<()> {
+        let json_bytes = serde_json::to_vec(&*input.gltf_json)?.len() as u64;
+        self.usage.input_bytes = json_bytes + input.binaries.values().map(|data| data.len() as u64).sum::<u64>();
+        self.usage.images = input.gltf_json.get("images").and_then(Value::as_array).map_or(0, Vec::len);
+        if let Some(max) = self.limits.max_input_bytes.filter(|&max| self.usage.input_bytes > max) {
+            return Err(Error::JobLimitExceeded(format!("input takes up {} bytes, more than the limit of {max}", self.usage.input_bytes)));
+        }
+        if let Some(max) = self.limits.max_images.filter(|&max| self.usage.images > max) {
+            return Err(Error::JobLimitExceeded(format!("input has {} images, more than the limit of {max}", self.usage.images)));
+        }
+        self.check_time()
+    }
+
+    /// Fail if decoding the sources of `jobs` would go over the pixel limit, before anything is decoded.
+    /// Sources shared between jobs are only counted once, as they're only decoded once.
+    pub fn check_jobs(&mut self, jobs: &[ImageReencodeJob]) -> Result<()> {
+        let mut counted: Vec<&Arc<crate::SourceImage>> = vec![];
+        let mut pixels = 0u64;
+        for (idx, job) in jobs.iter().enumerate() {
+            if counted.iter().any(|source| Arc::ptr_eq(source, &job.source)) {
+                continue;
+            }
+            counted.push(&job.source);
+            match job.source_dimensions() {
+                Some((width, height)) => pixels += width as u64 * height as u64,
+                None if self.limits.max_decode_pixels.is_some() => {
+                    return Err(Error::JobLimitExceeded(format!("the size of image job {idx}'s source can't be read before decoding it")));
+                }
+                None => {}
+            }
+        }
+        self.usage.decode_pixels = pixels;
+        if let Some(max) = self.limits.max_decode_pixels.filter(|&max| pixels > max) {
+            return Err(Error::JobLimitExceeded(format!("source images have {pixels} pixels, more than the limit of {max}")));
+        }
+        self.check_time()
+    }
+
+    /// Fail if the job has run for longer than its timeout.
+    /// Work already running isn't interrupted, so check this between steps, e.g. before encoding each image.
+    pub fn check_time(&mut self) -> Result<()> {
+        self.usage.elapsed = self.started.elapsed();
+        match self.limits.timeout {
+            Some(timeout) if self.usage.elapsed > timeout => Err(Error::JobTimedOut(timeout)),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn limits(&self) -> &JobLimits {
+        &self.limits
+    }
+    /// What the job has used, as of the last check.
+    pub fn usage(&self) -> JobUsage {
+        self.usage
+    }
+}
@@ -0,0 +1,641 @@
+use image::RgbaImage;
+
+use crate::{mipmap, Error, Result};
+
+/// The 12-byte file identifier at the start of every KTX2 file.
+/// KTX2 spec section 3.1.
+pub const KTX2_IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+
+/// Used by supercompressed formats like BasisLZ, whose format is only known from the DFD.
+pub const VK_FORMAT_UNDEFINED: u32 = 0;
+pub const VK_FORMAT_R8G8B8A8_UNORM: u32 = 37;
+pub const VK_FORMAT_R8G8B8A8_SRGB: u32 = 43;
+
+pub const SUPERCOMPRESSION_BASIS_LZ: u32 = 1;
+pub const SUPERCOMPRESSION_ZSTD: u32 = 2;
+
+/// Color models from the Khronos Data Format Specification section 5.6.
+pub const KHR_DF_MODEL_RGBSDA: u8 = 1;
+pub const KHR_DF_MODEL_ETC1S: u8 = 163;
+pub const KHR_DF_MODEL_UASTC: u8 = 166;
+
+pub const KHR_DF_TRANSFER_LINEAR: u8 = 1;
+pub const KHR_DF_TRANSFER_SRGB: u8 = 2;
+
+/// How the texel values of an image should be interpreted.
+/// glTF requires baseColor and emissive textures to be sRGB-encoded, and all others to be linear.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ColorSpace {
+    Srgb,
+    Linear,
+}
+
+/// An in-memory KTX2 texture, ready to be serialized with [Ktx2Texture::to_bytes].
+///
+/// `levels[0]` is the base (largest) mip level.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ktx2Texture {
+    pub vk_format: u32,
+    pub type_size: u32,
+    pub pixel_width: u32,
+    pub pixel_height: u32,
+    /// 0 for 2D textures.
+    pub pixel_depth: u32,
+    /// 0 for textures which aren't arrays.
+    pub layer_count: u32,
+    /// 6 for cubemaps, otherwise 1.
+    pub face_count: u32,
+    pub supercompression_scheme: u32,
+    pub dfd: Vec<u8>,
+    pub key_values: Vec<(String, Vec<u8>)>,
+    pub sgd: Vec<u8>,
+    pub levels: Vec<Ktx2Level>,
+}
+
+/// The length of the header and index sections, before the level index.
+const KTX2_HEADER_AND_INDEX_LEN: usize = 80;
+
+/// Offsets of each section within a serialized KTX2 file, see [Ktx2Texture::write_to].
+struct Ktx2Layout {
+    dfd_offset: usize,
+    kvd: Vec<u8>,
+    kvd_offset: usize,
+    sgd_offset: usize,
+    level_offsets: Vec<usize>,
+    total_len: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ktx2Level {
+    pub data: Vec<u8>,
+    pub uncompressed_byte_length: u64,
+}
+
+impl Ktx2Texture {
+    /// Create a single-level uncompressed RGBA8 texture.
+    pub fn from_rgba8(image: &RgbaImage, color_space: ColorSpace) -> Result<Self> {
+        Self::from_rgba8_levels(std::slice::from_ref(image), color_space)
+    }
+
+    /// Create an uncompressed RGBA8 texture with a full mip chain generated from `image`, see [generate_mipmaps].
+    pub fn from_rgba8_mipmapped(image: &RgbaImage, color_space: ColorSpace) -> Result<Self> {
+        Self::from_rgba8_levels(&generate_mipmaps(image), color_space)
+    }
+
+    /// Create an uncompressed RGBA8 texture from a list of mip levels, largest first.
+    /// Each level must be half the size of the previous one, rounding down, as required by section 3.9.
+    pub fn from_rgba8_levels(levels: &[RgbaImage], color_space: ColorSpace) -> Result<Self> {
+        Self::from_rgba8_images(&[levels], 0, 1, color_space)
+    }
+
+    /// Create an uncompressed RGBA8 array, cubemap or cubemap array texture.
+    ///
+    /// `images` holds the mip levels (largest first) of each layer and face, ordered by layer then face,
+    /// so there must be `max(layer_count, 1) * face_count` of them, each with the same number of levels.
+    /// Every image must be the same size, and cubemap faces must be square (section 4.4).
+    pub fn from_rgba8_images(images: &[&[RgbaImage]], layer_count: u32, face_count: u32, color_space: ColorSpace) -> Result<Self> {
+        if images.len() != (layer_count.max(1) * face_count) as usize {
+            return Err(Error::Ktx2WrongImageCount { expected: layer_count.max(1) * face_count, got: images.len() });
+        }
+        let Some(image) = images.first().and_then(|levels| levels.first()) else {
+            return Err(Error::Ktx2ZeroSize { width: 0, height: 0 });
+        };
+        if image.width() == 0 || image.height() == 0 {
+            return Err(Error::Ktx2ZeroSize { width: image.width(), height: image.height() });
+        }
+        let num_levels = images[0].len();
+        for levels in images {
+            if levels.len() != num_levels {
+                return Err(Error::Ktx2MismatchedLevelCounts);
+            }
+            for (i, level) in levels.iter().enumerate() {
+                let expected = ((image.width() >> i).max(1), (image.height() >> i).max(1));
+                if level.dimensions() != expected || (face_count == 6 && level.width() != level.height()) {
+                    return Err(Error::Ktx2BadMipLevel { level: i, width: level.width(), height: level.height() });
+                }
+            }
+        }
+        Ok(Self {
+            vk_format: match color_space {
+                ColorSpace::Srgb => VK_FORMAT_R8G8B8A8_SRGB,
+                ColorSpace::Linear => VK_FORMAT_R8G8B8A8_UNORM,
+            },
+            type_size: 1,
+            pixel_width: image.width(),
+            pixel_height: image.height(),
+            pixel_depth: 0,
+            layer_count,
+            face_count,
+            supercompression_scheme: 0,
+            dfd: rgba8_dfd(color_space),
+            key_values: vec![writer_key_value()],
+            sgd: vec![],
+            // Section 3.9.5: within a level, images are ordered by layer then face
+            levels: (0..num_levels)
+                .map(|i| {
+                    let data: Vec<u8> = images.iter().flat_map(|levels| levels[i].as_raw()).copied().collect();
+                    Ktx2Level { uncompressed_byte_length: data.len() as u64, data }
+                })
+                .collect(),
+        })
+    }
+
+    /// Serialize the texture following the KTX2 file layout in section 3 of the spec:
+    /// header, index, level index, DFD, KVD, SGD, then mip levels from smallest to largest.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.write_to(&mut out);
+        out
+    }
+
+    /// The size of the serialized texture in bytes.
+    pub fn encoded_len(&self) -> usize {
+        self.layout().total_len
+    }
+
+    /// Serialize the texture like [Ktx2Texture::to_bytes], appending it to the end of `out`
+    /// so it can be written straight into a larger buffer without an intermediate copy.
+    /// Offsets within the file are relative to where it starts in `out`.
+    pub fn write_to(&self, out: &mut Vec<u8>) {
+        let layout = self.layout();
+        let base = out.len();
+        out.reserve(layout.total_len);
+
+        out.extend_from_slice(&KTX2_IDENTIFIER);
+        for word in [
+            self.vk_format,
+            self.type_size,
+            self.pixel_width,
+            self.pixel_height,
+            self.pixel_depth,
+            self.layer_count,
+            self.face_count,
+            self.levels.len() as u32,
+            self.supercompression_scheme,
+        ] {
+            out.extend_from_slice(&word.to_le_bytes());
+        }
+        out.extend_from_slice(&(layout.dfd_offset as u32).to_le_bytes());
+        out.extend_from_slice(&(self.dfd.len() as u32).to_le_bytes());
+        out.extend_from_slice(&(if layout.kvd.is_empty() { 0 } else { layout.kvd_offset } as u32).to_le_bytes());
+        out.extend_from_slice(&(layout.kvd.len() as u32).to_le_bytes());
+        out.extend_from_slice(&(layout.sgd_offset as u64).to_le_bytes());
+        out.extend_from_slice(&(self.sgd.len() as u64).to_le_bytes());
+        assert_eq!(out.len() - base, KTX2_HEADER_AND_INDEX_LEN);
+
+        for (level, offset) in self.levels.iter().zip(&layout.level_offsets) {
+            out.extend_from_slice(&(*offset as u64).to_le_bytes());
+            out.extend_from_slice(&(level.data.len() as u64).to_le_bytes());
+            out.extend_from_slice(&level.uncompressed_byte_length.to_le_bytes());
+        }
+
+        out.extend_from_slice(&self.dfd);
+        out.extend_from_slice(&layout.kvd);
+        if !self.sgd.is_empty() {
+            out.resize(base + layout.sgd_offset, 0);
+            out.extend_from_slice(&self.sgd);
+        }
+        for (i, level) in self.levels.iter().enumerate().rev() {
+            out.resize(base + layout.level_offsets[i], 0);
+            out.extend_from_slice(&level.data);
+        }
+        debug_assert_eq!(out.len() - base, layout.total_len);
+    }
+
+    /// Work out where each section of the file goes.
+    fn layout(&self) -> Ktx2Layout {
+        let level_index_len = self.levels.len() * 24;
+        let dfd_offset = KTX2_HEADER_AND_INDEX_LEN + level_index_len;
+
+        let kvd = serialize_key_values(&self.key_values);
+        let kvd_offset = dfd_offset + self.dfd.len();
+
+        // Section 3.11: the SGD must start on an 8-byte boundary
+        let sgd_offset = if self.sgd.is_empty() { 0 } else { align_up(kvd_offset + kvd.len(), 8) };
+
+        // Levels are stored smallest-first, each aligned to lcm(texel block size, 4).
+        // Supercompressed levels only need 1-byte alignment (section 3.9.7).
+        let level_alignment = if self.supercompression_scheme == 0 {
+            lcm(self.texel_block_size(), 4)
+        } else {
+            1
+        };
+        let mut level_offsets = vec![0; self.levels.len()];
+        let mut cursor = if self.sgd.is_empty() { kvd_offset + kvd.len() } else { sgd_offset + self.sgd.len() };
+        for (i, level) in self.levels.iter().enumerate().rev() {
+            cursor = align_up(cursor, level_alignment);
+            level_offsets[i] = cursor;
+            cursor += level.data.len();
+        }
+
+        Ktx2Layout { dfd_offset, kvd, kvd_offset, sgd_offset, level_offsets, total_len: cursor }
+    }
+
+    /// Parse a KTX2 file, checking every section lies within the file.
+    /// Supercompressed level data is kept as-is.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if !bytes.starts_with(&KTX2_IDENTIFIER) {
+            return Err(Error::Ktx2Malformed("not a KTX2 file, as it doesn't start with the KTX2 identifier"));
+        }
+        let u32_at = |offset: usize| -> Result<u32> {
+            let word = bytes.get(offset..offset + 4).ok_or(Error::Ktx2Malformed("file is truncated"))?;
+            Ok(u32::from_le_bytes(word.try_into().unwrap()))
+        };
+        let u64_at = |offset: usize| -> Result<u64> {
+            let word = bytes.get(offset..offset + 8).ok_or(Error::Ktx2Malformed("file is truncated"))?;
+            Ok(u64::from_le_bytes(word.try_into().unwrap()))
+        };
+        let section = |offset: u64, len: u64, what: &'static str| -> Result<&[u8]> {
+            let start = usize::try_from(offset).map_err(|_| Error::Ktx2Malformed(what))?;
+            let len = usize::try_from(len).map_err(|_| Error::Ktx2Malformed(what))?;
+            start
+                .checked_add(len)
+                .and_then(|end| bytes.get(start..end))
+                .ok_or(Error::Ktx2Malformed(what))
+        };
+
+        let level_count = u32_at(40)?;
+        let dfd = section(u32_at(48)? as u64, u32_at(52)? as u64, "DFD is out of bounds")?.to_vec();
+        let kvd = section(u32_at(56)? as u64, u32_at(60)? as u64, "key/value data is out of bounds")?;
+        let sgd = section(u64_at(64)?, u64_at(72)?, "supercompression global data is out of bounds")?.to_vec();
+
+        // Section 3.9.1: a levelCount of 0 means the reader should generate mipmaps, but one level is still stored.
+        let levels = (0..level_count.max(1) as usize)
+            .map(|i| {
+                let entry = 80 + i * 24;
+                Ok(Ktx2Level {
+                    data: section(u64_at(entry)?, u64_at(entry + 8)?, "level is out of bounds")?.to_vec(),
+                    uncompressed_byte_length: u64_at(entry + 16)?,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            vk_format: u32_at(12)?,
+            type_size: u32_at(16)?,
+            pixel_width: u32_at(20)?,
+            pixel_height: u32_at(24)?,
+            pixel_depth: u32_at(28)?,
+            layer_count: u32_at(32)?,
+            face_count: u32_at(36)?,
+            supercompression_scheme: u32_at(44)?,
+            dfd,
+            key_values: parse_key_values(kvd)?,
+            sgd,
+            levels,
+        })
+    }
+
+    /// The number of images in each mip level: one per array layer, cubemap face and (for 3D textures) depth slice.
+    pub fn images_per_level(&self) -> u32 {
+        self.layer_count.max(1) * self.face_count.max(1) * self.pixel_depth.max(1)
+    }
+
+    /// The image at `level`, `layer` and `face` (or depth slice, for 3D textures).
+    pub fn image(&self, level: usize, layer: u32, face: u32) -> Result<&[u8]> {
+        let range = self.image_range(level, layer, face)?;
+        Ok(&self.levels[level].data[range])
+    }
+
+    /// Overwrite the image at `level`, `layer` and `face` (or depth slice, for 3D textures),
+    /// like libktx's `SetImageFromMemory` but with the indices and length checked, so a bad call is an error rather than a buffer overrun.
+    /// `data` must be exactly as long as the image it replaces.
+    pub fn set_image(&mut self, level: usize, layer: u32, face: u32, data: &[u8]) -> Result<()> {
+        let range = self.image_range(level, layer, face)?;
+        if data.len() != range.len() {
+            return Err(Error::Ktx2WrongImageSize { expected: range.len(), got: data.len() });
+        }
+        self.levels[level].data[range].copy_from_slice(data);
+        Ok(())
+    }
+
+    /// The base level of an uncompressed RGBA8 texture (the first layer and face), or None for any other format.
+    pub fn level0_rgba8(&self) -> Result<Option<RgbaImage>> {
+        if ![VK_FORMAT_R8G8B8A8_SRGB, VK_FORMAT_R8G8B8A8_UNORM].contains(&self.vk_format) || self.supercompression_scheme != 0 {
+            return Ok(None);
+        }
+        let data = self.image(0, 0, 0)?.to_vec();
+        RgbaImage::from_raw(self.pixel_width, self.pixel_height, data)
+            .map(Some)
+            .ok_or(Error::Ktx2Malformed("base level is smaller than its width and height"))
+    }
+
+    /// The byte range of one image within its level's data. Section 3.9.5: within a level, images are ordered by layer then face.
+    fn image_range(&self, level: usize, layer: u32, face: u32) -> Result<std::ops::Range<usize>> {
+        if self.supercompression_scheme != 0 {
+            return Err(Error::Ktx2Supercompressed);
+        }
+        let faces = self.face_count.max(1) * self.pixel_depth.max(1);
+        let Some(level_data) = self.levels.get(level).map(|level| &level.data).filter(|_| layer < self.layer_count.max(1) && face < faces) else {
+            return Err(Error::Ktx2BadImageIndex { level, layer, face });
+        };
+        let images = self.images_per_level() as usize;
+        if level_data.len() % images != 0 {
+            return Err(Error::Ktx2Malformed("level length isn't a multiple of the number of images"));
+        }
+        let image_len = level_data.len() / images;
+        let start = (layer * faces + face) as usize * image_len;
+        Ok(start..start + image_len)
+    }
+
+    /// The color model from the DFD's basic descriptor block, e.g. [KHR_DF_MODEL_UASTC].
+    pub fn dfd_color_model(&self) -> Option<u8> {
+        self.dfd.get(4 + 8).copied()
+    }
+
+    /// The color primaries from the DFD's basic descriptor block, e.g. 1 for BT.709.
+    pub fn dfd_color_primaries(&self) -> Option<u8> {
+        self.dfd.get(4 + 9).copied()
+    }
+
+    /// The transfer function from the DFD's basic descriptor block, e.g. [KHR_DF_TRANSFER_SRGB].
+    pub fn dfd_transfer_function(&self) -> Option<u8> {
+        self.dfd.get(4 + 10).copied()
+    }
+
+    /// The channel type of each sample in the DFD's basic descriptor block, without qualifier bits.
+    pub fn dfd_sample_channels(&self) -> Vec<u8> {
+        self.dfd
+            .get(4 + 24..)
+            .unwrap_or_default()
+            .chunks_exact(16)
+            .map(|sample| sample[3] & 0xF)
+            .collect()
+    }
+
+    /// Whether any DFD sample holds alpha: the A channel of RGBSDA and ETC1S, or UASTC's RGBA channel.
+    pub fn dfd_has_alpha(&self) -> bool {
+        const KHR_DF_CHANNEL_RGBSDA_ALPHA: u8 = 15;
+        const KHR_DF_CHANNEL_ETC1S_AAA: u8 = 15;
+        const KHR_DF_CHANNEL_UASTC_RGBA: u8 = 3;
+        let alpha = match self.dfd_color_model() {
+            Some(KHR_DF_MODEL_RGBSDA) => KHR_DF_CHANNEL_RGBSDA_ALPHA,
+            Some(KHR_DF_MODEL_ETC1S) => KHR_DF_CHANNEL_ETC1S_AAA,
+            Some(KHR_DF_MODEL_UASTC) => KHR_DF_CHANNEL_UASTC_RGBA,
+            _ => return false,
+        };
+        self.dfd_sample_channels().contains(&alpha)
+    }
+
+    /// The number of bytes in a single texel block, read from the DFD's bytesPlane0 field.
+    fn texel_block_size(&self) -> usize {
+        // dfdTotalSize (4) + 5 words of the basic descriptor block header.
+        self.dfd.get(4 + 16).copied().map(usize::from).filter(|&x| x > 0).unwrap_or(1)
+    }
+}
+
+/// Human-readable name for a supercompressionScheme, KTX2 spec section 3.4.
+pub fn supercompression_name(scheme: u32) -> &'static str {
+    match scheme {
+        0 => "none",
+        SUPERCOMPRESSION_BASIS_LZ => "BasisLZ",
+        SUPERCOMPRESSION_ZSTD => "Zstandard",
+        3 => "ZLIB",
+        _ => "unknown",
+    }
+}
+
+/// Human-readable name for a DFD color model, Khronos Data Format Specification section 5.6.
+pub fn color_model_name(model: u8) -> &'static str {
+    match model {
+        0 => "unspecified",
+        KHR_DF_MODEL_RGBSDA => "RGBSDA",
+        2 => "YUVSDA",
+        3 => "YIQSDA",
+        4 => "LabSDA",
+        5 => "CMYKA",
+        128 => "BC1A",
+        129 => "BC2",
+        130 => "BC3",
+        131 => "BC4",
+        132 => "BC5",
+        133 => "BC6H",
+        134 => "BC7",
+        160 => "ETC1",
+        161 => "ETC2",
+        162 => "ASTC",
+        KHR_DF_MODEL_ETC1S => "ETC1S",
+        164 => "PVRTC",
+        165 => "PVRTC2",
+        KHR_DF_MODEL_UASTC => "UASTC",
+        _ => "unknown",
+    }
+}
+
+/// Human-readable name for a DFD transfer function, Khronos Data Format Specification section 5.8.
+pub fn transfer_function_name(transfer: u8) -> &'static str {
+    match transfer {
+        0 => "unspecified",
+        KHR_DF_TRANSFER_LINEAR => "linear",
+        KHR_DF_TRANSFER_SRGB => "sRGB",
+        _ => "other",
+    }
+}
+
+/// Human-readable name for a DFD color primaries value, Khronos Data Format Specification section 5.7.
+pub fn color_primaries_name(primaries: u8) -> &'static str {
+    match primaries {
+        0 => "unspecified",
+        1 => "BT.709",
+        2 => "BT.601 (EBU)",
+        3 => "BT.601 (SMPTE)",
+        4 => "BT.2020",
+        10 => "Display P3",
+        11 => "Adobe RGB",
+        _ => "other",
+    }
+}
+
+/// Downsample `image` repeatedly down to 1x1 with [mipmap::downsample_half], returning every level largest first (including `image` itself).
+///
+/// Texels are filtered as-is, without converting sRGB data to linear first.
+pub fn generate_mipmaps(image: &RgbaImage) -> Vec<RgbaImage> {
+    let mut levels = vec![image.clone()];
+    while let Some(last) = levels.last().filter(|last| last.width() > 1 || last.height() > 1) {
+        levels.push(mipmap::downsample_half(last));
+    }
+    levels
+}
+
+/// Like [generate_mipmaps], but for an atlas: texels are only filtered together with others from the same tile of `grid`,
+/// see [mipmap::downsample_half_tiled].
+pub fn generate_atlas_mipmaps(image: &RgbaImage, grid: mipmap::TileGrid) -> Vec<RgbaImage> {
+    let mut levels = vec![image.clone()];
+    while let Some(last) = levels.last().filter(|last| last.width() > 1 || last.height() > 1) {
+        levels.push(mipmap::downsample_half_tiled(last, grid));
+    }
+    levels
+}
+
+/// Downscale `image` to fit within `max_dimension` in both width and height, keeping its aspect ratio.
+/// Images which already fit are returned unchanged.
+pub fn fit_within(image: &RgbaImage, max_dimension: u32) -> RgbaImage {
+    let (width, height) = fit_dimensions(image.dimensions(), max_dimension);
+    if (width, height) == image.dimensions() {
+        return image.clone();
+    }
+    image::imageops::resize(image, width, height, image::imageops::FilterType::Lanczos3)
+}
+
+/// The size [fit_within] scales an image of the given `(width, height)` to.
+pub fn fit_dimensions((width, height): (u32, u32), max_dimension: u32) -> (u32, u32) {
+    if width.max(height) <= max_dimension {
+        return (width, height);
+    }
+    let scale = max_dimension as f64 / width.max(height) as f64;
+    (((width as f64 * scale).round() as u32).max(1), ((height as f64 * scale).round() as u32).max(1))
+}
+
+/// A cheap sanity check of an encoded texture against the image it was made from,
+/// to catch encoder misconfiguration like wrong format enums or strides before files ship.
+///
+/// The texture must be `expected_dimensions` in size, and must have transparent texels exactly when `source` does.
+/// Uncompressed RGBA8 textures are checked texel by texel. Basis textures are lossy, so their texels can't be compared,
+/// and they only have to have an alpha channel in their DFD if the source has transparent texels.
+pub fn verify_encoded(ktx: &Ktx2Texture, source: &RgbaImage, expected_dimensions: (u32, u32)) -> Result<()> {
+    let fail = |message: String| Err(Error::OutputVerificationFailed(message));
+    let (width, height) = expected_dimensions;
+    if (ktx.pixel_width, ktx.pixel_height) != expected_dimensions {
+        return fail(format!("expected {width}x{height}, got {}x{}", ktx.pixel_width, ktx.pixel_height));
+    }
+    let source_has_alpha = source.pixels().any(|texel| texel[3] != 255);
+    match ktx.level0_rgba8()? {
+        Some(level0) => {
+            let output_has_alpha = level0.pixels().any(|texel| texel[3] != 255);
+            if source_has_alpha != output_has_alpha {
+                let (has, hasnt) = if source_has_alpha { ("source", "output") } else { ("output", "source") };
+                return fail(format!("the {has} has transparent texels but the {hasnt} doesn't"));
+            }
+        }
+        None if source_has_alpha && !ktx.dfd_has_alpha() => {
+            return fail("the source has transparent texels but the output has no alpha channel".to_string());
+        }
+        None => {}
+    }
+    Ok(())
+}
+
+/// Construct the Data Format Descriptor for an uncompressed RGBA8 texture.
+///
+/// Follows the Khronos Data Format Specification section 5, "Basic Data Format Descriptor Block",
+/// with one sample per channel.
+pub fn rgba8_dfd(color_space: ColorSpace) -> Vec<u8> {
+    const KHR_DF_PRIMARIES_BT709: u32 = 1;
+    const KHR_DF_SAMPLE_DATATYPE_LINEAR: u32 = 0x10;
+
+    let transfer = match color_space {
+        ColorSpace::Srgb => KHR_DF_TRANSFER_SRGB,
+        ColorSpace::Linear => KHR_DF_TRANSFER_LINEAR,
+    };
+    // channel ids for R, G, B, A in the RGBSDA model
+    let channels = [0u32, 1, 2, 15];
+    let block_size = 24 + 16 * channels.len() as u32;
+
+    let mut words = vec![
+        block_size + 4,                                                    // dfdTotalSize
+        0,                                                                 // vendorId = Khronos, descriptorType = basic
+        2 | (block_size << 16),                                            // versionNumber = 1.3, descriptorBlockSize
+        KHR_DF_MODEL_RGBSDA as u32 | (KHR_DF_PRIMARIES_BT709 << 8) | ((transfer as u32) << 16), // flags = straight alpha
+        0,                                                                 // texelBlockDimension = 1x1x1x1
+        4,                                                                 // bytesPlane0 = 4
+        0,                                                                 // bytesPlane4..7
+    ];
+    for (i, channel) in channels.into_iter().enumerate() {
+        // Section 5.6.1: alpha is never sRGB-encoded, so it's marked as linear when the rest of the texel is sRGB.
+        let qualifiers = if channel == 15 && color_space == ColorSpace::Srgb {
+            KHR_DF_SAMPLE_DATATYPE_LINEAR
+        } else {
+            0
+        };
+        words.push((i as u32 * 8) | (7 << 16) | ((channel | qualifiers) << 24)); // bitOffset, bitLength - 1, channelType
+        words.push(0); // samplePosition
+        words.push(0); // sampleLower
+        words.push(255); // sampleUpper
+    }
+
+    words.into_iter().flat_map(u32::to_le_bytes).collect()
+}
+
+/// Construct the Data Format Descriptor for a BasisLZ/ETC1S texture, as in the KTX2 spec's BasisLZ example:
+/// a 4x4 block with an RGB sample, and an AAA sample for the alpha slice if `has_alpha`.
+pub fn etc1s_dfd(color_space: ColorSpace, has_alpha: bool) -> Vec<u8> {
+    const KHR_DF_PRIMARIES_BT709: u32 = 1;
+    const KHR_DF_CHANNEL_ETC1S_RGB: u32 = 0;
+    const KHR_DF_CHANNEL_ETC1S_AAA: u32 = 15;
+
+    let transfer = match color_space {
+        ColorSpace::Srgb => KHR_DF_TRANSFER_SRGB,
+        ColorSpace::Linear => KHR_DF_TRANSFER_LINEAR,
+    };
+    let channels: &[u32] = if has_alpha { &[KHR_DF_CHANNEL_ETC1S_RGB, KHR_DF_CHANNEL_ETC1S_AAA] } else { &[KHR_DF_CHANNEL_ETC1S_RGB] };
+    let block_size = 24 + 16 * channels.len() as u32;
+
+    let mut words = vec![
+        block_size + 4,                                                    // dfdTotalSize
+        0,                                                                 // vendorId = Khronos, descriptorType = basic
+        2 | (block_size << 16),                                            // versionNumber = 1.3, descriptorBlockSize
+        KHR_DF_MODEL_ETC1S as u32 | (KHR_DF_PRIMARIES_BT709 << 8) | ((transfer as u32) << 16), // flags = straight alpha
+        3 | (3 << 8),                                                      // texelBlockDimension = 4x4x1x1
+        0,                                                                 // bytesPlane0 = 0, as the data is supercompressed
+        0,                                                                 // bytesPlane4..7
+    ];
+    for (i, &channel) in channels.iter().enumerate() {
+        words.push((i as u32 * 64) | (63 << 16) | (channel << 24)); // bitOffset, bitLength - 1, channelType
+        words.push(0); // samplePosition
+        words.push(0); // sampleLower
+        words.push(u32::MAX); // sampleUpper
+    }
+
+    words.into_iter().flat_map(u32::to_le_bytes).collect()
+}
+
+fn writer_key_value() -> (String, Vec<u8>) {
+    let mut writer = format!("gltf_ktxer v{}", env!("CARGO_PKG_VERSION")).into_bytes();
+    writer.push(0);
+    ("KTXwriter".to_string(), writer)
+}
+
+/// Section 3.10.3: each entry is a u32 length, a NUL-terminated key, the value, then padding to 4 bytes.
+/// Entries must be sorted by key.
+fn serialize_key_values(key_values: &[(String, Vec<u8>)]) -> Vec<u8> {
+    let mut sorted: Vec<_> = key_values.iter().collect();
+    sorted.sort_by(|a, b| a.0.as_bytes().cmp(b.0.as_bytes()));
+
+    let mut out = vec![];
+    for (key, value) in sorted {
+        let len = key.len() + 1 + value.len();
+        out.extend_from_slice(&(len as u32).to_le_bytes());
+        out.extend_from_slice(key.as_bytes());
+        out.push(0);
+        out.extend_from_slice(value);
+        out.resize(align_up(out.len(), 4), 0);
+    }
+    out
+}
+
+/// The inverse of [serialize_key_values].
+fn parse_key_values(mut kvd: &[u8]) -> Result<Vec<(String, Vec<u8>)>> {
+    let mut key_values = vec![];
+    while kvd.len() >= 4 {
+        let len = u32::from_le_bytes(kvd[..4].try_into().unwrap()) as usize;
+        let entry = kvd.get(4..4 + len).ok_or(Error::Ktx2Malformed("key/value entry is out of bounds"))?;
+        let key_len = entry.iter().position(|&b| b == 0).ok_or(Error::Ktx2Malformed("key isn't NUL-terminated"))?;
+        let key = String::from_utf8(entry[..key_len].to_vec()).map_err(|_| Error::Ktx2Malformed("key isn't UTF-8"))?;
+        key_values.push((key, entry[key_len + 1..].to_vec()));
+        kvd = kvd.get(align_up(4 + len, 4)..).unwrap_or_default();
+    }
+    Ok(key_values)
+}
+
+fn align_up(x: usize, align: usize) -> usize {
+    x.div_ceil(align) * align
+}
+
+fn lcm(a: usize, b: usize) -> usize {
+    fn gcd(a: usize, b: usize) -> usize {
+        if b == 0 { a } else { gcd(b, a % b) }
+    }
+    a / gcd(a, b) * b
+}
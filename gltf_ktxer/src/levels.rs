@@ -0,0 +1,79 @@
+//! How many mip levels each KTX2 image should get, from its size, the samplers using it, and the platform it targets.
+//!
+//! A full mip chain isn't always usable, see [NpotMipmaps]:
+//! - WebGL 1 can't mipmap non-power-of-two (NPOT) textures at all, and can't sample them with repeat wrapping either.
+//! - WebGL 2 exposes BC formats through `WEBGL_compressed_texture_s3tc`, which requires every level after the first
+//!   to be 1, 2 or a multiple of 4 texels in each dimension. Halving an NPOT texture soon breaks that.
+//!
+//! Samplers whose minification filter doesn't use mipmaps never sample past the first level, so a chain is wasted space.
+
+use std::collections::HashMap;
+
+use crate::{edit, gltf::{GltfList, GltfSampler, GltfTexture}, profile::{NpotMipmaps, TargetProfile}, validate::Warning, ImageReencodeFormat, ImageReencodeJob, Result};
+
+/// The number of levels in a full mip chain for an image of `(width, height)`, down to 1x1.
+pub fn full_level_count((width, height): (u32, u32)) -> u32 {
+    u32::BITS - width.max(height).max(1).leading_zeros()
+}
+
+fn is_npot((width, height): (u32, u32)) -> bool {
+    !width.is_power_of_two() || !height.is_power_of_two()
+}
+
+/// The number of mip levels an image of `dimensions` should have when sampled with `sampler` on `target`, at least 1.
+pub fn level_count(dimensions: (u32, u32), sampler: &GltfSampler, target: TargetProfile) -> u32 {
+    let full = full_level_count(dimensions);
+    if !sampler.uses_mipmaps() {
+        return 1;
+    }
+    match target.constraints().npot_mipmaps {
+        NpotMipmaps::Unsupported if is_npot(dimensions) => 1,
+        NpotMipmaps::BlockAligned => {
+            let block_compatible = |dim: u32| dim <= 2 || dim.is_multiple_of(4);
+            (1..full)
+                .find(|&level| !block_compatible((dimensions.0 >> level).max(1)) || !block_compatible((dimensions.1 >> level).max(1)))
+                .unwrap_or(full)
+        }
+        NpotMipmaps::Unsupported | NpotMipmaps::Full => full,
+    }
+}
+
+/// Why an image of `dimensions` can't be sampled as `sampler` asks on `target`, whatever its level count.
+pub fn unsupported_sampling(dimensions: (u32, u32), sampler: &GltfSampler, target: TargetProfile) -> Option<String> {
+    (target.constraints().npot_mipmaps == NpotMipmaps::Unsupported && is_npot(dimensions) && sampler.repeats()).then(|| {
+        format!("{}x{} is not a power of two, so {target} can't sample it with repeat wrapping", dimensions.0, dimensions.1)
+    })
+}
+
+/// Set the level count of every KTX2 image in `jobs` which generates mipmaps, taking the fewest levels any texture using it allows.
+/// `textures` must already point at `jobs`, as returned in [crate::ReencodeJobs::new_textures].
+/// Images whose dimensions can't be read from their header keep a full chain.
+///
+/// Returns a `npot_repeat` warning for each texture `target` can't sample as its sampler asks.
+pub fn plan_level_counts(textures: &[GltfTexture], samplers: &Vec<GltfSampler>, jobs: &mut [ImageReencodeJob], target: TargetProfile) -> Result<Vec<Warning>> {
+    let mut warnings = vec![];
+    let mut counts: HashMap<usize, u32> = HashMap::new();
+    let default_sampler = GltfSampler::default();
+    for (tex_idx, tex) in textures.iter().enumerate() {
+        let Some(img_idx) = edit::texture_ktx_source(tex).map_or(Ok(None), |img| img.idx_within("images", jobs.len()))? else {
+            continue;
+        };
+        let job = &jobs[img_idx];
+        let Some(dimensions) = job.source_dimensions() else {
+            continue;
+        };
+        let dimensions = job.fit_dimensions(dimensions);
+        let sampler = samplers.gltf_index(tex.sampler, "samplers").map_err(|e| e.at(format!("/textures/{tex_idx}")))?.unwrap_or(&default_sampler);
+        let count = level_count(dimensions, sampler, target);
+        counts.entry(img_idx).and_modify(|existing| *existing = (*existing).min(count)).or_insert(count);
+        if let Some(message) = unsupported_sampling(dimensions, sampler, target) {
+            warnings.push(Warning { code: "npot_repeat", json_pointer: format!("/textures/{tex_idx}"), message });
+        }
+    }
+    for (img_idx, count) in counts {
+        if let ImageReencodeFormat::Ktx { mipmaps: true, level_count, .. } = &mut jobs[img_idx].reencode_as {
+            *level_count = Some(count);
+        }
+    }
+    Ok(warnings)
+}
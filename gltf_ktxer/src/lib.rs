@@ -1,61 +1,255 @@
-use std::{collections::{HashMap, HashSet}, num::NonZeroU8};
+use std::{collections::{BTreeSet, HashMap, HashSet}, num::{NonZeroU8, NonZeroUsize}, sync::{Arc, Mutex}};
 
-use gltf::{GltfBuffer, GltfBufferView, GltfDoc, GltfImage, GltfIndex, GltfList, GltfTexture, U8VecOrSlice};
+use gltf::{GltfBuffer, GltfBufferView, GltfDoc, GltfImage, GltfIndex, GltfList, GltfTexture, GltfUri, U8VecOrSlice};
 // use libktx_rs::{sources::{CommonCreateInfo, Ktx2CreateInfo}, sys::ktxStream, TextureSource};
 
-mod gltf;
+pub mod adjust;
+pub mod animation;
+pub mod argfile;
+pub mod bundle;
+pub mod basis;
+pub mod cache;
+pub mod color;
+pub mod config;
+pub mod converted;
+pub mod decision;
+pub mod dedup;
+pub mod decode;
+pub mod depfile;
+pub mod disk_cache;
+pub mod dither;
+pub mod corpus;
+pub mod edit;
+pub mod etc1s;
+pub mod external_validate;
+pub mod fallback;
+pub mod filenames;
+pub mod fingerprint;
+pub mod gc;
+pub mod glb;
+pub mod gltf;
 mod error;
-use error::{Error, Result};
+pub mod groups;
+pub mod hash;
+pub mod ktx2;
+pub mod hints;
+pub mod job_limits;
+pub mod levels;
+pub mod limits;
+pub mod load;
+pub mod manifest;
+pub mod memory;
+pub mod mipmap;
+pub mod output;
+pub mod overrides;
+pub mod pipeline;
+pub mod placeholder;
+pub mod precompress;
+pub mod preset;
+pub mod profile;
+pub mod quality;
+pub mod report;
+pub mod schedule;
+#[cfg(feature = "schema")]
+pub mod schema;
+pub mod semantic;
+pub mod shutdown;
+pub mod slim;
+pub mod stats;
+pub mod summary;
+pub mod tiers;
+pub mod tiling;
+pub mod tuning;
+pub mod usage;
+pub mod uv_checker;
+pub mod validate;
+pub mod variants;
+pub mod video;
+pub mod watermark;
+pub use error::{Error, ErrorCode, Result};
+use image::RgbaImage;
 use serde::{de::DeserializeOwned, Serialize};
-use serde_json::json;
-use thiserror::Error;
+use glb::JsonFormat;
+use edit::{set_texture_extension_source, set_texture_ktx_source, texture_extension_source, texture_ktx_source};
+use schedule::JobOrder;
 
-struct Input<'a> {
-    gltf_json: &'a mut GltfDoc,
-    binaries: &'a HashMap<Option<String>, Vec<u8>>,
+pub struct Input<'a> {
+    pub gltf_json: &'a mut GltfDoc,
+    pub binaries: &'a HashMap<Option<String>, Vec<u8>>,
 }
 impl<'a> Input<'a> {
-    fn get_list<T: DeserializeOwned>(&self, name: &str) -> Result<Vec<T>> {
-        match self.gltf_json.get(name) {
-            None => Ok(vec![]),
-            Some(value) => Ok(serde_json::from_value(value.clone())?)
-        }
+    pub fn get_list<T: DeserializeOwned>(&self, name: &str) -> Result<Vec<T>> {
+        gltf::deserialize_list(self.gltf_json, name)
     }
-    fn get_gltf_index<T: DeserializeOwned>(&self, idx: GltfIndex<T>, list_name: &'static str) -> Result<Option<T>> {
+    pub fn get_gltf_index<T: DeserializeOwned>(&self, idx: GltfIndex<T>, list_name: &'static str) -> Result<Option<T>> {
         match self.gltf_json.get(list_name).and_then(|val| val.as_array()) {
             Some(array) => match idx.idx_within(list_name, array.len())? {
                 Some(idx) => {
                     let value = &array[idx];
-                    Ok(Some(serde_json::from_value(value.clone())?))
+                    Ok(Some(T::deserialize(value)?))
                 }
                 None => Ok(None)
             }
             _ => Ok(None) // TODO this will end up producing IdxNotSet which is the wrong kind of erroor...
         }
     }
-    fn get_gltf_index_required<T: DeserializeOwned>(&self, idx: GltfIndex<T>, list_name: &'static str) -> Result<T> {
+    pub fn get_gltf_index_required<T: DeserializeOwned>(&self, idx: GltfIndex<T>, list_name: &'static str) -> Result<T> {
         match self.get_gltf_index(idx, list_name)? {
             None => Err(Error::IdxNotSet { list_name }),
             Some(data) => Ok(data),
         }
     }
-    fn set_list<T: Serialize>(&mut self, name: &str, data: Vec<T>) -> Result<()> {
+    pub fn set_list<T: Serialize>(&mut self, name: &str, data: Vec<T>) -> Result<()> {
         self.gltf_json.insert(name.to_string(), serde_json::to_value(data)?);
         Ok(())
     }
-    fn consume_doc(mut self) -> GltfDoc {
-        std::mem::take(&mut self.gltf_json)
+    pub fn consume_doc(self) -> GltfDoc {
+        std::mem::take(self.gltf_json)
     }
 }
 
-struct Output {
-    gltf_json: GltfDoc,
-    binary: Vec<u8>,
+pub struct Output {
+    pub gltf_json: GltfDoc,
+    /// The data for buffer 0 if it has no URI, i.e. the GLB binary chunk.
+    pub binary: Vec<u8>,
+    /// The data for buffers with external URIs, keyed by URI.
+    /// Only filled in when buffers are kept separate, see [BufferLayout::PerBuffer].
+    pub external_binaries: HashMap<String, Vec<u8>>,
+    /// What [Params::dedup_image_views] merged.
+    pub image_view_dedup: dedup::DedupStats,
+    /// What [Params::dedup_samplers_and_textures] merged.
+    pub table_dedup: dedup::TableDedupStats,
+    /// What [Params::slim_json] removed, and the JSON size before and after.
+    pub json_slim: slim::SlimStats,
+}
+impl Output {
+    /// The output as a GLB container, with [Output::binary] as the binary chunk.
+    /// [Output::external_binaries] must be written alongside it.
+    pub fn to_glb(&self, json_format: JsonFormat) -> Result<Vec<u8>> {
+        glb::write(&self.gltf_json, &self.binary, json_format)
+    }
+    /// The output as `.gltf` JSON, pointing buffer 0 at `binary_uri` if it's the GLB binary chunk.
+    /// If [Output::binary] isn't empty it must then be written to `binary_uri`, and [Output::external_binaries] alongside it.
+    pub fn to_gltf(&self, binary_uri: &str, json_format: JsonFormat) -> Result<Vec<u8>> {
+        let mut gltf_json = self.gltf_json.clone();
+        if let Some(buffer) = gltf_json.get_mut("buffers").and_then(|val| val.get_mut(0)).filter(|_| !self.binary.is_empty()) {
+            let buffer = buffer.as_object_mut().ok_or(Error::ExpectedObject { key: "buffers" })?;
+            if !buffer.contains_key("uri") {
+                buffer.insert("uri".to_string(), binary_uri.into());
+            }
+        }
+        glb::json_bytes(&gltf_json, json_format)
+    }
 }
 
+/// How the input's buffers are laid out in the output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BufferLayout {
+    /// Copy every buffer view into a single new buffer, see [pack_buffers_together].
+    #[default]
+    Repack,
+    /// Keep the GLB binary chunk byte-for-byte and only append new data to the end, see [keep_buffers_in_place].
+    /// Falls back to [BufferLayout::Repack] if buffer 0 isn't a GLB binary chunk.
+    InPlace,
+    /// Repack each buffer's views separately, keeping one output buffer per input buffer with the same name and URI.
+    /// Only makes sense when writing a .gltf with separate .bin files, see [pack_buffers_separately].
+    PerBuffer,
+    /// Pack image views into one external buffer and everything else into another, see [pack_images_separately].
+    /// The buffer URIs are taken from [Params::geometry_buffer_uri] and [Params::image_buffer_uri].
+    SplitImages,
+}
 
+pub fn prepare_output_buffers(mut input: Input<'_>, params: &Params) -> Result<Output> {
+    if params.strip_image_view_targets {
+        edit::strip_image_view_targets(input.gltf_json)?;
+    }
+    let table_dedup = if params.dedup_samplers_and_textures {
+        dedup::dedup_samplers_and_textures(input.gltf_json, &params.references)?
+    } else {
+        dedup::TableDedupStats::default()
+    };
+    // Every layout but InPlace repacks, which is what drops the bytes of merged views
+    let dedup = |input: &mut Input<'_>| {
+        if params.dedup_image_views {
+            dedup::dedup_image_views(input.gltf_json, input.binaries, &params.references)
+        } else {
+            Ok(dedup::DedupStats::default())
+        }
+    };
+    let (image_view_dedup, output) = match params.buffer_layout {
+        BufferLayout::Repack if params.record_merged_buffer_names => {
+            let buffers: Vec<GltfBuffer> = input.get_list("buffers")?;
+            let stats = dedup(&mut input)?;
+            let mut output = pack_buffers_together(input)?;
+            record_merged_buffers(&mut output.gltf_json, &buffers)?;
+            (stats, output)
+        }
+        BufferLayout::Repack => (dedup(&mut input)?, pack_buffers_together(input)?),
+        BufferLayout::InPlace => match keep_buffers_in_place(input.gltf_json, input.binaries)? {
+            Some(output) => (dedup::DedupStats::default(), output),
+            None => (dedup(&mut input)?, pack_buffers_together(input)?),
+        },
+        BufferLayout::PerBuffer => (dedup(&mut input)?, pack_buffers_separately(input)?),
+        BufferLayout::SplitImages => (dedup(&mut input)?, pack_images_separately(input, &params.geometry_buffer_uri, &params.image_buffer_uri)?),
+    };
+    let mut output = Output { image_view_dedup, table_dedup, ..output };
+    // Last, as packing rewrites buffers and views
+    if let Some(options) = &params.slim_json {
+        output.json_slim = slim::slim_json(&mut output.gltf_json, options)?;
+    }
+    Ok(output)
+}
+
+/// Record the name and URI of each buffer that was merged into buffer 0 in its `extras.mergedBuffers`,
+/// for pipelines which key on the original buffer names.
+/// Data URIs are left out, as they're just a copy of the data.
+fn record_merged_buffers(gltf_json: &mut GltfDoc, merged: &[GltfBuffer]) -> Result<()> {
+    let merged: Vec<serde_json::Value> = merged
+        .iter()
+        .map(|buffer| {
+            let mut record = serde_json::Map::new();
+            if let Some(name) = &buffer.name {
+                record.insert("name".to_string(), name.clone().into());
+            }
+            if let Some(uri) = buffer.uri.as_ref().filter(|uri| !uri.is_data_uri()) {
+                record.insert("uri".to_string(), uri.as_str().into());
+            }
+            serde_json::Value::Object(record)
+        })
+        .collect();
+    let Some(buffer) = gltf_json.get_mut("buffers").and_then(|val| val.get_mut(0)) else {
+        return Ok(());
+    };
+    let extras = buffer
+        .as_object_mut()
+        .ok_or(Error::ExpectedObject { key: "buffers" })?
+        .entry("extras")
+        .or_insert_with(|| serde_json::Value::Object(Default::default()));
+    extras
+        .as_object_mut()
+        .ok_or(Error::ExpectedObject { key: "extras" })?
+        .insert("mergedBuffers".to_string(), merged.into());
+    Ok(())
+}
+
+/// Leave every buffer and buffer view where it is, starting the output binary as a copy of the GLB binary chunk.
+/// New image views can then be appended to the end with [edit::append_buffer_view],
+/// which avoids copying gigabytes of geometry around when only images change.
+/// The views of replaced images are left behind as unreferenced bytes.
+///
+/// Returns None if buffer 0 has a URI, as then there's no GLB binary chunk to append to.
+pub fn keep_buffers_in_place(gltf_json: &mut GltfDoc, binaries: &HashMap<Option<String>, Vec<u8>>) -> Result<Option<Output>> {
+    let input = Input { gltf_json, binaries };
+    let buffers: Vec<GltfBuffer> = input.get_list("buffers")?;
+    let binary = match buffers.first() {
+        None => vec![],
+        Some(buffer) if buffer.uri.is_none() => buffer.dump_data(0, binaries)?.to_vec(),
+        Some(_) => return Ok(None),
+    };
+    Ok(Some(Output { gltf_json: input.consume_doc(), binary, external_binaries: HashMap::new(), image_view_dedup: Default::default(), table_dedup: Default::default(), json_slim: Default::default() }))
+}
 
-fn pack_buffers_together(mut input: Input<'_>) -> Result<Output> {
+pub fn pack_buffers_together(mut input: Input<'_>) -> Result<Output> {
     let buffers: Vec<GltfBuffer> = input.get_list("buffers")?;
     let buffer_views: Vec<GltfBufferView> = input.get_list("bufferViews")?;
 
@@ -64,57 +258,163 @@ fn pack_buffers_together(mut input: Input<'_>) -> Result<Output> {
         .enumerate()
         .map(|(idx, b)| b.dump_data(idx, input.binaries))
         .collect::<Result<_>>()?;
-    let (new_buffer_views, new_buffer) = pack_buffer_views(
-        buffer_views.into_iter().map(|v| {
-            let slice = v.slice_from(&buffer_datas)?;
-            Ok((v, slice))
+    let (new_buffer_views, new_buffer) = pack_buffer_views(buffer_views, &buffer_datas)?;
+
+    input.set_list("buffers", vec![GltfBuffer::new(new_buffer.len())])?;
+    input.set_list("bufferViews", new_buffer_views)?;
+    Ok(Output { gltf_json: input.consume_doc(), binary: new_buffer, external_binaries: HashMap::new(), image_view_dedup: Default::default(), table_dedup: Default::default(), json_slim: Default::default() })
+}
+
+/// Like [pack_buffers_together], but packs the views of each buffer into their own output buffer,
+/// keeping the buffer's name, URI, extensions and extras.
+/// Buffers with data URIs get a new data URI holding the packed data.
+pub fn pack_buffers_separately(input: Input<'_>) -> Result<Output> {
+    let buffers: Vec<GltfBuffer> = input.get_list("buffers")?;
+    let buffer_views: Vec<GltfBufferView> = input.get_list("bufferViews")?;
+    let groups = buffers
+        .into_iter()
+        .enumerate()
+        .map(|(buffer_idx, buffer)| {
+            let view_idxs = (0..buffer_views.len())
+                .filter(|&view_idx| buffer_views[view_idx].buffer.raw_idx() == buffer_idx)
+                .collect();
+            (buffer, view_idxs)
         })
-    )?;
-
-    input.set_list("buffers", vec![
-        GltfBuffer {
-            uri: None,
-            byte_length: new_buffer.len(),
-            name: serde_json::Value::Null,
-            extensions: serde_json::Value::Null,
-            extras: serde_json::Value::Null,
+        .collect();
+    pack_view_groups(input, groups)
+}
+
+/// Pack the views used by images into a separate buffer from everything else,
+/// so engines can stream textures independently of geometry.
+/// Both buffers are external files with the given URIs, and a buffer is only created if it has any views.
+pub fn pack_images_separately(input: Input<'_>, geometry_uri: &str, image_uri: &str) -> Result<Output> {
+    let buffer_views: Vec<GltfBufferView> = input.get_list("bufferViews")?;
+    let images: Vec<GltfImage> = input.get_list("images")?;
+    let image_views: HashSet<usize> = images
+        .iter()
+        .filter(|image| image.buffer_view.is_defined())
+        .map(|image| image.buffer_view.raw_idx())
+        .collect();
+    let (image_view_idxs, geometry_view_idxs): (Vec<usize>, Vec<usize>) = (0..buffer_views.len())
+        .partition(|view_idx| image_views.contains(view_idx));
+    let groups = [(geometry_uri, geometry_view_idxs), (image_uri, image_view_idxs)]
+        .into_iter()
+        .filter(|(_, view_idxs)| !view_idxs.is_empty())
+        .map(|(uri, view_idxs)| (GltfBuffer::new(0).with_uri(uri), view_idxs))
+        .collect();
+    pack_view_groups(input, groups)
+}
+
+/// Pack each group of buffer views into its own buffer, taking the name, URI, extensions and extras from the group's template buffer.
+/// Buffers with data URIs get a new data URI holding the packed data, and every view must be in exactly one group.
+fn pack_view_groups(mut input: Input<'_>, groups: Vec<(GltfBuffer, Vec<usize>)>) -> Result<Output> {
+    let buffers: Vec<GltfBuffer> = input.get_list("buffers")?;
+    let buffer_views: Vec<GltfBufferView> = input.get_list("bufferViews")?;
+
+    let buffer_datas: Vec<U8VecOrSlice<'_>> = buffers
+        .iter()
+        .enumerate()
+        .map(|(idx, b)| b.dump_data(idx, input.binaries))
+        .collect::<Result<_>>()?;
+
+    // Check every view is in bounds up front, as views pointing at a buffer that doesn't exist aren't in any group.
+    for view in &buffer_views {
+        view.slice_from(&buffer_datas)?;
+    }
+
+    let mut new_buffer_views = vec![None; buffer_views.len()];
+    let mut new_buffers = vec![];
+    let mut binary = vec![];
+    let mut external_binaries = HashMap::new();
+    for (buffer_idx, (buffer, view_idxs)) in groups.into_iter().enumerate() {
+        let (packed_views, new_buffer) = pack_buffer_views(view_idxs.iter().map(|&view_idx| buffer_views[view_idx].clone()), &buffer_datas)?;
+        for (view_idx, view) in view_idxs.into_iter().zip(packed_views) {
+            new_buffer_views[view_idx] = Some(GltfBufferView { buffer: GltfIndex::of(buffer_idx), ..view });
         }
-    ])?;
+
+        let byte_length = new_buffer.len();
+        let uri = match buffer.uri {
+            Some(uri) if uri.is_data_uri() => Some(GltfUri::buffer_data_uri(&new_buffer)),
+            Some(uri) => {
+                external_binaries.insert(uri.as_str().to_string(), new_buffer);
+                Some(uri)
+            }
+            None => {
+                binary = new_buffer;
+                None
+            }
+        };
+        new_buffers.push(GltfBuffer { uri, byte_length, ..buffer });
+    }
+    let new_buffer_views: Vec<GltfBufferView> = new_buffer_views.into_iter().flatten().collect();
+
+    input.set_list("buffers", new_buffers)?;
     input.set_list("bufferViews", new_buffer_views)?;
-    Ok(Output { gltf_json: input.consume_doc(), binary: new_buffer, })
+    Ok(Output { gltf_json: input.consume_doc(), binary, external_binaries, image_view_dedup: Default::default(), table_dedup: Default::default(), json_slim: Default::default() })
 }
 
-fn pack_buffer_views<'a, I>(iter: I) -> Result<(Vec<GltfBufferView>, Vec<u8>)>
-    where I: IntoIterator<Item = Result<(GltfBufferView, &'a [u8])>>
+/// Copy the given views into a new buffer, in order, with every view starting 4-byte aligned.
+///
+/// Runs of views which are laid out back-to-back in the same source buffer are copied as one region,
+/// so packing a geometry-heavy file that's already tightly packed is a handful of large copies rather than one per view.
+/// Any padding between views in a run is copied from the source instead of zeroed.
+fn pack_buffer_views<I>(views: I, buffer_datas: &Vec<U8VecOrSlice<'_>>) -> Result<(Vec<GltfBufferView>, Vec<u8>)>
+    where I: IntoIterator<Item = GltfBufferView>
 {
+    /// A region of a source buffer which is copied in one go, starting at `out_offset` in the new buffer.
+    struct Run<'a> {
+        buffer: usize,
+        data: &'a [u8],
+        start: usize,
+        end: usize,
+        out_offset: usize,
+    }
+    fn flush(run: Run<'_>, new_buffer: &mut Vec<u8>) {
+        new_buffer.extend_from_slice(&run.data[run.start..run.end]);
+        // Pad out the new_buffer to be 4-byte aligned.
+        // Section 3.6.2.4 https://registry.khronos.org/glTF/specs/2.0/glTF-2.0.html#data-alignment
+        // requires accessor.byteOffset and (accessor.byteOffset + bufferView.byteOffset) to 
+        // always be a multiple of the size of the accessor's component type.
+        // the maximum component type size is 4 (32 bits, as seen in 3.6.2.2 Accessor Data Types).
+        // therefore always pad out to 4-bytes to be sure we're always aligned.
+        if !new_buffer.len().is_multiple_of(4) {
+            new_buffer.resize(new_buffer.len() + (4 - (new_buffer.len() % 4)), 0);
+        }
+        assert!(new_buffer.len().is_multiple_of(4));
+    }
+
     let mut new_buffer_views = vec![];
     let mut new_buffer = vec![];
+    let mut run: Option<Run<'_>> = None;
 
-    for item in iter {
-        match item {
-            Ok((buffer_view, data)) => {
-                new_buffer_views.push(
-                    GltfBufferView {
-                        buffer: 0.into(),
-                        byte_offset: data.len(),
-                        ..buffer_view
-                    }
-                );
-                new_buffer.extend_from_slice(data);
-                // Pad out the new_buffer to be 4-byte aligned.
-                // Section 3.6.2.4 https://registry.khronos.org/glTF/specs/2.0/glTF-2.0.html#data-alignment
-                // requires accessor.byteOffset and (accessor.byteOffset + bufferView.byteOffset) to 
-                // always be a multiple of the size of the accessor's component type.
-                // the maximum component type size is 4 (32 bits, as seen in 3.6.2.2 Accessor Data Types).
-                // therefore always pad out to 4-bytes to be sure we're always aligned.
-                if new_buffer.len() % 4 != 0 {
-                    new_buffer.resize(new_buffer.len() + (4 - (new_buffer.len() % 4)), 0);
+    for buffer_view in views {
+        buffer_view.slice_from(buffer_datas)?;
+        let buffer = buffer_view.buffer.raw_idx();
+        let (start, end) = (buffer_view.byte_offset, buffer_view.byte_offset + buffer_view.byte_length);
+
+        // A view continues the run if it starts exactly where the padded end of the run would be,
+        // so copying the gap along with it puts the view where it would have been packed anyway.
+        let continues_run = run.as_ref().is_some_and(|run| {
+            run.buffer == buffer && start >= run.end && start - run.end < 4 && (start - run.start) % 4 == 0
+        });
+        let byte_offset = match &mut run {
+            Some(run) if continues_run => {
+                run.end = end;
+                run.out_offset + (start - run.start)
+            }
+            _ => {
+                if let Some(run) = run.take() {
+                    flush(run, &mut new_buffer);
                 }
-                assert!(new_buffer.len() % 4 == 0);
+                let data = &buffer_datas[buffer][..];
+                run = Some(Run { buffer, data, start, end, out_offset: new_buffer.len() });
+                new_buffer.len()
             }
-            Err(e) => return Err(e)
-        } 
-        
+        };
+        new_buffer_views.push(GltfBufferView { buffer: 0.into(), byte_offset, ..buffer_view });
+    }
+    if let Some(run) = run {
+        flush(run, &mut new_buffer);
     }
 
     Ok((new_buffer_views, new_buffer))
@@ -130,109 +430,488 @@ fn pack_buffer_views<'a, I>(iter: I) -> Result<(Vec<GltfBufferView>, Vec<u8>)>
 //     export_as_srgb: bool,
 // }
 
-fn texture_ktx_source(texture: &GltfTexture) -> Option<GltfIndex<GltfImage>> {
-    texture
-        .extensions
-        .as_object()?
-        .get("KHR_texture_basisu")?
-        .as_object()?
-        .get("source")?
-        .as_u64()
-        .map(|idx| GltfIndex::of(idx as usize))
-}
-fn set_texture_ktx_source(texture: &mut GltfTexture, new_idx: GltfIndex<GltfImage>) -> Result<()> {
-    assert!(new_idx.is_defined());
-
-    let ext = match &mut texture.extensions {
-        serde_json::Value::Object(obj) => obj,
-        serde_json::Value::Null => {
-            texture.extensions = serde_json::Value::Object(serde_json::Map::new());
-            texture.extensions.as_object_mut().unwrap()
-        }
-        _ => return Err(Error::TextureHasInvalidExtensions)
-    };
-    
-    ext["KHR_texture_basisu"] = json!({
-        "source": (new_idx.raw_idx())
-    });
-
-    Ok(())
+/// Textures used in any slot whose [semantic::SemanticSettings] are sRGB.
+fn get_srgb_texture_indices(texture_semantics: &HashMap<GltfIndex<GltfTexture>, BTreeSet<semantic::Semantic>>, slots: &semantic::SlotRegistry) -> HashSet<GltfIndex<GltfTexture>> {
+    texture_semantics
+        .iter()
+        .filter(|(_, semantics)| semantics.iter().any(|&semantic| slots.settings(semantic).color_space == ktx2::ColorSpace::Srgb))
+        .map(|(&texture, _)| texture)
+        .collect()
 }
 
-fn material_diffuse_tex(mat: &serde_json::Value) -> Option<GltfIndex<GltfTexture>> {
-    mat
-        .as_object()?
-        .get("pbrMetallicRoughness")?
-        .as_object()?
-        .get("baseColorTexture")?
-        .as_object()?
-        .get("index")?
-        .as_u64()
-        .map(|idx| GltfIndex::of(idx as usize))
-}
-fn material_emissive_tex(mat: &serde_json::Value) -> Option<GltfIndex<GltfTexture>> {
-    mat
-        .as_object()?
-        .get("emissiveTexture")?
-        .as_object()?
-        .get("index")?
-        .as_u64()
-        .map(|idx| GltfIndex::of(idx as usize))
-}
-
-fn get_srgb_texture_indices(input: &Input) -> HashSet<GltfIndex<GltfTexture>> {
-    let mut set = HashSet::new();
-    if let Some(materials) = input.gltf_json.get("materials").and_then(|val| val.as_array()) {
-        for mat in materials {
-            if let Some(diffuse) = material_diffuse_tex(mat) {
-                set.insert(diffuse);
-            }
-            if let Some(emissive) = material_emissive_tex(mat) {
-                set.insert(emissive);
-            }
-        }
-    }
-    set
+pub struct ReencodeJobs {
+    pub new_textures: Vec<GltfTexture>,
+    pub new_images: Vec<ImageReencodeJob>,
+    /// Problems found while planning, like limits the output exceeds if [Params::limits] is set to warn.
+    pub warnings: Vec<validate::Warning>,
+    /// The estimated memory needed to run [ReencodeJobs::new_images] on [Params::max_threads] threads.
+    pub memory: memory::MemoryEstimate,
+    /// Statistics of each source image planned from, if [Params::image_stats] is set.
+    pub image_stats: HashMap<GltfIndex<GltfImage>, stats::ImageStats>,
+    /// Each image no texture uses, converted with [Params::convert_orphan_images], and the index of its converted image.
+    /// The indices of images change, so references to them from outside the textures, e.g. in custom extensions, must be updated.
+    pub orphan_images: Vec<(GltfIndex<GltfImage>, GltfIndex<GltfImage>)>,
+    /// Each other image referenced through [Params::references] from outside the textures, and the index of the
+    /// [ImageReencodeFormat::Copy] job keeping it as it is.
+    pub referenced_images: Vec<(GltfIndex<GltfImage>, GltfIndex<GltfImage>)>,
 }
 
-struct ReencodeJobs {
-    new_textures: Vec<GltfTexture>,
-    new_images: Vec<ImageReencodeJob>,
-}
-
-enum ImageReencodeFormat {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageReencodeFormat {
     Basic(image::ImageFormat),
     // a KTX2 texture using basis compression
     Ktx {
+        codec: KtxCodec,
         basis_compression_quality: Option<NonZeroU8>,
         transcoded_to_bc1_or_bc3: bool,
+        mipmaps: bool,
+        /// With `mipmaps`, the number of levels to keep from the start of the chain. A full chain if None.
+        level_count: Option<u32>,
+        /// With `mipmaps`, generate them with [ktx2::generate_atlas_mipmaps] on this grid.
+        atlas: Option<mipmap::TileGrid>,
+        /// Dither the image before encoding, for ETC1S only, see [dither].
+        dither: Option<dither::Dither>,
+        /// How hard the encoder works, or its default if None, see [tuning].
+        effort: Option<tuning::EncodeEffort>,
+    },
+    /// The source data, unchanged. Used for textures skipped by [animation::AnimationPolicy::Skip] or a [Params::decision_hook], and for failed jobs by [fallback::finish_jobs].
+    Copy,
+}
+
+/// The Basis Universal codec used for KTX2 images.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KtxCodec {
+    /// Small, lower quality. Good for color textures.
+    #[default]
+    Etc1s,
+    /// Larger, higher quality. Good for normal maps and other data textures.
+    Uastc,
+}
+impl std::str::FromStr for KtxCodec {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "etc1s" => Ok(KtxCodec::Etc1s),
+            "uastc" => Ok(KtxCodec::Uastc),
+            _ => Err(format!("unknown KTX2 codec '{s}', expected 'etc1s' or 'uastc'")),
+        }
+    }
+}
+impl std::fmt::Display for KtxCodec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            KtxCodec::Etc1s => "etc1s",
+            KtxCodec::Uastc => "uastc",
+        })
     }
 }
 
-struct Params {
-    uncompressed_format: image::ImageFormat,
-    ktx_basis_compression_quality: Option<NonZeroU8>,
-    ktx_transcode_to_bc1_or_bc3: bool,
+pub struct Params {
+    pub uncompressed_format: image::ImageFormat,
+    pub ktx_codec: KtxCodec,
+    pub ktx_basis_compression_quality: Option<NonZeroU8>,
+    pub ktx_transcode_to_bc1_or_bc3: bool,
+    /// Generate a full mip chain for KTX2 images.
+    pub generate_mipmaps: bool,
+    /// If set, adapt the output to the platform's constraints: downscale images to its maximum size,
+    /// only transcode to formats it supports, and cut mip chains short where it can't use them (see [levels::level_count]).
+    pub target: Option<profile::TargetProfile>,
+    /// Downscale images larger than this in either dimension, keeping their aspect ratio.
+    pub max_texture_size: Option<u32>,
+    /// Only downscale images used by textures with repeat wrapping by halving them, so they still tile, see [tiling].
+    pub preserve_tiling: bool,
+    /// How many resolution tiers to produce for each KTX2 image, each half the size of the last, see [tiers].
+    /// 1 produces just the full resolution image.
+    pub resolution_tiers: u32,
+    pub job_order: JobOrder,
+    /// Also emit an AVIF copy of every texture through EXT_texture_avif, for web-first consumers.
+    pub avif_fallback: bool,
+    /// Also convert images no texture uses to KTX2, for documents used as texture containers, see [ReencodeJobs::orphan_images].
+    /// They're planned as textures numbered after the document's own, which is what a [Params::decision_hook] sees.
+    pub convert_orphan_images: bool,
+    pub buffer_layout: BufferLayout,
+    /// When merging buffers with [BufferLayout::Repack], record the original buffer names and URIs in the new buffer's extras.
+    pub record_merged_buffer_names: bool,
+    pub geometry_buffer_uri: String,
+    pub image_buffer_uri: String,
+    /// Remove the GPU `target` from buffer views holding image data when repacking, see [edit::strip_image_view_targets].
+    pub strip_image_view_targets: bool,
+    /// Store byte-identical images once when repacking, see [dedup]. [BufferLayout::InPlace] never merges images.
+    pub dedup_image_views: bool,
+    /// Merge identical samplers, and textures with the same sources and sampler, see [dedup::dedup_samplers_and_textures].
+    pub dedup_samplers_and_textures: bool,
+    /// Shrink the output JSON, see [slim]. Off if None.
+    pub slim_json: Option<slim::SlimOptions>,
+    /// References in vendor extensions, which must be renumbered when [Params::dedup_image_views] or [Params::dedup_samplers_and_textures] remove objects.
+    pub references: edit::ReferenceRegistry,
+    /// Record the hash of each produced image's source data and encode settings in its `extras`, see [hash].
+    pub record_texture_hashes: bool,
+    /// Record the fingerprint of each output image in the document's `asset.extras`, for a later run's [Params::reuse_from], see [fingerprint].
+    pub record_fingerprints: bool,
+    /// A previous output to take images from instead of encoding them again, for jobs whose source and settings haven't changed,
+    /// see [fingerprint::PreviousOutput::reusable].
+    pub reuse_from: Option<Arc<fingerprint::PreviousOutput>>,
+    /// Checked against the planned output images before encoding.
+    pub limits: limits::Limits,
+    /// Check each KTX2 image against its source after encoding, see [ImageReencodeJob::verify_output].
+    pub verify_outputs: bool,
+    /// Settings for individual textures, keyed on their source image.
+    pub texture_overrides: overrides::TextureOverrides,
+    /// Which material slots hold which kind of data, and the color space each kind is encoded in.
+    pub slots: semantic::SlotRegistry,
+    /// Convert color images with an embedded non-sRGB color profile to sRGB when decoding them, see [color].
+    /// If unset, such images are decoded as if they were sRGB, with a warning.
+    pub convert_color_profiles: bool,
+    /// Rotate and flip images with an EXIF orientation, e.g. photos from phones, to their canonical orientation when decoding them.
+    /// KTX2 has no orientation flag, so if unset such images are encoded as stored, with a warning.
+    pub apply_exif_orientation: bool,
+    /// What to do with textures whose source image is animated, see [animation].
+    pub animated_images: animation::AnimationPolicy,
+    /// The maximum number of images to process at once. If None, use one thread per core.
+    pub max_threads: Option<NonZeroUsize>,
+    /// How hard the encoder works on KTX2 images. If None, the encoder's default. [tuning::Tuning::auto] picks one for the host.
+    pub encode_effort: Option<tuning::EncodeEffort>,
+    /// Fail while planning if the conversion is estimated to need more than this many bytes of memory, see [memory::estimate].
+    pub max_memory: Option<u64>,
+    /// Called for each texture while planning, to override its codec or quality or leave it unchanged, see [decision].
+    pub decision_hook: Option<decision::DecisionHook>,
+    /// What to do with images whose encoding fails, applied by [fallback::finish_jobs].
+    pub on_encode_failure: fallback::EncodeFailurePolicy,
+    /// Easier settings to retry failed images with, in order, applied by [fallback::encode_with_retries].
+    pub retry_ladder: Vec<fallback::Degradation>,
+    /// Abandon high-effort ETC1S encodes taking longer than this many seconds, and encode with less effort instead,
+    /// so one pathological texture can't stall a build, see [fallback::encode_with_time_limit]. Unlimited if None.
+    /// Must be finite and not negative, or [execute_reencode_jobs] fails with [Error::BadTimeLimit].
+    pub max_encode_seconds_per_texture: Option<f64>,
+    /// Follow the conversion hints asset authors put in the document's `extras`, see [hints].
+    pub extras_hints: bool,
+    /// Settings which take precedence over the document's hints, e.g. from command-line flags. Unset fields leave the hints to apply.
+    pub override_hints: hints::ConversionHints,
+    /// Groups of textures to give the same settings once each texture's settings are chosen, see [groups].
+    pub texture_groups: Vec<groups::TextureGroup>,
+    /// Transforms to choose from for each texture while planning, applied in order by [ImageReencodeJob::decode_and_transform].
+    pub image_transforms: Vec<Arc<dyn pipeline::ImageTransform>>,
+    /// Compute [stats::ImageStats] of every source image while planning, in [ReencodeJobs::image_stats].
+    /// This decodes every source image up front, and keeps them decoded until they're encoded.
+    pub image_stats: bool,
 }
 impl Default for Params {
     fn default() -> Self {
         Self {
             uncompressed_format: image::ImageFormat::Jpeg,
+            ktx_codec: KtxCodec::Etc1s,
             ktx_basis_compression_quality: None,
             ktx_transcode_to_bc1_or_bc3: true,
+            generate_mipmaps: false,
+            target: None,
+            max_texture_size: None,
+            preserve_tiling: true,
+            resolution_tiers: 1,
+            job_order: JobOrder::LargestFirst,
+            avif_fallback: false,
+            convert_orphan_images: false,
+            buffer_layout: BufferLayout::Repack,
+            record_merged_buffer_names: false,
+            geometry_buffer_uri: "geometry.bin".to_string(),
+            image_buffer_uri: "images.bin".to_string(),
+            strip_image_view_targets: true,
+            dedup_image_views: true,
+            dedup_samplers_and_textures: false,
+            slim_json: None,
+            references: edit::ReferenceRegistry::default(),
+            record_texture_hashes: false,
+            record_fingerprints: false,
+            reuse_from: None,
+            limits: limits::Limits::default(),
+            verify_outputs: false,
+            texture_overrides: overrides::TextureOverrides::default(),
+            slots: semantic::SlotRegistry::default(),
+            convert_color_profiles: true,
+            apply_exif_orientation: true,
+            animated_images: animation::AnimationPolicy::default(),
+            max_threads: None,
+            encode_effort: None,
+            max_memory: None,
+            decision_hook: None,
+            on_encode_failure: fallback::EncodeFailurePolicy::default(),
+            retry_ladder: fallback::DEFAULT_RETRY_LADDER.to_vec(),
+            max_encode_seconds_per_texture: None,
+            extras_hints: true,
+            override_hints: hints::ConversionHints::default(),
+            texture_groups: vec![],
+            image_transforms: vec![],
+            image_stats: false,
+        }
+    }
+}
+
+/// The encoded bytes of an image referenced by the input document.
+/// Shared between every job which re-encodes it, so it is only decoded once.
+pub struct SourceImage {
+    pub data: Vec<u8>,
+    pub mime_type: String,
+    color_conversion: Option<color::ColorConversion>,
+    orientation: Option<image::metadata::Orientation>,
+    decode_max_dimension: Mutex<Option<u32>>,
+    decoded: Mutex<Option<Arc<RgbaImage>>>,
+    stats: Mutex<Option<stats::ImageStats>>,
+}
+impl SourceImage {
+    pub fn new(data: Vec<u8>, mime_type: String) -> Self {
+        Self { data, mime_type, color_conversion: None, orientation: None, decode_max_dimension: Mutex::new(None), decoded: Mutex::new(None), stats: Mutex::new(None) }
+    }
+    /// Convert the image to sRGB with `conversion` after decoding it.
+    pub fn with_color_conversion(self, conversion: color::ColorConversion) -> Self {
+        Self { color_conversion: Some(conversion), ..self }
+    }
+    pub fn color_conversion(&self) -> Option<&color::ColorConversion> {
+        self.color_conversion.as_ref()
+    }
+    /// Rotate and flip the image to `orientation` after decoding it, see [decode::exif_orientation].
+    pub fn with_orientation(self, orientation: image::metadata::Orientation) -> Self {
+        Self { orientation: Some(orientation), ..self }
+    }
+    pub fn orientation(&self) -> Option<image::metadata::Orientation> {
+        self.orientation
+    }
+    /// Let the next decode downscale the image, as long as its longer side stays at least `max_dimension`,
+    /// see [decode::ImageDecoder::decode_within]. Has no effect if the image has already been decoded.
+    pub fn set_decode_max_dimension(&self, max_dimension: Option<u32>) {
+        *self.decode_max_dimension.lock().unwrap() = max_dimension;
+    }
+    /// Decode the image to RGBA8 with [decode::default_decoder], or return the result of a previous decode.
+    /// Concurrent callers wait for the first decode to finish instead of decoding again.
+    pub fn decode(&self) -> Result<Arc<RgbaImage>> {
+        self.decode_with(decode::default_decoder())
+    }
+    /// Like [SourceImage::decode], but with a specific decoder if the image hasn't been decoded yet.
+    pub fn decode_with(&self, decoder: &dyn decode::ImageDecoder) -> Result<Arc<RgbaImage>> {
+        let mut decoded = self.decoded.lock().unwrap();
+        if let Some(decoded) = decoded.as_ref() {
+            return Ok(decoded.clone());
+        }
+        let format = image::ImageFormat::from_mime_type(&self.mime_type);
+        let mut image = match *self.decode_max_dimension.lock().unwrap() {
+            Some(max_dimension) => decoder.decode_within(&self.data, format, max_dimension)?,
+            None => decoder.decode(&self.data, format)?,
+        };
+        if let Some(conversion) = &self.color_conversion {
+            conversion.apply(&mut image);
+        }
+        if let Some(orientation) = self.orientation {
+            image = decode::apply_orientation(image, orientation);
+        }
+        let image = Arc::new(image);
+        *decoded = Some(image.clone());
+        Ok(image)
+    }
+    /// Statistics of the decoded image, or the result of a previous call. This decodes the image, which is then reused when encoding.
+    pub fn stats(&self) -> Result<stats::ImageStats> {
+        if let Some(stats) = *self.stats.lock().unwrap() {
+            return Ok(stats);
         }
+        let stats = stats::ImageStats::of(&*self.decode()?);
+        *self.stats.lock().unwrap() = Some(stats);
+        Ok(stats)
     }
 }
 
-struct ImageReencodeJob {
-    data: Vec<u8>,
-    data_mime_type: String,
-    data_used_as_srgb: bool,
-    reencode_as: ImageReencodeFormat,
-    preexisting_buffer_view_idx: GltfIndex<GltfBufferView>,
+#[derive(Clone)]
+pub struct ImageReencodeJob {
+    pub source: Arc<SourceImage>,
+    pub data_used_as_srgb: bool,
+    pub reencode_as: ImageReencodeFormat,
+    /// Downscale the image to fit within this size first, see [Params::max_texture_size].
+    pub max_dimension: Option<u32>,
+    /// The image is sampled with repeat wrapping, so it's only downscaled by halving, which keeps it tiling, see [tiling].
+    pub repeats: bool,
+    /// Applied to the decoded source before anything else, see [adjust].
+    pub adjustments: Option<adjust::ColorAdjustments>,
+    /// Applied after downscaling, see [pipeline::ImageTransform].
+    pub transforms: Vec<Arc<dyn pipeline::ImageTransform>>,
+    pub preexisting_buffer_view_idx: GltfIndex<GltfBufferView>,
 }
 
-fn get_reencode_jobs(input: Input, params: Params) -> Result<ReencodeJobs> {
+/// Encode `image` as a Basis Universal KTX2 texture with [Params::ktx_codec].
+/// ETC1S textures are encoded with [etc1s::encode] at [Params::ktx_basis_compression_quality] and [Params::encode_effort].
+/// Nothing can encode UASTC yet.
+pub fn encode_ktx2(image: &RgbaImage, color_space: ktx2::ColorSpace, params: &Params) -> Result<ktx2::Ktx2Texture> {
+    match params.ktx_codec {
+        KtxCodec::Etc1s => etc1s::encode(image, color_space, params.ktx_basis_compression_quality, params.encode_effort),
+        KtxCodec::Uastc => Err(Error::EncoderUnavailable("UASTC")),
+    }
+}
+
+/// Add a brand-new texture made from `image` to the document, e.g. a baked lightmap or AO map,
+/// encoded with [encode_ktx2] and added with [edit::add_ktx2_texture].
+/// Hook it up to a material with [edit::set_material_texture].
+pub fn add_texture_from_rgba(doc: &mut GltfDoc, bin: &mut Vec<u8>, image: &RgbaImage, color_space: ktx2::ColorSpace, sampler: GltfIndex<gltf::GltfSampler>, params: &Params) -> Result<GltfIndex<GltfTexture>> {
+    let ktx = encode_ktx2(image, color_space, params)?;
+    edit::add_ktx2_texture(doc, bin, &ktx, sampler)
+}
+
+impl ImageReencodeJob {
+    /// Check `ktx`, the result of this job, against the source image with [ktx2::verify_encoded].
+    pub fn verify_output(&self, ktx: &ktx2::Ktx2Texture) -> Result<()> {
+        let source = self.source.decode()?;
+        ktx2::verify_encoded(ktx, &source, self.fit_dimensions(source.dimensions()))
+    }
+
+    /// The size this job downscales an image of `dimensions` to, see [ImageReencodeJob::max_dimension] and [ImageReencodeJob::repeats].
+    pub fn fit_dimensions(&self, dimensions: (u32, u32)) -> (u32, u32) {
+        match self.max_dimension {
+            Some(max_dimension) if self.repeats => tiling::halved_dimensions(dimensions, max_dimension),
+            Some(max_dimension) => ktx2::fit_dimensions(dimensions, max_dimension),
+            None => dimensions,
+        }
+    }
+}
+
+pub fn get_reencode_jobs(input: Input, params: Params) -> Result<ReencodeJobs> {
+    let jobs = plan_reencode_jobs(&input, &params, params.target, &mut HashMap::new())?;
+    limit_source_decodes(&jobs.new_images);
+    Ok(jobs)
+}
+
+/// Plan the jobs for each of `targets` in turn from one parse of the document, in place of [Params::target].
+/// The plans share their [SourceImage]s, so each source image is decoded at most once however many targets use it,
+/// at the largest size any of the plans needs.
+pub fn get_reencode_jobs_per_target(input: Input, params: &Params, targets: &[profile::TargetProfile]) -> Result<Vec<ReencodeJobs>> {
+    let mut sources = HashMap::new();
+    let plans = targets.iter().map(|&target| plan_reencode_jobs(&input, params, Some(target), &mut sources)).collect::<Result<Vec<_>>>()?;
+    limit_source_decodes(plans.iter().flat_map(|plan| &plan.new_images));
+    Ok(plans)
+}
+
+/// Run `jobs`, planned from `input` by [get_reencode_jobs], and write their output into the document:
+/// the [pipeline::Stage::DecodeTransform] and [pipeline::Stage::Encode] stages on [Params::max_threads] threads,
+/// retrying, limiting and handling failures as [Params::retry_ladder], [Params::max_encode_seconds_per_texture] and [Params::on_encode_failure] say (see [fallback]),
+/// then the [pipeline::Stage::Pack] stage with [prepare_output_buffers].
+///
+/// Jobs [Params::reuse_from] has an image for aren't encoded, and take that image instead.
+///
+/// The document's images are replaced by one image per job, in order, stored in buffer views appended to buffer 0,
+/// and its textures by [ReencodeJobs::new_textures]. The views only the old images used are removed.
+/// If buffer 0 has a URI, its data is moved into the GLB binary chunk, [Output::binary].
+/// Registered [Params::references] to images are renumbered to their converted copies, see [ReencodeJobs::orphan_images]
+/// and [ReencodeJobs::referenced_images], failing with [Error::ImageReferenceNotPlanned] for any image with no copy.
+/// `jobs` is updated to match the output, with warnings about failed and degraded images added, see [fallback::finish_jobs].
+///
+/// `binaries` are taken by value so buffer 0 can be moved into the output instead of copied, and KTX2 images are serialized
+/// straight into it with [edit::append_ktx2_buffer_view].
+pub fn execute_reencode_jobs(jobs: &mut ReencodeJobs, doc: &mut GltfDoc, mut binaries: HashMap<Option<String>, Vec<u8>>, params: &Params) -> Result<Output> {
+    let order = schedule::job_order(&jobs.new_images, params.job_order);
+    let verify_outputs = params.verify_outputs;
+    let time_limit = fallback::time_limit(params.max_encode_seconds_per_texture)?;
+    let results = schedule::run_jobs(&jobs.new_images, &order, params.max_threads, |job| {
+        match params.reuse_from.as_deref().and_then(|previous| previous.reusable(job)) {
+            Some(data) => Ok(pipeline::EncodedImage::Data(data.to_vec()).into()),
+            None => fallback::encode_with_time_limit(job, &params.retry_ladder, time_limit, move |job| job.encode_image(verify_outputs)),
+        }
+    });
+    let outputs = fallback::finish_jobs(jobs, results, params.on_encode_failure)?;
+
+    let new_image_idxs: HashMap<u64, u64> =
+        jobs.orphan_images.iter().chain(&jobs.referenced_images).map(|(old, new)| (old.raw_idx() as u64, new.raw_idx() as u64)).collect();
+    if let Some(&idx) = referenced_images(doc, &params.references)?.iter().find(|&&idx| !new_image_idxs.contains_key(&(idx as u64))) {
+        return Err(Error::ImageReferenceNotPlanned { idx });
+    }
+    // The textures' own references are replaced below
+    edit::for_each_reference(doc, "images", &params.references, &mut |reference| {
+        if let Some(&new_idx) = reference.as_u64().and_then(|old_idx| new_image_idxs.get(&old_idx)) {
+            *reference = new_idx.into();
+        }
+    })?;
+    let old_images: Vec<GltfImage> = gltf::deserialize_list(doc, "images")?;
+    Input { gltf_json: doc, binaries: &binaries }.set_list("textures", jobs.new_textures.clone())?;
+    doc.insert("images".to_string(), serde_json::Value::Array(vec![]));
+    // Remove from the back, so the indices of the remaining views don't shift
+    let mut still_used = HashSet::new();
+    edit::for_each_reference_by_holder(doc, "bufferViews", &params.references, &mut |_, reference| {
+        still_used.extend(reference.as_u64().map(|idx| idx as usize));
+    })?;
+    let old_views: BTreeSet<usize> = old_images.iter().filter(|img| img.buffer_view.is_defined()).map(|img| img.buffer_view.raw_idx()).collect();
+    for &view_idx in old_views.iter().rev().filter(|view_idx| !still_used.contains(view_idx)) {
+        edit::remove_with::<GltfBufferView>(doc, "bufferViews", GltfIndex::of(view_idx), &params.references)?;
+    }
+
+    let mut bin = take_buffer_zero(doc, &mut binaries)?;
+    for (job, output) in jobs.new_images.iter().zip(outputs) {
+        let view = match output {
+            pipeline::EncodedImage::Data(data) => edit::append_buffer_view(doc, &mut bin, &data, 8, None)?,
+            pipeline::EncodedImage::Ktx2(ktx) => edit::append_ktx2_buffer_view(doc, &mut bin, &ktx)?,
+        };
+        let image = GltfImage {
+            mime_type: Some(job.output_mime_type().to_string()),
+            buffer_view: view,
+            extras: params.record_texture_hashes.then(|| serde_json::json!({ hash::HASH_EXTRAS_KEY: job.hash_extras() })),
+            ..GltfImage::default()
+        };
+        edit::append_image(doc, &image)?;
+    }
+    binaries.insert(None, bin);
+    if params.record_fingerprints {
+        fingerprint::record_fingerprints(doc, &jobs.new_images)?;
+    }
+
+    let textures: Vec<GltfTexture> = gltf::deserialize_list(doc, "textures")?;
+    for ext_name in edit::TEXTURE_SOURCE_EXTENSIONS {
+        let mut using = textures.iter().filter(|tex| texture_extension_source(tex, ext_name).is_some()).peekable();
+        if using.peek().is_some() {
+            // Textures without a core source can't fall back to ignoring the extension
+            let required = using.any(|tex| !tex.source.is_defined());
+            edit::add_extension_used(doc, ext_name, required)?;
+        }
+    }
+    prepare_output_buffers(Input { gltf_json: doc, binaries: &binaries }, params)
+}
+
+/// The indices of the images referenced through `references` from outside the textures, e.g. from custom extensions or `extras`.
+fn referenced_images(doc: &GltfDoc, references: &edit::ReferenceRegistry) -> Result<BTreeSet<usize>> {
+    let mut referenced = BTreeSet::new();
+    edit::for_each_reference_by_holder(doc, "images", references, &mut |holder, reference| {
+        if holder.is_none_or(|(list_name, _)| list_name != "textures") {
+            referenced.extend(reference.as_u64().map(|idx| idx as usize));
+        }
+    })?;
+    Ok(referenced)
+}
+
+/// Make buffer 0 the GLB binary chunk if it isn't already, returning its data, so images can be appended with [edit::append_buffer_view].
+/// Buffer 0's data is moved out of `binaries`, and only copied if it's a data URI.
+fn take_buffer_zero(doc: &mut GltfDoc, binaries: &mut HashMap<Option<String>, Vec<u8>>) -> Result<Vec<u8>> {
+    let buffers: Vec<GltfBuffer> = gltf::deserialize_list(doc, "buffers")?;
+    let Some(buffer) = buffers.first() else {
+        return Ok(vec![]);
+    };
+    let data = if buffer.uri.as_ref().is_some_and(|uri| uri.is_data_uri()) {
+        buffer.dump_data(0, binaries)?.to_vec()
+    } else {
+        let key = buffer.uri.as_ref().map(|uri| uri.as_str().to_string());
+        let mut data = binaries.remove(&key).ok_or(Error::BufferUriMissingData(key))?;
+        if data.len() < buffer.byte_length {
+            return Err(Error::BufferNotLongEnough { expected_bytes: buffer.byte_length, got_bytes: data.len() });
+        }
+        data.truncate(buffer.byte_length);
+        data
+    };
+    if buffer.uri.is_some() {
+        if let Some(buffer) = doc.get_mut("buffers").and_then(|buffers| buffers.get_mut(0)).and_then(serde_json::Value::as_object_mut) {
+            buffer.remove("uri");
+        }
+    }
+    binaries.remove(&None);
+    Ok(data)
+}
+
+/// `sources` holds the [SourceImage] for each source image index, and is shared between calls on the same document.
+fn plan_reencode_jobs(
+    input: &Input,
+    params: &Params,
+    target: Option<profile::TargetProfile>,
+    sources: &mut HashMap<GltfIndex<GltfImage>, Arc<SourceImage>>,
+) -> Result<ReencodeJobs> {
     let mut textures: Vec<GltfTexture> = input.get_list("textures")?;
     let images: Vec<GltfImage> = input.get_list("images")?;
     let buffer_views: Vec<GltfBufferView> = input.get_list("bufferViews")?;
@@ -242,169 +921,419 @@ fn get_reencode_jobs(input: Input, params: Params) -> Result<ReencodeJobs> {
         .enumerate()
         .map(|(idx, b)| b.dump_data(idx, input.binaries))
         .collect::<Result<_>>()?;
-    let srgb_texture_indices = get_srgb_texture_indices(&input);
+    let mut texture_semantics = params.slots.texture_semantics(input.gltf_json)?;
+    let mut srgb_texture_indices = get_srgb_texture_indices(&texture_semantics, &params.slots);
+    // The size limit for a texture, given its own max_texture_size if any
+    let limit_max_dimension = |max_texture_size: Option<u32>| match target {
+        Some(target) => Some(target.max_texture_size(max_texture_size)),
+        None => max_texture_size,
+    };
+    let group_members = params
+        .texture_groups
+        .iter()
+        .map(|group| group.members(input.gltf_json, &textures, &images))
+        .collect::<Result<Vec<_>>>()?;
+    let material_hints = if params.extras_hints { hints::material_hints(input.gltf_json)? } else { HashMap::new() };
+    let texture_usages = usage::texture_usages(input.gltf_json)?;
+    // Orphan images are planned as extra textures after the document's own, with the semantic their name suggests
+    let texture_count = textures.len();
+    let mut orphans = vec![];
+    if params.convert_orphan_images {
+        let used: HashSet<GltfIndex<GltfImage>> = textures
+            .iter()
+            .flat_map(|tex| std::iter::once(tex.source).chain(edit::TEXTURE_SOURCE_EXTENSIONS.iter().chain(video::VIDEO_TEXTURE_EXTENSIONS).filter_map(|ext_name| texture_extension_source(tex, ext_name))))
+            .collect();
+        for (img_idx, image) in images.iter().enumerate().filter(|&(img_idx, _)| !used.contains(&GltfIndex::of(img_idx))) {
+            let tex_idx = GltfIndex::of(textures.len());
+            if let Some(semantic) = params.slots.semantic_for_names(filenames::image_stem(image).as_deref()) {
+                texture_semantics.insert(tex_idx, BTreeSet::from([semantic]));
+                if params.slots.settings(semantic).color_space == ktx2::ColorSpace::Srgb {
+                    srgb_texture_indices.insert(tex_idx);
+                }
+            }
+            textures.push(GltfTexture::new(GltfIndex::of(img_idx)));
+            orphans.push(GltfIndex::of(img_idx));
+        }
+    }
+    // Embedded color profiles only describe color, so images used only as data are decoded as-is
+    let srgb_images: HashSet<GltfIndex<GltfImage>> = textures
+        .iter()
+        .enumerate()
+        .filter(|(tex_idx, _)| srgb_texture_indices.contains(&GltfIndex::of(*tex_idx)))
+        .flat_map(|(_, tex)| [tex.source, texture_extension_source(tex, "EXT_texture_webp").unwrap_or(GltfIndex::UNDEFINED), texture_extension_source(tex, "EXT_texture_avif").unwrap_or(GltfIndex::UNDEFINED)])
+        .collect();
+    let mut source_warnings = vec![];
+    let mut image_stats = HashMap::new();
+    let transcode_to_bc1_or_bc3 =
+        params.ktx_transcode_to_bc1_or_bc3 && target.is_none_or(|target| target.supports(profile::TranscodeFormat::Bc1Bc3));
     
     let mut new_images = vec![];
     let mut old_image_idx_to_new_image_idx = HashMap::new();
-    let lookup_old_img = |old_img_idx: GltfIndex<GltfImage>, srgb: bool, initial_data: Vec<u8>, initial_data_mime_type: String, reencode_as: ImageReencodeFormat| -> Result<GltfIndex<GltfImage>> {
-        if let Some(new_img_idx) = old_image_idx_to_new_image_idx.get(&old_img_idx) {
+    // If a texture doesn't have an existing image for a given format, key the new image on the image the data came from instead.
+    let mut lookup_old_img = |old_img_idx: GltfIndex<GltfImage>, src_img_idx: GltfIndex<GltfImage>, srgb: bool, source: &Arc<SourceImage>, texture_override: &overrides::TextureOverride, transforms: &[Arc<dyn pipeline::ImageTransform>], max_dimension: Option<u32>, usages: Option<&BTreeSet<usage::TextureUsage>>, reencode_as: ImageReencodeFormat| -> Result<GltfIndex<GltfImage>> {
+        let key_img_idx = if old_img_idx.is_defined() { old_img_idx } else { src_img_idx };
+        let copy = reencode_as == ImageReencodeFormat::Copy;
+        let key = (key_img_idx, match &reencode_as {
+            ImageReencodeFormat::Basic(format) => Some(*format),
+            ImageReencodeFormat::Ktx { .. } | ImageReencodeFormat::Copy => None,
+        }, copy, usages.filter(|_| !copy).cloned());
+        if let Some(new_img_idx) = old_image_idx_to_new_image_idx.get(&key) {
             Ok(*new_img_idx)
         } else {
             let new_img_idx = GltfIndex::of(new_images.len());
             new_images.push(ImageReencodeJob {
-                data: initial_data,
-                data_mime_type: initial_data_mime_type,
+                source: source.clone(),
                 data_used_as_srgb: srgb,
                 reencode_as,
-                preexisting_buffer_view_idx: images.gltf_index_required(old_img_idx, "images")?.buffer_view,
+                max_dimension,
+                repeats: false,
+                adjustments: texture_override.adjustments.filter(|adjustments| !copy && !adjustments.is_identity()),
+                transforms: if copy { vec![] } else { transforms.to_vec() },
+                preexisting_buffer_view_idx: images.gltf_index(old_img_idx, "images")?.map_or(GltfIndex::UNDEFINED, |img| img.buffer_view),
             });
-            old_image_idx_to_new_image_idx.insert(old_img_idx, new_img_idx);
+            old_image_idx_to_new_image_idx.insert(key, new_img_idx);
             Ok(new_img_idx)
         }
     };
 
-    for (tex_idx, tex) in textures.iter().enumerate() {
-        let data_used_as_srgb = srgb_texture_indices.contains(&GltfIndex::of(tex_idx));
-        let unoptimized_img = tex.source;
-        let optimized_img = 
-            texture_ktx_source(tex).unwrap_or(GltfIndex::UNDEFINED);
-
-        let mut img_src = None;
-        if let Some(img) = input.get_gltf_index(unoptimized_img, "images")? {
-            let data = img.dump_data(&buffer_views, &buffer_datas, input.binaries)?;
-            let mime_type = match img.mime_type {
-                Some(mime_type) => mime_type,
-                None => image::guess_format(&data)?.to_mime_type().to_string()
-            };
-            img_src = Some((data, mime_type))
-        } else if let Some(img) = input.get_gltf_index(optimized_img, "images")? {
-            let data = img.dump_data(&buffer_views, &buffer_datas, input.binaries)?;
-            if (&data).starts_with(&[
-                0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
-            ]) {
-                img_src = Some((data, "image/ktx2".to_string()))
-            } else {
-                return Err(Error::ImageClaimedKtx2ButWasNot)
+    // Textures which share an image share its SourceImage too, through `sources`
+    for (tex_idx, tex) in textures.iter_mut().enumerate() {
+        // Locate any error at the texture it came from
+        (|| -> Result<()> {
+            let data_used_as_srgb = srgb_texture_indices.contains(&GltfIndex::of(tex_idx));
+            let orphan = tex_idx >= texture_count;
+            let unoptimized_img = tex.source;
+            let optimized_img = 
+                texture_ktx_source(tex).unwrap_or(GltfIndex::UNDEFINED);
+
+            let webp_img = texture_extension_source(tex, "EXT_texture_webp").unwrap_or(GltfIndex::UNDEFINED);
+            let avif_img = texture_extension_source(tex, "EXT_texture_avif").unwrap_or(GltfIndex::UNDEFINED);
+
+            // Video textures are never decoded, see [video]
+            let mut video_reason = video::video_extension(tex).map(|ext_name| format!("texture uses {ext_name}"));
+            let used_images = std::iter::once(unoptimized_img)
+                .chain(edit::TEXTURE_SOURCE_EXTENSIONS.iter().chain(video::VIDEO_TEXTURE_EXTENSIONS).filter_map(|ext_name| texture_extension_source(tex, ext_name)));
+            for img_idx in used_images {
+                if video_reason.is_some() {
+                    break;
+                }
+                if let Some(mime_type) = images.gltf_index(img_idx, "images")?.and_then(|img| img.mime_type.as_deref()).filter(|&mime_type| video::is_video(Some(mime_type), &[])) {
+                    video_reason = Some(format!("image {} is a video ({mime_type})", img_idx.raw_idx()));
+                }
             }
-        }
 
-        if let Some((initial_data, initial_data_mime_type)) = img_src {
-            tex.source = lookup_old_img(
+            // Prefer the core source, then the alternate formats which can be decoded directly, and only then the KTX2 source.
+            // Decoding AVIF requires the `avif` feature.
+            let candidates = [
                 unoptimized_img,
-                data_used_as_srgb,
-                initial_data.to_vec(),
-                initial_data_mime_type,
-                ImageReencodeFormat::Basic(params.uncompressed_format),
-            )?;
-            set_texture_ktx_source(
-                &mut tex, 
-                lookup_old_img(
-                    optimized_img,
+                webp_img,
+                avif_img,
+                optimized_img,
+            ];
+            let mut img_src = None;
+            let mut src_img = unoptimized_img;
+            for candidate in candidates.into_iter().filter(|_| video_reason.is_none()) {
+                if let Some(source) = sources.get(&candidate) {
+                    src_img = candidate;
+                    img_src = Some(source.clone());
+                    break;
+                } else if let Some(img) = images.gltf_index(candidate, "images")? {
+                    src_img = candidate;
+                    let data = img.dump_data(&buffer_views, &buffer_datas, input.binaries)?;
+                    if video::is_video(img.mime_type.as_deref(), &data) {
+                        video_reason = Some(format!("image {} is a video", candidate.raw_idx()));
+                        break;
+                    }
+                    let mime_type = if candidate == optimized_img {
+                        if !data.starts_with(&ktx2::KTX2_IDENTIFIER) {
+                            return Err(Error::ImageClaimedKtx2ButWasNot)
+                        }
+                        "image/ktx2".to_string()
+                    } else {
+                        match &img.mime_type {
+                            Some(mime_type) => mime_type.clone(),
+                            None => image::guess_format(&data)?.to_mime_type().to_string()
+                        }
+                    };
+                    let mut source = SourceImage::new(data.to_vec(), mime_type);
+                    if let Some(orientation) = decode::exif_orientation(&data) {
+                        if params.apply_exif_orientation {
+                            source = source.with_orientation(orientation);
+                        } else {
+                            source_warnings.push(validate::Warning {
+                                code: "exif_orientation_ignored",
+                                json_pointer: format!("/images/{}", candidate.raw_idx()),
+                                message: "image has an EXIF orientation, but is encoded as stored as applying EXIF orientation is disabled".to_string(),
+                            });
+                        }
+                    }
+                    if srgb_images.contains(&candidate) {
+                        let profile = color::embedded_profile(&data);
+                        let json_pointer = format!("/images/{}", candidate.raw_idx());
+                        match profile.as_ref().map(color::ColorConversion::from_profile) {
+                            None | Some(Ok(None)) => {}
+                            Some(Ok(Some(conversion))) if params.convert_color_profiles => source = source.with_color_conversion(conversion),
+                            Some(Ok(Some(_))) => source_warnings.push(validate::Warning {
+                                code: "color_profile_ignored",
+                                json_pointer,
+                                message: "image has a non-sRGB color profile, but is treated as sRGB as color profile conversion is disabled".to_string(),
+                            }),
+                            Some(Err(problem)) => source_warnings.push(validate::Warning {
+                                code: "color_profile_unsupported",
+                                json_pointer,
+                                message: format!("{problem}, so the image is treated as sRGB"),
+                            }),
+                        }
+                    }
+                    img_src = Some(Arc::new(source));
+                    break;
+                }
+            }
+
+            if let Some(reason) = video_reason {
+                let mut copy = |img_idx: GltfIndex<GltfImage>| -> Result<GltfIndex<GltfImage>> {
+                    let img = images.gltf_index_required(img_idx, "images")?;
+                    let data = img.dump_data(&buffer_views, &buffer_datas, input.binaries)?;
+                    let mime_type = img.mime_type.clone().unwrap_or_else(|| "application/octet-stream".to_string());
+                    // Not added to `sources`, as other textures using the image mustn't decode it either
+                    let source = Arc::new(SourceImage::new(data.to_vec(), mime_type));
+                    lookup_old_img(img_idx, img_idx, data_used_as_srgb, &source, &Default::default(), &[], None, None, ImageReencodeFormat::Copy)
+                };
+                if tex.source.is_defined() {
+                    tex.source = copy(tex.source)?;
+                }
+                for (ext_name, ext) in tex.extensions.iter_mut().flatten() {
+                    if !edit::TEXTURE_SOURCE_EXTENSIONS.contains(&ext_name.as_str()) && !video::VIDEO_TEXTURE_EXTENSIONS.contains(&ext_name.as_str()) {
+                        continue;
+                    }
+                    if let Some(source) = ext.get_mut("source").filter(|source| source.is_u64()) {
+                        *source = copy(GltfIndex::of(source.as_u64().expect("checked above") as usize))?.raw_idx().into();
+                    }
+                }
+                source_warnings.push(validate::Warning {
+                    code: "video_texture_skipped",
+                    json_pointer: format!("/textures/{tex_idx}"),
+                    message: format!("{reason}, so the texture is left unchanged"),
+                });
+                return Ok(());
+            }
+            if let Some(source) = img_src {
+                sources.insert(src_img, source.clone());
+                let texture_override = images
+                    .gltf_index(src_img, "images")?
+                    .and_then(|img| params.texture_overrides.for_image(img))
+                    .copied()
+                    .unwrap_or_default();
+                if let Some(problem) = texture_override.adjustments.and_then(|adjustments| adjustments.check()) {
+                    return Err(Error::BadOverride(problem));
+                }
+                if params.image_stats {
+                    image_stats.insert(src_img, source.stats()?);
+                }
+                let mut skip = false;
+                if animation::is_animated(&source.data) {
+                    let warning = |message: &str| validate::Warning {
+                        code: "animated_image",
+                        json_pointer: format!("/textures/{tex_idx}"),
+                        message: message.to_string(),
+                    };
+                    match params.animated_images {
+                        animation::AnimationPolicy::FirstFrame => source_warnings.push(warning("source image is animated, so only its first frame is encoded")),
+                        animation::AnimationPolicy::Error => return Err(Error::AnimatedImage),
+                        animation::AnimationPolicy::Skip => {
+                            source_warnings.push(warning("source image is animated, so the texture is left using it unchanged"));
+                            skip = true;
+                        }
+                    }
+                }
+                let image = images.gltf_index(src_img, "images")?;
+                let no_semantics = BTreeSet::new();
+                let no_usages = BTreeSet::new();
+                let document_hints = if params.extras_hints {
+                    let texture_hints = hints::ConversionHints::of_item(input.gltf_json, "textures", tex_idx)?;
+                    let image_hints = hints::ConversionHints::of_item(input.gltf_json, "images", src_img.raw_idx())?;
+                    texture_hints.or(image_hints).or(material_hints.get(&GltfIndex::of(tex_idx)).copied().unwrap_or_default())
+                } else {
+                    hints::ConversionHints::default()
+                };
+                let context = decision::TextureContext {
+                    texture: GltfIndex::of(tex_idx),
+                    texture_name: tex.name.as_deref(),
+                    image: src_img,
+                    image_name: image.and_then(|img| img.name.as_deref()),
+                    image_uri: image.and_then(|img| img.uri.as_ref()).filter(|uri| !uri.is_data_uri()).map(|uri| uri.as_str()),
+                    mime_type: &source.mime_type,
+                    semantics: texture_semantics.get(&GltfIndex::of(tex_idx)).unwrap_or(&no_semantics),
+                    usages: texture_usages.get(&GltfIndex::of(tex_idx)).unwrap_or(&no_usages),
                     data_used_as_srgb,
-                    initial_data.to_vec(),
-                    initial_data_mime_type,
-                ImageReencodeFormat::Ktx {
-                        basis_compression_quality: params.ktx_basis_compression_quality,
-                        transcoded_to_bc1_or_bc3: params.ktx_transcode_to_bc1_or_bc3,
-                    },
-                )?,
-            )?;
-        } else {
-            return Err(Error::ImageHasNoSources)
-        }
+                    hints: params.override_hints.or(document_hints),
+                    source: &source,
+                };
+                let decision = match &params.decision_hook {
+                    Some(hook) if !skip => hook(&context),
+                    _ => decision::TextureDecision::default(),
+                };
+                let transforms: Vec<_> = params.image_transforms.iter().filter(|transform| transform.applies_to(&context)).cloned().collect();
+                let hints = context.hints;
+                let max_dimension = limit_max_dimension(hints.max_size.or(params.max_texture_size));
+                // Atlas mipmapping and transforms change the image for every texture sharing the job, so only textures sampled
+                // the same ways share one, see [usage]
+                let usage_split = (texture_override.atlas.is_some() || !transforms.is_empty()).then(|| texture_usages.get(&GltfIndex::of(tex_idx)).unwrap_or(&no_usages));
+                if skip || decision.skip {
+                    let copy = lookup_old_img(src_img, src_img, data_used_as_srgb, &source, &texture_override, &[], None, None, ImageReencodeFormat::Copy)?;
+                    // Every other source would point at an image which is no longer output
+                    if let Some(extensions) = tex.extensions.as_mut() {
+                        extensions.retain(|name, _| !edit::TEXTURE_SOURCE_EXTENSIONS.contains(&name.as_str()));
+                    }
+                    match [(webp_img, "EXT_texture_webp"), (avif_img, "EXT_texture_avif")].into_iter().find(|&(img, _)| img == src_img) {
+                        Some((_, ext_name)) => {
+                            tex.source = GltfIndex::UNDEFINED;
+                            set_texture_extension_source(tex, ext_name, copy);
+                        }
+                        None => tex.source = copy,
+                    }
+                    return Ok(());
+                }
+                if usage_split.is_some_and(|usages| usages.len() > 1) {
+                    source_warnings.push(validate::Warning {
+                        code: "texture_usage_conflict",
+                        json_pointer: format!("/textures/{tex_idx}"),
+                        message: "texture is sampled with several texCoords or wrap modes, which its atlas or transforms may not suit".to_string(),
+                    });
+                }
+                let codec = decision.codec.or(hints.codec).unwrap_or(params.ktx_codec);
+                // Orphan images are only converted to KTX2, as nothing falls back to them
+                tex.source = if orphan {
+                    GltfIndex::UNDEFINED
+                } else {
+                    lookup_old_img(
+                        unoptimized_img,
+                        src_img,
+                        data_used_as_srgb,
+                        &source,
+                        &texture_override,
+                        &transforms,
+                        max_dimension,
+                        usage_split,
+                        ImageReencodeFormat::Basic(params.uncompressed_format),
+                    )?
+                };
+                set_texture_ktx_source(
+                    tex, 
+                    lookup_old_img(
+                        optimized_img,
+                        src_img,
+                        data_used_as_srgb,
+                        &source,
+                        &texture_override,
+                        &transforms,
+                        max_dimension,
+                        usage_split,
+                    ImageReencodeFormat::Ktx {
+                            codec,
+                            basis_compression_quality: decision.quality.or(hints.quality).or(params.ktx_basis_compression_quality),
+                            transcoded_to_bc1_or_bc3: transcode_to_bc1_or_bc3,
+                            mipmaps: params.generate_mipmaps,
+                            level_count: None,
+                            atlas: texture_override.atlas,
+                            dither: texture_override.dither.filter(|_| codec == KtxCodec::Etc1s),
+                            effort: params.encode_effort,
+                        },
+                    )?,
+                );
+                if params.avif_fallback && !orphan {
+                    set_texture_extension_source(
+                        tex,
+                        "EXT_texture_avif",
+                        lookup_old_img(avif_img, src_img, data_used_as_srgb, &source, &texture_override, &transforms, max_dimension, usage_split, ImageReencodeFormat::Basic(image::ImageFormat::Avif))?,
+                    );
+                }
+            } else {
+                return Err(Error::ImageHasNoSources)
+            }
+            Ok(())
+        })().map_err(|e| match orphans.get(tex_idx.wrapping_sub(texture_count)) {
+            Some(img_idx) => e.at(format!("/images/{}", img_idx.raw_idx())),
+            None => e.at(format!("/textures/{tex_idx}")),
+        })?;
+    }
+    // Images referenced from elsewhere are kept as they are, unless they were converted as orphans
+    let mut referenced = vec![];
+    for img_idx in referenced_images(input.gltf_json, &params.references)?.into_iter().map(GltfIndex::of).filter(|img_idx| !orphans.contains(img_idx)) {
+        let img = images.gltf_index_required(img_idx, "images")?;
+        let data = img.dump_data(&buffer_views, &buffer_datas, input.binaries)?;
+        let mime_type = img
+            .mime_type
+            .clone()
+            .or_else(|| image::guess_format(&data).ok().map(|format| format.to_mime_type().to_string()))
+            .unwrap_or_else(|| "application/octet-stream".to_string());
+        let source = Arc::new(SourceImage::new(data.to_vec(), mime_type));
+        referenced.push((img_idx, lookup_old_img(img_idx, img_idx, false, &source, &Default::default(), &[], None, None, ImageReencodeFormat::Copy)?));
     }
+    let orphan_images = orphans
+        .into_iter()
+        .zip(textures.split_off(texture_count))
+        .map(|(img_idx, tex)| (img_idx, texture_ktx_source(&tex).unwrap_or(tex.source)))
+        .collect();
 
+    for (group, members) in params.texture_groups.iter().zip(&group_members) {
+        if members.is_empty() {
+            source_warnings.push(validate::Warning {
+                code: "texture_group_empty",
+                json_pointer: String::new(),
+                message: format!("texture group '{}' matches no textures", group.name),
+            });
+        }
+        groups::unify_jobs(&mut new_images, &groups::member_jobs(&textures, members));
+    }
+    let samplers = input.get_list("samplers")?;
+    if params.preserve_tiling {
+        source_warnings.extend(tiling::mark_repeating_jobs(&textures, &samplers, &mut new_images)?);
+    }
+    if params.resolution_tiers > 1 {
+        tiers::add_resolution_tiers(&mut textures, &mut new_images, params.resolution_tiers);
+    }
+    let mut warnings = source_warnings;
+    warnings.extend(limits::check_limits(&new_images, &params.limits)?);
+    if let Some(target) = target {
+        if params.generate_mipmaps {
+            warnings.extend(levels::plan_level_counts(&textures, &samplers, &mut new_images, target)?);
+        }
+        warnings.extend(profile::check_jobs(&new_images, target));
+    }
+    let threads = params.max_threads.or_else(|| std::thread::available_parallelism().ok()).map_or(1, NonZeroUsize::get);
+    let memory = match params.max_memory {
+        Some(max_memory) => memory::check_estimate(&new_images, threads, max_memory)?,
+        None => memory::estimate(&new_images, threads),
+    };
     Ok(ReencodeJobs {
         new_textures: textures, // modified in place
         new_images,
+        warnings,
+        memory,
+        image_stats,
+        orphan_images,
+        referenced_images: referenced,
     })
 }
 
-/*
-fn parse_and_reencode(input: Input) -> Result<Output> {
-    // let glb = gltf::Gltf::from_slice(input)?;
-    // let (gltf, binary) = match glb.blob {
-    //     Some(blob) => (&mut glb.document, &mut blob),
-    //     None => return Error::Gltf(gltf::Error::MissingBlob),
-    // };
-
-    // let mut image_views = vec![];
-    // let mut unembedded_image_views = vec![];
-    // for image in gltf.images() {
-    //     let ktx_source_idx = ktx_source(&image);
-    //     match image.source() {
-    //         gltf::image::Source::View { view, mime_type } => {
-    //             image_views.push((image.index(), view, mime_type, ktx_source_idx))
-    //         }
-    //         gltf::image::Source::Uri { .. } => unembedded_image_views.push((image.index(), image.name())),
-    //     }
-    // }
-    let srgb_image_idxs = get_srgb_images(&input);
-    let mut images: Vec<GltfImage> = input.get_list("images")?;
-    for image in images {
-
-    }
-    
-
-    for (image_idx, uncompressed_view, uncompressed_type, ktx_source_idx) in image_views {
-        let buf = view_data(binary, &uncompressed_view);
-        let image_data = match ImageFormat::from_mime_type(uncompressed_type) {
-            Some(format) => image::load_from_memory_with_format(buf, format)?,
-            None => image::load_from_memory(buf)?
-        };
-        let (vk_format, rgb_bytes) = if image_data.color().has_alpha() {
-            (
-                43, // VK_FORMAT_R8G8B8A8_SRGB 
-                image_data.into_rgba8(),
-            )
-        } else {
-            (
-                29, // VK_FORMAT_R8G8B8_SRGB 
-                image_data.into_rgb8(),
-            )
-        };
-
-        let info = Ktx2CreateInfo {
-            vk_format,
-            dfd: None,
-            common: CommonCreateInfo {
-                create_storage: libktx_rs::CreateStorage::AllocStorage,
-                base_width: image_data.width(),
-                base_height: image_data.height(),
-                base_depth: 1,
-                num_dimensions: 2,
-                num_levels: 1,
-                num_faces: 1,
-                num_layers: 1,
-                is_array: false,
-                generate_mipmaps: false, // TODO make param
-            }
+/// Let each source be decoded at the largest size any job using it is downscaled to, so huge PNGs aren't decoded at
+/// full resolution, see [decode::decode_png_within]. Atlases are always decoded whole, as box filtering across their
+/// tiles would bleed one into the next. `jobs` must include every job sharing each source, across all plans.
+fn limit_source_decodes<'a>(jobs: impl IntoIterator<Item = &'a ImageReencodeJob>) {
+    let mut limits: HashMap<*const SourceImage, (&Arc<SourceImage>, Option<u32>)> = HashMap::new();
+    for job in jobs {
+        let max_dimension = match job.reencode_as {
+            ImageReencodeFormat::Copy => continue,
+            ImageReencodeFormat::Ktx { atlas: Some(_), .. } => None,
+            _ => job.max_dimension,
         };
-
-        let mut ktx = info.create_texture()?;
-        unsafe {
-            (*(*ktx.handle()).vtbl).SetImageFromMemory(ktx.handle(), 0, 0, 0, rgb_bytes.as_ptr(), rgb_bytes.len());
-        }
-        ktx.ktx2().unwrap().compress_basis(params.ktx_basis_compression_quality.into()); // TODO make param
-        match params.ktx_transcode_to {
-            Some(format) => {
-                // TODO check if this is smaller or larger
-                ktx.ktx2().unwrap().transcode_basis(format, libktx_rs::TranscodeFlags::HIGH_QUALITY);
-            }
-            None => {}
-        }
-        let ktx_bytes = ktx.write_to(sink)?;
-
-        let ktx_source_idx = match ktx_source_idx {
-            Some(ktx_source_idx) => ktx_source_idx,
-            None => {
-                // TDOO add new gltf source
-                // TODO point image at new source
-                // KHR_texture_basisu
-                gltf.views()
-            }
-        };
-        // TODO add data to binary blob
+        limits
+            .entry(Arc::as_ptr(&job.source))
+            .and_modify(|(_, limit)| *limit = limit.zip(max_dimension).map(|(a, b)| a.max(b)))
+            .or_insert((&job.source, max_dimension));
+    }
+    for (source, limit) in limits.into_values() {
+        source.set_decode_max_dimension(limit);
     }
 }
-    */
\ No newline at end of file
@@ -0,0 +1,65 @@
+//! Guardrails on the size of converted documents, for platforms with hard limits such as WebGL's maximum texture size.
+//! Limits are checked while planning, before anything is encoded, see [check_limits].
+
+use crate::{validate::Warning, Error, ImageReencodeJob, Result};
+
+/// What to do when a converted document exceeds one of its [Limits].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LimitAction {
+    /// Fail with [Error::LimitExceeded].
+    #[default]
+    Fail,
+    /// Carry on, reporting a `limit_exceeded` warning for each limit.
+    Warn,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Limits {
+    /// The most images the output document may have.
+    pub max_images: Option<usize>,
+    /// The most bytes all the output images may take up once decoded to RGBA8, not counting mip levels.
+    /// This approximates GPU memory use, as the encoded size isn't known until after encoding.
+    pub max_total_texture_bytes: Option<u64>,
+    /// The largest width or height of any output image, e.g. 4096 for broad WebGL support.
+    pub max_texture_dimension: Option<u32>,
+    pub on_exceeded: LimitAction,
+}
+
+/// Check the images `jobs` will produce against `limits`.
+/// Images whose dimensions can't be read from their header aren't counted towards the size limits.
+///
+/// Returns a warning for each exceeded limit if [Limits::on_exceeded] is [LimitAction::Warn],
+/// otherwise fails on the first exceeded limit.
+pub fn check_limits(jobs: &[ImageReencodeJob], limits: &Limits) -> Result<Vec<Warning>> {
+    let mut exceeded = vec![];
+
+    if let Some(max_images) = limits.max_images.filter(|&max| jobs.len() > max) {
+        exceeded.push(format!("output has {} images, more than the limit of {max_images}", jobs.len()));
+    }
+
+    let dimensions: Vec<Option<(u32, u32)>> = jobs.iter().map(ImageReencodeJob::source_dimensions).collect();
+    if let Some(max_bytes) = limits.max_total_texture_bytes {
+        let total_bytes: u64 = dimensions.iter().flatten().map(|&(width, height)| width as u64 * height as u64 * 4).sum();
+        if total_bytes > max_bytes {
+            exceeded.push(format!("output images take up {total_bytes} bytes, more than the limit of {max_bytes}"));
+        }
+    }
+    if let Some(max_dimension) = limits.max_texture_dimension {
+        for (idx, &(width, height)) in dimensions.iter().enumerate().filter_map(|(idx, dims)| Some((idx, dims.as_ref()?))) {
+            if width.max(height) > max_dimension {
+                exceeded.push(format!("output image {idx} is {width}x{height}, larger than the limit of {max_dimension}"));
+            }
+        }
+    }
+
+    match limits.on_exceeded {
+        LimitAction::Fail => match exceeded.into_iter().next() {
+            Some(message) => Err(Error::LimitExceeded(message)),
+            None => Ok(vec![]),
+        },
+        LimitAction::Warn => Ok(exceeded
+            .into_iter()
+            .map(|message| Warning { code: "limit_exceeded", json_pointer: String::new(), message })
+            .collect()),
+    }
+}
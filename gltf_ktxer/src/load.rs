@@ -0,0 +1,159 @@
+use std::{collections::HashMap, path::{Path, PathBuf}};
+
+use crate::{glb, gltf::GltfDoc, Error, Input, Result};
+
+/// A glTF document along with all the external files it refers to, keyed by URI as [Input] expects.
+pub struct LoadedGltf {
+    pub doc: GltfDoc,
+    pub binaries: HashMap<Option<String>, Vec<u8>>,
+}
+impl LoadedGltf {
+    pub fn input(&mut self) -> Input<'_> {
+        Input { gltf_json: &mut self.doc, binaries: &self.binaries }
+    }
+}
+
+/// How [load_gltf_with] resolves the files a document refers to.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LoadOptions {
+    /// Read URIs which lead outside the directory containing the document, e.g. `../../etc/passwd`, absolute paths,
+    /// or symlinks inside the directory pointing out of it.
+    /// These are rejected by default, so loading an untrusted document can't read arbitrary files.
+    pub allow_outside_root: bool,
+}
+
+/// Load a `.gltf` file and every non-`data:` buffer and image URI it references,
+/// resolving relative paths against the directory containing the file.
+/// A `.glb` file is loaded too, with its binary chunk keyed by `None`.
+///
+/// URIs leading outside that directory fail with [Error::UriOutsideRoot], see [load_gltf_with] to allow them.
+pub fn load_gltf(path: &Path) -> Result<LoadedGltf> {
+    load_gltf_with(path, &LoadOptions::default())
+}
+
+/// [load_gltf] with the given `options`.
+pub fn load_gltf_with(path: &Path, options: &LoadOptions) -> Result<LoadedGltf> {
+    let bytes = std::fs::read(path)?;
+    let (doc, bin): (GltfDoc, _) = match bytes.starts_with(&glb::GLB_MAGIC.to_le_bytes()) {
+        true => glb::read(&bytes).map(|(doc, bin)| (doc, Some(bin)))?,
+        false => (serde_json::from_slice(&bytes)?, None),
+    };
+    if !options.allow_outside_root {
+        for (list_name, idx, uri) in file_uris(&doc) {
+            if escapes_root(&percent_decode(uri)) {
+                return Err(Error::UriOutsideRoot(uri.to_string()).at(format!("/{list_name}/{idx}/uri")));
+            }
+        }
+    }
+    let root = match options.allow_outside_root {
+        true => None,
+        false => Some(canonical_root(path)?),
+    };
+    let mut binaries = HashMap::new();
+    if let Some(bin) = bin {
+        binaries.insert(None, bin);
+    }
+    for (uri, mut file) in referenced_files(path, &doc) {
+        if let Some(root) = &root {
+            // The URIs passed the check above, but a symlink can still lead out of the directory
+            file = match resolve_inside_root(root, &uri, &file)? {
+                Some(file) => file,
+                None => {
+                    let (list_name, idx, _) = file_uris(&doc).find(|(_, _, other)| *other == uri).expect("referenced files come from file_uris");
+                    return Err(Error::UriOutsideRoot(uri).at(format!("/{list_name}/{idx}/uri")));
+                }
+            };
+        }
+        binaries.insert(Some(uri), std::fs::read(file)?);
+    }
+    Ok(LoadedGltf { doc, binaries })
+}
+
+/// Each non-`data:` buffer and image URI in `doc`, along with the list and index of the object holding it.
+fn file_uris(doc: &GltfDoc) -> impl Iterator<Item = (&'static str, usize, &str)> {
+    ["buffers", "images"].into_iter().flat_map(move |list_name| {
+        let list = doc.get(list_name).and_then(|val| val.as_array()).map(Vec::as_slice).unwrap_or_default();
+        list.iter()
+            .enumerate()
+            .filter_map(move |(idx, item)| Some((list_name, idx, item.get("uri")?.as_str()?)))
+            .filter(|(_, _, uri)| !uri.starts_with("data:"))
+    })
+}
+
+/// Whether the (percent-decoded) relative path `path` leads outside the directory it's relative to,
+/// by being absolute or by going up more directories than it goes down.
+/// Both `/` and `\` count as separators whatever the OS, so documents are treated alike everywhere.
+pub fn escapes_root(path: &str) -> bool {
+    // Drive letters and UNC paths, which Path only recognises on Windows
+    let bytes = path.as_bytes();
+    if path.starts_with(['/', '\\']) || (bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':') {
+        return true;
+    }
+    let mut depth = 0usize;
+    for component in path.split(['/', '\\']) {
+        match component {
+            "" | "." => {}
+            ".." => match depth.checked_sub(1) {
+                Some(up) => depth = up,
+                None => return true,
+            },
+            _ => depth += 1,
+        }
+    }
+    false
+}
+
+/// The directory containing the document at `path`, with symlinks resolved, for [resolve_inside_root].
+pub fn canonical_root(path: &Path) -> Result<PathBuf> {
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    Ok(std::fs::canonicalize(dir)?)
+}
+
+/// `file`, which `uri` in a document in `root` (see [canonical_root]) resolves to, with symlinks resolved,
+/// or None if it leads outside `root`, by its text (see [escapes_root]) or by a symlink pointing out of `root`.
+/// Fails if the file doesn't exist.
+pub fn resolve_inside_root(root: &Path, uri: &str, file: &Path) -> Result<Option<PathBuf>> {
+    if escapes_root(&percent_decode(uri)) {
+        return Ok(None);
+    }
+    let file = std::fs::canonicalize(file)?;
+    Ok(file.starts_with(root).then_some(file))
+}
+
+/// Each distinct non-`data:` buffer and image URI in `doc`, the document at `path`, along with the file it resolves to.
+pub fn referenced_files(path: &Path, doc: &GltfDoc) -> Vec<(String, PathBuf)> {
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut files: Vec<(String, PathBuf)> = vec![];
+    for (_, _, uri) in file_uris(doc) {
+        if files.iter().any(|(seen, _)| seen == uri) {
+            continue;
+        }
+        files.push((uri.to_string(), dir.join(percent_decode(uri))));
+    }
+    files
+}
+
+/// glTF2.0 section 2.8: "Reference to an external file (either relative or absolute path).
+/// [...] Relative paths (which may contain percent-encoded characters) are relative to the location of the glTF file."
+pub(crate) fn percent_decode(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| bytes.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(byte) => {
+                out.push(byte);
+                i += 3;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
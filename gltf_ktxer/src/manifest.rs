@@ -0,0 +1,86 @@
+//! Manifests listing the input images of a multi-image KTX2 texture, like toktx's multi-file input.
+//!
+//! A manifest is a JSON file such as
+//! ```json
+//! {
+//!     "cubemap": true,
+//!     "images": ["px.png", "nx.png", "py.png", "ny.png", "pz.png", "nz.png"]
+//! }
+//! ```
+//! `images` has one entry per layer and face, ordered by layer then face (+X, -X, +Y, -Y, +Z, -Z for cubemaps).
+//! Each entry is either a single image, or a list of explicit mip levels largest first.
+//! Relative paths are resolved against the directory containing the manifest.
+
+use std::path::{Path, PathBuf};
+
+use image::RgbaImage;
+use serde_derive::Deserialize;
+
+use crate::{ktx2::{generate_mipmaps, ColorSpace, Ktx2Texture}, Error, Result};
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct ImageManifest {
+    #[serde(default)]
+    pub cubemap: bool,
+    /// The number of array layers, or 0 if the texture isn't an array.
+    #[serde(default)]
+    pub layers: u32,
+    pub images: Vec<ManifestEntry>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum ManifestEntry {
+    Image(PathBuf),
+    Levels(Vec<PathBuf>),
+}
+impl ManifestEntry {
+    pub fn levels(&self) -> &[PathBuf] {
+        match self {
+            ManifestEntry::Image(path) => std::slice::from_ref(path),
+            ManifestEntry::Levels(paths) => paths,
+        }
+    }
+}
+
+impl ImageManifest {
+    pub fn load(path: &Path) -> Result<Self> {
+        let manifest: Self = serde_json::from_slice(&std::fs::read(path)?)?;
+        let faces = if manifest.cubemap { 6 } else { 1 };
+        if manifest.images.len() != (manifest.layers.max(1) * faces) as usize {
+            return Err(Error::BadManifest(format!(
+                "expected {} images for {} layers and {faces} faces, got {}",
+                manifest.layers.max(1) * faces,
+                manifest.layers,
+                manifest.images.len(),
+            )));
+        }
+        if manifest.images.iter().any(|entry| entry.levels().is_empty()) {
+            return Err(Error::BadManifest("every image needs at least one mip level".to_string()));
+        }
+        Ok(manifest)
+    }
+
+    /// Load every image relative to `dir` and assemble them into one texture.
+    /// If `mipmaps` is set, images listed without explicit mip levels get a generated mip chain.
+    pub fn encode(&self, dir: &Path, mipmaps: bool, color_space: ColorSpace) -> Result<Ktx2Texture> {
+        let images: Vec<Vec<RgbaImage>> = self
+            .images
+            .iter()
+            .map(|entry| {
+                let levels = entry
+                    .levels()
+                    .iter()
+                    .map(|path| Ok(image::open(dir.join(path))?.into_rgba8()))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(match levels.as_slice() {
+                    [image] if mipmaps => generate_mipmaps(image),
+                    _ => levels,
+                })
+            })
+            .collect::<Result<_>>()?;
+        let images: Vec<&[RgbaImage]> = images.iter().map(Vec::as_slice).collect();
+        Ktx2Texture::from_rgba8_images(&images, self.layers, if self.cubemap { 6 } else { 1 }, color_space)
+    }
+}
@@ -0,0 +1,164 @@
+//! Accounting for the memory a conversion needs, so it can fail up front instead of being OOM-killed partway through a batch.
+//!
+//! Only the big allocations are counted: decoded source images, the resized copy and mip chain each job works on,
+//! and the encoded output held until it's written. Everything else is small next to those.
+//! [estimate] works from image headers while planning, and [MemoryTracker] counts allocations as they're made.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{ImageReencodeFormat, ImageReencodeJob, Result};
+
+/// Estimated memory use of a set of jobs, in bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MemoryEstimate {
+    /// Decoded source images, which stay cached for as long as the jobs do.
+    pub decoded_bytes: u64,
+    /// Resized images and mip chains being worked on, for the most expensive jobs which can run at once.
+    pub working_bytes: u64,
+    /// Encoded output images, all held until the output buffer is written.
+    pub output_bytes: u64,
+}
+impl MemoryEstimate {
+    pub fn peak(&self) -> u64 {
+        self.decoded_bytes + self.working_bytes + self.output_bytes
+    }
+}
+
+/// A size in bytes as given on the command line: a number with an optional K, M or G suffix, in powers of 1024.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteSize(pub u64);
+impl std::str::FromStr for ByteSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let digits = s.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+        let scale: u64 = match s[digits.len()..].to_ascii_uppercase().as_str() {
+            "" | "B" => 1,
+            "K" | "KB" | "KIB" => 1 << 10,
+            "M" | "MB" | "MIB" => 1 << 20,
+            "G" | "GB" | "GIB" => 1 << 30,
+            _ => return Err(format!("bad size '{s}', expected a number of bytes with an optional K, M or G suffix")),
+        };
+        digits
+            .parse::<u64>()
+            .ok()
+            .and_then(|count| count.checked_mul(scale))
+            .map(ByteSize)
+            .ok_or_else(|| format!("bad size '{s}', expected a number of bytes with an optional K, M or G suffix"))
+    }
+}
+impl std::fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            bytes if bytes >= 1 << 30 && bytes % (1 << 30) == 0 => write!(f, "{}G", bytes >> 30),
+            bytes if bytes >= 1 << 20 && bytes % (1 << 20) == 0 => write!(f, "{}M", bytes >> 20),
+            bytes if bytes >= 1 << 10 && bytes % (1 << 10) == 0 => write!(f, "{}K", bytes >> 10),
+            bytes => write!(f, "{bytes}"),
+        }
+    }
+}
+
+/// The bytes of an RGBA8 image, and of its full mip chain if `mipmaps` is set, which adds at most a third.
+pub fn rgba8_bytes((width, height): (u32, u32), mipmaps: bool) -> u64 {
+    let bytes = width as u64 * height as u64 * 4;
+    if mipmaps { bytes + bytes.div_ceil(3) } else { bytes }
+}
+
+impl ImageReencodeJob {
+    /// The bytes this job works on while running, apart from its decoded source: the resized image and any mip chain.
+    /// Returns None if the source dimensions can't be read from its header.
+    fn working_bytes(&self) -> Option<u64> {
+        let dimensions = self.source_dimensions()?;
+        let dimensions = self.fit_dimensions(dimensions);
+        Some(match self.reencode_as {
+            ImageReencodeFormat::Ktx { mipmaps, .. } => rgba8_bytes(dimensions, mipmaps),
+            ImageReencodeFormat::Basic(_) => rgba8_bytes(dimensions, false),
+            ImageReencodeFormat::Copy => 0,
+        })
+    }
+}
+
+/// Estimate the memory needed to run `jobs` on up to `threads` threads at once, from their sources' headers.
+/// Encoded outputs are assumed to be as large as their uncompressed texels, which is an upper bound for everything but copies.
+/// Jobs whose source dimensions can't be read are counted by their encoded source size.
+pub fn estimate(jobs: &[ImageReencodeJob], threads: usize) -> MemoryEstimate {
+    let mut sources: Vec<*const crate::SourceImage> = vec![];
+    let mut estimate = MemoryEstimate::default();
+    let mut working = vec![];
+    for job in jobs {
+        let source = std::sync::Arc::as_ptr(&job.source);
+        let working_bytes = job.working_bytes();
+        if job.reencode_as != ImageReencodeFormat::Copy && !sources.contains(&source) {
+            sources.push(source);
+            estimate.decoded_bytes += job.source_dimensions().map_or(job.source.data.len() as u64, |dimensions| rgba8_bytes(dimensions, false));
+        }
+        estimate.output_bytes += match job.reencode_as {
+            ImageReencodeFormat::Copy => job.source.data.len() as u64,
+            _ => working_bytes.unwrap_or(job.source.data.len() as u64),
+        };
+        working.push(working_bytes.unwrap_or(0));
+    }
+    working.sort_unstable_by(|a, b| b.cmp(a));
+    estimate.working_bytes = working.iter().take(threads.max(1)).sum();
+    estimate
+}
+
+/// Fail if running `jobs` on `threads` threads is estimated to need more than `max_bytes`.
+/// The error suggests running on one thread if that would fit.
+pub fn check_estimate(jobs: &[ImageReencodeJob], threads: usize, max_bytes: u64) -> Result<MemoryEstimate> {
+    let estimated = estimate(jobs, threads);
+    if estimated.peak() <= max_bytes {
+        return Ok(estimated);
+    }
+    let single_thread = estimate(jobs, 1).peak();
+    let hint = if threads > 1 && single_thread <= max_bytes {
+        format!("; running one job at a time would need about {single_thread} bytes, which fits")
+    } else {
+        "; reduce the number of images or their size, e.g. with a maximum texture size".to_string()
+    };
+    Err(crate::Error::MemoryLimitExceeded { needed: estimated.peak(), limit: max_bytes, hint })
+}
+
+/// Counts bytes as they're allocated and freed, failing allocations which would go over a limit.
+#[derive(Debug, Default)]
+pub struct MemoryTracker {
+    limit: Option<u64>,
+    current: AtomicU64,
+    peak: AtomicU64,
+}
+impl MemoryTracker {
+    pub fn new(limit: Option<u64>) -> Self {
+        Self { limit, ..Self::default() }
+    }
+
+    /// Count `bytes` as allocated until the returned guard is dropped.
+    /// Call this before making the allocation, so going over the limit fails before the memory is used.
+    pub fn alloc(&self, bytes: u64) -> Result<TrackedAllocation<'_>> {
+        let previous = self.current.fetch_add(bytes, Ordering::Relaxed);
+        let current = previous + bytes;
+        if let Some(limit) = self.limit.filter(|&limit| current > limit) {
+            self.current.fetch_sub(bytes, Ordering::Relaxed);
+            let hint = "; run fewer jobs at once, or downscale the images".to_string();
+            return Err(crate::Error::MemoryLimitExceeded { needed: current, limit, hint });
+        }
+        self.peak.fetch_max(current, Ordering::Relaxed);
+        Ok(TrackedAllocation { tracker: self, bytes })
+    }
+
+    /// The most bytes allocated at once so far.
+    pub fn peak(&self) -> u64 {
+        self.peak.load(Ordering::Relaxed)
+    }
+}
+
+/// Bytes counted by a [MemoryTracker], released when dropped.
+#[derive(Debug)]
+pub struct TrackedAllocation<'a> {
+    tracker: &'a MemoryTracker,
+    bytes: u64,
+}
+impl Drop for TrackedAllocation<'_> {
+    fn drop(&mut self) {
+        self.tracker.current.fetch_sub(self.bytes, Ordering::Relaxed);
+    }
+}
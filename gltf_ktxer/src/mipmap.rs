@@ -0,0 +1,164 @@
+//! Fast downsampling for mip chain generation.
+//!
+//! Each mip level is half the size of the previous one (rounded down), so every output texel covers a fixed footprint
+//! of between 2 and 3 source texels in each direction. The filter is a box over exactly that footprint:
+//! source texels are weighted by how much of them the footprint covers, so odd sizes are filtered correctly
+//! instead of dropping the last row or column.
+//!
+//! The filter is separable and computed in fixed point, with all four channels accumulated together so the compiler can vectorize it.
+//! Large levels are split into bands of rows processed on separate threads.
+//!
+//! Texture atlases pack unrelated images into a grid of tiles, and filtering across a tile boundary bleeds one tile's
+//! colors into its neighbour's. Given the atlas' [TileGrid], each output texel only averages source texels from the tile its center falls in.
+
+use std::num::NonZeroUsize;
+
+use image::RgbaImage;
+
+/// Levels with fewer texels than this are downsampled on the calling thread, as spawning threads would cost more.
+const PARALLEL_MIN_TEXELS: usize = 256 * 256;
+
+/// Fixed-point weights sum to this in each direction.
+const WEIGHT_ONE: u32 = 256;
+
+/// The source texels contributing to one output texel along one axis, with their fixed-point weights.
+#[derive(Debug, Clone, Copy)]
+struct Taps {
+    first: usize,
+    weights: [u32; 3],
+}
+
+/// An atlas of `columns` by `rows` equally sized tiles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TileGrid {
+    pub columns: u32,
+    pub rows: u32,
+}
+impl TileGrid {
+    /// A single tile covering the whole image, which filters the same as no grid at all.
+    pub const WHOLE: Self = Self { columns: 1, rows: 1 };
+}
+impl std::str::FromStr for TileGrid {
+    type Err = String;
+
+    /// Parse `<columns>x<rows>`, e.g. `4x2`.
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let parse = |n: &str| n.parse().ok().filter(|&n: &u32| n > 0);
+        match s.split_once('x').map(|(columns, rows)| (parse(columns), parse(rows))) {
+            Some((Some(columns), Some(rows))) => Ok(Self { columns, rows }),
+            _ => Err(format!("invalid tile grid '{s}', expected '<columns>x<rows>' e.g. '4x4'")),
+        }
+    }
+}
+impl std::fmt::Display for TileGrid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{}", self.columns, self.rows)
+    }
+}
+
+/// Compute the taps for each of `dst_len` outputs covering `src_len` inputs split into `tiles` equal tiles.
+/// Tiles start at whole texels, so if `src_len` isn't a multiple of `tiles` the earlier tiles are a texel larger.
+fn taps(src_len: usize, dst_len: usize, tiles: usize) -> Vec<Taps> {
+    let footprint = src_len as f64 / dst_len as f64;
+    let tiles = tiles.clamp(1, src_len);
+    (0..dst_len)
+        .map(|i| {
+            let (start, end) = (i as f64 * footprint, (i + 1) as f64 * footprint);
+            let first = start.floor() as usize;
+            // Only sample the tile the output texel's center is in
+            let tile = ((((i as f64 + 0.5) * footprint) as usize) * tiles / src_len).min(tiles - 1);
+            let (tile_start, tile_end) = ((tile * src_len).div_ceil(tiles) as f64, ((tile + 1) * src_len).div_ceil(tiles) as f64);
+            let (start, end) = (start.max(tile_start), end.min(tile_end));
+            let mut weights = [0; 3];
+            let mut total = 0;
+            for (tap, weight) in weights.iter_mut().enumerate() {
+                let texel = (first + tap) as f64;
+                let coverage = (end.min(texel + 1.0) - start.max(texel)).max(0.0);
+                *weight = (coverage / (end - start) * WEIGHT_ONE as f64).round() as u32;
+                total += *weight;
+            }
+            // Put any rounding error on the largest weight, so flat colors stay exactly flat
+            let largest = (0..3).max_by_key(|&tap| weights[tap]).unwrap();
+            weights[largest] = (weights[largest] + WEIGHT_ONE).saturating_sub(total);
+            Taps { first, weights }
+        })
+        .collect()
+}
+
+/// Downsample `image` to half its size in each dimension, rounded down but at least 1.
+pub fn downsample_half(image: &RgbaImage) -> RgbaImage {
+    let threads = std::thread::available_parallelism().map_or(1, NonZeroUsize::get);
+    downsample_half_with_threads(image, threads)
+}
+
+/// Like [downsample_half], but without filtering across the boundaries of the tiles in `grid`.
+pub fn downsample_half_tiled(image: &RgbaImage, grid: TileGrid) -> RgbaImage {
+    let threads = std::thread::available_parallelism().map_or(1, NonZeroUsize::get);
+    downsample(image, threads, grid)
+}
+
+/// Like [downsample_half], but using at most `threads` threads.
+pub fn downsample_half_with_threads(image: &RgbaImage, threads: usize) -> RgbaImage {
+    downsample(image, threads, TileGrid::WHOLE)
+}
+
+fn downsample(image: &RgbaImage, threads: usize, grid: TileGrid) -> RgbaImage {
+    let (src_width, src_height) = (image.width() as usize, image.height() as usize);
+    let (dst_width, dst_height) = ((src_width / 2).max(1), (src_height / 2).max(1));
+    let x_taps = taps(src_width, dst_width, grid.columns as usize);
+    let y_taps = taps(src_height, dst_height, grid.rows as usize);
+    let src = image.as_raw();
+
+    let mut dst = vec![0u8; dst_width * dst_height * 4];
+    let row_bytes = dst_width * 4;
+    let threads = if dst_width * dst_height < PARALLEL_MIN_TEXELS { 1 } else { threads.clamp(1, dst_height) };
+    let rows_per_band = dst_height.div_ceil(threads);
+
+    let downsample_band = |first_row: usize, band: &mut [u8]| {
+        // One row of horizontally filtered texels, reused for each of the vertical taps
+        let mut filtered_row = vec![[0u32; 4]; dst_width];
+        let mut accumulated = vec![[0u32; 4]; dst_width];
+        for (row_in_band, dst_row) in band.chunks_exact_mut(row_bytes).enumerate() {
+            let y = y_taps[first_row + row_in_band];
+            accumulated.fill([0; 4]);
+            for (tap, &y_weight) in y.weights.iter().enumerate() {
+                if y_weight == 0 {
+                    continue;
+                }
+                let src_row = &src[(y.first + tap).min(src_height - 1) * src_width * 4..][..src_width * 4];
+                for (out, x) in filtered_row.iter_mut().zip(&x_taps) {
+                    *out = [0; 4];
+                    for (tap, &x_weight) in x.weights.iter().enumerate() {
+                        let texel = &src_row[(x.first + tap).min(src_width - 1) * 4..][..4];
+                        for channel in 0..4 {
+                            out[channel] += texel[channel] as u32 * x_weight;
+                        }
+                    }
+                }
+                for (acc, filtered) in accumulated.iter_mut().zip(&filtered_row) {
+                    for channel in 0..4 {
+                        acc[channel] += filtered[channel] * y_weight;
+                    }
+                }
+            }
+            for (texel, acc) in dst_row.chunks_exact_mut(4).zip(&accumulated) {
+                for channel in 0..4 {
+                    texel[channel] = ((acc[channel] + WEIGHT_ONE * WEIGHT_ONE / 2) / (WEIGHT_ONE * WEIGHT_ONE)) as u8;
+                }
+            }
+        }
+    };
+
+    if threads == 1 {
+        downsample_band(0, &mut dst);
+    } else {
+        std::thread::scope(|scope| {
+            for (band_idx, band) in dst.chunks_mut(rows_per_band * row_bytes).enumerate() {
+                let downsample_band = &downsample_band;
+                scope.spawn(move || downsample_band(band_idx * rows_per_band, band));
+            }
+        });
+    }
+
+    RgbaImage::from_raw(dst_width as u32, dst_height as u32, dst).expect("buffer is dst_width * dst_height texels")
+}
@@ -0,0 +1,137 @@
+//! Writing output files so an interrupted run never leaves a truncated file where a good one used to be.
+//!
+//! Data is written to a temporary file next to the destination, flushed to disk, and renamed over the destination.
+//! The rename is atomic as both are in the same directory, so the destination always holds either the old file or the complete new one.
+//!
+//! If the process is asked to stop (see [crate::shutdown]), writes which haven't started fail with [crate::Error::Interrupted],
+//! and [remove_temp_files] deletes the temporary files of any still running.
+//!
+//! Re-runs can leave existing outputs alone, see [OverwritePolicy]. With [OverwritePolicy::IfNewer], each output gets
+//! a `.stamp` sidecar recording a hash of what it was made from, written by [write_stamp] and checked by [is_up_to_date].
+
+use std::{fs::File, io::Write, path::{Path, PathBuf}, sync::{atomic::{AtomicU32, Ordering}, Mutex}};
+
+use crate::{hash, shutdown, Result};
+
+/// The temporary files of writes in progress.
+static TEMP_FILES: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// Where the previous contents of `path` are kept when writing with a backup: `path` with `.bak` appended.
+pub fn backup_path(path: &Path) -> PathBuf {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(".bak");
+    PathBuf::from(backup)
+}
+
+/// Atomically replace the contents of `path` with `data`.
+/// If `backup` is set and `path` already exists, its previous contents are kept at [backup_path], replacing any older backup.
+pub fn write_atomic(path: &Path, data: &[u8], backup: bool) -> Result<()> {
+    static COUNTER: AtomicU32 = AtomicU32::new(0);
+
+    shutdown::check()?;
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp_path = dir.join(format!(".{file_name}.{}-{}.tmp", std::process::id(), COUNTER.fetch_add(1, Ordering::Relaxed)));
+
+    TEMP_FILES.lock().unwrap().push(temp_path.clone());
+    let written = (|| -> Result<()> {
+        let mut temp = File::options().write(true).create_new(true).open(&temp_path)?;
+        temp.write_all(data)?;
+        temp.sync_all()?;
+        drop(temp);
+        if backup && path.exists() {
+            let backup_path = backup_path(path);
+            match std::fs::remove_file(&backup_path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+            // Hard links are free, but not every filesystem has them
+            if std::fs::hard_link(path, &backup_path).is_err() {
+                std::fs::copy(path, &backup_path)?;
+            }
+        }
+        std::fs::rename(&temp_path, path)?;
+        Ok(())
+    })();
+    if written.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    TEMP_FILES.lock().unwrap().retain(|path| *path != temp_path);
+    written?;
+    // Make the rename itself durable. Directories can't be opened as files on every platform, so this is best effort.
+    if let Ok(dir) = File::open(dir) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+/// Delete the temporary files of any writes still in progress, e.g. on other threads when the process is about to exit,
+/// returning the ones deleted. Those writes then fail instead of replacing their destinations.
+pub fn remove_temp_files() -> Vec<PathBuf> {
+    let temp_files = std::mem::take(&mut *TEMP_FILES.lock().unwrap());
+    temp_files.into_iter().filter(|path| std::fs::remove_file(path).is_ok()).collect()
+}
+
+/// What to do when an output file already exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverwritePolicy {
+    /// Always replace it.
+    #[default]
+    Overwrite,
+    /// Leave it alone, whatever it was made from.
+    SkipExisting,
+    /// Leave it alone if its stamp shows it was made from the same inputs and settings, see [input_stamp].
+    IfNewer,
+}
+impl std::str::FromStr for OverwritePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "overwrite" => Ok(OverwritePolicy::Overwrite),
+            "skip-existing" => Ok(OverwritePolicy::SkipExisting),
+            "if-newer" => Ok(OverwritePolicy::IfNewer),
+            _ => Err(format!("unknown overwrite policy '{s}', expected 'overwrite', 'skip-existing' or 'if-newer'")),
+        }
+    }
+}
+impl std::fmt::Display for OverwritePolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            OverwritePolicy::Overwrite => "overwrite",
+            OverwritePolicy::SkipExisting => "skip-existing",
+            OverwritePolicy::IfNewer => "if-newer",
+        })
+    }
+}
+
+/// Where the stamp for `path` is kept: `path` with `.stamp` appended.
+pub fn stamp_path(path: &Path) -> PathBuf {
+    let mut stamp = path.as_os_str().to_owned();
+    stamp.push(".stamp");
+    PathBuf::from(stamp)
+}
+
+/// A stamp identifying the output made from `inputs` with `settings`, and by this version of the crate.
+/// Hashes the input contents rather than comparing modification times, which checkouts and copies don't preserve.
+pub fn input_stamp(settings: &str, inputs: &[&[u8]]) -> String {
+    let mut key = format!("gltf_ktxer={};settings={settings}", env!("CARGO_PKG_VERSION"));
+    for input in inputs {
+        key.push_str(";input=");
+        key.push_str(&hash::hex(&hash::sha256(input)));
+    }
+    format!("sha256:{}\n", hash::hex(&hash::sha256(key.as_bytes())))
+}
+
+/// Whether `path` exists and has a stamp matching `stamp`.
+pub fn is_up_to_date(path: &Path, stamp: &str) -> bool {
+    path.exists() && std::fs::read_to_string(stamp_path(path)).is_ok_and(|existing| existing == stamp)
+}
+
+/// Record that `path` was made from the inputs `stamp` identifies.
+pub fn write_stamp(path: &Path, stamp: &str) -> Result<()> {
+    write_atomic(&stamp_path(path), stamp.as_bytes(), false)
+}
@@ -0,0 +1,37 @@
+//! Settings for individual textures which differ from the rest of the document.
+//!
+//! Overrides are keyed on the source image's `name`, or failing that its URI, as those survive re-exports
+//! where indices don't. Data URIs are never matched.
+
+use std::collections::HashMap;
+
+use crate::{adjust::ColorAdjustments, dither::Dither, gltf::GltfImage, mipmap::TileGrid};
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct TextureOverride {
+    /// The texture is an atlas of tiles which must not bleed into each other when mipmapping, see [crate::mipmap].
+    pub atlas: Option<TileGrid>,
+    /// Dither smooth gradients before ETC1S encoding to reduce banding.
+    pub dither: Option<Dither>,
+    /// Color adjustments applied before encoding.
+    pub adjustments: Option<ColorAdjustments>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TextureOverrides {
+    by_key: HashMap<String, TextureOverride>,
+}
+impl TextureOverrides {
+    /// Override the settings for images named `key`, or with the URI `key`.
+    pub fn insert(&mut self, key: impl Into<String>, texture_override: TextureOverride) {
+        self.by_key.insert(key.into(), texture_override);
+    }
+    pub fn get(&self, key: &str) -> Option<&TextureOverride> {
+        self.by_key.get(key)
+    }
+    /// The override for `image`, matching its name before its URI.
+    pub fn for_image(&self, image: &GltfImage) -> Option<&TextureOverride> {
+        let name = image.name.as_deref().and_then(|name| self.get(name));
+        name.or_else(|| image.uri.as_ref().filter(|uri| !uri.is_data_uri()).and_then(|uri| self.get(uri.as_str())))
+    }
+}
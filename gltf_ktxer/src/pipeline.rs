@@ -0,0 +1,186 @@
+//! The stages a conversion runs through, and the image transforms library users can insert between decoding and encoding.
+//!
+//! Each [Stage] is a separate entry point, so applications can run the stages they need and do their own work in between:
+//! e.g. plan with [crate::get_reencode_jobs], run each job's [crate::ImageReencodeJob::decode_and_transform] on their own threads,
+//! and encode the results with their own encoder. [crate::execute_reencode_jobs] runs the stages after planning in one go.
+
+use std::{fmt::Display, str::FromStr, sync::Arc};
+
+use image::RgbaImage;
+
+use crate::{decision::TextureContext, dither, encode_ktx2, ktx2, mipmap, tiling, Error, ImageReencodeFormat, ImageReencodeJob, Params, Result};
+
+/// The stages of a conversion, in the order they run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Stage {
+    /// Read the document and the files it refers to, see [crate::load::load_gltf].
+    Load,
+    /// Check the document and inspect its images, see [crate::validate::validate] and [crate::stats].
+    Analyze,
+    /// Decide what to encode each texture as, see [crate::get_reencode_jobs].
+    Plan,
+    /// Decode each source image and prepare it for encoding, including any [ImageTransform]s, see [ImageReencodeJob::decode_and_transform].
+    DecodeTransform,
+    /// Encode the prepared images, see [ImageReencodeJob::encode].
+    Encode,
+    /// Lay the buffers out for output, see [crate::prepare_output_buffers].
+    Pack,
+    /// Write the output files, see [crate::output::write_atomic].
+    Write,
+}
+impl Stage {
+    pub const ALL: [Stage; 7] = [Stage::Load, Stage::Analyze, Stage::Plan, Stage::DecodeTransform, Stage::Encode, Stage::Pack, Stage::Write];
+}
+impl FromStr for Stage {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Stage::ALL
+            .into_iter()
+            .find(|stage| stage.to_string() == s)
+            .ok_or_else(|| format!("unknown stage '{s}', expected one of {}", Stage::ALL.map(|stage| format!("'{stage}'")).join(", ")))
+    }
+}
+impl Display for Stage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Stage::Load => "load",
+            Stage::Analyze => "analyze",
+            Stage::Plan => "plan",
+            Stage::DecodeTransform => "decode-transform",
+            Stage::Encode => "encode",
+            Stage::Pack => "pack",
+            Stage::Write => "write",
+        })
+    }
+}
+
+/// A change made to images after they're decoded and resized, and before they're encoded, e.g. watermarking or custom channel packing.
+/// Add transforms to [crate::Params::image_transforms].
+pub trait ImageTransform: Send + Sync {
+    /// Identifies the transform and its settings, for error messages and [ImageReencodeJob::encode_params_key].
+    /// Transforms which would change images differently must have different keys.
+    fn key(&self) -> String;
+    /// Whether to transform the image of the texture described by `context`, decided while planning.
+    /// Textures sharing an image get the transforms chosen for the first of them.
+    fn applies_to(&self, context: &TextureContext) -> bool {
+        let _ = context;
+        true
+    }
+    /// Transform `image`, which holds sRGB-encoded data if `srgb` is set and linear data otherwise.
+    /// The image must keep its dimensions.
+    fn apply(&self, image: &mut RgbaImage, srgb: bool) -> std::result::Result<(), String>;
+}
+
+impl ImageReencodeJob {
+    /// The [Stage::DecodeTransform] stage: decode the source image, apply any [crate::adjust] adjustments,
+    /// downscale it to [ImageReencodeJob::max_dimension], then apply each of [ImageReencodeJob::transforms] in turn.
+    pub fn decode_and_transform(&self) -> Result<RgbaImage> {
+        let source = self.source.decode()?;
+        let mut image = match self.max_dimension {
+            Some(max_dimension) if self.adjustments.is_none() => self.fit_within(&source, max_dimension),
+            _ => (*source).clone(),
+        };
+        if let Some(adjustments) = &self.adjustments {
+            adjustments.apply(&mut image, self.data_used_as_srgb);
+            if let Some(max_dimension) = self.max_dimension {
+                image = self.fit_within(&image, max_dimension);
+            }
+        }
+        apply_transforms(&self.transforms, &mut image, self.data_used_as_srgb)?;
+        Ok(image)
+    }
+
+    /// The [Stage::Encode] stage: [ImageReencodeJob::decode_and_transform] the source, and encode it as [ImageReencodeJob::reencode_as].
+    /// KTX2 images are encoded with [encode_ktx2], and checked with [ImageReencodeJob::verify_output] if `verify` is set, see [Params::verify_outputs].
+    /// [ImageReencodeFormat::Copy] returns the source data without decoding it.
+    pub fn encode(&self, verify: bool) -> Result<Vec<u8>> {
+        self.encode_image(verify).map(EncodedImage::into_bytes)
+    }
+
+    /// Like [ImageReencodeJob::encode], but KTX2 textures are returned unserialized,
+    /// so they can be written straight into the output buffer with [crate::edit::append_ktx2_buffer_view].
+    pub fn encode_image(&self, verify: bool) -> Result<EncodedImage> {
+        if self.reencode_as == ImageReencodeFormat::Copy {
+            return Ok(EncodedImage::Data(self.source.data.clone()));
+        }
+        let mut image = self.decode_and_transform()?;
+        match self.reencode_as {
+            ImageReencodeFormat::Basic(format) => {
+                let mut image = image::DynamicImage::ImageRgba8(image);
+                // JPEG has no alpha channel
+                if format == image::ImageFormat::Jpeg {
+                    image = image::DynamicImage::ImageRgb8(image.to_rgb8());
+                }
+                let mut data = std::io::Cursor::new(vec![]);
+                image.write_to(&mut data, format)?;
+                Ok(EncodedImage::Data(data.into_inner()))
+            }
+            ImageReencodeFormat::Ktx { codec, basis_compression_quality, dither, effort, .. } => {
+                if let Some(dither) = dither {
+                    dither.apply(&mut image, dither::ETC1S_ENDPOINT_BITS);
+                }
+                let color_space = if self.data_used_as_srgb { ktx2::ColorSpace::Srgb } else { ktx2::ColorSpace::Linear };
+                let ktx = encode_ktx2(&image, color_space, &Params { ktx_codec: codec, ktx_basis_compression_quality: basis_compression_quality, encode_effort: effort, ..Params::default() })?;
+                if verify {
+                    self.verify_output(&ktx)?;
+                }
+                Ok(EncodedImage::Ktx2(ktx))
+            }
+            ImageReencodeFormat::Copy => unreachable!("copies return early"),
+        }
+    }
+
+    /// The media type of the image this job produces.
+    pub fn output_mime_type(&self) -> &str {
+        match self.reencode_as {
+            ImageReencodeFormat::Basic(format) => format.to_mime_type(),
+            ImageReencodeFormat::Ktx { .. } => "image/ktx2",
+            ImageReencodeFormat::Copy => &self.source.mime_type,
+        }
+    }
+
+    /// Downscale `image` to fit within `max_dimension`, by halving if [ImageReencodeJob::repeats].
+    fn fit_within(&self, image: &RgbaImage, max_dimension: u32) -> RgbaImage {
+        match self.reencode_as {
+            _ if !self.repeats => ktx2::fit_within(image, max_dimension),
+            ImageReencodeFormat::Ktx { atlas: Some(grid), .. } => tiling::halve_within(image, max_dimension, grid),
+            _ => tiling::halve_within(image, max_dimension, mipmap::TileGrid::WHOLE),
+        }
+    }
+}
+
+/// The output of [ImageReencodeJob::encode_image].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EncodedImage {
+    /// An encoded file, e.g. a PNG, or a copy of the source.
+    Data(Vec<u8>),
+    /// A KTX2 texture, not yet serialized.
+    Ktx2(ktx2::Ktx2Texture),
+}
+impl EncodedImage {
+    pub fn into_bytes(self) -> Vec<u8> {
+        match self {
+            EncodedImage::Data(data) => data,
+            EncodedImage::Ktx2(ktx) => ktx.to_bytes(),
+        }
+    }
+}
+impl From<Vec<u8>> for EncodedImage {
+    fn from(data: Vec<u8>) -> Self {
+        EncodedImage::Data(data)
+    }
+}
+
+/// Apply each of `transforms` to `image` in turn, failing if any fails or changes the image's dimensions.
+pub fn apply_transforms(transforms: &[Arc<dyn ImageTransform>], image: &mut RgbaImage, srgb: bool) -> Result<()> {
+    let dimensions = image.dimensions();
+    for transform in transforms {
+        let failed = |message: String| Error::TransformFailed { key: transform.key(), message };
+        transform.apply(image, srgb).map_err(failed)?;
+        if image.dimensions() != dimensions {
+            return Err(failed(format!("changed the image size from {dimensions:?} to {:?}", image.dimensions())));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,55 @@
+use image::{Rgba, RgbaImage};
+
+use crate::{ktx2::{ColorSpace, Ktx2Texture}, Error, Result};
+
+/// A procedurally generated image, used in place of a texture that can't or shouldn't be converted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Placeholder {
+    Solid(Rgba<u8>),
+    Checkerboard {
+        a: Rgba<u8>,
+        b: Rgba<u8>,
+        /// The width and height of each checker cell in pixels.
+        cell_size: u32,
+    },
+}
+impl Placeholder {
+    /// The classic "missing texture" magenta/black checkerboard.
+    pub const MISSING: Self = Self::Checkerboard {
+        a: Rgba([0xff, 0x00, 0xff, 0xff]),
+        b: Rgba([0x00, 0x00, 0x00, 0xff]),
+        cell_size: 32,
+    };
+
+    pub fn generate(&self, width: u32, height: u32) -> RgbaImage {
+        match *self {
+            Placeholder::Solid(color) => RgbaImage::from_pixel(width, height, color),
+            Placeholder::Checkerboard { a, b, cell_size } => {
+                let cell_size = cell_size.max(1);
+                RgbaImage::from_fn(width, height, |x, y| {
+                    if ((x / cell_size) + (y / cell_size)) % 2 == 0 { a } else { b }
+                })
+            }
+        }
+    }
+
+    pub fn generate_ktx2(&self, width: u32, height: u32, color_space: ColorSpace) -> Result<Vec<u8>> {
+        let image = self.generate(width, height);
+        Ok(Ktx2Texture::from_rgba8(&image, color_space)?.to_bytes())
+    }
+}
+
+/// Parse a CSS-style `#rrggbb` or `#rrggbbaa` color. The leading `#` is optional.
+pub fn parse_color(s: &str) -> Result<Rgba<u8>> {
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    if !(hex.len() == 6 || hex.len() == 8) || !hex.is_ascii() {
+        return Err(Error::BadColorString(s.to_string()));
+    }
+    let mut color = Rgba([0xff; 4]);
+    for (i, channel) in hex.as_bytes().chunks(2).enumerate() {
+        // chunks of an ASCII string are always valid UTF-8
+        let channel = std::str::from_utf8(channel).unwrap();
+        color.0[i] = u8::from_str_radix(channel, 16).map_err(|_| Error::BadColorString(s.to_string()))?;
+    }
+    Ok(color)
+}
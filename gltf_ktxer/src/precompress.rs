@@ -0,0 +1,106 @@
+//! Precompressed copies of written files, e.g. `model.glb.gz` and `model.glb.br` next to `model.glb`,
+//! so web servers and CDNs can serve them with `Content-Encoding` without compressing on the fly or in a separate build step.
+//!
+//! Gzip is built in. Brotli runs an external command, `brotli` by default, as the crate has no Brotli encoder of its own.
+
+use std::{
+    fmt::Display,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    str::FromStr,
+};
+
+use crate::{argfile, Error, Result};
+
+/// The Brotli command, looked for on `PATH` unless another command is given. It must read stdin and write stdout.
+pub const DEFAULT_BROTLI_COMMAND: &str = "brotli --quality=11 --stdout";
+
+/// A kind of precompressed copy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Sidecar {
+    Gzip,
+    Brotli,
+}
+impl Sidecar {
+    /// The extension added to the original file's name.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Sidecar::Gzip => "gz",
+            Sidecar::Brotli => "br",
+        }
+    }
+    /// Where the copy of `path` goes, e.g. `model.glb.gz` for `model.glb`.
+    pub fn path_for(self, path: &Path) -> PathBuf {
+        let mut sidecar = path.as_os_str().to_owned();
+        sidecar.push(".");
+        sidecar.push(self.extension());
+        PathBuf::from(sidecar)
+    }
+    /// Compress `data`, running `brotli_command` (see [DEFAULT_BROTLI_COMMAND]) for [Sidecar::Brotli].
+    pub fn compress(self, data: &[u8], brotli_command: &str) -> Result<Vec<u8>> {
+        match self {
+            Sidecar::Gzip => gzip(data),
+            Sidecar::Brotli => run_compressor(brotli_command, data),
+        }
+    }
+}
+impl FromStr for Sidecar {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "gzip" => Ok(Sidecar::Gzip),
+            "brotli" => Ok(Sidecar::Brotli),
+            _ => Err(format!("unknown compression '{s}', expected 'gzip' or 'brotli'")),
+        }
+    }
+}
+impl Display for Sidecar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Sidecar::Gzip => "gzip",
+            Sidecar::Brotli => "brotli",
+        })
+    }
+}
+
+/// `data` as a gzip file at the best compression level.
+pub fn gzip(data: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+    encoder.write_all(data)?;
+    Ok(encoder.finish()?)
+}
+
+/// Pipe `data` through `command`, the program followed by any arguments quoted like a response file (see [argfile::split]).
+fn run_compressor(command: &str, data: &[u8]) -> Result<Vec<u8>> {
+    let args = argfile::split(command)?;
+    let Some((program, args)) = args.split_first() else {
+        return Err(Error::CompressorUnavailable("the compressor command is empty".to_string()));
+    };
+    let mut child = Command::new(program).args(args).stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().map_err(|e| {
+        Error::CompressorUnavailable(match e.kind() {
+            std::io::ErrorKind::NotFound => format!("'{program}' wasn't found, install it or configure the command"),
+            _ => format!("couldn't run '{program}': {e}"),
+        })
+    })?;
+    // Written from another thread, as the compressor may fill its output pipe before reading all its input
+    let output = std::thread::scope(|scope| {
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let writer = scope.spawn(move || stdin.write_all(data));
+        let output = child.wait_with_output();
+        (writer.join().expect("writing to the compressor doesn't panic"), output)
+    });
+    let output = match output {
+        (_, Err(e)) => return Err(e.into()),
+        (written, Ok(output)) if output.status.success() => {
+            written?;
+            output
+        }
+        (_, Ok(output)) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(Error::CompressorUnavailable(format!("'{program}' failed ({}): {}", output.status, stderr.trim())));
+        }
+    };
+    Ok(output.stdout)
+}
@@ -0,0 +1,93 @@
+//! Named bundles of conversion settings for common targets, see [Params::from_preset].
+
+use std::{fmt::Display, num::NonZeroU8, str::FromStr};
+
+use crate::{KtxCodec, Params};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    /// Small ETC1S textures capped at 2048px, for quick loads on the web.
+    WebFast,
+    /// High-quality ETC1S textures capped at 4096px with an AVIF fallback, for the web where quality matters more than size.
+    WebQuality,
+    /// Small ETC1S textures capped at 1024px, for mobile GPU memory budgets.
+    Mobile,
+    /// High-quality ETC1S textures for desktop GPUs, which transcode them to BC1/BC3.
+    DesktopBc,
+    /// Highest-quality ETC1S textures at full resolution, with lossless PNG fallbacks.
+    ArchivalLosslessFallback,
+}
+impl Preset {
+    pub const ALL: [Preset; 5] = [Preset::WebFast, Preset::WebQuality, Preset::Mobile, Preset::DesktopBc, Preset::ArchivalLosslessFallback];
+}
+impl FromStr for Preset {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Preset::ALL.into_iter().find(|preset| preset.to_string() == s).ok_or_else(|| {
+            let names: Vec<String> = Preset::ALL.iter().map(Preset::to_string).collect();
+            format!("unknown preset '{s}', expected one of {}", names.join(", "))
+        })
+    }
+}
+impl Display for Preset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Preset::WebFast => "web-fast",
+            Preset::WebQuality => "web-quality",
+            Preset::Mobile => "mobile",
+            Preset::DesktopBc => "desktop-bc",
+            Preset::ArchivalLosslessFallback => "archival-lossless-fallback",
+        })
+    }
+}
+
+impl Params {
+    /// The settings bundled by `preset`. Every other setting is left at its default,
+    /// and individual settings can be overridden afterwards with struct update syntax.
+    ///
+    /// There's no UASTC encoder yet, so the presets meant for it use ETC1S at a higher quality instead.
+    pub fn from_preset(preset: Preset) -> Self {
+        let defaults = Params::default();
+        match preset {
+            Preset::WebFast => Params {
+                ktx_codec: KtxCodec::Etc1s,
+                ktx_basis_compression_quality: NonZeroU8::new(64),
+                generate_mipmaps: true,
+                max_texture_size: Some(2048),
+                ..defaults
+            },
+            Preset::WebQuality => Params {
+                ktx_codec: KtxCodec::Etc1s,
+                ktx_basis_compression_quality: NonZeroU8::new(192),
+                generate_mipmaps: true,
+                max_texture_size: Some(4096),
+                avif_fallback: true,
+                ..defaults
+            },
+            Preset::Mobile => Params {
+                ktx_codec: KtxCodec::Etc1s,
+                ktx_basis_compression_quality: NonZeroU8::new(128),
+                ktx_transcode_to_bc1_or_bc3: false,
+                generate_mipmaps: true,
+                max_texture_size: Some(1024),
+                ..defaults
+            },
+            Preset::DesktopBc => Params {
+                ktx_codec: KtxCodec::Etc1s,
+                ktx_basis_compression_quality: NonZeroU8::new(192),
+                ktx_transcode_to_bc1_or_bc3: true,
+                generate_mipmaps: true,
+                ..defaults
+            },
+            Preset::ArchivalLosslessFallback => Params {
+                uncompressed_format: image::ImageFormat::Png,
+                ktx_codec: KtxCodec::Etc1s,
+                ktx_basis_compression_quality: NonZeroU8::new(255),
+                generate_mipmaps: true,
+                max_texture_size: None,
+                ..defaults
+            },
+        }
+    }
+}
@@ -0,0 +1,130 @@
+//! Constraints of the platforms converted files are meant for, so the planner can adapt conversions to them.
+//!
+//! Each [TargetProfile] bundles what a platform guarantees: the largest texture it must support, which GPU formats
+//! KTX2 textures can be transcoded to, whether non-power-of-two (NPOT) textures can be mipmapped, and whether
+//! compressed textures can be sampled as sRGB. Sizes are the minimums the APIs guarantee, not what typical hardware offers.
+
+use crate::{validate::Warning, ImageReencodeFormat, ImageReencodeJob};
+
+/// The platform the output is meant for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TargetProfile {
+    WebGl1,
+    WebGl2,
+    WebGpu,
+    Vulkan,
+    Gles3,
+    Metal,
+}
+impl std::str::FromStr for TargetProfile {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "webgl1" => Ok(TargetProfile::WebGl1),
+            "webgl2" => Ok(TargetProfile::WebGl2),
+            "webgpu" => Ok(TargetProfile::WebGpu),
+            "vulkan" => Ok(TargetProfile::Vulkan),
+            "gles3" => Ok(TargetProfile::Gles3),
+            "metal" => Ok(TargetProfile::Metal),
+            _ => Err(format!("unknown target '{s}', expected 'webgl1', 'webgl2', 'webgpu', 'vulkan', 'gles3' or 'metal'")),
+        }
+    }
+}
+impl std::fmt::Display for TargetProfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            TargetProfile::WebGl1 => "webgl1",
+            TargetProfile::WebGl2 => "webgl2",
+            TargetProfile::WebGpu => "webgpu",
+            TargetProfile::Vulkan => "vulkan",
+            TargetProfile::Gles3 => "gles3",
+            TargetProfile::Metal => "metal",
+        })
+    }
+}
+
+/// A GPU format Basis Universal textures can be transcoded to when loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TranscodeFormat {
+    Etc1,
+    Etc2,
+    /// BC1 for opaque textures, BC3 for ones with alpha.
+    Bc1Bc3,
+    Bc7,
+    Astc,
+    Pvrtc1,
+}
+
+/// Which mip chains an NPOT texture may have.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NpotMipmaps {
+    /// Only the first level, and no repeat wrapping either.
+    Unsupported,
+    /// Every level after the first must be 1, 2 or a multiple of 4 texels in each dimension, for block-compressed formats.
+    BlockAligned,
+    Full,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProfileConstraints {
+    /// The largest width or height every implementation supports.
+    pub max_texture_size: u32,
+    pub transcode_formats: &'static [TranscodeFormat],
+    pub npot_mipmaps: NpotMipmaps,
+    /// Whether compressed textures can be sampled as sRGB, with the hardware decoding to linear.
+    pub srgb: bool,
+}
+
+impl TargetProfile {
+    pub fn constraints(self) -> ProfileConstraints {
+        use TranscodeFormat::*;
+        const MODERN: &[TranscodeFormat] = &[Etc2, Bc1Bc3, Bc7, Astc];
+        match self {
+            // sRGB needs EXT_sRGB, which doesn't cover compressed formats
+            TargetProfile::WebGl1 => ProfileConstraints {
+                max_texture_size: 4096,
+                transcode_formats: &[Etc1, Bc1Bc3, Pvrtc1],
+                npot_mipmaps: NpotMipmaps::Unsupported,
+                srgb: false,
+            },
+            TargetProfile::WebGl2 => ProfileConstraints { max_texture_size: 4096, transcode_formats: MODERN, npot_mipmaps: NpotMipmaps::BlockAligned, srgb: true },
+            TargetProfile::WebGpu => ProfileConstraints { max_texture_size: 8192, transcode_formats: MODERN, npot_mipmaps: NpotMipmaps::Full, srgb: true },
+            TargetProfile::Vulkan => ProfileConstraints { max_texture_size: 4096, transcode_formats: MODERN, npot_mipmaps: NpotMipmaps::Full, srgb: true },
+            TargetProfile::Gles3 => ProfileConstraints { max_texture_size: 2048, transcode_formats: &[Etc2, Astc], npot_mipmaps: NpotMipmaps::Full, srgb: true },
+            TargetProfile::Metal => ProfileConstraints {
+                max_texture_size: 8192,
+                transcode_formats: &[Etc2, Bc1Bc3, Bc7, Astc, Pvrtc1],
+                npot_mipmaps: NpotMipmaps::Full,
+                srgb: true,
+            },
+        }
+    }
+
+    pub fn supports(self, format: TranscodeFormat) -> bool {
+        self.constraints().transcode_formats.contains(&format)
+    }
+
+    /// The size limit to downscale images to, given the user's own `max_texture_size` if any.
+    pub fn max_texture_size(self, max_texture_size: Option<u32>) -> u32 {
+        let limit = self.constraints().max_texture_size;
+        max_texture_size.map_or(limit, |size| size.min(limit))
+    }
+}
+
+/// Report the planned KTX2 images `profile` can't sample as intended, which it can't be adapted to.
+/// Returns an `srgb_unsupported` warning for each sRGB image if the profile can't sample compressed textures as sRGB.
+pub fn check_jobs(jobs: &[ImageReencodeJob], profile: TargetProfile) -> Vec<Warning> {
+    if profile.constraints().srgb {
+        return vec![];
+    }
+    jobs.iter()
+        .enumerate()
+        .filter(|(_, job)| job.data_used_as_srgb && matches!(job.reencode_as, ImageReencodeFormat::Ktx { .. }))
+        .map(|(idx, _)| Warning {
+            code: "srgb_unsupported",
+            json_pointer: String::new(),
+            message: format!("output image {idx} holds sRGB data, but {profile} can't sample compressed textures as sRGB, so it must be decoded in the shader"),
+        })
+        .collect()
+}
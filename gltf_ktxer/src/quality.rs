@@ -0,0 +1,165 @@
+//! Per-texture size and quality reports, and checking a run against the report of an earlier one, to catch
+//! accidental quality or size regressions when updating the tool or changing settings.
+//!
+//! A report is a JSON file such as
+//! ```json
+//! {
+//!     "version": 1,
+//!     "textures": [{ "name": "wall.ktx2", "bytes": 43690, "psnr": 41.7 }]
+//! }
+//! ```
+//! Textures are matched by `name`. Textures only in one of the reports aren't compared.
+//! Reports can also record each texture's codec, source size and encode time, which [crate::summary] prints but nothing compares.
+
+use std::path::Path;
+
+use image::RgbaImage;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{validate::Warning, Error, Result};
+
+/// The `version` written to reports, increased whenever their layout changes incompatibly.
+pub const QUALITY_REPORT_VERSION: u64 = 1;
+
+/// The PSNR recorded for an output identical to its reference, as the true value is infinite and JSON has no infinity.
+pub const MAX_PSNR: f64 = 100.0;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TextureQuality {
+    /// Identifies the texture between runs, e.g. its output file name.
+    pub name: String,
+    /// The size of the encoded texture.
+    pub bytes: u64,
+    /// The PSNR of the texture's first level against the image it was encoded from, in dB, see [psnr].
+    /// None if it couldn't be measured, e.g. as the texture can't be decoded.
+    pub psnr: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub codec: Option<String>,
+    /// The size of the files the texture was made from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_bytes: Option<u64>,
+    /// How long the texture took to make, in seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seconds: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct QualityReport {
+    pub version: u64,
+    pub textures: Vec<TextureQuality>,
+}
+impl Default for QualityReport {
+    fn default() -> Self {
+        Self { version: QUALITY_REPORT_VERSION, textures: vec![] }
+    }
+}
+impl QualityReport {
+    pub fn from_json(json: &[u8]) -> Result<Self> {
+        let report: Self = serde_json::from_slice(json)?;
+        if report.version != QUALITY_REPORT_VERSION {
+            return Err(Error::BadQualityReport(format!("version {} isn't supported, expected {QUALITY_REPORT_VERSION}", report.version)));
+        }
+        Ok(report)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::from_json(&std::fs::read(path)?)
+    }
+
+    pub fn to_json(&self) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec_pretty(self)?)
+    }
+}
+
+/// The peak signal-to-noise ratio of `output` against `reference` over all four channels, in dB, capped at [MAX_PSNR].
+/// Higher is better. The images must be the same size.
+pub fn psnr(reference: &RgbaImage, output: &RgbaImage) -> f64 {
+    assert_eq!(reference.dimensions(), output.dimensions(), "psnr compares images of the same size");
+    let squared_error: u64 = reference.as_raw().iter().zip(output.as_raw()).map(|(&a, &b)| (a.abs_diff(b) as u64).pow(2)).sum();
+    if squared_error == 0 {
+        return MAX_PSNR;
+    }
+    let mse = squared_error as f64 / reference.as_raw().len() as f64;
+    (10.0 * (255.0 * 255.0 / mse).log10()).min(MAX_PSNR)
+}
+
+/// How much worse a texture may get than in the baseline before it counts as a regression.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RegressionThresholds {
+    /// The most a texture may grow, as a percentage of its baseline size.
+    pub max_size_growth_percent: f64,
+    /// The most a texture's PSNR may drop, in dB.
+    pub max_psnr_drop: f64,
+}
+impl Default for RegressionThresholds {
+    fn default() -> Self {
+        Self { max_size_growth_percent: 5.0, max_psnr_drop: 0.5 }
+    }
+}
+
+/// What to do when a texture regresses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum RegressionAction {
+    /// Fail with [Error::QualityRegression].
+    #[default]
+    Fail,
+    /// Carry on, reporting a `quality_regression` warning for each regression.
+    Warn,
+}
+impl std::str::FromStr for RegressionAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "fail" => Ok(RegressionAction::Fail),
+            "warn" => Ok(RegressionAction::Warn),
+            _ => Err(format!("unknown regression action '{s}', expected 'fail' or 'warn'")),
+        }
+    }
+}
+impl std::fmt::Display for RegressionAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            RegressionAction::Fail => "fail",
+            RegressionAction::Warn => "warn",
+        })
+    }
+}
+
+/// Compare each texture of `current` to the texture of the same name in `baseline`.
+///
+/// Returns a warning for each regression beyond `thresholds` if `action` is [RegressionAction::Warn],
+/// otherwise fails on the first.
+pub fn check_regressions(baseline: &QualityReport, current: &QualityReport, thresholds: RegressionThresholds, action: RegressionAction) -> Result<Vec<Warning>> {
+    let mut regressions = vec![];
+    for texture in &current.textures {
+        let Some(before) = baseline.textures.iter().find(|before| before.name == texture.name) else {
+            continue;
+        };
+        let growth_percent = (texture.bytes as f64 - before.bytes as f64) * 100.0 / before.bytes.max(1) as f64;
+        if growth_percent > thresholds.max_size_growth_percent {
+            regressions.push(format!(
+                "'{}' grew from {} to {} bytes (+{growth_percent:.1}%), more than the {}% allowed",
+                texture.name, before.bytes, texture.bytes, thresholds.max_size_growth_percent
+            ));
+        }
+        if let (Some(psnr_before), Some(psnr)) = (before.psnr, texture.psnr) {
+            if psnr_before - psnr > thresholds.max_psnr_drop {
+                regressions.push(format!(
+                    "'{}' PSNR dropped from {psnr_before:.2} to {psnr:.2} dB, more than the {} dB allowed",
+                    texture.name, thresholds.max_psnr_drop
+                ));
+            }
+        }
+    }
+    match action {
+        RegressionAction::Fail => match regressions.into_iter().next() {
+            Some(message) => Err(Error::QualityRegression(message)),
+            None => Ok(vec![]),
+        },
+        RegressionAction::Warn => Ok(regressions
+            .into_iter()
+            .map(|message| Warning { code: "quality_regression", json_pointer: String::new(), message })
+            .collect()),
+    }
+}
@@ -0,0 +1,165 @@
+//! Error reports for the command-line tool.
+//!
+//! Human-readable reports show errors located with [Error::at] with the JSON pointer, the offending value,
+//! and an excerpt of the surrounding JSON with the offending property underlined.
+//! Machine-readable reports are a single JSON object per error, see [error_json].
+
+use std::{fmt::Write, path::Path};
+
+use serde_json::{json, Value};
+
+use crate::{gltf::GltfDoc, validate::Warning, Error};
+
+/// The most lines of JSON to show around the offending property.
+const EXCERPT_CONTEXT_LINES: usize = 3;
+/// Offending values longer than this are cut short, as they're usually data URIs.
+const MAX_VALUE_LEN: usize = 80;
+
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+const RESET: &str = "\x1b[0m";
+
+struct Style {
+    color: bool,
+}
+impl Style {
+    fn paint(&self, code: &str, text: &str) -> String {
+        if self.color {
+            format!("{code}{text}{RESET}")
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// Render `err` for the terminal, with ANSI colors if `color` is set.
+/// If `doc` is the document the error came from, located errors include an excerpt of it.
+pub fn render_error(err: &Error, doc: Option<&GltfDoc>, color: bool) -> String {
+    let style = Style { color };
+    let mut out = format!("{}: {}\n", style.paint(RED, "error"), err.without_location());
+    if let Some(pointer) = err.json_pointer() {
+        render_location(&mut out, pointer, doc, &style);
+    }
+    #[cfg(feature = "schema")]
+    if let Error::SchemaViolations(violations) = err.without_location() {
+        for violation in violations {
+            render_location(&mut out, &violation.json_pointer, doc, &style);
+        }
+    }
+    if let Error::ExternalValidation(messages) = err.without_location() {
+        for message in messages.iter().filter(|message| !message.json_pointer.is_empty()) {
+            render_location(&mut out, &message.json_pointer, doc, &style);
+        }
+    }
+    out
+}
+
+/// Render `warning` for the terminal, like [render_error].
+pub fn render_warning(warning: &Warning, doc: Option<&GltfDoc>, color: bool) -> String {
+    let style = Style { color };
+    let mut out = format!("{}: {warning}\n", style.paint(YELLOW, "warning"));
+    render_location(&mut out, &warning.json_pointer, doc, &style);
+    out
+}
+
+/// A machine-readable description of `err` for CI wrappers, with the fields
+/// `severity` (always "error"), `code` (see [crate::ErrorCode]), `message`, `json_pointer` (or null) and `file` (or null).
+/// Schema violations and glTF-Validator errors also list every `violations` entry with its own pointer and message,
+/// and for glTF-Validator errors the validator's `code`.
+pub fn error_json(err: &Error, file: Option<&Path>) -> Value {
+    let mut report = json!({
+        "severity": "error",
+        "code": err.code().as_str(),
+        "message": err.without_location().to_string(),
+        "json_pointer": err.json_pointer(),
+        "file": file.map(|file| file.display().to_string()),
+    });
+    #[cfg(feature = "schema")]
+    if let Error::SchemaViolations(violations) = err.without_location() {
+        report["violations"] = violations
+            .iter()
+            .map(|v| json!({ "json_pointer": v.json_pointer, "message": v.message }))
+            .collect();
+    }
+    if let Error::ExternalValidation(messages) = err.without_location() {
+        report["violations"] = messages
+            .iter()
+            .map(|m| json!({ "json_pointer": m.json_pointer, "message": m.message, "code": m.code }))
+            .collect();
+    }
+    report
+}
+
+/// A machine-readable description of `warning`, with the same fields as [error_json].
+pub fn warning_json(warning: &Warning, file: Option<&Path>) -> Value {
+    json!({
+        "severity": "warning",
+        "code": warning.code,
+        "message": warning.message,
+        "json_pointer": warning.json_pointer,
+        "file": file.map(|file| file.display().to_string()),
+    })
+}
+
+fn render_location(out: &mut String, pointer: &str, doc: Option<&GltfDoc>, style: &Style) {
+    let display_pointer = if pointer.is_empty() { "/" } else { pointer };
+    writeln!(out, "  {} {display_pointer}", style.paint(BLUE, "-->")).unwrap();
+
+    let Some(doc) = doc else {
+        return;
+    };
+    let root = Value::Object(doc.clone());
+    let Some(value) = root.pointer(pointer) else {
+        return;
+    };
+    let mut compact = value.to_string();
+    if compact.len() > MAX_VALUE_LEN {
+        let cut = (0..=MAX_VALUE_LEN).rev().find(|&i| compact.is_char_boundary(i)).unwrap_or(0);
+        compact.truncate(cut);
+        compact.push_str("...");
+    }
+    writeln!(out, "  {} value: {compact}", style.paint(BLUE, "=")).unwrap();
+
+    // Show the object holding the offending property, or the offending value itself if it's a list element
+    let (container, key) = match pointer.rsplit_once('/') {
+        Some((parent, key)) => match root.pointer(parent) {
+            Some(parent @ Value::Object(_)) => (parent, Some(key.replace("~1", "/").replace("~0", "~"))),
+            _ => (value, None),
+        },
+        None => (value, None),
+    };
+    let excerpt = serde_json::to_string_pretty(&truncate_long_strings(container)).unwrap();
+    let lines: Vec<&str> = excerpt.lines().collect();
+    // The property's own line is the first one at one indent level in which starts with its key
+    let key_line = key
+        .map(|key| format!("  {}:", Value::String(key)))
+        .and_then(|prefix| lines.iter().position(|line| line.starts_with(&prefix)));
+
+    let gutter = style.paint(BLUE, "|");
+    writeln!(out, "   {gutter}").unwrap();
+    let focus = key_line.unwrap_or(0);
+    let first = focus.saturating_sub(EXCERPT_CONTEXT_LINES);
+    let last = (focus + EXCERPT_CONTEXT_LINES).min(lines.len().saturating_sub(1));
+    for (i, line) in lines.iter().enumerate().take(last + 1).skip(first) {
+        writeln!(out, "   {gutter} {line}").unwrap();
+        if Some(i) == key_line {
+            let indent = line.len() - line.trim_start().len();
+            let carets = "^".repeat(line.trim().trim_end_matches(',').len().max(1));
+            writeln!(out, "   {gutter} {}{}", " ".repeat(indent), style.paint(RED, &carets)).unwrap();
+        }
+    }
+}
+
+/// Cut long strings (i.e. data URIs) short so they don't flood the excerpt.
+fn truncate_long_strings(value: &Value) -> Value {
+    match value {
+        Value::String(s) if s.len() > MAX_VALUE_LEN => {
+            let cut = (0..=MAX_VALUE_LEN).rev().find(|&i| s.is_char_boundary(i)).unwrap_or(0);
+            Value::String(format!("{}...", &s[..cut]))
+        }
+        Value::Array(items) => Value::Array(items.iter().map(truncate_long_strings).collect()),
+        Value::Object(map) => Value::Object(map.iter().map(|(k, v)| (k.clone(), truncate_long_strings(v))).collect()),
+        _ => value.clone(),
+    }
+}
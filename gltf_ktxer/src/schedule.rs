@@ -0,0 +1,345 @@
+use std::{fmt::Display, num::NonZeroUsize, path::{Path, PathBuf}, str::FromStr, sync::{atomic::{AtomicUsize, Ordering}, Arc, Condvar, Mutex}};
+
+use crate::{decode, groups, gltf::GltfDoc, ktx2, load, shutdown, Error, ImageReencodeJob, Result};
+
+/// The order image jobs are handed to worker threads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum JobOrder {
+    /// Start the most expensive jobs first, so one big texture doesn't end up running alone at the end.
+    /// This is the longest-processing-time-first heuristic for minimizing total wall time.
+    #[default]
+    LargestFirst,
+    /// Run jobs in the order their images appear in the document.
+    DocumentOrder,
+}
+impl FromStr for JobOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "largest-first" => Ok(JobOrder::LargestFirst),
+            "document-order" => Ok(JobOrder::DocumentOrder),
+            _ => Err(format!("unknown job order '{s}', expected 'largest-first' or 'document-order'")),
+        }
+    }
+}
+impl Display for JobOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            JobOrder::LargestFirst => "largest-first",
+            JobOrder::DocumentOrder => "document-order",
+        })
+    }
+}
+
+impl ImageReencodeJob {
+    /// A rough estimate of how expensive this job is to run, for scheduling purposes.
+    /// This is the pixel count if the image dimensions can be read from its header, otherwise the encoded byte count.
+    pub fn estimated_cost(&self) -> u64 {
+        match self.source_dimensions() {
+            Some((width, height)) => width as u64 * height.max(1) as u64,
+            None => self.source.data.len() as u64,
+        }
+    }
+
+    /// The width and height of the source image once its orientation is applied, if they can be read from its header without decoding it.
+    pub fn source_dimensions(&self) -> Option<(u32, u32)> {
+        if self.source.data.starts_with(&ktx2::KTX2_IDENTIFIER) && self.source.data.len() >= 28 {
+            let word = |offset: usize| u32::from_le_bytes(self.source.data[offset..offset + 4].try_into().unwrap());
+            Some((word(20), word(24)))
+        } else {
+            image::ImageReader::new(std::io::Cursor::new(&self.source.data))
+                .with_guessed_format()
+                .ok()
+                .and_then(|reader| reader.into_dimensions().ok())
+                .map(|(width, height)| match self.source.orientation() {
+                    Some(orientation) if decode::swaps_dimensions(orientation) => (height, width),
+                    _ => (width, height),
+                })
+        }
+    }
+}
+
+/// The indices of `jobs` in the order they should be started.
+/// Ties are broken by document order, so the result is deterministic.
+pub fn job_order(jobs: &[ImageReencodeJob], order: JobOrder) -> Vec<usize> {
+    let mut indices: Vec<usize> = (0..jobs.len()).collect();
+    match order {
+        JobOrder::LargestFirst => {
+            let costs: Vec<u64> = jobs.iter().map(ImageReencodeJob::estimated_cost).collect();
+            indices.sort_by_key(|&i| std::cmp::Reverse(costs[i]));
+        }
+        JobOrder::DocumentOrder => {}
+    }
+    indices
+}
+
+/// The order [run_batch] starts files in, after any priority patterns, see [batch_order].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BatchOrder {
+    /// Start files in the order they were given.
+    #[default]
+    Given,
+    /// Start the biggest files first, counting the files a `.gltf` refers to, so a big asset doesn't hold up the end of the batch.
+    LargestFirst,
+}
+impl FromStr for BatchOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "given" => Ok(BatchOrder::Given),
+            "largest-first" => Ok(BatchOrder::LargestFirst),
+            _ => Err(format!("unknown batch order '{s}', expected 'given' or 'largest-first'")),
+        }
+    }
+}
+impl Display for BatchOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            BatchOrder::Given => "given",
+            BatchOrder::LargestFirst => "largest-first",
+        })
+    }
+}
+
+/// The size of the asset at `path` in bytes, including the buffers and images it refers to if it's a `.gltf`.
+/// Files which can't be read count as empty, as do files outside the document's directory, which [load::load_gltf] won't read
+/// either, so sizing an untrusted document doesn't probe the rest of the file system.
+pub fn asset_size(path: &Path) -> u64 {
+    let size = |path: &Path| std::fs::metadata(path).map_or(0, |metadata| metadata.len());
+    let referenced = std::fs::read(path)
+        .ok()
+        .filter(|_| path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("gltf")))
+        .and_then(|json| serde_json::from_slice::<GltfDoc>(&json).ok())
+        .zip(load::canonical_root(path).ok())
+        .map_or(0, |(doc, root)| {
+            load::referenced_files(path, &doc)
+                .iter()
+                .filter_map(|(uri, file)| load::resolve_inside_root(&root, uri, file).ok().flatten())
+                .map(|file| size(&file))
+                .sum()
+        });
+    size(path) + referenced
+}
+
+/// The indices of `paths` in the order [run_batch] should start them.
+///
+/// Files matching one of the `priority` patterns come first, in the order of the patterns, e.g. to finish the assets
+/// a partial deployment needs before the rest. Patterns match the whole path (with `/` separators) or just the file name,
+/// with `*` matching any run of characters and `?` any one character. Within each group files are sorted by `order`,
+/// and ties keep the given order, so the result is deterministic.
+pub fn batch_order(paths: &[PathBuf], order: BatchOrder, priority: &[String]) -> Vec<usize> {
+    let group = |path: &PathBuf| {
+        let full = path.to_string_lossy().replace('\\', "/");
+        let file_name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+        priority
+            .iter()
+            .position(|pattern| groups::glob_match(pattern, &full) || groups::glob_match(pattern, &file_name))
+            .unwrap_or(priority.len())
+    };
+    let groups: Vec<usize> = paths.iter().map(group).collect();
+    let sizes: Vec<u64> = match order {
+        BatchOrder::Given => vec![0; paths.len()],
+        BatchOrder::LargestFirst => paths.iter().map(|path| asset_size(path)).collect(),
+    };
+    let mut indices: Vec<usize> = (0..paths.len()).collect();
+    indices.sort_by_key(|&i| (groups[i], std::cmp::Reverse(sizes[i])));
+    indices
+}
+
+/// Run `f` on every item using up to `threads` worker threads (or one per core if None),
+/// starting items in the sequence given by `order`.
+/// Results are returned in the original item order, regardless of the order they were run in.
+pub fn run_jobs<T: Sync, R: Send>(items: &[T], order: &[usize], threads: Option<NonZeroUsize>, f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    let threads = threads
+        .or_else(|| std::thread::available_parallelism().ok())
+        .map_or(1, NonZeroUsize::get)
+        .min(order.len().max(1));
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<R>>> = Mutex::new(items.iter().map(|_| None).collect());
+
+    std::thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                while let Some(&idx) = order.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    let result = f(&items[idx]);
+                    results.lock().unwrap()[idx] = Some(result);
+                }
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|result| result.expect("every item is in the run order exactly once"))
+        .collect()
+}
+
+/// A file being converted by [run_batch], whose image jobs any worker may pick up.
+struct FileInFlight<J, JR> {
+    file_idx: usize,
+    /// The file's position in the run order.
+    rank: usize,
+    jobs: Arc<Vec<J>>,
+    next_job: usize,
+    results: Vec<Option<JR>>,
+    /// Jobs started but not yet finished, or not yet started.
+    remaining: usize,
+}
+
+struct BatchState<J, JR, R> {
+    /// The position in the run order of the next file to plan.
+    next_file: usize,
+    /// Files which are planning, so may still add jobs.
+    planning: usize,
+    /// In run order, so stolen jobs finish the earliest files first and their memory is freed.
+    in_flight: Vec<FileInFlight<J, JR>>,
+    results: Vec<Option<Result<R>>>,
+}
+
+/// What a [run_batch] worker does next.
+enum BatchTask<J> {
+    Plan { file_idx: usize, rank: usize },
+    Job { file_idx: usize, jobs: Arc<Vec<J>>, job_idx: usize },
+}
+
+/// Convert every one of `files`, starting them in the sequence given by `order` (see [batch_order]),
+/// using up to `threads` worker threads in total (or one per core if None), sharing the threads between files and the images within them instead of nesting one pool inside another.
+///
+/// Each file is `plan`ned into image jobs, each job is `run`, and once every job of a file is done, the file is `finish`ed
+/// with the job results in the order `plan` returned them. Workers prefer the jobs of the file they planned,
+/// then steal jobs from other files, earliest in `order` first, and only plan another file once no jobs are waiting.
+/// So a batch of small files runs one file per thread, while a single big file gets every thread to itself,
+/// and there are never more files in flight than threads.
+///
+/// Once [shutdown::request] is called, files not yet started fail with [Error::Interrupted], and the files in flight are finished.
+///
+/// Results are returned in the order of `files`. Jobs are started in the order `plan` returns them, see [job_order].
+pub fn run_batch<F: Sync, J: Send + Sync, JR: Send, R: Send>(
+    files: &[F],
+    order: &[usize],
+    threads: Option<NonZeroUsize>,
+    plan: impl Fn(&F) -> Result<Vec<J>> + Sync,
+    run: impl Fn(&F, &J) -> JR + Sync,
+    finish: impl Fn(&F, Vec<JR>) -> Result<R> + Sync,
+) -> Vec<Result<R>> {
+    let threads = threads.or_else(|| std::thread::available_parallelism().ok()).map_or(1, NonZeroUsize::get);
+    let state: Mutex<BatchState<J, JR, R>> = Mutex::new(BatchState {
+        next_file: 0,
+        planning: 0,
+        in_flight: vec![],
+        results: files.iter().map(|_| None).collect(),
+    });
+    let changed = Condvar::new();
+
+    // Stops the other workers waiting forever on a plan which panicked
+    struct PlanningGuard<'a, J, JR, R>(&'a Mutex<BatchState<J, JR, R>>, &'a Condvar);
+    impl<J, JR, R> Drop for PlanningGuard<'_, J, JR, R> {
+        fn drop(&mut self) {
+            let mut state = self.0.lock().unwrap_or_else(|e| e.into_inner());
+            state.planning -= 1;
+            self.1.notify_all();
+        }
+    }
+
+    let worker = || {
+        let mut own_file = None;
+        loop {
+            let task = {
+                let mut state = state.lock().unwrap();
+                loop {
+                    let waiting = |file: &FileInFlight<J, JR>| file.next_job < file.jobs.len();
+                    let file = state
+                        .in_flight
+                        .iter_mut()
+                        .filter(|file| waiting(file))
+                        .min_by_key(|file| (Some(file.file_idx) != own_file, file.rank));
+                    if let Some(file) = file {
+                        file.next_job += 1;
+                        break Some(BatchTask::Job { file_idx: file.file_idx, jobs: file.jobs.clone(), job_idx: file.next_job - 1 });
+                    }
+                    // Files not started yet are cancelled, while the ones in flight finish
+                    if shutdown::requested() {
+                        while let Some(&file_idx) = order.get(state.next_file) {
+                            state.results[file_idx] = Some(Err(Error::Interrupted));
+                            state.next_file += 1;
+                        }
+                    }
+                    if let Some(&file_idx) = order.get(state.next_file) {
+                        let rank = state.next_file;
+                        state.next_file += 1;
+                        state.planning += 1;
+                        break Some(BatchTask::Plan { file_idx, rank });
+                    }
+                    if state.planning == 0 {
+                        break None;
+                    }
+                    state = changed.wait(state).unwrap();
+                }
+            };
+            match task {
+                None => return,
+                Some(BatchTask::Plan { file_idx, rank }) => {
+                    own_file = Some(file_idx);
+                    let guard = PlanningGuard(&state, &changed);
+                    let planned = plan(&files[file_idx]);
+                    let finished = match planned {
+                        Ok(jobs) if jobs.is_empty() => Some(finish(&files[file_idx], vec![])),
+                        Ok(jobs) => {
+                            let mut state = state.lock().unwrap();
+                            state.in_flight.push(FileInFlight {
+                                file_idx,
+                                rank,
+                                results: jobs.iter().map(|_| None).collect(),
+                                remaining: jobs.len(),
+                                jobs: Arc::new(jobs),
+                                next_job: 0,
+                            });
+                            // Files are planned out of order when planning takes different times
+                            state.in_flight.sort_by_key(|file| file.rank);
+                            None
+                        }
+                        Err(e) => Some(Err(e)),
+                    };
+                    if let Some(result) = finished {
+                        state.lock().unwrap().results[file_idx] = Some(result);
+                    }
+                    drop(guard);
+                }
+                Some(BatchTask::Job { file_idx, jobs, job_idx }) => {
+                    let result = run(&files[file_idx], &jobs[job_idx]);
+                    drop(jobs);
+                    let mut locked = state.lock().unwrap();
+                    let pos = locked.in_flight.iter().position(|file| file.file_idx == file_idx).expect("files stay in flight until their jobs finish");
+                    let file = &mut locked.in_flight[pos];
+                    file.results[job_idx] = Some(result);
+                    file.remaining -= 1;
+                    if file.remaining == 0 {
+                        let file = locked.in_flight.remove(pos);
+                        drop(locked);
+                        let results = file.results.into_iter().map(|result| result.expect("every job has finished")).collect();
+                        let result = finish(&files[file_idx], results);
+                        state.lock().unwrap().results[file_idx] = Some(result);
+                    }
+                }
+            }
+        }
+    };
+
+    std::thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(worker);
+        }
+    });
+
+    state
+        .into_inner()
+        .unwrap()
+        .results
+        .into_iter()
+        .map(|result| result.expect("every file is in the run order exactly once"))
+        .collect()
+}
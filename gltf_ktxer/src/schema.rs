@@ -0,0 +1,97 @@
+//! Validation against the glTF 2.0 JSON Schema, as a stricter complement to [crate::validate].
+//!
+//! The schemas in `src/schema/` are transcribed from the glTF 2.0 specification
+//! (https://github.com/KhronosGroup/glTF/tree/main/specification/2.0/schema),
+//! and refer to each other by relative file name exactly like the originals.
+
+use std::fmt::Display;
+
+use jsonschema::Resource;
+
+use crate::{gltf::GltfDoc, Error, Result};
+
+/// An arbitrary absolute base which the schemas' relative `$id`s and `$ref`s are resolved against.
+const SCHEMA_BASE_URI: &str = "https://gltf-ktxer.invalid/schema/2.0/";
+
+const SCHEMAS: &[(&str, &str)] = &[
+    ("accessor.schema.json", include_str!("schema/accessor.schema.json")),
+    ("accessor.sparse.indices.schema.json", include_str!("schema/accessor.sparse.indices.schema.json")),
+    ("accessor.sparse.schema.json", include_str!("schema/accessor.sparse.schema.json")),
+    ("accessor.sparse.values.schema.json", include_str!("schema/accessor.sparse.values.schema.json")),
+    ("animation.channel.schema.json", include_str!("schema/animation.channel.schema.json")),
+    ("animation.channel.target.schema.json", include_str!("schema/animation.channel.target.schema.json")),
+    ("animation.sampler.schema.json", include_str!("schema/animation.sampler.schema.json")),
+    ("animation.schema.json", include_str!("schema/animation.schema.json")),
+    ("asset.schema.json", include_str!("schema/asset.schema.json")),
+    ("buffer.schema.json", include_str!("schema/buffer.schema.json")),
+    ("bufferView.schema.json", include_str!("schema/bufferView.schema.json")),
+    ("camera.orthographic.schema.json", include_str!("schema/camera.orthographic.schema.json")),
+    ("camera.perspective.schema.json", include_str!("schema/camera.perspective.schema.json")),
+    ("camera.schema.json", include_str!("schema/camera.schema.json")),
+    ("extension.schema.json", include_str!("schema/extension.schema.json")),
+    ("extras.schema.json", include_str!("schema/extras.schema.json")),
+    ("glTF.schema.json", include_str!("schema/glTF.schema.json")),
+    ("glTFChildOfRootProperty.schema.json", include_str!("schema/glTFChildOfRootProperty.schema.json")),
+    ("glTFProperty.schema.json", include_str!("schema/glTFProperty.schema.json")),
+    ("glTFid.schema.json", include_str!("schema/glTFid.schema.json")),
+    ("image.schema.json", include_str!("schema/image.schema.json")),
+    ("material.normalTextureInfo.schema.json", include_str!("schema/material.normalTextureInfo.schema.json")),
+    ("material.occlusionTextureInfo.schema.json", include_str!("schema/material.occlusionTextureInfo.schema.json")),
+    ("material.pbrMetallicRoughness.schema.json", include_str!("schema/material.pbrMetallicRoughness.schema.json")),
+    ("material.schema.json", include_str!("schema/material.schema.json")),
+    ("mesh.primitive.schema.json", include_str!("schema/mesh.primitive.schema.json")),
+    ("mesh.schema.json", include_str!("schema/mesh.schema.json")),
+    ("node.schema.json", include_str!("schema/node.schema.json")),
+    ("sampler.schema.json", include_str!("schema/sampler.schema.json")),
+    ("scene.schema.json", include_str!("schema/scene.schema.json")),
+    ("skin.schema.json", include_str!("schema/skin.schema.json")),
+    ("texture.schema.json", include_str!("schema/texture.schema.json")),
+    ("textureInfo.schema.json", include_str!("schema/textureInfo.schema.json")),
+];
+
+/// A single place where the document doesn't match the schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaViolation {
+    /// RFC 6901 JSON pointer to the offending value, e.g. `/images/7/bufferView`.
+    pub json_pointer: String,
+    pub message: String,
+}
+impl Display for SchemaViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let pointer = if self.json_pointer.is_empty() { "/" } else { &self.json_pointer };
+        write!(f, "{pointer}: {}", self.message)
+    }
+}
+
+/// Validate `doc` against the top-level glTF schema, returning [Error::SchemaViolations] listing every violation.
+pub fn validate_schema(doc: &GltfDoc) -> Result<()> {
+    let violations = schema_violations(doc);
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::SchemaViolations(violations))
+    }
+}
+
+pub fn schema_violations(doc: &GltfDoc) -> Vec<SchemaViolation> {
+    let mut options = jsonschema::options();
+    let mut root = None;
+    for (name, contents) in SCHEMAS {
+        let mut schema: serde_json::Value = serde_json::from_str(contents).expect("embedded schemas are valid JSON");
+        schema["$id"] = format!("{SCHEMA_BASE_URI}{name}").into();
+        if *name == "glTF.schema.json" {
+            root = Some(schema.clone());
+        }
+        let resource = Resource::from_contents(schema).expect("embedded schemas are valid resources");
+        options = options.with_resource(format!("{SCHEMA_BASE_URI}{name}"), resource);
+    }
+    let validator = options
+        .build(&root.expect("glTF.schema.json is embedded"))
+        .expect("embedded schemas compile");
+
+    let instance = serde_json::Value::Object(doc.clone());
+    validator
+        .iter_errors(&instance)
+        .map(|e| SchemaViolation { json_pointer: e.instance_path.as_str().to_string(), message: e.to_string() })
+        .collect()
+}
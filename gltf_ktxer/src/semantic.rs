@@ -0,0 +1,222 @@
+//! What each texture holds, worked out from the material slots referencing it, and the encode settings that suit it.
+//!
+//! Slots are matched by a [SlotRegistry] of rules, which covers the core glTF material slots and common lightmap conventions:
+//! an `occlusionTexture` sampled with the second UV set, or Mozilla Hubs' `MOZ_lightmap` extension.
+//! Textures no material uses are matched by [NameRule]s on their names instead, e.g. `wall_normal.png` is a normal map.
+//! Tools with their own conventions can add rules, and change the settings used for each [Semantic].
+
+use std::{collections::{BTreeMap, BTreeSet, HashMap, HashSet}, num::NonZeroU8};
+
+use serde_json::Value;
+
+use crate::{
+    edit, filenames,
+    gltf::{deserialize_list, GltfDoc, GltfImage, GltfIndex, GltfList, GltfTexture, GltfTextureInfo},
+    groups::glob_match,
+    ktx2::ColorSpace,
+    KtxCodec, Result,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Semantic {
+    BaseColor,
+    MetallicRoughness,
+    Normal,
+    Occlusion,
+    Emissive,
+    /// Baked lighting, usually sampled with a second UV set.
+    Lightmap,
+    /// Referenced from a slot no rule matches, e.g. in an unknown material extension.
+    Other,
+}
+
+impl std::str::FromStr for Semantic {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "base-color" => Ok(Semantic::BaseColor),
+            "metallic-roughness" => Ok(Semantic::MetallicRoughness),
+            "normal" => Ok(Semantic::Normal),
+            "occlusion" => Ok(Semantic::Occlusion),
+            "emissive" => Ok(Semantic::Emissive),
+            "lightmap" => Ok(Semantic::Lightmap),
+            "other" => Ok(Semantic::Other),
+            _ => Err(format!(
+                "unknown semantic '{s}', expected 'base-color', 'metallic-roughness', 'normal', 'occlusion', 'emissive', 'lightmap' or 'other'"
+            )),
+        }
+    }
+}
+impl std::fmt::Display for Semantic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Semantic::BaseColor => "base-color",
+            Semantic::MetallicRoughness => "metallic-roughness",
+            Semantic::Normal => "normal",
+            Semantic::Occlusion => "occlusion",
+            Semantic::Emissive => "emissive",
+            Semantic::Lightmap => "lightmap",
+            Semantic::Other => "other",
+        })
+    }
+}
+
+/// Encode settings for the textures with a given [Semantic].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SemanticSettings {
+    pub color_space: ColorSpace,
+    pub codec: KtxCodec,
+    /// See [crate::Params::ktx_basis_compression_quality].
+    pub quality: Option<NonZeroU8>,
+    /// Allow rate-distortion optimization, trading quality for smaller supercompressed files.
+    pub rdo: bool,
+}
+impl SemanticSettings {
+    /// The settings used for `semantic` unless a [SlotRegistry] overrides them.
+    pub fn default_for(semantic: Semantic) -> Self {
+        let color = Self { color_space: ColorSpace::Srgb, codec: KtxCodec::Etc1s, quality: None, rdo: true };
+        let data = Self { color_space: ColorSpace::Linear, ..color };
+        match semantic {
+            Semantic::BaseColor | Semantic::Emissive => color,
+            Semantic::MetallicRoughness | Semantic::Occlusion | Semantic::Other => data,
+            // Normals and baked lighting show compression artifacts badly
+            Semantic::Normal => Self { codec: KtxCodec::Uastc, rdo: false, ..data },
+            Semantic::Lightmap => Self { codec: KtxCodec::Uastc, quality: NonZeroU8::new(255), rdo: false, ..data },
+        }
+    }
+}
+
+/// Classifies the textureInfo at `path` within a material (e.g. `["pbrMetallicRoughness", "baseColorTexture"]`) as `semantic`,
+/// if it samples the texture coordinate set `tex_coord` or `tex_coord` is None.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlotRule {
+    pub path: Vec<String>,
+    pub tex_coord: Option<u64>,
+    pub semantic: Semantic,
+}
+impl SlotRule {
+    pub fn new(path: &str, semantic: Semantic) -> Self {
+        Self { path: path.split('/').map(str::to_string).collect(), tex_coord: None, semantic }
+    }
+    pub fn with_tex_coord(self, tex_coord: u64) -> Self {
+        Self { tex_coord: Some(tex_coord), ..self }
+    }
+}
+
+/// Classifies a texture no material uses as `semantic` if its name matches `pattern`, ignoring case,
+/// where `*` matches any run of characters and `?` any one character.
+/// The names tried are the texture's name, then the name of each of its images or their file name without the extension.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameRule {
+    pub pattern: String,
+    pub semantic: Semantic,
+}
+impl NameRule {
+    pub fn new(pattern: &str, semantic: Semantic) -> Self {
+        Self { pattern: pattern.to_string(), semantic }
+    }
+}
+
+/// An ordered list of [SlotRule]s, where the first rule matching a slot wins, [NameRule]s likewise, and the settings for each [Semantic].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlotRegistry {
+    rules: Vec<SlotRule>,
+    name_rules: Vec<NameRule>,
+    settings: BTreeMap<Semantic, SemanticSettings>,
+}
+impl Default for SlotRegistry {
+    fn default() -> Self {
+        Self {
+            rules: vec![
+                SlotRule::new("occlusionTexture", Semantic::Lightmap).with_tex_coord(1),
+                SlotRule::new("extensions/MOZ_lightmap", Semantic::Lightmap),
+                SlotRule::new("pbrMetallicRoughness/baseColorTexture", Semantic::BaseColor),
+                SlotRule::new("pbrMetallicRoughness/metallicRoughnessTexture", Semantic::MetallicRoughness),
+                SlotRule::new("normalTexture", Semantic::Normal),
+                SlotRule::new("occlusionTexture", Semantic::Occlusion),
+                SlotRule::new("emissiveTexture", Semantic::Emissive),
+            ],
+            name_rules: [
+                (&["*_n", "*_nrm", "*_normal", "*_normalmap"][..], Semantic::Normal),
+                // Occlusion, roughness and metalness packed together are sampled like a metallicRoughnessTexture
+                (&["*_orm", "*_arm", "*_metallicroughness", "*_roughness", "*_metallic", "*_metalness"], Semantic::MetallicRoughness),
+                (&["*_albedo", "*_basecolor", "*_base_color", "*_diffuse", "*_color", "*_col"], Semantic::BaseColor),
+                (&["*_ao", "*_occlusion"], Semantic::Occlusion),
+                (&["*_emissive", "*_emission"], Semantic::Emissive),
+                (&["*_lightmap"], Semantic::Lightmap),
+            ]
+            .into_iter()
+            .flat_map(|(patterns, semantic)| patterns.iter().map(move |pattern| NameRule::new(pattern, semantic)))
+            .collect(),
+            settings: BTreeMap::new(),
+        }
+    }
+}
+impl SlotRegistry {
+    /// Add a rule which takes precedence over every existing rule.
+    pub fn add_rule(&mut self, rule: SlotRule) {
+        self.rules.insert(0, rule);
+    }
+    /// Add a name rule which takes precedence over every existing name rule.
+    pub fn add_name_rule(&mut self, rule: NameRule) {
+        self.name_rules.insert(0, rule);
+    }
+    pub fn set_settings(&mut self, semantic: Semantic, settings: SemanticSettings) {
+        self.settings.insert(semantic, settings);
+    }
+    pub fn settings(&self, semantic: Semantic) -> SemanticSettings {
+        self.settings.get(&semantic).copied().unwrap_or_else(|| SemanticSettings::default_for(semantic))
+    }
+
+    /// The semantic of the first [NameRule] matching any of `names`.
+    pub fn semantic_for_names<'a>(&self, names: impl IntoIterator<Item = &'a str>) -> Option<Semantic> {
+        let names: Vec<String> = names.into_iter().map(str::to_lowercase).collect();
+        self.name_rules
+            .iter()
+            .find(|rule| names.iter().any(|name| glob_match(&rule.pattern.to_lowercase(), name)))
+            .map(|rule| rule.semantic)
+    }
+
+    /// The semantics each texture is used with across every material.
+    /// Textures referenced from a slot no rule matches are [Semantic::Other]. Unreferenced textures get the semantic their name suggests,
+    /// see [NameRule], and are left out if no name rule matches.
+    pub fn texture_semantics(&self, doc: &GltfDoc) -> Result<HashMap<GltfIndex<GltfTexture>, BTreeSet<Semantic>>> {
+        let mut semantics: HashMap<GltfIndex<GltfTexture>, BTreeSet<Semantic>> = HashMap::new();
+        let mut matched = HashSet::new();
+        let materials = doc.get("materials").and_then(Value::as_array).map_or(&[][..], Vec::as_slice);
+        for (material_idx, material) in materials.iter().enumerate() {
+            for rule in &self.rules {
+                let pointer = format!("/{}", rule.path.join("/"));
+                let Some(info) = material.pointer(&pointer).filter(|info| info.get("index").is_some()) else {
+                    continue;
+                };
+                let info: GltfTextureInfo = serde::Deserialize::deserialize(info)?;
+                let slot = format!("/materials/{material_idx}{pointer}");
+                if rule.tex_coord.is_none_or(|tex_coord| tex_coord == info.effective_tex_coord()) && matched.insert(slot) {
+                    semantics.entry(info.index).or_default().insert(rule.semantic);
+                }
+            }
+        }
+        for (slot, info) in edit::texture_infos(doc)? {
+            if !matched.contains(&slot) {
+                semantics.entry(info.index).or_default().insert(Semantic::Other);
+            }
+        }
+
+        let textures: Vec<GltfTexture> = deserialize_list(doc, "textures")?;
+        let images: Vec<GltfImage> = deserialize_list(doc, "images")?;
+        for (tex_idx, texture) in textures.iter().enumerate() {
+            if semantics.contains_key(&GltfIndex::of(tex_idx)) {
+                continue;
+            }
+            let sources = [texture.source].into_iter().chain(edit::TEXTURE_SOURCE_EXTENSIONS.iter().filter_map(|ext| edit::texture_extension_source(texture, ext)));
+            let image_names = sources.filter_map(|img| images.gltf_index(img, "images").ok().flatten()).filter_map(filenames::image_stem);
+            let names: Vec<String> = texture.name.clone().into_iter().chain(image_names).collect();
+            if let Some(semantic) = self.semantic_for_names(names.iter().map(String::as_str)) {
+                semantics.insert(GltfIndex::of(tex_idx), BTreeSet::from([semantic]));
+            }
+        }
+        Ok(semantics)
+    }
+}
@@ -0,0 +1,57 @@
+//! Stopping cleanly on Ctrl+C or a termination request, instead of dying partway through writing a file.
+//!
+//! [install_handlers] makes SIGINT and SIGTERM set a flag rather than kill the process, and long-running work checks it with [check]
+//! between steps: [crate::schedule::run_batch] stops starting new files but lets the ones in flight finish,
+//! and [crate::output::write_atomic] refuses to start new writes. Everything left fails with [Error::Interrupted].
+//! A second signal exits straight away, for when finishing the in-flight work takes too long.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::{Error, Result};
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// The exit status after a second signal, as shells report for processes killed by SIGINT.
+pub const FORCED_EXIT_CODE: i32 = 130;
+
+/// Ask running work to stop, as a signal does once [install_handlers] has run.
+pub fn request() {
+    REQUESTED.store(true, Ordering::SeqCst);
+}
+
+/// Whether work has been asked to stop.
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Fail with [Error::Interrupted] if work has been asked to stop.
+pub fn check() -> Result<()> {
+    if requested() {
+        Err(Error::Interrupted)
+    } else {
+        Ok(())
+    }
+}
+
+/// Forget an earlier request, e.g. once a long-lived host has cleaned up after it.
+pub fn clear() {
+    REQUESTED.store(false, Ordering::SeqCst);
+}
+
+/// Handle SIGINT and SIGTERM by calling [request], or exiting with [FORCED_EXIT_CODE] if that was already done.
+/// Elsewhere than Unix this does nothing, so signals still end the process straight away.
+pub fn install_handlers() {
+    #[cfg(unix)]
+    {
+        extern "C" fn handle(_signal: libc::c_int) {
+            // Only async-signal-safe calls are allowed here, which an atomic swap and _exit are
+            if REQUESTED.swap(true, Ordering::SeqCst) {
+                unsafe { libc::_exit(FORCED_EXIT_CODE) };
+            }
+        }
+        for signal in [libc::SIGINT, libc::SIGTERM] {
+            // SAFETY: `handle` has the signature signal() expects, and only does async-signal-safe work
+            unsafe { libc::signal(signal, handle as extern "C" fn(libc::c_int) as libc::sighandler_t) };
+        }
+    }
+}
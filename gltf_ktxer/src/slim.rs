@@ -0,0 +1,200 @@
+//! Shrinking the JSON of an output without changing what it describes, for GLBs whose JSON chunk is a large share of their size.
+//!
+//! [slim_json] can remove `extras`, properties set to their default value in the glTF 2.0 specification,
+//! empty arrays and the names of objects. Everything but `extras` is left alone inside `extensions`,
+//! as this crate can't know what an empty array or a missing property means to every extension.
+
+use serde_json::{json, Map, Value};
+
+use crate::{edit, glb::{json_bytes, JsonFormat}, gltf::GltfDoc, groups::glob_match, Result};
+
+/// What [slim_json] removes, see [crate::Params::slim_json].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlimOptions {
+    /// Remove `extras` everywhere, apart from keys matching [SlimOptions::keep_extras].
+    pub strip_extras: bool,
+    /// Patterns for `extras` keys to keep, where `*` matches any run of characters, e.g. `GLTF_KTXER_*`.
+    pub keep_extras: Vec<String>,
+    /// Remove properties which are set to their default value, like a node's identity `rotation`.
+    pub drop_defaults: bool,
+    /// Remove empty arrays, and empty `extensions` and `extras` objects.
+    pub remove_empty: bool,
+    /// Remove the `name` of every object. Off by default, as applications often look nodes and materials up by name.
+    pub strip_names: bool,
+}
+impl Default for SlimOptions {
+    fn default() -> Self {
+        Self { strip_extras: true, keep_extras: vec![], drop_defaults: true, remove_empty: true, strip_names: false }
+    }
+}
+
+/// What [slim_json] removed, and the size of the minified JSON before and after.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SlimStats {
+    pub json_bytes_before: usize,
+    pub json_bytes_after: usize,
+    /// `extras` keys removed, counting `extras` which aren't objects as one.
+    pub extras_removed: usize,
+    pub defaults_removed: usize,
+    pub empty_removed: usize,
+    pub names_removed: usize,
+}
+
+/// Remove what `options` asks for from `doc`.
+pub fn slim_json(doc: &mut GltfDoc, options: &SlimOptions) -> Result<SlimStats> {
+    let mut stats = SlimStats { json_bytes_before: json_bytes(doc, JsonFormat::Minified)?.len(), ..SlimStats::default() };
+    if options.strip_names {
+        for &list_name in edit::GLTF_LISTS {
+            for item in doc.get_mut(list_name).and_then(Value::as_array_mut).into_iter().flatten() {
+                stats.names_removed += item.as_object_mut().and_then(|item| item.remove("name")).is_some() as usize;
+            }
+        }
+    }
+    if options.strip_extras {
+        for value in doc.values_mut() {
+            stats.extras_removed += strip_extras(value, &options.keep_extras);
+        }
+        stats.extras_removed += strip_extras_of(doc, &options.keep_extras);
+    }
+    if options.drop_defaults {
+        stats.defaults_removed = drop_defaults(doc);
+    }
+    if options.remove_empty {
+        stats.empty_removed = remove_empty(doc);
+    }
+    stats.json_bytes_after = json_bytes(doc, JsonFormat::Minified)?.len();
+    Ok(stats)
+}
+
+/// Strip the `extras` of `value` and everything inside it, returning how many were removed.
+fn strip_extras(value: &mut Value, keep: &[String]) -> usize {
+    match value {
+        Value::Object(obj) => strip_extras_of(obj, keep) + obj.values_mut().map(|child| strip_extras(child, keep)).sum::<usize>(),
+        Value::Array(arr) => arr.iter_mut().map(|child| strip_extras(child, keep)).sum(),
+        _ => 0,
+    }
+}
+fn strip_extras_of(obj: &mut Map<String, Value>, keep: &[String]) -> usize {
+    match obj.get_mut("extras") {
+        Some(Value::Object(extras)) => {
+            let before = extras.len();
+            extras.retain(|key, _| keep.iter().any(|pattern| glob_match(pattern, key)));
+            let removed = before - extras.len();
+            if extras.is_empty() {
+                obj.remove("extras");
+            }
+            removed
+        }
+        Some(_) => {
+            obj.remove("extras");
+            1
+        }
+        None => 0,
+    }
+}
+
+/// Whether `value` is `default`, comparing numbers by value so `1` and `1.0` are the same.
+fn is_default(value: &Value, default: &Value) -> bool {
+    match (value, default) {
+        (Value::Number(value), Value::Number(default)) => value.as_f64() == default.as_f64(),
+        (Value::Array(value), Value::Array(default)) => value.len() == default.len() && value.iter().zip(default).all(|(value, default)| is_default(value, default)),
+        _ => value == default,
+    }
+}
+
+/// Remove each of `defaults` from `obj` if it has its default value, returning how many were removed.
+fn remove_defaults(obj: &mut Map<String, Value>, defaults: &[(&str, Value)]) -> usize {
+    let mut removed = 0;
+    for (key, default) in defaults {
+        if obj.get(*key).is_some_and(|value| is_default(value, default)) {
+            obj.remove(*key);
+            removed += 1;
+        }
+    }
+    removed
+}
+
+/// Every object in the top-level list `list_name`.
+fn items<'a>(doc: &'a mut GltfDoc, list_name: &str) -> impl Iterator<Item = &'a mut Map<String, Value>> {
+    doc.get_mut(list_name).and_then(Value::as_array_mut).into_iter().flatten().filter_map(Value::as_object_mut)
+}
+
+/// Every object in the array `key` of `obj`.
+fn children<'a>(obj: &'a mut Map<String, Value>, key: &str) -> impl Iterator<Item = &'a mut Map<String, Value>> {
+    obj.get_mut(key).and_then(Value::as_array_mut).into_iter().flatten().filter_map(Value::as_object_mut)
+}
+
+/// Remove the core properties which are set to their default value, returning how many were removed.
+fn drop_defaults(doc: &mut GltfDoc) -> usize {
+    let mut removed = 0;
+    for accessor in items(doc, "accessors") {
+        removed += remove_defaults(accessor, &[("byteOffset", json!(0)), ("normalized", json!(false))]);
+        if let Some(sparse) = accessor.get_mut("sparse").and_then(Value::as_object_mut) {
+            for key in ["indices", "values"] {
+                if let Some(part) = sparse.get_mut(key).and_then(Value::as_object_mut) {
+                    removed += remove_defaults(part, &[("byteOffset", json!(0))]);
+                }
+            }
+        }
+    }
+    for view in items(doc, "bufferViews") {
+        removed += remove_defaults(view, &[("byteOffset", json!(0))]);
+    }
+    for sampler in items(doc, "samplers") {
+        removed += remove_defaults(sampler, &[("wrapS", json!(10497)), ("wrapT", json!(10497))]);
+    }
+    for material in items(doc, "materials") {
+        removed += remove_defaults(material, &[("alphaMode", json!("OPAQUE")), ("alphaCutoff", json!(0.5)), ("doubleSided", json!(false)), ("emissiveFactor", json!([0, 0, 0]))]);
+        if let Some(pbr) = material.get_mut("pbrMetallicRoughness").and_then(Value::as_object_mut) {
+            removed += remove_defaults(pbr, &[("baseColorFactor", json!([1, 1, 1, 1])), ("metallicFactor", json!(1)), ("roughnessFactor", json!(1))]);
+        }
+        for (key, defaults) in [("normalTexture", [("scale", json!(1))]), ("occlusionTexture", [("strength", json!(1))])] {
+            if let Some(info) = material.get_mut(key).and_then(Value::as_object_mut) {
+                removed += remove_defaults(info, &defaults);
+            }
+        }
+    }
+    // Texture infos in extensions have the same default
+    for material in doc.get_mut("materials").and_then(Value::as_array_mut).into_iter().flatten() {
+        edit::for_each_texture_info(material, &mut |info| removed += remove_defaults(info, &[("texCoord", json!(0))]));
+    }
+    for mesh in items(doc, "meshes") {
+        for primitive in children(mesh, "primitives") {
+            removed += remove_defaults(primitive, &[("mode", json!(4))]);
+        }
+    }
+    for node in items(doc, "nodes") {
+        removed += remove_defaults(node, &[
+            ("matrix", json!([1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1])),
+            ("rotation", json!([0, 0, 0, 1])),
+            ("scale", json!([1, 1, 1])),
+            ("translation", json!([0, 0, 0])),
+        ]);
+    }
+    for animation in items(doc, "animations") {
+        for sampler in children(animation, "samplers") {
+            removed += remove_defaults(sampler, &[("interpolation", json!("LINEAR"))]);
+        }
+    }
+    removed
+}
+
+/// Remove empty arrays and empty `extensions` and `extras` objects, along with `pbrMetallicRoughness` if dropping defaults emptied it,
+/// returning how many were removed. Doesn't look inside `extensions` or `extras`.
+fn remove_empty(obj: &mut Map<String, Value>) -> usize {
+    let mut removed = 0;
+    for (_, value) in obj.iter_mut().filter(|(key, _)| !matches!(key.as_str(), "extensions" | "extras")) {
+        match value {
+            Value::Object(child) => removed += remove_empty(child),
+            Value::Array(arr) => removed += arr.iter_mut().filter_map(Value::as_object_mut).map(remove_empty).sum::<usize>(),
+            _ => {}
+        }
+    }
+    let before = obj.len();
+    obj.retain(|key, value| match value {
+        Value::Array(arr) => !arr.is_empty(),
+        Value::Object(child) if matches!(key.as_str(), "extensions" | "extras" | "pbrMetallicRoughness") => !child.is_empty(),
+        _ => true,
+    });
+    removed + before - obj.len()
+}
@@ -0,0 +1,117 @@
+//! Statistics of decoded source images, for [crate::decision] hooks, choosing codecs, and reports.
+//!
+//! Set [crate::Params::image_stats] to compute them for every source image while planning, in [crate::ReencodeJobs::image_stats].
+
+use image::RgbaImage;
+use serde_derive::Serialize;
+
+/// Texels whose alpha is at least this count towards [ImageStats::alpha_coverage], the usual alpha test cutoff.
+const ALPHA_COVERAGE_CUTOFF: u8 = 128;
+/// Bits in the bitmap [ImageStats::unique_colors] is estimated with.
+/// Estimates stay within a few percent up to several times this many colors.
+const UNIQUE_COLOR_BITS: usize = 1 << 20;
+
+/// Summary statistics of a decoded image.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ImageStats {
+    pub width: u32,
+    pub height: u32,
+    /// The channels which carry information: 1 for grayscale, 2 for grayscale with alpha, 3 for color, 4 for color with alpha.
+    pub channels: u8,
+    /// Whether any texel isn't fully opaque.
+    pub has_alpha: bool,
+    /// Whether every texel has equal red, green and blue.
+    pub is_grayscale: bool,
+    /// The fraction of texels which pass an alpha test at 0.5.
+    pub alpha_coverage: f32,
+    /// An estimate of the number of distinct RGBA colors.
+    pub unique_colors: u64,
+    /// The Shannon entropy of the luma histogram, from 0 to 8 bits.
+    pub entropy: f32,
+    /// The mean absolute difference in luma between neighbouring texels, from 0 to 1. Low for smooth images, high for noisy ones.
+    pub average_gradient: f32,
+    /// The mean of each channel, from 0 to 1.
+    pub mean: [f32; 4],
+}
+impl ImageStats {
+    pub fn of(image: &RgbaImage) -> Self {
+        let (width, height) = image.dimensions();
+        let mut sums = [0u64; 4];
+        let (mut has_alpha, mut is_grayscale) = (false, true);
+        let mut covered = 0u64;
+        let mut histogram = [0u64; 256];
+        let mut color_bitmap = vec![0u64; UNIQUE_COLOR_BITS / 64];
+        for texel in image.pixels() {
+            let [r, g, b, a] = texel.0;
+            has_alpha |= a < 255;
+            is_grayscale &= r == g && g == b;
+            covered += (a >= ALPHA_COVERAGE_CUTOFF) as u64;
+            histogram[luma(texel.0) as usize] += 1;
+            let bit = color_hash(u32::from_le_bytes(texel.0)) as usize % UNIQUE_COLOR_BITS;
+            color_bitmap[bit / 64] |= 1 << (bit % 64);
+            for (sum, value) in sums.iter_mut().zip(texel.0) {
+                *sum += value as u64;
+            }
+        }
+        let count = (width as u64 * height as u64).max(1) as f64;
+
+        let entropy = histogram
+            .iter()
+            .filter(|&&n| n > 0)
+            .map(|&n| {
+                let p = n as f64 / count;
+                -p * p.log2()
+            })
+            .sum::<f64>();
+
+        // Linear counting: the expected number of empty bits falls exponentially with the number of distinct colors
+        let set_bits = color_bitmap.iter().map(|word| word.count_ones() as u64).sum::<u64>();
+        let unique_colors = match (set_bits, UNIQUE_COLOR_BITS as u64 - set_bits) {
+            (0, _) => 0,
+            (_, 0) => count as u64,
+            (_, empty_bits) => {
+                let bits = UNIQUE_COLOR_BITS as f64;
+                ((-bits * (empty_bits as f64 / bits).ln()).round() as u64).min(count as u64)
+            }
+        };
+
+        let (mut gradient_sum, mut gradient_count) = (0u64, 0u64);
+        for (x, y, texel) in image.enumerate_pixels() {
+            let here = luma(texel.0) as i32;
+            for neighbour in [(x + 1 < width).then(|| image.get_pixel(x + 1, y)), (y + 1 < height).then(|| image.get_pixel(x, y + 1))].into_iter().flatten() {
+                gradient_sum += here.abs_diff(luma(neighbour.0) as i32) as u64;
+                gradient_count += 1;
+            }
+        }
+        let average_gradient = match gradient_count {
+            0 => 0.0,
+            n => (gradient_sum as f64 / n as f64 / 255.0) as f32,
+        };
+
+        Self {
+            width,
+            height,
+            channels: if is_grayscale { 1 } else { 3 } + has_alpha as u8,
+            has_alpha,
+            is_grayscale,
+            alpha_coverage: (covered as f64 / count) as f32,
+            unique_colors,
+            entropy: entropy as f32,
+            average_gradient,
+            mean: sums.map(|sum| (sum as f64 / count / 255.0) as f32),
+        }
+    }
+}
+
+/// Rec. 601 luma, on the encoded values.
+fn luma([r, g, b, _]: [u8; 4]) -> u8 {
+    ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114 + 500) / 1000) as u8
+}
+
+/// Spread colors evenly over the bitmap. Nearby colors would otherwise land in nearby bits.
+fn color_hash(color: u32) -> u32 {
+    let mut h = color.wrapping_mul(0x9e37_79b9);
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x85eb_ca6b);
+    h ^ (h >> 13)
+}
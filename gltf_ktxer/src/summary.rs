@@ -0,0 +1,56 @@
+//! The table summarizing a conversion which the command-line tool prints when it finishes, from its [QualityReport].
+
+use crate::quality::QualityReport;
+
+const HEADINGS: [&str; 6] = ["texture", "codec", "in-size", "out-size", "ratio", "time"];
+
+/// An aligned table of each texture's codec, source and output sizes, the output size as a percentage of the source's,
+/// and how long it took, with a row of totals. Unknown values are shown as `-`, and left out of the totals.
+pub fn render_summary(report: &QualityReport) -> String {
+    let bytes = |bytes: Option<u64>| bytes.map_or("-".to_string(), |bytes| bytes.to_string());
+    let ratio = |source_bytes: Option<u64>, bytes: u64| source_bytes.filter(|&source| source > 0).map_or("-".to_string(), |source| format!("{:.1}%", bytes as f64 * 100.0 / source as f64));
+    let time = |seconds: Option<f64>| match seconds {
+        Some(seconds) if seconds < 1.0 => format!("{:.0} ms", seconds * 1000.0),
+        Some(seconds) => format!("{seconds:.2} s"),
+        None => "-".to_string(),
+    };
+
+    let mut rows: Vec<[String; 6]> = vec![HEADINGS.map(str::to_string)];
+    for texture in &report.textures {
+        rows.push([
+            texture.name.clone(),
+            texture.codec.clone().unwrap_or_else(|| "-".to_string()),
+            bytes(texture.source_bytes),
+            bytes(Some(texture.bytes)),
+            ratio(texture.source_bytes, texture.bytes),
+            time(texture.seconds),
+        ]);
+    }
+    let total_source = report.textures.iter().filter_map(|texture| texture.source_bytes).reduce(|a, b| a + b);
+    // Only textures with a known source size count towards the total ratio, so it compares like with like
+    let total_bytes_with_source: u64 = report.textures.iter().filter(|texture| texture.source_bytes.is_some()).map(|texture| texture.bytes).sum();
+    let total_seconds = report.textures.iter().filter_map(|texture| texture.seconds).reduce(|a, b| a + b);
+    rows.push([
+        "total".to_string(),
+        String::new(),
+        bytes(total_source),
+        bytes(Some(report.textures.iter().map(|texture| texture.bytes).sum())),
+        ratio(total_source, total_bytes_with_source),
+        time(total_seconds),
+    ]);
+
+    let widths: Vec<usize> = (0..HEADINGS.len()).map(|column| rows.iter().map(|row| row[column].chars().count()).max().unwrap_or(0)).collect();
+    let mut out = String::new();
+    for row in &rows {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .enumerate()
+            // Names and codecs read left to right, numbers line up on the right
+            .map(|(column, (cell, &width))| if column < 2 { format!("{cell:<width$}") } else { format!("{cell:>width$}") })
+            .collect();
+        out.push_str(cells.join("  ").trim_end());
+        out.push('\n');
+    }
+    out
+}
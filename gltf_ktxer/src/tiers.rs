@@ -0,0 +1,70 @@
+//! Lower resolution copies of each KTX2 texture, for runtimes which stream textures in as they're needed.
+//!
+//! With [crate::Params::resolution_tiers] set to `n`, every KTX2 image gets `n - 1` extra tiers, each half the size of the one before,
+//! so 3 gives full, half and quarter resolution. The full resolution image stays the texture's source, and the tiers are listed in its extras:
+//! ```json
+//! "extensions": { "KHR_texture_basisu": { "source": 1 } },
+//! "extras": { "GLTF_KTXER_tiers": [{ "source": 3, "scale": 0.5 }, { "source": 4, "scale": 0.25 }] }
+//! ```
+//! Tiers stop early once the image is down to 1 texel across.
+
+use std::collections::HashMap;
+
+use serde_json::{json, Value};
+
+use crate::{edit, gltf::{GltfIndex, GltfTexture}, ImageReencodeJob};
+
+/// The key in a texture's `extras` listing its lower resolution tiers.
+pub const TIERS_EXTRAS_KEY: &str = "GLTF_KTXER_tiers";
+
+/// Add a job for each lower resolution tier of the KTX2 image each texture uses, and list them in the texture's extras.
+/// `textures` must already point at `jobs`, as returned in [crate::ReencodeJobs::new_textures].
+/// Textures sharing an image share its tiers. Images whose dimensions can't be read from their header get no tiers,
+/// and neither do textures whose `extras` isn't an object.
+pub fn add_resolution_tiers(textures: &mut [GltfTexture], jobs: &mut Vec<ImageReencodeJob>, tiers: u32) {
+    let mut tier_images: HashMap<usize, Vec<(usize, f64)>> = HashMap::new();
+    for tex in textures.iter_mut() {
+        let Some(img_idx) = edit::texture_ktx_source(tex).map(|img| img.raw_idx()).filter(|&idx| idx < jobs.len()) else {
+            continue;
+        };
+        if !tex.extras.as_ref().is_none_or(Value::is_object) {
+            continue;
+        }
+        let tiers = tier_images.entry(img_idx).or_insert_with(|| {
+            let full = &jobs[img_idx];
+            let Some(dimensions) = full.source_dimensions() else {
+                return vec![];
+            };
+            let (width, height) = full.fit_dimensions(dimensions);
+            let new_jobs: Vec<_> = (1..tiers.min(u32::BITS))
+                .map_while(|tier| {
+                    let max_dimension = width.max(height) >> tier;
+                    (max_dimension > 0).then(|| ImageReencodeJob {
+                        source: full.source.clone(),
+                        data_used_as_srgb: full.data_used_as_srgb,
+                        reencode_as: full.reencode_as,
+                        max_dimension: Some(max_dimension),
+                        repeats: full.repeats,
+                        adjustments: full.adjustments,
+                        transforms: full.transforms.clone(),
+                        preexisting_buffer_view_idx: GltfIndex::UNDEFINED,
+                    })
+                })
+                .collect();
+            new_jobs
+                .into_iter()
+                .enumerate()
+                .map(|(tier, job)| {
+                    jobs.push(job);
+                    (jobs.len() - 1, 0.5f64.powi(tier as i32 + 1))
+                })
+                .collect()
+        });
+        if tiers.is_empty() {
+            continue;
+        }
+        let listed: Vec<Value> = tiers.iter().map(|&(source, scale)| json!({ "source": source, "scale": scale })).collect();
+        let extras = tex.extras.get_or_insert_with(|| json!({}));
+        extras.as_object_mut().expect("checked above").insert(TIERS_EXTRAS_KEY.to_string(), Value::Array(listed));
+    }
+}
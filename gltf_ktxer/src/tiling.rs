@@ -0,0 +1,80 @@
+//! Downscaling textures sampled with repeat wrapping without breaking their tiling.
+//!
+//! [ktx2::fit_within] resamples with a Lanczos filter which clamps at the image's edges, so the left and right edges
+//! (or top and bottom) of a downscaled seamless texture no longer match, and a seam shows wherever it repeats.
+//! Halving with [mipmap::downsample_half] doesn't have that problem: its box footprints exactly divide the image,
+//! so no footprint crosses an edge and the result tiles as the source did.
+//!
+//! With [crate::Params::preserve_tiling], [mark_repeating_jobs] restricts jobs used by repeating textures to halving,
+//! which may leave them smaller than [crate::ImageReencodeJob::max_dimension] allows.
+
+use std::collections::HashSet;
+
+use image::RgbaImage;
+
+use crate::{
+    edit,
+    gltf::{GltfList, GltfSampler, GltfTexture},
+    ktx2, mipmap,
+    validate::Warning,
+    ImageReencodeFormat, ImageReencodeJob, Result,
+};
+
+/// The size [halve_within] scales an image of the given `(width, height)` to: halved, rounding down, until it fits within `max_dimension`.
+pub fn halved_dimensions((mut width, mut height): (u32, u32), max_dimension: u32) -> (u32, u32) {
+    while width.max(height) > max_dimension.max(1) {
+        (width, height) = ((width / 2).max(1), (height / 2).max(1));
+    }
+    (width, height)
+}
+
+/// Halve `image` until it fits within `max_dimension` in both width and height, without filtering across the tiles of
+/// `grid` if it's an atlas. Images which already fit are returned unchanged.
+pub fn halve_within(image: &RgbaImage, max_dimension: u32, grid: mipmap::TileGrid) -> RgbaImage {
+    let mut image = image.clone();
+    while image.width().max(image.height()) > max_dimension.max(1) {
+        image = mipmap::downsample_half_tiled(&image, grid);
+    }
+    image
+}
+
+/// Set [ImageReencodeJob::repeats] on every job which is decoded and used by a texture whose sampler repeats in either direction.
+/// `textures` must already point at `jobs`, as returned in [crate::ReencodeJobs::new_textures].
+///
+/// Returns a `tiling_downscale_restricted` warning for each job which ends up smaller than its size limit allows,
+/// located at the first repeating texture using it.
+pub fn mark_repeating_jobs(textures: &[GltfTexture], samplers: &Vec<GltfSampler>, jobs: &mut [ImageReencodeJob]) -> Result<Vec<Warning>> {
+    let mut warnings = vec![];
+    let mut marked = HashSet::new();
+    let default_sampler = GltfSampler::default();
+    let job_count = jobs.len();
+    for (tex_idx, tex) in textures.iter().enumerate() {
+        let sampler = samplers.gltf_index(tex.sampler, "samplers").map_err(|e| e.at(format!("/textures/{tex_idx}")))?.unwrap_or(&default_sampler);
+        if !sampler.repeats() {
+            continue;
+        }
+        let used_images = std::iter::once(tex.source).chain(edit::TEXTURE_SOURCE_EXTENSIONS.iter().filter_map(|ext_name| edit::texture_extension_source(tex, ext_name)));
+        for img_idx in used_images.filter(|img| img.is_defined()).map(|img| img.raw_idx()).filter(|&idx| idx < job_count) {
+            let job = &mut jobs[img_idx];
+            if job.reencode_as == ImageReencodeFormat::Copy || !marked.insert(img_idx) {
+                continue;
+            }
+            job.repeats = true;
+            let (Some(max_dimension), Some(dimensions)) = (job.max_dimension, job.source_dimensions()) else {
+                continue;
+            };
+            let (halved, fitted) = (halved_dimensions(dimensions, max_dimension), ktx2::fit_dimensions(dimensions, max_dimension));
+            if halved != fitted {
+                warnings.push(Warning {
+                    code: "tiling_downscale_restricted",
+                    json_pointer: format!("/textures/{tex_idx}"),
+                    message: format!(
+                        "texture repeats, so image {img_idx} is halved to {}x{} rather than resized to {}x{}, which would break its tiling",
+                        halved.0, halved.1, fitted.0, fitted.1
+                    ),
+                });
+            }
+        }
+    }
+    Ok(warnings)
+}
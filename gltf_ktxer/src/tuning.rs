@@ -0,0 +1,124 @@
+//! Choosing the encoder effort and thread count from the machine running the conversion,
+//! so default runs finish quickly on a laptop and use every core of a build server.
+//!
+//! Hosts call [Tuning::auto] on [HostCapabilities::detect] at startup, log the [Tuning], and [Tuning::apply] it to their [Params].
+//! Settings the host already chose, e.g. from command-line flags, are left alone.
+//!
+//! [Tuning::threads] is the total for the run. Converting one file, every thread encodes images of that file.
+//! Converting several, hosts pass it to [crate::schedule::run_batch], which splits it between the files and the images within them
+//! as they come, rather than fixing a number of files and of threads per file up front.
+
+use std::{fmt::Display, num::NonZeroUsize, str::FromStr};
+
+use crate::Params;
+
+/// How hard the encoder works to find a good encoding. More effort makes smaller or better-looking textures, slower.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum EncodeEffort {
+    Fast,
+    Balanced,
+    Thorough,
+}
+impl EncodeEffort {
+    /// One step less effort, or the same if there's no less.
+    pub fn lower(self) -> Self {
+        match self {
+            EncodeEffort::Thorough => EncodeEffort::Balanced,
+            EncodeEffort::Balanced | EncodeEffort::Fast => EncodeEffort::Fast,
+        }
+    }
+}
+impl FromStr for EncodeEffort {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "fast" => Ok(EncodeEffort::Fast),
+            "balanced" => Ok(EncodeEffort::Balanced),
+            "thorough" => Ok(EncodeEffort::Thorough),
+            _ => Err(format!("unknown encode effort '{s}', expected 'fast', 'balanced' or 'thorough'")),
+        }
+    }
+}
+impl Display for EncodeEffort {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            EncodeEffort::Fast => "fast",
+            EncodeEffort::Balanced => "balanced",
+            EncodeEffort::Thorough => "thorough",
+        })
+    }
+}
+
+/// What the machine offers the encoder.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostCapabilities {
+    /// The number of threads which can run at once.
+    pub cores: NonZeroUsize,
+    /// The SIMD instruction sets the CPU supports, e.g. `avx2`, in the order they're checked.
+    /// Only logged: the encoder has no vectorized paths, so they don't change the [Tuning].
+    pub simd: Vec<&'static str>,
+}
+impl HostCapabilities {
+    /// The capabilities of the machine this is running on.
+    pub fn detect() -> Self {
+        let cores = std::thread::available_parallelism().unwrap_or(NonZeroUsize::MIN);
+        let mut simd = vec![];
+        #[cfg(target_arch = "x86_64")]
+        {
+            if std::arch::is_x86_feature_detected!("sse4.1") {
+                simd.push("sse4.1");
+            }
+            if std::arch::is_x86_feature_detected!("avx2") {
+                simd.push("avx2");
+            }
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            if std::arch::is_aarch64_feature_detected!("neon") {
+                simd.push("neon");
+            }
+        }
+        Self { cores, simd }
+    }
+}
+
+/// The encoder effort and thread count chosen for a host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tuning {
+    pub effort: EncodeEffort,
+    /// The number of images to encode at once across the run, see [Params::max_threads] and the [module docs](self).
+    pub threads: NonZeroUsize,
+}
+impl Tuning {
+    /// Pick settings for `host`: one image per core, with more effort the more cores there are to spread it over.
+    pub fn auto(host: &HostCapabilities) -> Self {
+        let effort = match host.cores.get() {
+            ..=4 => EncodeEffort::Fast,
+            5..=16 => EncodeEffort::Balanced,
+            _ => EncodeEffort::Thorough,
+        };
+        Self { effort, threads: host.cores }
+    }
+
+    /// Fill in [Params::encode_effort] and [Params::max_threads] if they aren't already set,
+    /// returning the settings the params end up with.
+    pub fn apply(self, params: &mut Params) -> Self {
+        let effort = *params.encode_effort.get_or_insert(self.effort);
+        let threads = *params.max_threads.get_or_insert(self.threads);
+        Self { effort, threads }
+    }
+}
+impl Display for Tuning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let threads = if self.threads.get() == 1 { "thread" } else { "threads" };
+        write!(f, "{} effort, {} {threads}", self.effort, self.threads)
+    }
+}
+
+/// A line describing `host` and the settings chosen for it, for hosts to log at startup.
+pub fn describe(host: &HostCapabilities, tuning: &Tuning) -> String {
+    let simd = if host.simd.is_empty() { "no SIMD".to_string() } else { host.simd.join(", ") };
+    let cores = if host.cores.get() == 1 { "core" } else { "cores" };
+    format!("{} {cores}, {simd}: {tuning}", host.cores)
+}
@@ -0,0 +1,40 @@
+//! How materials sample each texture: the texture coordinate set and the sampler's wrap modes.
+//!
+//! Atlas mipmapping and [crate::pipeline::ImageTransform]s change an image for every texture using it, which is only safe if they all
+//! sample it the same way. When textures sharing such an image are sampled differently, planning gives each way its own job,
+//! and warns about single textures sampled several ways, which can't be split without rewriting their materials.
+
+use std::collections::{BTreeSet, HashMap};
+
+use crate::{
+    edit,
+    gltf::{deserialize_list, GltfDoc, GltfIndex, GltfList, GltfSampler, GltfTexture},
+    Result,
+};
+
+/// One way a material samples a texture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TextureUsage {
+    /// The texture coordinate set, after any `KHR_texture_transform` override.
+    pub tex_coord: u64,
+    pub wrap_s: u32,
+    pub wrap_t: u32,
+}
+
+/// The ways each texture is sampled across every material. Textures no material uses are left out.
+pub fn texture_usages(doc: &GltfDoc) -> Result<HashMap<GltfIndex<GltfTexture>, BTreeSet<TextureUsage>>> {
+    let textures: Vec<GltfTexture> = deserialize_list(doc, "textures")?;
+    let samplers: Vec<GltfSampler> = deserialize_list(doc, "samplers")?;
+    let mut usages: HashMap<GltfIndex<GltfTexture>, BTreeSet<TextureUsage>> = HashMap::new();
+    for (pointer, info) in edit::texture_infos(doc)? {
+        let texture = textures.gltf_index_required(info.index, "textures").map_err(|e| e.at(format!("{pointer}/index")))?;
+        let sampler = samplers.gltf_index(texture.sampler, "samplers")?;
+        let wrap = |wrap: fn(&GltfSampler) -> Option<u32>| sampler.and_then(wrap).unwrap_or(GltfSampler::WRAP_REPEAT);
+        usages.entry(info.index).or_default().insert(TextureUsage {
+            tex_coord: info.effective_tex_coord(),
+            wrap_s: wrap(|sampler| sampler.wrap_s),
+            wrap_t: wrap(|sampler| sampler.wrap_t),
+        });
+    }
+    Ok(usages)
+}
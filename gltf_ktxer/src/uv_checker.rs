@@ -0,0 +1,45 @@
+//! A debug [ImageTransform] replacing textures with a UV checker at the same resolution, to check that UV mapping survives conversion and atlasing.
+//!
+//! Each checker cell is tinted by its position: red increases with U and green with V, so flipped or rotated UVs are easy to spot.
+//! As a transform, the checker goes through the rest of the pipeline like the texture it replaces, including downscaling and KTX2 encoding.
+
+use image::RgbaImage;
+
+use crate::{decision::TextureContext, pipeline::ImageTransform, semantic::Semantic};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UvChecker {
+    /// Checker cells across each side of the image.
+    pub cells: u32,
+    pub semantics: Vec<Semantic>,
+}
+impl Default for UvChecker {
+    fn default() -> Self {
+        Self { cells: 8, semantics: vec![Semantic::BaseColor] }
+    }
+}
+impl ImageTransform for UvChecker {
+    fn key(&self) -> String {
+        format!("uv_checker;cells={}", self.cells)
+    }
+    fn applies_to(&self, context: &TextureContext) -> bool {
+        self.semantics.iter().any(|semantic| context.semantics.contains(semantic))
+    }
+    /// Replace the color channels of `image`. Alpha is kept, so cutouts stay cut out.
+    fn apply(&self, image: &mut RgbaImage, _srgb: bool) -> std::result::Result<(), String> {
+        if self.cells == 0 {
+            return Err("a UV checker needs at least one cell".to_string());
+        }
+        let (width, height) = image.dimensions();
+        for (x, y, texel) in image.enumerate_pixels_mut() {
+            // The cell at each UV is the same at any resolution
+            let (u, v) = (x as u64 * self.cells as u64 / width as u64, y as u64 * self.cells as u64 / height as u64);
+            let tint = |cell: u64| (64 + cell * 191 / (self.cells as u64 - 1).max(1)) as u8;
+            let [r, g, b] = [tint(u), tint(v), 160];
+            let dark = (u + v) % 2 == 1;
+            let shade = |value: u8| if dark { value / 3 } else { value };
+            texel.0 = [shade(r), shade(g), shade(b), texel.0[3]];
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+
+use crate::{edit::{self, texture_extension_source, TEXTURE_SOURCE_EXTENSIONS}, gltf::{deserialize_list, GltfBuffer, GltfBufferView, GltfDoc, GltfImage, GltfList, GltfMesh, GltfTexture, GltfTextureInfo, U8VecOrSlice}, etc1s, ktx2, variants, Error, Result};
+
+/// Check the structural invariants the rest of the crate relies on:
+/// every buffer has enough data, every buffer view fits in its buffer,
+/// every image has exactly one source, every texture refers to images that exist,
+/// every mesh primitive refers to accessors that exist, and every `KHR_materials_variants` mapping refers to a material
+/// and variants that exist.
+///
+/// `binaries` follows the same convention as [crate::Input::binaries].
+pub fn validate(doc: &GltfDoc, binaries: &HashMap<Option<String>, Vec<u8>>) -> Result<()> {
+    let buffers: Vec<GltfBuffer> = get_list(doc, "buffers")?;
+    let buffer_views: Vec<GltfBufferView> = get_list(doc, "bufferViews")?;
+    let images: Vec<GltfImage> = get_list(doc, "images")?;
+    let textures: Vec<GltfTexture> = get_list(doc, "textures")?;
+    let meshes: Vec<GltfMesh> = get_list(doc, "meshes")?;
+    let accessor_count = doc.get("accessors").and_then(|val| val.as_array()).map_or(0, Vec::len);
+
+    let buffer_datas = dump_buffers(&buffers, binaries)?;
+    for (idx, view) in buffer_views.iter().enumerate() {
+        view.slice_from(&buffer_datas).map_err(|e| e.at(format!("/bufferViews/{idx}")))?;
+    }
+    for (mesh_idx, mesh) in meshes.iter().enumerate() {
+        for (primitive_idx, primitive) in mesh.primitives.iter().enumerate() {
+            for accessor in primitive.accessors() {
+                accessor
+                    .idx_within("accessors", accessor_count)
+                    .map_err(|e| e.at(format!("/meshes/{mesh_idx}/primitives/{primitive_idx}")))?;
+            }
+        }
+    }
+    variants::material_variants(doc)?;
+    for (idx, image) in images.iter().enumerate() {
+        image.dump_data(&buffer_views, &buffer_datas, binaries).map_err(|e| e.at(image_source_pointer(idx, image)))?;
+    }
+    for (idx, texture) in textures.iter().enumerate() {
+        images.gltf_index(texture.source, "images").map_err(|e| e.at(format!("/textures/{idx}/source")))?;
+        for ext in TEXTURE_SOURCE_EXTENSIONS {
+            if let Some(ext_source) = texture_extension_source(texture, ext) {
+                images
+                    .gltf_index_required(ext_source, "images")
+                    .map_err(|e| e.at(format!("/textures/{idx}/extensions/{ext}/source")))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Check that every buffer view starts 4-byte aligned, as this crate lays out the views of its outputs, see `pack_buffer_views`.
+/// glTF itself only requires accessors to be aligned, so inputs needn't pass this.
+pub fn check_output_alignment(doc: &GltfDoc) -> Result<()> {
+    let buffer_views: Vec<GltfBufferView> = get_list(doc, "bufferViews")?;
+    for (idx, view) in buffer_views.iter().enumerate() {
+        if view.byte_offset % 4 != 0 {
+            let e = Error::BufferViewMisaligned { buffer_view: idx, byte_offset: view.byte_offset };
+            return Err(e.at(format!("/bufferViews/{idx}/byteOffset")));
+        }
+    }
+    Ok(())
+}
+
+/// Check that every image in the document can be decoded:
+/// non-KTX2 images must be readable by the `image` crate, and KTX2 images must parse, with every level of
+/// BasisLZ/ETC1S textures decoding with [etc1s::decode] and the base level of uncompressed RGBA8 ones being complete.
+/// Other KTX2 formats, such as UASTC, are only parsed.
+pub fn check_images_decode(doc: &GltfDoc, binaries: &HashMap<Option<String>, Vec<u8>>) -> Result<()> {
+    let buffers: Vec<GltfBuffer> = get_list(doc, "buffers")?;
+    let buffer_views: Vec<GltfBufferView> = get_list(doc, "bufferViews")?;
+    let images: Vec<GltfImage> = get_list(doc, "images")?;
+
+    let buffer_datas = dump_buffers(&buffers, binaries)?;
+    for (idx, image) in images.iter().enumerate() {
+        let data = image.dump_data(&buffer_views, &buffer_datas, binaries).map_err(|e| e.at(image_source_pointer(idx, image)))?;
+        if image.mime_type.as_deref() == Some("image/ktx2") {
+            if !data.starts_with(&ktx2::KTX2_IDENTIFIER) {
+                return Err(Error::ImageClaimedKtx2ButWasNot.at(format!("/images/{idx}")));
+            }
+            decode_ktx2(&data).map_err(|e| e.at(format!("/images/{idx}")))?;
+        } else {
+            image::load_from_memory(&data).map_err(|e| Error::from(e).at(format!("/images/{idx}")))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Decode what [check_images_decode] decodes of the KTX2 file `data`.
+fn decode_ktx2(data: &[u8]) -> Result<()> {
+    let ktx = ktx2::Ktx2Texture::from_bytes(data)?;
+    if ktx.supercompression_scheme == ktx2::SUPERCOMPRESSION_BASIS_LZ {
+        for level in 0..ktx.levels.len() {
+            etc1s::decode(&ktx, level)?;
+        }
+    } else {
+        ktx.level0_rgba8()?;
+    }
+    Ok(())
+}
+
+/// Something in the document which is valid, but probably not intended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    /// A stable identifier for the kind of warning, like [crate::ErrorCode].
+    pub code: &'static str,
+    pub json_pointer: String,
+    pub message: String,
+}
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// Find things in the document which don't stop it being converted, but which are likely mistakes:
+/// images which no texture uses, and texture extensions missing from `extensionsUsed`.
+pub fn lint(doc: &GltfDoc) -> Result<Vec<Warning>> {
+    let images: Vec<GltfImage> = get_list(doc, "images")?;
+    let textures: Vec<GltfTexture> = get_list(doc, "textures")?;
+    let extensions_used: Vec<&str> = doc
+        .get("extensionsUsed")
+        .and_then(|val| val.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|ext| ext.as_str())
+        .collect();
+
+    let mut warnings = vec![];
+    let mut used_images = vec![false; images.len()];
+    for (idx, texture) in textures.iter().enumerate() {
+        let sources = TEXTURE_SOURCE_EXTENSIONS.iter().filter_map(|ext| texture_extension_source(texture, ext));
+        for source in std::iter::once(texture.source).chain(sources) {
+            if let Some(used) = used_images.get_mut(source.raw_idx()) {
+                *used = true;
+            }
+        }
+        for ext in texture.extensions.iter().flat_map(|exts| exts.keys()) {
+            if !extensions_used.contains(&ext.as_str()) {
+                warnings.push(Warning {
+                    code: "undeclared_extension",
+                    json_pointer: format!("/textures/{idx}/extensions/{ext}"),
+                    message: format!("texture {idx} uses {ext}, which isn't listed in extensionsUsed"),
+                });
+            }
+        }
+    }
+    for view in edit::image_views_with_target(doc)? {
+        let idx = view.raw_idx();
+        warnings.push(Warning {
+            code: "image_view_target",
+            json_pointer: format!("/bufferViews/{idx}/target"),
+            message: format!("buffer view {idx} holds image data but sets a GPU target"),
+        });
+    }
+    for (idx, _) in used_images.iter().enumerate().filter(|(_, used)| !**used) {
+        warnings.push(Warning {
+            code: "unused_image",
+            json_pointer: format!("/images/{idx}"),
+            message: format!("image {idx} isn't used by any texture"),
+        });
+    }
+    Ok(warnings)
+}
+
+/// Check every textureInfo which is still in `after` samples the same texture coordinate set as it did in `before`,
+/// including KHR_texture_transform's `texCoord` override.
+/// Material rewrites are free to drop or add textureInfos, but must never silently move one to another UV set.
+pub fn check_tex_coords_preserved(before: &GltfDoc, after: &GltfDoc) -> Result<()> {
+    let before: HashMap<String, GltfTextureInfo> = edit::texture_infos(before)?.into_iter().collect();
+    for (pointer, info) in edit::texture_infos(after)? {
+        let Some(original) = before.get(&pointer) else {
+            continue;
+        };
+        if info.tex_coord() != original.tex_coord() {
+            let e = Error::TexCoordChanged { before: original.tex_coord(), after: info.tex_coord() };
+            return Err(e.at(format!("{pointer}/texCoord")));
+        }
+        if info.effective_tex_coord() != original.effective_tex_coord() {
+            let e = Error::TexCoordChanged { before: original.effective_tex_coord(), after: info.effective_tex_coord() };
+            return Err(e.at(format!("{pointer}/extensions/KHR_texture_transform/texCoord")));
+        }
+    }
+    Ok(())
+}
+
+fn get_list<T: serde::de::DeserializeOwned>(doc: &GltfDoc, name: &'static str) -> Result<Vec<T>> {
+    match doc.get(name) {
+        Some(value) if !value.is_array() => Err(Error::ExpectedList { key: name }.at(format!("/{name}"))),
+        _ => deserialize_list(doc, name),
+    }
+}
+
+fn dump_buffers<'a>(buffers: &[GltfBuffer], binaries: &'a HashMap<Option<String>, Vec<u8>>) -> Result<Vec<U8VecOrSlice<'a>>> {
+    buffers
+        .iter()
+        .enumerate()
+        .map(|(idx, b)| b.dump_data(idx, binaries).map_err(|e| e.at(format!("/buffers/{idx}"))))
+        .collect()
+}
+
+/// The property an image's data comes from, for locating errors loading it.
+fn image_source_pointer(idx: usize, image: &GltfImage) -> String {
+    let field = if image.buffer_view.is_defined() { "bufferView" } else { "uri" };
+    format!("/images/{idx}/{field}")
+}
@@ -0,0 +1,90 @@
+//! `KHR_materials_variants`, which lets each mesh primitive switch between materials by variant, e.g. the colorways of a shoe.
+//!
+//! Variant materials are ordinary entries of `materials`, referred to from each primitive's `mappings` rather than its `material`.
+//! So anything scanning `materials`, like [crate::semantic::SlotRegistry::texture_semantics], sees their textures,
+//! and [crate::gc] keeps them through the mappings registered in [crate::edit::ReferenceRegistry].
+//! [material_variants] reads the mappings, to check them and to describe which material belongs to which variant.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde_derive::Deserialize;
+use serde_json::Value;
+
+use crate::{gltf::{GltfDoc, GltfIndex}, Error, Result};
+
+pub const KHR_MATERIALS_VARIANTS: &str = "KHR_materials_variants";
+
+#[derive(Deserialize)]
+struct RootExtension {
+    variants: Vec<Variant>,
+}
+
+#[derive(Deserialize)]
+struct Variant {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct PrimitiveExtension {
+    mappings: Vec<Mapping>,
+}
+
+#[derive(Deserialize)]
+struct Mapping {
+    material: GltfIndex<Value>,
+    variants: Vec<GltfIndex<Variant>>,
+}
+
+/// The document's variants, and the materials primitives switch to for them.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct MaterialVariants {
+    /// The name of each variant, in order. Empty if the document doesn't use the extension.
+    pub names: Vec<String>,
+    /// The variants each material is mapped to, by index into [MaterialVariants::names].
+    /// Materials no mapping uses are left out, even if primitives use them as their default.
+    pub materials: BTreeMap<usize, BTreeSet<usize>>,
+}
+impl MaterialVariants {
+    /// The names of the variants `material` is mapped to, in variant order.
+    pub fn names_of(&self, material: usize) -> Vec<&str> {
+        self.materials.get(&material).into_iter().flatten().map(|&variant| self.names[variant].as_str()).collect()
+    }
+}
+
+/// Read the document's variants and every primitive's mappings, failing if a mapping refers to a material or variant which doesn't exist.
+pub fn material_variants(doc: &GltfDoc) -> Result<MaterialVariants> {
+    let names = match doc.get("extensions").and_then(|extensions| extensions.get(KHR_MATERIALS_VARIANTS)) {
+        Some(extension) => {
+            let extension: RootExtension =
+                serde::Deserialize::deserialize(extension).map_err(|e| Error::from(e).at(format!("/extensions/{KHR_MATERIALS_VARIANTS}")))?;
+            extension.variants.into_iter().map(|variant| variant.name).collect()
+        }
+        None => vec![],
+    };
+    let material_count = doc.get("materials").and_then(Value::as_array).map_or(0, Vec::len);
+    let mut materials: BTreeMap<usize, BTreeSet<usize>> = BTreeMap::new();
+    let meshes = doc.get("meshes").and_then(Value::as_array).map_or(&[][..], Vec::as_slice);
+    for (mesh_idx, mesh) in meshes.iter().enumerate() {
+        let primitives = mesh.get("primitives").and_then(Value::as_array).map_or(&[][..], Vec::as_slice);
+        for (primitive_idx, primitive) in primitives.iter().enumerate() {
+            let Some(extension) = primitive.get("extensions").and_then(|extensions| extensions.get(KHR_MATERIALS_VARIANTS)) else {
+                continue;
+            };
+            let pointer = format!("/meshes/{mesh_idx}/primitives/{primitive_idx}/extensions/{KHR_MATERIALS_VARIANTS}");
+            let extension: PrimitiveExtension = serde::Deserialize::deserialize(extension).map_err(|e| Error::from(e).at(pointer.clone()))?;
+            for (mapping_idx, mapping) in extension.mappings.iter().enumerate() {
+                let at = |key: &str| format!("{pointer}/mappings/{mapping_idx}/{key}");
+                let Some(material) = mapping.material.idx_within("materials", material_count).map_err(|e| e.at(at("material")))? else {
+                    return Err(Error::IdxNotSet { list_name: "materials" }.at(at("material")));
+                };
+                let variants = materials.entry(material).or_default();
+                for (idx, variant) in mapping.variants.iter().enumerate() {
+                    if let Some(variant) = variant.idx_within("variants", names.len()).map_err(|e| e.at(at(&format!("variants/{idx}"))))? {
+                        variants.insert(variant);
+                    }
+                }
+            }
+        }
+    }
+    Ok(MaterialVariants { names, materials })
+}
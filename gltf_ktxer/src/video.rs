@@ -0,0 +1,35 @@
+//! Detecting video textures, which are left as they are rather than decoded as images.
+//!
+//! A texture is a video texture if it has one of the [VIDEO_TEXTURE_EXTENSIONS], or if any image it uses is a video,
+//! going by its `mimeType` or the container signature at the start of its data.
+//! Every image such a texture uses is copied into the output unchanged, with a `video_texture_skipped` warning.
+
+use crate::gltf::GltfTexture;
+
+/// Texture extensions for video sources. Those with a `source` property point at an image, which holds the video.
+pub const VIDEO_TEXTURE_EXTENSIONS: &[&str] = &[
+    "EXT_texture_video",
+    "KHR_texture_video",
+    "MPEG_texture_video",
+];
+
+/// The first of the [VIDEO_TEXTURE_EXTENSIONS] on `texture`, if any.
+pub fn video_extension(texture: &GltfTexture) -> Option<&'static str> {
+    let extensions = texture.extensions.as_ref()?;
+    VIDEO_TEXTURE_EXTENSIONS.iter().copied().find(|&name| extensions.contains_key(name))
+}
+
+/// Whether an image with the given `mime_type` and encoded `data` is a video. Only reads the container signature.
+pub fn is_video(mime_type: Option<&str>, data: &[u8]) -> bool {
+    if mime_type.is_some_and(|mime_type| mime_type.starts_with("video/")) {
+        return true;
+    }
+    // ISO base media files (MP4, QuickTime) start with an `ftyp` box, whose major brand says what they hold.
+    // AVIF and HEIF images use the same container, so only video brands count
+    const VIDEO_BRANDS: &[&[u8; 4]] = &[b"isom", b"iso2", b"iso4", b"iso5", b"iso6", b"mp41", b"mp42", b"avc1", b"M4V ", b"qt  ", b"dash", b"3gp4", b"3gp5", b"3g2a"];
+    if data.get(4..8) == Some(b"ftyp") {
+        return data.get(8..12).is_some_and(|brand| VIDEO_BRANDS.iter().any(|video| brand == *video));
+    }
+    // Matroska and WebM start with an EBML header, and Ogg with its page signature
+    data.starts_with(&[0x1a, 0x45, 0xdf, 0xa3]) || data.starts_with(b"OggS")
+}
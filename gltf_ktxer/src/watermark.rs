@@ -0,0 +1,214 @@
+//! A built-in [ImageTransform] stamping a small image or text, like a build ID, onto textures, to tell review builds apart.
+//!
+//! ```no_run
+//! # use std::sync::Arc;
+//! # use gltf_ktxer::{watermark::{Position, Watermark}, Params};
+//! let params = Params {
+//!     image_transforms: vec![Arc::new(Watermark::text("build 1234", 2).with_position(Position::TopLeft).with_opacity(0.75))],
+//!     ..Params::default()
+//! };
+//! ```
+
+use std::{fmt::Display, str::FromStr};
+
+use image::{Rgba, RgbaImage};
+
+use crate::{decision::TextureContext, hash, pipeline::ImageTransform, semantic::Semantic};
+
+/// Where the overlay goes on each image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Position {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+    Center,
+}
+impl FromStr for Position {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "top-left" => Ok(Position::TopLeft),
+            "top-right" => Ok(Position::TopRight),
+            "bottom-left" => Ok(Position::BottomLeft),
+            "bottom-right" => Ok(Position::BottomRight),
+            "center" => Ok(Position::Center),
+            _ => Err(format!("unknown position '{s}', expected 'top-left', 'top-right', 'bottom-left', 'bottom-right' or 'center'")),
+        }
+    }
+}
+impl Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Position::TopLeft => "top-left",
+            Position::TopRight => "top-right",
+            Position::BottomLeft => "bottom-left",
+            Position::BottomRight => "bottom-right",
+            Position::Center => "center",
+        })
+    }
+}
+
+/// Composites an overlay onto the images of textures used in any of [Watermark::semantics], base color textures by default.
+/// Images smaller than the overlay show as much of it as fits.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Watermark {
+    overlay: RgbaImage,
+    pub position: Position,
+    /// How strongly the overlay shows, from 0 (not at all) to 1 (as opaque as the overlay itself).
+    pub opacity: f32,
+    /// Pixels between the overlay and the nearest edges of the image.
+    pub margin: u32,
+    pub semantics: Vec<Semantic>,
+}
+impl Watermark {
+    pub fn image(overlay: RgbaImage) -> Self {
+        Self { overlay, position: Position::default(), opacity: 1.0, margin: 4, semantics: vec![Semantic::BaseColor] }
+    }
+    /// White text on a black background, in a built-in 3x5 pixel font enlarged `scale` times.
+    /// Letters are shown in upper case, and characters the font doesn't have are shown as boxes.
+    pub fn text(text: &str, scale: u32) -> Self {
+        Self::image(render_text(text, scale.max(1)))
+    }
+    pub fn with_position(self, position: Position) -> Self {
+        Self { position, ..self }
+    }
+    pub fn with_opacity(self, opacity: f32) -> Self {
+        Self { opacity, ..self }
+    }
+    pub fn with_margin(self, margin: u32) -> Self {
+        Self { margin, ..self }
+    }
+    pub fn overlay(&self) -> &RgbaImage {
+        &self.overlay
+    }
+
+    /// The top-left corner of the overlay on an image of the given size. Overlays larger than the image start at its top left.
+    fn origin(&self, (width, height): (u32, u32)) -> (u32, u32) {
+        let (overlay_width, overlay_height) = self.overlay.dimensions();
+        let (room_x, room_y) = (width.saturating_sub(overlay_width), height.saturating_sub(overlay_height));
+        let x = match self.position {
+            Position::TopLeft | Position::BottomLeft => self.margin.min(room_x),
+            Position::TopRight | Position::BottomRight => room_x.saturating_sub(self.margin),
+            Position::Center => room_x / 2,
+        };
+        let y = match self.position {
+            Position::TopLeft | Position::TopRight => self.margin.min(room_y),
+            Position::BottomLeft | Position::BottomRight => room_y.saturating_sub(self.margin),
+            Position::Center => room_y / 2,
+        };
+        (x, y)
+    }
+}
+impl ImageTransform for Watermark {
+    fn key(&self) -> String {
+        let overlay = [&self.overlay.width().to_le_bytes()[..], &self.overlay.height().to_le_bytes(), self.overlay.as_raw()].concat();
+        format!(
+            "watermark;position={};opacity={};margin={};overlay=sha256:{}",
+            self.position,
+            self.opacity,
+            self.margin,
+            hash::hex(&hash::sha256(&overlay))
+        )
+    }
+    fn applies_to(&self, context: &TextureContext) -> bool {
+        self.semantics.iter().any(|semantic| context.semantics.contains(semantic))
+    }
+    fn apply(&self, image: &mut RgbaImage, _srgb: bool) -> std::result::Result<(), String> {
+        if !(0.0..=1.0).contains(&self.opacity) {
+            return Err(format!("opacity must be between 0 and 1, not {}", self.opacity));
+        }
+        let (origin_x, origin_y) = self.origin(image.dimensions());
+        for (x, y, over) in self.overlay.enumerate_pixels() {
+            let Some(under) = image.get_pixel_mut_checked(origin_x + x, origin_y + y) else {
+                continue;
+            };
+            // Blended on the stored values, which is close enough for a marker
+            let alpha = over.0[3] as f32 / 255.0 * self.opacity;
+            for channel in 0..3 {
+                under.0[channel] = (under.0[channel] as f32 * (1.0 - alpha) + over.0[channel] as f32 * alpha).round() as u8;
+            }
+            under.0[3] = (under.0[3] as f32 + (255.0 - under.0[3] as f32) * alpha).round() as u8;
+        }
+        Ok(())
+    }
+}
+
+const GLYPH_WIDTH: u32 = 3;
+const GLYPH_HEIGHT: u32 = 5;
+
+/// Each row of a glyph, top first, with the leftmost pixel in the highest of 3 bits.
+fn glyph(c: char) -> [u8; GLYPH_HEIGHT as usize] {
+    match c.to_ascii_uppercase() {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '#' => [0b101, 0b111, 0b101, 0b111, 0b101],
+        ' ' => [0b000; 5],
+        _ => [0b111; 5],
+    }
+}
+
+/// `text` in white on black, with a pixel of black between glyphs and around the edges.
+fn render_text(text: &str, scale: u32) -> RgbaImage {
+    let cell = GLYPH_WIDTH + 1;
+    let chars: Vec<char> = text.chars().collect();
+    let width = (chars.len() as u32 * cell + 1) * scale;
+    let height = (GLYPH_HEIGHT + 2) * scale;
+    let mut image = RgbaImage::from_pixel(width, height, Rgba([0, 0, 0, 255]));
+    for (i, &c) in chars.iter().enumerate() {
+        for (row, bits) in glyph(c).into_iter().enumerate() {
+            for column in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - column)) == 0 {
+                    continue;
+                }
+                let (x, y) = ((1 + i as u32 * cell + column) * scale, (1 + row as u32) * scale);
+                for (dx, dy) in (0..scale).flat_map(|dx| (0..scale).map(move |dy| (dx, dy))) {
+                    image.put_pixel(x + dx, y + dy, Rgba([255; 4]));
+                }
+            }
+        }
+    }
+    image
+}
@@ -0,0 +1,42 @@
+use gltf_ktxer::adjust::ColorAdjustments;
+use image::{Rgba, RgbaImage};
+
+fn adjusted(texel: [u8; 4], adjustments: ColorAdjustments, srgb: bool) -> [u8; 4] {
+    let mut image = RgbaImage::from_pixel(1, 1, Rgba(texel));
+    adjustments.apply(&mut image, srgb);
+    image.get_pixel(0, 0).0
+}
+
+#[test]
+fn default_adjustments_change_nothing() {
+    for srgb in [false, true] {
+        assert_eq!(adjusted([12, 128, 250, 7], ColorAdjustments::default(), srgb), [12, 128, 250, 7]);
+    }
+}
+
+#[test]
+fn exposure_scales_linear_light() {
+    let double = ColorAdjustments { exposure: 2.0, ..ColorAdjustments::default() };
+    assert_eq!(adjusted([50, 100, 200, 9], double, false), [100, 200, 255, 9]);
+    // sRGB 128 is about 0.216 linear, doubled is 0.432, which is sRGB 176
+    assert_eq!(adjusted([128, 128, 128, 255], double, true), [176, 176, 176, 255]);
+}
+
+#[test]
+fn gamma_and_saturation() {
+    let gamma = ColorAdjustments { gamma: 2.0, ..ColorAdjustments::default() };
+    // sqrt(64 / 255) * 255 = 127.7
+    assert_eq!(adjusted([64, 0, 255, 255], gamma, false), [128, 0, 255, 255]);
+
+    let gray = ColorAdjustments { saturation: 0.0, ..ColorAdjustments::default() };
+    let [r, g, b, _] = adjusted([255, 0, 0, 255], gray, false);
+    assert!(r == g && g == b);
+}
+
+#[test]
+fn invalid_adjustments_are_reported() {
+    assert!(ColorAdjustments { gamma: 0.0, ..ColorAdjustments::default() }.check().is_some());
+    assert!(ColorAdjustments { exposure: -1.0, ..ColorAdjustments::default() }.check().is_some());
+    assert!(ColorAdjustments { saturation: f32::NAN, ..ColorAdjustments::default() }.check().is_some());
+    assert!(ColorAdjustments::default().check().is_none());
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use base64::prelude::*;
+use common::{doc, plan, png};
+use gltf_ktxer::{animation::{is_animated, AnimationPolicy}, Error, ImageReencodeFormat, Params};
+use image::{codecs::gif::GifEncoder, Delay, Frame, Rgba, RgbaImage};
+use serde_json::json;
+
+fn gif(frame_count: usize) -> Vec<u8> {
+    let mut data = vec![];
+    let frames = (0..frame_count).map(|i| Frame::from_parts(RgbaImage::from_pixel(4, 4, Rgba([i as u8 * 100, 0, 0, 255])), 0, 0, Delay::from_numer_denom_ms(100, 1)));
+    GifEncoder::new(&mut data).encode_frames(frames).unwrap();
+    data
+}
+
+/// A PNG with an acTL chunk claiming `frame_count` frames inserted straight after IHDR.
+fn apng(frame_count: u32) -> Vec<u8> {
+    let png = png(&RgbaImage::new(4, 4));
+    let (head, tail) = png.split_at(8 + 4 + 4 + 13 + 4);
+    // the CRC isn't checked when detecting
+    let chunk = [&8u32.to_be_bytes(), b"acTL".as_slice(), &frame_count.to_be_bytes(), &[0; 4], &[0; 4]].concat();
+    [head, &chunk, tail].concat()
+}
+
+fn webp_vp8x(flags: u8) -> Vec<u8> {
+    [b"RIFF\0\0\0\0WEBPVP8X\x0a\0\0\0".as_slice(), &[flags, 0, 0, 0], &[3, 0, 0, 3, 0, 0]].concat()
+}
+
+#[test]
+fn animated_sources_are_detected() {
+    assert!(!is_animated(&gif(1)));
+    assert!(is_animated(&gif(3)));
+    assert!(!is_animated(&apng(1)));
+    assert!(is_animated(&apng(2)));
+    assert!(!is_animated(&webp_vp8x(0x10)));
+    assert!(is_animated(&webp_vp8x(0x02)));
+    assert!(!is_animated(b"not an image"));
+}
+
+#[test]
+fn policy_names_round_trip() {
+    for policy in [AnimationPolicy::FirstFrame, AnimationPolicy::Error, AnimationPolicy::Skip] {
+        assert_eq!(policy.to_string().parse(), Ok(policy));
+    }
+    assert!("loop".parse::<AnimationPolicy>().is_err());
+}
+
+#[test]
+fn planning_follows_the_policy() {
+    let doc = doc(json!({
+        "asset": { "version": "2.0" },
+        "images": [{ "uri": format!("data:image/gif;base64,{}", BASE64_STANDARD.encode(gif(2))) }, { "uri": "previous.ktx2" }],
+        "textures": [{ "source": 0, "extensions": { "KHR_texture_basisu": { "source": 1 }, "OTHER_ext": {} } }],
+    }));
+    let jobs_with = |animated_images: AnimationPolicy| {
+        plan(&mut doc.clone(), Params { animated_images, ..Params::default() })
+    };
+
+    let jobs = jobs_with(AnimationPolicy::FirstFrame).unwrap();
+    assert_eq!(jobs.new_images.len(), 2);
+    assert_eq!(jobs.warnings.iter().map(|warning| warning.code).collect::<Vec<_>>(), ["animated_image"]);
+
+    let Err(err) = jobs_with(AnimationPolicy::Error) else {
+        panic!("animated image was accepted");
+    };
+    assert!(matches!(err.without_location(), Error::AnimatedImage));
+
+    let jobs = jobs_with(AnimationPolicy::Skip).unwrap();
+    assert_eq!(jobs.new_images.len(), 1);
+    assert_eq!(jobs.new_images[0].reencode_as, ImageReencodeFormat::Copy);
+    assert_eq!(jobs.new_images[0].max_dimension, None);
+    let texture = serde_json::to_value(&jobs.new_textures[0]).unwrap();
+    assert_eq!(texture, json!({ "source": 0, "extensions": { "OTHER_ext": {} } }));
+    assert_eq!(jobs.warnings.iter().map(|warning| warning.code).collect::<Vec<_>>(), ["animated_image"]);
+}
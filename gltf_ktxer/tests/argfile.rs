@@ -0,0 +1,48 @@
+use std::{ffi::OsString, path::PathBuf};
+
+use gltf_ktxer::{argfile::{expand, split}, Error};
+
+fn dir(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn os(args: &[&str]) -> Vec<OsString> {
+    args.iter().map(OsString::from).collect()
+}
+
+#[test]
+fn splitting_follows_gcc() {
+    assert_eq!(split("  encode-image\n\tin.png -o out.ktx2  ").unwrap(), ["encode-image", "in.png", "-o", "out.ktx2"]);
+    assert_eq!(split("'my textures/a.png' \"b \\\"c\\\".png\" d\\ e ''").unwrap(), ["my textures/a.png", "b \"c\".png", "d e", ""]);
+    assert_eq!(split("'C:\\textures\\a.png'").unwrap(), ["C:\\textures\\a.png"]);
+    assert!(matches!(split("'unterminated"), Err(Error::BadArgFile(_))));
+}
+
+#[test]
+fn response_files_are_expanded_in_place() {
+    let dir = dir("argfile_expand");
+    let inner = dir.join("inner.txt");
+    let outer = dir.join("outer.txt");
+    std::fs::write(&inner, "--mipmaps --target webgl2,vulkan").unwrap();
+    std::fs::write(&outer, format!("in.png\n@{}\n-o out.ktx2\n", inner.display())).unwrap();
+
+    let expanded = expand(os(&["gltf-ktxer", "encode-image", &format!("@{}", outer.display()), "--linear"])).unwrap();
+    assert_eq!(expanded, os(&["gltf-ktxer", "encode-image", "in.png", "--mipmaps", "--target", "webgl2,vulkan", "-o", "out.ktx2", "--linear"]));
+    // The program name is never a response file
+    assert_eq!(expand(os(&["@gltf-ktxer"])).unwrap(), os(&["@gltf-ktxer"]));
+}
+
+#[test]
+fn bad_response_files_are_errors() {
+    let dir = dir("argfile_bad");
+    let looped = dir.join("loop.txt");
+    std::fs::write(&looped, format!("@{}", looped.display())).unwrap();
+    let Err(err) = expand(os(&["gltf-ktxer", &format!("@{}", looped.display())])) else {
+        panic!("response file loop was expanded");
+    };
+    assert!(err.to_string().contains("nest"), "{err}");
+    assert!(matches!(expand(os(&["gltf-ktxer", "@missing.txt"])), Err(Error::BadArgFile(_))));
+}
@@ -0,0 +1,124 @@
+//! Check `.basis` export against the container layout in Basis Universal's `basisu_file_headers.h`.
+
+mod common;
+
+use std::collections::HashMap;
+
+use base64::prelude::*;
+use common::doc;
+use gltf_ktxer::{
+    basis::{crc16, export_basis_files, ktx2_to_basis, BASIS_EXTRAS_KEY},
+    ktx2::{ColorSpace, Ktx2Level, Ktx2Texture, KHR_DF_MODEL_ETC1S, SUPERCOMPRESSION_BASIS_LZ},
+    Error, Input,
+};
+use serde_json::json;
+
+fn uint(bytes: &[u8], offset: usize, len: usize) -> u64 {
+    let mut word = [0; 8];
+    word[..len].copy_from_slice(&bytes[offset..offset + len]);
+    u64::from_le_bytes(word)
+}
+
+/// A 4x4 single-level ETC1S texture with alpha, with made-up codebooks and slice data.
+fn etc1s_texture() -> Ktx2Texture {
+    let mut sgd = vec![];
+    for word in [3u16, 5] {
+        sgd.extend_from_slice(&word.to_le_bytes()); // endpointCount, selectorCount
+    }
+    for len in [2u32, 3, 4, 0] {
+        sgd.extend_from_slice(&len.to_le_bytes()); // endpoints, selectors, tables, extended byte lengths
+    }
+    for word in [0u32, 0, 6, 6, 4] {
+        sgd.extend_from_slice(&word.to_le_bytes()); // imageFlags, rgb offset/length, alpha offset/length
+    }
+    sgd.extend_from_slice(&[1, 1, 2, 2, 2, 3, 3, 3, 3]);
+
+    let mut dfd = gltf_ktxer::ktx2::rgba8_dfd(ColorSpace::Srgb);
+    dfd[12] = KHR_DF_MODEL_ETC1S;
+    Ktx2Texture {
+        vk_format: 0,
+        type_size: 1,
+        pixel_width: 4,
+        pixel_height: 4,
+        pixel_depth: 0,
+        layer_count: 0,
+        face_count: 1,
+        supercompression_scheme: SUPERCOMPRESSION_BASIS_LZ,
+        dfd,
+        key_values: vec![],
+        sgd,
+        levels: vec![Ktx2Level { data: vec![9, 9, 9, 9, 9, 9, 7, 7, 7, 7], uncompressed_byte_length: 0 }],
+    }
+}
+
+#[test]
+fn etc1s_ktx2_roundtrips_through_bytes() {
+    let etc1s = etc1s_texture();
+    assert_eq!(Ktx2Texture::from_bytes(&etc1s.to_bytes()).unwrap(), etc1s);
+}
+
+#[test]
+fn crc16_matches_basisu() {
+    // basisu's crc16 is CRC-16/GENIBUS, whose standard check value is for the ASCII digits 1-9
+    assert_eq!(crc16(b"123456789"), 0xD64E);
+}
+
+#[test]
+fn etc1s_basis_layout() {
+    let basis = ktx2_to_basis(&etc1s_texture()).unwrap();
+
+    assert_eq!(&basis[..2], b"sB");
+    assert_eq!(uint(&basis, 4, 2), 77);
+    assert_eq!(uint(&basis, 6, 2), crc16(&basis[8..77]) as u64);
+    assert_eq!(uint(&basis, 8, 4), basis.len() as u64 - 77);
+    assert_eq!(uint(&basis, 12, 2), crc16(&basis[77..]) as u64);
+    assert_eq!(uint(&basis, 14, 3), 2, "one color and one alpha slice");
+    assert_eq!(uint(&basis, 17, 3), 1, "one image");
+    assert_eq!(uint(&basis, 21, 2), 1 | 4 | 16, "ETC1S, has alpha slices, sRGB");
+    assert_eq!(uint(&basis, 39, 2), 3);
+    assert_eq!(uint(&basis, 48, 2), 5);
+
+    let endpoints = uint(&basis, 41, 4) as usize;
+    assert_eq!(&basis[endpoints..endpoints + 2], [1, 1]);
+    let tables = uint(&basis, 57, 4) as usize;
+    assert_eq!(&basis[tables..tables + uint(&basis, 61, 4) as usize], [3, 3, 3, 3]);
+
+    let slice_descs = uint(&basis, 65, 4) as usize;
+    for (i, (flags, expected)) in [(0, &[9u8; 6][..]), (1, &[7u8; 4][..])].into_iter().enumerate() {
+        let desc = slice_descs + i * 23;
+        assert_eq!(uint(&basis, desc + 4, 1), flags);
+        assert_eq!(uint(&basis, desc + 5, 2), 4);
+        assert_eq!(uint(&basis, desc + 9, 2), 1, "4 pixels is one block");
+        let offset = uint(&basis, desc + 13, 4) as usize;
+        let len = uint(&basis, desc + 17, 4) as usize;
+        assert_eq!(&basis[offset..offset + len], expected);
+        assert_eq!(uint(&basis, desc + 21, 2), crc16(expected) as u64);
+    }
+}
+
+#[test]
+fn uncompressed_ktx2_is_rejected() {
+    let rgba = Ktx2Texture::from_rgba8(&image::RgbaImage::new(4, 4), ColorSpace::Srgb).unwrap();
+    assert!(matches!(ktx2_to_basis(&rgba), Err(Error::Ktx2NotBasis)));
+}
+
+#[test]
+fn export_points_textures_at_basis_files() {
+    let uri = format!("data:image/ktx2;base64,{}", BASE64_STANDARD.encode(etc1s_texture().to_bytes()));
+    let mut gltf_json = doc(json!({
+        "asset": { "version": "2.0" },
+        "images": [{ "uri": uri }],
+        "textures": [
+            { "extensions": { "KHR_texture_basisu": { "source": 0 } } },
+            { "extensions": { "KHR_texture_basisu": { "source": 0 } }, "extras": { "author": "test" } },
+        ],
+    }));
+    let binaries = HashMap::new();
+    let files = export_basis_files(Input { gltf_json: &mut gltf_json, binaries: &binaries }, |idx| format!("image{idx}.basis")).unwrap();
+
+    assert_eq!(files.keys().collect::<Vec<_>>(), ["image0.basis"]);
+    for texture in gltf_json["textures"].as_array().unwrap() {
+        assert_eq!(texture["extras"][BASIS_EXTRAS_KEY], json!({ "uri": "image0.basis" }));
+    }
+    assert_eq!(gltf_json["textures"][1]["extras"]["author"], "test");
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use std::collections::HashMap;
+
+use common::{blank_png_uri, doc, plan};
+use gltf_ktxer::{bundle::texture_bundle, get_reencode_jobs, ImageReencodeFormat, Input, Params};
+use serde_json::json;
+
+#[test]
+fn slots_map_to_ktx2_files() {
+    let mut doc = doc(json!({
+        "asset": { "version": "2.0" },
+        "images": [{ "uri": blank_png_uri(4, 4), "name": "Bark Color" }, { "uri": blank_png_uri(4, 4) }],
+        "samplers": [{ "wrapS": 33071 }],
+        "textures": [{ "source": 0, "sampler": 0 }, { "source": 1 }],
+        "materials": [
+            {
+                "name": "Bark",
+                "pbrMetallicRoughness": { "baseColorTexture": { "index": 0 } },
+                "normalTexture": { "index": 1, "scale": 0.5, "texCoord": 1 },
+            },
+            { "name": "Plain" },
+            { "emissiveTexture": { "index": 0 } },
+        ],
+    }));
+    let jobs = plan(&mut doc, Params::default()).unwrap();
+    let bundle = texture_bundle(&doc, &jobs).unwrap();
+
+    let files: Vec<_> = bundle.files.iter().map(|file| file.file_name.as_str()).collect();
+    assert_eq!(files, ["Bark_Color.ktx2", "texture1.ktx2"]);
+    for file in &bundle.files {
+        assert!(matches!(jobs.new_images[file.job].reencode_as, ImageReencodeFormat::Ktx { .. }));
+    }
+    assert_eq!(bundle.mapping, json!({
+        "version": 1,
+        "materials": [
+            {
+                "material": 0,
+                "name": "Bark",
+                "slots": {
+                    "pbrMetallicRoughness/baseColorTexture": { "uri": "Bark_Color.ktx2", "colorSpace": "srgb", "sampler": { "wrapS": 33071 } },
+                    "normalTexture": { "uri": "texture1.ktx2", "colorSpace": "linear", "scale": 0.5, "texCoord": 1 },
+                },
+            },
+            { "material": 1, "name": "Plain", "slots": {} },
+            { "material": 2, "slots": { "emissiveTexture": { "uri": "Bark_Color.ktx2", "colorSpace": "srgb", "sampler": { "wrapS": 33071 } } } },
+        ],
+    }));
+    assert!(bundle.warnings.is_empty());
+}
+
+#[test]
+fn slots_without_ktx2_are_left_out() {
+    let mut doc = doc(json!({
+        "asset": { "version": "2.0" },
+        "images": [{ "uri": "clip.mp4", "mimeType": "video/mp4" }],
+        "textures": [{ "source": 0 }],
+        "materials": [{ "emissiveTexture": { "index": 0 } }],
+    }));
+    let binaries = HashMap::from([(Some("clip.mp4".to_string()), b"\0\0\0\x18ftypmp42\0\0\0\0mp42isom".to_vec())]);
+    let jobs = get_reencode_jobs(Input { gltf_json: &mut doc, binaries: &binaries }, Params::default()).unwrap();
+    let bundle = texture_bundle(&doc, &jobs).unwrap();
+
+    assert!(bundle.files.is_empty());
+    assert_eq!(bundle.mapping["materials"], json!([{ "material": 0, "slots": {} }]));
+    let warnings: Vec<_> = bundle.warnings.iter().map(|warning| (warning.code, warning.json_pointer.as_str())).collect();
+    assert_eq!(warnings, [("bundle_slot_without_ktx2", "/materials/0/emissiveTexture")]);
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use std::{collections::HashMap, num::NonZeroUsize, sync::atomic::{AtomicUsize, Ordering}};
+
+use common::{data_uri, doc};
+use gltf_ktxer::{cache::EncodeCache, get_reencode_jobs, gltf::GltfDoc, schedule::run_jobs, Error, Input, Params};
+use serde_json::json;
+
+fn doc_with_images(images: &[&[u8]]) -> GltfDoc {
+    let images: Vec<_> = images
+        .iter()
+        .map(|data| json!({ "uri": data_uri(data), "mimeType": "image/png" }))
+        .collect();
+    let textures: Vec<_> = (0..images.len()).map(|idx| json!({ "source": idx })).collect();
+    doc(json!({ "asset": { "version": "2.0" }, "images": images, "textures": textures }))
+}
+
+#[test]
+fn shared_textures_are_encoded_once_per_run() {
+    let binaries = HashMap::new();
+    // Two documents sharing the "tiles" texture
+    let jobs: Vec<_> = [doc_with_images(&[b"tiles", b"rock"]), doc_with_images(&[b"tiles", b"grass"])]
+        .into_iter()
+        .flat_map(|mut doc| get_reencode_jobs(Input { gltf_json: &mut doc, binaries: &binaries }, Params::default()).unwrap().new_images)
+        .collect();
+    assert_eq!(jobs.len(), 8, "one uncompressed and one KTX2 job per image");
+
+    let cache = EncodeCache::new();
+    let encodes = AtomicUsize::new(0);
+    let order: Vec<usize> = (0..jobs.len()).collect();
+    let results = run_jobs(&jobs, &order, NonZeroUsize::new(4), |job| {
+        cache.get_or_encode(job, |job| {
+            encodes.fetch_add(1, Ordering::Relaxed);
+            Ok(job.source.data.clone())
+        })
+    });
+
+    assert_eq!(encodes.load(Ordering::Relaxed), 6);
+    assert_eq!((cache.hits(), cache.misses()), (2, 6));
+    assert_eq!(*results[0].as_ref().unwrap(), *results[4].as_ref().unwrap());
+}
+
+#[test]
+fn failed_encodes_are_retried() {
+    let binaries = HashMap::new();
+    let mut doc = doc_with_images(&[b"tiles"]);
+    let jobs = get_reencode_jobs(Input { gltf_json: &mut doc, binaries: &binaries }, Params::default()).unwrap().new_images;
+
+    let cache = EncodeCache::new();
+    assert!(cache.get_or_encode(&jobs[0], |_| Err(Error::EncoderUnavailable("test"))).is_err());
+    assert_eq!(*cache.get_or_encode(&jobs[0], |_| Ok(vec![1])).unwrap(), [1]);
+    assert_eq!(cache.misses(), 2);
+}
+
+#[test]
+fn unfinished_entries_are_discarded() {
+    let binaries = HashMap::new();
+    let mut doc = doc_with_images(&[b"tiles", b"rock"]);
+    let jobs = get_reencode_jobs(Input { gltf_json: &mut doc, binaries: &binaries }, Params::default()).unwrap().new_images;
+
+    let cache = EncodeCache::new();
+    cache.get_or_encode(&jobs[0], |_| Ok(vec![1])).unwrap();
+    assert!(cache.get_or_encode(&jobs[1], |_| Err(Error::Interrupted)).is_err());
+    assert_eq!(cache.discard_unfinished(), 1);
+    assert_eq!(cache.discard_unfinished(), 0);
+    // The finished encode is still there
+    assert_eq!(*cache.get_or_encode(&jobs[0], |_| Ok(vec![2])).unwrap(), [1]);
+}
@@ -0,0 +1,151 @@
+mod common;
+
+use common::{data_uri, doc, plan, png};
+use gltf_ktxer::{color::{embedded_profile, ColorConversion, EmbeddedProfile}, Params};
+use image::{Rgba, RgbaImage};
+use serde_json::json;
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb88320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// A 2x2 PNG with a cICP chunk inserted straight after IHDR.
+fn png_with_cicp(primaries: u8, transfer: u8) -> Vec<u8> {
+    let mut png = std::io::Cursor::new(vec![]);
+    RgbaImage::from_pixel(2, 2, Rgba([200, 100, 100, 255])).write_to(&mut png, image::ImageFormat::Png).unwrap();
+    let png = png.into_inner();
+    // signature, then IHDR's length, type, 13 bytes of data and CRC
+    let (head, tail) = png.split_at(8 + 4 + 4 + 13 + 4);
+    let body = [b"cICP".as_slice(), &[primaries, transfer, 0, 1]].concat();
+    let chunk = [&4u32.to_be_bytes(), body.as_slice(), &crc32(&body).to_be_bytes()].concat();
+    [head, &chunk, tail].concat()
+}
+
+fn s15f16(value: f64) -> [u8; 4] {
+    ((value * 65536.0).round() as i32).to_be_bytes()
+}
+
+/// A matrix/TRC ICC profile with the same tone curve on each channel.
+fn icc_profile(columns: [[f64; 3]; 3], curve: &[u8]) -> Vec<u8> {
+    let xyz = |column: [f64; 3]| [b"XYZ \0\0\0\0".as_slice(), &column.map(s15f16).concat()].concat();
+    let tags: Vec<(&[u8; 4], Vec<u8>)> = vec![
+        (b"rXYZ", xyz(columns[0])),
+        (b"gXYZ", xyz(columns[1])),
+        (b"bXYZ", xyz(columns[2])),
+        (b"rTRC", curve.to_vec()),
+        (b"gTRC", curve.to_vec()),
+        (b"bTRC", curve.to_vec()),
+    ];
+    let mut header = vec![0u8; 128];
+    header[12..16].copy_from_slice(b"mntr");
+    header[16..20].copy_from_slice(b"RGB ");
+    header[20..24].copy_from_slice(b"XYZ ");
+    header[36..40].copy_from_slice(b"acsp");
+    let mut table = (tags.len() as u32).to_be_bytes().to_vec();
+    let mut data = vec![];
+    let data_start = 128 + 4 + tags.len() * 12;
+    for (signature, tag) in &tags {
+        table.extend_from_slice(*signature);
+        table.extend_from_slice(&((data_start + data.len()) as u32).to_be_bytes());
+        table.extend_from_slice(&(tag.len() as u32).to_be_bytes());
+        data.extend_from_slice(tag);
+    }
+    let mut icc = [header, table, data].concat();
+    let len = icc.len() as u32;
+    icc[0..4].copy_from_slice(&len.to_be_bytes());
+    icc
+}
+
+const SRGB_D50: [[f64; 3]; 3] = [[0.4361, 0.2225, 0.0139], [0.3851, 0.7169, 0.0971], [0.1431, 0.0606, 0.7142]];
+const ADOBE_RGB_D50: [[f64; 3]; 3] = [[0.6097, 0.3111, 0.0195], [0.2053, 0.6257, 0.0609], [0.1492, 0.0632, 0.7446]];
+
+fn srgb_curve() -> Vec<u8> {
+    let params = [2.4, 1.0 / 1.055, 0.055 / 1.055, 1.0 / 12.92, 0.04045].map(s15f16).concat();
+    [b"para\0\0\0\0\0\x03\0\0".as_slice(), &params].concat()
+}
+
+fn converted(conversion: &ColorConversion, texel: [u8; 4]) -> [u8; 4] {
+    let mut image = RgbaImage::from_pixel(1, 1, Rgba(texel));
+    conversion.apply(&mut image);
+    image.get_pixel(0, 0).0
+}
+
+#[test]
+fn cicp_is_found_in_pngs() {
+    assert_eq!(embedded_profile(&png_with_cicp(12, 13)), Some(EmbeddedProfile::Cicp { primaries: 12, transfer: 13 }));
+    // decodes fine with the extra chunk
+    image::load_from_memory(&png_with_cicp(12, 13)).unwrap();
+
+    assert_eq!(embedded_profile(&png(&RgbaImage::new(2, 2))), None);
+}
+
+#[test]
+fn cicp_conversions() {
+    assert_eq!(ColorConversion::from_profile(&EmbeddedProfile::Cicp { primaries: 1, transfer: 13 }), Ok(None));
+    assert!(ColorConversion::from_profile(&EmbeddedProfile::Cicp { primaries: 9, transfer: 16 }).is_err());
+    assert!(ColorConversion::from_profile(&EmbeddedProfile::Cicp { primaries: 22, transfer: 13 }).is_err());
+
+    let p3 = ColorConversion::from_profile(&EmbeddedProfile::Cicp { primaries: 12, transfer: 13 }).unwrap().unwrap();
+    // Both share the D65 white point, so grays are unchanged
+    assert_eq!(converted(&p3, [128, 128, 128, 7]), [128, 128, 128, 7]);
+    // P3 is a wider gamut, so its colors become more saturated in sRGB
+    let [r, g, b, a] = converted(&p3, [200, 100, 100, 255]);
+    assert!(r > 200 && g < 100 && b < 100 && a == 255, "{:?}", [r, g, b, a]);
+}
+
+#[test]
+fn icc_conversions() {
+    let srgb = icc_profile(SRGB_D50, &srgb_curve());
+    assert_eq!(ColorConversion::from_profile(&EmbeddedProfile::Icc(srgb)), Ok(None));
+
+    // Adobe RGB with a gamma 2.2 curve
+    let adobe = icc_profile(ADOBE_RGB_D50, b"curv\0\0\0\0\0\0\0\x01\x02\x33");
+    let adobe = ColorConversion::from_profile(&EmbeddedProfile::Icc(adobe)).unwrap().unwrap();
+    let [r, g, b, _] = converted(&adobe, [128, 128, 128, 255]);
+    assert!(r.abs_diff(g) <= 1 && g.abs_diff(b) <= 1, "{:?}", [r, g, b]);
+    let [r, g, b, _] = converted(&adobe, [100, 200, 100, 255]);
+    assert!(r < 100 && g > 200 && b < 100, "{:?}", [r, g, b]);
+
+    let lut = icc_profile(ADOBE_RGB_D50, b"mAB \0\0\0\0");
+    assert!(ColorConversion::from_profile(&EmbeddedProfile::Icc(lut)).is_err());
+}
+
+#[test]
+fn color_images_are_converted_when_planning() {
+    let doc = doc(json!({
+        "asset": { "version": "2.0" },
+        "images": [
+            { "uri": data_uri(&png_with_cicp(12, 13)) },
+            { "uri": data_uri(&png_with_cicp(12, 13)) },
+            { "uri": data_uri(&png_with_cicp(9, 16)) },
+        ],
+        "textures": [{ "source": 0 }, { "source": 1 }, { "source": 2 }],
+        "materials": [{
+            "pbrMetallicRoughness": { "baseColorTexture": { "index": 0 } },
+            "normalTexture": { "index": 1 },
+            "emissiveTexture": { "index": 2 },
+        }],
+    }));
+    let jobs_with = |params: Params| {
+        plan(&mut doc.clone(), params).unwrap()
+    };
+
+    let jobs = jobs_with(Params::default());
+    let converted: Vec<_> = jobs.new_images.iter().map(|job| job.source.color_conversion().is_some()).collect();
+    // base color and KTX2 images of each texture in turn, and the normal map is left alone
+    assert_eq!(converted, [true, true, false, false, false, false]);
+    let warnings: Vec<_> = jobs.warnings.iter().map(|warning| (warning.code, warning.json_pointer.as_str())).collect();
+    assert_eq!(warnings, [("color_profile_unsupported", "/images/2")]);
+
+    let jobs = jobs_with(Params { convert_color_profiles: false, ..Params::default() });
+    assert!(jobs.new_images.iter().all(|job| job.source.color_conversion().is_none()));
+    let warnings: Vec<_> = jobs.warnings.iter().map(|warning| (warning.code, warning.json_pointer.as_str())).collect();
+    assert_eq!(warnings, [("color_profile_ignored", "/images/0"), ("color_profile_unsupported", "/images/2")]);
+}
@@ -0,0 +1,43 @@
+//! Fixtures shared by the integration tests. Each test crate compiles its own copy of this module, so not every helper is used by all of them.
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+
+use base64::prelude::*;
+use gltf_ktxer::{get_reencode_jobs, gltf::GltfDoc, Input, Params, ReencodeJobs, Result};
+use image::RgbaImage;
+use serde_json::Value;
+
+/// `image` encoded as a PNG.
+pub fn png(image: &RgbaImage) -> Vec<u8> {
+    let mut png = std::io::Cursor::new(vec![]);
+    image.write_to(&mut png, image::ImageFormat::Png).unwrap();
+    png.into_inner()
+}
+
+/// `data` as a data URI claiming to be a PNG, whether or not it is one.
+pub fn data_uri(data: &[u8]) -> String {
+    format!("data:image/png;base64,{}", BASE64_STANDARD.encode(data))
+}
+
+/// `image` as a PNG data URI.
+pub fn png_uri(image: &RgbaImage) -> String {
+    data_uri(&png(image))
+}
+
+/// A transparent black PNG data URI.
+pub fn blank_png_uri(width: u32, height: u32) -> String {
+    png_uri(&RgbaImage::new(width, height))
+}
+
+pub fn doc(value: Value) -> GltfDoc {
+    match value {
+        Value::Object(map) => map,
+        _ => panic!("test document must be an object"),
+    }
+}
+
+/// The jobs for `doc`, which has no external files.
+pub fn plan(doc: &mut GltfDoc, params: Params) -> Result<ReencodeJobs> {
+    get_reencode_jobs(Input { gltf_json: doc, binaries: &HashMap::new() }, params)
+}
@@ -0,0 +1,84 @@
+use gltf_ktxer::{config::Config, groups::TextureGroup, mipmap::TileGrid, semantic::{NameRule, Semantic}, Error};
+
+#[test]
+fn config_uses_long_option_names() {
+    let config = Config::parse("preset = \"web-fast\"\nmax-size = 2048\njson-pretty = true\n").unwrap();
+    assert_eq!(config, Config {
+        preset: Some("web-fast".to_string()),
+        max_size: Some(2048),
+        json_pretty: Some(true),
+        ..Config::default()
+    });
+}
+
+#[test]
+fn config_lists_several_targets() {
+    let config = Config::parse("target = [\"webgl2\", \"vulkan\"]\n").unwrap();
+    assert_eq!(config.target, Some(vec!["webgl2".to_string(), "vulkan".to_string()]));
+}
+
+#[test]
+fn config_sets_the_cache_dir() {
+    let config = Config::parse("cache-dir = \"/var/cache/gltf-ktxer\"\n").unwrap();
+    assert_eq!(config.cache_dir.as_deref(), Some("/var/cache/gltf-ktxer"));
+}
+
+#[test]
+fn config_sets_the_encode_time_limit() {
+    let config = Config::parse("max-encode-seconds-per-texture = 30.5\n").unwrap();
+    assert_eq!(config.max_encode_seconds_per_texture, Some(30.5));
+}
+
+#[test]
+fn config_sets_the_effort_and_threads() {
+    let config = Config::parse("effort = \"thorough\"\nthreads = 6\n").unwrap();
+    assert_eq!((config.effort.as_deref(), config.threads.map(|threads| threads.get())), (Some("thorough"), Some(6)));
+    assert!(matches!(Config::parse("threads = 0\n"), Err(Error::BadConfig(_))));
+}
+
+#[test]
+fn texture_overrides_are_checked() {
+    let config = Config::parse("[textures.\"atlas.png\"]\natlas = \"4x2\"\n").unwrap();
+    let atlas = config.texture_overrides().unwrap().get("atlas.png").unwrap().atlas;
+    assert_eq!(atlas, Some(TileGrid { columns: 4, rows: 2 }));
+
+    let config = Config::parse("[textures.\"atlas.png\"]\natlas = \"four\"\n").unwrap();
+    assert!(matches!(config.texture_overrides(), Err(Error::BadConfig(_))));
+
+    let config = Config::parse("[textures.sky]\nexposure = 1.5\n").unwrap();
+    let adjustments = config.texture_overrides().unwrap().get("sky").unwrap().adjustments.unwrap();
+    assert_eq!((adjustments.exposure, adjustments.gamma, adjustments.saturation), (1.5, 1.0, 1.0));
+
+    let config = Config::parse("[textures.sky]\ngamma = 0\n").unwrap();
+    assert!(matches!(config.texture_overrides(), Err(Error::BadConfig(_))));
+}
+
+#[test]
+fn texture_groups_are_read() {
+    let config = Config::parse("[groups.hero]\nmaterials = [\"Face\", \"Body\"]\ntextures = [\"hero_*.png\"]\n[groups.props]\ntextures = [\"crate\"]\n").unwrap();
+    assert_eq!(config.texture_groups(), [
+        TextureGroup { name: "hero".to_string(), materials: vec!["Face".to_string(), "Body".to_string()], textures: vec!["hero_*.png".to_string()] },
+        TextureGroup { name: "props".to_string(), materials: vec![], textures: vec!["crate".to_string()] },
+    ]);
+    assert!(matches!(Config::parse("[groups.hero]\nmaterial = [\"Face\"]\n"), Err(Error::BadConfig(_))));
+}
+
+#[test]
+fn semantic_names_are_checked() {
+    let config = Config::parse("[[semantic-names]]\npattern = \"*_nor\"\nsemantic = \"normal\"\n[[semantic-names]]\npattern = \"*_c\"\nsemantic = \"base-color\"\n").unwrap();
+    assert_eq!(config.name_rules().unwrap(), [NameRule::new("*_nor", Semantic::Normal), NameRule::new("*_c", Semantic::BaseColor)]);
+
+    let config = Config::parse("[[semantic-names]]\npattern = \"*_x\"\nsemantic = \"bump\"\n").unwrap();
+    let e = config.name_rules().unwrap_err();
+    assert!(e.to_string().contains("semantic-names[0].semantic"), "{e}");
+}
+
+#[test]
+fn unknown_config_keys_are_rejected() {
+    assert!(matches!(Config::parse("max_size = 2048"), Err(Error::BadConfig(_))));
+}
+
+#[test]
+fn missing_config_file_is_not_an_error() {
+    assert_eq!(Config::discover(std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").as_path()).unwrap(), None);
+}
@@ -0,0 +1,51 @@
+mod common;
+
+use std::{collections::BTreeSet, path::Path};
+
+use common::doc;
+use gltf_ktxer::{
+    converted::ConvertedAsset,
+    edit,
+    glb::{self, JsonFormat},
+    gltf::{GltfIndex, GltfTextureInfo},
+    ktx2::{self, ColorSpace, Ktx2Texture},
+    semantic::Semantic,
+    KtxCodec,
+};
+use image::RgbaImage;
+use serde_json::json;
+
+fn converted_glb(path: &Path) -> (Ktx2Texture, Ktx2Texture) {
+    let mut doc = doc(json!({ "asset": { "version": "2.0" }, "materials": [{}] }));
+    let mut bin = vec![];
+    let color = Ktx2Texture::from_rgba8_mipmapped(&RgbaImage::new(8, 4), ColorSpace::Srgb).unwrap();
+    let mut normal = Ktx2Texture::from_rgba8(&RgbaImage::new(2, 2), ColorSpace::Linear).unwrap();
+    // Only the header is read, so a DFD claiming UASTC is enough
+    normal.dfd[4 + 8] = ktx2::KHR_DF_MODEL_UASTC;
+    for (ktx, slot) in [(&color, "pbrMetallicRoughness/baseColorTexture"), (&normal, "normalTexture")] {
+        let texture = edit::add_ktx2_texture(&mut doc, &mut bin, ktx, GltfIndex::UNDEFINED).unwrap();
+        edit::set_material_texture(&mut doc, GltfIndex::of(0), slot, &GltfTextureInfo::new(texture)).unwrap();
+    }
+    std::fs::write(path, glb::write(&doc, &bin, JsonFormat::Minified).unwrap()).unwrap();
+    (color, normal)
+}
+
+#[test]
+fn converted_textures_are_indexed() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("converted");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("scene.glb");
+    let (color, normal) = converted_glb(&path);
+    let asset = ConvertedAsset::open(&path).unwrap();
+
+    let [base, norm] = asset.textures() else { panic!("expected two textures") };
+    assert_eq!((base.codec, base.color_space, base.width, base.height, base.level_count), (None, ColorSpace::Srgb, 8, 4, 4));
+    assert_eq!(base.semantics, BTreeSet::from([Semantic::BaseColor]));
+    assert_eq!(base.byte_length, color.encoded_len());
+    assert_eq!((norm.codec, norm.color_space, norm.level_count), (Some(KtxCodec::Uastc), ColorSpace::Linear, 1));
+    assert_eq!(norm.semantics, BTreeSet::from([Semantic::Normal]));
+
+    assert_eq!(asset.texture(GltfIndex::of(1)), Some(norm));
+    assert_eq!(asset.ktx2_bytes(base).unwrap(), color.to_bytes());
+    assert_eq!(asset.ktx2(norm).unwrap(), normal);
+}
@@ -0,0 +1,104 @@
+mod common;
+
+use std::{collections::HashMap, path::PathBuf};
+
+use base64::prelude::*;
+use common::{doc, png, png_uri};
+use gltf_ktxer::{
+    corpus::{run_corpus, AssetOutcome},
+    etc1s,
+    glb::{self, JsonFormat},
+    gltf::GltfDoc,
+    ktx2::ColorSpace,
+    validate::check_images_decode,
+};
+use image::{Rgba, RgbaImage};
+use serde_json::json;
+
+const SAMPLE_ASSETS_REPO: &str = "https://github.com/KhronosGroup/glTF-Sample-Assets";
+
+/// Set GLTF_SAMPLE_ASSETS_DIR to use an existing checkout (or any other asset directory),
+/// otherwise the Khronos sample assets are shallow-cloned into the cargo target directory.
+fn sample_assets_dir() -> PathBuf {
+    if let Some(dir) = std::env::var_os("GLTF_SAMPLE_ASSETS_DIR") {
+        return PathBuf::from(dir);
+    }
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("glTF-Sample-Assets");
+    if !dir.exists() {
+        let status = std::process::Command::new("git")
+            .args(["clone", "--depth", "1", SAMPLE_ASSETS_REPO])
+            .arg(&dir)
+            .status()
+            .expect("couldn't run git");
+        assert!(status.success(), "couldn't clone {SAMPLE_ASSETS_REPO}");
+    }
+    dir.join("Models")
+}
+
+#[test]
+#[ignore = "downloads the Khronos sample assets"]
+fn corpus() {
+    let report = run_corpus(&sample_assets_dir()).unwrap();
+    for (path, outcome) in &report.assets {
+        match outcome {
+            AssetOutcome::Passed => {}
+            AssetOutcome::Skipped { unsupported_extension } => println!("SKIP {} (requires {unsupported_extension})", path.display()),
+            AssetOutcome::Failed(e) => println!("FAIL {}: {e}", path.display()),
+        }
+    }
+    println!("{}/{} assets passed", report.num_passed(), report.assets.len());
+    assert_eq!(report.failures().count(), 0);
+}
+
+fn wood(size: u32) -> RgbaImage {
+    RgbaImage::from_fn(size, size, |x, y| Rgba([(x * 30) as u8, (y * 30) as u8, 90, 255]))
+}
+
+#[test]
+fn assets_are_converted_and_their_outputs_decoded() {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("corpus");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("nested")).unwrap();
+    std::fs::write(dir.join("nested/wood.png"), png(&wood(8))).unwrap();
+    let gltf = json!({
+        "asset": { "version": "2.0" },
+        "images": [{ "uri": "wood.png" }, { "uri": png_uri(&wood(4)) }],
+        "textures": [{ "source": 0 }, { "source": 1 }],
+    });
+    std::fs::write(dir.join("nested/scene.gltf"), gltf.to_string()).unwrap();
+    let bin = png(&wood(8));
+    let glb_doc = doc(json!({
+        "asset": { "version": "2.0" },
+        "buffers": [{ "byteLength": bin.len() }],
+        "bufferViews": [{ "buffer": 0, "byteLength": bin.len() }],
+        "images": [{ "bufferView": 0, "mimeType": "image/png" }],
+        "textures": [{ "source": 0 }],
+    }));
+    std::fs::write(dir.join("scene.glb"), glb::write(&glb_doc, &bin, JsonFormat::Minified).unwrap()).unwrap();
+    std::fs::write(dir.join("notes.txt"), "not an asset").unwrap();
+
+    let report = run_corpus(&dir).unwrap();
+    let paths: Vec<PathBuf> = report.assets.iter().map(|(path, _)| path.strip_prefix(&dir).unwrap().to_path_buf()).collect();
+    assert_eq!(paths, [PathBuf::from("nested/scene.gltf"), PathBuf::from("scene.glb")]);
+    if let Some((path, e)) = report.failures().next() {
+        panic!("{} failed: {e}", path.display());
+    }
+    assert_eq!(report.num_passed(), 2);
+}
+
+#[test]
+fn ktx2_images_must_decode() {
+    let mut ktx = etc1s::encode(&RgbaImage::from_pixel(8, 8, Rgba([10, 20, 30, 255])), ColorSpace::Srgb, None, None).unwrap();
+    let doc_with = |ktx: &[u8]| -> GltfDoc {
+        doc(json!({
+            "asset": { "version": "2.0" },
+            "images": [{ "uri": format!("data:image/ktx2;base64,{}", BASE64_STANDARD.encode(ktx)), "mimeType": "image/ktx2" }],
+        }))
+    };
+    check_images_decode(&doc_with(&ktx.to_bytes()), &HashMap::new()).unwrap();
+
+    // A valid KTX2 container whose slice data doesn't cover its size
+    ktx.pixel_width = 4096;
+    let e = check_images_decode(&doc_with(&ktx.to_bytes()), &HashMap::new()).unwrap_err();
+    assert_eq!((e.code().as_str(), e.json_pointer()), ("ktx2_malformed", Some("/images/0")));
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use std::{num::NonZeroU8, sync::{Arc, Mutex}};
+
+use common::{doc, plan, png_uri};
+use gltf_ktxer::{decision::TextureDecision, semantic::Semantic, ImageReencodeFormat, KtxCodec, Params};
+use image::{Rgba, RgbaImage};
+use serde_json::json;
+
+#[test]
+fn hook_overrides_each_texture() {
+    let mut doc = doc(json!({
+        "asset": { "version": "2.0" },
+        "images": [
+            { "uri": png_uri(&RgbaImage::from_pixel(4, 4, Rgba([128, 128, 255, 255]))), "name": "normal" },
+            { "uri": png_uri(&RgbaImage::from_pixel(4, 4, Rgba([90, 90, 90, 255]))), "name": "gray" },
+            { "uri": png_uri(&RgbaImage::from_pixel(4, 4, Rgba([200, 10, 10, 255]))) },
+        ],
+        "textures": [{ "source": 0 }, { "source": 1 }, { "source": 2, "name": "keep" }],
+        "materials": [{ "normalTexture": { "index": 0 }, "pbrMetallicRoughness": { "baseColorTexture": { "index": 1 } } }],
+    }));
+    let seen = Arc::new(Mutex::new(vec![]));
+    let hook_seen = seen.clone();
+    let params = Params {
+        decision_hook: Some(Box::new(move |context| {
+            let stats = context.stats().unwrap();
+            hook_seen.lock().unwrap().push((context.image_name.map(str::to_string), context.semantics.iter().copied().collect::<Vec<_>>(), context.data_used_as_srgb));
+            if context.texture_name == Some("keep") {
+                TextureDecision { skip: true, ..Default::default() }
+            } else if context.semantics.contains(&Semantic::Normal) {
+                TextureDecision { codec: Some(KtxCodec::Uastc), quality: NonZeroU8::new(200), ..Default::default() }
+            } else if stats.is_grayscale {
+                TextureDecision { quality: NonZeroU8::new(50), ..Default::default() }
+            } else {
+                TextureDecision::default()
+            }
+        })),
+        ..Params::default()
+    };
+    let jobs = plan(&mut doc, params).unwrap();
+
+    assert_eq!(
+        *seen.lock().unwrap(),
+        [
+            (Some("normal".to_string()), vec![Semantic::Normal], false),
+            (Some("gray".to_string()), vec![Semantic::BaseColor], true),
+            (None, vec![], false),
+        ]
+    );
+    let formats = jobs
+        .new_images
+        .iter()
+        .filter_map(|job| match job.reencode_as {
+            ImageReencodeFormat::Ktx { codec, basis_compression_quality, .. } => Some(Some((codec, basis_compression_quality.map(NonZeroU8::get)))),
+            ImageReencodeFormat::Copy => Some(None),
+            ImageReencodeFormat::Basic(_) => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(formats, [Some((KtxCodec::Uastc, Some(200))), Some((KtxCodec::Etc1s, Some(50))), None]);
+}
@@ -0,0 +1,166 @@
+mod common;
+
+use base64::prelude::*;
+use common::{doc, plan, png, png_uri};
+use gltf_ktxer::{decode::{decode_png_within, default_decoder, downscale_factor, exif_orientation, ImageCrateDecoder, ImageDecoder}, Params, SourceImage};
+use image::{metadata::Orientation, ImageFormat, Rgba, RgbaImage};
+use serde_json::json;
+
+fn encode(format: ImageFormat) -> Vec<u8> {
+    let image = RgbaImage::from_fn(16, 8, |x, y| Rgba([x as u8 * 16, y as u8 * 32, 128, 255]));
+    let mut data = std::io::Cursor::new(vec![]);
+    match format {
+        // JPEG has no alpha channel
+        ImageFormat::Jpeg => image::DynamicImage::ImageRgba8(image).into_rgb8().write_to(&mut data, format).unwrap(),
+        _ => image.write_to(&mut data, format).unwrap(),
+    }
+    data.into_inner()
+}
+
+#[test]
+fn default_decoder_matches_image_crate() {
+    for format in [ImageFormat::Jpeg, ImageFormat::Png] {
+        let data = encode(format);
+        for hint in [Some(format), None] {
+            let expected = ImageCrateDecoder.decode(&data, hint).unwrap();
+            let decoded = default_decoder().decode(&data, hint).unwrap();
+            assert_eq!(decoded.dimensions(), (16, 8));
+            // JPEG decoders may round differently
+            for (a, b) in decoded.pixels().zip(expected.pixels()) {
+                assert!(a.0.iter().zip(b.0).all(|(a, b)| a.abs_diff(b) <= 2), "{format:?}: {a:?} != {b:?}");
+            }
+        }
+    }
+}
+
+#[test]
+fn bad_data_is_an_error() {
+    assert!(default_decoder().decode(b"not an image", Some(ImageFormat::Jpeg)).is_err());
+    assert!(default_decoder().decode(b"not an image", None).is_err());
+}
+
+/// A JPEG from [encode] with an EXIF APP1 segment holding just `orientation`.
+fn jpeg_with_orientation(orientation: u8) -> Vec<u8> {
+    let jpeg = encode(ImageFormat::Jpeg);
+    // big-endian TIFF header, then an IFD with one SHORT entry for tag 0x0112 and no next IFD
+    let tiff = [b"MM\0*\0\0\0\x08\0\x01\x01\x12\0\x03\0\0\0\x01\0".as_slice(), &[orientation], b"\0\0\0\0\0\0"].concat();
+    let app1 = [b"Exif\0\0".as_slice(), &tiff].concat();
+    let segment = [b"\xff\xe1".as_slice(), &(app1.len() as u16 + 2).to_be_bytes(), &app1].concat();
+    [&jpeg[..2], &segment, &jpeg[2..]].concat()
+}
+
+#[test]
+fn exif_orientation_is_read_and_applied() {
+    assert_eq!(exif_orientation(&encode(ImageFormat::Jpeg)), None);
+    assert_eq!(exif_orientation(&jpeg_with_orientation(1)), None);
+    assert_eq!(exif_orientation(&jpeg_with_orientation(6)), Some(Orientation::Rotate90));
+
+    let source = SourceImage::new(jpeg_with_orientation(6), "image/jpeg".to_string()).with_orientation(Orientation::Rotate90);
+    let decoded = source.decode().unwrap();
+    assert_eq!(decoded.dimensions(), (8, 16));
+    // rotated clockwise, so the bottom left texel ends up top left
+    let stored = default_decoder().decode(&source.data, None).unwrap();
+    assert_eq!(decoded.get_pixel(0, 0), stored.get_pixel(0, 7));
+}
+
+#[test]
+fn planning_applies_exif_orientation() {
+    let data_uri = format!("data:image/jpeg;base64,{}", BASE64_STANDARD.encode(jpeg_with_orientation(8)));
+    let jobs_with = |params: Params| {
+        let mut doc = doc(json!({
+            "asset": { "version": "2.0" },
+            "images": [{ "uri": data_uri }],
+            "textures": [{ "source": 0 }],
+        }));
+        plan(&mut doc, params).unwrap()
+    };
+
+    let jobs = jobs_with(Params::default());
+    assert!(jobs.warnings.is_empty());
+    for job in &jobs.new_images {
+        assert_eq!(job.source.orientation(), Some(Orientation::Rotate270));
+        assert_eq!(job.source_dimensions(), Some((8, 16)));
+    }
+
+    let jobs = jobs_with(Params { apply_exif_orientation: false, ..Params::default() });
+    assert!(jobs.new_images.iter().all(|job| job.source.orientation().is_none() && job.source_dimensions() == Some((16, 8))));
+    let warnings: Vec<_> = jobs.warnings.iter().map(|warning| (warning.code, warning.json_pointer.as_str())).collect();
+    assert_eq!(warnings, [("exif_orientation_ignored", "/images/0")]);
+}
+
+/// Average `factor` x `factor` blocks of `image`, folding leftover rows and columns into the last block.
+fn box_downscale(image: &RgbaImage, factor: u32) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let (out_width, out_height) = ((width / factor).max(1), (height / factor).max(1));
+    RgbaImage::from_fn(out_width, out_height, |out_x, out_y| {
+        let xs = out_x * factor..if out_x + 1 == out_width { width } else { (out_x + 1) * factor };
+        let ys = out_y * factor..if out_y + 1 == out_height { height } else { (out_y + 1) * factor };
+        let count = xs.len() as u32 * ys.len() as u32;
+        let mut sum = [0u32; 4];
+        for y in ys {
+            for x in xs.clone() {
+                sum.iter_mut().zip(image.get_pixel(x, y).0).for_each(|(sum, value)| *sum += value as u32);
+            }
+        }
+        Rgba(sum.map(|sum| ((sum + count / 2) / count) as u8))
+    })
+}
+
+#[test]
+fn downscale_factor_keeps_the_longer_side() {
+    assert_eq!(downscale_factor((8192, 4096), 2048), 4);
+    assert_eq!(downscale_factor((8000, 100), 2048), 2);
+    assert_eq!(downscale_factor((4095, 4095), 2048), 1);
+    assert_eq!(downscale_factor((64, 64), 64), 1);
+    assert_eq!(downscale_factor((64, 64), 0), 64);
+}
+
+#[test]
+fn large_pngs_are_downscaled_while_decoding() {
+    // Odd sizes, so the last row and column of blocks take the leftovers
+    let image = RgbaImage::from_fn(67, 41, |x, y| Rgba([(x * 3) as u8, (y * 6) as u8, ((x * y) % 256) as u8, (255 - x) as u8]));
+    let data = png(&image);
+    let decoded = decode_png_within(&data, 16).unwrap();
+    assert_eq!(decoded.dimensions(), (16, 10));
+    assert_eq!(decoded, box_downscale(&image, 4));
+    assert_eq!(default_decoder().decode_within(&data, None, 16).unwrap(), decoded);
+
+    // Nothing to gain, so it's decoded whole
+    assert_eq!(decode_png_within(&data, 40).unwrap(), image);
+    // Other formats are decoded whole too
+    let jpeg = encode(ImageFormat::Jpeg);
+    assert_eq!(default_decoder().decode_within(&jpeg, Some(ImageFormat::Jpeg), 2).unwrap().dimensions(), (16, 8));
+}
+
+#[test]
+fn downscaled_decodes_expand_every_color_type() {
+    let gray = image::ImageBuffer::<image::Luma<u16>, _>::from_fn(32, 32, |x, y| image::Luma([(x * 2000 + y * 40) as u16]));
+    let mut data = std::io::Cursor::new(vec![]);
+    gray.write_to(&mut data, ImageFormat::Png).unwrap();
+    let full = ImageCrateDecoder.decode(data.get_ref(), Some(ImageFormat::Png)).unwrap();
+    let decoded = decode_png_within(data.get_ref(), 8).unwrap();
+    assert_eq!(decoded.dimensions(), (8, 8));
+    for (a, b) in decoded.pixels().zip(box_downscale(&full, 4).pixels()) {
+        assert!(a.0.iter().zip(b.0).all(|(a, b)| a.abs_diff(b) <= 1), "{a:?} != {b:?}");
+        assert_eq!((a[0], a[3]), (a[2], 255));
+    }
+}
+
+#[test]
+fn planning_limits_how_large_sources_are_decoded() {
+    let data_uri = png_uri(&RgbaImage::from_pixel(64, 32, Rgba([1, 2, 3, 255])));
+    let jobs_with = |params: Params| {
+        let mut doc = doc(json!({
+            "asset": { "version": "2.0" },
+            "images": [{ "uri": data_uri }],
+            "textures": [{ "source": 0 }],
+        }));
+        plan(&mut doc, params).unwrap()
+    };
+
+    let jobs = jobs_with(Params { max_texture_size: Some(10), ..Params::default() });
+    assert_eq!(jobs.new_images[0].source.decode().unwrap().dimensions(), (16, 8));
+    assert_eq!(jobs.new_images[0].source_dimensions(), Some((64, 32)));
+    let jobs = jobs_with(Params { max_texture_size: None, ..Params::default() });
+    assert_eq!(jobs.new_images[0].source.decode().unwrap().dimensions(), (64, 32));
+}
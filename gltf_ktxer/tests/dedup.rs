@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+
+use gltf_ktxer::{dedup::{dedup_image_views, dedup_samplers_and_textures, DedupStats, TableDedupStats}, edit::{CustomReference, ReferenceRegistry}, gltf::GltfDoc, prepare_output_buffers, BufferLayout, Input, Params};
+use serde_json::json;
+
+/// Views 0 and 2 hold the same bytes as image data, and view 1 the same bytes as an accessor.
+fn doc_with_duplicates() -> (GltfDoc, HashMap<Option<String>, Vec<u8>>) {
+    let serde_json::Value::Object(doc) = json!({
+        "asset": { "version": "2.0" },
+        "buffers": [{ "uri": "a.bin", "byteLength": 16 }],
+        "bufferViews": [
+            { "buffer": 0, "byteLength": 4 },
+            { "buffer": 0, "byteOffset": 4, "byteLength": 4 },
+            { "buffer": 0, "byteOffset": 8, "byteLength": 4 },
+            { "buffer": 0, "byteOffset": 12, "byteLength": 4 },
+        ],
+        "accessors": [{ "bufferView": 1, "componentType": 5126, "count": 1, "type": "SCALAR" }],
+        "images": [
+            { "bufferView": 2, "mimeType": "image/ktx2" },
+            { "bufferView": 0, "mimeType": "image/ktx2" },
+            { "bufferView": 3, "mimeType": "image/ktx2" },
+        ],
+    }) else {
+        unreachable!()
+    };
+    let data = [[1, 2, 3, 4], [1, 2, 3, 4], [1, 2, 3, 4], [5, 6, 7, 8]].concat();
+    (doc, HashMap::from([(Some("a.bin".to_string()), data)]))
+}
+
+#[test]
+fn identical_image_views_are_merged() {
+    let (mut doc, binaries) = doc_with_duplicates();
+    let stats = dedup_image_views(&mut doc, &binaries, &ReferenceRegistry::default()).unwrap();
+    assert_eq!(stats, DedupStats { images_repointed: 1, views_removed: 1, bytes_saved: 4 });
+
+    // The first image's view is kept, and the views after the removed one are renumbered
+    assert_eq!(doc["images"], json!([
+        { "bufferView": 1, "mimeType": "image/ktx2" },
+        { "bufferView": 1, "mimeType": "image/ktx2" },
+        { "bufferView": 2, "mimeType": "image/ktx2" },
+    ]));
+    // The accessor's view holds the same bytes, but isn't only image data
+    assert_eq!(doc["accessors"][0]["bufferView"], 0);
+    assert_eq!(doc["bufferViews"].as_array().unwrap().len(), 3);
+}
+
+#[test]
+fn repacking_drops_the_merged_bytes() {
+    let (mut doc, binaries) = doc_with_duplicates();
+    let output = prepare_output_buffers(Input { gltf_json: &mut doc, binaries: &binaries }, &Params::default()).unwrap();
+    assert_eq!(output.image_view_dedup.views_removed, 1);
+    assert_eq!(output.binary, [1, 2, 3, 4, 1, 2, 3, 4, 5, 6, 7, 8]);
+    assert_eq!(output.gltf_json["buffers"][0]["byteLength"], 12);
+}
+
+#[test]
+fn merging_can_be_turned_off() {
+    let (mut doc, binaries) = doc_with_duplicates();
+    let params = Params { dedup_image_views: false, ..Params::default() };
+    let output = prepare_output_buffers(Input { gltf_json: &mut doc, binaries: &binaries }, &params).unwrap();
+    assert_eq!(output.image_view_dedup, DedupStats::default());
+    assert_eq!(output.gltf_json["bufferViews"].as_array().unwrap().len(), 4);
+}
+
+#[test]
+fn in_place_layout_keeps_every_view() {
+    let serde_json::Value::Object(mut doc) = json!({
+        "asset": { "version": "2.0" },
+        "buffers": [{ "byteLength": 8 }],
+        "bufferViews": [{ "buffer": 0, "byteLength": 4 }, { "buffer": 0, "byteOffset": 4, "byteLength": 4 }],
+        "images": [{ "bufferView": 0, "mimeType": "image/png" }, { "bufferView": 1, "mimeType": "image/png" }],
+    }) else {
+        unreachable!()
+    };
+    let binaries = HashMap::from([(None, vec![9; 8])]);
+    let params = Params { buffer_layout: BufferLayout::InPlace, ..Params::default() };
+    let output = prepare_output_buffers(Input { gltf_json: &mut doc, binaries: &binaries }, &params).unwrap();
+    assert_eq!(output.image_view_dedup, DedupStats::default());
+    assert_eq!(output.gltf_json["images"][1]["bufferView"], 1);
+}
+
+/// One sampler and texture per material, as many exporters write them.
+fn doc_with_a_sampler_per_texture() -> GltfDoc {
+    let serde_json::Value::Object(doc) = json!({
+        "asset": { "version": "2.0" },
+        "samplers": [
+            { "name": "a", "magFilter": 9729 },
+            { "name": "b", "magFilter": 9729, "wrapS": 10497, "wrapT": 10497 },
+            { "magFilter": 9728 },
+            { "magFilter": 9729 },
+        ],
+        "textures": [
+            { "source": 0, "sampler": 0 },
+            { "source": 0, "sampler": 1 },
+            { "source": 0, "sampler": 2 },
+            { "source": 1, "sampler": 3 },
+            { "source": 1, "sampler": 3, "name": "copy" },
+        ],
+        "materials": [
+            { "pbrMetallicRoughness": { "baseColorTexture": { "index": 1 } } },
+            { "pbrMetallicRoughness": { "baseColorTexture": { "index": 2 } }, "normalTexture": { "index": 4 } },
+            { "extensions": { "MY_ext": { "textureIdx": 3 } } },
+        ],
+    }) else {
+        unreachable!()
+    };
+    doc
+}
+
+#[test]
+fn identical_samplers_and_textures_are_merged() {
+    let mut doc = doc_with_a_sampler_per_texture();
+    let mut registry = ReferenceRegistry::default();
+    registry.register("/materials/*/extensions/MY_ext/textureIdx -> textures".parse::<CustomReference>().unwrap());
+    let stats = dedup_samplers_and_textures(&mut doc, &registry).unwrap();
+    assert_eq!(stats, TableDedupStats { samplers_removed: 2, textures_removed: 2 });
+
+    // Unset wrap modes are the same as repeat, and names don't matter
+    assert_eq!(doc["samplers"], json!([{ "name": "a", "magFilter": 9729 }, { "magFilter": 9728 }]));
+    assert_eq!(doc["textures"], json!([
+        { "source": 0, "sampler": 0 },
+        { "source": 0, "sampler": 1 },
+        { "source": 1, "sampler": 0 },
+    ]));
+    assert_eq!(doc["materials"], json!([
+        { "pbrMetallicRoughness": { "baseColorTexture": { "index": 0 } } },
+        { "pbrMetallicRoughness": { "baseColorTexture": { "index": 1 } }, "normalTexture": { "index": 2 } },
+        { "extensions": { "MY_ext": { "textureIdx": 2 } } },
+    ]));
+}
+
+#[test]
+fn merging_samplers_and_textures_is_opt_in() {
+    let mut doc = doc_with_a_sampler_per_texture();
+    let binaries = HashMap::new();
+    let output = prepare_output_buffers(Input { gltf_json: &mut doc, binaries: &binaries }, &Params::default()).unwrap();
+    assert_eq!(output.table_dedup, TableDedupStats::default());
+    assert_eq!(output.gltf_json["textures"].as_array().unwrap().len(), 5);
+
+    let mut doc = doc_with_a_sampler_per_texture();
+    let params = Params { dedup_samplers_and_textures: true, ..Params::default() };
+    let output = prepare_output_buffers(Input { gltf_json: &mut doc, binaries: &binaries }, &params).unwrap();
+    assert_eq!(output.table_dedup, TableDedupStats { samplers_removed: 2, textures_removed: 2 });
+    assert_eq!(output.gltf_json["samplers"].as_array().unwrap().len(), 2);
+}
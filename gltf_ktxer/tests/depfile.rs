@@ -0,0 +1,32 @@
+mod common;
+
+use std::path::PathBuf;
+
+use common::doc;
+use gltf_ktxer::depfile::{gltf_inputs, render};
+use serde_json::json;
+
+#[test]
+fn rules_list_every_input_for_each_output() {
+    let outputs = [PathBuf::from("out/a.webgl2.ktx2"), PathBuf::from("out/a.vulkan.ktx2")];
+    let inputs = [PathBuf::from("a.png"), PathBuf::from("gltf-ktxer.toml")];
+    assert_eq!(render(&outputs, &inputs), "out/a.webgl2.ktx2: a.png gltf-ktxer.toml\nout/a.vulkan.ktx2: a.png gltf-ktxer.toml\n");
+    assert_eq!(render(&outputs[..1], &[]), "out/a.webgl2.ktx2:\n");
+}
+
+#[test]
+fn special_characters_are_escaped() {
+    let inputs = [PathBuf::from("my textures/wood #2.png"), PathBuf::from("$HOME.png")];
+    assert_eq!(render(&[PathBuf::from("out.glb")], &inputs), "out.glb: my\\ textures/wood\\ \\#2.png $$HOME.png\n");
+}
+
+#[test]
+fn gltf_inputs_include_referenced_files_once() {
+    let doc = doc(json!({
+        "asset": { "version": "2.0" },
+        "buffers": [{ "uri": "scene.bin", "byteLength": 4 }, { "uri": "data:application/octet-stream;base64,AAAAAA==", "byteLength": 4 }],
+        "images": [{ "uri": "textures/wood%20grain.png" }, { "uri": "scene.bin" }, { "bufferView": 0, "mimeType": "image/png" }],
+    }));
+    let dir = PathBuf::from("models");
+    assert_eq!(gltf_inputs(&dir.join("scene.gltf"), &doc), [dir.join("scene.gltf"), dir.join("scene.bin"), dir.join("textures/wood grain.png")]);
+}
@@ -0,0 +1,188 @@
+mod common;
+
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, SystemTime},
+};
+
+use common::{blank_png_uri, doc, plan};
+use gltf_ktxer::{
+    cache::{CacheCounters, EncodeCache, EncodeCacheKey},
+    disk_cache::{Age, DirStats, DiskCache, PruneStats},
+    Error, Params,
+};
+use serde_json::json;
+
+fn cache_dir(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("disk_cache").join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+fn key(params: &str) -> EncodeCacheKey {
+    EncodeCacheKey { source_sha256: [7; 32], encode_params_key: params.to_string() }
+}
+
+#[test]
+fn entries_round_trip() {
+    let cache = DiskCache::open(&cache_dir("round_trip")).unwrap();
+    assert_eq!(cache.get(&key("a")).unwrap(), None);
+    cache.put(&key("a"), b"encoded").unwrap();
+    assert_eq!(cache.get(&key("a")).unwrap().as_deref(), Some(&b"encoded"[..]));
+    assert_eq!(cache.get(&key("b")).unwrap(), None);
+
+    let path = cache.entry_path(&key("a"));
+    let name = path.file_name().unwrap().to_string_lossy().into_owned();
+    assert_eq!(path.parent().unwrap().file_name().unwrap().to_string_lossy(), name[..2]);
+}
+
+#[test]
+fn damaged_entries_are_replaced() {
+    let cache = DiskCache::open(&cache_dir("damaged")).unwrap();
+    cache.put(&key("a"), b"encoded").unwrap();
+    let path = cache.entry_path(&key("a"));
+    let mut entry = std::fs::read(&path).unwrap();
+
+    // A flipped bit in the data
+    let last = entry.len() - 1;
+    entry[last] ^= 1;
+    std::fs::write(&path, &entry).unwrap();
+    assert_eq!(cache.get(&key("a")).unwrap(), None);
+    assert!(!path.exists());
+
+    // A write cut short
+    cache.put(&key("a"), b"encoded").unwrap();
+    let entry = std::fs::read(&path).unwrap();
+    std::fs::write(&path, &entry[..20]).unwrap();
+    assert_eq!(cache.get_or_encode(&key("a"), || Ok(b"again".to_vec())).unwrap(), b"again");
+    assert_eq!(cache.get(&key("a")).unwrap().as_deref(), Some(&b"again"[..]));
+    assert_eq!(cache.corrupt_entries(), 2);
+}
+
+#[test]
+fn parallel_writers_encode_each_entry_once() {
+    let dir = cache_dir("parallel");
+    let encodes = AtomicUsize::new(0);
+    let results: Vec<Vec<u8>> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                // Separate caches, and so separate lock file handles, like separate processes
+                let cache = DiskCache::open(&dir).unwrap();
+                let encodes = &encodes;
+                scope.spawn(move || {
+                    cache
+                        .get_or_encode(&key("shared"), || {
+                            encodes.fetch_add(1, Ordering::SeqCst);
+                            std::thread::sleep(Duration::from_millis(50));
+                            Ok(vec![42; 1000])
+                        })
+                        .unwrap()
+                })
+            })
+            .collect();
+        handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+    });
+    assert_eq!(encodes.load(Ordering::SeqCst), 1);
+    assert!(results.iter().all(|data| *data == vec![42; 1000]));
+    // Only the entry, its lock file and no temporary files
+    let subdir = DiskCache::open(&dir).unwrap().entry_path(&key("shared")).parent().unwrap().to_path_buf();
+    assert_eq!(std::fs::read_dir(subdir).unwrap().count(), 2);
+}
+
+#[test]
+fn failed_encodes_are_not_stored() {
+    let cache = DiskCache::open(&cache_dir("failed")).unwrap();
+    assert!(cache.get_or_encode(&key("a"), || Err(Error::EncoderUnavailable("test"))).is_err());
+    assert_eq!(cache.get(&key("a")).unwrap(), None);
+}
+
+#[test]
+fn encode_caches_share_encodes_through_the_disk() {
+    let mut doc = doc(json!({
+        "asset": { "version": "2.0" },
+        "images": [{ "uri": blank_png_uri(4, 4) }],
+        "textures": [{ "source": 0 }],
+    }));
+    let jobs = plan(&mut doc, Params::default()).unwrap().new_images;
+
+    let dir = cache_dir("layered");
+    let first_run = EncodeCache::with_disk_cache(DiskCache::open(&dir).unwrap());
+    assert_eq!(*first_run.get_or_encode(&jobs[0], |_| Ok(vec![1, 2, 3])).unwrap(), [1, 2, 3]);
+    assert_eq!((first_run.hits(), first_run.misses()), (0, 1));
+
+    let second_run = EncodeCache::with_disk_cache(DiskCache::open(&dir).unwrap());
+    assert_eq!(*second_run.get_or_encode(&jobs[0], |_| panic!("already encoded")).unwrap(), [1, 2, 3]);
+    assert_eq!(second_run.counters(), CacheCounters { hits: 1, misses: 0, corrupt_entries: 0 });
+}
+
+/// Set when the entry for `key` was last used, `days` days before `now`.
+fn last_used(cache: &DiskCache, key: &EncodeCacheKey, now: SystemTime, days: u64) {
+    let file = std::fs::File::options().write(true).open(cache.entry_path(key)).unwrap();
+    file.set_modified(now - Duration::from_secs(days * 24 * 60 * 60)).unwrap();
+}
+
+#[test]
+fn pruning_removes_old_then_least_recently_used_entries() {
+    let cache = DiskCache::open(&cache_dir("prune")).unwrap();
+    let now = SystemTime::now();
+    // Entries are 40 bytes of header and hash, then the data
+    for (name, days) in [("a", 40), ("b", 10), ("c", 5), ("d", 1)] {
+        cache.put(&key(name), &[0; 60]).unwrap();
+        last_used(&cache, &key(name), now, days);
+    }
+    // Reading an entry makes it the most recently used
+    cache.get(&key("b")).unwrap();
+    let stats = cache.stats().unwrap();
+    assert_eq!((stats.entries, stats.bytes), (4, 400));
+    assert!(stats.least_recently_used < stats.most_recently_used);
+
+    let pruned = cache.prune(Some(200), Some(Duration::from_secs(30 * 24 * 60 * 60)), now).unwrap();
+    assert_eq!(pruned, PruneStats { removed_entries: 2, removed_bytes: 200, removed_temp_files: 0, kept_entries: 2, kept_bytes: 200 });
+    let remaining: Vec<bool> = ["a", "b", "c", "d"].iter().map(|name| cache.get(&key(name)).unwrap().is_some()).collect();
+    assert_eq!(remaining, [false, true, false, true]);
+
+    let pruned = cache.prune(None, None, now).unwrap();
+    assert_eq!((pruned.removed_entries, pruned.kept_entries), (0, 2));
+}
+
+#[test]
+fn pruning_removes_abandoned_files() {
+    let cache = DiskCache::open(&cache_dir("abandoned")).unwrap();
+    let now = SystemTime::now();
+    cache.get_or_encode(&key("a"), || Ok(vec![1])).unwrap();
+    let entry = cache.entry_path(&key("a"));
+    let subdir = entry.parent().unwrap();
+    // A write which never finished, and one which may still be running
+    std::fs::write(subdir.join(".x.entry.1-0.tmp"), b"partial").unwrap();
+    std::fs::File::options().write(true).open(subdir.join(".x.entry.1-0.tmp")).unwrap().set_modified(now - Duration::from_secs(2 * 60 * 60)).unwrap();
+    std::fs::write(subdir.join(".y.entry.1-1.tmp"), b"partial").unwrap();
+
+    let pruned = cache.prune(Some(0), None, now).unwrap();
+    assert_eq!((pruned.removed_entries, pruned.removed_temp_files), (1, 1));
+    let mut left: Vec<_> = std::fs::read_dir(subdir).unwrap().map(|file| file.unwrap().file_name().to_string_lossy().into_owned()).collect();
+    left.sort();
+    // The removed entry's lock file goes too, as nothing holds it
+    assert_eq!(left, [".y.entry.1-1.tmp"]);
+    assert_eq!(cache.stats().unwrap(), DirStats::default());
+}
+
+#[test]
+fn ages() {
+    assert_eq!("30d".parse(), Ok(Age(Duration::from_secs(30 * 24 * 60 * 60))));
+    assert_eq!("2w".parse(), Ok(Age(Duration::from_secs(14 * 24 * 60 * 60))));
+    assert_eq!("90m".parse::<Age>().unwrap().to_string(), "90m");
+    assert_eq!("120m".parse::<Age>().unwrap().to_string(), "2h");
+    assert_eq!("45s".parse::<Age>().unwrap().to_string(), "45s");
+    for bad in ["30", "d", "3y", "-1d", "1.5h"] {
+        assert!(bad.parse::<Age>().is_err(), "{bad}");
+    }
+}
+
+#[test]
+fn counters_describe_the_hit_rate() {
+    let counters = CacheCounters { hits: 3, misses: 1, corrupt_entries: 0 };
+    assert_eq!(counters.to_string(), "3 cache hits and 1 misses (75% hit rate)");
+    let counters = CacheCounters { hits: 0, misses: 0, corrupt_entries: 2 };
+    assert_eq!(counters.to_string(), "0 cache hits and 0 misses (0% hit rate), 2 damaged entries replaced");
+}
@@ -0,0 +1,31 @@
+use gltf_ktxer::dither::{Dither, ETC1S_ENDPOINT_BITS};
+use image::{Rgba, RgbaImage};
+
+/// Quantize to `bits` per channel, as an encoder would.
+fn quantize(value: u8, bits: u32) -> u8 {
+    let levels = ((1u32 << bits) - 1) as f32;
+    ((value as f32 / 255.0 * levels).round() / levels * 255.0).round() as u8
+}
+
+#[test]
+fn dithering_preserves_average_color_through_quantization() {
+    // A value a third of the way between two 5-bit levels, which plain quantization rounds away
+    let value = 85;
+    for dither in [Dither::Ordered, Dither::BlueNoise] {
+        let mut image = RgbaImage::from_pixel(64, 64, Rgba([value, value, value, 77]));
+        dither.apply(&mut image, ETC1S_ENDPOINT_BITS);
+        assert!(image.pixels().all(|texel| texel.0[3] == 77), "{dither} changed alpha");
+
+        let mean = image.pixels().map(|texel| quantize(texel.0[0], ETC1S_ENDPOINT_BITS) as f64).sum::<f64>() / (64.0 * 64.0);
+        assert!((mean - value as f64).abs() < 1.0, "{dither}: mean {mean}");
+        assert_ne!(quantize(value, ETC1S_ENDPOINT_BITS), value);
+    }
+}
+
+#[test]
+fn dithers_parse_from_their_names() {
+    for dither in [Dither::Ordered, Dither::BlueNoise] {
+        assert_eq!(dither.to_string().parse(), Ok(dither));
+    }
+    assert!("random".parse::<Dither>().is_err());
+}
@@ -0,0 +1,118 @@
+//! Check the ETC1S encoder by decoding what it writes, and against the BasisLZ layout in KTX2 spec section 3.12.
+
+use std::num::NonZeroU8;
+
+use gltf_ktxer::{
+    basis::ktx2_to_basis,
+    etc1s::{codebook_size, decode, encode, encode_levels},
+    ktx2::{generate_mipmaps, verify_encoded, ColorSpace, Ktx2Texture, KHR_DF_MODEL_ETC1S, KHR_DF_TRANSFER_SRGB, SUPERCOMPRESSION_BASIS_LZ},
+    quality::psnr,
+    tuning::EncodeEffort,
+};
+use image::{Rgba, RgbaImage};
+
+/// Smooth gradients in each channel, sized so the last row and column of blocks are partial.
+fn gradient(alpha: bool) -> RgbaImage {
+    RgbaImage::from_fn(30, 18, |x, y| Rgba([(x * 8) as u8, (y * 14) as u8, ((x + y) * 5) as u8, if alpha { (x * 8) as u8 } else { 255 }]))
+}
+
+fn quality(q: u8) -> Option<NonZeroU8> {
+    NonZeroU8::new(q)
+}
+
+fn codebook_counts(ktx: &Ktx2Texture) -> (u16, u16) {
+    (u16::from_le_bytes([ktx.sgd[0], ktx.sgd[1]]), u16::from_le_bytes([ktx.sgd[2], ktx.sgd[3]]))
+}
+
+#[test]
+fn textures_decode_close_to_their_source() {
+    let image = gradient(false);
+    let ktx = encode(&image, ColorSpace::Srgb, quality(255), None).unwrap();
+    assert_eq!((ktx.pixel_width, ktx.pixel_height, ktx.vk_format), (30, 18, 0));
+    assert_eq!(ktx.supercompression_scheme, SUPERCOMPRESSION_BASIS_LZ);
+    assert_eq!(ktx.dfd_color_model(), Some(KHR_DF_MODEL_ETC1S));
+    assert_eq!(ktx.dfd_transfer_function(), Some(KHR_DF_TRANSFER_SRGB));
+    assert!(!ktx.dfd_has_alpha());
+    assert_eq!(ktx.levels[0].uncompressed_byte_length, 0);
+
+    let decoded = decode(&ktx, 0).unwrap();
+    assert_eq!(decoded.dimensions(), image.dimensions());
+    assert!(decoded.pixels().all(|texel| texel[3] == 255));
+    let psnr = psnr(&image, &decoded);
+    assert!(psnr > 28.0, "{psnr} dB");
+    verify_encoded(&ktx, &image, (30, 18)).unwrap();
+}
+
+#[test]
+fn textures_survive_serialization() {
+    let ktx = encode(&gradient(true), ColorSpace::Linear, None, Some(EncodeEffort::Fast)).unwrap();
+    let parsed = Ktx2Texture::from_bytes(&ktx.to_bytes()).unwrap();
+    assert_eq!(parsed, ktx);
+    assert_eq!(decode(&parsed, 0).unwrap(), decode(&ktx, 0).unwrap());
+    ktx2_to_basis(&parsed).unwrap();
+}
+
+#[test]
+fn alpha_gets_its_own_slice() {
+    let image = gradient(true);
+    let ktx = encode(&image, ColorSpace::Srgb, quality(255), None).unwrap();
+    assert!(ktx.dfd_has_alpha());
+    let decoded = decode(&ktx, 0).unwrap();
+    let worst = image.pixels().zip(decoded.pixels()).map(|(a, b)| a[3].abs_diff(b[3])).max().unwrap();
+    assert!(worst <= 16, "alpha is off by up to {worst}");
+    verify_encoded(&ktx, &image, (30, 18)).unwrap();
+}
+
+#[test]
+fn solid_colors_are_nearly_exact() {
+    let image = RgbaImage::from_pixel(8, 8, Rgba([200, 100, 50, 255]));
+    let ktx = encode(&image, ColorSpace::Srgb, None, None).unwrap();
+    assert_eq!(codebook_counts(&ktx), (1, 1));
+    let decoded = decode(&ktx, 0).unwrap();
+    for (a, b) in image.pixels().zip(decoded.pixels()) {
+        assert!(a.0.iter().zip(b.0).all(|(&a, b)| a.abs_diff(b) <= 4), "{a:?} decoded as {b:?}");
+    }
+}
+
+#[test]
+fn quality_sets_the_codebook_size() {
+    assert_eq!(codebook_size(NonZeroU8::MIN), 32);
+    assert_eq!(codebook_size(NonZeroU8::MAX), 4096);
+    assert!(codebook_size(NonZeroU8::new(128).unwrap()) > 300);
+
+    let image = RgbaImage::from_fn(64, 64, |x, y| Rgba([(x * 37 % 256) as u8, (y * 91 % 256) as u8, ((x * y) % 256) as u8, 255]));
+    let low = encode(&image, ColorSpace::Srgb, quality(1), None).unwrap();
+    let high = encode(&image, ColorSpace::Srgb, quality(255), None).unwrap();
+    let (low_endpoints, low_selectors) = codebook_counts(&low);
+    assert!(low_endpoints <= 32 && low_selectors <= 32);
+    assert!(codebook_counts(&high).0 > low_endpoints);
+    assert!(psnr(&image, &decode(&high, 0).unwrap()) > psnr(&image, &decode(&low, 0).unwrap()));
+}
+
+#[test]
+fn mip_levels_share_codebooks() {
+    let levels = generate_mipmaps(&gradient(false));
+    let ktx = encode_levels(&levels, ColorSpace::Srgb, None, None).unwrap();
+    assert_eq!(ktx.levels.len(), levels.len());
+    for (i, level) in levels.iter().enumerate() {
+        assert_eq!(decode(&ktx, i).unwrap().dimensions(), level.dimensions());
+    }
+    assert!(psnr(&levels[0], &decode(&ktx, 0).unwrap()) > 28.0);
+}
+
+#[test]
+fn sizes_the_slice_data_cant_cover_are_rejected() {
+    let mut ktx = encode(&gradient(false), ColorSpace::Srgb, None, None).unwrap();
+    // A fuzzed header claiming over 250 GB of texels, which mustn't be allocated before the slice data is checked
+    for size in [1 << 18, u32::MAX] {
+        (ktx.pixel_width, ktx.pixel_height) = (size, size);
+        assert_eq!(decode(&ktx, 0).unwrap_err().code().as_str(), "ktx2_malformed");
+    }
+}
+
+#[test]
+fn bad_input_is_rejected() {
+    assert!(encode(&RgbaImage::new(0, 4), ColorSpace::Srgb, None, None).is_err());
+    let uncompressed = Ktx2Texture::from_rgba8(&gradient(false), ColorSpace::Srgb).unwrap();
+    assert_eq!(decode(&uncompressed, 0).unwrap_err().code().as_str(), "ktx2_not_basis");
+}
@@ -0,0 +1,168 @@
+mod common;
+
+use std::collections::HashMap;
+
+use common::{data_uri, doc, plan, png};
+use gltf_ktxer::{edit::ReferenceRegistry, etc1s, execute_reencode_jobs, fallback::EncodeFailurePolicy, get_reencode_jobs, gltf::GltfDoc, ktx2::Ktx2Texture, Input, KtxCodec, Params};
+use image::{Rgba, RgbaImage};
+use serde_json::json;
+
+/// A GLB with one accessor's worth of geometry followed by an 8x8 PNG, used as a base color texture.
+fn glb() -> (GltfDoc, HashMap<Option<String>, Vec<u8>>) {
+    let png = png(&RgbaImage::from_pixel(8, 8, Rgba([10, 20, 30, 255])));
+    let mut bin = vec![7; 12];
+    bin.extend_from_slice(&png);
+    let doc = doc(json!({
+        "asset": { "version": "2.0" },
+        "scenes": [{ "nodes": [0] }],
+        "nodes": [{ "mesh": 0 }],
+        "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 }, "material": 0 }] }],
+        "accessors": [{ "bufferView": 0, "componentType": 5126, "count": 1, "type": "VEC3" }],
+        "buffers": [{ "byteLength": bin.len() }],
+        "bufferViews": [
+            { "buffer": 0, "byteLength": 12 },
+            { "buffer": 0, "byteOffset": 12, "byteLength": png.len() },
+        ],
+        "images": [{ "bufferView": 1, "mimeType": "image/png" }],
+        "textures": [{ "source": 0 }],
+        "materials": [{ "pbrMetallicRoughness": { "baseColorTexture": { "index": 0 } } }],
+    }));
+    (doc, HashMap::from([(None, bin)]))
+}
+
+fn params() -> Params {
+    Params { uncompressed_format: image::ImageFormat::Png, max_texture_size: Some(4), ..Params::default() }
+}
+
+/// Read the image at `idx` out of the output binary.
+fn image_data<'a>(doc: &GltfDoc, binary: &'a [u8], idx: usize) -> &'a [u8] {
+    let view = &doc["bufferViews"][doc["images"][idx]["bufferView"].as_u64().unwrap() as usize];
+    let offset = view.get("byteOffset").and_then(|offset| offset.as_u64()).unwrap_or(0) as usize;
+    &binary[offset..offset + view["byteLength"].as_u64().unwrap() as usize]
+}
+
+#[test]
+fn images_are_encoded_into_the_output() {
+    let (mut doc, binaries) = glb();
+    let mut jobs = get_reencode_jobs(Input { gltf_json: &mut doc, binaries: &binaries }, params()).unwrap();
+    let output = execute_reencode_jobs(&mut jobs, &mut doc, binaries, &params()).unwrap();
+
+    assert!(jobs.warnings.is_empty());
+    let doc = &output.gltf_json;
+    assert_eq!(doc["textures"], json!([{ "source": 0, "extensions": { "KHR_texture_basisu": { "source": 1 } } }]));
+    assert_eq!(doc["extensionsUsed"], json!(["KHR_texture_basisu"]));
+    assert_eq!(doc["images"][1]["mimeType"], "image/ktx2");
+
+    // The geometry is untouched, and the original image's view is gone
+    assert_eq!(doc["accessors"][0]["bufferView"], 0);
+    assert_eq!(&output.binary[..12], &[7; 12]);
+    assert_eq!(doc["bufferViews"].as_array().unwrap().len(), 3);
+    let ktx = Ktx2Texture::from_bytes(image_data(doc, &output.binary, 1)).unwrap();
+    let image = etc1s::decode(&ktx, 0).unwrap();
+    assert_eq!(image.dimensions(), (4, 4));
+    assert!(image.get_pixel(1, 2).0.iter().zip([10, 20, 30, 255]).all(|(&a, b)| a.abs_diff(b) <= 4));
+}
+
+#[test]
+fn failed_images_keep_their_fallback() {
+    let (mut doc, binaries) = glb();
+    let uastc = || Params { ktx_codec: KtxCodec::Uastc, ..params() };
+    let keep_original = Params { on_encode_failure: EncodeFailurePolicy::KeepOriginal, ..uastc() };
+    let mut jobs = get_reencode_jobs(Input { gltf_json: &mut doc, binaries: &binaries }, uastc()).unwrap();
+    let output = execute_reencode_jobs(&mut jobs, &mut doc, binaries, &keep_original).unwrap();
+
+    // Nothing can encode UASTC, so the texture keeps only its downscaled PNG
+    assert_eq!(jobs.warnings.iter().map(|warning| warning.code).collect::<Vec<_>>(), ["encode_failed"]);
+    let doc = &output.gltf_json;
+    assert_eq!(doc["textures"], json!([{ "source": 0 }]));
+    assert_eq!(doc["images"].as_array().unwrap().len(), 2);
+    assert_eq!(doc["images"][0]["mimeType"], "image/png");
+    assert!(doc.get("extensionsUsed").is_none());
+    let image = image::load_from_memory_with_format(image_data(doc, &output.binary, 0), image::ImageFormat::Png).unwrap().to_rgba8();
+    assert_eq!(image.dimensions(), (4, 4));
+    assert_eq!(image.get_pixel(1, 2), &Rgba([10, 20, 30, 255]));
+}
+
+#[test]
+fn encode_failures_abort_by_default() {
+    let (mut doc, binaries) = glb();
+    let uastc = || Params { ktx_codec: KtxCodec::Uastc, ..params() };
+    let mut jobs = get_reencode_jobs(Input { gltf_json: &mut doc, binaries: &binaries }, uastc()).unwrap();
+    let e = execute_reencode_jobs(&mut jobs, &mut doc, binaries, &uastc()).err().unwrap();
+    assert_eq!(e.code().as_str(), "encoder_unavailable");
+    assert_eq!(e.json_pointer(), Some("/images/1"));
+}
+
+#[test]
+fn external_buffers_become_the_binary_chunk() {
+    let (mut doc, mut binaries) = glb();
+    doc["buffers"][0]["uri"] = json!("scene.bin");
+    binaries = HashMap::from([(Some("scene.bin".to_string()), binaries.remove(&None).unwrap())]);
+    let keep_original = Params { on_encode_failure: EncodeFailurePolicy::KeepOriginal, ..params() };
+    let mut jobs = get_reencode_jobs(Input { gltf_json: &mut doc, binaries: &binaries }, params()).unwrap();
+    let output = execute_reencode_jobs(&mut jobs, &mut doc, binaries, &keep_original).unwrap();
+    assert!(output.gltf_json["buffers"][0].get("uri").is_none());
+    assert_eq!(&output.binary[..12], &[7; 12]);
+    assert!(output.external_binaries.is_empty());
+}
+
+#[test]
+fn data_uri_buffers_become_the_binary_chunk() {
+    use base64::prelude::*;
+    let (mut doc, mut binaries) = glb();
+    doc["buffers"][0]["uri"] = json!(format!("data:application/octet-stream;base64,{}", BASE64_STANDARD.encode(binaries.remove(&None).unwrap())));
+    let keep_original = Params { on_encode_failure: EncodeFailurePolicy::KeepOriginal, ..params() };
+    let mut jobs = get_reencode_jobs(Input { gltf_json: &mut doc, binaries: &binaries }, params()).unwrap();
+    let output = execute_reencode_jobs(&mut jobs, &mut doc, binaries, &keep_original).unwrap();
+    assert!(output.gltf_json["buffers"][0].get("uri").is_none());
+    assert_eq!(&output.binary[..12], &[7; 12]);
+}
+
+/// Two textures, and an image no texture uses, with images referenced from the document's extras.
+fn referencing_doc(extras: serde_json::Value) -> (GltfDoc, Vec<Vec<u8>>) {
+    let pngs: Vec<_> = [10, 20, 30].into_iter().map(|value| png(&RgbaImage::from_pixel(4, 4, Rgba([value, value, value, 255])))).collect();
+    let doc = doc(json!({
+        "asset": { "version": "2.0" },
+        "images": pngs.iter().map(|png| json!({ "uri": data_uri(png) })).collect::<Vec<_>>(),
+        "textures": [{ "source": 0 }, { "source": 1 }],
+        "extras": extras,
+    }));
+    (doc, pngs)
+}
+
+fn extras_references() -> ReferenceRegistry {
+    let mut references = ReferenceRegistry::default();
+    references.register("/extras/* -> images".parse().unwrap());
+    references
+}
+
+#[test]
+fn referenced_images_are_kept() {
+    let (mut doc, pngs) = referencing_doc(json!({ "thumb": 0, "normal": 2 }));
+    let params = || Params { references: extras_references(), ..Params::default() };
+    let mut jobs = get_reencode_jobs(Input { gltf_json: &mut doc, binaries: &HashMap::new() }, params()).unwrap();
+    let output = execute_reencode_jobs(&mut jobs, &mut doc, HashMap::new(), &params()).unwrap();
+
+    // Each referenced image is copied unchanged, even the one a texture also uses
+    let doc = &output.gltf_json;
+    let (thumb, normal) = (doc["extras"]["thumb"].as_u64().unwrap() as usize, doc["extras"]["normal"].as_u64().unwrap() as usize);
+    assert_eq!(image_data(doc, &output.binary, thumb), pngs[0]);
+    assert_eq!(image_data(doc, &output.binary, normal), pngs[2]);
+    assert_eq!(doc["images"][normal]["mimeType"], "image/png");
+    // A fallback and a KTX2 image for each texture, and the two copies
+    assert_eq!(doc["images"].as_array().unwrap().len(), 6);
+}
+
+#[test]
+fn unmappable_image_references_are_errors() {
+    let (mut doc, _) = referencing_doc(json!({ "missing": 3 }));
+    let e = plan(&mut doc, Params { references: extras_references(), ..Params::default() }).err().unwrap();
+    assert_eq!(e.code().as_str(), "index_out_of_bounds");
+
+    // Planned without knowing about the reference, so nothing keeps the image
+    let (mut doc, _) = referencing_doc(json!({ "normal": 2 }));
+    let mut jobs = plan(&mut doc, Params::default()).unwrap();
+    let params = Params { references: extras_references(), ..Params::default() };
+    let e = execute_reencode_jobs(&mut jobs, &mut doc, HashMap::new(), &params).err().unwrap();
+    assert_eq!(e.code().as_str(), "image_reference_not_planned");
+}
@@ -0,0 +1,84 @@
+use std::path::Path;
+
+use gltf_ktxer::{
+    external_validate::{into_findings, parse_report, run_validator, Severity, ValidatorMessage},
+    report::error_json,
+    Error,
+};
+use serde_json::json;
+
+fn report() -> serde_json::Value {
+    json!({
+        "uri": "model.glb",
+        "issues": {
+            "numErrors": 1,
+            "numWarnings": 1,
+            "numInfos": 1,
+            "numHints": 0,
+            "messages": [
+                { "code": "ACCESSOR_MIN_MISMATCH", "message": "Declared minimum value for this component (0) does not match actual minimum (-1).", "severity": 0, "pointer": "/accessors/2/min/0" },
+                { "code": "UNUSED_OBJECT", "message": "This object may be unused.", "severity": 2, "pointer": "/textures/1" },
+                { "code": "BUFFER_VIEW_TOO_LONG", "message": "BufferView does not fit buffer (0) byteLength (4).", "severity": 1, "offset": 12 }
+            ],
+            "truncated": false
+        }
+    })
+}
+
+#[test]
+fn reports_are_parsed() {
+    let messages = parse_report(&report()).unwrap();
+    let summary: Vec<_> = messages.iter().map(|m| (m.code.as_str(), m.severity, m.json_pointer.as_str())).collect();
+    assert_eq!(summary, [
+        ("ACCESSOR_MIN_MISMATCH", Severity::Error, "/accessors/2/min/0"),
+        ("UNUSED_OBJECT", Severity::Information, "/textures/1"),
+        ("BUFFER_VIEW_TOO_LONG", Severity::Warning, ""),
+    ]);
+
+    let e = parse_report(&json!({ "issues": {} })).unwrap_err();
+    assert_eq!(e.code().as_str(), "external_validator_unavailable");
+}
+
+#[test]
+fn errors_fail_and_warnings_are_merged() {
+    let (error, warnings) = into_findings(parse_report(&report()).unwrap());
+    let error = error.unwrap();
+    assert!(matches!(&error, Error::ExternalValidation(errors) if errors.len() == 1));
+    assert_eq!(error_json(&error, None)["violations"], json!([
+        { "json_pointer": "/accessors/2/min/0", "message": "Declared minimum value for this component (0) does not match actual minimum (-1).", "code": "ACCESSOR_MIN_MISMATCH" }
+    ]));
+
+    // Information and hints are left out
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].code, "external_validator_warning");
+    assert_eq!(warnings[0].message, "glTF-Validator: BufferView does not fit buffer (0) byteLength (4). (BUFFER_VIEW_TOO_LONG)");
+
+    let clean = ValidatorMessage { code: "UNUSED_OBJECT".to_string(), message: String::new(), severity: Severity::Hint, json_pointer: String::new() };
+    let (error, warnings) = into_findings(vec![clean]);
+    assert!(error.is_none() && warnings.is_empty());
+}
+
+#[test]
+fn missing_validator_is_reported() {
+    let e = run_validator("gltf-ktxer-no-such-validator", Path::new("model.glb")).unwrap_err();
+    assert_eq!(e.code().as_str(), "external_validator_unavailable");
+    assert!(e.to_string().contains("wasn't found"), "{e}");
+    assert_eq!(run_validator("  ", Path::new("model.glb")).unwrap_err().code().as_str(), "external_validator_unavailable");
+}
+
+#[cfg(unix)]
+#[test]
+fn configured_command_is_run() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("external_validate");
+    std::fs::create_dir_all(&dir).unwrap();
+    let report_path = dir.join("report.json");
+    std::fs::write(&report_path, report().to_string()).unwrap();
+
+    // Prints the report whatever it's asked to validate
+    let command = format!("sh -c 'cat \"{}\"'", report_path.display());
+    let messages = run_validator(&command, Path::new("model.glb")).unwrap();
+    assert_eq!(messages.len(), 3);
+
+    let e = run_validator("sh -c 'echo oops >&2'", Path::new("model.glb")).unwrap_err();
+    assert!(e.to_string().contains("oops"), "{e}");
+}
@@ -0,0 +1,240 @@
+mod common;
+
+use std::{
+    num::NonZeroU8,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use common::{blank_png_uri, doc, plan};
+use gltf_ktxer::{
+    edit::texture_ktx_source,
+    fallback::{check_abandoned, encode_with_retries, encode_with_time_limit, finish_jobs, is_high_effort, time_limit, Degradation, EncodeFailurePolicy, Encoded, DEFAULT_RETRY_LADDER},
+    tuning::EncodeEffort,
+    Error, ImageReencodeFormat, ImageReencodeJob, KtxCodec, Params, ReencodeJobs, Result,
+};
+use serde_json::json;
+
+fn jobs() -> ReencodeJobs {
+    let mut doc = doc(json!({
+        "asset": { "version": "2.0" },
+        "images": [{ "uri": blank_png_uri(4, 4) }, { "uri": blank_png_uri(8, 8) }],
+        "textures": [{ "source": 0 }, { "source": 1 }],
+    }));
+    plan(&mut doc, Params::default()).unwrap()
+}
+
+/// Every job succeeding apart from the KTX2 image of the first texture.
+fn results(jobs: &ReencodeJobs) -> (usize, Vec<Result<Encoded>>) {
+    let failed = texture_ktx_source(&jobs.new_textures[0]).unwrap().raw_idx();
+    let results = (0..jobs.new_images.len()).map(|idx| if idx == failed { Err(Error::EncoderUnavailable("ETC1S")) } else { Ok(vec![idx as u8].into()) }).collect();
+    (failed, results)
+}
+
+#[test]
+fn failures_abort_by_default() {
+    let mut jobs = jobs();
+    let (failed, results) = results(&jobs);
+    let e = finish_jobs(&mut jobs, results, Params::default().on_encode_failure).unwrap_err();
+    assert_eq!(e.code().as_str(), "encoder_unavailable");
+    assert_eq!(e.json_pointer(), Some(format!("/images/{failed}").as_str()));
+}
+
+#[test]
+fn failed_textures_can_keep_their_original() {
+    let mut jobs = jobs();
+    let (failed, results) = results(&jobs);
+    let outputs = finish_jobs(&mut jobs, results, EncodeFailurePolicy::KeepOriginal).unwrap();
+
+    assert_eq!(outputs[failed], jobs.new_images[failed].source.data);
+    assert_eq!(jobs.new_images[failed].reencode_as, ImageReencodeFormat::Copy);
+    assert!(texture_ktx_source(&jobs.new_textures[0]).is_none());
+    assert!(jobs.new_textures[0].source.is_defined());
+    // The other texture is untouched
+    let other = texture_ktx_source(&jobs.new_textures[1]).unwrap().raw_idx();
+    assert_eq!(outputs[other], [other as u8]);
+
+    let [warning] = jobs.warnings.as_slice() else { panic!("expected one warning, got {:?}", jobs.warnings) };
+    assert_eq!(warning.code, "encode_failed");
+    assert_eq!(warning.json_pointer, format!("/images/{failed}"));
+    assert_eq!(warning.message, "encoding failed (the ETC1S encoder isn't available yet), so the original image is kept");
+}
+
+#[test]
+fn policies_parse() {
+    for policy in [EncodeFailurePolicy::Abort, EncodeFailurePolicy::KeepOriginal] {
+        assert_eq!(policy.to_string().parse(), Ok(policy));
+    }
+    assert!("retry".parse::<EncodeFailurePolicy>().is_err());
+}
+
+#[test]
+fn failures_retry_down_the_ladder() {
+    let mut jobs = jobs();
+    let failed = texture_ktx_source(&jobs.new_textures[1]).unwrap().raw_idx();
+    if let ImageReencodeFormat::Ktx { basis_compression_quality, .. } = &mut jobs.new_images[failed].reencode_as {
+        *basis_compression_quality = NonZeroU8::new(255);
+    }
+    // An encoder which only copes with images up to 4 pixels wide
+    let encode = |job: &ImageReencodeJob| match job.max_dimension {
+        Some(max_dimension) if max_dimension <= 4 => Ok(vec![max_dimension as u8]),
+        _ => Err(Error::Ktx2Malformed("too large")),
+    };
+    let retried = encode_with_retries(&jobs.new_images[failed], DEFAULT_RETRY_LADDER, encode).unwrap();
+    assert_eq!(retried, Encoded { data: vec![4], degradations: vec![Degradation::DefaultQuality, Degradation::HalveResolution], timed_out: false });
+
+    // Nothing on the ladder helps an unavailable encoder
+    let e = encode_with_retries(&jobs.new_images[failed], DEFAULT_RETRY_LADDER, |_| Err::<Vec<u8>, _>(Error::EncoderUnavailable("ETC1S"))).unwrap_err();
+    assert_eq!(e.code().as_str(), "encoder_unavailable");
+    // Every step failing gives the original error
+    let e = encode_with_retries(&jobs.new_images[failed], DEFAULT_RETRY_LADDER, |job| Err::<Vec<u8>, _>(Error::LimitExceeded(format!("{:?}", job.max_dimension)))).unwrap_err();
+    assert_eq!(e.to_string(), "None");
+
+    let results = (0..jobs.new_images.len()).map(|idx| Ok(if idx == failed { retried.clone() } else { vec![].into() })).collect();
+    finish_jobs(&mut jobs, results, EncodeFailurePolicy::Abort).unwrap();
+    let job = &jobs.new_images[failed];
+    assert_eq!(job.max_dimension, Some(4));
+    assert!(matches!(job.reencode_as, ImageReencodeFormat::Ktx { basis_compression_quality: None, codec: KtxCodec::Etc1s, .. }));
+    let [warning] = jobs.warnings.as_slice() else { panic!("expected one warning, got {:?}", jobs.warnings) };
+    assert_eq!((warning.code, warning.json_pointer.clone()), ("encode_degraded", format!("/images/{failed}")));
+    assert_eq!(warning.message, "encoding failed, and succeeded after retrying with default-quality, halve-resolution");
+}
+
+#[test]
+fn degradations_only_apply_where_they_make_a_difference() {
+    let jobs = jobs();
+    let ktx = &jobs.new_images[texture_ktx_source(&jobs.new_textures[0]).unwrap().raw_idx()];
+    // Planned at the default quality already
+    assert!(Degradation::DefaultQuality.apply(ktx).is_none());
+    let uastc = Degradation::SwitchToUastc.apply(ktx).unwrap();
+    assert!(matches!(uastc.reencode_as, ImageReencodeFormat::Ktx { codec: KtxCodec::Uastc, .. }));
+    assert!(Degradation::SwitchToUastc.apply(&uastc).is_none());
+
+    let mut job = Degradation::HalveResolution.apply(ktx).unwrap();
+    assert_eq!(job.max_dimension, Some(2));
+    job = Degradation::HalveResolution.apply(&job).unwrap();
+    assert_eq!(job.max_dimension, Some(1));
+    assert!(Degradation::HalveResolution.apply(&job).is_none());
+
+    let lower = Degradation::LowerEffort.apply(ktx).unwrap();
+    assert!(matches!(lower.reencode_as, ImageReencodeFormat::Ktx { effort: Some(EncodeEffort::Fast), .. }));
+    assert!(Degradation::LowerEffort.apply(&lower).is_none());
+
+    for step in DEFAULT_RETRY_LADDER.iter().chain([&Degradation::LowerEffort]) {
+        assert_eq!(step.to_string().parse(), Ok(*step));
+    }
+}
+
+#[test]
+fn slow_high_effort_encodes_fall_back_to_the_default_quality() {
+    let mut jobs = jobs();
+    let slow = texture_ktx_source(&jobs.new_textures[1]).unwrap().raw_idx();
+    if let ImageReencodeFormat::Ktx { basis_compression_quality, .. } = &mut jobs.new_images[slow].reencode_as {
+        *basis_compression_quality = NonZeroU8::new(255);
+    }
+    // An encoder which takes far too long at any quality but the default
+    let encode = |job: &ImageReencodeJob| match job.reencode_as {
+        ImageReencodeFormat::Ktx { basis_compression_quality: Some(_), .. } => {
+            std::thread::sleep(Duration::from_secs(5));
+            Ok(vec![1])
+        }
+        _ => Ok(vec![2]),
+    };
+    let limit = Some(Duration::from_millis(20));
+    let encoded = encode_with_time_limit(&jobs.new_images[slow], DEFAULT_RETRY_LADDER, limit, encode).unwrap();
+    assert_eq!(encoded, Encoded { data: vec![2], degradations: vec![Degradation::LowerEffort, Degradation::DefaultQuality], timed_out: true });
+
+    // Jobs at the default quality aren't limited
+    let fast = texture_ktx_source(&jobs.new_textures[0]).unwrap().raw_idx();
+    assert_eq!(encode_with_time_limit(&jobs.new_images[fast], DEFAULT_RETRY_LADDER, limit, encode).unwrap(), vec![2].into());
+
+    let results = (0..jobs.new_images.len()).map(|idx| Ok(if idx == slow { encoded.clone() } else { vec![].into() })).collect();
+    finish_jobs(&mut jobs, results, EncodeFailurePolicy::Abort).unwrap();
+    assert!(matches!(jobs.new_images[slow].reencode_as, ImageReencodeFormat::Ktx { basis_compression_quality: None, effort: Some(EncodeEffort::Fast), .. }));
+    let [warning] = jobs.warnings.as_slice() else { panic!("expected one warning, got {:?}", jobs.warnings) };
+    assert_eq!(warning.message, "encoding took longer than the time limit, and succeeded after retrying with lower-effort, default-quality");
+}
+
+/// The KTX2 job of the first texture, with `quality` and `effort`.
+fn ktx_job(quality: Option<u8>, effort: Option<EncodeEffort>) -> ImageReencodeJob {
+    let jobs = jobs();
+    let mut job = jobs.new_images[texture_ktx_source(&jobs.new_textures[0]).unwrap().raw_idx()].clone();
+    if let ImageReencodeFormat::Ktx { basis_compression_quality, effort: job_effort, .. } = &mut job.reencode_as {
+        *basis_compression_quality = quality.and_then(NonZeroU8::new);
+        *job_effort = effort;
+    }
+    job
+}
+
+#[test]
+fn high_effort_means_above_the_default_quality_or_thorough() {
+    assert!(!is_high_effort(&ktx_job(None, None)));
+    assert!(!is_high_effort(&ktx_job(Some(1), None)));
+    assert!(!is_high_effort(&ktx_job(Some(128), Some(EncodeEffort::Balanced))));
+    assert!(is_high_effort(&ktx_job(Some(129), None)));
+    assert!(is_high_effort(&ktx_job(None, Some(EncodeEffort::Thorough))));
+}
+
+#[test]
+fn slow_thorough_encodes_fall_back_to_less_effort() {
+    let encode = |job: &ImageReencodeJob| match job.reencode_as {
+        ImageReencodeFormat::Ktx { effort: Some(EncodeEffort::Thorough), .. } => {
+            std::thread::sleep(Duration::from_secs(5));
+            Ok(vec![1])
+        }
+        _ => Ok(vec![2]),
+    };
+    // Lowering the quality to the default would make a low quality encode slower, so it's left alone
+    let job = ktx_job(Some(10), Some(EncodeEffort::Thorough));
+    let encoded = encode_with_time_limit(&job, DEFAULT_RETRY_LADDER, Some(Duration::from_millis(20)), encode).unwrap();
+    assert_eq!(encoded, Encoded { data: vec![2], degradations: vec![Degradation::LowerEffort], timed_out: true });
+}
+
+#[test]
+fn time_limits_must_be_durations() {
+    assert_eq!(time_limit(None).unwrap(), None);
+    assert_eq!(time_limit(Some(1.5)).unwrap(), Some(Duration::from_millis(1500)));
+    for seconds in [-1.0, f64::NAN, f64::INFINITY] {
+        assert!(matches!(time_limit(Some(seconds)), Err(Error::BadTimeLimit(_))), "{seconds}");
+    }
+}
+
+#[test]
+fn abandoned_encodes_stop_at_their_next_check() {
+    let stopped = Arc::new(AtomicBool::new(false));
+    let encoder_stopped = stopped.clone();
+    // An encoder which never finishes unless it's abandoned
+    let encode = move |job: &ImageReencodeJob| {
+        if matches!(job.reencode_as, ImageReencodeFormat::Ktx { effort: Some(EncodeEffort::Thorough), .. }) {
+            while check_abandoned().is_ok() {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            encoder_stopped.store(true, Ordering::Relaxed);
+            return Err(Error::EncodeAbandoned);
+        }
+        Ok(vec![2])
+    };
+    let job = ktx_job(None, Some(EncodeEffort::Thorough));
+    let encoded = encode_with_time_limit(&job, DEFAULT_RETRY_LADDER, Some(Duration::from_millis(20)), encode).unwrap();
+    assert!(encoded.timed_out);
+    for _ in 0..1000 {
+        if stopped.load(Ordering::Relaxed) {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    assert!(stopped.load(Ordering::Relaxed), "the abandoned encode kept running");
+    // Outside a time-limited encode there's nothing to abandon
+    assert!(check_abandoned().is_ok());
+}
+
+#[test]
+fn encoder_panics_keep_their_payload() {
+    let job = ktx_job(None, Some(EncodeEffort::Thorough));
+    let encode = |_: &ImageReencodeJob| -> Result<Vec<u8>> { panic!("the encoder hit a bug") };
+    let payload = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| encode_with_time_limit(&job, DEFAULT_RETRY_LADDER, Some(Duration::from_secs(60)), encode))).unwrap_err();
+    assert_eq!(payload.downcast_ref::<&str>(), Some(&"the encoder hit a bug"));
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use std::path::{Component, Path};
+
+use common::doc;
+use gltf_ktxer::filenames::{file_uri, image_file_names, sanitize_stem, FileNamer, MAX_STEM_BYTES};
+use serde_json::json;
+
+#[test]
+fn names_are_single_portable_components() {
+    let cases = [
+        ("wood", Some("wood")),
+        ("../../etc/passwd", Some("etc_passwd")),
+        ("C:\\Users\\me\\wood.png", Some("C_Users_me_wood.png")),
+        ("a/b", Some("a_b")),
+        ("what? <this> \"is\" | it*", Some("what_this_is_it")),
+        ("..", None),
+        ("  . ", None),
+        ("", None),
+        (".hidden", Some("hidden")),
+        ("trailing. ", Some("trailing")),
+        ("tab\there\nnewline", Some("tab_here_newline")),
+        ("evil\u{202E}gnp.exe", Some("evil_gnp.exe")),
+        ("木材 の 色", Some("木材_の_色")),
+        ("Ünïcödé", Some("Ünïcödé")),
+        ("CON", Some("CON_")),
+        ("con.tar.gz", Some("con_.tar.gz")),
+        ("Lpt9", Some("Lpt9_")),
+        ("COM¹", Some("COM¹_")),
+        ("console", Some("console")),
+    ];
+    for (name, expected) in cases {
+        let stem = sanitize_stem(name);
+        assert_eq!(stem.as_deref(), expected, "{name:?}");
+        if let Some(stem) = stem {
+            let components: Vec<_> = Path::new(&stem).components().collect();
+            assert!(matches!(components[..], [Component::Normal(_)]), "{name:?} became {stem:?}");
+        }
+    }
+}
+
+#[test]
+fn long_names_are_cut_between_characters() {
+    let stem = sanitize_stem(&"é".repeat(200)).unwrap();
+    assert!(stem.len() <= MAX_STEM_BYTES);
+    assert_eq!(stem, "é".repeat(MAX_STEM_BYTES / 2));
+
+    // Cutting mustn't leave a trailing dot
+    let stem = sanitize_stem(&format!("{}.{}", "a".repeat(MAX_STEM_BYTES - 1), "b".repeat(10))).unwrap();
+    assert_eq!(stem, "a".repeat(MAX_STEM_BYTES - 1));
+}
+
+#[test]
+fn clashing_names_get_stable_suffixes() {
+    let mut namer = FileNamer::default();
+    assert_eq!(namer.name(Some("wood"), "image0", 0, "basis"), "wood.basis");
+    assert_eq!(namer.name(Some("Wood"), "image1", 1, "basis"), "Wood-1.basis");
+    assert_eq!(namer.name(Some("wood?"), "image2", 2, "basis"), "wood-2.basis");
+    assert_eq!(namer.name(Some("wood-2"), "image3", 3, "basis"), "wood-2-3.basis");
+    assert_eq!(namer.name(Some("???"), "image4", 4, "basis"), "image4.basis");
+    assert_eq!(namer.name(Some("wood-2-3"), "image5", 5, "basis"), "wood-2-3-5.basis");
+}
+
+#[test]
+fn images_are_named_after_their_names_or_uris() {
+    let doc = doc(json!({
+        "asset": { "version": "2.0" },
+        "images": [
+            { "name": "Base Color", "bufferView": 0, "mimeType": "image/png" },
+            { "uri": "textures/base%20color.png" },
+            { "uri": "data:image/png;base64,AAAA" },
+            { "name": "NUL", "bufferView": 1, "mimeType": "image/png" },
+            { "name": "/", "uri": "normal.png" },
+            { "bufferView": 2, "mimeType": "image/png" },
+        ],
+    }));
+    let names = image_file_names(&doc, "ktx2").unwrap();
+    assert_eq!(names, ["Base_Color.ktx2", "base_color-1.ktx2", "image2.ktx2", "NUL_.ktx2", "normal.ktx2", "image5.ktx2"]);
+    // The same document always gets the same names
+    assert_eq!(image_file_names(&doc, "ktx2").unwrap(), names);
+}
+
+#[test]
+fn uris_escape_what_uris_cant_hold() {
+    assert_eq!(file_uri("Base_Color.ktx2"), "Base_Color.ktx2");
+    assert_eq!(file_uri("木.basis"), "%E6%9C%A8.basis");
+    assert_eq!(file_uri("a b"), "a%20b");
+}
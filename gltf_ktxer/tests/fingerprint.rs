@@ -0,0 +1,98 @@
+mod common;
+
+use std::{collections::HashMap, sync::Arc};
+
+use base64::prelude::*;
+use common::{data_uri, doc, plan, png_uri};
+use gltf_ktxer::{
+    fingerprint::{changed_jobs, record_fingerprints, PreviousOutput, FINGERPRINTS_EXTRAS_KEY},
+    execute_reencode_jobs, get_reencode_jobs,
+    gltf::GltfDoc,
+    Input, Params, ReencodeJobs,
+};
+use image::{Rgba, RgbaImage};
+use serde_json::json;
+
+/// The jobs for a document with one texture for each of `sources`.
+fn jobs_for(sources: &[&[u8]]) -> ReencodeJobs {
+    let mut doc = doc(json!({
+        "asset": { "version": "2.0" },
+        "images": sources.iter().map(|source| json!({ "uri": data_uri(source), "mimeType": "image/png" })).collect::<Vec<_>>(),
+        "textures": (0..sources.len()).map(|idx| json!({ "source": idx })).collect::<Vec<_>>(),
+    }));
+    plan(&mut doc, Params::default()).unwrap()
+}
+
+/// An output with an image holding `[idx]` for each of `jobs`, fingerprinted.
+fn output(jobs: &ReencodeJobs) -> GltfDoc {
+    let mut doc = doc(json!({
+        "asset": { "version": "2.0", "extras": { "note": "kept" } },
+        "images": (0..jobs.new_images.len()).map(|idx| json!({ "uri": data_uri(&[idx as u8]) })).collect::<Vec<_>>(),
+    }));
+    record_fingerprints(&mut doc, &jobs.new_images).unwrap();
+    doc
+}
+
+#[test]
+fn only_changed_sources_are_reencoded() {
+    let before = jobs_for(&[b"\x89PNG wood", b"\x89PNG metal"]);
+    let mut previous_doc = output(&before);
+    assert_eq!(previous_doc["asset"]["extras"]["note"], "kept");
+    assert_eq!(previous_doc["asset"]["extras"][FINGERPRINTS_EXTRAS_KEY][1], before.new_images[1].hash_extras());
+
+    let binaries = HashMap::new();
+    let previous = PreviousOutput::read(&Input { gltf_json: &mut previous_doc, binaries: &binaries }).unwrap();
+    assert_eq!(previous.len(), before.new_images.len());
+    assert!(changed_jobs(&before.new_images, &previous).is_empty());
+
+    // The metal texture changed, and a new texture was added in front of the others
+    let after = jobs_for(&[b"\x89PNG stone", b"\x89PNG wood", b"\x89PNG metal v2"]);
+    let [stone, stone_ktx, wood, wood_ktx, metal, metal_ktx] = after.new_images.as_slice() else {
+        panic!("expected two jobs per texture");
+    };
+    assert_eq!(changed_jobs(&after.new_images, &previous), [0, 1, 4, 5]);
+    for job in [stone, stone_ktx, metal, metal_ktx] {
+        assert_eq!(previous.reusable(job), None);
+    }
+    assert_eq!(previous.reusable(wood), Some(&[0][..]));
+    assert_eq!(previous.reusable(wood_ktx), Some(&[1][..]));
+}
+
+#[test]
+fn outputs_without_fingerprints_reuse_nothing() {
+    let jobs = jobs_for(&[b"\x89PNG wood"]);
+    let mut previous_doc = doc(json!({ "asset": { "version": "2.0" }, "images": [{ "uri": data_uri(b"old") }] }));
+    let binaries = HashMap::new();
+    let previous = PreviousOutput::read(&Input { gltf_json: &mut previous_doc, binaries: &binaries }).unwrap();
+    assert!(previous.is_empty());
+    assert_eq!(changed_jobs(&jobs.new_images, &previous), [0, 1]);
+}
+
+#[test]
+fn execution_records_fingerprints_and_reuses_previous_images() {
+    let source = doc(json!({
+        "asset": { "version": "2.0" },
+        "images": [{ "uri": png_uri(&RgbaImage::from_pixel(8, 8, Rgba([10, 20, 30, 255]))), "mimeType": "image/png" }],
+        "textures": [{ "source": 0 }],
+    }));
+    let convert = |params: &dyn Fn() -> Params| {
+        let (mut doc, binaries) = (source.clone(), HashMap::new());
+        let mut jobs = get_reencode_jobs(Input { gltf_json: &mut doc, binaries: &binaries }, params()).unwrap();
+        execute_reencode_jobs(&mut jobs, &mut doc, binaries, &params()).unwrap()
+    };
+
+    let first = convert(&|| Params { record_fingerprints: true, ..Params::default() });
+    let fingerprints = &first.gltf_json["asset"]["extras"][FINGERPRINTS_EXTRAS_KEY];
+    assert_eq!(fingerprints.as_array().unwrap().len(), 2);
+
+    // Swap the KTX2 image for a marker, which the next run should pass through rather than encode again
+    let mut previous_doc = first.gltf_json.clone();
+    previous_doc["images"][1] = json!({ "uri": format!("data:image/ktx2;base64,{}", BASE64_STANDARD.encode(b"marker")) });
+    let binaries = HashMap::from([(None, first.binary.clone())]);
+    let previous = Arc::new(PreviousOutput::read(&Input { gltf_json: &mut previous_doc, binaries: &binaries }).unwrap());
+    let second = convert(&|| Params { record_fingerprints: true, reuse_from: Some(previous.clone()), ..Params::default() });
+    assert_eq!(&second.gltf_json["asset"]["extras"][FINGERPRINTS_EXTRAS_KEY], fingerprints);
+    let view = &second.gltf_json["bufferViews"][second.gltf_json["images"][1]["bufferView"].as_u64().unwrap() as usize];
+    let offset = view.get("byteOffset").and_then(|offset| offset.as_u64()).unwrap_or(0) as usize;
+    assert_eq!(&second.binary[offset..offset + view["byteLength"].as_u64().unwrap() as usize], b"marker");
+}
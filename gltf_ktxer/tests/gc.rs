@@ -0,0 +1,232 @@
+//! Check garbage collection only removes objects unreachable from every scene, and never touches the scene graph.
+
+mod common;
+
+use std::collections::BTreeMap;
+
+use common::doc;
+use gltf_ktxer::{edit::{self, CustomReference, ReferenceRegistry}, gc, gltf::{GltfDoc, GltfMesh}};
+use serde_json::json;
+
+/// Scene 0 is the default and has no meshes. Scene 1 holds a two-node hierarchy whose child draws with material 1,
+/// which samples texture 1 with image 1 and its KTX2 copy, image 2.
+/// Material 0, texture 0 and image 0 are garbage.
+fn multi_scene_doc() -> GltfDoc {
+    doc(json!({
+        "asset": { "version": "2.0" },
+        "scene": 0,
+        "scenes": [{ "nodes": [0] }, { "nodes": [1] }],
+        "nodes": [
+            { "name": "empty" },
+            { "name": "parent", "children": [2] },
+            { "name": "child", "mesh": 0 },
+        ],
+        "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 }, "material": 1 }] }],
+        "accessors": [{ "bufferView": 0, "componentType": 5126, "count": 1, "type": "VEC3" }],
+        "buffers": [{ "byteLength": 32 }],
+        "bufferViews": [
+            { "buffer": 0, "byteLength": 12 },
+            { "buffer": 0, "byteOffset": 12, "byteLength": 8 },
+            { "buffer": 0, "byteOffset": 20, "byteLength": 12 },
+        ],
+        "materials": [
+            { "name": "unused", "emissiveTexture": { "index": 0 } },
+            { "name": "used", "pbrMetallicRoughness": { "baseColorTexture": { "index": 1 } } },
+        ],
+        "textures": [
+            { "source": 0 },
+            { "source": 1, "extensions": { "KHR_texture_basisu": { "source": 2 } } },
+        ],
+        "images": [
+            { "uri": "unused.png" },
+            { "bufferView": 1, "mimeType": "image/png" },
+            { "bufferView": 2, "mimeType": "image/ktx2" },
+        ],
+    }))
+}
+
+#[test]
+fn objects_used_by_a_non_default_scene_are_kept() {
+    let mut doc = multi_scene_doc();
+    let removed = gc::collect_garbage(&mut doc, &ReferenceRegistry::default()).unwrap();
+    assert_eq!(removed, BTreeMap::from([("images", vec![0]), ("materials", vec![0]), ("textures", vec![0])]));
+
+    assert_eq!(doc["materials"], json!([{ "name": "used", "pbrMetallicRoughness": { "baseColorTexture": { "index": 0 } } }]));
+    assert_eq!(doc["textures"], json!([{ "source": 0, "extensions": { "KHR_texture_basisu": { "source": 1 } } }]));
+    assert_eq!(doc["images"].as_array().unwrap().len(), 2);
+    assert_eq!(doc["meshes"][0]["primitives"][0]["material"], 0);
+}
+
+#[test]
+fn scene_graph_is_untouched() {
+    let original = multi_scene_doc();
+    let mut doc = original.clone();
+    gc::collect_garbage(&mut doc, &ReferenceRegistry::default()).unwrap();
+    // Meshes only have their material indices renumbered
+    for key in ["scene", "scenes", "nodes", "accessors"] {
+        assert_eq!(doc[key], original[key], "{key} changed");
+    }
+
+    // Collecting again finds nothing more to remove
+    assert!(gc::collect_garbage(&mut doc, &ReferenceRegistry::default()).unwrap().is_empty());
+}
+
+fn morph_target_doc() -> GltfDoc {
+    doc(json!({
+        "asset": { "version": "2.0" },
+        "scenes": [{ "nodes": [0] }],
+        "nodes": [{ "mesh": 0 }],
+        "meshes": [{
+            "primitives": [{
+                "attributes": { "POSITION": 1 },
+                "indices": 3,
+                "targets": [{ "POSITION": 0 }, { "POSITION": 2, "NORMAL": 4 }],
+            }],
+            "weights": [0.0, 0.5],
+        }],
+        "accessors": [
+            { "bufferView": 0, "componentType": 5126, "count": 1, "type": "VEC3" },
+            { "bufferView": 0, "componentType": 5126, "count": 1, "type": "VEC3" },
+            { "bufferView": 1, "componentType": 5126, "count": 1, "type": "VEC3" },
+            { "bufferView": 2, "componentType": 5123, "count": 3, "type": "SCALAR" },
+            { "bufferView": 1, "componentType": 5126, "count": 1, "type": "VEC3" },
+            { "bufferView": 3, "componentType": 5126, "count": 1, "type": "VEC3", "name": "unused" },
+        ],
+        "buffers": [{ "byteLength": 48 }],
+        "bufferViews": [
+            { "buffer": 0, "byteLength": 12 },
+            { "buffer": 0, "byteOffset": 12, "byteLength": 12 },
+            { "buffer": 0, "byteOffset": 24, "byteLength": 12 },
+            { "buffer": 0, "byteOffset": 36, "byteLength": 12 },
+        ],
+    }))
+}
+
+#[test]
+fn morph_target_accessors_are_kept() {
+    let mut doc = morph_target_doc();
+    let removed = gc::collect_garbage(&mut doc, &ReferenceRegistry::default()).unwrap();
+    assert_eq!(removed, BTreeMap::from([("accessors", vec![5]), ("bufferViews", vec![3])]));
+    assert_eq!(doc["meshes"], morph_target_doc()["meshes"]);
+}
+
+#[test]
+fn reference_walker_matches_typed_primitives() {
+    let doc = morph_target_doc();
+    let meshes: Vec<GltfMesh> = serde_json::from_value(doc["meshes"].clone()).unwrap();
+    let mut typed: Vec<usize> = meshes[0].primitives[0].accessors().map(|idx| idx.raw_idx()).collect();
+    typed.sort();
+
+    let mut walked = vec![];
+    edit::for_each_reference_by_holder(&doc, "accessors", &ReferenceRegistry::default(), &mut |holder, reference| {
+        assert_eq!(holder.unwrap().0, "meshes");
+        walked.push(reference.as_u64().unwrap() as usize);
+    })
+    .unwrap();
+    walked.sort();
+    assert_eq!(typed, walked);
+    assert_eq!(typed, [0, 1, 2, 3, 4]);
+}
+
+#[test]
+fn instancing_accessors_are_kept_and_renumbered() {
+    let mut doc = doc(json!({
+        "asset": { "version": "2.0" },
+        "extensionsUsed": ["EXT_mesh_gpu_instancing"],
+        "scenes": [{ "nodes": [0] }],
+        "nodes": [{
+            "mesh": 0,
+            "extensions": { "EXT_mesh_gpu_instancing": { "attributes": { "TRANSLATION": 2, "SCALE": 3 } } },
+        }],
+        "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 } }] }],
+        "accessors": [
+            { "bufferView": 0, "componentType": 5126, "count": 1, "type": "VEC3" },
+            { "bufferView": 0, "componentType": 5126, "count": 1, "type": "VEC3", "name": "unused" },
+            { "bufferView": 1, "componentType": 5126, "count": 2, "type": "VEC3" },
+            { "bufferView": 1, "componentType": 5126, "count": 2, "type": "VEC3" },
+        ],
+        "buffers": [{ "byteLength": 36 }],
+        "bufferViews": [
+            { "buffer": 0, "byteLength": 12 },
+            { "buffer": 0, "byteOffset": 12, "byteLength": 24 },
+        ],
+    }));
+    let removed = gc::collect_garbage(&mut doc, &ReferenceRegistry::default()).unwrap();
+    assert_eq!(removed, BTreeMap::from([("accessors", vec![1])]));
+    assert_eq!(doc["nodes"][0]["extensions"]["EXT_mesh_gpu_instancing"]["attributes"], json!({ "TRANSLATION": 1, "SCALE": 2 }));
+}
+
+#[test]
+fn custom_references_are_kept_and_renumbered() {
+    let original = doc(json!({
+        "asset": { "version": "2.0" },
+        "scenes": [{ "nodes": [0] }],
+        "nodes": [{ "mesh": 0 }],
+        "meshes": [{ "primitives": [{ "attributes": {}, "material": 0 }] }],
+        "materials": [{ "extensions": { "MY_ext": { "imageIndex": 1 } } }],
+        "images": [{ "uri": "unused.png" }, { "uri": "lightmap.png" }],
+        "extensions": { "MY_ext": { "fallbackImages": [1] } },
+    }));
+
+    let mut registry = ReferenceRegistry::default();
+    registry.register("/materials/*/extensions/MY_ext/imageIndex -> images".parse().unwrap());
+    registry.register("/extensions/MY_ext/fallbackImages/* -> images".parse().unwrap());
+    let mut doc = original.clone();
+    let removed = gc::collect_garbage(&mut doc, &registry).unwrap();
+    assert_eq!(removed, BTreeMap::from([("images", vec![0])]));
+    assert_eq!(doc["materials"][0]["extensions"]["MY_ext"]["imageIndex"], 0);
+    assert_eq!(doc["extensions"]["MY_ext"]["fallbackImages"], json!([0]));
+
+    // Without the registry, the extension's image looks unused
+    let mut doc = original.clone();
+    let removed = gc::collect_garbage(&mut doc, &ReferenceRegistry::default()).unwrap();
+    assert_eq!(removed, BTreeMap::from([("images", vec![0, 1])]));
+}
+
+#[test]
+fn bad_reference_patterns() {
+    for pattern in ["materials/*/x -> images", "/materials/*/x", "/materials/*/x -> nonsense", "/materials//x -> images"] {
+        let e = pattern.parse::<CustomReference>().unwrap_err();
+        assert_eq!(e.code().as_str(), "bad_reference_pattern", "{pattern}");
+    }
+    let reference: CustomReference = "/extensions/MY~1ext/index -> nodes".parse().unwrap();
+    assert_eq!(reference.path, ["extensions", "MY/ext", "index"]);
+    assert_eq!(reference.list_name, "nodes");
+    assert!(!reference.root);
+}
+
+#[test]
+fn root_references_are_kept_even_if_their_holder_is_not() {
+    // The engine reads level-of-detail materials from node extras, and a texture from an unused material's extras
+    let original = doc(json!({
+        "asset": { "version": "2.0" },
+        "scenes": [{ "nodes": [0] }],
+        "nodes": [{ "mesh": 0, "extras": { "lod": [2, 3] } }],
+        "meshes": [{ "primitives": [{ "attributes": {}, "material": 0 }] }],
+        "materials": [
+            { "name": "lod0" },
+            { "name": "unused", "extras": { "impostorTexture": 1 } },
+            { "name": "lod1" },
+            { "name": "lod2" },
+        ],
+        "textures": [{ "source": 0 }, { "source": 0 }],
+        "images": [{ "uri": "impostor.png" }],
+    }));
+
+    let mut registry = ReferenceRegistry::default();
+    registry.register_root("/nodes/*/extras/lod/* -> materials".parse().unwrap());
+    registry.register_root("/materials/*/extras/impostorTexture -> textures".parse().unwrap());
+    let mut doc = original.clone();
+    let removed = gc::collect_garbage(&mut doc, &registry).unwrap();
+    assert_eq!(removed, BTreeMap::from([("materials", vec![1]), ("textures", vec![0])]));
+    assert_eq!(doc["nodes"][0]["extras"]["lod"], json!([1, 2]));
+    assert_eq!(doc["textures"], json!([{ "source": 0 }]));
+
+    // As plain references, the node's are still kept as nodes are never removed, but the removed material's texture isn't
+    let mut registry = ReferenceRegistry::default();
+    registry.register("/nodes/*/extras/lod/* -> materials".parse().unwrap());
+    registry.register("/materials/*/extras/impostorTexture -> textures".parse().unwrap());
+    let mut doc = original.clone();
+    let removed = gc::collect_garbage(&mut doc, &registry).unwrap();
+    assert_eq!(removed, BTreeMap::from([("images", vec![0]), ("materials", vec![1]), ("textures", vec![0, 1])]));
+}
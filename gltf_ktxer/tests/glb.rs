@@ -0,0 +1,152 @@
+//! Check GLB output against the container layout in glTF2.0 section 4.4.
+
+mod common;
+
+use common::doc;
+use gltf_ktxer::{
+    glb::{self, JsonFormat, CHUNK_TYPE_BIN, CHUNK_TYPE_JSON, GLB_MAGIC},
+    gltf::GltfDoc,
+};
+use serde_json::json;
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+/// A document with a 3 byte buffer.
+fn with_buffer() -> GltfDoc {
+    doc(json!({
+        "asset": { "version": "2.0" },
+        "buffers": [{ "byteLength": 3 }],
+    }))
+}
+
+#[test]
+fn glb_chunks_are_padded() {
+    let doc = with_buffer();
+    let glb = glb::write(&doc, &[1, 2, 3], JsonFormat::Minified).unwrap();
+
+    assert_eq!(u32_at(&glb, 0), GLB_MAGIC);
+    assert_eq!(u32_at(&glb, 4), 2);
+    assert_eq!(u32_at(&glb, 8) as usize, glb.len());
+
+    let json_len = u32_at(&glb, 12) as usize;
+    assert_eq!(json_len % 4, 0);
+    assert_eq!(u32_at(&glb, 16), CHUNK_TYPE_JSON);
+    let json_chunk = &glb[20..20 + json_len];
+    let json = serde_json::to_vec(&doc).unwrap();
+    assert_eq!(&json_chunk[..json.len()], json);
+    assert!(json_chunk[json.len()..].iter().all(|&b| b == b' '), "JSON chunk is padded with spaces");
+
+    let bin_start = 20 + json_len;
+    assert_eq!(u32_at(&glb, bin_start), 4);
+    assert_eq!(u32_at(&glb, bin_start + 4), CHUNK_TYPE_BIN);
+    assert_eq!(&glb[bin_start + 8..], [1, 2, 3, 0], "BIN chunk is padded with zeros");
+}
+
+#[test]
+fn glb_without_binary_has_no_bin_chunk() {
+    let glb = glb::write(&with_buffer(), &[], JsonFormat::Minified).unwrap();
+    assert_eq!(glb.len(), 20 + u32_at(&glb, 12) as usize);
+}
+
+#[test]
+fn json_formats() {
+    let minified = glb::json_bytes(&with_buffer(), JsonFormat::Minified).unwrap();
+    assert!(!minified.iter().any(u8::is_ascii_whitespace));
+
+    let pretty = glb::json_bytes(&with_buffer(), JsonFormat::Pretty).unwrap();
+    assert!(pretty.contains(&b'\n'));
+    let reparsed: GltfDoc = serde_json::from_slice(&pretty).unwrap();
+    assert_eq!(reparsed, with_buffer());
+
+    let glb = glb::write(&with_buffer(), &[], JsonFormat::Pretty).unwrap();
+    assert_eq!(&glb[20..20 + pretty.len()], pretty);
+}
+
+#[test]
+fn glb_len_overflow_is_an_error() {
+    let doc = with_buffer();
+    let json_len = glb::json_bytes(&doc, JsonFormat::Minified).unwrap().len();
+    let glb = glb::write(&doc, &[1, 2, 3], JsonFormat::Minified).unwrap();
+    assert_eq!(glb::glb_len(json_len, 3).unwrap() as usize, glb.len());
+
+    // The largest binary chunk which still fits alongside the headers and JSON
+    let max_bin_len = (glb::MAX_GLB_LEN - 12 - 8 - 8 - json_len.next_multiple_of(4) as u64) as usize & !3;
+    assert!(glb::glb_len(json_len, max_bin_len).is_ok());
+    let e = glb::glb_len(json_len, max_bin_len + 1).unwrap_err();
+    assert_eq!(e.code().as_str(), "glb_too_large");
+}
+
+#[test]
+fn glb_reads_back() {
+    let glb = glb::write(&with_buffer(), &[1, 2, 3], JsonFormat::Pretty).unwrap();
+    let (doc, bin) = glb::read(&glb).unwrap();
+    assert_eq!(doc, with_buffer());
+    // The padding is part of the chunk
+    assert_eq!(bin, [1, 2, 3, 0]);
+
+    let (_, bin) = glb::read(&glb::write(&doc, &[], JsonFormat::Minified).unwrap()).unwrap();
+    assert!(bin.is_empty());
+
+    for truncated in [&glb[..8], &glb[..glb.len() - 2]] {
+        assert_eq!(glb::read(truncated).unwrap_err().code().as_str(), "glb_malformed");
+    }
+    assert_eq!(glb::read(b"{\"asset\":{}}").unwrap_err().code().as_str(), "glb_malformed");
+}
+
+/// Build a GLB from raw chunks, with `length` in the header if given, or the real length otherwise.
+fn raw_glb(chunks: &[(u32, &[u8])], length: Option<u32>) -> Vec<u8> {
+    let mut glb = vec![];
+    for (chunk_type, data) in chunks {
+        glb.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        glb.extend_from_slice(&chunk_type.to_le_bytes());
+        glb.extend_from_slice(data);
+    }
+    let mut header = vec![];
+    for word in [GLB_MAGIC, 2, length.unwrap_or(12 + glb.len() as u32)] {
+        header.extend_from_slice(&word.to_le_bytes());
+    }
+    header.extend(glb);
+    header
+}
+
+#[test]
+fn glb_headers_are_checked() {
+    let json: &[u8] = br#"{"asset":{"version":"2.0"}}     "#;
+    let error = |glb: &[u8]| glb::read(glb).unwrap_err().to_string();
+
+    let mut version_1 = raw_glb(&[(CHUNK_TYPE_JSON, json)], None);
+    version_1[4] = 1;
+    assert!(error(&version_1).contains("only version 2"));
+    assert!(error(&raw_glb(&[(CHUNK_TYPE_JSON, json)], Some(8))).contains("shorter than the header"));
+
+    // The header says there's more than the file holds, e.g. a download cut off after the JSON chunk
+    let cut_off = raw_glb(&[(CHUNK_TYPE_JSON, json)], Some(12 + 8 + json.len() as u32 + 12));
+    assert!(error(&cut_off).contains("shorter than the length in its header"));
+
+    let mut chunk_header_cut = raw_glb(&[(CHUNK_TYPE_JSON, json)], Some(12 + 8 + json.len() as u32 + 4));
+    chunk_header_cut.extend_from_slice(&[4, 0, 0, 0]);
+    assert!(error(&chunk_header_cut).contains("chunk header is truncated"));
+
+    let mut chunk_cut = raw_glb(&[(CHUNK_TYPE_JSON, json), (CHUNK_TYPE_BIN, &[1, 2, 3, 4])], None);
+    chunk_cut[12..16].copy_from_slice(&100u32.to_le_bytes());
+    assert!(error(&chunk_cut).contains("chunk is truncated"));
+}
+
+#[test]
+fn glb_chunk_order_is_checked() {
+    let json: &[u8] = br#"{"asset":{"version":"2.0"}}     "#;
+    let unknown = u32::from_le_bytes(*b"XTRA");
+    let error = |chunks: &[(u32, &[u8])]| glb::read(&raw_glb(chunks, None)).unwrap_err().to_string();
+    assert!(error(&[(CHUNK_TYPE_BIN, &[0; 4]), (CHUNK_TYPE_JSON, json)]).contains("first chunk must be JSON"));
+    assert!(error(&[(CHUNK_TYPE_JSON, json), (CHUNK_TYPE_JSON, json)]).contains("only one JSON chunk"));
+    assert!(error(&[(CHUNK_TYPE_JSON, json), (unknown, &[0; 4]), (CHUNK_TYPE_BIN, &[0; 4])]).contains("BIN chunk must be the second"));
+
+    // Unknown chunks after the BIN chunk are skipped, as are bytes after the length in the header
+    let mut glb = raw_glb(&[(CHUNK_TYPE_JSON, json), (CHUNK_TYPE_BIN, &[1, 2, 3, 4]), (unknown, &[0; 8])], None);
+    glb.extend_from_slice(b"trailing");
+    let (doc, bin) = glb::read(&glb).unwrap();
+    assert_eq!(doc["asset"]["version"], "2.0");
+    assert_eq!(bin, [1, 2, 3, 4]);
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use std::num::NonZeroU8;
+
+use common::{doc, plan, png_uri};
+use gltf_ktxer::{
+    dither::Dither,
+    groups::{glob_match, TextureGroup},
+    overrides::{TextureOverride, TextureOverrides},
+    ImageReencodeFormat, KtxCodec, Params, ReencodeJobs,
+};
+use image::{Rgba, RgbaImage};
+use serde_json::json;
+
+#[test]
+fn globs_match_whole_names() {
+    assert!(glob_match("hero_*.png", "hero_face.png"));
+    assert!(glob_match("hero_*.png", "hero_.png"));
+    assert!(!glob_match("hero_*.png", "hero_face.png.bak"));
+    assert!(glob_match("*_?.png", "a_b_c.png"));
+    assert!(!glob_match("*_?.png", "a_bc.png"));
+    assert!(glob_match("*", ""));
+    assert!(glob_match("Face", "Face"));
+    assert!(!glob_match("Face", "face"));
+    assert!(glob_match("a*b*c", "aXbYbZc"));
+}
+
+fn hero_jobs(params: Params) -> ReencodeJobs {
+    let data_uri = png_uri(&RgbaImage::from_pixel(4, 4, Rgba([9, 9, 9, 255])));
+    let mut doc = doc(json!({
+        "asset": { "version": "2.0" },
+        "images": [
+            { "uri": data_uri, "name": "hero_face", "extras": { "gltf_ktxer": { "codec": "uastc", "maxSize": 2 } } },
+            { "uri": data_uri, "name": "hero_body", "extras": { "gltf_ktxer": { "quality": 200 } } },
+            { "uri": data_uri, "name": "hero_cloak" },
+            { "uri": data_uri, "name": "tree" },
+        ],
+        "textures": [{ "source": 0 }, { "source": 1 }, { "source": 2 }, { "source": 3 }],
+        "materials": [
+            { "name": "Face", "pbrMetallicRoughness": { "baseColorTexture": { "index": 0 } } },
+            { "name": "Body", "pbrMetallicRoughness": { "baseColorTexture": { "index": 1 } } },
+            { "name": "Tree", "pbrMetallicRoughness": { "baseColorTexture": { "index": 3 } } },
+        ],
+    }));
+    plan(&mut doc, params).unwrap()
+}
+
+/// The codec, quality, max size and dither of each KTX2 job.
+fn ktx_settings(jobs: &ReencodeJobs) -> Vec<(KtxCodec, Option<u8>, Option<u32>, bool)> {
+    jobs.new_images
+        .iter()
+        .filter_map(|job| match job.reencode_as {
+            ImageReencodeFormat::Ktx { codec, basis_compression_quality, dither, .. } => {
+                Some((codec, basis_compression_quality.map(NonZeroU8::get), job.max_dimension, dither.is_some()))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn groups_share_the_strongest_settings() {
+    let mut texture_overrides = TextureOverrides::default();
+    texture_overrides.insert("hero_cloak", TextureOverride { dither: Some(Dither::Ordered), ..Default::default() });
+    let ungrouped = hero_jobs(Params { texture_overrides: texture_overrides.clone(), ..Params::default() });
+    assert_eq!(ktx_settings(&ungrouped), [
+        (KtxCodec::Uastc, None, Some(2), false),
+        (KtxCodec::Etc1s, Some(200), None, false),
+        (KtxCodec::Etc1s, None, None, true),
+        (KtxCodec::Etc1s, None, None, false),
+    ]);
+
+    let hero = TextureGroup { name: "hero".to_string(), materials: vec!["Face".to_string(), "Bo*".to_string()], textures: vec!["*_cloak".to_string()] };
+    let grouped = hero_jobs(Params { texture_overrides, texture_groups: vec![hero], ..Params::default() });
+    let shared = (KtxCodec::Uastc, Some(200), None, false);
+    assert_eq!(ktx_settings(&grouped), [shared, shared, shared, (KtxCodec::Etc1s, None, None, false)]);
+    assert!(grouped.warnings.is_empty());
+}
+
+#[test]
+fn empty_groups_are_warned_about() {
+    let typo = TextureGroup { name: "villain".to_string(), materials: vec!["Villian*".to_string()], ..Default::default() };
+    let jobs = hero_jobs(Params { texture_groups: vec![typo], ..Params::default() });
+    assert_eq!(jobs.warnings.iter().map(|warning| warning.code).collect::<Vec<_>>(), ["texture_group_empty"]);
+    assert_eq!(ktx_settings(&jobs).len(), 4);
+}
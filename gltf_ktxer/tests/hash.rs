@@ -0,0 +1,39 @@
+mod common;
+
+use common::{data_uri, doc, plan};
+use gltf_ktxer::{hash::{hex, sha256}, Params};
+use serde_json::json;
+
+#[test]
+fn sha256_test_vectors() {
+    // From the NIST example values for FIPS 180-4
+    assert_eq!(hex(&sha256(b"")), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+    assert_eq!(hex(&sha256(b"abc")), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    assert_eq!(
+        hex(&sha256(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")),
+        "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+    );
+}
+
+#[test]
+fn hash_extras_track_source_and_settings() {
+    let png = b"\x89PNG not really a png";
+    let mut gltf_json = doc(json!({
+        "asset": { "version": "2.0" },
+        "images": [{ "uri": data_uri(png), "mimeType": "image/png" }],
+        "textures": [{ "source": 0 }],
+    }));
+    let jobs = plan(&mut gltf_json, Params::default()).unwrap();
+
+    let [uncompressed, ktx] = jobs.new_images.as_slice() else {
+        panic!("expected one uncompressed and one KTX2 job");
+    };
+    let source_hash = format!("sha256:{}", hex(&sha256(png)));
+    assert_eq!(uncompressed.hash_extras()["source"], source_hash);
+    assert_eq!(ktx.hash_extras()["source"], source_hash);
+    assert_ne!(uncompressed.hash_extras()["encodeParams"], ktx.hash_extras()["encodeParams"]);
+    assert_eq!(
+        ktx.encode_params_key(),
+        format!("gltf_ktxer={};format=ktx2;codec=etc1s;quality=default;bc1_or_bc3=true;mipmaps=false;srgb=false;max_size=none", env!("CARGO_PKG_VERSION"))
+    );
+}
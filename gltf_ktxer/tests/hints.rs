@@ -0,0 +1,79 @@
+mod common;
+
+use std::num::NonZeroU8;
+
+use common::{doc, plan, png_uri};
+use gltf_ktxer::{hints::ConversionHints, ImageReencodeFormat, KtxCodec, Params, ReencodeJobs};
+use image::{Rgba, RgbaImage};
+use serde_json::json;
+
+/// The codec, quality and max size of each KTX2 job.
+fn ktx_settings(jobs: &ReencodeJobs) -> Vec<(KtxCodec, Option<u8>, Option<u32>)> {
+    jobs.new_images
+        .iter()
+        .filter_map(|job| match job.reencode_as {
+            ImageReencodeFormat::Ktx { codec, basis_compression_quality, .. } => Some((codec, basis_compression_quality.map(NonZeroU8::get), job.max_dimension)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn hints_are_parsed() {
+    let hints = ConversionHints::from_json(&json!({ "codec": "uastc", "quality": 200, "maxSize": 1024 })).unwrap();
+    assert_eq!(hints, ConversionHints { codec: Some(KtxCodec::Uastc), quality: NonZeroU8::new(200), max_size: Some(1024) });
+    assert_eq!(ConversionHints::from_json(&json!({})).unwrap(), ConversionHints::default());
+    for bad in [json!([]), json!({ "codec": "bc7" }), json!({ "quality": 0 }), json!({ "quality": 256 }), json!({ "maxSize": "big" }), json!({ "maxsize": 1 })] {
+        let err = ConversionHints::from_json(&bad).unwrap_err();
+        assert_eq!(err.code().as_str(), "bad_hint", "{bad}");
+    }
+}
+
+#[test]
+fn the_most_specific_hint_wins() {
+    let uri = png_uri(&RgbaImage::from_pixel(4, 4, Rgba([1, 2, 3, 255])));
+    let doc = doc(json!({
+        "asset": { "version": "2.0" },
+        "images": [{ "uri": uri, "extras": { "gltf_ktxer": { "quality": 90, "maxSize": 2 } } }, { "uri": uri }, { "uri": uri }],
+        "textures": [
+            { "source": 0, "extras": { "gltf_ktxer": { "maxSize": 1 } } },
+            { "source": 1 },
+            { "source": 2 },
+        ],
+        "materials": [
+            { "normalTexture": { "index": 1 } },
+            { "extras": { "gltf_ktxer": { "codec": "uastc" } }, "pbrMetallicRoughness": { "baseColorTexture": { "index": 0 } } },
+            { "extras": { "gltf_ktxer": { "quality": 10 } }, "normalTexture": { "index": 1 } },
+        ],
+    }));
+    let jobs = plan(&mut doc.clone(), Params::default()).unwrap();
+    assert_eq!(
+        ktx_settings(&jobs),
+        [(KtxCodec::Uastc, Some(90), Some(1)), (KtxCodec::Etc1s, Some(10), None), (KtxCodec::Etc1s, None, None)]
+    );
+
+    let jobs = plan(&mut doc.clone(), Params { extras_hints: false, max_texture_size: Some(3), ..Params::default() }).unwrap();
+    assert_eq!(ktx_settings(&jobs), [(KtxCodec::Etc1s, None, Some(3)); 3]);
+
+    let override_hints = ConversionHints { codec: Some(KtxCodec::Etc1s), max_size: Some(4), ..Default::default() };
+    let jobs = plan(&mut doc.clone(), Params { override_hints, ..Params::default() }).unwrap();
+    assert_eq!(
+        ktx_settings(&jobs),
+        [(KtxCodec::Etc1s, Some(90), Some(4)), (KtxCodec::Etc1s, Some(10), Some(4)), (KtxCodec::Etc1s, None, Some(4))]
+    );
+}
+
+#[test]
+fn bad_hints_are_located() {
+    let mut doc = doc(json!({
+        "asset": { "version": "2.0" },
+        "images": [{ "uri": png_uri(&RgbaImage::from_pixel(4, 4, Rgba([1, 2, 3, 255]))) }],
+        "textures": [{ "source": 0 }],
+        "materials": [{ "extras": { "gltf_ktxer": { "codec": 7 } }, "emissiveTexture": { "index": 0 } }],
+    }));
+    let Err(err) = plan(&mut doc, Params::default()) else {
+        panic!("bad hint was accepted");
+    };
+    assert_eq!(err.json_pointer(), Some("/materials/0/extras/gltf_ktxer"));
+    assert_eq!(err.without_location().to_string(), "bad conversion hint: 'codec' must be a string, not 7");
+}
@@ -0,0 +1,70 @@
+mod common;
+
+use std::{collections::HashMap, time::Duration};
+
+use common::{blank_png_uri, doc, plan};
+use gltf_ktxer::{
+    gltf::GltfDoc,
+    job_limits::{JobBudget, JobLimits},
+    Error, Input, Params,
+};
+use serde_json::json;
+
+/// Two images, 64x64 and 32x16, the first used by two textures.
+fn two_images() -> GltfDoc {
+    doc(json!({
+        "asset": { "version": "2.0" },
+        "images": [{ "uri": blank_png_uri(64, 64) }, { "uri": blank_png_uri(32, 16) }],
+        "textures": [{ "source": 0 }, { "source": 1 }, { "source": 0 }],
+    }))
+}
+
+#[test]
+fn inputs_over_the_limits_are_rejected() {
+    let mut doc = two_images();
+    let binaries = HashMap::from([(Some("scene.bin".to_string()), vec![0; 1000])]);
+    let input = Input { gltf_json: &mut doc, binaries: &binaries };
+
+    let mut budget = JobBudget::start(JobLimits::default());
+    budget.check_input(&input).unwrap();
+    let usage = budget.usage();
+    assert_eq!(usage.images, 2);
+    assert!(usage.input_bytes > 1000, "{usage:?}");
+
+    let mut budget = JobBudget::start(JobLimits { max_input_bytes: Some(usage.input_bytes - 1), ..JobLimits::default() });
+    assert!(matches!(budget.check_input(&input), Err(Error::JobLimitExceeded(_))));
+    let mut budget = JobBudget::start(JobLimits { max_input_bytes: Some(usage.input_bytes), max_images: Some(2), ..JobLimits::default() });
+    budget.check_input(&input).unwrap();
+
+    let mut budget = JobBudget::start(JobLimits { max_images: Some(1), ..JobLimits::default() });
+    let e = budget.check_input(&input).unwrap_err();
+    assert_eq!(e.code().as_str(), "job_limit_exceeded");
+    assert!(e.to_string().contains("2 images"), "{e}");
+}
+
+#[test]
+fn decode_pixels_count_each_source_once() {
+    let mut doc = two_images();
+    let jobs = plan(&mut doc, Params::default()).unwrap().new_images;
+    let pixels = 64 * 64 + 32 * 16;
+
+    let mut budget = JobBudget::start(JobLimits { max_decode_pixels: Some(pixels), ..JobLimits::default() });
+    budget.check_jobs(&jobs).unwrap();
+    assert_eq!(budget.usage().decode_pixels, pixels);
+
+    let mut budget = JobBudget::start(JobLimits { max_decode_pixels: Some(pixels - 1), ..JobLimits::default() });
+    let e = budget.check_jobs(&jobs).unwrap_err();
+    assert!(e.to_string().contains(&format!("{pixels} pixels")), "{e}");
+}
+
+#[test]
+fn jobs_time_out() {
+    let mut budget = JobBudget::start(JobLimits { timeout: Some(Duration::from_secs(3600)), ..JobLimits::default() });
+    budget.check_time().unwrap();
+
+    let mut budget = JobBudget::start(JobLimits { timeout: Some(Duration::ZERO), ..JobLimits::default() });
+    std::thread::sleep(Duration::from_millis(2));
+    let e = budget.check_time().unwrap_err();
+    assert_eq!(e.code().as_str(), "job_timed_out");
+    assert!(budget.usage().elapsed >= Duration::from_millis(2));
+}
@@ -0,0 +1,283 @@
+//! Snapshot tests pinning the exact JSON emitted by each document mutation.
+//! Run `cargo insta review` to inspect and accept changes.
+
+mod common;
+
+use std::collections::HashMap;
+
+use common::{doc, plan, png_uri};
+use gltf_ktxer::{edit::{self, add_extension_used}, gltf::{GltfDoc, GltfTexture, GltfTextureInfo}, ktx2::{ColorSpace, Ktx2Texture}, pack_buffers_separately, pack_buffers_together, pack_images_separately, prepare_output_buffers, BufferLayout, Input, KtxCodec, Params};
+use image::{Rgba, RgbaImage};
+use serde_json::json;
+
+fn red() -> RgbaImage {
+    RgbaImage::from_pixel(2, 2, Rgba([255, 0, 0, 255]))
+}
+
+#[test]
+fn texture_ktx_extension_injection() {
+    let mut gltf_json = doc(json!({
+        "asset": { "version": "2.0" },
+        "images": [{ "uri": png_uri(&red()) }, { "uri": png_uri(&red()) }],
+        "textures": [
+            { "source": 0 },
+            { "source": 1, "extensions": { "EXT_other": {} } },
+            { "source": 0, "sampler": 0 },
+        ],
+        "samplers": [{}],
+        "materials": [{ "pbrMetallicRoughness": { "baseColorTexture": { "index": 0 } } }],
+    }));
+    let jobs = plan(&mut gltf_json, Params::default()).unwrap();
+
+    insta::assert_json_snapshot!(jobs.new_textures);
+}
+
+#[test]
+fn avif_fallback_injection() {
+    let mut gltf_json = doc(json!({
+        "asset": { "version": "2.0" },
+        "images": [{ "uri": png_uri(&red()) }, { "uri": png_uri(&red()), "mimeType": "image/avif" }],
+        "textures": [
+            { "source": 0 },
+            { "extensions": { "EXT_texture_avif": { "source": 1 } } },
+        ],
+    }));
+    let params = Params { avif_fallback: true, ..Params::default() };
+    let jobs = plan(&mut gltf_json, params).unwrap();
+
+    insta::assert_json_snapshot!(jobs.new_textures);
+    let sources: Vec<&str> = jobs.new_images.iter().map(|job| job.source.mime_type.as_str()).collect();
+    assert_eq!(sources, ["image/png", "image/png", "image/png", "image/avif", "image/avif", "image/avif"]);
+}
+
+#[test]
+fn extensions_used_maintenance() {
+    let mut gltf_json = doc(json!({
+        "asset": { "version": "2.0" },
+        "extensionsUsed": ["KHR_materials_emissive_strength"],
+    }));
+    add_extension_used(&mut gltf_json, "KHR_texture_basisu", false).unwrap();
+    add_extension_used(&mut gltf_json, "KHR_texture_basisu", false).unwrap();
+    add_extension_used(&mut gltf_json, "EXT_meshopt_compression", true).unwrap();
+
+    insta::assert_json_snapshot!(gltf_json);
+}
+
+#[test]
+fn buffer_view_packing() {
+    let mut gltf_json = doc(json!({
+        "asset": { "version": "2.0" },
+        "buffers": [
+            { "uri": "a.bin", "byteLength": 10, "name": "a" },
+            { "uri": "data:application/octet-stream;base64,AAECAwQFBgc=", "byteLength": 8 },
+        ],
+        "bufferViews": [
+            { "buffer": 0, "byteOffset": 2, "byteLength": 6, "byteStride": 4, "target": 34962 },
+            { "buffer": 1, "byteLength": 7, "name": "odd" },
+            { "buffer": 1, "byteOffset": 4, "byteLength": 4 },
+        ],
+        "images": [{ "bufferView": 1, "mimeType": "image/png" }],
+    }));
+    let binaries = HashMap::from([(Some("a.bin".to_string()), (0..10).collect())]);
+    let output = pack_buffers_together(Input { gltf_json: &mut gltf_json, binaries: &binaries }).unwrap();
+
+    insta::assert_json_snapshot!(output.gltf_json);
+    assert_eq!(output.binary, [2, 3, 4, 5, 6, 7, 0, 0, 0, 1, 2, 3, 4, 5, 6, 0, 4, 5, 6, 7]);
+}
+
+#[test]
+fn contiguous_views_are_copied_as_one_run() {
+    let mut gltf_json = doc(json!({
+        "asset": { "version": "2.0" },
+        "buffers": [{ "uri": "a.bin", "byteLength": 16 }],
+        "bufferViews": [
+            { "buffer": 0, "byteLength": 6 },
+            { "buffer": 0, "byteOffset": 8, "byteLength": 4 },
+            { "buffer": 0, "byteOffset": 12, "byteLength": 2 },
+            { "buffer": 0, "byteOffset": 4, "byteLength": 2 },
+        ],
+    }));
+    let binaries = HashMap::from([(Some("a.bin".to_string()), (0..16).collect())]);
+    let output = pack_buffers_together(Input { gltf_json: &mut gltf_json, binaries: &binaries }).unwrap();
+
+    let offsets: Vec<_> = output.gltf_json["bufferViews"].as_array().unwrap().iter().map(|v| v["byteOffset"].as_u64().unwrap()).collect();
+    assert_eq!(offsets, [0, 8, 12, 16]);
+    // The first three views are one run, so the padding between them comes from the source
+    assert_eq!(output.binary, [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 0, 0, 4, 5, 0, 0]);
+}
+
+#[test]
+fn image_view_targets_are_stripped() {
+    let doc_with_targets = || doc(json!({
+        "asset": { "version": "2.0" },
+        "buffers": [{ "uri": "a.bin", "byteLength": 12 }],
+        "bufferViews": [
+            { "buffer": 0, "byteLength": 4, "target": 34962 },
+            { "buffer": 0, "byteOffset": 4, "byteLength": 4, "target": 34962 },
+            { "buffer": 0, "byteOffset": 8, "byteLength": 4, "target": 34963 },
+        ],
+        "accessors": [{ "bufferView": 0, "componentType": 5126, "count": 1, "type": "SCALAR" }],
+        "images": [{ "bufferView": 1, "mimeType": "image/png" }, { "bufferView": 2, "mimeType": "image/png" }],
+    }));
+    let binaries = HashMap::from([(Some("a.bin".to_string()), (0..12).collect())]);
+
+    let mut gltf_json = doc_with_targets();
+    assert_eq!(gltf_ktxer::validate::lint(&gltf_json).unwrap().iter().filter(|w| w.code == "image_view_target").count(), 2);
+    let output = prepare_output_buffers(Input { gltf_json: &mut gltf_json, binaries: &binaries }, &Params::default()).unwrap();
+    let targets: Vec<_> = output.gltf_json["bufferViews"].as_array().unwrap().iter().map(|v| v.get("target").cloned()).collect();
+    assert_eq!(targets, [Some(json!(34962)), None, None]);
+
+    let mut gltf_json = doc_with_targets();
+    let params = Params { strip_image_view_targets: false, ..Params::default() };
+    let output = prepare_output_buffers(Input { gltf_json: &mut gltf_json, binaries: &binaries }, &params).unwrap();
+    assert!(output.gltf_json["bufferViews"].as_array().unwrap().iter().all(|v| v.get("target").is_some()));
+}
+
+fn multi_buffer_doc() -> GltfDoc {
+    doc(json!({
+        "asset": { "version": "2.0" },
+        "buffers": [
+            { "uri": "a.bin", "byteLength": 10, "name": "a" },
+            { "uri": "data:application/octet-stream;base64,AAECAwQFBgc=", "byteLength": 8, "name": "embedded" },
+        ],
+        "bufferViews": [
+            { "buffer": 1, "byteOffset": 4, "byteLength": 4 },
+            { "buffer": 0, "byteOffset": 2, "byteLength": 6, "target": 34962 },
+            { "buffer": 1, "byteLength": 3 },
+        ],
+    }))
+}
+
+#[test]
+fn buffer_packing_records_merged_names() {
+    let mut gltf_json = multi_buffer_doc();
+    let binaries = HashMap::from([(Some("a.bin".to_string()), (0..10).collect())]);
+    let params = Params { record_merged_buffer_names: true, ..Params::default() };
+    let output = prepare_output_buffers(Input { gltf_json: &mut gltf_json, binaries: &binaries }, &params).unwrap();
+
+    insta::assert_json_snapshot!(output.gltf_json["buffers"]);
+}
+
+#[test]
+fn per_buffer_packing_keeps_identity() {
+    let mut gltf_json = multi_buffer_doc();
+    let binaries = HashMap::from([(Some("a.bin".to_string()), (0..10).collect())]);
+    let output = pack_buffers_separately(Input { gltf_json: &mut gltf_json, binaries: &binaries }).unwrap();
+
+    insta::assert_json_snapshot!(output.gltf_json);
+    assert!(output.binary.is_empty());
+    assert_eq!(output.external_binaries, HashMap::from([("a.bin".to_string(), vec![2, 3, 4, 5, 6, 7, 0, 0])]));
+}
+
+#[test]
+fn split_image_packing() {
+    let mut gltf_json = multi_buffer_doc();
+    gltf_json.insert("images".to_string(), json!([{ "bufferView": 2, "mimeType": "image/png" }]));
+    let binaries = HashMap::from([(Some("a.bin".to_string()), (0..10).collect())]);
+    let output = pack_images_separately(Input { gltf_json: &mut gltf_json, binaries: &binaries }, "scene.bin", "scene_images.bin").unwrap();
+
+    insta::assert_json_snapshot!(output.gltf_json);
+    assert_eq!(output.external_binaries, HashMap::from([
+        ("scene.bin".to_string(), vec![4, 5, 6, 7, 2, 3, 4, 5, 6, 7, 0, 0]),
+        ("scene_images.bin".to_string(), vec![0, 1, 2, 0]),
+    ]));
+}
+
+#[test]
+fn in_place_buffer_layout_appends() {
+    let mut gltf_json = doc(json!({
+        "asset": { "version": "2.0" },
+        "buffers": [{ "byteLength": 6 }],
+        "bufferViews": [{ "buffer": 0, "byteOffset": 1, "byteLength": 3 }],
+    }));
+    let binaries = HashMap::from([(None, vec![0, 1, 2, 3, 4, 5, 0, 0])]);
+    let mut output = prepare_output_buffers(Input { gltf_json: &mut gltf_json, binaries: &binaries }, &Params { buffer_layout: BufferLayout::InPlace, ..Params::default() }).unwrap();
+    let view = edit::append_buffer_view(&mut output.gltf_json, &mut output.binary, &[9, 9], 4, None).unwrap();
+
+    assert_eq!(view, 1.into());
+    insta::assert_json_snapshot!(output.gltf_json);
+    assert_eq!(output.binary, [0, 1, 2, 3, 4, 5, 0, 0, 9, 9]);
+}
+
+#[test]
+fn image_removal_renumbers_references() {
+    let mut gltf_json = doc(json!({
+        "asset": { "version": "2.0" },
+        "images": [{ "uri": "a.png" }, { "uri": "b.png" }, { "uri": "c.ktx2" }],
+        "textures": [
+            { "source": 0 },
+            { "source": 1, "extensions": { "KHR_texture_basisu": { "source": 2 } } },
+        ],
+    }));
+    assert!(edit::remove_image(&mut gltf_json, 1.into()).is_err());
+    let mut texture: GltfTexture = edit::get(&gltf_json, "textures", 1.into()).unwrap();
+    texture.source = 0.into();
+    edit::replace_texture(&mut gltf_json, 1.into(), &texture).unwrap();
+    edit::remove_image(&mut gltf_json, 1.into()).unwrap();
+
+    insta::assert_json_snapshot!(gltf_json);
+}
+
+#[test]
+fn texture_removal_preserves_tex_coords() {
+    let original = doc(json!({
+        "asset": { "version": "2.0" },
+        "images": [{ "uri": "a.png" }],
+        "textures": [{ "source": 0 }, { "source": 0 }],
+        "materials": [{
+            "pbrMetallicRoughness": { "baseColorTexture": { "index": 1, "texCoord": 1 } },
+            "occlusionTexture": { "index": 1, "strength": 0.5 },
+            "emissiveTexture": {
+                "index": 1,
+                "extensions": { "KHR_texture_transform": { "scale": [2, 2], "texCoord": 2 } },
+            },
+        }],
+    }));
+    let mut gltf_json = original.clone();
+    let mut material = edit::get::<serde_json::Value>(&gltf_json, "materials", 0.into()).unwrap();
+    edit::for_each_texture_info(&mut material, &mut |info| { info.insert("index".to_string(), 0.into()); });
+    edit::replace_material(&mut gltf_json, 0.into(), &material).unwrap();
+    edit::remove_texture(&mut gltf_json, 1.into()).unwrap();
+
+    let infos = edit::texture_infos(&gltf_json).unwrap();
+    let tex_coords: Vec<_> = infos.iter().map(|(pointer, info)| (pointer.as_str(), info.tex_coord(), info.effective_tex_coord())).collect();
+    assert_eq!(tex_coords, [
+        ("/materials/0/emissiveTexture", 0, 2),
+        ("/materials/0/occlusionTexture", 0, 0),
+        ("/materials/0/pbrMetallicRoughness/baseColorTexture", 1, 1),
+    ]);
+    gltf_ktxer::validate::check_tex_coords_preserved(&original, &gltf_json).unwrap();
+
+    gltf_json["materials"][0]["emissiveTexture"]["extensions"]["KHR_texture_transform"]["texCoord"] = 0.into();
+    let e = gltf_ktxer::validate::check_tex_coords_preserved(&original, &gltf_json).unwrap_err();
+    assert_eq!(e.json_pointer(), Some("/materials/0/emissiveTexture/extensions/KHR_texture_transform/texCoord"));
+}
+
+#[test]
+fn new_texture_from_pixels() {
+    let mut gltf_json = doc(json!({
+        "asset": { "version": "2.0" },
+        "materials": [{ "pbrMetallicRoughness": { "baseColorFactor": [1, 1, 1, 1] } }],
+    }));
+    let mut bin = vec![];
+    let lightmap = image::RgbaImage::from_pixel(4, 4, image::Rgba([128, 128, 128, 255]));
+
+    // UASTC encoding isn't available, so nothing is added
+    let uastc = Params { ktx_codec: KtxCodec::Uastc, ..Params::default() };
+    let e = gltf_ktxer::add_texture_from_rgba(&mut gltf_json, &mut bin, &lightmap, ColorSpace::Linear, Default::default(), &uastc).unwrap_err();
+    assert_eq!(e.code().as_str(), "encoder_unavailable");
+    assert!(bin.is_empty() && !gltf_json.contains_key("textures"));
+
+    let ktx = Ktx2Texture::from_rgba8(&lightmap, ColorSpace::Linear).unwrap();
+    let sampler = edit::append_sampler(&mut gltf_json, &json!({ "magFilter": 9729 })).unwrap();
+    let texture = edit::add_ktx2_texture(&mut gltf_json, &mut bin, &ktx, sampler).unwrap();
+    let info = GltfTextureInfo { tex_coord: Some(1), ..GltfTextureInfo::new(texture) };
+    edit::set_material_texture(&mut gltf_json, 0.into(), "occlusionTexture", &info).unwrap();
+    assert!(edit::set_material_texture(&mut gltf_json, 0.into(), "occlusion", &info).is_err());
+
+    assert_eq!(gltf_json["textures"], json!([{ "sampler": 0, "extensions": { "KHR_texture_basisu": { "source": 0 } } }]));
+    assert_eq!(gltf_json["images"], json!([{ "bufferView": 0, "mimeType": "image/ktx2" }]));
+    assert_eq!(gltf_json["extensionsRequired"], json!(["KHR_texture_basisu"]));
+    assert_eq!(gltf_json["materials"][0]["occlusionTexture"], json!({ "index": 0, "texCoord": 1 }));
+    assert_eq!(Ktx2Texture::from_bytes(&bin).unwrap(), ktx);
+}
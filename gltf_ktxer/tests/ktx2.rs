@@ -0,0 +1,115 @@
+use gltf_ktxer::{ktx2::{self, generate_mipmaps, ColorSpace, Ktx2Texture}, Error};
+use image::RgbaImage;
+
+#[test]
+fn ktx2_roundtrips_through_bytes() {
+    let rgba = Ktx2Texture::from_rgba8(&RgbaImage::new(3, 2), ColorSpace::Linear).unwrap();
+    assert_eq!(Ktx2Texture::from_bytes(&rgba.to_bytes()).unwrap(), rgba);
+    let mipmapped = Ktx2Texture::from_rgba8_mipmapped(&RgbaImage::new(5, 3), ColorSpace::Srgb).unwrap();
+    assert_eq!(Ktx2Texture::from_bytes(&mipmapped.to_bytes()).unwrap(), mipmapped);
+}
+
+#[test]
+fn other_files_are_not_ktx2() {
+    let e = Ktx2Texture::from_bytes(b"\x89PNG\r\n\x1a\n").unwrap_err();
+    assert!(matches!(e, Error::Ktx2Malformed(_)));
+    assert!(e.to_string().contains("not a KTX2 file"), "{e}");
+}
+
+#[test]
+fn mip_chain_halves_down_to_one_pixel() {
+    let dims: Vec<_> = generate_mipmaps(&RgbaImage::new(5, 3)).iter().map(|level| level.dimensions()).collect();
+    assert_eq!(dims, [(5, 3), (2, 1), (1, 1)]);
+}
+
+#[test]
+fn mip_levels_must_halve() {
+    let levels = [RgbaImage::new(4, 4), RgbaImage::new(4, 4)];
+    assert!(matches!(
+        Ktx2Texture::from_rgba8_levels(&levels, ColorSpace::Srgb),
+        Err(Error::Ktx2BadMipLevel { level: 1, width: 4, height: 4 })
+    ));
+}
+
+#[test]
+fn array_images_are_ordered_by_layer_then_face() {
+    let solid = |value: u8| RgbaImage::from_pixel(1, 1, image::Rgba([value; 4]));
+    let images: Vec<Vec<RgbaImage>> = (0..12).map(|i| vec![solid(i)]).collect();
+    let images: Vec<&[RgbaImage]> = images.iter().map(Vec::as_slice).collect();
+    let ktx = Ktx2Texture::from_rgba8_images(&images, 2, 6, ColorSpace::Linear).unwrap();
+
+    assert_eq!((ktx.layer_count, ktx.face_count), (2, 6));
+    let first_bytes: Vec<u8> = ktx.levels[0].data.chunks(4).map(|texel| texel[0]).collect();
+    assert_eq!(first_bytes, (0..12).collect::<Vec<u8>>());
+
+    assert!(matches!(
+        Ktx2Texture::from_rgba8_images(&images[..5], 0, 6, ColorSpace::Linear),
+        Err(Error::Ktx2WrongImageCount { expected: 6, got: 5 })
+    ));
+}
+
+#[test]
+fn ktx2_writes_in_place_after_existing_data() {
+    let ktx = Ktx2Texture::from_rgba8_mipmapped(&RgbaImage::new(5, 3), ColorSpace::Srgb).unwrap();
+    let mut out = vec![7; 13];
+    ktx.write_to(&mut out);
+    assert_eq!(&out[..13], [7; 13]);
+    assert_eq!(out[13..], ktx.to_bytes());
+    assert_eq!(ktx.encoded_len(), out.len() - 13);
+}
+
+#[test]
+fn ktx2_buffer_view_is_aligned_and_roundtrips() {
+    let ktx = Ktx2Texture::from_rgba8(&RgbaImage::new(2, 2), ColorSpace::Linear).unwrap();
+    let mut doc = gltf_ktxer::gltf::GltfDoc::new();
+    let mut bin = vec![1, 2, 3];
+    let view = gltf_ktxer::edit::append_ktx2_buffer_view(&mut doc, &mut bin, &ktx).unwrap();
+
+    let view: gltf_ktxer::gltf::GltfBufferView = gltf_ktxer::edit::get(&doc, "bufferViews", view).unwrap();
+    assert_eq!(view.byte_offset, 8);
+    assert_eq!(Ktx2Texture::from_bytes(&bin[view.byte_offset..][..view.byte_length]).unwrap(), ktx);
+    assert_eq!(doc["buffers"][0]["byteLength"], bin.len());
+}
+
+#[test]
+fn set_image_checks_indices_and_lengths() {
+    let solid = |value: u8| vec![RgbaImage::from_pixel(2, 2, image::Rgba([value; 4])), RgbaImage::from_pixel(1, 1, image::Rgba([value; 4]))];
+    let images: Vec<Vec<RgbaImage>> = (0..6).map(solid).collect();
+    let images: Vec<&[RgbaImage]> = images.iter().map(Vec::as_slice).collect();
+    let mut ktx = Ktx2Texture::from_rgba8_images(&images, 2, 3, ColorSpace::Linear).unwrap();
+
+    assert_eq!(ktx.image(1, 1, 2).unwrap(), [5; 4]);
+    ktx.set_image(1, 1, 2, &[9; 4]).unwrap();
+    assert_eq!(ktx.image(1, 1, 2).unwrap(), [9; 4]);
+    assert_eq!(ktx.image(1, 1, 1).unwrap(), [4; 4]);
+    assert_eq!(ktx.image(0, 0, 1).unwrap(), [1; 16]);
+
+    for (level, layer, face) in [(2, 0, 0), (0, 2, 0), (0, 0, 3)] {
+        assert!(matches!(ktx.set_image(level, layer, face, &[0; 16]), Err(Error::Ktx2BadImageIndex { .. })));
+    }
+    assert!(matches!(ktx.set_image(0, 0, 0, &[0; 15]), Err(Error::Ktx2WrongImageSize { expected: 16, got: 15 })));
+    assert!(matches!(ktx.set_image(1, 0, 0, &[0; 16]), Err(Error::Ktx2WrongImageSize { expected: 4, got: 16 })));
+
+    ktx.levels[1].data.pop();
+    assert!(matches!(ktx.image(1, 0, 0), Err(Error::Ktx2Malformed(_))));
+    ktx.supercompression_scheme = 1;
+    assert!(matches!(ktx.image(0, 0, 0), Err(Error::Ktx2Supercompressed)));
+}
+
+#[test]
+fn encoded_output_is_verified_against_source() {
+    let opaque = RgbaImage::from_pixel(4, 2, image::Rgba([10, 20, 30, 255]));
+    let mut transparent = opaque.clone();
+    transparent.put_pixel(3, 1, image::Rgba([0, 0, 0, 0]));
+    let ktx = Ktx2Texture::from_rgba8(&opaque, ColorSpace::Srgb).unwrap();
+
+    ktx2::verify_encoded(&ktx, &opaque, (4, 2)).unwrap();
+    assert!(matches!(ktx2::verify_encoded(&ktx, &opaque, (2, 1)), Err(Error::OutputVerificationFailed(_))));
+    assert!(matches!(ktx2::verify_encoded(&ktx, &transparent, (4, 2)), Err(Error::OutputVerificationFailed(_))));
+
+    // Without a transcoder, only the DFD's alpha channel is checked
+    let basis_like = Ktx2Texture { vk_format: 0, ..ktx.clone() };
+    assert!(basis_like.dfd_has_alpha());
+    ktx2::verify_encoded(&basis_like, &transparent, (4, 2)).unwrap();
+    assert_eq!(ktx2::fit_dimensions((4096, 1024), 1000), (1000, 250));
+}
@@ -0,0 +1,200 @@
+//! Checks the layout of written KTX2 files against section 3 of the KTX2 spec, reading the bytes directly rather than through
+//! [Ktx2Texture::from_bytes], which shares its assumptions with the writer. Padding mistakes are easy to miss because many loaders
+//! tolerate them, while others reject the file or read levels from the wrong place.
+
+use gltf_ktxer::ktx2::{self, ColorSpace, Ktx2Level, Ktx2Texture, SUPERCOMPRESSION_BASIS_LZ, SUPERCOMPRESSION_ZSTD};
+use image::RgbaImage;
+
+/// Where each level starts in a checked file, level 0 first.
+struct Layout {
+    level_offsets: Vec<usize>,
+    sgd_offset: usize,
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> usize {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap()) as usize
+}
+fn u64_at(bytes: &[u8], offset: usize) -> usize {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap()) as usize
+}
+fn align_up(x: usize, align: usize) -> usize {
+    x.div_ceil(align) * align
+}
+fn assert_zero_padding(bytes: &[u8], range: std::ops::Range<usize>, what: &str) {
+    assert!(bytes[range.clone()].iter().all(|&b| b == 0), "{what} padding at {range:?} isn't zeroed");
+}
+
+/// Check every section of `bytes` is where the spec requires, and that nothing is left between sections but minimal zeroed padding.
+fn check_layout(bytes: &[u8]) -> Layout {
+    assert!(bytes.starts_with(&ktx2::KTX2_IDENTIFIER));
+    let level_count = u32_at(bytes, 40).max(1);
+    let scheme = u32_at(bytes, 44) as u32;
+    let (dfd_offset, dfd_len) = (u32_at(bytes, 48), u32_at(bytes, 52));
+    let (kvd_offset, kvd_len) = (u32_at(bytes, 56), u32_at(bytes, 60));
+    let (sgd_offset, sgd_len) = (u64_at(bytes, 64), u64_at(bytes, 72));
+
+    // Section 3.8: the DFD directly follows the level index, and starts with its own total size
+    assert_eq!(dfd_offset, 80 + 24 * level_count);
+    assert_eq!(dfd_offset % 4, 0);
+    assert_eq!(u32_at(bytes, dfd_offset), dfd_len);
+    let mut end = dfd_offset + dfd_len;
+
+    // Section 3.10: the KVD directly follows the DFD, with each entry padded to 4 bytes and sorted by key
+    if kvd_len == 0 {
+        assert_eq!(kvd_offset, 0, "an empty KVD must have offset 0");
+    } else {
+        assert_eq!(kvd_offset, end);
+        assert_eq!(kvd_offset % 4, 0);
+        let mut entry = kvd_offset;
+        let mut keys = vec![];
+        while entry < kvd_offset + kvd_len {
+            let len = u32_at(bytes, entry);
+            let key_value = &bytes[entry + 4..entry + 4 + len];
+            keys.push(key_value[..key_value.iter().position(|&b| b == 0).expect("key isn't NUL-terminated")].to_vec());
+            assert_zero_padding(bytes, entry + 4 + len..align_up(entry + 4 + len, 4), "key/value");
+            entry += 4 + align_up(len, 4);
+        }
+        assert_eq!(entry, kvd_offset + kvd_len, "the KVD length must include the last entry's padding");
+        assert!(keys.is_sorted(), "key/value entries must be sorted by key");
+        end = kvd_offset + kvd_len;
+    }
+
+    // Section 3.11: the SGD starts on an 8-byte boundary after the KVD
+    if sgd_len == 0 {
+        assert_eq!(sgd_offset, 0, "an empty SGD must have offset 0");
+    } else {
+        assert_eq!(sgd_offset, align_up(end, 8));
+        assert_zero_padding(bytes, end..sgd_offset, "SGD");
+        end = sgd_offset + sgd_len;
+    }
+
+    // Section 3.9.7: levels are stored smallest first, aligned to lcm(texel block size, 4) unless supercompressed
+    let level_alignment = if scheme == 0 { lcm(bytes[dfd_offset + 4 + 16].max(1) as usize, 4) } else { 1 };
+    let mut level_offsets = vec![0; level_count];
+    for level in (0..level_count).rev() {
+        let index = 80 + 24 * level;
+        let (offset, len, uncompressed_len) = (u64_at(bytes, index), u64_at(bytes, index + 8), u64_at(bytes, index + 16));
+        assert_eq!(offset, align_up(end, level_alignment), "level {level} isn't at the next {level_alignment}-byte boundary");
+        assert_zero_padding(bytes, end..offset, "mip");
+        if scheme == 0 {
+            assert_eq!(uncompressed_len, len, "level {level} isn't supercompressed, so its lengths must match");
+        }
+        level_offsets[level] = offset;
+        end = offset + len;
+    }
+    assert_eq!(end, bytes.len(), "level 0 must end the file");
+
+    Layout { level_offsets, sgd_offset }
+}
+
+fn lcm(a: usize, b: usize) -> usize {
+    (1..=a * b).find(|x| x % a == 0 && x % b == 0).unwrap()
+}
+
+/// An RGBA8 DFD claiming `block_size` bytes per texel block, which is all the layout depends on.
+fn dfd_with_block_size(block_size: u8) -> Vec<u8> {
+    let mut dfd = ktx2::rgba8_dfd(ColorSpace::Linear);
+    dfd[4 + 16] = block_size;
+    dfd
+}
+
+/// A texture with non-zero level data of the given lengths, level 0 first.
+fn texture(scheme: u32, dfd: Vec<u8>, key_values: &[(&str, &[u8])], sgd: &[u8], level_lens: &[usize]) -> Ktx2Texture {
+    Ktx2Texture {
+        vk_format: 0,
+        type_size: 1,
+        pixel_width: 1 << (level_lens.len() - 1),
+        pixel_height: 1 << (level_lens.len() - 1),
+        pixel_depth: 0,
+        layer_count: 0,
+        face_count: 1,
+        supercompression_scheme: scheme,
+        dfd,
+        key_values: key_values.iter().map(|(key, value)| (key.to_string(), value.to_vec())).collect(),
+        sgd: sgd.to_vec(),
+        levels: level_lens
+            .iter()
+            .map(|&len| Ktx2Level { data: vec![0xab; len], uncompressed_byte_length: if scheme == 0 { len as u64 } else { len as u64 * 3 } })
+            .collect(),
+    }
+}
+
+#[test]
+fn rgba8_levels_are_4_byte_aligned() {
+    for (width, height) in [(1, 1), (3, 2), (5, 3), (7, 7), (16, 9)] {
+        let texture = Ktx2Texture::from_rgba8_mipmapped(&RgbaImage::new(width, height), ColorSpace::Srgb).unwrap();
+        let bytes = texture.to_bytes();
+        assert_eq!(bytes.len(), texture.encoded_len());
+        let layout = check_layout(&bytes);
+        assert!(layout.level_offsets.iter().all(|offset| offset % 4 == 0));
+    }
+}
+
+#[test]
+fn array_and_cubemap_levels_are_aligned() {
+    let levels = [RgbaImage::new(3, 3), RgbaImage::new(1, 1)];
+    let images: Vec<&[RgbaImage]> = vec![&levels; 12];
+    let cubemap_array = Ktx2Texture::from_rgba8_images(&images, 2, 6, ColorSpace::Linear).unwrap();
+    check_layout(&cubemap_array.to_bytes());
+    let array = Ktx2Texture::from_rgba8_images(&images[..3], 3, 1, ColorSpace::Linear).unwrap();
+    check_layout(&array.to_bytes());
+}
+
+#[test]
+fn block_compressed_levels_are_aligned_to_the_block_size() {
+    // 16-byte blocks, like UASTC or BC7, after a KVD which doesn't end on a 16-byte boundary
+    let uastc = texture(0, dfd_with_block_size(16), &[("KTXwriter", b"test\0")], &[], &[64, 16, 16]);
+    let layout = check_layout(&uastc.to_bytes());
+    assert!(layout.level_offsets.iter().all(|offset| offset % 16 == 0));
+
+    // 8-byte blocks, like BC1 or ETC2 RGB
+    let bc1 = texture(0, dfd_with_block_size(8), &[("a", b"1")], &[], &[32, 8, 8]);
+    let layout = check_layout(&bc1.to_bytes());
+    assert!(layout.level_offsets.iter().all(|offset| offset % 8 == 0));
+}
+
+#[test]
+fn levels_with_a_block_size_not_dividing_4_are_aligned_to_the_lcm() {
+    // 6-byte texels, like R16G16B16_UNORM, need 12-byte alignment: a multiple of both the texel size and 4
+    let rgb16 = texture(0, dfd_with_block_size(6), &[], &[], &[6 * 4 * 4, 6 * 2 * 2, 6]);
+    let layout = check_layout(&rgb16.to_bytes());
+    assert!(layout.level_offsets.iter().all(|offset| offset % 12 == 0));
+}
+
+#[test]
+fn supercompressed_levels_are_packed_without_padding() {
+    let zstd = texture(SUPERCOMPRESSION_ZSTD, dfd_with_block_size(16), &[("odd", b"x")], &[], &[37, 11, 5, 3]);
+    let layout = check_layout(&zstd.to_bytes());
+    let lens = [37, 11, 5, 3];
+    for level in 0..lens.len() - 1 {
+        assert_eq!(layout.level_offsets[level], layout.level_offsets[level + 1] + lens[level + 1]);
+    }
+}
+
+#[test]
+fn sgd_is_8_byte_aligned_and_followed_directly_by_levels() {
+    // The KVD ends on a 4-byte but not an 8-byte boundary
+    let basis = texture(SUPERCOMPRESSION_BASIS_LZ, dfd_with_block_size(0), &[("k", b"vv")], &[0xcd; 13], &[21, 7]);
+    let bytes = basis.to_bytes();
+    let layout = check_layout(&bytes);
+    assert_eq!(layout.sgd_offset % 8, 0);
+    assert_eq!(layout.level_offsets[1], layout.sgd_offset + 13);
+}
+
+#[test]
+fn empty_sections_have_zero_offsets() {
+    let bare = texture(0, dfd_with_block_size(4), &[], &[], &[4]);
+    let bytes = bare.to_bytes();
+    check_layout(&bytes);
+    assert_eq!(u32_at(&bytes, 56), 0);
+    assert_eq!(u64_at(&bytes, 64), 0);
+}
+
+#[test]
+fn offsets_are_relative_to_the_start_of_the_file_when_appended() {
+    let texture = texture(0, dfd_with_block_size(16), &[("KTXwriter", b"test\0")], &[], &[64, 16]);
+    let mut out = vec![0xff; 13];
+    texture.write_to(&mut out);
+    assert_eq!(out[13..], texture.to_bytes());
+    check_layout(&out[13..]);
+}
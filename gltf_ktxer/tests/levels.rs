@@ -0,0 +1,58 @@
+mod common;
+
+use common::{blank_png_uri, doc, plan};
+use gltf_ktxer::{gltf::{GltfDoc, GltfSampler}, levels::{full_level_count, level_count}, profile::TargetProfile, ImageReencodeFormat, Params};
+use serde_json::json;
+
+#[test]
+fn full_chains_go_down_to_1x1() {
+    assert_eq!(full_level_count((1, 1)), 1);
+    assert_eq!(full_level_count((256, 256)), 9);
+    assert_eq!(full_level_count((300, 5)), 9);
+}
+
+#[test]
+fn level_count_depends_on_target_and_size() {
+    let repeat = GltfSampler::default();
+    for target in [TargetProfile::WebGl1, TargetProfile::WebGl2, TargetProfile::WebGpu, TargetProfile::Vulkan, TargetProfile::Gles3, TargetProfile::Metal] {
+        assert_eq!(level_count((256, 64), &repeat, target), 9, "{target}");
+    }
+    assert_eq!(level_count((300, 200), &repeat, TargetProfile::WebGl1), 1);
+    // 300x200 -> 150x100: 150 isn't a multiple of 4
+    assert_eq!(level_count((300, 200), &repeat, TargetProfile::WebGl2), 1);
+    // 24x24 -> 12x12 -> 6x6: 6 isn't a multiple of 4
+    assert_eq!(level_count((24, 24), &repeat, TargetProfile::WebGl2), 2);
+    assert_eq!(level_count((300, 200), &repeat, TargetProfile::Vulkan), 9);
+
+    let no_mipmaps = GltfSampler { min_filter: Some(GltfSampler::FILTER_LINEAR), ..GltfSampler::default() };
+    assert_eq!(level_count((256, 256), &no_mipmaps, TargetProfile::Vulkan), 1);
+}
+
+/// One 24x24 texture sampled with repeat wrapping, and the same image sampled with clamping.
+fn npot_doc() -> GltfDoc {
+    doc(json!({
+        "asset": { "version": "2.0" },
+        "images": [{ "uri": blank_png_uri(24, 24) }],
+        "samplers": [{ "wrapS": GltfSampler::WRAP_CLAMP_TO_EDGE, "wrapT": GltfSampler::WRAP_CLAMP_TO_EDGE }],
+        "textures": [{ "source": 0 }, { "source": 0, "sampler": 0 }],
+    }))
+}
+
+#[test]
+fn planned_images_get_levels_for_the_target() {
+    for (target, expected_levels, expected_warnings) in [(TargetProfile::WebGl1, 1, 1), (TargetProfile::WebGl2, 2, 0), (TargetProfile::Vulkan, 5, 0)] {
+        let params = Params { generate_mipmaps: true, target: Some(target), ..Params::default() };
+        let jobs = plan(&mut npot_doc(), params).unwrap();
+        let ktx_levels: Vec<_> = jobs
+            .new_images
+            .iter()
+            .filter_map(|job| match job.reencode_as {
+                ImageReencodeFormat::Ktx { level_count, .. } => Some(level_count),
+                ImageReencodeFormat::Basic(_) | ImageReencodeFormat::Copy => None,
+            })
+            .collect();
+        assert_eq!(ktx_levels, [Some(expected_levels)], "{target}");
+        assert_eq!(jobs.warnings.len(), expected_warnings, "{target}");
+        assert!(jobs.warnings.iter().all(|warning| warning.code == "npot_repeat" && warning.json_pointer == "/textures/0"));
+    }
+}
@@ -0,0 +1,42 @@
+//! Transcode ETC1S outputs with the bundled libktx (`app/libktx`), the reference Basis Universal transcoder,
+//! as the other ETC1S tests only decode with [gltf_ktxer::etc1s::decode], which shares its reading of the format with the encoder.
+//!
+//! Needs Node.js: `cargo test --test libktx -- --ignored`. See `tests/libktx/transcode.cjs` for what's checked.
+
+use std::{num::NonZeroU8, path::PathBuf, process::Command};
+
+use gltf_ktxer::{etc1s::{encode, encode_levels}, ktx2::{generate_mipmaps, ColorSpace}, tuning::EncodeEffort};
+use image::{Rgba, RgbaImage};
+
+const HARNESS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/libktx/transcode.cjs");
+
+fn gradient(width: u32, height: u32, alpha: bool) -> RgbaImage {
+    RgbaImage::from_fn(width, height, |x, y| Rgba([(x * 255 / width) as u8, (y * 255 / height) as u8, ((x + y) * 7 % 256) as u8, if alpha { (x * 16) as u8 } else { 255 }]))
+}
+
+#[test]
+#[ignore = "runs the bundled libktx with Node.js"]
+fn libktx_transcodes_etc1s_outputs() {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("libktx");
+    std::fs::create_dir_all(&dir).unwrap();
+    let quality = |quality| NonZeroU8::new(quality);
+    let mut inputs = vec![];
+    for (name, ktx) in [
+        ("opaque.ktx2", encode(&gradient(32, 32, false), ColorSpace::Srgb, None, None)),
+        ("alpha.ktx2", encode(&gradient(32, 32, true), ColorSpace::Srgb, None, None)),
+        ("linear.ktx2", encode(&gradient(16, 16, false), ColorSpace::Linear, None, None)),
+        ("partial_blocks.ktx2", encode(&gradient(13, 7, true), ColorSpace::Srgb, None, None)),
+        ("low_quality.ktx2", encode(&gradient(64, 64, false), ColorSpace::Srgb, quality(1), Some(EncodeEffort::Fast))),
+        ("high_quality.ktx2", encode(&gradient(64, 64, false), ColorSpace::Srgb, quality(255), Some(EncodeEffort::Thorough))),
+        ("solid.ktx2", encode(&RgbaImage::from_pixel(64, 64, Rgba([200, 100, 50, 255])), ColorSpace::Srgb, None, None)),
+        ("mipmapped.ktx2", encode_levels(&generate_mipmaps(&gradient(40, 24, true)), ColorSpace::Srgb, None, None)),
+    ] {
+        let path = dir.join(name);
+        std::fs::write(&path, ktx.unwrap().to_bytes()).unwrap();
+        inputs.push(path);
+    }
+
+    let output = Command::new("node").arg(HARNESS).args(&inputs).output().expect("couldn't run node");
+    print!("{}", String::from_utf8_lossy(&output.stdout));
+    assert!(output.status.success(), "libktx couldn't transcode an output\n{}", String::from_utf8_lossy(&output.stderr));
+}
@@ -0,0 +1,38 @@
+// Transcodes each .ktx2 file given on the command line with the bundled libktx (app/libktx), the reference transcoder
+// the viewer uses, to each of a few GPU formats, and fails if any transcode doesn't succeed. Run by tests/libktx.rs.
+//
+// libktx.js is a CommonJS module, and transcodeBasis replaces the texture's data with the transcoded data,
+// so each target gets a fresh copy of the texture.
+
+const { readFileSync } = require('node:fs');
+const path = require('node:path');
+
+const LIBKTX_DIR = path.join(__dirname, '..', '..', '..', 'app', 'libktx');
+const LIBKTX = require(path.join(LIBKTX_DIR, 'libktx.js'));
+
+const TARGETS = ['RGBA32', 'ETC1_RGB', 'BC3_RGBA', 'ASTC_4x4_RGBA'];
+
+LIBKTX({ locateFile: (file) => path.join(LIBKTX_DIR, file) }).then((ktx) => {
+    let failed = false;
+    for (const file of process.argv.slice(2)) {
+        const data = new Uint8Array(readFileSync(file));
+        for (const target of TARGETS) {
+            let result;
+            try {
+                const texture = new ktx.ktxTexture(data);
+                if (texture.needsTranscoding) {
+                    const code = texture.transcodeBasis(ktx.TranscodeTarget[target], 0);
+                    result = Object.keys(ktx.ErrorCode).find((name) => ktx.ErrorCode[name] === code);
+                } else {
+                    result = 'not a Basis Universal texture';
+                }
+                texture.delete();
+            } catch (e) {
+                result = `threw ${e}`;
+            }
+            failed ||= result !== 'SUCCESS';
+            console.log(`${path.basename(file)} -> ${target}: ${result}`);
+        }
+    }
+    process.exit(failed ? 1 : 0);
+});
@@ -0,0 +1,51 @@
+mod common;
+
+use common::{blank_png_uri, doc, plan};
+use gltf_ktxer::{gltf::GltfDoc, limits::{LimitAction, Limits}, Error, Params, ReencodeJobs};
+use serde_json::json;
+
+/// A document with one texture using an 8x4 PNG, which plans two output images.
+fn one_texture() -> GltfDoc {
+    doc(json!({
+        "asset": { "version": "2.0" },
+        "images": [{ "uri": blank_png_uri(8, 4) }],
+        "textures": [{ "source": 0 }],
+    }))
+}
+
+fn plan_with(limits: Limits) -> gltf_ktxer::Result<ReencodeJobs> {
+    plan(&mut one_texture(), Params { limits, ..Params::default() })
+}
+
+#[test]
+fn within_limits() {
+    let limits = Limits { max_images: Some(2), max_total_texture_bytes: Some(2 * 8 * 4 * 4), max_texture_dimension: Some(8), ..Limits::default() };
+    assert!(plan_with(limits).unwrap().warnings.is_empty());
+}
+
+#[test]
+fn exceeded_limits_fail() {
+    for limits in [
+        Limits { max_images: Some(1), ..Limits::default() },
+        Limits { max_total_texture_bytes: Some(255), ..Limits::default() },
+        Limits { max_texture_dimension: Some(4), ..Limits::default() },
+    ] {
+        assert!(matches!(plan_with(limits), Err(Error::LimitExceeded(_))), "{limits:?}");
+    }
+}
+
+#[test]
+fn exceeded_limits_can_warn() {
+    let limits = Limits {
+        max_images: Some(1),
+        max_texture_dimension: Some(4),
+        on_exceeded: LimitAction::Warn,
+        ..Limits::default()
+    };
+    let messages: Vec<String> = plan_with(limits).unwrap().warnings.into_iter().map(|warning| warning.message).collect();
+    assert_eq!(messages, [
+        "output has 2 images, more than the limit of 1",
+        "output image 0 is 8x4, larger than the limit of 4",
+        "output image 1 is 8x4, larger than the limit of 4",
+    ]);
+}
@@ -0,0 +1,79 @@
+use std::path::{Path, PathBuf};
+
+use gltf_ktxer::load::{escapes_root, load_gltf, load_gltf_with, LoadOptions};
+use serde_json::json;
+
+#[test]
+fn paths_leaving_the_root_are_spotted() {
+    for path in ["scene.bin", "textures/wood.png", "textures/../scene.bin", "./a/./b/../../c.png", "a//b.png", "..a.png", "a..png"] {
+        assert!(!escapes_root(path), "{path}");
+    }
+    for path in ["../scene.bin", "../../etc/passwd", "textures/../../x.png", "a/../../b", "/etc/passwd", "\\\\server\\share\\x.png", "..\\..\\x.png", "C:/x.png", "c:x.png"] {
+        assert!(escapes_root(path), "{path}");
+    }
+}
+
+/// A directory holding `secret.bin`, and a `models` directory inside it for documents.
+fn sandbox(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("load").join(name);
+    std::fs::create_dir_all(dir.join("models/textures")).unwrap();
+    std::fs::write(dir.join("secret.bin"), [1, 2, 3, 4]).unwrap();
+    std::fs::write(dir.join("models/textures/local.bin"), [5, 6, 7, 8]).unwrap();
+    dir
+}
+
+fn write_doc(path: &Path, uri: &str) {
+    let doc = json!({ "asset": { "version": "2.0" }, "buffers": [{ "uri": "data:application/octet-stream;base64,AAAAAA==", "byteLength": 4 }, { "uri": uri, "byteLength": 4 }] });
+    std::fs::write(path, doc.to_string()).unwrap();
+}
+
+#[test]
+fn uris_outside_the_document_directory_are_rejected() {
+    let dir = sandbox("rejected");
+    let path = dir.join("models/scene.gltf");
+    for uri in ["../secret.bin", "textures/..%2F..%2Fsecret.bin", &dir.join("secret.bin").to_string_lossy()] {
+        write_doc(&path, uri);
+        let e = load_gltf(&path).err().unwrap();
+        assert_eq!(e.code().as_str(), "uri_outside_root", "{uri}");
+        assert_eq!(e.json_pointer(), Some("/buffers/1/uri"));
+    }
+
+    write_doc(&path, "textures/./local.bin");
+    assert_eq!(load_gltf(&path).unwrap().binaries[&Some("textures/./local.bin".to_string())], [5, 6, 7, 8]);
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinks_out_of_the_document_directory_are_rejected() {
+    let dir = sandbox("symlinks");
+    let path = dir.join("models/scene.gltf");
+    let link = |target: &Path, name: &str| {
+        let link = dir.join("models/textures").join(name);
+        let _ = std::fs::remove_file(&link);
+        std::os::unix::fs::symlink(target, &link).unwrap();
+    };
+    link(Path::new("../../secret.bin"), "secret.bin");
+    link(&dir, "up");
+    link(Path::new("local.bin"), "inside.bin");
+    for (uri, idx) in [("textures/secret.bin", 1), ("textures/up/secret.bin", 1)] {
+        write_doc(&path, uri);
+        let e = load_gltf(&path).err().unwrap();
+        assert_eq!(e.code().as_str(), "uri_outside_root", "{uri}");
+        assert_eq!(e.json_pointer(), Some(format!("/buffers/{idx}/uri").as_str()));
+        let loaded = load_gltf_with(&path, &LoadOptions { allow_outside_root: true }).unwrap();
+        assert_eq!(loaded.binaries[&Some(uri.to_string())], [1, 2, 3, 4]);
+    }
+
+    // Symlinks within the directory are fine
+    write_doc(&path, "textures/inside.bin");
+    assert_eq!(load_gltf(&path).unwrap().binaries[&Some("textures/inside.bin".to_string())], [5, 6, 7, 8]);
+}
+
+#[test]
+fn uris_outside_the_root_can_be_allowed() {
+    let dir = sandbox("allowed");
+    let path = dir.join("models/scene.gltf");
+    write_doc(&path, "../secret.bin");
+    let loaded = load_gltf_with(&path, &LoadOptions { allow_outside_root: true }).unwrap();
+    assert_eq!(loaded.binaries[&Some("../secret.bin".to_string())], [1, 2, 3, 4]);
+}
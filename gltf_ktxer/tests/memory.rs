@@ -0,0 +1,66 @@
+mod common;
+
+use std::num::NonZeroUsize;
+
+use common::{blank_png_uri, doc, plan};
+use gltf_ktxer::{gltf::GltfDoc, memory::{estimate, rgba8_bytes, ByteSize, MemoryEstimate, MemoryTracker}, Error, Params};
+use serde_json::json;
+
+/// Two images, 64x64 and 32x32, the first used by two textures.
+fn two_images() -> GltfDoc {
+    doc(json!({
+        "asset": { "version": "2.0" },
+        "images": [{ "uri": blank_png_uri(64, 64) }, { "uri": blank_png_uri(32, 32) }],
+        "textures": [{ "source": 0 }, { "source": 1 }, { "source": 0 }],
+    }))
+}
+
+#[test]
+fn byte_sizes() {
+    assert_eq!("512".parse(), Ok(ByteSize(512)));
+    assert_eq!("64k".parse(), Ok(ByteSize(64 << 10)));
+    assert_eq!("2G".parse(), Ok(ByteSize(2 << 30)));
+    assert_eq!("3MiB".parse(), Ok(ByteSize(3 << 20)));
+    assert!("lots".parse::<ByteSize>().is_err());
+    assert!("2T".parse::<ByteSize>().is_err());
+    assert_eq!(ByteSize(2 << 30).to_string(), "2G");
+    assert_eq!(ByteSize(1536).to_string(), "1536");
+
+    assert_eq!(rgba8_bytes((16, 8), false), 512);
+    assert_eq!(rgba8_bytes((16, 8), true), 683);
+}
+
+#[test]
+fn planning_estimates_memory() {
+    let one_thread = Params { max_threads: NonZeroUsize::new(1), ..Params::default() };
+    let jobs = plan(&mut two_images(), one_thread).unwrap();
+    // A PNG and a KTX2 job for each image, and the third texture shares the first image
+    assert_eq!(jobs.new_images.len(), 4);
+    let (big, small) = (rgba8_bytes((64, 64), false), rgba8_bytes((32, 32), false));
+    assert_eq!(jobs.memory, MemoryEstimate { decoded_bytes: big + small, working_bytes: big, output_bytes: 2 * (big + small) });
+    assert_eq!(estimate(&jobs.new_images, 8).working_bytes, 2 * (big + small));
+}
+
+#[test]
+fn max_memory_fails_fast() {
+    let needed = plan(&mut two_images(), Params { max_threads: NonZeroUsize::new(4), ..Params::default() }).unwrap().memory.peak();
+    let fits_one_thread = plan(&mut two_images(), Params { max_threads: NonZeroUsize::new(1), ..Params::default() }).unwrap().memory.peak();
+
+    assert!(plan(&mut two_images(), Params { max_threads: NonZeroUsize::new(4), max_memory: Some(needed), ..Params::default() }).is_ok());
+    let Err(err) = plan(&mut two_images(), Params { max_threads: NonZeroUsize::new(4), max_memory: Some(fits_one_thread), ..Params::default() }) else {
+        panic!("went over the memory limit");
+    };
+    assert!(matches!(err.without_location(), Error::MemoryLimitExceeded { needed: n, .. } if *n == needed));
+    assert!(err.to_string().contains("one job at a time"), "{err}");
+}
+
+#[test]
+fn tracker_counts_live_allocations() {
+    let tracker = MemoryTracker::new(Some(100));
+    let a = tracker.alloc(60).unwrap();
+    assert!(tracker.alloc(50).is_err());
+    drop(a);
+    let _b = tracker.alloc(50).unwrap();
+    let _c = tracker.alloc(40).unwrap();
+    assert_eq!(tracker.peak(), 90);
+}
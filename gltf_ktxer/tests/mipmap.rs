@@ -0,0 +1,54 @@
+use gltf_ktxer::mipmap::{downsample_half, downsample_half_tiled, downsample_half_with_threads, TileGrid};
+use image::{Rgba, RgbaImage};
+
+#[test]
+fn even_sizes_average_2x2_blocks() {
+    let image = RgbaImage::from_fn(4, 2, |x, y| Rgba([(x * 10 + y * 40) as u8, 255, 0, (x * 50) as u8]));
+    let half = downsample_half(&image);
+    assert_eq!(half.dimensions(), (2, 1));
+    // (0 + 10 + 40 + 50) / 4 = 25, (20 + 30 + 60 + 70) / 4 = 45
+    assert_eq!(half.get_pixel(0, 0).0, [25, 255, 0, 25]);
+    assert_eq!(half.get_pixel(1, 0).0, [45, 255, 0, 125]);
+}
+
+#[test]
+fn odd_sizes_cover_every_texel() {
+    // 5 texels into 2: the middle texel is split between both outputs
+    let image = RgbaImage::from_fn(5, 1, |x, _| Rgba([if x == 4 { 250 } else { 0 }, 0, 0, 255]));
+    let half = downsample_half(&image);
+    assert_eq!(half.dimensions(), (2, 1));
+    assert_eq!(half.get_pixel(0, 0).0[0], 0);
+    // The last texel is 1 of 2.5 texels in the footprint, give or take fixed-point rounding
+    assert!(half.get_pixel(1, 0).0[0].abs_diff(100) <= 1);
+}
+
+#[test]
+fn flat_colors_stay_flat() {
+    for (width, height) in [(7, 3), (9, 9), (1, 6), (2, 1)] {
+        let image = RgbaImage::from_pixel(width, height, Rgba([255, 128, 1, 255]));
+        assert!(downsample_half(&image).pixels().all(|texel| texel.0 == [255, 128, 1, 255]), "{width}x{height}");
+    }
+}
+
+#[test]
+fn threaded_matches_single_threaded() {
+    let image = RgbaImage::from_fn(1030, 771, |x, y| Rgba([(x ^ y) as u8, (x * 3) as u8, (y * 7) as u8, (x + y) as u8]));
+    assert_eq!(downsample_half_with_threads(&image, 4), downsample_half_with_threads(&image, 1));
+}
+
+#[test]
+fn atlas_tiles_do_not_bleed() {
+    // Two 3x2 tiles, red then blue. Halving to 3x1 puts the middle output over both tiles
+    let image = RgbaImage::from_fn(6, 2, |x, _| if x < 3 { Rgba([255, 0, 0, 255]) } else { Rgba([0, 0, 255, 255]) });
+    let bled = downsample_half(&image);
+    assert_eq!(bled.get_pixel(1, 0).0, [128, 0, 128, 255]);
+
+    let grid: TileGrid = "2x1".parse().unwrap();
+    let tiled = downsample_half_tiled(&image, grid);
+    let texels: Vec<_> = tiled.pixels().map(|texel| texel.0).collect();
+    assert_eq!(texels, [[255, 0, 0, 255], [0, 0, 255, 255], [0, 0, 255, 255]]);
+
+    assert_eq!(downsample_half_tiled(&image, TileGrid::WHOLE), bled);
+    assert!("4".parse::<TileGrid>().is_err());
+    assert!("0x4".parse::<TileGrid>().is_err());
+}
@@ -0,0 +1,44 @@
+mod common;
+
+use common::{blank_png_uri, doc, plan};
+use gltf_ktxer::{edit::texture_ktx_source, gltf::{GltfDoc, GltfIndex}, ImageReencodeFormat, Params};
+use serde_json::json;
+
+/// Three images, of which only the unnamed one is used by a texture.
+fn rocks() -> GltfDoc {
+    doc(json!({
+        "asset": { "version": "2.0" },
+        "images": [{ "uri": blank_png_uri(4, 4), "name": "rock_albedo" }, { "uri": blank_png_uri(4, 4) }, { "uri": blank_png_uri(4, 4), "name": "rock_normal" }],
+        "textures": [{ "source": 1 }],
+    }))
+}
+
+#[test]
+fn orphan_images_are_ignored_by_default() {
+    let mut doc = rocks();
+    let jobs = plan(&mut doc, Params::default()).unwrap();
+    assert_eq!(jobs.new_images.len(), 2);
+    assert!(jobs.orphan_images.is_empty());
+}
+
+#[test]
+fn orphan_images_can_be_converted() {
+    let mut doc = rocks();
+    let params = Params { convert_orphan_images: true, ..Params::default() };
+    let jobs = plan(&mut doc, params).unwrap();
+
+    // Only the document's own textures are planned
+    assert_eq!(jobs.new_textures.len(), 1);
+    let texture_ktx = texture_ktx_source(&jobs.new_textures[0]).unwrap();
+    let orphans: Vec<_> = jobs.orphan_images.iter().map(|&(old, new)| (old.raw_idx(), new.raw_idx())).collect();
+    assert_eq!(orphans.iter().map(|&(old, _)| old).collect::<Vec<_>>(), [0, 2]);
+    // Only the KTX2 image is made for orphans, without an uncompressed fallback
+    assert_eq!(jobs.new_images.len(), 4);
+    for &(_, new) in &orphans {
+        assert_ne!(GltfIndex::of(new), texture_ktx);
+        assert!(matches!(jobs.new_images[new].reencode_as, ImageReencodeFormat::Ktx { .. }));
+    }
+    // The color space comes from the image's name
+    assert!(jobs.new_images[orphans[0].1].data_used_as_srgb);
+    assert!(!jobs.new_images[orphans[1].1].data_used_as_srgb);
+}
@@ -0,0 +1,78 @@
+use std::path::PathBuf;
+
+use gltf_ktxer::output::{backup_path, input_stamp, is_up_to_date, stamp_path, write_atomic, write_stamp, OverwritePolicy};
+
+fn empty_dir(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn writes_replace_the_file_and_leave_no_temporaries() {
+    let dir = empty_dir("output_replace");
+    let path = dir.join("out.glb");
+    write_atomic(&path, b"first", false).unwrap();
+    write_atomic(&path, b"second", false).unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), b"second");
+    assert!(!backup_path(&path).exists());
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+}
+
+#[test]
+fn backups_keep_the_previous_version() {
+    let dir = empty_dir("output_backup");
+    let path = dir.join("out.ktx2");
+    assert_eq!(backup_path(&path), dir.join("out.ktx2.bak"));
+
+    // Nothing to back up yet
+    write_atomic(&path, b"first", true).unwrap();
+    assert!(!backup_path(&path).exists());
+    write_atomic(&path, b"second", true).unwrap();
+    write_atomic(&path, b"third", true).unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), b"third");
+    assert_eq!(std::fs::read(backup_path(&path)).unwrap(), b"second");
+}
+
+#[test]
+fn failed_writes_leave_the_old_file() {
+    let dir = empty_dir("output_failed");
+    let path = dir.join("out.gltf");
+    write_atomic(&path, b"good", false).unwrap();
+    // Renaming a file over a directory fails after the data is written
+    std::fs::create_dir(dir.join("blocked")).unwrap();
+    assert!(write_atomic(&dir.join("blocked"), b"bad", false).is_err());
+    assert_eq!(std::fs::read(&path).unwrap(), b"good");
+    let mut names: Vec<_> = std::fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+    names.sort();
+    assert_eq!(names, ["blocked", "out.gltf"]);
+}
+
+#[test]
+fn stamps_track_inputs_and_settings() {
+    let dir = empty_dir("output_stamps");
+    let path = dir.join("out.ktx2");
+    let stamp = input_stamp("--mipmaps", &[b"image"]);
+    assert_ne!(stamp, input_stamp("", &[b"image"]));
+    assert_ne!(stamp, input_stamp("--mipmaps", &[b"other image"]));
+    assert_ne!(stamp, input_stamp("--mipmaps", &[b"image", b"level 1"]));
+
+    // No output yet, then no stamp
+    assert!(!is_up_to_date(&path, &stamp));
+    write_atomic(&path, b"ktx", false).unwrap();
+    assert!(!is_up_to_date(&path, &stamp));
+
+    write_stamp(&path, &stamp).unwrap();
+    assert_eq!(stamp_path(&path), dir.join("out.ktx2.stamp"));
+    assert!(is_up_to_date(&path, &stamp));
+    assert!(!is_up_to_date(&path, &input_stamp("", &[b"image"])));
+}
+
+#[test]
+fn overwrite_policy_names_round_trip() {
+    for policy in [OverwritePolicy::Overwrite, OverwritePolicy::SkipExisting, OverwritePolicy::IfNewer] {
+        assert_eq!(policy.to_string().parse(), Ok(policy));
+    }
+    assert!("never".parse::<OverwritePolicy>().is_err());
+}
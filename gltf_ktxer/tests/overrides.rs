@@ -0,0 +1,92 @@
+mod common;
+
+use common::{blank_png_uri, doc, plan};
+use gltf_ktxer::{adjust::ColorAdjustments, dither::Dither, gltf::GltfImage, mipmap::TileGrid, overrides::{TextureOverride, TextureOverrides}, Error, ImageReencodeFormat, KtxCodec, Params};
+use serde_json::json;
+
+const ATLAS: TextureOverride = TextureOverride { atlas: Some(TileGrid { columns: 4, rows: 4 }), dither: None, adjustments: None };
+
+#[test]
+fn overrides_match_name_then_uri() {
+    let mut overrides = TextureOverrides::default();
+    overrides.insert("atlas", ATLAS);
+    overrides.insert("textures/atlas.png", TextureOverride::default());
+
+    let named = GltfImage { name: Some("atlas".to_string()), ..GltfImage::from_uri("textures/atlas.png") };
+    assert_eq!(overrides.for_image(&named), Some(&ATLAS));
+    assert_eq!(overrides.for_image(&GltfImage::from_uri("textures/atlas.png")), Some(&TextureOverride::default()));
+    assert_eq!(overrides.for_image(&GltfImage::from_uri("textures/other.png")), None);
+}
+
+#[test]
+fn planned_ktx2_images_use_the_atlas_grid() {
+    let data_uri = blank_png_uri(8, 8);
+    let mut doc = doc(json!({
+        "asset": { "version": "2.0" },
+        "images": [{ "uri": data_uri, "name": "atlas" }, { "uri": data_uri }],
+        "textures": [{ "source": 0 }, { "source": 1 }],
+    }));
+
+    let mut params = Params { generate_mipmaps: true, ..Params::default() };
+    params.texture_overrides.insert("atlas", ATLAS);
+    let jobs = plan(&mut doc, params).unwrap();
+    let atlases: Vec<_> = jobs
+        .new_images
+        .iter()
+        .filter_map(|job| match job.reencode_as {
+            ImageReencodeFormat::Ktx { atlas, .. } => Some(atlas),
+            ImageReencodeFormat::Basic(_) | ImageReencodeFormat::Copy => None,
+        })
+        .collect();
+    assert_eq!(atlases, [ATLAS.atlas, None]);
+}
+
+#[test]
+fn dithering_only_applies_to_etc1s() {
+    let data_uri = blank_png_uri(8, 8);
+    for (codec, expected) in [(KtxCodec::Etc1s, Some(Dither::Ordered)), (KtxCodec::Uastc, None)] {
+        let mut doc = doc(json!({
+            "asset": { "version": "2.0" },
+            "images": [{ "uri": data_uri, "name": "sky" }],
+            "textures": [{ "source": 0 }],
+        }));
+        let mut params = Params { ktx_codec: codec, ..Params::default() };
+        params.texture_overrides.insert("sky", TextureOverride { dither: Some(Dither::Ordered), ..TextureOverride::default() });
+        let jobs = plan(&mut doc, params).unwrap();
+        let dithers: Vec<_> = jobs
+            .new_images
+            .iter()
+            .filter_map(|job| match job.reencode_as {
+                ImageReencodeFormat::Ktx { dither, .. } => Some(dither),
+                ImageReencodeFormat::Basic(_) | ImageReencodeFormat::Copy => None,
+            })
+            .collect();
+        assert_eq!(dithers, [expected], "{codec}");
+    }
+}
+
+#[test]
+fn adjustments_apply_to_every_output_format() {
+    let data_uri = blank_png_uri(8, 8);
+    let plan = |adjustments: ColorAdjustments| {
+        let mut doc = doc(json!({
+            "asset": { "version": "2.0" },
+            "images": [{ "uri": data_uri, "name": "wall" }],
+            "textures": [{ "source": 0 }],
+        }));
+        let mut params = Params::default();
+        params.texture_overrides.insert("wall", TextureOverride { adjustments: Some(adjustments), ..TextureOverride::default() });
+        plan(&mut doc, params)
+    };
+
+    let brighter = ColorAdjustments { exposure: 1.5, ..ColorAdjustments::default() };
+    let jobs = plan(brighter).unwrap();
+    assert_eq!(jobs.new_images.len(), 2);
+    assert!(jobs.new_images.iter().all(|job| job.adjustments == Some(brighter)));
+
+    assert!(plan(ColorAdjustments::default()).unwrap().new_images.iter().all(|job| job.adjustments.is_none()));
+    let Err(err) = plan(ColorAdjustments { gamma: -1.0, ..ColorAdjustments::default() }) else {
+        panic!("negative gamma was accepted");
+    };
+    assert!(matches!(err.without_location(), Error::BadOverride(_)));
+}
@@ -0,0 +1,82 @@
+mod common;
+
+use std::sync::Arc;
+
+use common::{doc, plan, png_uri};
+use gltf_ktxer::{
+    decision::TextureContext,
+    pipeline::{ImageTransform, Stage},
+    semantic::Semantic,
+    Error, ImageReencodeFormat, Params,
+};
+use image::{Rgba, RgbaImage};
+use serde_json::json;
+
+struct Invert;
+impl ImageTransform for Invert {
+    fn key(&self) -> String {
+        "invert".to_string()
+    }
+    fn applies_to(&self, context: &TextureContext) -> bool {
+        context.semantics.contains(&Semantic::BaseColor)
+    }
+    fn apply(&self, image: &mut RgbaImage, _srgb: bool) -> Result<(), String> {
+        image::imageops::invert(image);
+        Ok(())
+    }
+}
+
+struct Crop;
+impl ImageTransform for Crop {
+    fn key(&self) -> String {
+        "crop".to_string()
+    }
+    fn apply(&self, image: &mut RgbaImage, _srgb: bool) -> Result<(), String> {
+        *image = image::imageops::crop_imm(image, 0, 0, 1, 1).to_image();
+        Ok(())
+    }
+}
+
+fn transformed(image_transforms: Vec<Arc<dyn ImageTransform>>) -> gltf_ktxer::ReencodeJobs {
+    let uri = png_uri(&RgbaImage::from_pixel(8, 8, Rgba([10, 20, 30, 255])));
+    let mut doc = doc(json!({
+        "asset": { "version": "2.0" },
+        "images": [{ "uri": uri }, { "uri": uri }],
+        "textures": [{ "source": 0 }, { "source": 1 }],
+        "materials": [{ "pbrMetallicRoughness": { "baseColorTexture": { "index": 0 }, "metallicRoughnessTexture": { "index": 1 } } }],
+    }));
+    plan(&mut doc, Params { max_texture_size: Some(4), image_transforms, ..Params::default() }).unwrap()
+}
+
+#[test]
+fn stage_names_round_trip() {
+    for stage in Stage::ALL {
+        assert_eq!(stage.to_string().parse(), Ok(stage));
+    }
+    assert!("render".parse::<Stage>().is_err());
+    assert!(Stage::DecodeTransform > Stage::Plan && Stage::DecodeTransform < Stage::Encode);
+}
+
+#[test]
+fn transforms_apply_to_the_textures_they_choose() {
+    let jobs = transformed(vec![Arc::new(Invert)]);
+    let ktx_jobs: Vec<_> = jobs.new_images.iter().filter(|job| matches!(job.reencode_as, ImageReencodeFormat::Ktx { .. })).collect();
+    assert_eq!(ktx_jobs.len(), 2);
+
+    let base_color = ktx_jobs[0].decode_and_transform().unwrap();
+    assert_eq!(base_color.dimensions(), (4, 4));
+    assert_eq!(*base_color.get_pixel(0, 0), Rgba([245, 235, 225, 255]));
+    assert_eq!(*ktx_jobs[1].decode_and_transform().unwrap().get_pixel(0, 0), Rgba([10, 20, 30, 255]));
+
+    assert!(ktx_jobs[0].encode_params_key().ends_with(";transform=invert"));
+    assert!(!ktx_jobs[1].encode_params_key().contains("transform"));
+    assert_eq!(transformed(vec![]).new_images[0].encode_params_key(), jobs.new_images[0].encode_params_key().replace(";transform=invert", ""));
+}
+
+#[test]
+fn transforms_must_keep_the_image_size() {
+    let jobs = transformed(vec![Arc::new(Crop)]);
+    let err = jobs.new_images[0].decode_and_transform().unwrap_err();
+    assert!(matches!(&err, Error::TransformFailed { key, .. } if key == "crop"), "{err}");
+    assert_eq!(err.code().as_str(), "transform_failed");
+}
@@ -0,0 +1,36 @@
+use std::{io::Read, path::Path};
+
+use gltf_ktxer::precompress::{gzip, Sidecar};
+
+#[test]
+fn gzip_round_trips() {
+    let data: Vec<u8> = (0..10_000u32).map(|i| (i % 7) as u8).collect();
+    let compressed = gzip(&data).unwrap();
+    assert!(compressed.len() < data.len() / 10);
+    let mut decompressed = vec![];
+    flate2::read::GzDecoder::new(compressed.as_slice()).read_to_end(&mut decompressed).unwrap();
+    assert_eq!(decompressed, data);
+}
+
+#[test]
+fn sidecars_sit_next_to_the_file() {
+    assert_eq!(Sidecar::Gzip.path_for(Path::new("out/model.glb")), Path::new("out/model.glb.gz"));
+    assert_eq!(Sidecar::Brotli.path_for(Path::new("model.bin")), Path::new("model.bin.br"));
+    for sidecar in [Sidecar::Gzip, Sidecar::Brotli] {
+        assert_eq!(sidecar.to_string().parse(), Ok(sidecar));
+    }
+    assert!("zstd".parse::<Sidecar>().is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn brotli_runs_the_given_command() {
+    // Any command reading stdin and writing stdout will do
+    let data = vec![7; 200_000];
+    assert_eq!(Sidecar::Brotli.compress(&data, "cat").unwrap(), data);
+
+    let e = Sidecar::Brotli.compress(&data, "false").unwrap_err();
+    assert_eq!(e.code().as_str(), "compressor_unavailable");
+    let e = Sidecar::Brotli.compress(&data, "gltf-ktxer-no-such-compressor").unwrap_err();
+    assert!(e.to_string().contains("wasn't found"), "{e}");
+}
@@ -0,0 +1,35 @@
+use gltf_ktxer::{ktx2::fit_within, preset::Preset, KtxCodec, Params};
+use image::RgbaImage;
+
+#[test]
+fn preset_names_roundtrip() {
+    for preset in Preset::ALL {
+        assert_eq!(preset.to_string().parse::<Preset>(), Ok(preset));
+    }
+    assert!("web-slow".parse::<Preset>().unwrap_err().contains("web-fast, web-quality"));
+}
+
+#[test]
+fn presets_can_be_overridden() {
+    let params = Params { max_texture_size: Some(512), ..Params::from_preset(Preset::WebFast) };
+    assert_eq!(params.ktx_codec, KtxCodec::Etc1s);
+    assert!(params.generate_mipmaps);
+    assert_eq!(params.max_texture_size, Some(512));
+
+    let archival = Params::from_preset(Preset::ArchivalLosslessFallback);
+    assert_eq!(archival.ktx_basis_compression_quality, std::num::NonZeroU8::new(255));
+    assert_eq!((archival.ktx_codec, archival.uncompressed_format), (KtxCodec::Etc1s, image::ImageFormat::Png));
+}
+
+#[test]
+fn fit_within_keeps_aspect_ratio() {
+    assert_eq!(fit_within(&RgbaImage::new(400, 100), 200).dimensions(), (200, 50));
+    assert_eq!(fit_within(&RgbaImage::new(30, 300), 100).dimensions(), (10, 100));
+    assert_eq!(fit_within(&RgbaImage::new(16, 16), 1024).dimensions(), (16, 16));
+}
+
+#[test]
+fn presets_only_use_available_encoders() {
+    // Nothing can encode UASTC yet
+    assert!(Preset::ALL.into_iter().all(|preset| Params::from_preset(preset).ktx_codec == KtxCodec::Etc1s));
+}
@@ -0,0 +1,87 @@
+mod common;
+
+use std::{collections::HashMap, sync::Arc};
+
+use common::{blank_png_uri, doc, plan};
+use gltf_ktxer::{execute_reencode_jobs, get_reencode_jobs_per_target, gltf::GltfDoc, ktx2::Ktx2Texture, profile::{TargetProfile, TranscodeFormat}, ImageReencodeFormat, Input, Params, ReencodeJobs};
+use serde_json::json;
+
+/// A 3000x1 base color texture, wider than some platforms guarantee.
+fn wide_doc() -> GltfDoc {
+    doc(json!({
+        "asset": { "version": "2.0" },
+        "images": [{ "uri": blank_png_uri(3000, 1) }],
+        "textures": [{ "source": 0 }],
+        "materials": [{ "pbrMetallicRoughness": { "baseColorTexture": { "index": 0 } } }],
+    }))
+}
+
+fn plan_wide(params: Params) -> ReencodeJobs {
+    plan(&mut wide_doc(), params).unwrap()
+}
+
+#[test]
+fn profiles_parse_from_their_names() {
+    for profile in [TargetProfile::WebGl1, TargetProfile::WebGl2, TargetProfile::WebGpu, TargetProfile::Vulkan, TargetProfile::Gles3, TargetProfile::Metal] {
+        assert_eq!(profile.to_string().parse(), Ok(profile));
+    }
+    assert!("webgl3".parse::<TargetProfile>().is_err());
+}
+
+#[test]
+fn planner_adapts_to_the_profile() {
+    let jobs = plan_wide(Params { target: Some(TargetProfile::Gles3), max_texture_size: Some(4096), preserve_tiling: false, ..Params::default() });
+    assert!(jobs.new_images.iter().all(|job| job.max_dimension == Some(2048)));
+    assert!(!TargetProfile::Gles3.supports(TranscodeFormat::Bc1Bc3));
+    assert!(jobs.new_images.iter().any(|job| matches!(job.reencode_as, ImageReencodeFormat::Ktx { transcoded_to_bc1_or_bc3: false, .. })));
+    assert!(jobs.warnings.is_empty());
+
+    let jobs = plan_wide(Params { target: Some(TargetProfile::WebGpu), max_texture_size: Some(1024), ..Params::default() });
+    assert!(jobs.new_images.iter().all(|job| job.max_dimension == Some(1024)));
+    assert!(jobs.new_images.iter().any(|job| matches!(job.reencode_as, ImageReencodeFormat::Ktx { transcoded_to_bc1_or_bc3: true, .. })));
+}
+
+#[test]
+fn srgb_without_profile_support_is_a_warning() {
+    let jobs = plan_wide(Params { target: Some(TargetProfile::WebGl1), ..Params::default() });
+    let codes: Vec<_> = jobs.warnings.iter().map(|warning| warning.code).collect();
+    assert_eq!(codes, ["srgb_unsupported"]);
+}
+
+#[test]
+fn several_targets_share_sources() {
+    let binaries = HashMap::new();
+    let targets = [TargetProfile::Gles3, TargetProfile::WebGpu];
+    let plans = get_reencode_jobs_per_target(Input { gltf_json: &mut wide_doc(), binaries: &binaries }, &Params::default(), &targets).unwrap();
+    let max_dimensions: Vec<_> = plans.iter().map(|plan| plan.new_images[0].max_dimension).collect();
+    assert_eq!(max_dimensions, [Some(2048), Some(8192)]);
+    let sources: Vec<_> = plans.iter().flat_map(|plan| &plan.new_images).map(|job| &job.source).collect();
+    assert!(sources.iter().all(|source| Arc::ptr_eq(source, sources[0])));
+}
+
+#[test]
+fn shared_sources_are_decoded_large_enough_for_every_target() {
+    let binaries = HashMap::new();
+    let mut doc = doc(json!({
+        "asset": { "version": "2.0" },
+        "images": [{ "uri": blank_png_uri(4096, 16) }],
+        "textures": [{ "source": 0 }],
+        "materials": [{ "pbrMetallicRoughness": { "baseColorTexture": { "index": 0 } } }],
+    }));
+    let targets = [TargetProfile::WebGpu, TargetProfile::Gles3];
+    let plans = get_reencode_jobs_per_target(Input { gltf_json: &mut doc, binaries: &binaries }, &Params::default(), &targets).unwrap();
+
+    // The last target planned has the smaller limit, but mustn't shrink the first target's outputs
+    for (mut jobs, expected) in plans.into_iter().zip([(4096, 16), (2048, 8)]) {
+        let output = execute_reencode_jobs(&mut jobs, &mut doc.clone(), HashMap::new(), &Params::default()).unwrap();
+        let image_data = |idx: usize| {
+            let view = &output.gltf_json["bufferViews"][output.gltf_json["images"][idx]["bufferView"].as_u64().unwrap() as usize];
+            let offset = view.get("byteOffset").and_then(|offset| offset.as_u64()).unwrap_or(0) as usize;
+            &output.binary[offset..offset + view["byteLength"].as_u64().unwrap() as usize]
+        };
+        let fallback = image::load_from_memory(image_data(0)).unwrap();
+        assert_eq!((fallback.width(), fallback.height()), expected);
+        let ktx = Ktx2Texture::from_bytes(image_data(1)).unwrap();
+        assert_eq!((ktx.pixel_width, ktx.pixel_height), expected);
+    }
+}
@@ -0,0 +1,64 @@
+use gltf_ktxer::{
+    quality::{check_regressions, psnr, QualityReport, RegressionAction, RegressionThresholds, TextureQuality, MAX_PSNR},
+    Error,
+};
+use image::{Rgba, RgbaImage};
+
+fn report(textures: &[(&str, u64, Option<f64>)]) -> QualityReport {
+    QualityReport {
+        textures: textures.iter().map(|&(name, bytes, psnr)| TextureQuality { name: name.to_string(), bytes, psnr, codec: None, source_bytes: None, seconds: None }).collect(),
+        ..QualityReport::default()
+    }
+}
+
+#[test]
+fn psnr_measures_the_error() {
+    let reference = RgbaImage::from_pixel(4, 4, Rgba([100, 100, 100, 255]));
+    assert_eq!(psnr(&reference, &reference), MAX_PSNR);
+    // Every channel off by 1 except alpha, so the MSE is 0.75
+    let output = RgbaImage::from_pixel(4, 4, Rgba([101, 99, 101, 255]));
+    assert!((psnr(&reference, &output) - 49.38).abs() < 0.01);
+}
+
+#[test]
+fn reports_round_trip() {
+    let report = report(&[("wall.ktx2", 1000, Some(41.5)), ("floor.ktx2", 2000, None)]);
+    assert_eq!(QualityReport::from_json(&report.to_json().unwrap()).unwrap(), report);
+
+    let future = br#"{ "version": 2, "textures": [] }"#;
+    assert!(matches!(QualityReport::from_json(future), Err(Error::BadQualityReport(_))));
+}
+
+#[test]
+fn small_changes_are_allowed() {
+    let baseline = report(&[("wall.ktx2", 1000, Some(40.0))]);
+    let current = report(&[("wall.ktx2", 1040, Some(39.6)), ("new.ktx2", 5000, Some(20.0))]);
+    assert!(check_regressions(&baseline, &current, RegressionThresholds::default(), RegressionAction::Fail).unwrap().is_empty());
+}
+
+#[test]
+fn regressions_fail_or_warn() {
+    let baseline = report(&[("wall.ktx2", 1000, Some(40.0)), ("floor.ktx2", 1000, Some(40.0)), ("sky.ktx2", 1000, None)]);
+    let current = report(&[("wall.ktx2", 1100, Some(40.0)), ("floor.ktx2", 900, Some(38.0)), ("sky.ktx2", 1000, Some(10.0))]);
+
+    let e = check_regressions(&baseline, &current, RegressionThresholds::default(), RegressionAction::Fail).unwrap_err();
+    assert_eq!(e.code().as_str(), "quality_regression");
+    assert!(e.to_string().contains("'wall.ktx2' grew from 1000 to 1100 bytes (+10.0%)"), "{e}");
+
+    let warnings = check_regressions(&baseline, &current, RegressionThresholds::default(), RegressionAction::Warn).unwrap();
+    let messages: Vec<_> = warnings.iter().map(|warning| (warning.code, warning.message.as_str())).collect();
+    assert_eq!(messages, [
+        ("quality_regression", "'wall.ktx2' grew from 1000 to 1100 bytes (+10.0%), more than the 5% allowed"),
+        ("quality_regression", "'floor.ktx2' PSNR dropped from 40.00 to 38.00 dB, more than the 0.5 dB allowed"),
+    ]);
+
+    let loose = RegressionThresholds { max_size_growth_percent: 20.0, max_psnr_drop: 3.0 };
+    assert!(check_regressions(&baseline, &current, loose, RegressionAction::Fail).unwrap().is_empty());
+}
+
+#[test]
+fn regression_actions_parse() {
+    assert_eq!("warn".parse(), Ok(RegressionAction::Warn));
+    assert_eq!(RegressionAction::Fail.to_string(), "fail");
+    assert!("ignore".parse::<RegressionAction>().is_err());
+}
@@ -0,0 +1,61 @@
+mod common;
+
+use std::collections::HashMap;
+
+use common::{doc, png};
+use gltf_ktxer::{report::{error_json, render_error}, validate::{check_output_alignment, lint, validate}, ErrorCode};
+use serde_json::json;
+
+#[test]
+fn out_of_bounds_buffer_view_is_located() {
+    let doc = doc(json!({
+        "asset": { "version": "2.0" },
+        "images": [
+            { "uri": "data:image/png;base64,AAAA" },
+            { "name": "broken", "bufferView": 7, "mimeType": "image/png" },
+        ],
+    }));
+    let err = validate(&doc, &HashMap::new()).unwrap_err();
+
+    assert_eq!(err.json_pointer(), Some("/images/1/bufferView"));
+    insta::assert_snapshot!(render_error(&err, Some(&doc), false));
+    assert_eq!(err.code(), ErrorCode::IndexOutOfBounds);
+    assert_eq!(error_json(&err, Some("scene.gltf".as_ref())), json!({
+        "severity": "error",
+        "code": "index_out_of_bounds",
+        "message": "glTF document list 'bufferViews' has 0 elements, index 7 out of bounds",
+        "json_pointer": "/images/1/bufferView",
+        "file": "scene.gltf",
+    }));
+}
+
+#[test]
+fn only_outputs_need_aligned_buffer_views() {
+    let png = png(&image::RgbaImage::new(2, 2));
+    let bin = [vec![0; 2], png.clone()].concat();
+    let doc = doc(json!({
+        "asset": { "version": "2.0" },
+        "buffers": [{ "byteLength": bin.len() }],
+        "bufferViews": [{ "buffer": 0, "byteOffset": 2, "byteLength": png.len() }],
+        "images": [{ "bufferView": 0, "mimeType": "image/png" }],
+    }));
+    // glTF only requires accessors to be aligned
+    validate(&doc, &HashMap::from([(None, bin)])).unwrap();
+    let err = check_output_alignment(&doc).unwrap_err();
+    assert_eq!(err.json_pointer(), Some("/bufferViews/0/byteOffset"));
+    assert_eq!(err.code(), ErrorCode::BufferViewMisaligned);
+}
+
+#[test]
+fn unused_images_and_undeclared_extensions_warn() {
+    let doc = doc(json!({
+        "asset": { "version": "2.0" },
+        "images": [{ "uri": "a.png" }, { "uri": "b.png" }],
+        "textures": [{ "source": 0, "extensions": { "EXT_texture_webp": { "source": 0 } } }],
+    }));
+    let codes: Vec<_> = lint(&doc).unwrap().into_iter().map(|w| (w.code, w.json_pointer)).collect();
+    assert_eq!(codes, [
+        ("undeclared_extension", "/textures/0/extensions/EXT_texture_webp".to_string()),
+        ("unused_image", "/images/1".to_string()),
+    ]);
+}
@@ -0,0 +1,150 @@
+use std::{
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Barrier, Mutex,
+    },
+    time::Duration,
+};
+
+use gltf_ktxer::{
+    schedule::{asset_size, batch_order, run_batch, BatchOrder},
+    Error,
+};
+
+#[test]
+fn batch_results_keep_file_and_job_order() {
+    // Each file is its number of jobs, and file 3 fails to plan
+    let files = [3, 0, 5, 1, 2];
+    let order: Vec<usize> = (0..files.len()).collect();
+    let results = run_batch(
+        &files,
+        &order,
+        NonZeroUsize::new(3),
+        |&jobs| match jobs {
+            1 => Err(Error::BadHint("no plan".to_string())),
+            _ => Ok((0..jobs).rev().collect::<Vec<usize>>()),
+        },
+        |&file, &job| {
+            // Make later jobs finish first
+            std::thread::sleep(Duration::from_millis(job as u64));
+            file * 10 + job
+        },
+        |_, results| Ok(results),
+    );
+    let summary: Vec<_> = results.into_iter().map(|result| result.map_err(|e| e.code().as_str())).collect();
+    assert_eq!(summary, [Ok(vec![32, 31, 30]), Ok(vec![]), Ok(vec![54, 53, 52, 51, 50]), Err("bad_hint"), Ok(vec![21, 20])]);
+}
+
+#[test]
+fn batches_stay_within_the_thread_budget() {
+    let running = AtomicUsize::new(0);
+    let most_running = AtomicUsize::new(0);
+    let busy = || {
+        let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+        most_running.fetch_max(now, Ordering::SeqCst);
+        std::thread::sleep(Duration::from_millis(2));
+        running.fetch_sub(1, Ordering::SeqCst);
+    };
+    let files: Vec<usize> = (0..12).map(|idx| idx % 4).collect();
+    let order: Vec<usize> = (0..files.len()).collect();
+    let results = run_batch(
+        &files,
+        &order,
+        NonZeroUsize::new(3),
+        |&jobs| {
+            busy();
+            Ok(vec![(); jobs])
+        },
+        |_, _| busy(),
+        |_, results| Ok(results.len()),
+    );
+    assert_eq!(results.into_iter().map(Result::unwrap).collect::<Vec<_>>(), files);
+    assert!(most_running.load(Ordering::SeqCst) <= 3);
+}
+
+#[test]
+fn one_big_file_gets_every_thread() {
+    // Every job waits for the others, so this only finishes if the other workers steal the planning worker's jobs
+    let barrier = Barrier::new(4);
+    let results = run_batch(
+        &["big"],
+        &[0],
+        NonZeroUsize::new(4),
+        |_| Ok(vec![(); 4]),
+        |_, _| {
+            barrier.wait();
+        },
+        |_, results| Ok(results.len()),
+    );
+    assert_eq!(results[0].as_ref().unwrap(), &4);
+}
+
+
+#[test]
+fn batches_run_in_the_given_order() {
+    let started = Mutex::new(vec![]);
+    let files = ["a", "b", "c", "d"];
+    let results = run_batch(
+        &files,
+        &[2, 0, 3, 1],
+        NonZeroUsize::new(1),
+        |&file| {
+            started.lock().unwrap().push(file);
+            Ok(vec![()])
+        },
+        |_, _| (),
+        |&file, _| Ok(file),
+    );
+    assert_eq!(*started.lock().unwrap(), ["c", "a", "d", "b"]);
+    assert_eq!(results.into_iter().map(Result::unwrap).collect::<Vec<_>>(), files);
+}
+
+#[test]
+fn batch_order_puts_priorities_then_the_largest_first() {
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("batch_order");
+    std::fs::create_dir_all(dir.join("props")).unwrap();
+    let write = |name: &str, len: usize| {
+        std::fs::write(dir.join(name), vec![b' '; len]).unwrap();
+        dir.join(name)
+    };
+    // The .gltf is small, but refers to a big buffer
+    std::fs::write(dir.join("hero.bin"), vec![0; 5000]).unwrap();
+    std::fs::write(dir.join("hero.gltf"), r#"{ "asset": { "version": "2.0" }, "buffers": [{ "uri": "hero.bin", "byteLength": 5000 }] }"#).unwrap();
+    let paths = vec![write("tree.glb", 300), write("props/crate.glb", 100), dir.join("hero.gltf"), write("props/barrel.glb", 200), write("rock.glb", 300)];
+
+    assert_eq!(batch_order(&paths, BatchOrder::Given, &[]), [0, 1, 2, 3, 4]);
+    assert_eq!(batch_order(&paths, BatchOrder::LargestFirst, &[]), [2, 0, 4, 3, 1]);
+    let priority = ["*/props/*".to_string(), "rock.glb".to_string()];
+    assert_eq!(batch_order(&paths, BatchOrder::LargestFirst, &priority), [3, 1, 4, 2, 0]);
+    assert_eq!(batch_order(&paths, BatchOrder::Given, &priority), [1, 3, 4, 0, 2]);
+}
+
+#[test]
+fn asset_sizes_only_count_files_inside_the_documents_directory() {
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("asset_size");
+    std::fs::create_dir_all(dir.join("models")).unwrap();
+    std::fs::write(dir.join("secret.bin"), vec![0; 5000]).unwrap();
+    std::fs::write(dir.join("models/local.bin"), vec![0; 100]).unwrap();
+    let gltf = r#"{ "asset": { "version": "2.0" }, "buffers": [{ "uri": "local.bin", "byteLength": 100 }, { "uri": "../secret.bin", "byteLength": 5000 }] }"#;
+    std::fs::write(dir.join("models/scene.gltf"), gltf).unwrap();
+    assert_eq!(asset_size(&dir.join("models/scene.gltf")), gltf.len() as u64 + 100);
+
+    #[cfg(unix)]
+    {
+        let link = dir.join("models/link.bin");
+        let _ = std::fs::remove_file(&link);
+        std::os::unix::fs::symlink("../secret.bin", &link).unwrap();
+        let gltf = r#"{ "asset": { "version": "2.0" }, "buffers": [{ "uri": "link.bin", "byteLength": 5000 }] }"#;
+        std::fs::write(dir.join("models/linked.gltf"), gltf).unwrap();
+        assert_eq!(asset_size(&dir.join("models/linked.gltf")), gltf.len() as u64);
+    }
+}
+
+#[test]
+fn batch_orders_round_trip() {
+    for order in [BatchOrder::Given, BatchOrder::LargestFirst] {
+        assert_eq!(order.to_string().parse(), Ok(order));
+    }
+    assert!("smallest-first".parse::<BatchOrder>().is_err());
+}
@@ -0,0 +1,25 @@
+#![cfg(feature = "schema")]
+
+use gltf_ktxer::schema::schema_violations;
+use serde_json::json;
+
+#[test]
+fn schema_violations_have_json_pointers() {
+    let doc = json!({
+        "asset": { "version": "2.0" },
+        "buffers": [{ "byteLength": 4 }],
+        "bufferViews": [{ "buffer": 0, "byteLength": 4, "byteStride": 3 }],
+        "images": [{ "bufferView": 0 }, { "uri": "a.png" }],
+    });
+    let violations = schema_violations(doc.as_object().unwrap());
+    let pointers: Vec<_> = violations.iter().map(|v| v.json_pointer.as_str()).collect();
+    assert!(pointers.contains(&"/bufferViews/0/byteStride"), "{violations:?}");
+    assert!(pointers.contains(&"/images/0"), "{violations:?}");
+    assert!(!pointers.iter().any(|p| p.starts_with("/images/1")), "{violations:?}");
+}
+
+#[test]
+fn minimal_document_is_valid() {
+    let doc = json!({ "asset": { "version": "2.0" } });
+    assert_eq!(schema_violations(doc.as_object().unwrap()), vec![]);
+}
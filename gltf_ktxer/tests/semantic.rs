@@ -0,0 +1,107 @@
+//! Check textures are classified by the material slots referencing them, including lightmap conventions.
+
+mod common;
+
+use std::collections::BTreeSet;
+
+use common::doc;
+use gltf_ktxer::{gltf::{GltfDoc, GltfIndex}, ktx2::ColorSpace, semantic::{NameRule, Semantic, SemanticSettings, SlotRegistry, SlotRule}, KtxCodec};
+use serde_json::json;
+
+/// Texture 0 is base color, 1 is a lightmap through the second UV set, 2 is ordinary occlusion,
+/// 3 is a MOZ_lightmap and 4 sits in an unknown extension.
+fn lightmapped_doc() -> GltfDoc {
+    doc(json!({
+        "asset": { "version": "2.0" },
+        "materials": [
+            {
+                "pbrMetallicRoughness": { "baseColorTexture": { "index": 0 } },
+                "occlusionTexture": { "index": 1, "texCoord": 1 },
+            },
+            {
+                "occlusionTexture": { "index": 2 },
+                "extensions": {
+                    "MOZ_lightmap": { "index": 3, "texCoord": 1, "intensity": 1.0 },
+                    "VENDOR_detail": { "detailTexture": { "index": 4 } },
+                },
+            },
+        ],
+        "textures": [{ "source": 0 }, { "source": 1 }, { "source": 2 }, { "source": 3 }, { "source": 4 }],
+    }))
+}
+
+fn semantics_of(slots: &SlotRegistry, doc: &GltfDoc, texture: usize) -> BTreeSet<Semantic> {
+    slots.texture_semantics(doc).unwrap().remove(&GltfIndex::of(texture)).unwrap_or_default()
+}
+
+#[test]
+fn lightmap_conventions_are_recognized() {
+    let doc = lightmapped_doc();
+    let slots = SlotRegistry::default();
+    assert_eq!(semantics_of(&slots, &doc, 0), BTreeSet::from([Semantic::BaseColor]));
+    assert_eq!(semantics_of(&slots, &doc, 1), BTreeSet::from([Semantic::Lightmap]));
+    assert_eq!(semantics_of(&slots, &doc, 2), BTreeSet::from([Semantic::Occlusion]));
+    assert_eq!(semantics_of(&slots, &doc, 3), BTreeSet::from([Semantic::Lightmap]));
+    assert_eq!(semantics_of(&slots, &doc, 4), BTreeSet::from([Semantic::Other]));
+
+    let lightmap = slots.settings(Semantic::Lightmap);
+    assert_eq!(lightmap.color_space, ColorSpace::Linear);
+    assert_eq!(lightmap.codec, KtxCodec::Uastc);
+    assert!(!lightmap.rdo);
+}
+
+#[test]
+fn texture_transform_tex_coord_selects_lightmap() {
+    let mut doc = lightmapped_doc();
+    doc["materials"][1]["occlusionTexture"]["extensions"] = json!({ "KHR_texture_transform": { "texCoord": 1 } });
+    assert_eq!(semantics_of(&SlotRegistry::default(), &doc, 2), BTreeSet::from([Semantic::Lightmap]));
+}
+
+#[test]
+fn custom_rules_and_settings_take_priority() {
+    let doc = lightmapped_doc();
+    let mut slots = SlotRegistry::default();
+    slots.add_rule(SlotRule::new("extensions/VENDOR_detail/detailTexture", Semantic::Lightmap));
+    let srgb_lightmap = SemanticSettings { color_space: ColorSpace::Srgb, ..SemanticSettings::default_for(Semantic::Lightmap) };
+    slots.set_settings(Semantic::Lightmap, srgb_lightmap);
+
+    assert_eq!(semantics_of(&slots, &doc, 4), BTreeSet::from([Semantic::Lightmap]));
+    assert_eq!(slots.settings(Semantic::Lightmap), srgb_lightmap);
+    assert_eq!(slots.settings(Semantic::Occlusion), SemanticSettings::default_for(Semantic::Occlusion));
+}
+
+#[test]
+fn unreferenced_textures_are_classified_by_name() {
+    let doc = doc(json!({
+        "asset": { "version": "2.0" },
+        "materials": [{ "pbrMetallicRoughness": { "baseColorTexture": { "index": 0 } } }],
+        "images": [{ "uri": "textures/Wall_Normal.png" }, { "uri": "wall_albedo.png" }, { "uri": "wall_orm.png", "name": "Wall_ORM" }, { "uri": "wall_n.jpg" }, { "uri": "detail.png" }],
+        "textures": [
+            // Material slots take precedence over names
+            { "source": 0 },
+            { "source": 1 },
+            { "source": 2 },
+            { "extensions": { "KHR_texture_basisu": { "source": 3 } } },
+            { "source": 4, "name": "detail_nrm" },
+            { "source": 4 },
+        ],
+    }));
+    let slots = SlotRegistry::default();
+    assert_eq!(semantics_of(&slots, &doc, 0), BTreeSet::from([Semantic::BaseColor]));
+    assert_eq!(semantics_of(&slots, &doc, 1), BTreeSet::from([Semantic::BaseColor]));
+    assert_eq!(semantics_of(&slots, &doc, 2), BTreeSet::from([Semantic::MetallicRoughness]));
+    assert_eq!(semantics_of(&slots, &doc, 3), BTreeSet::from([Semantic::Normal]));
+    assert_eq!(semantics_of(&slots, &doc, 4), BTreeSet::from([Semantic::Normal]));
+    assert!(!slots.texture_semantics(&doc).unwrap().contains_key(&GltfIndex::of(5)));
+
+    let mut slots = SlotRegistry::default();
+    slots.add_name_rule(NameRule::new("detail*", Semantic::Occlusion));
+    assert_eq!(semantics_of(&slots, &doc, 4), BTreeSet::from([Semantic::Occlusion]));
+    assert_eq!(semantics_of(&slots, &doc, 5), BTreeSet::from([Semantic::Occlusion]));
+    assert_eq!(slots.semantic_for_names(["DETAIL_albedo"]), Some(Semantic::Occlusion));
+    assert_eq!(slots.semantic_for_names(["grass"]), None);
+
+    for semantic in [Semantic::BaseColor, Semantic::MetallicRoughness, Semantic::Lightmap, Semantic::Other] {
+        assert_eq!(semantic.to_string().parse(), Ok(semantic));
+    }
+}
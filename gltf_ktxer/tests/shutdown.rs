@@ -0,0 +1,54 @@
+//! The stop flag is global, so everything touching it runs in one test.
+
+use std::{num::NonZeroUsize, path::Path};
+
+use gltf_ktxer::{
+    output::{remove_temp_files, write_atomic},
+    schedule::run_batch,
+    shutdown,
+};
+
+#[test]
+fn stop_requests_finish_in_flight_work_and_cancel_the_rest() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("shutdown");
+    std::fs::create_dir_all(&dir).unwrap();
+    let output = dir.join("out.bin");
+    let _ = std::fs::remove_file(&output);
+
+    shutdown::install_handlers();
+    #[cfg(unix)]
+    {
+        // Handled, so the test process carries on
+        assert_eq!(unsafe { libc::raise(libc::SIGINT) }, 0);
+        assert!(shutdown::requested());
+        shutdown::clear();
+    }
+
+    // The first file asks to stop while planning, so it finishes but the others never start
+    let results = run_batch(
+        &[1, 2, 3],
+        &[0, 1, 2],
+        NonZeroUsize::new(1),
+        |&file| {
+            if file == 1 {
+                shutdown::request();
+            }
+            Ok(vec![file; 2])
+        },
+        |_, &job| job * 10,
+        |_, results| Ok(results),
+    );
+    let summary: Vec<_> = results.into_iter().map(|result| result.map_err(|e| e.code().as_str())).collect();
+    assert_eq!(summary, [Ok(vec![10, 10]), Err("interrupted"), Err("interrupted")]);
+
+    let e = write_atomic(&output, b"data", false).unwrap_err();
+    assert_eq!(e.code().as_str(), "interrupted");
+    assert!(!output.exists());
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0, "no temporary file is left");
+    assert!(remove_temp_files().is_empty());
+
+    shutdown::clear();
+    shutdown::check().unwrap();
+    write_atomic(&output, b"data", false).unwrap();
+    assert_eq!(std::fs::read(&output).unwrap(), b"data");
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use std::collections::HashMap;
+
+use common::doc;
+use gltf_ktxer::{
+    gltf::GltfDoc,
+    prepare_output_buffers,
+    slim::{slim_json, SlimOptions, SlimStats},
+    Input, Params,
+};
+use serde_json::json;
+
+/// A material and nodes written out with every default spelled out, as exporters tend to.
+fn blender_export() -> GltfDoc {
+    doc(json!({
+        "asset": { "version": "2.0", "extras": { "exporter": "Blender" } },
+        "extensionsUsed": [],
+        "nodes": [
+            { "name": "Root", "rotation": [0, 0, 0, 1.0], "scale": [1, 1, 1], "translation": [0, 2, 0], "children": [] },
+            { "name": "Tree", "extras": "just a note", "extensions": {} },
+        ],
+        "materials": [{
+            "name": "Bark",
+            "pbrMetallicRoughness": { "baseColorFactor": [1, 1, 1, 1], "metallicFactor": 1.0, "roughnessFactor": 1, "baseColorTexture": { "index": 0, "texCoord": 0 } },
+            "normalTexture": { "index": 0, "scale": 1, "texCoord": 1 },
+            "alphaMode": "OPAQUE",
+            "doubleSided": false,
+            "extras": { "GLTF_KTXER_hints": { "codec": "uastc" }, "blender_id": 7 },
+            "extensions": {
+                "KHR_materials_unlit": {},
+                "MY_vendor_ext": { "layers": [], "strength": 1 },
+            },
+        }],
+        "samplers": [{ "wrapS": 10497, "wrapT": 33071 }],
+        "textures": [{ "source": 0, "sampler": 0 }],
+    }))
+}
+
+#[test]
+fn slimming_keeps_the_meaning() {
+    let mut doc = blender_export();
+    let options = SlimOptions { keep_extras: vec!["GLTF_KTXER_*".to_string()], ..SlimOptions::default() };
+    let stats = slim_json(&mut doc, &options).unwrap();
+
+    assert_eq!(serde_json::Value::Object(doc.clone()), json!({
+        "asset": { "version": "2.0" },
+        "nodes": [{ "name": "Root", "translation": [0, 2, 0] }, { "name": "Tree" }],
+        "materials": [{
+            "name": "Bark",
+            "pbrMetallicRoughness": { "baseColorTexture": { "index": 0 } },
+            "normalTexture": { "index": 0, "texCoord": 1 },
+            "extras": { "GLTF_KTXER_hints": { "codec": "uastc" } },
+            // Vendor extensions are left alone, and empty extensions still mean something
+            "extensions": {
+                "KHR_materials_unlit": {},
+                "MY_vendor_ext": { "layers": [], "strength": 1 },
+            },
+        }],
+        "samplers": [{ "wrapT": 33071 }],
+        "textures": [{ "source": 0, "sampler": 0 }],
+    }));
+    assert_eq!(stats.extras_removed, 3);
+    assert_eq!(stats.defaults_removed, 10);
+    assert_eq!(stats.empty_removed, 3);
+    assert_eq!(stats.names_removed, 0);
+    assert_eq!(stats.json_bytes_after, serde_json::to_vec(&doc).unwrap().len());
+    assert!(stats.json_bytes_after < stats.json_bytes_before);
+}
+
+#[test]
+fn names_are_only_stripped_on_request() {
+    let mut doc = blender_export();
+    let options = SlimOptions { strip_extras: false, drop_defaults: false, remove_empty: false, strip_names: true, ..SlimOptions::default() };
+    let stats = slim_json(&mut doc, &options).unwrap();
+    assert_eq!(stats.names_removed, 3);
+    assert_eq!(doc["nodes"][0], json!({ "rotation": [0, 0, 0, 1.0], "scale": [1, 1, 1], "translation": [0, 2, 0], "children": [] }));
+    assert_eq!(doc["asset"]["extras"]["exporter"], "Blender");
+}
+
+#[test]
+fn slimming_is_opt_in_when_packing() {
+    let binaries = HashMap::new();
+    let mut unslimmed = blender_export();
+    let output = prepare_output_buffers(Input { gltf_json: &mut unslimmed, binaries: &binaries }, &Params::default()).unwrap();
+    assert_eq!(output.json_slim, SlimStats::default());
+    assert_eq!(output.gltf_json["nodes"][1]["extras"], "just a note");
+
+    let mut slimmed = blender_export();
+    let params = Params { slim_json: Some(SlimOptions::default()), ..Params::default() };
+    let output = prepare_output_buffers(Input { gltf_json: &mut slimmed, binaries: &binaries }, &params).unwrap();
+    assert!(output.json_slim.json_bytes_after < output.json_slim.json_bytes_before);
+    assert!(!output.gltf_json["nodes"][1].as_object().unwrap().contains_key("extras"));
+}
@@ -0,0 +1,102 @@
+//! Check the serialized form of each glTF struct against the properties allowed by the glTF 2.0 JSON schema
+//! (https://github.com/KhronosGroup/glTF/tree/main/specification/2.0/schema).
+
+use gltf_ktxer::gltf::{GltfBuffer, GltfBufferView, GltfImage, GltfTexture};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+#[derive(Clone, Copy)]
+enum Type {
+    Integer,
+    String,
+    Object,
+    Any,
+}
+
+/// (property, type, required) for each property in the schema, including those inherited from glTFChildOfRootProperty.
+const BUFFER: &[(&str, Type, bool)] = &[
+    ("uri", Type::String, false),
+    ("byteLength", Type::Integer, true),
+    ("name", Type::String, false),
+    ("extensions", Type::Object, false),
+    ("extras", Type::Any, false),
+];
+const BUFFER_VIEW: &[(&str, Type, bool)] = &[
+    ("buffer", Type::Integer, true),
+    ("byteOffset", Type::Integer, false),
+    ("byteLength", Type::Integer, true),
+    ("byteStride", Type::Integer, false),
+    ("target", Type::Integer, false),
+    ("name", Type::String, false),
+    ("extensions", Type::Object, false),
+    ("extras", Type::Any, false),
+];
+const IMAGE: &[(&str, Type, bool)] = &[
+    ("uri", Type::String, false),
+    ("mimeType", Type::String, false),
+    ("bufferView", Type::Integer, false),
+    ("name", Type::String, false),
+    ("extensions", Type::Object, false),
+    ("extras", Type::Any, false),
+];
+const TEXTURE: &[(&str, Type, bool)] = &[
+    ("sampler", Type::Integer, false),
+    ("source", Type::Integer, false),
+    ("name", Type::String, false),
+    ("extensions", Type::Object, false),
+    ("extras", Type::Any, false),
+];
+
+fn assert_matches_schema<T: Serialize>(item: &T, schema: &[(&str, Type, bool)]) {
+    let value = serde_json::to_value(item).unwrap();
+    let obj = value.as_object().expect("glTF objects must serialize to JSON objects");
+    for (key, val) in obj {
+        let Some((_, ty, _)) = schema.iter().find(|(prop, _, _)| prop == key) else {
+            panic!("{key} isn't in the schema: {value}");
+        };
+        let ok = match ty {
+            Type::Integer => val.is_u64(),
+            Type::String => val.is_string(),
+            Type::Object => val.is_object(),
+            Type::Any => !val.is_null(),
+        };
+        assert!(ok, "{key} has the wrong type: {value}");
+    }
+    for (prop, _, required) in schema {
+        assert!(!required || obj.contains_key(*prop), "missing required {prop}: {value}");
+    }
+}
+
+fn extras() -> Option<Value> {
+    Some(json!({ "author": "test" }))
+}
+
+#[test]
+fn buffers_match_schema() {
+    assert_matches_schema(&GltfBuffer::default(), BUFFER);
+    assert_matches_schema(&GltfBuffer::new(16).with_uri("a.bin").with_name("a"), BUFFER);
+    assert_matches_schema(&GltfBuffer { extras: extras(), extensions: Some(Default::default()), ..GltfBuffer::new(4) }, BUFFER);
+}
+
+#[test]
+fn buffer_views_match_schema() {
+    assert_matches_schema(&GltfBufferView::new(0.into(), 0, 4), BUFFER_VIEW);
+    assert_matches_schema(&GltfBufferView::new(1.into(), 8, 4).with_stride(12).with_target(34962).with_name("v"), BUFFER_VIEW);
+    assert_matches_schema(&GltfBufferView { extras: extras(), ..GltfBufferView::new(0.into(), 0, 4) }, BUFFER_VIEW);
+}
+
+#[test]
+fn images_match_schema() {
+    assert_matches_schema(&GltfImage::from_uri("a.png"), IMAGE);
+    assert_matches_schema(&GltfImage::from_buffer_view(3.into(), "image/ktx2").with_name("i"), IMAGE);
+}
+
+#[test]
+fn textures_match_schema() {
+    assert_matches_schema(&GltfTexture::default(), TEXTURE);
+    assert_matches_schema(&GltfTexture::new(0.into()).with_sampler(1.into()).with_name("t"), TEXTURE);
+
+    let mut texture = GltfTexture::new(0.into());
+    gltf_ktxer::edit::set_texture_ktx_source(&mut texture, 1.into());
+    assert_matches_schema(&texture, TEXTURE);
+}
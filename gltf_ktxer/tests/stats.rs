@@ -0,0 +1,66 @@
+mod common;
+
+use common::{doc, plan, png_uri};
+use gltf_ktxer::{gltf::GltfIndex, stats::ImageStats, Params};
+use image::{Rgba, RgbaImage};
+use serde_json::json;
+
+#[test]
+fn flat_images_have_no_detail() {
+    let stats = ImageStats::of(&RgbaImage::from_pixel(8, 4, Rgba([255, 0, 0, 255])));
+    assert_eq!((stats.width, stats.height, stats.channels), (8, 4, 3));
+    assert!(!stats.has_alpha && !stats.is_grayscale);
+    assert_eq!((stats.alpha_coverage, stats.unique_colors, stats.entropy, stats.average_gradient), (1.0, 1, 0.0, 0.0));
+    assert_eq!(stats.mean, [1.0, 0.0, 0.0, 1.0]);
+}
+
+#[test]
+fn alpha_and_grayscale_are_detected() {
+    let mut image = RgbaImage::from_pixel(2, 2, Rgba([255, 255, 255, 255]));
+    image.put_pixel(1, 0, Rgba([0, 0, 0, 0]));
+    image.put_pixel(1, 1, Rgba([0, 0, 0, 100]));
+    let stats = ImageStats::of(&image);
+    assert_eq!((stats.channels, stats.has_alpha, stats.is_grayscale), (2, true, true));
+    assert_eq!(stats.alpha_coverage, 0.5);
+    assert_eq!(stats.unique_colors, 3);
+    // half black, half white
+    assert_eq!(stats.entropy, 1.0);
+    // each row changes from white to black, but neither column changes
+    assert_eq!(stats.average_gradient, 0.5);
+}
+
+#[test]
+fn unique_colors_are_estimated_closely() {
+    let image = RgbaImage::from_fn(256, 256, |x, y| Rgba([x as u8, y as u8, 0, 255]));
+    let stats = ImageStats::of(&image);
+    assert!(stats.unique_colors.abs_diff(65536) < 65536 / 50, "{}", stats.unique_colors);
+    assert_eq!(stats.channels, 3);
+    assert!(stats.entropy > 6.0);
+}
+
+#[test]
+fn noise_has_a_higher_gradient_than_a_ramp() {
+    let ramp = ImageStats::of(&RgbaImage::from_fn(64, 64, |x, _| Rgba([x as u8 * 4, x as u8 * 4, x as u8 * 4, 255])));
+    let noise = ImageStats::of(&RgbaImage::from_fn(64, 64, |x, y| {
+        let v = ((x * 7919 + y * 104729) % 251) as u8;
+        Rgba([v, v, v, 255])
+    }));
+    assert!(ramp.average_gradient < 0.01 && noise.average_gradient > 0.1, "{} {}", ramp.average_gradient, noise.average_gradient);
+}
+
+#[test]
+fn plans_include_stats_when_requested() {
+    let doc = doc(json!({
+        "asset": { "version": "2.0" },
+        "images": [{ "uri": png_uri(&RgbaImage::from_pixel(4, 2, Rgba([10, 10, 10, 255]))) }],
+        "textures": [{ "source": 0 }, { "source": 0 }],
+    }));
+    let with_stats = |image_stats: bool| plan(&mut doc.clone(), Params { image_stats, ..Params::default() }).unwrap();
+
+    assert!(with_stats(false).image_stats.is_empty());
+    let jobs = with_stats(true);
+    assert_eq!(jobs.image_stats.len(), 1);
+    let stats = jobs.image_stats[&GltfIndex::of(0)];
+    assert_eq!((stats.width, stats.height, stats.channels), (4, 2, 1));
+    assert_eq!(serde_json::to_value(stats).unwrap()["unique_colors"], 1);
+}
@@ -0,0 +1,35 @@
+use gltf_ktxer::{
+    quality::{QualityReport, TextureQuality},
+    summary::render_summary,
+};
+
+fn texture(name: &str, codec: Option<&str>, source_bytes: Option<u64>, bytes: u64, seconds: Option<f64>) -> TextureQuality {
+    TextureQuality { name: name.to_string(), bytes, psnr: None, codec: codec.map(str::to_string), source_bytes, seconds }
+}
+
+#[test]
+fn summary_is_aligned_with_totals() {
+    let report = QualityReport {
+        textures: vec![
+            texture("wall.ktx2", Some("etc1s"), Some(400_000), 100_000, Some(0.25)),
+            texture("floor_normal.ktx2", Some("uastc"), Some(1_000_000), 500_000, Some(1.5)),
+            texture("sky.ktx2", None, None, 2_000, None),
+        ],
+        ..QualityReport::default()
+    };
+    assert_eq!(
+        render_summary(&report),
+        "\
+texture            codec  in-size  out-size  ratio    time
+wall.ktx2          etc1s   400000    100000  25.0%  250 ms
+floor_normal.ktx2  uastc  1000000    500000  50.0%  1.50 s
+sky.ktx2           -            -      2000      -       -
+total                     1400000    602000  42.9%  1.75 s
+"
+    );
+}
+
+#[test]
+fn empty_summary_has_zero_totals() {
+    assert_eq!(render_summary(&QualityReport::default()), "texture  codec  in-size  out-size  ratio  time\ntotal                 -         0      -     -\n");
+}
@@ -0,0 +1,40 @@
+mod common;
+
+use common::{blank_png_uri, doc, plan};
+use gltf_ktxer::{edit::texture_ktx_source, gltf::GltfDoc, tiers::TIERS_EXTRAS_KEY, Params};
+use serde_json::json;
+
+/// Two textures sharing an 8x4 PNG.
+fn shared_image() -> GltfDoc {
+    doc(json!({
+        "asset": { "version": "2.0" },
+        "images": [{ "uri": blank_png_uri(8, 4) }],
+        "textures": [{ "source": 0 }, { "source": 0, "extras": { "keep": true } }],
+    }))
+}
+
+#[test]
+fn tiers_halve_each_time_and_are_shared() {
+    let params = Params { resolution_tiers: 5, ..Params::default() };
+    let jobs = plan(&mut shared_image(), params).unwrap();
+
+    // PNG, KTX2, then 4x2, 2x1 and 1x1 tiers; 8x4 would need a fifth tier of 0 texels
+    assert_eq!(jobs.new_images.len(), 5);
+    let tier_sizes: Vec<_> = jobs.new_images[2..].iter().map(|job| job.max_dimension).collect();
+    assert_eq!(tier_sizes, [Some(4), Some(2), Some(1)]);
+    assert!(jobs.new_images[2..].iter().all(|job| job.reencode_as == jobs.new_images[1].reencode_as));
+
+    let expected = json!([{ "source": 2, "scale": 0.5 }, { "source": 3, "scale": 0.25 }, { "source": 4, "scale": 0.125 }]);
+    for tex in &jobs.new_textures {
+        assert_eq!(texture_ktx_source(tex).map(|img| img.raw_idx()), Some(1));
+        assert_eq!(tex.extras.as_ref().unwrap()[TIERS_EXTRAS_KEY], expected);
+    }
+    assert_eq!(jobs.new_textures[1].extras.as_ref().unwrap()["keep"], true);
+}
+
+#[test]
+fn one_tier_is_just_the_full_image() {
+    let jobs = plan(&mut shared_image(), Params::default()).unwrap();
+    assert_eq!(jobs.new_images.len(), 2);
+    assert!(jobs.new_textures[0].extras.is_none());
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use common::{doc, plan, png_uri};
+use gltf_ktxer::{
+    gltf::GltfDoc,
+    mipmap::TileGrid,
+    tiling::{halve_within, halved_dimensions},
+    Params,
+};
+use image::{Rgba, RgbaImage};
+use serde_json::json;
+
+/// A 12x6 seamless texture: a horizontal gradient up to the middle and back down, so its first and last columns match.
+fn seamless() -> RgbaImage {
+    RgbaImage::from_fn(12, 6, |x, _| {
+        let v = (x.min(11 - x) * 40) as u8;
+        Rgba([v, v, v, 255])
+    })
+}
+
+/// A document using [seamless] as a base color texture, sampled by `sampler`.
+fn seamless_doc(sampler: serde_json::Value) -> GltfDoc {
+    doc(json!({
+        "asset": { "version": "2.0" },
+        "images": [{ "uri": png_uri(&seamless()) }],
+        "samplers": [sampler],
+        "textures": [{ "source": 0, "sampler": 0 }],
+        "materials": [{ "pbrMetallicRoughness": { "baseColorTexture": { "index": 0 } } }],
+    }))
+}
+
+/// The jobs for `doc` with textures no bigger than 8 texels.
+fn plan_within_8(doc: &mut GltfDoc, params: Params) -> gltf_ktxer::ReencodeJobs {
+    plan(doc, Params { max_texture_size: Some(8), ..params }).unwrap()
+}
+
+#[test]
+fn halving_rounds_down_until_it_fits() {
+    assert_eq!(halved_dimensions((12, 6), 8), (6, 3));
+    assert_eq!(halved_dimensions((12, 6), 12), (12, 6));
+    assert_eq!(halved_dimensions((3000, 1), 1024), (750, 1));
+    assert_eq!(halved_dimensions((5, 5), 0), (1, 1));
+}
+
+#[test]
+fn halved_images_still_tile() {
+    let halved = halve_within(&seamless(), 8, TileGrid::WHOLE);
+    assert_eq!(halved.dimensions(), (6, 3));
+    // Each texel averages exactly two source texels, so the gradient stays symmetric and the edges still match
+    let row: Vec<u8> = (0..6).map(|x| halved.get_pixel(x, 0)[0]).collect();
+    assert_eq!(row, [20, 100, 180, 180, 100, 20]);
+}
+
+#[test]
+fn repeating_textures_are_halved() {
+    let mut doc = seamless_doc(json!({}));
+    let jobs = plan_within_8(&mut doc, Params::default());
+
+    assert!(jobs.new_images.iter().all(|job| job.repeats));
+    let ktx = jobs.new_images.iter().find(|job| matches!(job.reencode_as, gltf_ktxer::ImageReencodeFormat::Ktx { .. })).unwrap();
+    assert_eq!(ktx.fit_dimensions((12, 6)), (6, 3));
+    assert_eq!(ktx.decode_and_transform().unwrap().dimensions(), (6, 3));
+    let restricted: Vec<_> = jobs.warnings.iter().filter(|warning| warning.code == "tiling_downscale_restricted").map(|warning| warning.json_pointer.as_str()).collect();
+    assert_eq!(restricted, ["/textures/0"; 2]);
+}
+
+#[test]
+fn clamped_textures_are_resized() {
+    let mut doc = seamless_doc(json!({ "wrapS": 33071, "wrapT": 33071 }));
+    let jobs = plan_within_8(&mut doc, Params::default());
+
+    assert!(jobs.new_images.iter().all(|job| !job.repeats && job.fit_dimensions((12, 6)) == (8, 4)));
+    assert!(jobs.warnings.is_empty());
+}
+
+#[test]
+fn tiling_can_be_ignored() {
+    let mut doc = seamless_doc(json!({}));
+    let jobs = plan_within_8(&mut doc, Params { preserve_tiling: false, ..Params::default() });
+
+    assert!(jobs.new_images.iter().all(|job| !job.repeats));
+    assert!(jobs.warnings.is_empty());
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use std::num::NonZeroUsize;
+
+use common::{blank_png_uri, doc, plan};
+use gltf_ktxer::{
+    tuning::{describe, EncodeEffort, HostCapabilities, Tuning},
+    ImageReencodeFormat, Params,
+};
+use serde_json::json;
+
+fn host(cores: usize, simd: &[&'static str]) -> HostCapabilities {
+    HostCapabilities { cores: NonZeroUsize::new(cores).unwrap(), simd: simd.to_vec() }
+}
+
+#[test]
+fn effort_grows_with_the_host() {
+    let efforts: Vec<EncodeEffort> = [1, 4, 8, 16, 64].into_iter().map(|cores| Tuning::auto(&host(cores, &["sse4.1", "avx2"])).effort).collect();
+    assert_eq!(efforts, [EncodeEffort::Fast, EncodeEffort::Fast, EncodeEffort::Balanced, EncodeEffort::Balanced, EncodeEffort::Thorough]);
+    // The encoder has no vectorized paths, so SIMD support doesn't matter
+    assert_eq!(Tuning::auto(&host(64, &[])), Tuning::auto(&host(64, &["neon"])));
+    assert_eq!(Tuning::auto(&host(12, &[])).threads.get(), 12);
+}
+
+#[test]
+fn detection_finds_at_least_one_core() {
+    let detected = HostCapabilities::detect();
+    assert!(detected.cores.get() >= 1);
+    let tuning = Tuning::auto(&detected);
+    assert!(describe(&detected, &tuning).ends_with(&tuning.to_string()));
+    assert_eq!(describe(&host(8, &["sse4.1", "avx2"]), &Tuning::auto(&host(8, &["avx2"]))), "8 cores, sse4.1, avx2: balanced effort, 8 threads");
+    assert_eq!(describe(&host(2, &[]), &Tuning::auto(&host(2, &[]))), "2 cores, no SIMD: fast effort, 2 threads");
+    assert_eq!(describe(&host(1, &[]), &Tuning::auto(&host(1, &[]))), "1 core, no SIMD: fast effort, 1 thread");
+}
+
+#[test]
+fn explicit_settings_are_kept() {
+    let tuning = Tuning::auto(&host(32, &["avx2"]));
+    let mut params = Params::default();
+    assert_eq!(tuning.apply(&mut params), tuning);
+    assert_eq!((params.encode_effort, params.max_threads), (Some(EncodeEffort::Thorough), NonZeroUsize::new(32)));
+
+    let mut params = Params { encode_effort: Some(EncodeEffort::Fast), ..Params::default() };
+    let applied = tuning.apply(&mut params);
+    assert_eq!((applied.effort, applied.threads.get()), (EncodeEffort::Fast, 32));
+}
+
+#[test]
+fn effort_names_round_trip() {
+    for effort in [EncodeEffort::Fast, EncodeEffort::Balanced, EncodeEffort::Thorough] {
+        assert_eq!(effort.to_string().parse(), Ok(effort));
+    }
+    assert!("max".parse::<EncodeEffort>().unwrap_err().contains("'fast', 'balanced' or 'thorough'"));
+}
+
+#[test]
+fn effort_reaches_ktx_jobs() {
+    let mut doc = doc(json!({
+        "asset": { "version": "2.0" },
+        "images": [{ "uri": blank_png_uri(4, 4) }],
+        "textures": [{ "source": 0 }],
+    }));
+    let params = Params { encode_effort: Some(EncodeEffort::Thorough), ..Params::default() };
+    let jobs = plan(&mut doc, params).unwrap();
+    let ktx = jobs.new_images.iter().find(|job| matches!(job.reencode_as, ImageReencodeFormat::Ktx { .. })).unwrap();
+    assert!(matches!(ktx.reencode_as, ImageReencodeFormat::Ktx { effort: Some(EncodeEffort::Thorough), .. }));
+    assert!(ktx.encode_params_key().contains(";effort=thorough"));
+}
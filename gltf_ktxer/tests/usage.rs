@@ -0,0 +1,79 @@
+mod common;
+
+use std::collections::BTreeSet;
+
+use common::{blank_png_uri, doc, plan};
+use gltf_ktxer::{
+    edit::texture_ktx_source,
+    gltf::{GltfDoc, GltfIndex, GltfSampler},
+    mipmap::TileGrid,
+    overrides::TextureOverride,
+    usage::{texture_usages, TextureUsage},
+    Params,
+};
+use serde_json::json;
+
+/// Three textures sharing the image `atlas`: 0 and 1 sampled the same way, 2 clamped.
+fn atlas_doc() -> GltfDoc {
+    doc(json!({
+        "asset": { "version": "2.0" },
+        "images": [{ "uri": blank_png_uri(4, 4), "name": "atlas" }],
+        "samplers": [{ "wrapS": 33071, "wrapT": 33071 }],
+        "textures": [{ "source": 0 }, { "source": 0 }, { "source": 0, "sampler": 0 }],
+        "materials": [
+            { "pbrMetallicRoughness": { "baseColorTexture": { "index": 0 } } },
+            { "pbrMetallicRoughness": { "baseColorTexture": { "index": 1 } }, "emissiveTexture": { "index": 2 } },
+        ],
+    }))
+}
+
+fn atlas_params() -> Params {
+    let mut params = Params::default();
+    params.texture_overrides.insert("atlas", TextureOverride { atlas: Some(TileGrid { columns: 2, rows: 2 }), ..TextureOverride::default() });
+    params
+}
+
+const REPEAT: TextureUsage = TextureUsage { tex_coord: 0, wrap_s: GltfSampler::WRAP_REPEAT, wrap_t: GltfSampler::WRAP_REPEAT };
+
+#[test]
+fn usages_combine_tex_coord_and_sampler() {
+    let mut doc = atlas_doc();
+    doc["materials"][0]["occlusionTexture"] = json!({ "index": 0, "texCoord": 1 });
+    let usages = texture_usages(&doc).unwrap();
+
+    assert_eq!(usages[&GltfIndex::of(0)], BTreeSet::from([REPEAT, TextureUsage { tex_coord: 1, ..REPEAT }]));
+    assert_eq!(usages[&GltfIndex::of(1)], BTreeSet::from([REPEAT]));
+    let clamped = GltfSampler::WRAP_CLAMP_TO_EDGE;
+    assert_eq!(usages[&GltfIndex::of(2)], BTreeSet::from([TextureUsage { tex_coord: 0, wrap_s: clamped, wrap_t: clamped }]));
+}
+
+#[test]
+fn atlases_are_split_by_usage() {
+    let mut doc = atlas_doc();
+    let jobs = plan(&mut doc, atlas_params()).unwrap();
+
+    let ktx: Vec<_> = jobs.new_textures.iter().map(|tex| texture_ktx_source(tex).unwrap()).collect();
+    assert_eq!(ktx[0], ktx[1]);
+    assert_ne!(ktx[0], ktx[2]);
+    assert_ne!(jobs.new_textures[0].source, jobs.new_textures[2].source);
+    assert!(jobs.warnings.iter().all(|warning| warning.code != "texture_usage_conflict"));
+}
+
+#[test]
+fn textures_are_shared_without_atlases() {
+    let mut doc = atlas_doc();
+    let jobs = plan(&mut doc, Params::default()).unwrap();
+
+    let ktx: Vec<_> = jobs.new_textures.iter().map(|tex| texture_ktx_source(tex).unwrap()).collect();
+    assert_eq!(ktx, [ktx[0]; 3]);
+}
+
+#[test]
+fn textures_sampled_several_ways_warn() {
+    let mut doc = atlas_doc();
+    doc["materials"][0]["occlusionTexture"] = json!({ "index": 0, "texCoord": 1 });
+    let jobs = plan(&mut doc, atlas_params()).unwrap();
+
+    let conflicts: Vec<_> = jobs.warnings.iter().filter(|warning| warning.code == "texture_usage_conflict").map(|warning| warning.json_pointer.as_str()).collect();
+    assert_eq!(conflicts, ["/textures/0"]);
+}
@@ -0,0 +1,36 @@
+use gltf_ktxer::{pipeline::ImageTransform, uv_checker::UvChecker};
+use image::{Rgba, RgbaImage};
+
+#[test]
+fn cells_are_tinted_by_uv_and_alternate() {
+    let mut image = RgbaImage::from_pixel(8, 8, Rgba([0, 0, 0, 77]));
+    UvChecker { cells: 2, ..UvChecker::default() }.apply(&mut image, true).unwrap();
+    let top_left = *image.get_pixel(0, 0);
+    let top_right = *image.get_pixel(7, 0);
+    let bottom_left = *image.get_pixel(0, 7);
+    let bottom_right = *image.get_pixel(7, 7);
+    assert_eq!(top_left, Rgba([64, 64, 160, 77]));
+    assert_eq!(bottom_right, Rgba([255, 255, 160, 77]));
+    // the other two cells are dark, and tinted along one axis each
+    assert_eq!(top_right, Rgba([85, 21, 53, 77]));
+    assert_eq!(bottom_left, Rgba([21, 85, 53, 77]));
+    assert_eq!(*image.get_pixel(3, 3), top_left);
+}
+
+#[test]
+fn pattern_is_the_same_at_any_resolution() {
+    let checker = UvChecker::default();
+    let mut large = RgbaImage::new(64, 32);
+    let mut small = RgbaImage::new(16, 8);
+    checker.apply(&mut large, true).unwrap();
+    checker.apply(&mut small, true).unwrap();
+    assert_eq!(image::imageops::resize(&large, 16, 8, image::imageops::FilterType::Nearest), small);
+}
+
+#[test]
+fn at_least_one_cell_is_needed() {
+    assert!(UvChecker { cells: 0, ..UvChecker::default() }.apply(&mut RgbaImage::new(2, 2), true).is_err());
+    let mut one = RgbaImage::new(2, 2);
+    UvChecker { cells: 1, ..UvChecker::default() }.apply(&mut one, true).unwrap();
+    assert!(one.pixels().all(|&texel| texel == Rgba([64, 64, 160, 0])));
+}
@@ -0,0 +1,89 @@
+mod common;
+
+use std::collections::HashMap;
+
+use common::{blank_png_uri, doc, plan};
+use gltf_ktxer::{
+    bundle::texture_bundle,
+    edit::{texture_ktx_source, ReferenceRegistry},
+    gc::live_objects,
+    gltf::GltfDoc,
+    validate::validate,
+    variants::material_variants,
+    Params,
+};
+use serde_json::json;
+
+/// A primitive whose default material has no textures, with a "Red" variant using textures only it refers to.
+fn variants_doc() -> GltfDoc {
+    doc(json!({
+        "asset": { "version": "2.0" },
+        "extensionsUsed": ["KHR_materials_variants"],
+        "extensions": { "KHR_materials_variants": { "variants": [{ "name": "Plain" }, { "name": "Red" }] } },
+        "images": [{ "uri": blank_png_uri(4, 4) }, { "uri": blank_png_uri(4, 4) }],
+        "textures": [{ "source": 0 }, { "source": 1 }],
+        "materials": [
+            { "name": "Default" },
+            { "name": "Red", "pbrMetallicRoughness": { "baseColorTexture": { "index": 0 } }, "normalTexture": { "index": 1 } },
+        ],
+        "meshes": [{
+            "primitives": [{
+                "attributes": {},
+                "material": 0,
+                "extensions": { "KHR_materials_variants": { "mappings": [{ "material": 0, "variants": [0] }, { "material": 1, "variants": [1] }] } },
+            }],
+        }],
+        "nodes": [{ "mesh": 0 }],
+        "scenes": [{ "nodes": [0] }],
+    }))
+}
+
+#[test]
+fn mappings_are_read() {
+    let variants = material_variants(&variants_doc()).unwrap();
+    assert_eq!(variants.names, ["Plain", "Red"]);
+    assert_eq!(variants.names_of(0), ["Plain"]);
+    assert_eq!(variants.names_of(1), ["Red"]);
+    assert!(material_variants(&doc(json!({ "asset": { "version": "2.0" } }))).unwrap().materials.is_empty());
+}
+
+#[test]
+fn bad_mappings_are_invalid() {
+    let mut bad_variant = variants_doc();
+    bad_variant["meshes"][0]["primitives"][0]["extensions"]["KHR_materials_variants"]["mappings"][1]["variants"] = json!([2]);
+    let e = validate(&bad_variant, &HashMap::new()).unwrap_err();
+    assert_eq!(e.code().as_str(), "index_out_of_bounds");
+    assert_eq!(e.json_pointer(), Some("/meshes/0/primitives/0/extensions/KHR_materials_variants/mappings/1/variants/0"));
+
+    let mut bad_material = variants_doc();
+    bad_material["meshes"][0]["primitives"][0]["extensions"]["KHR_materials_variants"]["mappings"][1]["material"] = json!(5);
+    let e = validate(&bad_material, &HashMap::new()).unwrap_err();
+    assert_eq!(e.json_pointer(), Some("/meshes/0/primitives/0/extensions/KHR_materials_variants/mappings/1/material"));
+}
+
+#[test]
+fn variant_only_textures_are_classified() {
+    let mut doc = variants_doc();
+    let jobs = plan(&mut doc, Params::default()).unwrap();
+    let srgb: Vec<_> = jobs.new_textures.iter().map(|tex| jobs.new_images[texture_ktx_source(tex).unwrap().raw_idx()].data_used_as_srgb).collect();
+    assert_eq!(srgb, [true, false]);
+}
+
+#[test]
+fn variant_only_textures_are_kept() {
+    let live = live_objects(&variants_doc(), &ReferenceRegistry::default()).unwrap();
+    assert_eq!(live["materials"].len(), 2);
+    assert_eq!(live["textures"].len(), 2);
+    assert_eq!(live["images"].len(), 2);
+}
+
+#[test]
+fn bundles_name_each_materials_variants() {
+    let mut doc = variants_doc();
+    let jobs = plan(&mut doc, Params::default()).unwrap();
+    let bundle = texture_bundle(&doc, &jobs).unwrap();
+    let materials = bundle.mapping["materials"].as_array().unwrap();
+    assert_eq!(materials[0]["variants"], json!(["Plain"]));
+    assert_eq!(materials[1]["variants"], json!(["Red"]));
+    assert_eq!(materials[1]["slots"].as_object().unwrap().len(), 2);
+}
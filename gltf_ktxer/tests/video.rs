@@ -0,0 +1,58 @@
+mod common;
+
+use std::collections::HashMap;
+
+use common::{blank_png_uri, doc, png};
+use gltf_ktxer::{get_reencode_jobs, video::is_video, ImageReencodeFormat, Input, Params};
+use serde_json::json;
+
+const MP4: &[u8] = b"\0\0\0\x18ftypmp42\0\0\0\0mp42isom";
+
+#[test]
+fn videos_are_recognised_by_mime_type_or_signature() {
+    assert!(is_video(Some("video/mp4"), b""));
+    assert!(is_video(None, MP4));
+    assert!(is_video(None, b"\x1a\x45\xdf\xa3webm"));
+    assert!(is_video(None, b"OggS\0"));
+    // AVIF uses the same container as MP4
+    assert!(!is_video(Some("image/avif"), b"\0\0\0\x1cftypavif\0\0\0\0avifmif1"));
+    assert!(!is_video(Some("image/png"), &png(&image::RgbaImage::new(4, 4))));
+}
+
+#[test]
+fn video_textures_are_left_unchanged() {
+    let mut doc = doc(json!({
+        "asset": { "version": "2.0" },
+        "images": [
+            { "uri": blank_png_uri(4, 4) },
+            { "uri": "clip.mp4", "mimeType": "video/mp4" },
+            { "uri": "clip.bin" },
+        ],
+        "textures": [
+            // A poster image for clients without video support, and the video itself
+            { "source": 0, "extensions": { "EXT_texture_video": { "source": 1, "loop": true } } },
+            { "source": 2 },
+            { "source": 0 },
+        ],
+    }));
+    let binaries = HashMap::from([(Some("clip.mp4".to_string()), MP4.to_vec()), (Some("clip.bin".to_string()), MP4.to_vec())]);
+    let jobs = get_reencode_jobs(Input { gltf_json: &mut doc, binaries: &binaries }, Params::default()).unwrap();
+
+    let textures = &jobs.new_textures;
+    assert_eq!(textures[0].source.raw_idx(), 0);
+    assert_eq!(textures[0].extensions.as_ref().unwrap()["EXT_texture_video"], json!({ "source": 1, "loop": true }));
+    assert_eq!(textures[1].source.raw_idx(), 2);
+    assert!(textures[1].extensions.is_none());
+    for idx in 0..3 {
+        assert_eq!(jobs.new_images[idx].reencode_as, ImageReencodeFormat::Copy, "{idx}");
+    }
+    assert_eq!(jobs.new_images[1].source.mime_type, "video/mp4");
+    // The poster is still encoded for the texture using it on its own
+    assert!(jobs.new_images[3..].iter().all(|job| job.reencode_as != ImageReencodeFormat::Copy));
+
+    let skipped: Vec<_> = jobs.warnings.iter().filter(|warning| warning.code == "video_texture_skipped").map(|warning| (warning.json_pointer.as_str(), warning.message.as_str())).collect();
+    assert_eq!(skipped, [
+        ("/textures/0", "texture uses EXT_texture_video, so the texture is left unchanged"),
+        ("/textures/1", "image 2 is a video, so the texture is left unchanged"),
+    ]);
+}
@@ -0,0 +1,84 @@
+mod common;
+
+use std::sync::Arc;
+
+use common::{doc, plan, png_uri};
+use gltf_ktxer::{pipeline::ImageTransform, watermark::{Position, Watermark}, ImageReencodeFormat, Params};
+use image::{Rgba, RgbaImage};
+use serde_json::json;
+
+const GRAY: Rgba<u8> = Rgba([100, 100, 100, 255]);
+
+#[test]
+fn position_names_round_trip() {
+    for position in [Position::TopLeft, Position::TopRight, Position::BottomLeft, Position::BottomRight, Position::Center] {
+        assert_eq!(position.to_string().parse(), Ok(position));
+    }
+    assert!("middle".parse::<Position>().is_err());
+}
+
+#[test]
+fn text_is_white_on_black() {
+    let watermark = Watermark::text("1", 2);
+    // one glyph and a pixel of border, twice as big
+    assert_eq!(watermark.overlay().dimensions(), ((3 + 2) * 2, (5 + 2) * 2));
+    assert_eq!(*watermark.overlay().get_pixel(0, 0), Rgba([0, 0, 0, 255]));
+    // the top of the 1's stem
+    assert_eq!(*watermark.overlay().get_pixel(4, 2), Rgba([255; 4]));
+    assert_eq!(*watermark.overlay().get_pixel(2, 2), Rgba([0, 0, 0, 255]));
+}
+
+#[test]
+fn overlays_go_in_the_chosen_corner() {
+    let overlay = RgbaImage::from_pixel(2, 2, Rgba([255, 0, 0, 255]));
+    let stamp = |position: Position| {
+        let mut image = RgbaImage::from_pixel(10, 8, GRAY);
+        Watermark::image(overlay.clone()).with_position(position).with_margin(1).apply(&mut image, true).unwrap();
+        image
+    };
+    let red = Rgba([255, 0, 0, 255]);
+    let image = stamp(Position::BottomRight);
+    assert_eq!((*image.get_pixel(7, 5), *image.get_pixel(8, 6), *image.get_pixel(9, 7)), (red, red, GRAY));
+    let image = stamp(Position::TopLeft);
+    assert_eq!((*image.get_pixel(0, 0), *image.get_pixel(1, 1), *image.get_pixel(2, 2)), (GRAY, red, red));
+    let image = stamp(Position::Center);
+    assert_eq!((*image.get_pixel(4, 3), *image.get_pixel(5, 4), *image.get_pixel(6, 5)), (red, red, GRAY));
+
+    // Larger than the image, so as much as fits from the top left
+    let mut small = RgbaImage::from_pixel(1, 1, GRAY);
+    Watermark::image(overlay.clone()).apply(&mut small, true).unwrap();
+    assert_eq!(*small.get_pixel(0, 0), red);
+}
+
+#[test]
+fn opacity_blends_with_the_image() {
+    let mut image = RgbaImage::from_pixel(1, 1, Rgba([0, 0, 0, 0]));
+    let watermark = Watermark::image(RgbaImage::from_pixel(1, 1, Rgba([255; 4]))).with_opacity(0.5).with_margin(0);
+    watermark.apply(&mut image, true).unwrap();
+    assert_eq!(*image.get_pixel(0, 0), Rgba([128; 4]));
+
+    assert!(watermark.clone().with_opacity(1.5).apply(&mut image, true).is_err());
+    assert_ne!(watermark.key(), watermark.clone().with_opacity(0.25).key());
+    assert_ne!(watermark.key(), Watermark::text("a", 1).key());
+}
+
+#[test]
+fn only_base_color_textures_are_stamped() {
+    let uri = png_uri(&RgbaImage::from_pixel(32, 32, GRAY));
+    let mut doc = doc(json!({
+        "asset": { "version": "2.0" },
+        "images": [{ "uri": uri }, { "uri": uri }],
+        "textures": [{ "source": 0 }, { "source": 1 }],
+        "materials": [{ "normalTexture": { "index": 0 }, "pbrMetallicRoughness": { "baseColorTexture": { "index": 1 } } }],
+    }));
+    let params = Params { image_transforms: vec![Arc::new(Watermark::text("rc1", 1))], ..Params::default() };
+    let jobs = plan(&mut doc, params).unwrap();
+    let images: Vec<RgbaImage> = jobs
+        .new_images
+        .iter()
+        .filter(|job| matches!(job.reencode_as, ImageReencodeFormat::Ktx { .. }))
+        .map(|job| job.decode_and_transform().unwrap())
+        .collect();
+    assert!(images[0].pixels().all(|&texel| texel == GRAY));
+    assert_eq!(*images[1].get_pixel(31 - 4, 31 - 4), Rgba([0, 0, 0, 255]));
+}
@@ -0,0 +1,119 @@
+//! Load outputs with the KTX2 loaders of three.js and Babylon.js, which reject some files validation accepts,
+//! e.g. ones missing `extensionsRequired` or using a supercompression scheme a loader can't handle.
+//!
+//! Needs Node.js and npm: `cargo test --test web_loaders -- --ignored`. See `tests/web_loaders/check.mjs` for what's checked.
+
+mod common;
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use common::png;
+use gltf_ktxer::{
+    edit,
+    glb::{self, JsonFormat},
+    gltf::{GltfDoc, GltfImage, GltfTexture, GltfTextureInfo},
+    ktx2::{ColorSpace, Ktx2Texture},
+};
+use image::{Rgba, RgbaImage};
+use serde_json::{json, Value};
+
+const HARNESS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/web_loaders");
+
+/// The harness, with its npm dependencies installed, in the cargo target directory.
+fn harness_dir() -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("web_loaders");
+    std::fs::create_dir_all(&dir).unwrap();
+    for file in ["package.json", "check.mjs"] {
+        std::fs::copy(Path::new(HARNESS_DIR).join(file), dir.join(file)).unwrap();
+    }
+    if !dir.join("node_modules").exists() {
+        let status = Command::new("npm")
+            .args(["install", "--no-audit", "--no-fund"])
+            .current_dir(&dir)
+            .status()
+            .expect("couldn't run npm");
+        assert!(status.success(), "couldn't install the loaders");
+    }
+    dir
+}
+
+/// A document with one material, whose base color texture `add_texture` adds.
+fn glb_with_texture(add_texture: impl FnOnce(&mut GltfDoc, &mut Vec<u8>) -> GltfTextureInfo) -> Vec<u8> {
+    let Value::Object(mut doc) = json!({
+        "asset": { "version": "2.0" },
+        "materials": [{ "pbrMetallicRoughness": {} }],
+    }) else {
+        unreachable!()
+    };
+    let mut bin = vec![];
+    let info = add_texture(&mut doc, &mut bin);
+    edit::set_material_texture(&mut doc, 0.into(), "pbrMetallicRoughness/baseColorTexture", &info).unwrap();
+    glb::write(&doc, &bin, JsonFormat::Minified).unwrap()
+}
+
+fn base_color() -> RgbaImage {
+    RgbaImage::from_fn(8, 4, |x, y| Rgba([x as u8 * 32, y as u8 * 64, 128, 255]))
+}
+
+/// The base color texture is only available as KTX2, so KHR_texture_basisu is required.
+fn ktx2_only() -> Vec<u8> {
+    glb_with_texture(|doc, bin| {
+        let ktx = Ktx2Texture::from_rgba8_mipmapped(&base_color(), ColorSpace::Srgb).unwrap();
+        let sampler = edit::append_sampler(doc, &json!({})).unwrap();
+        GltfTextureInfo::new(edit::add_ktx2_texture(doc, bin, &ktx, sampler).unwrap())
+    })
+}
+
+/// The base color texture has a PNG fallback for loaders without KHR_texture_basisu.
+fn ktx2_with_png_fallback() -> Vec<u8> {
+    glb_with_texture(|doc, bin| {
+        let png = png(&base_color());
+        let png_view = edit::append_buffer_view(doc, bin, &png, 4, None).unwrap();
+        let png_image = edit::append_image(doc, &GltfImage::from_buffer_view(png_view, "image/png")).unwrap();
+
+        let ktx = Ktx2Texture::from_rgba8(&base_color(), ColorSpace::Srgb).unwrap();
+        let ktx_view = edit::append_ktx2_buffer_view(doc, bin, &ktx).unwrap();
+        let ktx_image = edit::append_image(doc, &GltfImage::from_buffer_view(ktx_view, "image/ktx2")).unwrap();
+
+        let mut texture = GltfTexture::new(png_image);
+        edit::set_texture_ktx_source(&mut texture, ktx_image);
+        edit::add_extension_used(doc, "KHR_texture_basisu", false).unwrap();
+        GltfTextureInfo::new(edit::append_texture(doc, &texture).unwrap())
+    })
+}
+
+#[test]
+#[ignore = "installs three.js and Babylon.js with npm, and runs them with Node.js"]
+fn web_loaders_accept_outputs() {
+    let dir = harness_dir();
+    let fixtures = dir.join("fixtures");
+    std::fs::create_dir_all(&fixtures).unwrap();
+    let linear_mipmapped = Ktx2Texture::from_rgba8_mipmapped(&RgbaImage::new(5, 3), ColorSpace::Linear).unwrap();
+    let mut inputs = vec![];
+    for (name, bytes) in [
+        ("ktx2_only.glb", ktx2_only()),
+        ("ktx2_with_png_fallback.glb", ktx2_with_png_fallback()),
+        ("linear_mipmapped.ktx2", linear_mipmapped.to_bytes()),
+    ] {
+        let path = fixtures.join(name);
+        std::fs::write(&path, bytes).unwrap();
+        inputs.push(path);
+    }
+
+    // Set GLTF_KTXER_WEB_LOADER_INPUTS to a directory of `.glb` and `.ktx2` outputs to check those too
+    if let Some(extra) = std::env::var_os("GLTF_KTXER_WEB_LOADER_INPUTS") {
+        for entry in std::fs::read_dir(extra).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("glb") || ext.eq_ignore_ascii_case("ktx2")) {
+                inputs.push(path);
+            }
+        }
+    }
+
+    let output = Command::new("node").arg("check.mjs").args(&inputs).current_dir(&dir).output().expect("couldn't run node");
+    print!("{}", String::from_utf8_lossy(&output.stdout));
+    assert!(output.status.success(), "a loader rejected an output\n{}", String::from_utf8_lossy(&output.stderr));
+}
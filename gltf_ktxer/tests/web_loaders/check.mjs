@@ -0,0 +1,187 @@
+// Loads each .glb or .ktx2 file given on the command line with three.js and Babylon.js, the way a web viewer would,
+// and fails if either loader rejects it or warns about it. Run by tests/web_loaders.rs, which installs package.json first.
+//
+// Node has no WebGL or Web Workers, so:
+// - three.js decodes uncompressed and Zstandard-supercompressed KTX2 textures itself, but only the containers of
+//   Basis Universal textures are checked, as the transcoder does before transcoding.
+// - Babylon.js loads glTF documents into a NullEngine, which skips texture data, so KTX2 files are parsed separately
+//   with the reader its KTX2 decoder uses.
+
+import { readFile } from 'node:fs/promises';
+import path from 'node:path';
+
+import { CompressedTexture } from 'three';
+import { GLTFLoader } from 'three/examples/jsm/loaders/GLTFLoader.js';
+import { KTX2Loader } from 'three/examples/jsm/loaders/KTX2Loader.js';
+import {
+    read,
+    KHR_DF_MODEL_ETC1S,
+    KHR_DF_MODEL_UASTC,
+    KHR_SUPERCOMPRESSION_BASISLZ,
+    KHR_SUPERCOMPRESSION_NONE,
+    KHR_SUPERCOMPRESSION_ZSTD,
+    VK_FORMAT_UNDEFINED,
+} from 'three/examples/jsm/libs/ktx-parse.module.js';
+import { NullEngine } from '@babylonjs/core/Engines/nullEngine.js';
+import { SceneLoader } from '@babylonjs/core/Loading/sceneLoader.js';
+import { Scene } from '@babylonjs/core/scene.js';
+import '@babylonjs/loaders/glTF/index.js';
+import { KTX2FileReader } from '@babylonjs/ktx2decoder';
+
+const GLB_MAGIC = 0x46546c67;
+const GLB_CHUNK_JSON = 0x4e4f534a;
+const GLB_CHUNK_BIN = 0x004e4942;
+
+class CheckingKTX2Loader extends KTX2Loader {
+    constructor() {
+        super();
+        // What detectSupport() would find on a GPU supporting no compressed formats, so everything transcodes to RGBA8
+        this.workerConfig = {
+            astcSupported: false,
+            astcHDRSupported: false,
+            etc1Supported: false,
+            etc2Supported: false,
+            dxtSupported: false,
+            bptcSupported: false,
+            pvrtcSupported: false,
+        };
+    }
+
+    async _createTexture(buffer, config) {
+        const container = read(new Uint8Array(buffer));
+        if (container.vkFormat !== VK_FORMAT_UNDEFINED) {
+            return super._createTexture(buffer, config);
+        }
+        const colorModel = container.dataFormatDescriptor[0].colorModel;
+        const scheme = container.supercompressionScheme;
+        const transcodable =
+            (colorModel === KHR_DF_MODEL_ETC1S && scheme === KHR_SUPERCOMPRESSION_BASISLZ) ||
+            (colorModel === KHR_DF_MODEL_UASTC && (scheme === KHR_SUPERCOMPRESSION_NONE || scheme === KHR_SUPERCOMPRESSION_ZSTD));
+        if (!transcodable) {
+            throw new Error(`can't transcode color model ${colorModel} with supercompression scheme ${scheme}`);
+        }
+        return new CompressedTexture([], container.pixelWidth, container.pixelHeight);
+    }
+
+    parseAsync(buffer) {
+        return new Promise((resolve, reject) => this.parse(buffer, resolve, reject));
+    }
+}
+
+/** The JSON and binary chunks of a GLB file. */
+function splitGlb(bytes) {
+    const view = new DataView(bytes.buffer, bytes.byteOffset, bytes.byteLength);
+    if (view.getUint32(0, true) !== GLB_MAGIC) {
+        throw new Error('not a GLB file');
+    }
+    let json;
+    let bin = new Uint8Array();
+    for (let offset = 12; offset < bytes.byteLength; ) {
+        const length = view.getUint32(offset, true);
+        const type = view.getUint32(offset + 4, true);
+        const chunk = bytes.subarray(offset + 8, offset + 8 + length);
+        if (type === GLB_CHUNK_JSON) {
+            json = JSON.parse(new TextDecoder().decode(chunk));
+        } else if (type === GLB_CHUNK_BIN) {
+            bin = chunk;
+        }
+        offset += 8 + length;
+    }
+    return { json, bin };
+}
+
+/** The KTX2 images stored in the binary chunk, by image index. */
+function glbKtx2Images({ json, bin }) {
+    return (json.images ?? []).flatMap((image, idx) => {
+        if (image.mimeType !== 'image/ktx2' || image.bufferView === undefined) {
+            return [];
+        }
+        const view = json.bufferViews[image.bufferView];
+        const start = view.byteOffset ?? 0;
+        return [[idx, bin.slice(start, start + view.byteLength)]];
+    });
+}
+
+function arrayBufferOf(bytes) {
+    return bytes.buffer.slice(bytes.byteOffset, bytes.byteOffset + bytes.byteLength);
+}
+
+async function loadGlbWithThree(bytes) {
+    const loader = new GLTFLoader().setKTX2Loader(new CheckingKTX2Loader());
+    const gltf = await loader.parseAsync(arrayBufferOf(bytes), '');
+    const textures = await gltf.parser.getDependencies('texture');
+    textures.forEach((texture, idx) => {
+        if (!texture) {
+            throw new Error(`texture ${idx} didn't load`);
+        }
+    });
+}
+
+async function loadGlbWithBabylon(bytes) {
+    const engine = new NullEngine();
+    try {
+        const scene = new Scene(engine);
+        SceneLoader.OnPluginActivatedObservable.addOnce((loader) => {
+            loader.loadAllMaterials = true;
+        });
+        const data = `data:;base64,${Buffer.from(bytes).toString('base64')}`;
+        await SceneLoader.LoadAssetContainerAsync('', data, scene, null, '.glb');
+    } finally {
+        engine.dispose();
+    }
+}
+
+function parseKtx2WithBabylon(bytes) {
+    const reader = new KTX2FileReader(bytes);
+    if (!reader.isValid()) {
+        throw new Error('not a KTX2 file');
+    }
+    reader.parse();
+}
+
+/** Run `load`, failing if it throws or logs any warnings. */
+async function check(failures, file, loaderName, load) {
+    const warnings = [];
+    const warn = console.warn;
+    console.warn = (...args) => warnings.push(args.join(' '));
+    try {
+        await load();
+        if (warnings.length > 0) {
+            failures.push(`${file} (${loaderName}): warned: ${warnings.join('; ')}`);
+        }
+    } catch (e) {
+        failures.push(`${file} (${loaderName}): ${e?.message ?? e}`);
+    } finally {
+        console.warn = warn;
+    }
+}
+
+const failures = [];
+for (const file of process.argv.slice(2)) {
+    const bytes = new Uint8Array(await readFile(file));
+    const before = failures.length;
+    switch (path.extname(file).toLowerCase()) {
+        case '.glb': {
+            await check(failures, file, 'three.js', () => loadGlbWithThree(bytes));
+            await check(failures, file, 'Babylon.js', () => loadGlbWithBabylon(bytes));
+            for (const [idx, ktx2] of glbKtx2Images(splitGlb(bytes))) {
+                await check(failures, `${file} image ${idx}`, 'Babylon.js', () => parseKtx2WithBabylon(ktx2));
+            }
+            break;
+        }
+        case '.ktx2':
+            await check(failures, file, 'three.js', () => new CheckingKTX2Loader().parseAsync(arrayBufferOf(bytes)));
+            await check(failures, file, 'Babylon.js', () => parseKtx2WithBabylon(bytes));
+            break;
+        default:
+            failures.push(`${file}: expected a .glb or .ktx2 file`);
+    }
+    if (failures.length === before) {
+        console.log(`ok ${file}`);
+    }
+}
+
+for (const failure of failures) {
+    console.log(`FAIL ${failure}`);
+}
+process.exit(failures.length > 0 ? 1 : 0);